@@ -34,6 +34,13 @@ pub struct Model {
     pub reason: Option<String>,
     pub created_at: chrono::NaiveDateTime,
     pub ended_at: Option<chrono::NaiveDateTime>,
+    pub opening_moves: i16,
+    pub bot_difficulty: Option<String>,
+    pub bot_uuid: Option<Uuid>,
+    pub x_time_left: Option<i64>,
+    pub o_time_left: Option<i64>,
+    pub board_size: i16,
+    pub win_length: i16,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
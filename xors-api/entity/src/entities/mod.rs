@@ -14,6 +14,26 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+pub mod achievement;
+pub mod audit_event;
+pub mod block;
+pub mod chat_message;
+pub mod data_export;
+pub mod friendship;
 pub mod game;
+pub mod game_move;
+pub mod invite;
+pub mod notification;
 pub mod prelude;
+pub mod rating_change;
+pub mod recovery_code;
+pub mod revoked_token;
+pub mod season;
+pub mod season_standing;
+pub mod tournament;
+pub mod tournament_match;
+pub mod tournament_match_game;
+pub mod tournament_player;
 pub mod user;
+pub mod webhook;
+pub mod webhook_delivery;
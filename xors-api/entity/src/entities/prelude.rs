@@ -23,6 +23,106 @@ pub use super::user::{
     ActiveModel as UserActiveModel, Column as UserColumn, Entity as UserEntity, Model as UserModel,
 };
 
+pub use super::achievement::{
+    ActiveModel as AchievementActiveModel, Column as AchievementColumn,
+    Entity as AchievementEntity, Model as AchievementModel,
+};
+
+pub use super::block::{
+    ActiveModel as BlockActiveModel, Column as BlockColumn, Entity as BlockEntity,
+    Model as BlockModel,
+};
+
+pub use super::friendship::{
+    ActiveModel as FriendshipActiveModel, Column as FriendshipColumn, Entity as FriendshipEntity,
+    Model as FriendshipModel,
+};
+
+pub use super::rating_change::{
+    ActiveModel as RatingChangeActiveModel, Column as RatingChangeColumn,
+    Entity as RatingChangeEntity, Model as RatingChangeModel,
+};
+
+pub use super::revoked_token::{
+    ActiveModel as RevokedTokenActiveModel, Column as RevokedTokenColumn,
+    Entity as RevokedTokenEntity, Model as RevokedTokenModel,
+};
+
 pub use super::game::{
     ActiveModel as GameActiveModel, Column as GameColumn, Entity as GameEntity, Model as GameModel,
 };
+
+pub use super::game_move::{
+    ActiveModel as GameMoveActiveModel, Column as GameMoveColumn, Entity as GameMoveEntity,
+    Model as GameMoveModel,
+};
+
+pub use super::invite::{
+    ActiveModel as InviteActiveModel, Column as InviteColumn, Entity as InviteEntity,
+    Model as InviteModel,
+};
+
+pub use super::notification::{
+    ActiveModel as NotificationActiveModel, Column as NotificationColumn,
+    Entity as NotificationEntity, Model as NotificationModel,
+};
+
+pub use super::tournament::{
+    ActiveModel as TournamentActiveModel, Column as TournamentColumn, Entity as TournamentEntity,
+    Model as TournamentModel,
+};
+
+pub use super::tournament_player::{
+    ActiveModel as TournamentPlayerActiveModel, Column as TournamentPlayerColumn,
+    Entity as TournamentPlayerEntity, Model as TournamentPlayerModel,
+};
+
+pub use super::tournament_match::{
+    ActiveModel as TournamentMatchActiveModel, Column as TournamentMatchColumn,
+    Entity as TournamentMatchEntity, Model as TournamentMatchModel,
+};
+
+pub use super::tournament_match_game::{
+    ActiveModel as TournamentMatchGameActiveModel, Column as TournamentMatchGameColumn,
+    Entity as TournamentMatchGameEntity, Model as TournamentMatchGameModel,
+};
+
+pub use super::chat_message::{
+    ActiveModel as ChatMessageActiveModel, Column as ChatMessageColumn,
+    Entity as ChatMessageEntity, Model as ChatMessageModel,
+};
+
+pub use super::recovery_code::{
+    ActiveModel as RecoveryCodeActiveModel, Column as RecoveryCodeColumn,
+    Entity as RecoveryCodeEntity, Model as RecoveryCodeModel,
+};
+
+pub use super::audit_event::{
+    ActiveModel as AuditEventActiveModel, Column as AuditEventColumn, Entity as AuditEventEntity,
+    Model as AuditEventModel,
+};
+
+pub use super::webhook::{
+    ActiveModel as WebhookActiveModel, Column as WebhookColumn, Entity as WebhookEntity,
+    Model as WebhookModel,
+};
+
+pub use super::webhook_delivery::{
+    ActiveModel as WebhookDeliveryActiveModel, Column as WebhookDeliveryColumn,
+    Entity as WebhookDeliveryEntity, Model as WebhookDeliveryModel,
+};
+
+pub use super::data_export::{
+    ActiveModel as DataExportActiveModel, Column as DataExportColumn, Entity as DataExportEntity,
+    Model as DataExportModel,
+};
+
+pub use super::season::{
+    ActiveModel as SeasonActiveModel, Column as SeasonColumn, Entity as SeasonEntity,
+    Model as SeasonModel,
+};
+
+pub use super::season_standing::{
+    ActiveModel as SeasonStandingActiveModel, Column as SeasonStandingColumn,
+    Entity as SeasonStandingEntity, Model as SeasonStandingModel,
+};
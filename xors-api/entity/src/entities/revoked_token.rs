@@ -0,0 +1,41 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "revoked_token")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub jti: Uuid,
+    pub expires_at: chrono::NaiveDateTime,
+    pub revoked_at: chrono::NaiveDateTime,
+    pub consumed: bool,
+    /// The `jti` of the JWT token that issued when the refresh token consumed.
+    pub successor_jwt_jti: Option<Uuid>,
+    /// The `jti` of the refresh token that issued when the refresh token consumed.
+    pub successor_refresh_jti: Option<Uuid>,
+    /// When the successor tokens issued, they are re-issued with it when the consumed refresh token is reused.
+    pub successor_issued_at: Option<chrono::NaiveDateTime>,
+    /// Until when the consumed refresh token can be presented again once, to get its successor tokens.
+    pub reusable_until: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -25,12 +25,27 @@ pub struct Model {
     pub first_name: String,
     pub last_name: Option<String>,
     pub username: String,
+    pub normalized_username: String,
     pub profile_image_path: String,
     pub wins: i64,
     pub losts: i64,
     pub draw: i64,
+    pub current_streak: i64,
+    pub longest_win_streak: i64,
+    pub longest_loss_streak: i64,
+    pub rating: i64,
+    /// The total duration of the user's ended games, in seconds.
+    pub games_duration: i64,
     pub password_hash: String,
     pub created_at: chrono::NaiveDateTime,
+    /// The version of the user's tokens, the tokens of the older versions are revoked.
+    pub token_version: i32,
+    pub role: String,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    /// The time step of the last accepted TOTP code, the codes of it and the older steps are rejected.
+    pub totp_last_step: Option<i64>,
+    pub banned_at: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
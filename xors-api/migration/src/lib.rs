@@ -19,6 +19,46 @@ pub use sea_orm_migration::prelude::*;
 mod m20240108_114814_user_table;
 mod m20240119_135153_game;
 mod m20240201_110331_add_start_chat_columns_to_game_table;
+mod m20261014_093012_add_username_search_index;
+mod m20261014_151240_add_streak_columns_to_user_table;
+mod m20261014_183507_block_table;
+mod m20261014_201045_achievement_table;
+mod m20261014_214420_add_game_ended_at_index;
+mod m20261014_231512_add_opening_moves_column_to_game_table;
+mod m20261014_235210_revoked_token_table;
+mod m20261014_235845_add_consumed_column_to_revoked_token_table;
+mod m20261014_235912_add_tokens_revoked_at_column_to_user_table;
+mod m20261014_235958_add_role_column_to_user_table;
+mod m20261014_235959_add_normalized_username_column_to_user_table;
+mod m20261015_000000_add_rating_column_to_user_table;
+mod m20261015_000100_rating_change_table;
+mod m20261015_000200_game_move_table;
+mod m20261015_000300_add_bot_difficulty_column_to_game_table;
+mod m20261015_000400_invite_table;
+mod m20261015_000500_tournament_table;
+mod m20261015_000600_tournament_player_table;
+mod m20261015_000700_tournament_match_table;
+mod m20261015_000800_chat_message_table;
+mod m20261015_000900_add_totp_columns_to_user_table;
+mod m20261015_001000_recovery_code_table;
+mod m20261015_001100_add_banned_at_column_to_user_table;
+mod m20261015_001200_audit_event_table;
+mod m20261015_001300_add_games_duration_column_to_user_table;
+mod m20261015_001400_add_time_left_columns_to_game_table;
+mod m20261015_001500_friendship_table;
+mod m20261015_001600_notification_table;
+mod m20261015_001700_add_variant_columns_to_game_table;
+mod m20261015_001800_add_variant_columns_to_invite_table;
+mod m20261015_001900_webhook_table;
+mod m20261015_002000_webhook_delivery_table;
+mod m20261015_002100_data_export_table;
+mod m20261015_002200_season_table;
+mod m20261015_002300_season_standing_table;
+mod m20261015_002400_add_token_version_column_to_user_table;
+mod m20261015_002500_add_successor_columns_to_revoked_token_table;
+mod m20261015_002600_add_totp_last_step_column_to_user_table;
+mod m20261015_002700_tournament_match_game_table;
+mod m20261015_002800_add_bot_uuid_column_to_game_table;
 
 pub struct Migrator;
 
@@ -29,6 +69,46 @@ impl MigratorTrait for Migrator {
             Box::new(m20240108_114814_user_table::Migration),
             Box::new(m20240119_135153_game::Migration),
             Box::new(m20240201_110331_add_start_chat_columns_to_game_table::Migration),
+            Box::new(m20261014_093012_add_username_search_index::Migration),
+            Box::new(m20261014_151240_add_streak_columns_to_user_table::Migration),
+            Box::new(m20261014_183507_block_table::Migration),
+            Box::new(m20261014_201045_achievement_table::Migration),
+            Box::new(m20261014_214420_add_game_ended_at_index::Migration),
+            Box::new(m20261014_231512_add_opening_moves_column_to_game_table::Migration),
+            Box::new(m20261014_235210_revoked_token_table::Migration),
+            Box::new(m20261014_235845_add_consumed_column_to_revoked_token_table::Migration),
+            Box::new(m20261014_235912_add_tokens_revoked_at_column_to_user_table::Migration),
+            Box::new(m20261014_235958_add_role_column_to_user_table::Migration),
+            Box::new(m20261014_235959_add_normalized_username_column_to_user_table::Migration),
+            Box::new(m20261015_000000_add_rating_column_to_user_table::Migration),
+            Box::new(m20261015_000100_rating_change_table::Migration),
+            Box::new(m20261015_000200_game_move_table::Migration),
+            Box::new(m20261015_000300_add_bot_difficulty_column_to_game_table::Migration),
+            Box::new(m20261015_000400_invite_table::Migration),
+            Box::new(m20261015_000500_tournament_table::Migration),
+            Box::new(m20261015_000600_tournament_player_table::Migration),
+            Box::new(m20261015_000700_tournament_match_table::Migration),
+            Box::new(m20261015_000800_chat_message_table::Migration),
+            Box::new(m20261015_000900_add_totp_columns_to_user_table::Migration),
+            Box::new(m20261015_001000_recovery_code_table::Migration),
+            Box::new(m20261015_001100_add_banned_at_column_to_user_table::Migration),
+            Box::new(m20261015_001200_audit_event_table::Migration),
+            Box::new(m20261015_001300_add_games_duration_column_to_user_table::Migration),
+            Box::new(m20261015_001400_add_time_left_columns_to_game_table::Migration),
+            Box::new(m20261015_001500_friendship_table::Migration),
+            Box::new(m20261015_001600_notification_table::Migration),
+            Box::new(m20261015_001700_add_variant_columns_to_game_table::Migration),
+            Box::new(m20261015_001800_add_variant_columns_to_invite_table::Migration),
+            Box::new(m20261015_001900_webhook_table::Migration),
+            Box::new(m20261015_002000_webhook_delivery_table::Migration),
+            Box::new(m20261015_002100_data_export_table::Migration),
+            Box::new(m20261015_002200_season_table::Migration),
+            Box::new(m20261015_002300_season_standing_table::Migration),
+            Box::new(m20261015_002400_add_token_version_column_to_user_table::Migration),
+            Box::new(m20261015_002500_add_successor_columns_to_revoked_token_table::Migration),
+            Box::new(m20261015_002600_add_totp_last_step_column_to_user_table::Migration),
+            Box::new(m20261015_002700_tournament_match_game_table::Migration),
+            Box::new(m20261015_002800_add_bot_uuid_column_to_game_table::Migration),
         ]
     }
 }
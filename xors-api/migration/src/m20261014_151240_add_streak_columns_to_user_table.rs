@@ -0,0 +1,103 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use sea_orm_migration::prelude::*;
+
+use crate::m20240108_114814_user_table::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+#[allow(clippy::enum_variant_names)]
+enum UserStreak {
+    CurrentStreak,
+    LongestWinStreak,
+    LongestLossStreak,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(
+                        ColumnDef::new(UserStreak::CurrentStreak)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .add_column(
+                        ColumnDef::new(UserStreak::LongestWinStreak)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .add_column(
+                        ColumnDef::new(UserStreak::LongestLossStreak)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Compute the streaks of the already ended games, the consecutive results are grouped
+        // by the difference between the user games order and the user results order.
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"WITH results AS (
+                    SELECT "user".uuid AS user_uuid, game.ended_at,
+                        CASE WHEN game.winner = "user".uuid THEN 'W' WHEN game.winner IS NULL THEN 'D' ELSE 'L' END AS result
+                    FROM "user" JOIN game ON (game.x_player = "user".uuid OR game.o_player = "user".uuid) AND game.ended_at IS NOT NULL
+                ), grouped_results AS (
+                    SELECT user_uuid, result, ended_at,
+                        ROW_NUMBER() OVER (PARTITION BY user_uuid ORDER BY ended_at)
+                        - ROW_NUMBER() OVER (PARTITION BY user_uuid, result ORDER BY ended_at) AS streak_group
+                    FROM results
+                ), streaks AS (
+                    SELECT user_uuid, result, COUNT(*) AS length, MAX(ended_at) AS last_ended_at
+                    FROM grouped_results GROUP BY user_uuid, result, streak_group
+                )
+                UPDATE "user" SET
+                    longest_win_streak = COALESCE((SELECT MAX(length) FROM streaks WHERE user_uuid = "user".uuid AND result = 'W'), 0),
+                    longest_loss_streak = COALESCE((SELECT MAX(length) FROM streaks WHERE user_uuid = "user".uuid AND result = 'L'), 0),
+                    current_streak = COALESCE((
+                        SELECT CASE result WHEN 'W' THEN length WHEN 'L' THEN -length ELSE 0 END
+                        FROM streaks WHERE user_uuid = "user".uuid ORDER BY last_ended_at DESC LIMIT 1
+                    ), 0);"#,
+            )
+            .await
+            .map(|_| ())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(UserStreak::CurrentStreak)
+                    .drop_column(UserStreak::LongestWinStreak)
+                    .drop_column(UserStreak::LongestLossStreak)
+                    .to_owned(),
+            )
+            .await
+    }
+}
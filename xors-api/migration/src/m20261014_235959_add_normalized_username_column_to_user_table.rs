@@ -0,0 +1,92 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+use sea_orm_migration::prelude::*;
+
+use crate::m20240108_114814_user_table::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+enum UserNormalizedUsername {
+    NormalizedUsername,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(ColumnDef::new(UserNormalizedUsername::NormalizedUsername).string())
+                    .to_owned(),
+            )
+            .await?;
+        // The existing usernames that only differ in case keep working, the newer ones get a suffix that can't be
+        // a valid username, so they don't block the unique index. They still signin with their exact username
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE \"user\" SET normalized_username = LOWER(username);
+                UPDATE \"user\" AS u SET normalized_username = LOWER(u.username) || '#' || u.id
+                WHERE EXISTS (SELECT 1 FROM \"user\" AS o WHERE LOWER(o.username) = LOWER(u.username) AND o.id < u.id);",
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .modify_column(
+                        ColumnDef::new(UserNormalizedUsername::NormalizedUsername)
+                            .string()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_user_normalized_username")
+                    .table(User::Table)
+                    .col(UserNormalizedUsername::NormalizedUsername)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_user_normalized_username")
+                    .table(User::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(UserNormalizedUsername::NormalizedUsername)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,69 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(GameMove::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(GameMove::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(GameMove::GameUuid).uuid().not_null())
+                    .col(ColumnDef::new(GameMove::Sequence).integer().not_null())
+                    .col(ColumnDef::new(GameMove::Round).small_integer().not_null())
+                    .col(ColumnDef::new(GameMove::Place).small_integer().not_null())
+                    .col(ColumnDef::new(GameMove::PlayedAt).date_time().not_null())
+                    .index(
+                        Index::create()
+                            .name("idx_game_move_game_uuid_sequence")
+                            .col(GameMove::GameUuid)
+                            .col(GameMove::Sequence)
+                            .unique(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(GameMove::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum GameMove {
+    Table,
+    Id,
+    GameUuid,
+    Sequence,
+    Round,
+    Place,
+    PlayedAt,
+}
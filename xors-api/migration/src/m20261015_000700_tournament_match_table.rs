@@ -0,0 +1,87 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TournamentMatch::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TournamentMatch::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TournamentMatch::TournamentUuid)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TournamentMatch::Round)
+                            .small_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TournamentMatch::Slot)
+                            .small_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TournamentMatch::XPlayer).uuid().null())
+                    .col(ColumnDef::new(TournamentMatch::OPlayer).uuid().null())
+                    .col(ColumnDef::new(TournamentMatch::GameUuid).uuid().null())
+                    .col(ColumnDef::new(TournamentMatch::Winner).uuid().null())
+                    .index(
+                        Index::create()
+                            .name("idx_tournament_match_tournament_uuid_round_slot")
+                            .col(TournamentMatch::TournamentUuid)
+                            .col(TournamentMatch::Round)
+                            .col(TournamentMatch::Slot)
+                            .unique(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TournamentMatch::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TournamentMatch {
+    Table,
+    Id,
+    TournamentUuid,
+    Round,
+    Slot,
+    XPlayer,
+    OPlayer,
+    GameUuid,
+    Winner,
+}
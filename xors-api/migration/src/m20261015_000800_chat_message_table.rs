@@ -0,0 +1,78 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ChatMessage::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ChatMessage::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ChatMessage::GameUuid).uuid().not_null())
+                    .col(ColumnDef::new(ChatMessage::Sender).uuid().not_null())
+                    .col(
+                        ColumnDef::new(ChatMessage::EncryptedMessage)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ChatMessage::Signature).text().not_null())
+                    .col(ColumnDef::new(ChatMessage::SentAt).date_time().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        // The index is used by the game replay, the game chat messages
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_chat_message_game_uuid")
+                    .table(ChatMessage::Table)
+                    .col(ChatMessage::GameUuid)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ChatMessage::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum ChatMessage {
+    Table,
+    Id,
+    GameUuid,
+    Sender,
+    EncryptedMessage,
+    Signature,
+    SentAt,
+}
@@ -0,0 +1,81 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(DataExport::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(DataExport::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(DataExport::Uuid)
+                            .uuid()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(DataExport::UserUuid).uuid().not_null())
+                    .col(ColumnDef::new(DataExport::Status).string().not_null())
+                    .col(ColumnDef::new(DataExport::Data).text().null())
+                    .col(ColumnDef::new(DataExport::CreatedAt).date_time().not_null())
+                    .col(ColumnDef::new(DataExport::CompletedAt).date_time().null())
+                    .to_owned(),
+            )
+            .await?;
+        // The index is used by the user latest export lookup
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_data_export_user_uuid")
+                    .table(DataExport::Table)
+                    .col(DataExport::UserUuid)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DataExport::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum DataExport {
+    Table,
+    Id,
+    Uuid,
+    UserUuid,
+    Status,
+    Data,
+    CreatedAt,
+    CompletedAt,
+}
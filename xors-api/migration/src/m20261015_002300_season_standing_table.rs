@@ -0,0 +1,112 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SeasonStanding::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(SeasonStanding::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(SeasonStanding::Season).integer().not_null())
+                    .col(ColumnDef::new(SeasonStanding::UserUuid).uuid().not_null())
+                    .col(
+                        ColumnDef::new(SeasonStanding::Rank)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SeasonStanding::Rating)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SeasonStanding::Wins)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SeasonStanding::Losts)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SeasonStanding::Draw)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(SeasonStanding::Reward).string().null())
+                    .to_owned(),
+            )
+            .await?;
+        // The index is used by the archived leaderboards pages
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_season_standing_season_rank")
+                    .table(SeasonStanding::Table)
+                    .col(SeasonStanding::Season)
+                    .col(SeasonStanding::Rank)
+                    .to_owned(),
+            )
+            .await?;
+        // The index is used by the user season rewards lookup
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_season_standing_user_uuid")
+                    .table(SeasonStanding::Table)
+                    .col(SeasonStanding::UserUuid)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SeasonStanding::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum SeasonStanding {
+    Table,
+    Id,
+    Season,
+    UserUuid,
+    Rank,
+    Rating,
+    Wins,
+    Losts,
+    Draw,
+    Reward,
+}
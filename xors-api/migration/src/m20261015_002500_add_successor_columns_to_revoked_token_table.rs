@@ -0,0 +1,77 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use sea_orm_migration::prelude::*;
+
+use crate::m20261014_235210_revoked_token_table::RevokedToken;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+enum RevokedTokenSuccessor {
+    SuccessorJwtJti,
+    SuccessorRefreshJti,
+    SuccessorIssuedAt,
+    ReusableUntil,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RevokedToken::Table)
+                    .add_column(
+                        ColumnDef::new(RevokedTokenSuccessor::SuccessorJwtJti)
+                            .uuid()
+                            .null(),
+                    )
+                    .add_column(
+                        ColumnDef::new(RevokedTokenSuccessor::SuccessorRefreshJti)
+                            .uuid()
+                            .null(),
+                    )
+                    .add_column(
+                        ColumnDef::new(RevokedTokenSuccessor::SuccessorIssuedAt)
+                            .date_time()
+                            .null(),
+                    )
+                    .add_column(
+                        ColumnDef::new(RevokedTokenSuccessor::ReusableUntil)
+                            .date_time()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RevokedToken::Table)
+                    .drop_column(RevokedTokenSuccessor::SuccessorJwtJti)
+                    .drop_column(RevokedTokenSuccessor::SuccessorRefreshJti)
+                    .drop_column(RevokedTokenSuccessor::SuccessorIssuedAt)
+                    .drop_column(RevokedTokenSuccessor::ReusableUntil)
+                    .to_owned(),
+            )
+            .await
+    }
+}
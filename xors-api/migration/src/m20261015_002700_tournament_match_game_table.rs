@@ -0,0 +1,91 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use sea_orm_migration::prelude::*;
+
+use crate::m20261015_000700_tournament_match_table::TournamentMatch;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TournamentMatchGame::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TournamentMatchGame::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TournamentMatchGame::MatchId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TournamentMatchGame::GameUuid)
+                            .uuid()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_tournament_match_game_match_id")
+                            .col(TournamentMatchGame::MatchId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Link the games of the already played matches
+        manager
+            .exec_stmt(
+                Query::insert()
+                    .into_table(TournamentMatchGame::Table)
+                    .columns([TournamentMatchGame::MatchId, TournamentMatchGame::GameUuid])
+                    .select_from(
+                        Query::select()
+                            .columns([TournamentMatch::Id, TournamentMatch::GameUuid])
+                            .from(TournamentMatch::Table)
+                            .and_where(Expr::col(TournamentMatch::GameUuid).is_not_null())
+                            .to_owned(),
+                    )
+                    .map_err(|err| DbErr::Migration(err.to_string()))?
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TournamentMatchGame::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TournamentMatchGame {
+    Table,
+    Id,
+    MatchId,
+    GameUuid,
+}
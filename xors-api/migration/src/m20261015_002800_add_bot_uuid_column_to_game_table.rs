@@ -0,0 +1,64 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use sea_orm_migration::prelude::*;
+
+use crate::m20240119_135153_game::Game;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+enum GameBotUuid {
+    BotUuid,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .add_column(ColumnDef::new(GameBotUuid::BotUuid).uuid().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // The bot of the already created games against the AI opponent is the player that is not a user
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"UPDATE game SET bot_uuid = CASE
+                    WHEN EXISTS (SELECT 1 FROM "user" WHERE "user".uuid = game.x_player) THEN game.o_player
+                    ELSE game.x_player
+                END WHERE bot_difficulty IS NOT NULL;"#,
+            )
+            .await
+            .map(|_| ())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .drop_column(GameBotUuid::BotUuid)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,425 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{str::FromStr, sync::Arc};
+
+use futures_util::StreamExt;
+use salvo::{
+    oapi::extract::{JsonBody, PathParam, QueryParam},
+    prelude::*,
+};
+use salvo_captcha::CaptchaStorage;
+use sea_orm::{DatabaseConnection, IntoActiveModel};
+use uuid::Uuid;
+
+use crate::{
+    db_utils,
+    errors::{ApiError, ApiResult},
+    schemas::*,
+};
+
+use super::{
+    audit::AuditEvent,
+    captcha_storage::CaptchaAnswerStorage,
+    exts::*,
+    jwt::{JwtKeys, TokenLifetimes},
+    notification, xo,
+};
+
+/// Ban a user.
+///
+/// This endpoint will ban the user, the banned user can't signin and all of its tokens are rejected, and its XO
+/// websocket connections are closed. Only the moderators can ban the users, and they can't ban the users that have
+/// the same role or higher. Banning an already banned user does nothing.
+#[endpoint(
+    operation_id = "ban_user",
+    tags("Admin"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The uuid of the user to ban"),
+    ),
+    responses(
+        (status_code = 200, description = "The user has been banned", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is not a moderator, or the banned user has the same role or higher", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn ban_user(
+    req: &mut Request,
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    // Note: The role is checked by the `require_role` middleware.
+    let moderator = depot.user(conn.as_ref()).await?;
+    let user_uuid = uuid.into_inner();
+
+    let user = db_utils::get_user(conn.as_ref(), user_uuid).await?;
+    if UserRole::from_str(user.role.as_ref()).unwrap_or_default() >= depot.jwt_claims().role {
+        return Err(ApiError::Forbidden);
+    }
+    db_utils::set_user_ban(conn.as_ref(), user_uuid, true).await?;
+    xo::disconnect_banned_player(&user_uuid);
+    AuditEvent::new(AuditAction::UserBan, req, depot)
+        .actor(moderator.uuid)
+        .target(user_uuid)
+        .record(conn)
+        .await;
+    Ok(Json(MessageSchema::new(
+        "The user has been banned".to_owned(),
+    )))
+}
+
+/// Unban a user.
+///
+/// This endpoint will unban the user, its tokens that issued before the ban are accepted again. Unbanning a not
+/// banned user does nothing.
+#[endpoint(
+    operation_id = "unban_user",
+    tags("Admin"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The uuid of the user to unban"),
+    ),
+    responses(
+        (status_code = 200, description = "The user has been unbanned", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is not a moderator", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn unban_user(
+    req: &mut Request,
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    // Note: The role is checked by the `require_role` middleware.
+    let moderator = depot.user(conn.as_ref()).await?;
+    let user_uuid = uuid.into_inner();
+
+    db_utils::set_user_ban(conn.as_ref(), user_uuid, false).await?;
+    AuditEvent::new(AuditAction::UserUnban, req, depot)
+        .actor(moderator.uuid)
+        .target(user_uuid)
+        .record(conn)
+        .await;
+    Ok(Json(MessageSchema::new(
+        "The user has been unbanned".to_owned(),
+    )))
+}
+
+/// Warn a user.
+///
+/// This endpoint will send the warning message to the user as an `admin_warning` notification. Only the moderators
+/// can warn the users, and they can't warn the users that have the same role or higher.
+#[endpoint(
+    operation_id = "warn_user",
+    tags("Admin"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The uuid of the user to warn"),
+    ),
+    request_body(
+        content = WarningSchema,
+        description = "The warning message",
+        example = json!(WarningSchema::new("Please be respectful in the chat".to_owned())),
+        content_type = "application/json",
+    ),
+    responses(
+        (status_code = 200, description = "The user has been warned", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The warning message is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is not a moderator, or the warned user has the same role or higher", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn warn_user(
+    req: &mut Request,
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+    warning: JsonBody<WarningSchema>,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    // Note: The role is checked by the `require_role` middleware.
+    let moderator = depot.user(conn.as_ref()).await?;
+    let user_uuid = uuid.into_inner();
+    let message = warning.into_inner().message.trim().to_owned();
+    if !(1..=500).contains(&message.chars().count()) {
+        return Err(ApiError::InvalidWarningMessage);
+    }
+
+    let user = db_utils::get_user(conn.as_ref(), user_uuid).await?;
+    if UserRole::from_str(user.role.as_ref()).unwrap_or_default() >= depot.jwt_claims().role {
+        return Err(ApiError::Forbidden);
+    }
+    notification::notify(conn, user_uuid, NotificationData::AdminWarning { message }).await;
+    AuditEvent::new(AuditAction::UserWarning, req, depot)
+        .actor(moderator.uuid)
+        .target(user_uuid)
+        .record(conn)
+        .await;
+    Ok(Json(MessageSchema::new(
+        "The user has been warned".to_owned(),
+    )))
+}
+
+/// Force end a game.
+///
+/// This endpoint will end the in-progress game without a winner, the players and the spectators receive the
+/// `game_over` event with the `force_ended` reason. The game counts as a draw in the players results.
+#[endpoint(
+    operation_id = "force_end_game",
+    tags("Admin"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The uuid of the game to end"),
+    ),
+    responses(
+        (status_code = 200, description = "The game has been ended", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is not a moderator", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "The game is not found or already ended", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn end_game(
+    req: &mut Request,
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    // Note: The role is checked by the `require_role` middleware.
+    let moderator = depot.user(conn.as_ref()).await?;
+    let game_uuid = uuid.into_inner();
+
+    xo::force_end_game(conn.as_ref(), game_uuid).await?;
+    AuditEvent::new(AuditAction::GameEnd, req, depot)
+        .actor(moderator.uuid)
+        .target(game_uuid)
+        .record(conn)
+        .await;
+    Ok(Json(MessageSchema::new(
+        "The game has been ended".to_owned(),
+    )))
+}
+
+/// Get the recent signups.
+///
+/// This endpoint will return the recently signed up users, sorted by the join date (newest first). Each page has
+/// 20 users.
+#[endpoint(
+    operation_id = "get_recent_signups",
+    tags("Admin"),
+    parameters(
+        ("page" = Option<u64>, Query, description = "The page number, starting from 0, default is `0`"),
+    ),
+    responses(
+        (status_code = 200, description = "The recent signups", content_type = "application/json", body = Vec<UserSchema>),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is not a moderator", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn get_recent_signups(
+    depot: &mut Depot,
+    page: QueryParam<u64, false>,
+) -> ApiResult<Json<Vec<UserSchema>>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    // Note: The role is checked by the `require_role` middleware.
+    depot.user(conn.as_ref()).await?;
+
+    let users =
+        db_utils::get_recent_signups(conn.as_ref(), page.into_inner().unwrap_or_default()).await?;
+    Ok(Json(
+        futures_util::stream::iter(users)
+            .then(|user| UserSchema::from_active_model(conn, user.into_active_model()))
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<ApiResult<_>>()?,
+    ))
+}
+
+/// Delete the captchas.
+///
+/// This endpoint will delete all the stored captchas, the not answered captchas are rejected and the users need to
+/// get new ones. Only the admins can delete the captchas.
+#[endpoint(
+    operation_id = "delete_captchas",
+    tags("Admin"),
+    responses(
+        (status_code = 200, description = "The captchas have been deleted", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is not an admin", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn delete_captchas(
+    req: &mut Request,
+    depot: &mut Depot,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    // Note: The role is checked by the `require_role` middleware.
+    let admin = depot.user(conn.as_ref()).await?;
+
+    log::warn!("Deleting all the stored captchas");
+    depot
+        .obtain::<Arc<CaptchaAnswerStorage>>()
+        .unwrap()
+        .clear_expired(std::time::Duration::ZERO)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to delete the captchas: {err}");
+            ApiError::InternalServer
+        })?;
+    AuditEvent::new(AuditAction::CaptchasDeletion, req, depot)
+        .actor(admin.uuid)
+        .record(conn)
+        .await;
+    Ok(Json(MessageSchema::new(
+        "The captchas have been deleted".to_owned(),
+    )))
+}
+
+/// Get the audit log.
+///
+/// This endpoint will return the security-relevant events, sorted by their date (newest first). The events can be
+/// filtered by the action and the actor. Each page has 50 events. Only the admins can get the audit log.
+#[endpoint(
+    operation_id = "get_audit_log",
+    tags("Admin"),
+    parameters(
+        ("action" = Option<AuditAction>, Query, description = "Only the events of the action"),
+        ("actor" = Option<Uuid>, Query, description = "Only the events of the actor"),
+        ("page" = Option<u64>, Query, description = "The page number, starting from 0, default is `0`"),
+    ),
+    responses(
+        (status_code = 200, description = "The audit events", content_type = "application/json", body = Vec<AuditEventSchema>),
+        (status_code = 400, description = "Invalid audit action", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is not an admin", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn get_audit_log(
+    depot: &mut Depot,
+    action: QueryParam<String, false>,
+    actor: QueryParam<Uuid, false>,
+    page: QueryParam<u64, false>,
+) -> ApiResult<Json<Vec<AuditEventSchema>>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    // Note: The role is checked by the `require_role` middleware.
+    depot.user(conn.as_ref()).await?;
+    // Parsed here, the invalid actions are rejected instead of ignored
+    let action = action
+        .into_inner()
+        .map(|action| AuditAction::from_str(&action))
+        .transpose()?;
+
+    Ok(Json(
+        db_utils::get_audit_events(
+            conn.as_ref(),
+            action,
+            actor.into_inner(),
+            page.into_inner().unwrap_or_default(),
+        )
+        .await?
+        .into_iter()
+        .map(AuditEventSchema::from)
+        .collect(),
+    ))
+}
+
+/// Rotate the tokens key.
+///
+/// This endpoint will reload the private key file of the tokens key, if it has a new key, the new tokens are signed
+/// with it and the previous key is retired. The retired key still validates the issued tokens until the longest
+/// token lifetime passes, so the sessions are not invalidated. Only the admins can rotate the key, and only if the
+/// server loads the key from a file (the `RS256` and `EdDSA` algorithms).
+#[endpoint(
+    operation_id = "rotate_jwt_key",
+    tags("Admin"),
+    responses(
+        (status_code = 200, description = "The tokens key has been reloaded", content_type = "application/json", body = JwtKeyRotationSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is not an admin", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "The tokens key can't be rotated", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn rotate_jwt_key(
+    req: &mut Request,
+    depot: &mut Depot,
+) -> ApiResult<Json<JwtKeyRotationSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    // Note: The role is checked by the `require_role` middleware.
+    let admin = depot.user(conn.as_ref()).await?;
+    let jwt_keys = depot.obtain::<Arc<JwtKeys>>().unwrap();
+    let lifetimes = depot.obtain::<Arc<TokenLifetimes>>().unwrap();
+
+    let retired = jwt_keys
+        .reload(lifetimes.refresh.max(lifetimes.short_refresh))
+        .map_err(ApiError::JwtKeyRotationFailed)?;
+    let kid = jwt_keys.kid();
+    if let Some((retired_kid, _)) = &retired {
+        AuditEvent::new(AuditAction::JwtKeyRotation, req, depot)
+            .actor(admin.uuid)
+            .target(retired_kid.clone())
+            .record(conn)
+            .await;
+    }
+    Ok(Json(JwtKeyRotationSchema::new(
+        kid,
+        retired.is_some(),
+        retired.as_ref().map(|(retired_kid, _)| retired_kid.clone()),
+        retired.and_then(|(_, until)| {
+            chrono::DateTime::from_timestamp(until, 0).map(|until| until.naive_utc())
+        }),
+    )))
+}
@@ -0,0 +1,74 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use salvo::{Depot, Request};
+use uuid::Uuid;
+
+use crate::{db_utils, schemas::AuditAction};
+
+use super::risk;
+
+/// The maximum length of the stored user agent, the longer ones are truncated.
+const MAX_USER_AGENT_LENGTH: usize = 256;
+
+/// A security-relevant event, build it with [`AuditEvent::new`] and the setters then record it in the audit log
+/// with [`AuditEvent::record`].
+#[derive(Debug)]
+pub struct AuditEvent {
+    pub(crate) action: AuditAction,
+    pub(crate) actor: Option<Uuid>,
+    pub(crate) target: Option<String>,
+    pub(crate) ip: Option<String>,
+    pub(crate) user_agent: Option<String>,
+}
+
+impl AuditEvent {
+    /// Creates a new event of the request, the client IP and user agent are taken from it.
+    pub fn new(action: AuditAction, req: &Request, depot: &Depot) -> Self {
+        Self {
+            action,
+            actor: None,
+            target: None,
+            ip: risk::depot_client_ip(req, depot).map(|ip| ip.to_string()),
+            user_agent: req
+                .header::<String>("User-Agent")
+                .map(|user_agent| user_agent.chars().take(MAX_USER_AGENT_LENGTH).collect()),
+        }
+    }
+
+    /// Sets the user that did the action.
+    pub fn actor(mut self, actor: Uuid) -> Self {
+        self.actor = Some(actor);
+        self
+    }
+
+    /// Sets the target of the action, e.g. the banned user uuid.
+    pub fn target(mut self, target: impl ToString) -> Self {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    /// Records the event. The failures are logged, they don't fail the request.
+    pub async fn record(self, conn: &sea_orm::DatabaseConnection) {
+        let action = self.action;
+        if let Err(err) = db_utils::create_audit_event(conn, self).await {
+            log::error!(
+                "Failed to record the `{}` audit event: {err}",
+                action.to_string()
+            );
+        }
+    }
+}
@@ -0,0 +1,107 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use rand::prelude::SliceRandom;
+
+use crate::schemas::{Board, BotDifficulty, XoSymbol};
+
+/// The center place of the board.
+const CENTER: u8 = 4;
+
+/// The corners places of the board.
+const CORNERS: [u8; 4] = [0, 2, 6, 8];
+
+/// Returns the bot move on the board, for the board turn.
+///
+/// ### Panics
+/// - Panics if the board is full.
+pub fn choose_move(board: &Board, difficulty: BotDifficulty) -> u8 {
+    let places = match difficulty {
+        BotDifficulty::Random => board.empty_cells(),
+        BotDifficulty::Heuristic => heuristic_places(board),
+        BotDifficulty::Perfect => perfect_places(board),
+    };
+    *places
+        .choose(&mut rand::thread_rng())
+        .expect("There is at least one empty cell")
+}
+
+/// Returns the places that complete a line of the symbol.
+fn winning_places(board: &Board, symbol: XoSymbol) -> Vec<u8> {
+    board
+        .empty_cells()
+        .into_iter()
+        .filter(|&place| {
+            let mut board = board.clone();
+            board.set_cell(place, symbol);
+            board.is_win(&symbol)
+        })
+        .collect()
+}
+
+/// Returns the best places by the heuristic, the winning places, then the places that block the other
+/// symbol win, then the center, then the corners.
+fn heuristic_places(board: &Board) -> Vec<u8> {
+    let turn = board.turn();
+    let other = match turn {
+        XoSymbol::X => XoSymbol::O,
+        XoSymbol::O => XoSymbol::X,
+    };
+    let empty_cells = board.empty_cells();
+
+    [
+        winning_places(board, turn),
+        winning_places(board, other),
+        empty_cells
+            .iter()
+            .copied()
+            .filter(|&place| place == CENTER)
+            .collect(),
+        empty_cells
+            .iter()
+            .copied()
+            .filter(|place| CORNERS.contains(place))
+            .collect(),
+    ]
+    .into_iter()
+    .find(|places| !places.is_empty())
+    .unwrap_or(empty_cells)
+}
+
+/// Returns the places that keep the best outcome the symbol can force (minimax).
+fn perfect_places(board: &Board) -> Vec<u8> {
+    let turn = board.turn();
+    let outcomes: Vec<_> = board
+        .empty_cells()
+        .into_iter()
+        .map(|place| {
+            let mut board = board.clone();
+            board.set_cell(place, turn);
+            (place, -board.perfect_play_outcome())
+        })
+        .collect();
+    let best_outcome = outcomes
+        .iter()
+        .map(|(_, outcome)| *outcome)
+        .max()
+        .unwrap_or_default();
+
+    outcomes
+        .into_iter()
+        .filter(|(_, outcome)| *outcome == best_outcome)
+        .map(|(place, _)| place)
+        .collect()
+}
@@ -0,0 +1,291 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::TokioExecutor,
+};
+use salvo::{
+    async_trait,
+    hyper::{header, Method},
+    Depot, Request,
+};
+use salvo_captcha::CaptchaDepotExt;
+use serde::Deserialize;
+
+use crate::{
+    errors::{ApiError, ApiResult},
+    schemas::CaptchaProviderKind,
+    utils,
+};
+
+use super::risk;
+
+/// The header of the captcha response token of the hCaptcha and reCAPTCHA widgets.
+pub const CAPTCHA_RESPONSE_HEADER: &str = "X-Captcha-Response";
+
+/// The hCaptcha verification endpoint.
+pub const HCAPTCHA_VERIFY_URL: &str = "https://api.hcaptcha.com/siteverify";
+
+/// The reCAPTCHA verification endpoint.
+pub const RECAPTCHA_VERIFY_URL: &str = "https://www.google.com/recaptcha/api/siteverify";
+
+/// The default minimum reCAPTCHA v3 score, the requests with a lower score are rejected.
+pub const DEFAULT_RECAPTCHA_MIN_SCORE: f64 = 0.5;
+
+/// The timeout of the verification requests.
+const VERIFY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// The HTTP client of the captcha verification requests.
+type VerifyClient = Client<HttpsConnector<HttpConnector>, Full<Bytes>>;
+
+/// The captcha provider, it's verify the captcha of the signup, signin and guest upgrade requests.
+///
+/// Implement it to use another captcha service, and pass it to the
+/// [`CaptchaGuard::with_provider`](super::risk::CaptchaGuard::with_provider).
+#[async_trait]
+pub trait CaptchaProvider: Send + Sync + 'static {
+    /// Returns the provider kind, the clients use it to show the right captcha widget.
+    fn kind(&self) -> CaptchaProviderKind;
+
+    /// Returns the public site key of the widget, if the provider has one.
+    fn site_key(&self) -> Option<&str> {
+        None
+    }
+
+    /// Verifies the captcha of the request, returns [`ApiError::InternalServer`] if the provider is failing.
+    async fn verify(&self, req: &Request, depot: &Depot) -> ApiResult<()>;
+}
+
+/// The image captcha provider, the captcha is created with `/auth/captcha` and verified by the `salvo_captcha`
+/// middleware. This is the default one.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ImageCaptchaProvider;
+
+/// The hCaptcha provider, the response token is verified with the hCaptcha API.
+#[derive(Debug, Clone)]
+pub struct HCaptchaProvider {
+    client: VerifyClient,
+    site_key: String,
+    secret: String,
+    verify_url: String,
+}
+
+/// The reCAPTCHA v3 provider, the response token is verified with the reCAPTCHA API and its score must be
+/// at least the minimum score.
+#[derive(Debug, Clone)]
+pub struct RecaptchaProvider {
+    client: VerifyClient,
+    site_key: String,
+    secret: String,
+    min_score: f64,
+    verify_url: String,
+}
+
+/// The response of the `siteverify` endpoints, it's the same for hCaptcha and reCAPTCHA.
+#[derive(Deserialize, Debug)]
+struct SiteVerifyResponse {
+    success: bool,
+    /// The reCAPTCHA v3 score, from `0.0` (bot) to `1.0` (human).
+    score: Option<f64>,
+    #[serde(rename = "error-codes", default)]
+    error_codes: Vec<String>,
+}
+
+#[async_trait]
+impl CaptchaProvider for ImageCaptchaProvider {
+    fn kind(&self) -> CaptchaProviderKind {
+        CaptchaProviderKind::Image
+    }
+
+    async fn verify(&self, _: &Request, depot: &Depot) -> ApiResult<()> {
+        utils::handle_captcha_state(
+            depot
+                .get_captcha_state()
+                .expect("This route is protected by the `salvo_captcha` middleware"),
+        )
+    }
+}
+
+impl HCaptchaProvider {
+    /// Create a new hCaptcha provider, returns an error if there are no native root CA certificates.
+    pub fn new(site_key: impl Into<String>, secret: impl Into<String>) -> Result<Self, String> {
+        Ok(Self {
+            client: verify_client()?,
+            site_key: site_key.into(),
+            secret: secret.into(),
+            verify_url: HCAPTCHA_VERIFY_URL.to_owned(),
+        })
+    }
+
+    /// Sets the verification endpoint, e.g. for the hCaptcha Enterprise endpoint.
+    pub fn with_verify_url(self, verify_url: impl Into<String>) -> Self {
+        Self {
+            verify_url: verify_url.into(),
+            ..self
+        }
+    }
+}
+
+#[async_trait]
+impl CaptchaProvider for HCaptchaProvider {
+    fn kind(&self) -> CaptchaProviderKind {
+        CaptchaProviderKind::Hcaptcha
+    }
+
+    fn site_key(&self) -> Option<&str> {
+        Some(&self.site_key)
+    }
+
+    async fn verify(&self, req: &Request, depot: &Depot) -> ApiResult<()> {
+        let response =
+            site_verify(&self.client, &self.verify_url, &self.secret, req, depot).await?;
+        if !response.success {
+            log::info!("hCaptcha verification failed: {:?}", response.error_codes);
+            return Err(ApiError::InvalidCaptchaResponse);
+        }
+        Ok(())
+    }
+}
+
+impl RecaptchaProvider {
+    /// Create a new reCAPTCHA v3 provider, with the [`DEFAULT_RECAPTCHA_MIN_SCORE`]. Returns an error if there
+    /// are no native root CA certificates.
+    pub fn new(site_key: impl Into<String>, secret: impl Into<String>) -> Result<Self, String> {
+        Ok(Self {
+            client: verify_client()?,
+            site_key: site_key.into(),
+            secret: secret.into(),
+            min_score: DEFAULT_RECAPTCHA_MIN_SCORE,
+            verify_url: RECAPTCHA_VERIFY_URL.to_owned(),
+        })
+    }
+
+    /// Sets the minimum score, between `0.0` and `1.0`.
+    pub fn with_min_score(self, min_score: f64) -> Self {
+        Self { min_score, ..self }
+    }
+
+    /// Sets the verification endpoint, e.g. `https://www.recaptcha.net/recaptcha/api/siteverify`.
+    pub fn with_verify_url(self, verify_url: impl Into<String>) -> Self {
+        Self {
+            verify_url: verify_url.into(),
+            ..self
+        }
+    }
+}
+
+#[async_trait]
+impl CaptchaProvider for RecaptchaProvider {
+    fn kind(&self) -> CaptchaProviderKind {
+        CaptchaProviderKind::Recaptcha
+    }
+
+    fn site_key(&self) -> Option<&str> {
+        Some(&self.site_key)
+    }
+
+    async fn verify(&self, req: &Request, depot: &Depot) -> ApiResult<()> {
+        let response =
+            site_verify(&self.client, &self.verify_url, &self.secret, req, depot).await?;
+        if !response.success {
+            log::info!("reCAPTCHA verification failed: {:?}", response.error_codes);
+            return Err(ApiError::InvalidCaptchaResponse);
+        }
+        // The v2 responses don't have a score, they are rejected
+        if response.score.unwrap_or_default() < self.min_score {
+            log::info!("reCAPTCHA score is too low: {:?}", response.score);
+            return Err(ApiError::InvalidCaptchaResponse);
+        }
+        Ok(())
+    }
+}
+
+/// Returns the client of the captcha verification requests, with the native root CA certificates.
+fn verify_client() -> Result<VerifyClient, String> {
+    // The verification URLs are HTTPS, outside of the test environment
+    let https = HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .map_err(|err| format!("No native root CA certificates found: {err}"))?
+        .https_or_http()
+        .enable_http1()
+        .build();
+    Ok(Client::builder(TokioExecutor::new()).build(https))
+}
+
+/// Verifies the captcha response header of the request with the `siteverify` endpoint.
+///
+/// The unreachable endpoint and its invalid responses are [`ApiError::InternalServer`].
+async fn site_verify(
+    client: &VerifyClient,
+    verify_url: &str,
+    secret: &str,
+    req: &Request,
+    depot: &Depot,
+) -> ApiResult<SiteVerifyResponse> {
+    let captcha_response = req
+        .header::<String>(CAPTCHA_RESPONSE_HEADER)
+        .filter(|response| !response.trim().is_empty())
+        .ok_or(ApiError::UnProvidedCaptchaResponse)?;
+    let form = {
+        let mut form = form_urlencoded::Serializer::new(String::new());
+        form.append_pair("secret", secret)
+            .append_pair("response", &captcha_response);
+        if let Some(ip) = risk::depot_client_ip(req, depot) {
+            form.append_pair("remoteip", &ip.to_string());
+        }
+        form.finish()
+    };
+    let request = salvo::hyper::Request::builder()
+        .method(Method::POST)
+        .uri(verify_url)
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Full::new(Bytes::from(form)))
+        .map_err(|err| {
+            log::error!("Invalid captcha verification request: {err}");
+            ApiError::InternalServer
+        })?;
+
+    let body = match tokio::time::timeout(VERIFY_TIMEOUT, async {
+        client
+            .request(request)
+            .await
+            .map_err(|err| err.to_string())?
+            .into_body()
+            .collect()
+            .await
+            .map_err(|err| err.to_string())
+    })
+    .await
+    {
+        Ok(Ok(body)) => body.to_bytes(),
+        Ok(Err(err)) => {
+            log::error!("The captcha verification request failed: {err}");
+            return Err(ApiError::InternalServer);
+        }
+        Err(_) => {
+            log::error!("The captcha verification endpoint didn't respond in time");
+            return Err(ApiError::InternalServer);
+        }
+    };
+    serde_json::from_slice(&body).map_err(|err| {
+        log::error!("Invalid captcha verification response: {err}");
+        ApiError::InternalServer
+    })
+}
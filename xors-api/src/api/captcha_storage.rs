@@ -0,0 +1,84 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use salvo_captcha::{CacacheStorage, CaptchaStorage};
+
+/// The prefix of the stored case-insensitive answers, it's not a captcha character.
+const CASE_INSENSITIVE_PREFIX: char = '~';
+
+/// The captcha answer, it remembers whether its matching is case-sensitive.
+///
+/// Two answers are equal if they are the same, or the same ignoring the case if one of them is case-insensitive.
+#[derive(Debug, Clone, derive_new::new)]
+pub struct CaptchaAnswer {
+    answer: String,
+    case_sensitive: bool,
+}
+
+impl From<String> for CaptchaAnswer {
+    /// The answers of the requests are case-sensitive, the stored answer decides the matching.
+    fn from(answer: String) -> Self {
+        Self::new(answer, true)
+    }
+}
+
+impl PartialEq for CaptchaAnswer {
+    fn eq(&self, other: &Self) -> bool {
+        if self.case_sensitive && other.case_sensitive {
+            self.answer == other.answer
+        } else {
+            self.answer.eq_ignore_ascii_case(&other.answer)
+        }
+    }
+}
+
+/// The captcha storage, stores the answers in the [`CacacheStorage`] with their case sensitivity.
+#[derive(Debug, Clone, derive_new::new)]
+pub struct CaptchaAnswerStorage {
+    storage: CacacheStorage,
+}
+
+impl CaptchaStorage for CaptchaAnswerStorage {
+    type Error = <CacacheStorage as CaptchaStorage>::Error;
+    type Token = <CacacheStorage as CaptchaStorage>::Token;
+    type Answer = CaptchaAnswer;
+
+    async fn store_answer(&self, answer: Self::Answer) -> Result<Self::Token, Self::Error> {
+        let answer = if answer.case_sensitive {
+            answer.answer
+        } else {
+            format!("{CASE_INSENSITIVE_PREFIX}{}", answer.answer)
+        };
+        self.storage.store_answer(answer).await
+    }
+
+    async fn get_answer(&self, token: &Self::Token) -> Result<Option<Self::Answer>, Self::Error> {
+        Ok(self.storage.get_answer(token).await?.map(|answer| {
+            match answer.strip_prefix(CASE_INSENSITIVE_PREFIX) {
+                Some(answer) => CaptchaAnswer::new(answer.to_owned(), false),
+                None => CaptchaAnswer::new(answer, true),
+            }
+        }))
+    }
+
+    async fn clear_expired(&self, expired_after: std::time::Duration) -> Result<(), Self::Error> {
+        self.storage.clear_expired(expired_after).await
+    }
+
+    async fn clear_by_token(&self, token: &Self::Token) -> Result<(), Self::Error> {
+        self.storage.clear_by_token(token).await
+    }
+}
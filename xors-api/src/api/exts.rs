@@ -29,7 +29,12 @@ use crate::{
     schemas::{GameOverReason, XoServerEventData},
 };
 
-use super::{jwt::JwtClaims, xo::Player};
+use super::{
+    jwt::JwtClaims,
+    matchmaking::QueuedPlayer,
+    metrics::METRICS,
+    xo::{MessageSender, Player},
+};
 
 #[ext(UserExt)]
 impl Depot {
@@ -52,6 +57,23 @@ impl Depot {
         }
     }
 
+    /// Checks that the user confirmed the password recently, if the server requires it for the sensitive actions.
+    pub(crate) fn require_fresh_auth(&self) -> ApiResult<()> {
+        let fresh_auth_period = **self
+            .get::<Arc<Option<u64>>>("fresh_auth_period")
+            .expect("The fresh auth period should be in the depot");
+
+        match fresh_auth_period {
+            Some(period)
+                if self.jwt_claims().auth_time + (period as i64)
+                    < chrono::Utc::now().timestamp() =>
+            {
+                Err(ApiError::ReauthenticationRequired)
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub(crate) fn jwt_claims(&self) -> &JwtClaims {
         &self
             .jwt_auth_data::<JwtClaims>()
@@ -62,8 +84,30 @@ impl Depot {
 
 #[ext(WriteGamesExt)]
 impl RwLock<super::xo::Games> {
-    pub(crate) async fn add_game(&self, game_uuid: Uuid, player1: Player, player2: Player) {
+    pub(crate) async fn add_game(
+        &self,
+        conn: &DatabaseConnection,
+        game_uuid: Uuid,
+        player1: Player,
+        player2: Player,
+    ) {
+        let players = [*player1.0, *player2.0];
         self.write().await.insert(game_uuid, (player1, player2));
+        METRICS.game_started();
+        for player in players {
+            super::xo::presence_changed(conn, player).await;
+        }
+    }
+
+    /// Replaces the player's events sender in its game, after it reconnects.
+    pub(crate) async fn replace_player(&self, game_uuid: &Uuid, player: Player) {
+        if let Some((player1, player2)) = self.write().await.get_mut(game_uuid) {
+            if player1.0 == player.0 {
+                *player1 = player;
+            } else if player2.0 == player.0 {
+                *player2 = player;
+            }
+        }
     }
 
     /// This will remove the game from the database and the in-memory map.
@@ -76,25 +120,61 @@ impl RwLock<super::xo::Games> {
         win_reason: &GameOverReason,
     ) -> ApiResult<()> {
         db_utils::end_game(conn, game_uuid, winner, win_reason).await?;
-        self.write().await.remove(game_uuid);
+        super::notification::tournament_game_ended(conn, *game_uuid).await;
+        super::webhook::game_finished(conn, *game_uuid).await;
+        let removed_game = self.write().await.remove(game_uuid);
+        if let Some((player1, player2)) = removed_game {
+            METRICS.game_ended();
+            for player in [player1, player2] {
+                super::xo::presence_changed(conn, *player.0).await;
+            }
+        }
         Ok(())
     }
 }
 
 #[ext(WriteSearchUsersExt)]
-impl RwLock<VecDeque<Player>> {
-    pub(crate) async fn add_user(&self, player: Player) {
+impl RwLock<VecDeque<QueuedPlayer>> {
+    pub(crate) async fn add_user(&self, player: QueuedPlayer) {
         self.write().await.push_back(player);
     }
 
-    pub(crate) async fn pop_front(&self) -> Option<Player> {
-        self.write().await.pop_front()
+    /// Removes the user from the search queue and returns it, if it's in the queue.
+    pub(crate) async fn take_user(&self, user_uuid: &Uuid) -> Option<QueuedPlayer> {
+        let mut search_users = self.write().await;
+        let index = search_users
+            .iter()
+            .position(|queued| queued.player.0.as_ref() == user_uuid)?;
+        search_users.remove(index)
+    }
+
+    /// Removes the two users from the search queue and returns them, only if both of them are in the queue.
+    pub(crate) async fn take_pair(
+        &self,
+        user_uuid: &Uuid,
+        other_uuid: &Uuid,
+    ) -> Option<(QueuedPlayer, QueuedPlayer)> {
+        let mut search_users = self.write().await;
+        let position = |uuid: &Uuid| {
+            search_users
+                .iter()
+                .position(|queued| queued.player.0.as_ref() == uuid)
+        };
+        let (index, other_index) = (position(user_uuid)?, position(other_uuid)?);
+        // Remove the later one first, so the index of the other doesn't shift
+        if index > other_index {
+            let user = search_users.remove(index)?;
+            Some((user, search_users.remove(other_index)?))
+        } else {
+            let other = search_users.remove(other_index)?;
+            Some((search_users.remove(index)?, other))
+        }
     }
 
     pub(crate) async fn remove_user(&self, user_uuid: &Uuid) {
         self.write()
             .await
-            .retain(|player| player.0.as_ref() != user_uuid);
+            .retain(|queued| queued.player.0.as_ref() != user_uuid);
     }
 }
 
@@ -147,16 +227,70 @@ impl RwLock<super::xo::Games> {
 }
 
 #[ext(ReadSearchUsersExt)]
-impl RwLock<VecDeque<Player>> {
+impl RwLock<VecDeque<QueuedPlayer>> {
     pub(crate) async fn is_user_in_search(&self, user_uuid: &Uuid) -> bool {
         self.read()
             .await
             .iter()
-            .any(|player| player.0.as_ref() == user_uuid)
+            .any(|queued| queued.player.0.as_ref() == user_uuid)
     }
 
-    pub(crate) async fn search_users_count(&self) -> usize {
-        self.read().await.len()
+    /// Returns the user in the search queue, if it's in the queue.
+    pub(crate) async fn queued_player(&self, user_uuid: &Uuid) -> Option<QueuedPlayer> {
+        self.read()
+            .await
+            .iter()
+            .find(|queued| queued.player.0.as_ref() == user_uuid)
+            .cloned()
+    }
+
+    /// Returns the users in the search queue, in the queue order.
+    pub(crate) async fn queued_players(&self) -> Vec<QueuedPlayer> {
+        self.read().await.iter().cloned().collect()
+    }
+}
+
+#[ext(SpectatorsExt)]
+impl RwLock<super::xo::Spectators> {
+    pub(crate) async fn add_spectator(&self, game_uuid: Uuid, spectator: MessageSender) {
+        self.write()
+            .await
+            .entry(game_uuid)
+            .or_default()
+            .push(spectator);
+    }
+
+    pub(crate) async fn remove_spectator(&self, game_uuid: &Uuid, spectator: &MessageSender) {
+        let mut spectators = self.write().await;
+        if let Some(game_spectators) = spectators.get_mut(game_uuid) {
+            game_spectators.retain(|tx| !Arc::ptr_eq(tx, spectator));
+            if game_spectators.is_empty() {
+                spectators.remove(game_uuid);
+            }
+        }
+    }
+
+    pub(crate) async fn broadcast_messages(&self, game_uuid: Uuid, messages: &[XoServerEventData]) {
+        if let Some(spectators) = self.read().await.get(&game_uuid) {
+            for spectator in spectators {
+                messages
+                    .iter()
+                    .for_each(|event| spectator.send_server_event(event.clone()));
+            }
+        }
+    }
+
+    pub(crate) async fn broadcast_message(&self, game_uuid: Uuid, message: XoServerEventData) {
+        self.broadcast_messages(game_uuid, &[message]).await;
+    }
+
+    /// Closes the connections of the game spectators, after the game is over.
+    pub(crate) async fn remove_game(&self, game_uuid: &Uuid) {
+        if let Some(spectators) = self.write().await.remove(game_uuid) {
+            for spectator in spectators {
+                spectator.send(Ok(Message::close())).ok();
+            }
+        }
     }
 }
 
@@ -0,0 +1,335 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{
+    db_utils,
+    errors::{ApiError, ApiResult},
+    schemas::*,
+    utils,
+};
+
+use entity::prelude::*;
+use futures_util::StreamExt;
+use salvo::oapi::extract::{JsonBody, PathParam, QueryParam};
+use salvo::prelude::*;
+use salvo::{oapi::endpoint, writing::Json};
+use sea_orm::DatabaseConnection;
+use uuid::Uuid;
+
+use std::sync::Arc;
+
+use super::{exts::UserExt, notification, xo};
+
+/// Returns the friend requests schemas of the requests users and their dates.
+async fn request_schemas(
+    conn: &DatabaseConnection,
+    requests: Vec<(UserModel, chrono::NaiveDateTime)>,
+) -> ApiResult<Vec<FriendRequestSchema>> {
+    futures_util::stream::iter(requests)
+        .then(|(user, created_at)| async move {
+            UserSchema::from_active_model(conn, user.into_active_model())
+                .await
+                .map(|user| FriendRequestSchema::new(user, created_at))
+        })
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// Get the user's friends.
+///
+/// This endpoint will return the user's friends with their presence, the latest friends first. The friend is online
+/// if it's connected to the XO websocket, and in a game if it's playing one.
+#[endpoint(
+    operation_id = "get_friends",
+    tags("Friends"),
+    responses(
+        (status_code = 200, description = "The user's friends", content_type = "application/json", body = Vec<FriendSchema>),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn get_friends(depot: &mut Depot) -> ApiResult<Json<Vec<FriendSchema>>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    Ok(Json(
+        futures_util::stream::iter(db_utils::get_friends(conn, user.uuid).await?)
+            .then(|(friend, since)| async move {
+                let presence = xo::player_presence(&friend.uuid).await;
+                UserSchema::from_active_model(conn, friend.into_active_model())
+                    .await
+                    .map(|friend| FriendSchema::new(friend, presence, since))
+            })
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<ApiResult<_>>()?,
+    ))
+}
+
+/// Remove a friend.
+///
+/// This endpoint will remove the friendship with the user, or the pending friend request between you in any
+/// direction. Removing a not friend does nothing.
+#[endpoint(
+    operation_id = "remove_friend",
+    tags("Friends"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The uuid of the friend to remove"),
+    ),
+    responses(
+        (status_code = 200, description = "The friend has been removed", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn remove_friend(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    db_utils::remove_friend(conn.as_ref(), user.uuid, uuid.into_inner()).await?;
+    Ok(Json(MessageSchema::new(
+        "The friend has been removed".to_owned(),
+    )))
+}
+
+/// Send a friend request.
+///
+/// This endpoint will send a friend request to the user, if the user already sent you a request it's accepted
+/// instead. You can't send a request to a user that you blocked or that blocked you.
+#[endpoint(
+    operation_id = "send_friend_request",
+    tags("Friends"),
+    request_body(
+        content = NewFriendRequestSchema,
+        description = "The user to send the friend request to",
+        example = json!(NewFriendRequestSchema::new(Uuid::new_v4())),
+        content_type = "application/json",
+    ),
+    responses(
+        (status_code = 200, description = "The friend request has been sent or accepted", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "You can't send a friend request to yourself", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "You can't send a friend request to this user", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "You are already friends or the request is already sent", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn send_friend_request(
+    depot: &mut Depot,
+    request: JsonBody<NewFriendRequestSchema>,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+    let addressee = request.into_inner().user_uuid;
+
+    if db_utils::send_friend_request(conn, user.uuid, addressee).await? {
+        xo::friends_added(user.uuid, addressee).await;
+        Ok(Json(MessageSchema::new(
+            "The friend request has been accepted".to_owned(),
+        )))
+    } else {
+        notification::notify(
+            conn,
+            addressee,
+            NotificationData::FriendRequest {
+                requester: user.uuid,
+                username: user.username,
+            },
+        )
+        .await;
+        Ok(Json(MessageSchema::new(
+            "The friend request has been sent".to_owned(),
+        )))
+    }
+}
+
+/// Get the friend requests.
+///
+/// This endpoint will return the user's pending friend requests, the requests sent to the user and the requests
+/// sent by the user, the latest first.
+#[endpoint(
+    operation_id = "get_friend_requests",
+    tags("Friends"),
+    responses(
+        (status_code = 200, description = "The pending friend requests", content_type = "application/json", body = FriendRequestsSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn get_friend_requests(depot: &mut Depot) -> ApiResult<Json<FriendRequestsSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    let (incoming, outgoing) = db_utils::get_friend_requests(conn, user.uuid).await?;
+    Ok(Json(FriendRequestsSchema::new(
+        request_schemas(conn, incoming).await?,
+        request_schemas(conn, outgoing).await?,
+    )))
+}
+
+/// Accept a friend request.
+///
+/// This endpoint will accept the pending friend request that the user sent to you.
+#[endpoint(
+    operation_id = "accept_friend_request",
+    tags("Friends"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The uuid of the user that sent the request"),
+    ),
+    responses(
+        (status_code = 200, description = "The friend request has been accepted", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "The friend request is not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn accept_friend_request(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+    let requester = uuid.into_inner();
+
+    db_utils::accept_friend_request(conn, user.uuid, requester).await?;
+    xo::friends_added(user.uuid, requester).await;
+    Ok(Json(MessageSchema::new(
+        "The friend request has been accepted".to_owned(),
+    )))
+}
+
+/// Decline a friend request.
+///
+/// This endpoint will decline the pending friend request that the user sent to you, the request is deleted
+/// and the user can send a new one.
+#[endpoint(
+    operation_id = "decline_friend_request",
+    tags("Friends"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The uuid of the user that sent the request"),
+    ),
+    responses(
+        (status_code = 200, description = "The friend request has been declined", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "The friend request is not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn decline_friend_request(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    db_utils::decline_friend_request(conn, user.uuid, uuid.into_inner()).await?;
+    Ok(Json(MessageSchema::new(
+        "The friend request has been declined".to_owned(),
+    )))
+}
+
+/// Invite a friend to a private game.
+///
+/// This endpoint will create a new invite for a private game, like the `/game/invite` endpoint, and the friend
+/// receives a `game_invite` notification with the invite code. Only the friends can be invited, your previous
+/// invites are replaced. The game variant is selected like the `/game/invite` endpoint.
+#[endpoint(
+    operation_id = "invite_friend",
+    tags("Friends"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The uuid of the friend to invite"),
+        ("size" = Option<u8>, Query, description = "The board size, between 3 and 15, default is `3`"),
+        ("win_length" = Option<u8>, Query, description = "The cells count in a row to win, between 3 and the board size, default is the board size at most `5`"),
+    ),
+    responses(
+        (status_code = 200, description = "The friend has been invited", content_type = "application/json", body = InviteSchema),
+        (status_code = 400, description = "Invalid game variant", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is not your friend", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn invite_friend(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+    size: QueryParam<u8, false>,
+    win_length: QueryParam<u8, false>,
+) -> ApiResult<Json<InviteSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+    let invite_ttl = **depot.get::<Arc<u64>>("invite_ttl").unwrap();
+    let variant = utils::game_variant(size.into_inner(), win_length.into_inner())?;
+    let friend = uuid.into_inner();
+
+    if !db_utils::are_friends(conn, user.uuid, friend).await? {
+        return Err(ApiError::NotFriend);
+    }
+    let invite = db_utils::create_invite(conn, user.uuid, invite_ttl, variant).await?;
+    notification::notify(
+        conn,
+        friend,
+        NotificationData::GameInvite {
+            host: user.uuid,
+            host_username: user.username,
+            code: invite.code.clone(),
+            expires_at: invite.expires_at,
+            variant,
+        },
+    )
+    .await;
+    Ok(Json(invite.into()))
+}
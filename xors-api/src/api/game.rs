@@ -14,10 +14,17 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::{db_utils, errors::ApiResult, schemas::*};
+use crate::{
+    db_utils,
+    errors::{ApiError, ApiResult},
+    schemas::*,
+    utils,
+};
 
 use futures_util::StreamExt;
-use salvo::oapi::extract::PathParam;
+use salvo::http::HeaderValue;
+use salvo::hyper::header;
+use salvo::oapi::extract::{JsonBody, PathParam, QueryParam};
 use salvo::prelude::*;
 use salvo::{oapi::endpoint, writing::Json};
 use sea_orm::DatabaseConnection;
@@ -25,6 +32,8 @@ use uuid::Uuid;
 
 use std::sync::Arc;
 
+use super::exts::UserExt;
+
 /// Get the game by uuid.
 ///
 /// **Note**: This will return the game only if it's ended.
@@ -82,3 +91,476 @@ pub async fn get_lastest_games(depot: &mut Depot) -> ApiResult<Json<Vec<GameSche
             .collect::<ApiResult<_>>()?,
     ))
 }
+
+/// Get the recent games.
+///
+/// This endpoint will return the recently ended games of all the users, sorted by the end date (newest first).
+/// Each page has 20 games, the response is cacheable for 10 seconds.
+///
+/// **Note**: If the O player or the X player is deleted, the game will return it as deleted user, which it's uuid is `00000000-0000-0000-0000-000000000000` and username is `Deleted`.
+#[endpoint(
+    operation_id = "get_recent_games",
+    tags("Game"),
+    parameters(
+        ("page" = Option<u64>, Query, description = "The page number, starting from 0, default is `0`"),
+    ),
+    responses(
+        (status_code = 200, description = "The recent games", content_type = "application/json", body = Vec<GameSchema>),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn get_recent_games(
+    depot: &mut Depot,
+    res: &mut Response,
+    page: QueryParam<u64, false>,
+) -> ApiResult<Json<Vec<GameSchema>>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap().as_ref();
+
+    let games = db_utils::get_recent_games(conn, page.into_inner().unwrap_or_default()).await?;
+    res.headers_mut().insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=10"),
+    );
+    Ok(Json(
+        futures_util::stream::iter(games)
+            .then(|game| GameSchema::from_game(conn, game))
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<ApiResult<_>>()?,
+    ))
+}
+
+/// Get the game's moves.
+///
+/// This endpoint will return the game's moves that their sequence is greater than `since`, so the client can catch up the missed moves.
+///
+/// **Note**: The moves of an in-progress game are only available for its players.
+#[endpoint(
+    operation_id = "get_game_moves",
+    tags("Game"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The requested game's uuid"),
+        ("since" = Option<u32>, Query, description = "Returns the moves after this sequence, default is `0`"),
+    ),
+    responses(
+        (status_code = 200, description = "The game's moves", content_type = "application/json", body = Vec<MoveSchema>),
+        (status_code = 400, description = "The uuid is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "You are not a player in this game", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "Game not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn get_game_moves(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+    since: QueryParam<u32, false>,
+) -> ApiResult<Json<Vec<MoveSchema>>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap().as_ref();
+    let user = depot.user(conn).await?;
+    let game_uuid = uuid.into_inner();
+    let since = since.into_inner().unwrap_or_default();
+
+    let game = match db_utils::get_game::<true>(conn, &game_uuid).await {
+        Err(ApiError::GameNotFound) => {
+            let game = db_utils::get_game::<false>(conn, &game_uuid).await?;
+            if game.x_player != user.uuid && game.o_player != user.uuid {
+                return Err(ApiError::NotGamePlayer);
+            }
+            game
+        }
+        game => game?,
+    };
+
+    Ok(Json(
+        MoveSchema::from_game(&game)
+            .into_iter()
+            .filter(|game_move| game_move.sequence > since)
+            .collect(),
+    ))
+}
+
+/// Decode a compact board.
+///
+/// This endpoint will decode the compact board state and return the full board, whose turn it is and the winner.
+/// Use it to load an arbitrary position, for puzzles or analysis.
+/// - `board`: Base64 of the board width, height and cells bytes, the cell byte is `0` for an empty cell, `1` for X and `2` for O, row by row.
+///
+/// **Note**: Only the 3x3 boards are supported, and the position must be legal (X always plays first).
+#[endpoint(
+    operation_id = "decode_board",
+    tags("Game"),
+    request_body(
+        content = EncodedBoardSchema,
+        description = "The compact board",
+        example = json!(EncodedBoardSchema::new("AwMBAgABAAAAAAA=".to_owned())),
+        content_type = "application/json",
+    ),
+    responses(
+        (status_code = 200, description = "The decoded board", content_type = "application/json", body = DecodedBoardSchema),
+        (status_code = 400, description = "Invalid or illegal board encoding", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn decode_board(
+    board: JsonBody<EncodedBoardSchema>,
+) -> ApiResult<Json<DecodedBoardSchema>> {
+    utils::decode_compact_board(&board.into_inner().board).map(Json)
+}
+
+/// Validate a game.
+///
+/// This endpoint will replay the moves on the starting board and return whether if they are legal, the resulting board
+/// and the first illegal move with its reason. Use it to validate the imported games, nothing is stored.
+/// - `board`: The starting compact board of the [decode endpoint](#/Game/decode_board), null for an empty board.
+/// - `moves`: The played places in order, the symbols alternate starting from the board turn.
+///
+/// **Note**: The moves after an illegal move are not replayed, and any move after the board is ended is illegal.
+#[endpoint(
+    operation_id = "validate_game",
+    tags("Game"),
+    request_body(
+        content = ValidateGameSchema,
+        description = "The starting board and the moves",
+        example = json!(ValidateGameSchema::new(None, vec![4, 0, 8, 2, 1, 7, 6, 3, 5])),
+        content_type = "application/json",
+    ),
+    responses(
+        (status_code = 200, description = "The game validation", content_type = "application/json", body = GameValidationSchema),
+        (status_code = 400, description = "Invalid or illegal board encoding", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn validate_game(
+    game: JsonBody<ValidateGameSchema>,
+) -> ApiResult<Json<GameValidationSchema>> {
+    let game = game.into_inner();
+    let board = match game.board {
+        Some(board) => utils::parse_compact_board(&board)?,
+        None => Board::default(),
+    };
+
+    Ok(Json(GameValidationSchema::replay(board, &game.moves)))
+}
+
+/// Evaluate a board.
+///
+/// This endpoint will return the game-theoretic value of the position for the symbol to move, with a perfect play (minimax) from both symbols:
+/// - `win`: The symbol to move can force a win.
+/// - `draw`: Both symbols can force a draw.
+/// - `loss`: The other symbol can force a win.
+///
+/// An ended board is evaluated as is, a `loss` if the other symbol won and a `draw` if it's full.
+///
+/// **Note**: The board is the compact board of the [decode endpoint](#/Game/decode_board), and the position must be legal.
+#[endpoint(
+    operation_id = "evaluate_board",
+    tags("Game"),
+    request_body(
+        content = EvaluateBoardSchema,
+        description = "The compact board and the symbol to move",
+        example = json!(EvaluateBoardSchema::new("AwMBAgABAAAAAAA=".to_owned(), XoSymbol::O)),
+        content_type = "application/json",
+    ),
+    responses(
+        (status_code = 200, description = "The board evaluation", content_type = "application/json", body = BoardEvaluationSchema),
+        (status_code = 400, description = "Invalid or illegal board encoding, or the symbol to move doesn't match the board", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn evaluate_board(
+    evaluate: JsonBody<EvaluateBoardSchema>,
+) -> ApiResult<Json<BoardEvaluationSchema>> {
+    let evaluate = evaluate.into_inner();
+    let board = utils::parse_compact_board(&evaluate.board)?;
+
+    if board.turn() != evaluate.turn {
+        return Err(ApiError::InvalidBoardEncoding(format!(
+            "It's not the {} turn, X plays first",
+            evaluate.turn.to_string()
+        )));
+    }
+    Ok(Json(BoardEvaluationSchema::from(&board)))
+}
+
+/// Get the game's analysis.
+///
+/// This endpoint will replay the game's moves and annotate each move compared with the perfect play (minimax):
+/// - `optimal`: The move keeps the best outcome the player can force.
+/// - `neutral`: The move is not the best, but the opponent can't force a win after it.
+/// - `blunder`: The move allows the opponent to force a win.
+///
+/// **Note**: This will return the analysis only if the game is ended and it's a classic 3x3 game, the response is cacheable.
+#[endpoint(
+    operation_id = "get_game_analysis",
+    tags("Game"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The requested game's uuid"),
+    ),
+    responses(
+        (status_code = 200, description = "The game's moves analysis", content_type = "application/json", body = Vec<MoveAnalysisSchema>),
+        (status_code = 400, description = "The uuid is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The game isn't a classic 3x3 game", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "Game not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn get_game_analysis(
+    depot: &mut Depot,
+    res: &mut Response,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<Vec<MoveAnalysisSchema>>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap().as_ref();
+    let game = db_utils::get_game::<true>(conn, &uuid.into_inner()).await?;
+    if !GameVariant::from(&game).is_classic() {
+        return Err(ApiError::UnsupportedGameVariant);
+    }
+
+    // The ended game's history is fixed
+    res.headers_mut().insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=86400, immutable"),
+    );
+    Ok(Json(MoveAnalysisSchema::from_game(&game)))
+}
+
+/// Get the game's replay.
+///
+/// This endpoint will return the game's moves in order with when they are played, the game's chat messages
+/// if the server saves them, the game's result and its compact text notation, e.g. `1. X4 O0 X8 2. *X2 O4 1-0`. The rounds are numbered, the random opening
+/// moves are prefixed with `*`, and the result is `1-0` if X won, `0-1` if O won and `1/2-1/2` if there is no winner.
+///
+/// **Note**: This will return the replay only if the game is ended, the response is cacheable.
+#[endpoint(
+    operation_id = "get_game_replay",
+    tags("Game"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The requested game's uuid"),
+    ),
+    responses(
+        (status_code = 200, description = "The game's replay", content_type = "application/json", body = ReplaySchema),
+        (status_code = 400, description = "The uuid is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "Game not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn get_game_replay(
+    depot: &mut Depot,
+    res: &mut Response,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<ReplaySchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap().as_ref();
+    let game = db_utils::get_game::<true>(conn, &uuid.into_inner()).await?;
+    let saved_moves = db_utils::get_game_moves(conn, game.uuid).await?;
+    let chat_messages = db_utils::get_chat_messages(conn, game.uuid).await?;
+
+    // The ended game's history is fixed
+    res.headers_mut().insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=86400, immutable"),
+    );
+    Ok(Json(ReplaySchema::from_game(
+        &game,
+        &saved_moves,
+        chat_messages,
+    )))
+}
+
+/// Pause the matchmaking.
+///
+/// The user keeps its place in the search queue, but will not be matched with other players until it resumes.
+/// The search still times out while it's paused. Pausing an already paused matchmaking does nothing.
+///
+/// **Note**: The user must be searching for a game through the XO websocket.
+#[endpoint(
+    operation_id = "pause_matchmaking",
+    tags("Game"),
+    responses(
+        (status_code = 200, description = "The matchmaking has been paused", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "You are not in the search queue", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn pause_matchmaking(depot: &mut Depot) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap().as_ref();
+    let user = depot.user(conn).await?;
+
+    super::matchmaking::pause_search(&user.uuid).await?;
+    Ok(Json(MessageSchema::new(
+        "The matchmaking has been paused".to_owned(),
+    )))
+}
+
+/// Resume the matchmaking.
+///
+/// The user is matched with the closest rating player in the search queue within their rating tolerance, otherwise
+/// it waits in its original place in the queue, the time spent paused doesn't lose its priority nor its widened
+/// tolerance.
+/// Resuming a not paused matchmaking does nothing.
+#[endpoint(
+    operation_id = "resume_matchmaking",
+    tags("Game"),
+    responses(
+        (status_code = 200, description = "The matchmaking has been resumed", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "You are not in the search queue", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn resume_matchmaking(depot: &mut Depot) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap().as_ref();
+    let user = depot.user(conn).await?;
+    let max_online_games = **depot.get::<Arc<usize>>("max_online_games").unwrap();
+    let game_clock = **depot.obtain::<Arc<super::xo::GameClock>>().unwrap();
+    let unique_pair_games = **depot.get::<Arc<bool>>("unique_pair_games").unwrap();
+    let random_opening_moves = **depot.get::<Arc<u8>>("random_opening_moves").unwrap();
+    let matchmaking_policy = **depot
+        .obtain::<Arc<super::matchmaking::MatchmakingPolicy>>()
+        .unwrap();
+
+    super::matchmaking::resume_search(
+        conn,
+        &user.uuid,
+        max_online_games,
+        game_clock,
+        unique_pair_games,
+        random_opening_moves,
+        matchmaking_policy,
+    )
+    .await?;
+    Ok(Json(MessageSchema::new(
+        "The matchmaking has been resumed".to_owned(),
+    )))
+}
+
+/// Create an invite for a private game.
+///
+/// The private game isn't matched publicly, share the invite code with your friend to join it with the
+/// `/game/join/{code}` endpoint or the `join_code` XO websocket event. Creating a new invite replaces your
+/// previous invite, and the invite expires after the server's invite TTL.
+///
+/// The private game variant is selected with the board size and the win length, e.g. `?size=15&win_length=5`
+/// for 5 in a row on the 15x15 board. By default it's the classic 3x3 board.
+///
+/// **Note**: You must be connected to the XO websocket when your friend joins.
+#[endpoint(
+    operation_id = "create_invite",
+    tags("Game"),
+    parameters(
+        ("size" = Option<u8>, Query, description = "The board size, between 3 and 15, default is `3`"),
+        ("win_length" = Option<u8>, Query, description = "The cells count in a row to win, between 3 and the board size, default is the board size at most `5`"),
+    ),
+    responses(
+        (status_code = 200, description = "The invite has been created", content_type = "application/json", body = InviteSchema),
+        (status_code = 400, description = "Invalid game variant", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn create_invite(
+    depot: &mut Depot,
+    size: QueryParam<u8, false>,
+    win_length: QueryParam<u8, false>,
+) -> ApiResult<Json<InviteSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap().as_ref();
+    let user = depot.user(conn).await?;
+    let invite_ttl = **depot.get::<Arc<u64>>("invite_ttl").unwrap();
+    let variant = utils::game_variant(size.into_inner(), win_length.into_inner())?;
+
+    db_utils::create_invite(conn, user.uuid, invite_ttl, variant)
+        .await
+        .map(|invite| Json(invite.into()))
+}
+
+/// Join the private game of an invite code.
+///
+/// The game starts immediately, the invite host is the X player. The game events are sent to the XO websocket
+/// connections of both players, the invite can be used once.
+///
+/// **Note**: Both players must be connected to the XO websocket.
+#[endpoint(
+    operation_id = "join_invite",
+    tags("Game"),
+    parameters(
+        ("code" = String, Path, description = "The invite code"),
+    ),
+    responses(
+        (status_code = 200, description = "The game has been started", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "You can't join your own invite", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "The invite code is invalid or expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "You are not connected to the XO websocket", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "The invite host is offline or can't start a game now", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "You can't start a game now", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn join_invite(
+    depot: &mut Depot,
+    code: PathParam<String>,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap().as_ref();
+    let user = depot.user(conn).await?;
+    let max_online_games = **depot.get::<Arc<usize>>("max_online_games").unwrap();
+    let game_clock = **depot.obtain::<Arc<super::xo::GameClock>>().unwrap();
+    let unique_pair_games = **depot.get::<Arc<bool>>("unique_pair_games").unwrap();
+    let daily_games_limit = **depot.get::<Arc<Option<u64>>>("daily_games_limit").unwrap();
+    let random_opening_moves = **depot.get::<Arc<u8>>("random_opening_moves").unwrap();
+
+    let events_tx = super::xo::player_events_tx(&user.uuid).ok_or(ApiError::NotConnected)?;
+    let join_error = super::xo::join_invite(
+        conn,
+        &max_online_games,
+        game_clock,
+        unique_pair_games,
+        daily_games_limit,
+        random_opening_moves,
+        (Arc::new(user.uuid), events_tx),
+        &code.into_inner(),
+    )
+    .await?;
+
+    match join_error {
+        None => Ok(Json(MessageSchema::new(
+            "The game has been started".to_owned(),
+        ))),
+        Some(ErrorData::InvalidInviteCode) => Err(ApiError::InvalidInviteCode),
+        Some(ErrorData::CannotJoinOwnInvite) => Err(ApiError::CannotJoinOwnInvite),
+        Some(ErrorData::InviteHostUnavailable) => Err(ApiError::InviteHostUnavailable),
+        Some(err) => Err(ApiError::CannotStartGame(
+            serde_json::to_value(err)
+                .ok()
+                .and_then(|err| err.as_str().map(ToOwned::to_owned))
+                .unwrap_or_default(),
+        )),
+    }
+}
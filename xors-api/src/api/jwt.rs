@@ -14,7 +14,11 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{str::FromStr, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
 
 use crate::{
     db_utils,
@@ -25,19 +29,49 @@ use crate::{
 
 use base64::Engine;
 use chrono::Duration;
-use salvo::{oapi::extract::JsonBody, prelude::*};
-use salvo_captcha::{
-    CacacheStorage, CaptchaDepotExt, CaptchaDifficulty, CaptchaGenerator, CaptchaName,
+use salvo::{
+    jwt_auth::JwtAuthDecoder,
+    oapi::extract::{JsonBody, QueryParam},
+    prelude::*,
 };
+use salvo_captcha::CaptchaStorage;
+use sea_orm::IntoActiveModel;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::exts::UserExt;
+use super::{
+    audit::AuditEvent,
+    captcha_storage::{CaptchaAnswer, CaptchaAnswerStorage},
+    exts::UserExt,
+    metrics::METRICS,
+    risk::CaptchaGuard,
+    webhook,
+};
+
+/// The captcha characters count, if it's not specified.
+pub const DEFAULT_CAPTCHA_LENGTH: u8 = 5;
 
+/// The maximum number of usernames that can be checked at once.
+pub const MAX_USERNAMES_CHECK: usize = 10;
+
+#[allow(clippy::too_many_arguments)]
 #[derive(Debug, Serialize, Deserialize, derive_new::new)]
 pub struct JwtClaims {
     /// The user's uuid.
     pub uuid: Uuid,
+    /// The token's unique identifier, used to revoke it.
+    pub jti: Uuid,
+    /// The user's role when the token issued.
+    #[serde(default)]
+    pub role: UserRole,
+    /// The user's tokens version when the token issued, see `revoke_user_tokens`.
+    #[serde(default)]
+    pub ver: i32,
+    /// The token's issue date.
+    pub iat: i64,
+    /// The last password authentication date, the refreshed tokens keep it.
+    #[serde(default)]
+    pub auth_time: i64,
     /// The refresh token activate date.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub active_after: Option<i64>,
@@ -57,28 +91,504 @@ impl JwtClaims {
     }
 }
 
+/// The lifetimes of the issued tokens.
+#[derive(Debug, Clone, Copy)]
+pub struct TokenLifetimes {
+    /// The JWT token lifetime.
+    pub jwt: Duration,
+    /// The refresh token lifetime, when the user signin with `remember_me`.
+    pub refresh: Duration,
+    /// The refresh token lifetime, when the user signin without `remember_me`.
+    pub short_refresh: Duration,
+    /// The period after issuing the refresh token that it can't be used in.
+    pub refresh_active_after: Duration,
+    /// The period after consuming the refresh token that it can be presented again once, to get the same
+    /// successor tokens, so the concurrent refreshes of the same client are not detected as a reuse.
+    pub refresh_grace_period: Duration,
+}
+
+impl TokenLifetimes {
+    /// Returns the refresh token lifetime of the session.
+    pub fn refresh_lifetime(&self, remember_me: bool) -> Duration {
+        if remember_me {
+            self.refresh
+        } else {
+            self.short_refresh
+        }
+    }
+
+    /// Checks that the lifetimes make sense, the JWT token can't outlive the refresh tokens and the refresh tokens
+    /// must be active before they expire.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.jwt <= Duration::zero() {
+            return Err("The JWT token lifetime must be positive".to_owned());
+        }
+        if self.jwt > self.short_refresh {
+            return Err("The JWT token can't outlive the short refresh token".to_owned());
+        }
+        if self.short_refresh > self.refresh {
+            return Err("The short refresh token can't outlive the refresh token".to_owned());
+        }
+        if self.refresh_active_after >= self.short_refresh {
+            return Err("The refresh tokens must be active before they expire".to_owned());
+        }
+        if self.refresh_grace_period < Duration::zero() {
+            return Err("The refresh token grace period can't be negative".to_owned());
+        }
+        Ok(())
+    }
+}
+
+impl Default for TokenLifetimes {
+    fn default() -> Self {
+        Self {
+            jwt: Duration::hours(1),
+            refresh: Duration::hours(3),
+            short_refresh: Duration::minutes(90),
+            refresh_active_after: Duration::minutes(58),
+            refresh_grace_period: Duration::seconds(10),
+        }
+    }
+}
+
+/// The algorithm that the tokens signed with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JwtAlgorithm {
+    /// HMAC with SHA-256, the tokens are signed and validated with the shared secret key.
+    #[default]
+    HS256,
+    /// RSA with SHA-256, the tokens are signed with the private key and the public key is published in the
+    /// JWKS endpoint, so other services can validate the tokens.
+    RS256,
+    /// Ed25519, the tokens are signed with the private key and the public key is published in the JWKS
+    /// endpoint, like the RS256 algorithm.
+    EdDSA,
+}
+
+/// A key that the tokens signed and validated with.
+#[derive(Clone)]
+pub struct JwtKey {
+    algorithm: JwtAlgorithm,
+    kid: String,
+    encoding_key: jsonwebtoken::EncodingKey,
+    decoding_key: jsonwebtoken::DecodingKey,
+    /// The public key, only with the asymmetric algorithms.
+    jwk: Option<JwkSchema>,
+}
+
+/// The key set that the tokens signed and validated with.
+///
+/// The tokens are signed with the active key, and validated with the key of their `kid` header, the active key
+/// or one of the retired keys. So a rotated key keeps validating the issued tokens until they expire.
+/// The clones share the same key set, the rotation is visible to all of them.
+#[derive(Clone)]
+pub struct JwtKeys {
+    keys: Arc<RwLock<JwtKeySet>>,
+    /// The private key file of the active key and its algorithm, the key is reloaded from it by the rotation.
+    source: Option<(JwtAlgorithm, PathBuf)>,
+}
+
+/// The active key and the retired keys.
+struct JwtKeySet {
+    active: JwtKey,
+    /// The retired keys and the timestamp that they stop validating the tokens in, the previous keys of the
+    /// configuration have no end.
+    retired: Vec<(JwtKey, Option<i64>)>,
+}
+
+impl FromStr for JwtAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "HS256" => Ok(Self::HS256),
+            "RS256" => Ok(Self::RS256),
+            "EdDSA" => Ok(Self::EdDSA),
+            _ => Err(format!(
+                "Invalid JWT algorithm `{s}`, expected `HS256`, `RS256` or `EdDSA`"
+            )),
+        }
+    }
+}
+
+impl From<JwtAlgorithm> for jsonwebtoken::Algorithm {
+    fn from(algorithm: JwtAlgorithm) -> Self {
+        match algorithm {
+            JwtAlgorithm::HS256 => Self::HS256,
+            JwtAlgorithm::RS256 => Self::RS256,
+            JwtAlgorithm::EdDSA => Self::EdDSA,
+        }
+    }
+}
+
+impl JwtKey {
+    /// The minimum size of the RSA key in bits.
+    pub const MIN_RSA_KEY_BITS: usize = 2048;
+
+    /// Create a HS256 key from the shared secret key.
+    pub fn hs256(secret_key: &str) -> Self {
+        use sha2::Digest;
+
+        // The key id is a truncated hash of the secret, it's not published
+        let kid = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(sha2::Sha256::digest(format!("xors-hs256:{secret_key}")))[..16]
+            .to_owned();
+        Self {
+            algorithm: JwtAlgorithm::HS256,
+            kid,
+            encoding_key: jsonwebtoken::EncodingKey::from_secret(secret_key.as_bytes()),
+            decoding_key: jsonwebtoken::DecodingKey::from_secret(secret_key.as_bytes()),
+            jwk: None,
+        }
+    }
+
+    /// Create a RS256 key from a PEM encoded (PKCS#1 or PKCS#8) RSA private key.
+    pub fn rs256(private_key_pem: &str) -> Result<Self, String> {
+        use rsa::{pkcs1::DecodeRsaPrivateKey, pkcs8::DecodePrivateKey, traits::PublicKeyParts};
+        use sha2::Digest;
+
+        let private_key = rsa::RsaPrivateKey::from_pkcs8_pem(private_key_pem)
+            .or_else(|_| rsa::RsaPrivateKey::from_pkcs1_pem(private_key_pem))
+            .map_err(|err| format!("Invalid RSA private key: {err}"))?;
+        if private_key.size() * 8 < Self::MIN_RSA_KEY_BITS {
+            return Err(format!(
+                "The RSA private key must be at least {} bits",
+                Self::MIN_RSA_KEY_BITS
+            ));
+        }
+        let encoding_key = jsonwebtoken::EncodingKey::from_rsa_pem(private_key_pem.as_bytes())
+            .map_err(|err| format!("Invalid RSA private key: {err}"))?;
+
+        let (modulus, exponent) = (private_key.n().to_bytes_be(), private_key.e().to_bytes_be());
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let (n, e) = (engine.encode(&modulus), engine.encode(&exponent));
+        // The key id is the JWK thumbprint (RFC 7638)
+        let kid = engine.encode(sha2::Sha256::digest(format!(
+            r#"{{"e":"{e}","kty":"RSA","n":"{n}"}}"#
+        )));
+
+        Ok(Self {
+            algorithm: JwtAlgorithm::RS256,
+            kid: kid.clone(),
+            encoding_key,
+            decoding_key: jsonwebtoken::DecodingKey::from_rsa_raw_components(&modulus, &exponent),
+            jwk: Some(JwkSchema {
+                kty: "RSA".to_owned(),
+                use_: "sig".to_owned(),
+                alg: "RS256".to_owned(),
+                kid,
+                n: Some(n),
+                e: Some(e),
+                crv: None,
+                x: None,
+            }),
+        })
+    }
+
+    /// Create an EdDSA key from a PEM encoded (PKCS#8) Ed25519 private key.
+    pub fn eddsa(private_key_pem: &str) -> Result<Self, String> {
+        use ed25519_dalek::pkcs8::DecodePrivateKey;
+        use sha2::Digest;
+
+        let private_key = ed25519_dalek::SigningKey::from_pkcs8_pem(private_key_pem)
+            .map_err(|err| format!("Invalid Ed25519 private key: {err}"))?;
+        let encoding_key = jsonwebtoken::EncodingKey::from_ed_pem(private_key_pem.as_bytes())
+            .map_err(|err| format!("Invalid Ed25519 private key: {err}"))?;
+
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let x = engine.encode(private_key.verifying_key().as_bytes());
+        // The key id is the JWK thumbprint (RFC 7638)
+        let kid = engine.encode(sha2::Sha256::digest(format!(
+            r#"{{"crv":"Ed25519","kty":"OKP","x":"{x}"}}"#
+        )));
+
+        Ok(Self {
+            algorithm: JwtAlgorithm::EdDSA,
+            kid: kid.clone(),
+            encoding_key,
+            decoding_key: jsonwebtoken::DecodingKey::from_ed_der(
+                private_key.verifying_key().as_bytes(),
+            ),
+            jwk: Some(JwkSchema {
+                kty: "OKP".to_owned(),
+                use_: "sig".to_owned(),
+                alg: "EdDSA".to_owned(),
+                kid,
+                n: None,
+                e: None,
+                crv: Some("Ed25519".to_owned()),
+                x: Some(x),
+            }),
+        })
+    }
+
+    /// Create a key from a PEM encoded private key, the algorithm is detected from the key.
+    pub fn from_pem(private_key_pem: &str) -> Result<Self, String> {
+        Self::rs256(private_key_pem).or_else(|rsa_err| {
+            Self::eddsa(private_key_pem).map_err(|ed_err| format!("{rsa_err}, {ed_err}"))
+        })
+    }
+
+    /// Returns the key algorithm.
+    pub fn algorithm(&self) -> JwtAlgorithm {
+        self.algorithm
+    }
+
+    /// Returns the key id.
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+}
+
+impl JwtKeys {
+    /// Create a key set with the active key.
+    pub fn new(active: JwtKey) -> Self {
+        Self {
+            keys: Arc::new(RwLock::new(JwtKeySet {
+                active,
+                retired: Vec::new(),
+            })),
+            source: None,
+        }
+    }
+
+    /// Create a key set with a HS256 key from the shared secret key.
+    pub fn hs256(secret_key: &str) -> Self {
+        Self::new(JwtKey::hs256(secret_key))
+    }
+
+    /// Create a key set with a RS256 key from a PEM encoded (PKCS#1 or PKCS#8) RSA private key.
+    pub fn rs256(private_key_pem: &str) -> Result<Self, String> {
+        JwtKey::rs256(private_key_pem).map(Self::new)
+    }
+
+    /// Create a key set with an EdDSA key from a PEM encoded (PKCS#8) Ed25519 private key.
+    pub fn eddsa(private_key_pem: &str) -> Result<Self, String> {
+        JwtKey::eddsa(private_key_pem).map(Self::new)
+    }
+
+    /// Create a key set with the key of the private key file, the key can be rotated by replacing the file
+    /// and calling [`JwtKeys::reload`].
+    pub fn from_file(algorithm: JwtAlgorithm, path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        Ok(Self {
+            source: Some((algorithm, path.clone())),
+            ..Self::new(read_key_file(algorithm, &path)?)
+        })
+    }
+
+    /// Adds the previous keys, they validate the tokens signed with them and they are published in the JWKS
+    /// endpoint, until they are removed from the configuration.
+    pub fn with_previous(self, previous: Vec<JwtKey>) -> Self {
+        self.write()
+            .retired
+            .extend(previous.into_iter().map(|key| (key, None)));
+        self
+    }
+
+    /// Returns the algorithm of the active key.
+    pub fn algorithm(&self) -> JwtAlgorithm {
+        self.read().active.algorithm
+    }
+
+    /// Returns the key id of the active key.
+    pub fn kid(&self) -> String {
+        self.read().active.kid.clone()
+    }
+
+    /// Signs the claims with the active key, the token has its key id.
+    pub fn encode<T: Serialize>(&self, claims: &T) -> jsonwebtoken::errors::Result<String> {
+        let keys = self.read();
+        let header = jsonwebtoken::Header {
+            kid: Some(keys.active.kid.clone()),
+            ..jsonwebtoken::Header::new(keys.active.algorithm.into())
+        };
+        jsonwebtoken::encode(&header, &claims, &keys.active.encoding_key)
+    }
+
+    /// Validates the token with the key of its key id, the tokens without a key id are validated with the keys
+    /// of their algorithm.
+    pub fn decode<C: serde::de::DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> jsonwebtoken::errors::Result<jsonwebtoken::TokenData<C>> {
+        let header = jsonwebtoken::decode_header(token)?;
+        let keys = self.read();
+        let now = chrono::Utc::now().timestamp();
+        let mut result = Err(jsonwebtoken::errors::ErrorKind::InvalidSignature.into());
+        for key in std::iter::once(&keys.active).chain(
+            keys.retired
+                .iter()
+                .filter(|(_, until)| until.map_or(true, |until| until > now))
+                .map(|(key, _)| key),
+        ) {
+            let matches = match &header.kid {
+                Some(kid) => kid == &key.kid,
+                None => jsonwebtoken::Algorithm::from(key.algorithm) == header.alg,
+            };
+            if matches {
+                result = jsonwebtoken::decode::<C>(
+                    token,
+                    &key.decoding_key,
+                    &jsonwebtoken::Validation::new(key.algorithm.into()),
+                );
+                if result.is_ok() {
+                    break;
+                }
+            }
+        }
+        result
+    }
+
+    /// Makes the key the active key, the previous active key is retired for `retire_period`, it's the lifetime
+    /// of the longest issued token. Returns the retired key id and its end, if the key is changed.
+    pub fn rotate(&self, key: JwtKey, retire_period: Duration) -> Option<(String, i64)> {
+        let mut keys = self.write();
+        let now = chrono::Utc::now().timestamp();
+        keys.retired.retain(|(retired, until)| {
+            until.map_or(true, |until| until > now) && retired.kid != key.kid
+        });
+        if keys.active.kid == key.kid {
+            return None;
+        }
+        let until = now + retire_period.num_seconds();
+        let retired = std::mem::replace(&mut keys.active, key);
+        let retired_kid = retired.kid.clone();
+        keys.retired.push((retired, Some(until)));
+        log::info!(
+            "The tokens key is rotated to `{}`, the key `{retired_kid}` is retired",
+            keys.active.kid
+        );
+        Some((retired_kid, until))
+    }
+
+    /// Reloads the active key from the private key file, and rotates it if it's changed.
+    ///
+    /// Returns an error if the key set is not loaded from a file or the file has an invalid key.
+    pub fn reload(&self, retire_period: Duration) -> Result<Option<(String, i64)>, String> {
+        let (algorithm, path) = self
+            .source
+            .as_ref()
+            .ok_or_else(|| "The tokens key is not loaded from a file".to_owned())?;
+        Ok(self.rotate(read_key_file(*algorithm, path)?, retire_period))
+    }
+
+    /// Returns the published public keys, the active key and the retired keys. The HS256 keys are not published.
+    pub fn jwks(&self) -> JwksSchema {
+        let keys = self.read();
+        let now = chrono::Utc::now().timestamp();
+        JwksSchema {
+            keys: std::iter::once(&keys.active)
+                .chain(
+                    keys.retired
+                        .iter()
+                        .filter(|(_, until)| until.map_or(true, |until| until > now))
+                        .map(|(key, _)| key),
+                )
+                .filter_map(|key| key.jwk.clone())
+                .collect(),
+        }
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, JwtKeySet> {
+        self.keys.read().expect("The lock is not poisoned")
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, JwtKeySet> {
+        self.keys.write().expect("The lock is not poisoned")
+    }
+}
+
+impl JwtAuthDecoder for JwtKeys {
+    type Error = jsonwebtoken::errors::Error;
+
+    async fn decode<C>(
+        &self,
+        token: &str,
+        _: &mut Depot,
+    ) -> Result<jsonwebtoken::TokenData<C>, Self::Error>
+    where
+        C: for<'de> Deserialize<'de>,
+    {
+        JwtKeys::decode(self, token)
+    }
+}
+
+/// Reads the private key of the algorithm from the file.
+fn read_key_file(algorithm: JwtAlgorithm, path: &Path) -> Result<JwtKey, String> {
+    let private_key = std::fs::read_to_string(path).map_err(|err| {
+        format!(
+            "Failed to read the JWT private key `{}`: {err}",
+            path.display()
+        )
+    })?;
+    match algorithm {
+        JwtAlgorithm::HS256 => Ok(JwtKey::hs256(private_key.trim())),
+        JwtAlgorithm::RS256 => JwtKey::rs256(&private_key),
+        JwtAlgorithm::EdDSA => JwtKey::eddsa(&private_key),
+    }
+    .map_err(|err| format!("Invalid JWT private key `{}`: {err}", path.display()))
+}
+
 /// Create a new captcha.
 ///
 /// This endpoint will create a new captcha and return the captcha token and the captcha image as base64.
 /// - The token are valid for 5 minutes.
 /// - The token can only be used one time correctly.
 /// - If the token is used incorrectly, it will not be deleted and can be used again until it expires.
+/// - The answer of the easy captcha is case-insensitive.
 #[endpoint(
     operation_id = "create_captcha",
     tags("Auth"),
+    parameters(
+        ("difficulty" = Option<CaptchaDifficulty>, Query, description = "The captcha difficulty, default is `medium`"),
+        ("length" = Option<u8>, Query, description = "The captcha characters count, between 4 and 7, default is `5`"),
+    ),
     responses(
         (status_code = 200, description = "Captcha created", content_type = "application/json", body = CaptchaSchema),
+        (status_code = 400, description = "Invalid captcha difficulty", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "Invalid captcha length", content_type = "application/json", body = MessageSchema),
         (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
         (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
     )
 )]
-pub async fn captcha(depot: &mut Depot) -> ApiResult<Json<CaptchaSchema>> {
-    let captcha_storage = depot.obtain::<Arc<CacacheStorage>>().unwrap();
-    let (captcha_token, captcha_image) = captcha_storage
-        .new_captcha(CaptchaName::Amelia, CaptchaDifficulty::Medium)
+pub async fn captcha(
+    depot: &mut Depot,
+    difficulty: QueryParam<String, false>,
+    length: QueryParam<String, false>,
+) -> ApiResult<Json<CaptchaSchema>> {
+    let captcha_storage = depot.obtain::<Arc<CaptchaAnswerStorage>>().unwrap();
+    let captcha_guard = depot.obtain::<Arc<CaptchaGuard>>().unwrap();
+    // Parsed here, the invalid query values are rejected instead of ignored
+    let difficulty = difficulty
+        .into_inner()
+        .map(|difficulty| CaptchaDifficulty::from_str(&difficulty))
+        .transpose()?
+        .unwrap_or_default();
+    let length = length
+        .into_inner()
+        .map(|length| length.parse().map_err(|_| ApiError::InvalidCaptchaLength))
+        .transpose()?
+        .unwrap_or(DEFAULT_CAPTCHA_LENGTH);
+
+    let started_at = std::time::Instant::now();
+    let generated = utils::generate_captcha(difficulty, length)?;
+    METRICS.captcha_generated(started_at.elapsed());
+    let Some((captcha_answer, captcha_image)) = generated else {
+        captcha_guard.captcha_failed();
+        return Err(ApiError::InternalServer);
+    };
+    let Ok(captcha_token) = captcha_storage
+        .store_answer(CaptchaAnswer::new(
+            captcha_answer,
+            difficulty != CaptchaDifficulty::Easy,
+        ))
         .await
-        .map_err(|_| ApiError::InternalServer)?
-        .ok_or(ApiError::InternalServer)?;
+    else {
+        captcha_guard.captcha_failed();
+        return Err(ApiError::InternalServer);
+    };
+    captcha_guard.captcha_succeeded();
 
     Ok(Json(CaptchaSchema {
         captcha_token: Uuid::from_str(&captcha_token)
@@ -87,10 +597,81 @@ pub async fn captcha(depot: &mut Depot) -> ApiResult<Json<CaptchaSchema>> {
             "data:image/png;base64,{}",
             crate::BASE_64_ENGINE.encode(captcha_image)
         ),
+        difficulty,
         expired_at: chrono::Utc::now().naive_utc() + Duration::minutes(5),
     }))
 }
 
+/// Preview a captcha.
+///
+/// This endpoint will create a new captcha and return the captcha image as base64 and its answer, for the UI development.
+/// - The captcha is not stored, it can't be used to signup or signin.
+/// - The endpoint is available only if the server enables `XORS_API_CAPTCHA_PREVIEW`, which is refused in the release builds.
+#[endpoint(
+    operation_id = "preview_captcha",
+    tags("Auth"),
+    responses(
+        (status_code = 200, description = "Captcha created", content_type = "application/json", body = CaptchaPreviewSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+    )
+)]
+pub async fn captcha_preview() -> ApiResult<Json<CaptchaPreviewSchema>> {
+    log::warn!("The captcha preview endpoint is hit, the captcha is not stored");
+
+    let Some((captcha_answer, captcha_image)) =
+        utils::generate_captcha(CaptchaDifficulty::Medium, DEFAULT_CAPTCHA_LENGTH)?
+    else {
+        return Err(ApiError::InternalServer);
+    };
+
+    Ok(Json(CaptchaPreviewSchema {
+        captcha_image: format!(
+            "data:image/png;base64,{}",
+            crate::BASE_64_ENGINE.encode(captcha_image)
+        ),
+        captcha_answer,
+    }))
+}
+
+/// Get the captcha provider.
+///
+/// This endpoint will return the captcha that the signup, signin and guest upgrade requests must solve.
+/// - The `image` captcha is created with `/auth/captcha`, and sent with the `X-Captcha-Token` and `X-Captcha-Answer` headers.
+/// - The `hcaptcha` and `recaptcha` widgets are rendered with the site key, and their response token is sent with the `X-Captcha-Response` header.
+#[endpoint(
+    operation_id = "get_captcha_provider",
+    tags("Auth"),
+    responses(
+        (status_code = 200, description = "The captcha provider", content_type = "application/json", body = CaptchaProviderSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    )
+)]
+pub async fn captcha_provider(depot: &mut Depot) -> Json<CaptchaProviderSchema> {
+    let provider = depot.obtain::<Arc<CaptchaGuard>>().unwrap().provider();
+
+    Json(CaptchaProviderSchema::new(
+        provider.kind(),
+        provider.site_key().map(ToOwned::to_owned),
+    ))
+}
+
+/// Get the tokens public keys.
+///
+/// This endpoint will return the JSON web key set that the tokens can be validated with, so other services can
+/// validate the tokens without the secret. The set is empty when the server signs the tokens with the shared secret (HS256).
+///
+/// **Note**: This endpoint is not rate limited.
+#[endpoint(
+    operation_id = "get_jwks",
+    tags("Auth"),
+    responses(
+        (status_code = 200, description = "The public keys", content_type = "application/json", body = JwksSchema),
+    )
+)]
+pub async fn jwks(depot: &mut Depot) -> Json<JwksSchema> {
+    Json(depot.obtain::<Arc<JwtKeys>>().unwrap().jwks())
+}
+
 /// Sign up a new user.
 ///
 /// This endpoint will create a new user and return a JWT token.
@@ -113,30 +694,162 @@ pub async fn captcha(depot: &mut Depot) -> ApiResult<Json<CaptchaSchema>> {
         (status_code = 200, description = "User created", content_type = "application/json", body = UserSigninSchema),
         (status_code = 400, description = "Username already exists", content_type = "application/json", body = MessageSchema),
         (status_code = 400, description = "Unprovided captcha token or answer", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "Unprovided captcha response", content_type = "application/json", body = MessageSchema),
         (status_code = 403, description = "Invalid captcha token", content_type = "application/json", body = MessageSchema),
         (status_code = 403, description = "Invalid captcha answer", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "Invalid captcha response", content_type = "application/json", body = MessageSchema),
         (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
         (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
     )
 )]
 pub async fn signup(
+    req: &mut Request,
     depot: &mut Depot,
     new_user: JsonBody<NewUserSchema>,
 ) -> ApiResult<Json<UserSigninSchema>> {
     let conn = depot.obtain::<Arc<sea_orm::DatabaseConnection>>().unwrap();
-    let secret_key = depot.get::<Arc<String>>("secret_key").unwrap();
+    let jwt_keys = depot.obtain::<Arc<JwtKeys>>().unwrap();
     let user = new_user.into_inner();
 
-    utils::handle_captcha_state(
-        depot
-            .get_captcha_state()
-            .expect("This route is protected by the `salvo_captcha` middleware"),
+    depot
+        .obtain::<Arc<CaptchaGuard>>()
+        .unwrap()
+        .verify(req, depot)
+        .await?;
+    utils::validate_user_registration(
+        &user,
+        depot.obtain::<Arc<utils::PasswordPolicy>>().unwrap(),
     )?;
-    utils::validate_user_registration(&user)?;
 
+    let user = db_utils::create_user(conn.as_ref(), user).await?;
+    METRICS.user_signed_up();
+    webhook::dispatch(conn, WebhookEvent::UserCreated, &user);
+    db_utils::signin_user(
+        conn,
+        user,
+        jwt_keys,
+        depot.obtain::<Arc<TokenLifetimes>>().unwrap(),
+        true,
+    )
+    .await
+    .map(Json)
+}
+
+/// Play as a guest.
+///
+/// This endpoint will create a guest user and return a JWT token with a short refresh token. The guest plays the
+/// unranked games only, and it can't join the tournaments. Upgrade it to a full account with the `/auth/upgrade`
+/// endpoint, otherwise it's deleted after 7 days without games. The captcha is required like the `/auth/signup`
+/// endpoint.
+/// - `captcha_token`: The captcha token. Get it from the `/auth/captcha` endpoint.
+/// - `captcha_answer`: The captcha answer. The text that in the captcha image.
+#[endpoint(
+    operation_id = "signin_guest",
+    tags("Auth"),
+    parameters(
+        ("X-Captcha-Token" = String, Header, description = "The captcha token, get it from the `/auth/captcha` endpoint"),
+        ("X-Captcha-Answer" = String, Header, description = "The captcha answer, the text that in the captcha image"),
+    ),
+    responses(
+        (status_code = 200, description = "Guest user created", content_type = "application/json", body = UserSigninSchema),
+        (status_code = 400, description = "Unprovided captcha token or answer", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "Unprovided captcha response", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "Invalid captcha token", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "Invalid captcha answer", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "Invalid captcha response", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    )
+)]
+pub async fn signin_guest(
+    req: &mut Request,
+    depot: &mut Depot,
+) -> ApiResult<Json<UserSigninSchema>> {
+    let conn = depot.obtain::<Arc<sea_orm::DatabaseConnection>>().unwrap();
+
+    depot
+        .obtain::<Arc<CaptchaGuard>>()
+        .unwrap()
+        .verify(req, depot)
+        .await?;
+
+    let guest = db_utils::create_guest(conn.as_ref()).await?;
+    webhook::dispatch(conn, WebhookEvent::UserCreated, &guest);
     db_utils::signin_user(
-        db_utils::create_user(conn.as_ref(), user).await?,
-        secret_key,
+        conn,
+        guest,
+        depot.obtain::<Arc<JwtKeys>>().unwrap(),
+        depot.obtain::<Arc<TokenLifetimes>>().unwrap(),
+        false,
+    )
+    .await
+    .map(Json)
+}
+
+/// Upgrade the guest user.
+///
+/// This endpoint will convert the guest user of the JWT token to a full account with the new credentials, and return
+/// a JWT token with a refresh token. The guest's games are kept, the new user data are validated like the `/auth/signup`
+/// endpoint.
+#[endpoint(
+    operation_id = "upgrade_guest",
+    tags("Auth"),
+    request_body(
+        content = NewUserSchema,
+        description = "The new user data",
+        example = json!(NewUserSchema::default()),
+        content_type = "application/json",
+    ),
+    parameters(
+        ("X-Captcha-Token" = String, Header, description = "The captcha token, get it from the `/auth/captcha` endpoint"),
+        ("X-Captcha-Answer" = String, Header, description = "The captcha answer, the text that in the captcha image"),
+    ),
+    responses(
+        (status_code = 200, description = "The guest has been upgraded", content_type = "application/json", body = UserSigninSchema),
+        (status_code = 400, description = "Username already exists", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "Unprovided captcha token or answer", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "Unprovided captcha response", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is revoked", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "Invalid captcha token", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "Invalid captcha answer", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "Invalid captcha response", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "The user is not a guest", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn upgrade_guest(
+    req: &mut Request,
+    depot: &mut Depot,
+    new_user: JsonBody<NewUserSchema>,
+) -> ApiResult<Json<UserSigninSchema>> {
+    let conn = depot.obtain::<Arc<sea_orm::DatabaseConnection>>().unwrap();
+    let new_user = new_user.into_inner();
+
+    depot
+        .obtain::<Arc<CaptchaGuard>>()
+        .unwrap()
+        .verify(req, depot)
+        .await?;
+    let guest = depot.user(conn.as_ref()).await?;
+    utils::validate_user_registration(
+        &new_user,
+        depot.obtain::<Arc<utils::PasswordPolicy>>().unwrap(),
+    )?;
+
+    let user = db_utils::upgrade_guest(conn.as_ref(), guest, new_user).await?;
+    METRICS.user_signed_up();
+    db_utils::signin_user(
+        conn,
+        user,
+        depot.obtain::<Arc<JwtKeys>>().unwrap(),
+        depot.obtain::<Arc<TokenLifetimes>>().unwrap(),
+        true,
     )
     .await
     .map(Json)
@@ -145,6 +858,8 @@ pub async fn signup(
 /// Signin a user.
 ///
 /// This endpoint will return a JWT token with a refresh token.
+/// - `totp_code`: The two-factor authentication code, required if the user enabled it. A recovery code is accepted
+///   instead of it.
 #[endpoint(
     operation_id = "signin_user",
     tags("Auth"),
@@ -162,47 +877,98 @@ pub async fn signup(
         (status_code = 200, description = "User signed in", content_type = "application/json", body = UserSigninSchema),
         (status_code = 400, description = "Invalid username or password", content_type = "application/json", body = MessageSchema),
         (status_code = 400, description = "Unprovided captcha token or answer", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "Unprovided captcha response", content_type = "application/json", body = MessageSchema),
         (status_code = 403, description = "Invalid captcha token", content_type = "application/json", body = MessageSchema),
         (status_code = 403, description = "Invalid captcha answer", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "Invalid captcha response", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The two-factor authentication code is required", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The two-factor authentication code is incorrect", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is banned", content_type = "application/json", body = MessageSchema),
         (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
         (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
     )
 )]
 pub async fn signin(
+    req: &mut Request,
     depot: &mut Depot,
     signin_schema: JsonBody<SigninSchema>,
 ) -> ApiResult<Json<UserSigninSchema>> {
     let conn = depot.obtain::<Arc<sea_orm::DatabaseConnection>>().unwrap();
-    let secret_key = depot.get::<Arc<String>>("secret_key").unwrap();
+    let jwt_keys = depot.obtain::<Arc<JwtKeys>>().unwrap();
     let signin_schema = signin_schema.into_inner();
 
-    utils::handle_captcha_state(
-        depot
-            .get_captcha_state()
-            .expect("This route is protected by the `salvo_captcha` middleware"),
-    )?;
+    depot
+        .obtain::<Arc<CaptchaGuard>>()
+        .unwrap()
+        .verify(req, depot)
+        .await?;
 
-    utils::validate_password(&signin_schema.password)?;
-    utils::validate_user_signin(&signin_schema.username)?;
+    utils::validate_signin_password(&signin_schema.password)?;
+    utils::validate_user_signin(signin_schema.username.trim())?;
 
+    let mut failed_signin =
+        AuditEvent::new(AuditAction::FailedSignin, req, depot).target(&signin_schema.username);
     if let Ok(user) = db_utils::get_user_by_username(conn.as_ref(), signin_schema.username).await {
-        if bcrypt::verify(&signin_schema.password, user.password_hash.as_ref()).unwrap_or_default()
+        failed_signin = failed_signin.actor(*user.uuid.as_ref());
+        let hasher = utils::PasswordHasher::current();
+        if hasher
+            .verify(&signin_schema.password, user.password_hash.as_ref())
+            .unwrap_or_default()
         {
+            // The old bcrypt hashes are migrated to Argon2id without a password reset
+            let user = if hasher.needs_rehash(user.password_hash.as_ref()) {
+                db_utils::rehash_password(conn.as_ref(), user, &signin_schema.password).await?
+            } else {
+                user
+            };
+            if user.banned_at.as_ref().is_some() {
+                return Err(ApiError::BannedUser);
+            }
+            if *user.totp_enabled.as_ref() {
+                let code = signin_schema.totp_code.ok_or(ApiError::TotpRequired)?;
+                let secret = user
+                    .totp_secret
+                    .as_ref()
+                    .as_deref()
+                    .expect("The enabled two-factor authentication has a secret");
+                if !db_utils::use_totp_code(conn.as_ref(), *user.uuid.as_ref(), secret, &code)
+                    .await?
+                    && !db_utils::use_recovery_code(conn.as_ref(), *user.uuid.as_ref(), &code)
+                        .await?
+                {
+                    METRICS.signin_failed(true);
+                    failed_signin.record(conn).await;
+                    return Err(ApiError::InvalidTotpCode);
+                }
+            }
+            AuditEvent::new(AuditAction::Signin, req, depot)
+                .actor(*user.uuid.as_ref())
+                .record(conn)
+                .await;
             return db_utils::signin_user(
+                conn,
                 UserSchema::from_active_model(conn, user).await?,
-                secret_key,
+                jwt_keys,
+                depot.obtain::<Arc<TokenLifetimes>>().unwrap(),
+                signin_schema.remember_me.unwrap_or(true),
             )
             .await
             .map(Json);
         }
     }
+    METRICS.signin_failed(false);
+    failed_signin.record(conn).await;
     Err(ApiError::InvalidSigninCredentials)
 }
 
 /// Refresh a JWT token.
 ///
-/// This endpoint will return a new JWT token with the refresh token.
+/// This endpoint will return a new JWT token with a new refresh token, the used refresh token can't be used again.
 /// Note: You need to authorize with the refresh token to get a new JWT token.
+///
+/// **Note**: If a used refresh token is presented again, all the user tokens are revoked, so a stolen refresh token
+/// can't be used along with the legitimate one. Except once in the refresh grace period after using it, it gets the
+/// same new tokens, so the concurrent refreshes of the same client don't revoke its sessions.
 #[endpoint(
     operation_id = "refresh_token",
     tags("Auth"),
@@ -211,6 +977,8 @@ pub async fn signin(
         (status_code = 400, description = "The token is not a refresh token", content_type = "application/json", body = MessageSchema),
         (status_code = 403, description = "The refresh token is not active yet", content_type = "application/json", body = MessageSchema),
         (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is revoked", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The refresh token is already used", content_type = "application/json", body = MessageSchema),
         (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
         (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
         (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
@@ -218,25 +986,32 @@ pub async fn signin(
     ),
     security(("bearerAuth" = [])),
 )]
-pub async fn refresh(depot: &mut Depot) -> ApiResult<Json<UserSigninSchema>> {
+pub async fn refresh(req: &mut Request, depot: &mut Depot) -> ApiResult<Json<UserSigninSchema>> {
     let conn = depot.obtain::<Arc<sea_orm::DatabaseConnection>>().unwrap();
-    let secret_key = depot.get::<Arc<String>>("secret_key").unwrap();
+    let jwt_keys = depot.obtain::<Arc<JwtKeys>>().unwrap();
 
     // Note: The `Unauthorized` and `Forbidden` errors are handled by the `JwtAuth` middleware.
     let refresh_token = depot.jwt_claims();
     if let Some(active_after) = refresh_token.active_after {
         if !refresh_token.is_expired() {
             if active_after <= chrono::Utc::now().timestamp() {
-                db_utils::signin_user(
+                let tokens = db_utils::consume_refresh_token(
+                    conn.as_ref(),
+                    refresh_token,
                     UserSchema::from_active_model(
                         conn,
                         db_utils::get_user(conn.as_ref(), refresh_token.uuid).await?,
                     )
                     .await?,
-                    secret_key,
+                    jwt_keys,
+                    depot.obtain::<Arc<TokenLifetimes>>().unwrap(),
                 )
-                .await
-                .map(Json)
+                .await?;
+                AuditEvent::new(AuditAction::TokenRefresh, req, depot)
+                    .actor(refresh_token.uuid)
+                    .record(conn)
+                    .await;
+                Ok(Json(tokens))
             } else {
                 Err(ApiError::UnActiveRefreshToken)
             }
@@ -247,3 +1022,350 @@ pub async fn refresh(depot: &mut Depot) -> ApiResult<Json<UserSigninSchema>> {
         Err(ApiError::NotRefreshToken)
     }
 }
+
+/// Get the refresh token info.
+///
+/// This endpoint will return the refresh token activate date and expiration date, and the seconds until each of them,
+/// without refreshing the token, so the client can schedule the refresh.
+/// Note: You need to authorize with the refresh token, the not active yet refresh tokens are accepted.
+#[endpoint(
+    operation_id = "refresh_token_info",
+    tags("Auth"),
+    responses(
+        (status_code = 200, description = "The refresh token info", content_type = "application/json", body = RefreshTokenInfoSchema),
+        (status_code = 400, description = "The token is not a refresh token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is revoked", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn refresh_info(depot: &mut Depot) -> ApiResult<Json<RefreshTokenInfoSchema>> {
+    let refresh_token = depot.jwt_claims();
+    let Some(active_after) = refresh_token.active_after else {
+        return Err(ApiError::NotRefreshToken);
+    };
+    if refresh_token.is_expired() {
+        return Err(ApiError::ExpiredToken);
+    }
+
+    Ok(Json(RefreshTokenInfoSchema::new(
+        active_after,
+        refresh_token.exp,
+        chrono::Utc::now().timestamp(),
+    )))
+}
+
+/// Logout a session.
+///
+/// This endpoint will revoke the token that authorized the request, the revoked token will be rejected until it expires.
+/// Authorize with the refresh token to revoke it, so it can't be used to get new JWT tokens, and once with the JWT token
+/// to revoke it too.
+///
+/// **Note**: Logging out with an already revoked token is rejected as the other authorized endpoints.
+#[endpoint(
+    operation_id = "logout",
+    tags("Auth"),
+    responses(
+        (status_code = 200, description = "The token has been revoked", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is revoked", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn logout(depot: &mut Depot) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<sea_orm::DatabaseConnection>>().unwrap();
+
+    // Note: The `Unauthorized` and revoked tokens errors are handled by the middlewares.
+    let claims = depot.jwt_claims();
+    if claims.is_expired() {
+        return Err(ApiError::ExpiredToken);
+    }
+    db_utils::revoke_token(conn.as_ref(), claims).await?;
+    Ok(Json(MessageSchema::new(
+        "The token has been revoked".to_owned(),
+    )))
+}
+
+/// Logout all the sessions.
+///
+/// This endpoint will revoke all the user tokens that issued until now, the JWT and the refresh tokens of all
+/// the sessions including this session, so all the sessions need to signin again.
+#[endpoint(
+    operation_id = "revoke_all_tokens",
+    tags("Auth"),
+    responses(
+        (status_code = 200, description = "All the tokens have been revoked", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is revoked", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn revoke_all_tokens(depot: &mut Depot) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<sea_orm::DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    db_utils::revoke_user_tokens(conn.as_ref(), user.uuid).await?;
+    Ok(Json(MessageSchema::new(
+        "All the tokens have been revoked, signin again".to_owned(),
+    )))
+}
+
+/// Change the user's password.
+///
+/// This endpoint will change the password and revoke all the user tokens, including the tokens of this session,
+/// so all the sessions are logged out and you need to signin again with the new password.
+#[endpoint(
+    operation_id = "change_password",
+    tags("Auth"),
+    responses(
+        (status_code = 200, description = "The password has been changed", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The password does not change", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The password dose not meet the requirements", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is revoked", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The current password is incorrect", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "Reauthentication required", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn change_password(
+    req: &mut Request,
+    depot: &mut Depot,
+    passwords: JsonBody<ChangePasswordSchema>,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<sea_orm::DatabaseConnection>>().unwrap();
+    let passwords = passwords.into_inner();
+
+    let user = depot.user(conn.as_ref()).await?;
+    depot.require_fresh_auth()?;
+
+    if !utils::PasswordHasher::current().verify(&passwords.current_password, &user.password_hash)? {
+        return Err(ApiError::InvalidSigninCredentials);
+    }
+    utils::validate_password(
+        &passwords.new_password,
+        depot.obtain::<Arc<utils::PasswordPolicy>>().unwrap(),
+    )?;
+    if passwords.current_password == passwords.new_password {
+        return Err(ApiError::PasswordNotChanged);
+    }
+
+    let user_uuid = user.uuid;
+    db_utils::reset_password(conn.as_ref(), user, &passwords.new_password).await?;
+    db_utils::revoke_user_tokens(conn.as_ref(), user_uuid).await?;
+    AuditEvent::new(AuditAction::PasswordChange, req, depot)
+        .actor(user_uuid)
+        .record(conn)
+        .await;
+    Ok(Json(MessageSchema::new(
+        "The password has been changed, signin again with the new password".to_owned(),
+    )))
+}
+
+/// Get the current user.
+///
+/// This endpoint will return the account of the JWT token, the refresh token is rejected. The response never contains
+/// the password hash.
+#[endpoint(
+    operation_id = "current_user",
+    tags("Auth"),
+    responses(
+        (status_code = 200, description = "The current user", content_type = "application/json", body = UserSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is revoked", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn current_user(depot: &mut Depot) -> ApiResult<Json<UserSchema>> {
+    let conn = depot.obtain::<Arc<sea_orm::DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    UserSchema::from_active_model(conn, user.into_active_model())
+        .await
+        .map(Json)
+}
+
+/// Delete the user's account.
+///
+/// This endpoint will delete the user's account and the user's data after confirming the password, and all the user's
+/// tokens are revoked. It requires the JWT token, the refresh token is rejected.
+#[endpoint(
+    operation_id = "delete_account",
+    tags("Auth"),
+    responses(
+        (status_code = 200, description = "The user's account has been deleted", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is revoked", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The password is incorrect", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "Reauthentication required", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn delete_account(
+    req: &mut Request,
+    depot: &mut Depot,
+    delete_user_schema: JsonBody<DeleteUserSchema>,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<sea_orm::DatabaseConnection>>().unwrap();
+
+    let user = depot.user(conn.as_ref()).await?;
+    depot.require_fresh_auth()?;
+
+    if !utils::PasswordHasher::current().verify(
+        &delete_user_schema.into_inner().password,
+        &user.password_hash,
+    )? {
+        return Err(ApiError::InvalidSigninCredentials);
+    }
+    db_utils::delete_user(conn.as_ref(), user.uuid).await?;
+    AuditEvent::new(AuditAction::AccountDeletion, req, depot)
+        .actor(user.uuid)
+        .record(conn)
+        .await;
+    Ok(Json(MessageSchema::new(
+        "The user's account has been deleted".to_owned(),
+    )))
+}
+
+/// Confirm the user's password.
+///
+/// This endpoint will return a new JWT token after confirming the password, and the two-factor authentication code if
+/// the user enabled it. The sensitive actions (deleting the account and changing the password) accept it if the server
+/// requires a recent password confirmation. There is no new refresh token, so refreshing the tokens doesn't confirm the
+/// password.
+///
+/// **Note**: This endpoint is rate limited to 5 requests per 15 minutes for each user.
+#[endpoint(
+    operation_id = "confirm_auth",
+    tags("Auth"),
+    responses(
+        (status_code = 200, description = "The password is confirmed", content_type = "application/json", body = AccessTokenSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is revoked", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The password is incorrect", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The two-factor authentication code is required", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The two-factor authentication code is incorrect", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn confirm_auth(
+    depot: &mut Depot,
+    confirm: JsonBody<ConfirmAuthSchema>,
+) -> ApiResult<Json<AccessTokenSchema>> {
+    let conn = depot.obtain::<Arc<sea_orm::DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+    let confirm = confirm.into_inner();
+
+    if !utils::PasswordHasher::current().verify(&confirm.password, &user.password_hash)? {
+        return Err(ApiError::InvalidSigninCredentials);
+    }
+    if user.totp_enabled {
+        let code = confirm.totp_code.ok_or(ApiError::TotpRequired)?;
+        let secret = user
+            .totp_secret
+            .as_deref()
+            .expect("The enabled two-factor authentication has a secret");
+        if !db_utils::use_totp_code(conn.as_ref(), user.uuid, secret, &code).await?
+            && !db_utils::use_recovery_code(conn.as_ref(), user.uuid, &code).await?
+        {
+            return Err(ApiError::InvalidTotpCode);
+        }
+    }
+    db_utils::issue_access_token(
+        &user,
+        depot.obtain::<Arc<JwtKeys>>().unwrap(),
+        depot.obtain::<Arc<TokenLifetimes>>().unwrap(),
+    )
+    .map(|jwt| Json(AccessTokenSchema::new(jwt)))
+}
+
+/// Check usernames availability.
+///
+/// This endpoint will return the availability of each candidate username, the usernames are validated
+/// the same way as the signup, so an invalid username will have the validation reason.
+///
+/// **Note**: This endpoint is rate limited to 5 requests per minute.
+#[endpoint(
+    operation_id = "usernames_available",
+    tags("Auth"),
+    request_body(
+        content = UsernamesSchema,
+        description = "The candidate usernames, at most 10 usernames",
+        example = json!(UsernamesSchema::default()),
+        content_type = "application/json",
+    ),
+    responses(
+        (status_code = 200, description = "The usernames availability", content_type = "application/json", body = Vec<UsernameAvailabilitySchema>),
+        (status_code = 400, description = "Too many usernames", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    )
+)]
+pub async fn usernames_available(
+    depot: &mut Depot,
+    usernames: JsonBody<UsernamesSchema>,
+) -> ApiResult<Json<Vec<UsernameAvailabilitySchema>>> {
+    let conn = depot.obtain::<Arc<sea_orm::DatabaseConnection>>().unwrap();
+    let usernames = usernames.into_inner().usernames;
+
+    if usernames.len() > MAX_USERNAMES_CHECK {
+        return Err(ApiError::TooManyUsernames(MAX_USERNAMES_CHECK));
+    }
+
+    let taken_usernames = db_utils::get_taken_usernames(
+        conn.as_ref(),
+        usernames
+            .iter()
+            .filter(|username| utils::validate_user_signin(username.trim()).is_ok())
+            .cloned()
+            .collect(),
+    )
+    .await?;
+
+    Ok(Json(
+        usernames
+            .into_iter()
+            .map(|username| {
+                if let Err(err) = utils::validate_user_signin(username.trim()) {
+                    UsernameAvailabilitySchema::new(username, false, Some(err.to_string()))
+                } else if taken_usernames.contains(&utils::normalize_username(&username)) {
+                    let reason = ApiError::UsernameAlreadyExists(username.clone()).to_string();
+                    UsernameAvailabilitySchema::new(username, false, Some(reason))
+                } else {
+                    UsernameAvailabilitySchema::new(username, true, None)
+                }
+            })
+            .collect(),
+    ))
+}
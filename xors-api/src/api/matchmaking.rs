@@ -0,0 +1,528 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+use sea_orm::DatabaseConnection;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::{
+    db_utils,
+    errors::{ApiError, ApiResult},
+    schemas::*,
+};
+
+use super::{
+    exts::*,
+    xo::{self, GameClock, Player},
+};
+
+/// The max rating difference of the matched players when they start searching, if it's not specified.
+pub const DEFAULT_INITIAL_TOLERANCE: u64 = 100;
+
+/// How much the rating tolerance widens every second in the search queue, if it's not specified.
+pub const DEFAULT_TOLERANCE_GROWTH: u64 = 10;
+
+/// The widest rating tolerance, if it's not specified.
+pub const DEFAULT_MAX_TOLERANCE: u64 = 400;
+
+/// The seconds in the search queue before the search is cancelled, if it's not specified.
+pub const DEFAULT_QUEUE_TIMEOUT: u64 = 300;
+
+/// The period between the checks of each queued player, its timeout and its widened tolerance.
+const MATCHMAKING_TICK: Duration = Duration::from_secs(1);
+
+/// The players searching for a game, in the queue order.
+pub(super) static SEARCH_FOR_GAME: Lazy<RwLock<VecDeque<QueuedPlayer>>> =
+    Lazy::new(RwLock::default);
+
+/// The players that paused their search, they keep their place in the search queue but are not matched.
+pub(super) static PAUSED_SEARCH: Lazy<RwLock<HashSet<Uuid>>> = Lazy::new(RwLock::default);
+
+/// The matchmaking policy, the players are matched by their rating within a tolerance that widens while they
+/// wait in the search queue.
+#[derive(Debug, Clone, Copy)]
+pub struct MatchmakingPolicy {
+    /// The max rating difference of the matched players when they start searching.
+    pub initial_tolerance: u64,
+    /// How much the tolerance widens every second in the search queue.
+    pub tolerance_growth: u64,
+    /// The widest tolerance, it stops widening at it.
+    pub max_tolerance: u64,
+    /// The seconds in the search queue before the search is cancelled, if any.
+    pub queue_timeout: Option<u64>,
+}
+
+impl MatchmakingPolicy {
+    /// Checks that the policy makes sense, the initial tolerance must not exceed the max tolerance and the queue
+    /// timeout must be positive.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.initial_tolerance > self.max_tolerance {
+            return Err("The initial tolerance must not exceed the max tolerance".to_owned());
+        }
+        if self.queue_timeout == Some(0) {
+            return Err("The queue timeout must be positive".to_owned());
+        }
+        Ok(())
+    }
+
+    /// Returns the rating tolerance of a player that waited `waited` in the search queue.
+    pub fn tolerance(&self, waited: Duration) -> u64 {
+        self.tolerance_growth
+            .saturating_mul(waited.as_secs())
+            .saturating_add(self.initial_tolerance)
+            .min(self.max_tolerance)
+    }
+
+    /// Returns whether the two players can be matched. The tolerance of the longer waiting player is used, so a
+    /// newcomer doesn't narrow it.
+    pub fn can_match(
+        &self,
+        rating: i64,
+        waited: Duration,
+        other_rating: i64,
+        other_waited: Duration,
+    ) -> bool {
+        rating.abs_diff(other_rating) <= self.tolerance(waited.max(other_waited))
+    }
+}
+
+impl Default for MatchmakingPolicy {
+    fn default() -> Self {
+        Self {
+            initial_tolerance: DEFAULT_INITIAL_TOLERANCE,
+            tolerance_growth: DEFAULT_TOLERANCE_GROWTH,
+            max_tolerance: DEFAULT_MAX_TOLERANCE,
+            queue_timeout: Some(DEFAULT_QUEUE_TIMEOUT),
+        }
+    }
+}
+
+/// A player waiting in the search queue.
+#[derive(Debug, Clone)]
+pub(crate) struct QueuedPlayer {
+    pub player: Player,
+    /// The player's rating when it started searching.
+    pub rating: i64,
+    /// When the player joined the search queue, its tolerance widens since it.
+    pub queued_at: Instant,
+}
+
+/// The opponent search result.
+enum Opponent {
+    /// The player to play against, it's taken from the search queue. With the searching player if it was waiting
+    /// in the search queue too, both of them are taken together.
+    Found {
+        opponent: QueuedPlayer,
+        player: Option<QueuedPlayer>,
+    },
+    /// There is no player to play against. With the in-progress game of the player against one of
+    /// the skipped players, if all the candidates are skipped because of it.
+    NotFound(Option<Uuid>),
+}
+
+/// Takes the closest rating player in the search queue that the player can play against, the players with the
+/// same rating keep their queue order. The searching player is taken with it if it's queued, `queued_at` is when
+/// it joined the search queue.
+///
+/// If `unique_pair_games` is `true`, the players that already have an in-progress game against the player are skipped.
+async fn find_opponent(
+    conn: &DatabaseConnection,
+    player_uuid: &Uuid,
+    rating: i64,
+    queued_at: Option<Instant>,
+    unique_pair_games: bool,
+    policy: &MatchmakingPolicy,
+) -> ApiResult<Opponent> {
+    let block_relations = db_utils::get_block_relations(conn, *player_uuid).await?;
+    let paused_players = PAUSED_SEARCH.read().await.clone();
+    let waited = queued_at.map_or(Duration::ZERO, |queued_at| queued_at.elapsed());
+    let mut candidates: Vec<_> = SEARCH_FOR_GAME
+        .queued_players()
+        .await
+        .into_iter()
+        .filter(|candidate| {
+            let candidate_uuid = candidate.player.0.as_ref();
+            candidate_uuid != player_uuid
+                && !block_relations.contains(candidate_uuid)
+                && !paused_players.contains(candidate_uuid)
+                && policy.can_match(
+                    rating,
+                    waited,
+                    candidate.rating,
+                    candidate.queued_at.elapsed(),
+                )
+        })
+        .collect();
+    candidates.sort_by_key(|candidate| candidate.rating.abs_diff(rating));
+    let mut active_game = None;
+
+    for candidate in candidates {
+        let candidate_uuid = candidate.player.0;
+        if unique_pair_games {
+            if let Some(game) =
+                db_utils::get_active_game_between(conn, *player_uuid, *candidate_uuid).await?
+            {
+                active_game = Some(game.uuid);
+                continue;
+            }
+        }
+        if queued_at.is_some() {
+            if let Some((player, opponent)) = SEARCH_FOR_GAME
+                .take_pair(player_uuid, &candidate_uuid)
+                .await
+            {
+                return Ok(Opponent::Found {
+                    opponent,
+                    player: Some(player),
+                });
+            }
+            if !SEARCH_FOR_GAME.is_user_in_search(player_uuid).await {
+                // The player left the search queue while searching for its opponent
+                return Ok(Opponent::NotFound(None));
+            }
+        } else if let Some(opponent) = SEARCH_FOR_GAME.take_user(&candidate_uuid).await {
+            return Ok(Opponent::Found {
+                opponent,
+                player: None,
+            });
+        }
+    }
+    Ok(Opponent::NotFound(active_game))
+}
+
+/// Search for a game, the player is matched with the closest rating player in the search queue within their
+/// tolerance. Otherwise it's added to the search queue with the `queued` event, and it waits until its tolerance
+/// widens enough or its search times out.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn search_for_game(
+    conn: &Arc<DatabaseConnection>,
+    max_online_games: &usize,
+    clock: GameClock,
+    unique_pair_games: bool,
+    daily_games_limit: Option<u64>,
+    random_opening_moves: u8,
+    policy: MatchmakingPolicy,
+    player: Player,
+) -> ApiResult<()> {
+    log::info!("Player {} is searching for a game", player.0);
+
+    if let Some(err) =
+        xo::start_game_error(conn, max_online_games, daily_games_limit, &player.0).await?
+    {
+        player.1.send_server_event(XoServerEventData::Error(err));
+        return Ok(());
+    }
+
+    let rating = *db_utils::get_user(conn, *player.0).await?.rating.as_ref();
+    match find_opponent(conn, &player.0, rating, None, unique_pair_games, &policy).await? {
+        Opponent::Found { opponent, .. } => {
+            start_match(
+                conn,
+                clock,
+                unique_pair_games,
+                random_opening_moves,
+                opponent,
+                QueuedPlayer {
+                    player,
+                    rating,
+                    queued_at: Instant::now(),
+                },
+            )
+            .await?;
+        }
+        Opponent::NotFound(Some(game_id)) => {
+            log::error!(
+                "Player {} is already playing the searching players in game {game_id}",
+                player.0
+            );
+            player.1.send_server_event(XoServerEventData::Error(
+                ErrorData::AlreadyPlayingEachOther { game_id },
+            ));
+        }
+        Opponent::NotFound(None) => {
+            queue_player(
+                conn.clone(),
+                *max_online_games,
+                clock,
+                unique_pair_games,
+                random_opening_moves,
+                policy,
+                QueuedPlayer {
+                    player,
+                    rating,
+                    queued_at: Instant::now(),
+                },
+            )
+            .await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Starts the game of the matched players, both of them receive the `match_found` event with the other player's
+/// rating, then the game starts with the `x_player` as the player that was taken from the search queue.
+async fn start_match(
+    conn: &DatabaseConnection,
+    clock: GameClock,
+    unique_pair_games: bool,
+    random_opening_moves: u8,
+    x_player: QueuedPlayer,
+    o_player: QueuedPlayer,
+) -> ApiResult<()> {
+    log::info!(
+        "Player {} ({}) is matched with player {} ({})",
+        x_player.player.0,
+        x_player.rating,
+        o_player.player.0,
+        o_player.rating
+    );
+
+    x_player
+        .player
+        .1
+        .send_server_event(XoServerEventData::MatchFound {
+            opponent: *o_player.player.0,
+            opponent_rating: o_player.rating,
+        });
+    o_player
+        .player
+        .1
+        .send_server_event(XoServerEventData::MatchFound {
+            opponent: *x_player.player.0,
+            opponent_rating: x_player.rating,
+        });
+    xo::start_game(
+        conn,
+        clock,
+        unique_pair_games,
+        random_opening_moves,
+        x_player.player,
+        o_player.player,
+        None,
+        GameVariant::default(),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Adds the player to the search queue with the `queued` event, then watches its search until it leaves the queue.
+async fn queue_player(
+    conn: Arc<DatabaseConnection>,
+    max_online_games: usize,
+    clock: GameClock,
+    unique_pair_games: bool,
+    random_opening_moves: u8,
+    policy: MatchmakingPolicy,
+    player: QueuedPlayer,
+) {
+    log::info!("Player {} is added to the search queue", player.player.0);
+
+    let (player_uuid, queued_at) = (*player.player.0, player.queued_at);
+    PAUSED_SEARCH.write().await.remove(&player_uuid);
+    player
+        .player
+        .1
+        .send_server_event(XoServerEventData::Queued {
+            rating: player.rating,
+            timeout_at: policy
+                .queue_timeout
+                .map(|timeout| chrono::Utc::now().timestamp() + timeout as i64),
+        });
+    SEARCH_FOR_GAME.add_user(player).await;
+
+    tokio::spawn(async move {
+        watch_queued_player(
+            &conn,
+            max_online_games,
+            clock,
+            unique_pair_games,
+            random_opening_moves,
+            policy,
+            player_uuid,
+            queued_at,
+        )
+        .await;
+    });
+}
+
+/// Watches the queued player's search, it's cancelled with the `queue_cancelled` event when it times out, and the
+/// player is matched again every time its tolerance widens. Stops when the player leaves the search queue.
+#[allow(clippy::too_many_arguments)]
+async fn watch_queued_player(
+    conn: &DatabaseConnection,
+    max_online_games: usize,
+    clock: GameClock,
+    unique_pair_games: bool,
+    random_opening_moves: u8,
+    policy: MatchmakingPolicy,
+    player_uuid: Uuid,
+    queued_at: Instant,
+) {
+    let mut tolerance = policy.tolerance(Duration::ZERO);
+    loop {
+        tokio::time::sleep(MATCHMAKING_TICK).await;
+        let Some(queued) = SEARCH_FOR_GAME
+            .queued_player(&player_uuid)
+            .await
+            .filter(|queued| queued.queued_at == queued_at)
+        else {
+            // The player left the search queue, it may be in another search now
+            return;
+        };
+
+        let waited = queued_at.elapsed();
+        if policy
+            .queue_timeout
+            .is_some_and(|timeout| waited >= Duration::from_secs(timeout))
+        {
+            log::info!("Player {player_uuid} search timed out");
+            cancel_queued_player(&player_uuid, QueueCancelReason::Timeout).await;
+            return;
+        }
+        if policy.tolerance(waited) == tolerance
+            || PAUSED_SEARCH.read().await.contains(&player_uuid)
+            || xo::live_counts().await.0 >= max_online_games
+        {
+            continue;
+        }
+        tolerance = policy.tolerance(waited);
+        if let Err(err) = match_queued_player(
+            conn,
+            &queued,
+            clock,
+            unique_pair_games,
+            random_opening_moves,
+            &policy,
+        )
+        .await
+        {
+            log::error!("Failed to match the queued player {player_uuid}: {err}");
+        }
+    }
+}
+
+/// Matches the queued player with the closest rating player in the search queue within their tolerance,
+/// otherwise it keeps waiting in its place.
+async fn match_queued_player(
+    conn: &DatabaseConnection,
+    queued: &QueuedPlayer,
+    clock: GameClock,
+    unique_pair_games: bool,
+    random_opening_moves: u8,
+    policy: &MatchmakingPolicy,
+) -> ApiResult<()> {
+    if let Opponent::Found {
+        opponent,
+        player: Some(player),
+    } = find_opponent(
+        conn,
+        &queued.player.0,
+        queued.rating,
+        Some(queued.queued_at),
+        unique_pair_games,
+        policy,
+    )
+    .await?
+    {
+        start_match(
+            conn,
+            clock,
+            unique_pair_games,
+            random_opening_moves,
+            opponent,
+            player,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Removes the player from the search queue with the `queue_cancelled` event, returns whether it was in the queue.
+async fn cancel_queued_player(player_uuid: &Uuid, reason: QueueCancelReason) -> bool {
+    let Some(queued) = SEARCH_FOR_GAME.take_user(player_uuid).await else {
+        return false;
+    };
+    PAUSED_SEARCH.write().await.remove(player_uuid);
+    queued
+        .player
+        .1
+        .send_server_event(XoServerEventData::QueueCancelled { reason });
+    true
+}
+
+/// Cancel the player search, the player leaves the search queue with the `queue_cancelled` event.
+pub(super) async fn cancel_search(player_uuid: &Uuid) -> ApiResult<()> {
+    if !cancel_queued_player(player_uuid, QueueCancelReason::Cancelled).await {
+        return Err(ApiError::NotInSearch);
+    }
+    log::info!("Player {player_uuid} cancelled its search");
+    Ok(())
+}
+
+/// Pause the player search, the player keeps its place in the search queue but will not be matched until it resumes.
+///
+/// Pausing an already paused search does nothing.
+pub(crate) async fn pause_search(player_uuid: &Uuid) -> ApiResult<()> {
+    if !SEARCH_FOR_GAME.is_user_in_search(player_uuid).await {
+        return Err(ApiError::NotInSearch);
+    }
+    log::info!("Player {player_uuid} paused its search");
+    PAUSED_SEARCH.write().await.insert(*player_uuid);
+    Ok(())
+}
+
+/// Resume the player search, the player is matched with the closest rating player in the search queue within
+/// their tolerance, otherwise it waits in its original place in the queue.
+///
+/// Resuming a not paused search does nothing.
+pub(crate) async fn resume_search(
+    conn: &DatabaseConnection,
+    player_uuid: &Uuid,
+    max_online_games: usize,
+    clock: GameClock,
+    unique_pair_games: bool,
+    random_opening_moves: u8,
+    policy: MatchmakingPolicy,
+) -> ApiResult<()> {
+    let Some(queued) = SEARCH_FOR_GAME.queued_player(player_uuid).await else {
+        return Err(ApiError::NotInSearch);
+    };
+    if !PAUSED_SEARCH.write().await.remove(player_uuid) {
+        return Ok(());
+    }
+    log::info!("Player {player_uuid} resumed its search");
+
+    if xo::live_counts().await.0 >= max_online_games {
+        return Ok(());
+    }
+    match_queued_player(
+        conn,
+        &queued,
+        clock,
+        unique_pair_games,
+        random_opening_moves,
+        &policy,
+    )
+    .await
+}
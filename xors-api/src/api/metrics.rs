@@ -0,0 +1,529 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+use salvo::{http::HeaderValue, hyper::header, oapi::endpoint, prelude::*, routing::PathParams};
+use subtle::ConstantTimeEq;
+
+use super::xo;
+use crate::{errors::ApiError, schemas::MessageSchema};
+
+/// The upper bounds of the duration histograms buckets, in seconds.
+const DURATION_BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// The content type of the Prometheus text exposition format.
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// The route label of the requests that don't match any route pattern.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// The instance metrics, rendered by the `/metrics` endpoint.
+pub(crate) static METRICS: Lazy<Metrics> = Lazy::new(Metrics::default);
+
+/// A histogram of durations, with the [`DURATION_BUCKETS`] buckets.
+#[derive(Debug, Default)]
+struct Histogram {
+    /// The cumulative count of each bucket.
+    buckets: [u64; DURATION_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bucket, upper_bound) in self.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if seconds <= upper_bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    /// Writes the histogram samples, the labels are written without the braces, e.g. `route="/time"`.
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let label_set = |le: &str| match (labels.is_empty(), le.is_empty()) {
+            (true, true) => String::new(),
+            (true, false) => format!("{{le=\"{le}\"}}"),
+            (false, true) => format!("{{{labels}}}"),
+            (false, false) => format!("{{{labels},le=\"{le}\"}}"),
+        };
+        for (bucket, upper_bound) in self.buckets.iter().zip(DURATION_BUCKETS) {
+            writeln!(
+                out,
+                "{name}_bucket{} {bucket}",
+                label_set(&upper_bound.to_string())
+            )
+            .ok();
+        }
+        writeln!(out, "{name}_bucket{} {}", label_set("+Inf"), self.count).ok();
+        writeln!(out, "{name}_sum{} {}", label_set(""), self.sum).ok();
+        writeln!(out, "{name}_count{} {}", label_set(""), self.count).ok();
+    }
+}
+
+/// The instance metrics.
+#[derive(Debug, Default)]
+pub(crate) struct Metrics {
+    /// The requests latency, by the method, the route and the status code.
+    requests: Mutex<BTreeMap<(String, String, u16), Histogram>>,
+    /// The database queries duration, by the query operation.
+    db_queries: Mutex<BTreeMap<&'static str, Histogram>>,
+    /// The failed database queries, by the query operation.
+    db_failed_queries: Mutex<BTreeMap<&'static str, u64>>,
+    captcha_generation: Mutex<Histogram>,
+    signups: AtomicU64,
+    /// The signins with invalid credentials.
+    failed_signins: AtomicU64,
+    /// The signins with invalid two-factor authentication codes.
+    failed_totp_signins: AtomicU64,
+    games_started: AtomicU64,
+    games_ended: AtomicU64,
+}
+
+impl Metrics {
+    fn request_finished(&self, method: &str, route: &str, status: u16, duration: Duration) {
+        self.requests
+            .lock()
+            .expect("The lock is not poisoned")
+            .entry((method.to_owned(), route.to_owned(), status))
+            .or_default()
+            .observe(duration);
+    }
+
+    /// Records the executed database query.
+    pub(crate) fn query_executed(&self, info: &sea_orm::metric::Info<'_>) {
+        let operation = query_operation(&info.statement.sql);
+        self.db_queries
+            .lock()
+            .expect("The lock is not poisoned")
+            .entry(operation)
+            .or_default()
+            .observe(info.elapsed);
+        if info.failed {
+            *self
+                .db_failed_queries
+                .lock()
+                .expect("The lock is not poisoned")
+                .entry(operation)
+                .or_default() += 1;
+        }
+    }
+
+    /// Records the duration of a captcha generation.
+    pub(crate) fn captcha_generated(&self, duration: Duration) {
+        self.captcha_generation
+            .lock()
+            .expect("The lock is not poisoned")
+            .observe(duration);
+    }
+
+    pub(crate) fn user_signed_up(&self) {
+        self.signups.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a signin with invalid credentials, or with an invalid two-factor authentication code.
+    pub(crate) fn signin_failed(&self, totp: bool) {
+        if totp {
+            self.failed_totp_signins.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.failed_signins.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn game_started(&self) {
+        self.games_started.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn game_ended(&self) {
+        self.games_ended.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    async fn render(&self) -> String {
+        let (online_games, connected_players) = xo::live_counts().await;
+        let mut out = String::new();
+
+        write_header(
+            &mut out,
+            "xors_http_request_duration_seconds",
+            "histogram",
+            "The HTTP requests latency, by route.",
+        );
+        for ((method, route, status), histogram) in self
+            .requests
+            .lock()
+            .expect("The lock is not poisoned")
+            .iter()
+        {
+            histogram.render(
+                &mut out,
+                "xors_http_request_duration_seconds",
+                &format!(
+                    "method=\"{method}\",route=\"{}\",status=\"{status}\"",
+                    route.replace('\\', "\\\\").replace('"', "\\\"")
+                ),
+            );
+        }
+
+        write_header(
+            &mut out,
+            "xors_db_query_duration_seconds",
+            "histogram",
+            "The database queries duration, by operation.",
+        );
+        for (operation, histogram) in self
+            .db_queries
+            .lock()
+            .expect("The lock is not poisoned")
+            .iter()
+        {
+            histogram.render(
+                &mut out,
+                "xors_db_query_duration_seconds",
+                &format!("operation=\"{operation}\""),
+            );
+        }
+        write_header(
+            &mut out,
+            "xors_db_query_failures_total",
+            "counter",
+            "The failed database queries, by operation.",
+        );
+        for (operation, count) in self
+            .db_failed_queries
+            .lock()
+            .expect("The lock is not poisoned")
+            .iter()
+        {
+            writeln!(
+                out,
+                "xors_db_query_failures_total{{operation=\"{operation}\"}} {count}"
+            )
+            .ok();
+        }
+
+        write_header(
+            &mut out,
+            "xors_captcha_generation_duration_seconds",
+            "histogram",
+            "The captcha images generation duration.",
+        );
+        self.captcha_generation
+            .lock()
+            .expect("The lock is not poisoned")
+            .render(&mut out, "xors_captcha_generation_duration_seconds", "");
+
+        write_header(
+            &mut out,
+            "xors_signups_total",
+            "counter",
+            "The signed up users.",
+        );
+        writeln!(
+            out,
+            "xors_signups_total {}",
+            self.signups.load(Ordering::Relaxed)
+        )
+        .ok();
+        write_header(
+            &mut out,
+            "xors_failed_signins_total",
+            "counter",
+            "The failed signins, by reason.",
+        );
+        writeln!(
+            out,
+            "xors_failed_signins_total{{reason=\"credentials\"}} {}",
+            self.failed_signins.load(Ordering::Relaxed)
+        )
+        .ok();
+        writeln!(
+            out,
+            "xors_failed_signins_total{{reason=\"totp\"}} {}",
+            self.failed_totp_signins.load(Ordering::Relaxed)
+        )
+        .ok();
+
+        write_header(
+            &mut out,
+            "xors_online_games",
+            "gauge",
+            "The in-progress websocket games.",
+        );
+        writeln!(out, "xors_online_games {online_games}").ok();
+        write_header(
+            &mut out,
+            "xors_connected_players",
+            "gauge",
+            "The players connected to the websocket.",
+        );
+        writeln!(out, "xors_connected_players {connected_players}").ok();
+        write_header(
+            &mut out,
+            "xors_games_started_total",
+            "counter",
+            "The started websocket games, including the resumed ones.",
+        );
+        writeln!(
+            out,
+            "xors_games_started_total {}",
+            self.games_started.load(Ordering::Relaxed)
+        )
+        .ok();
+        write_header(
+            &mut out,
+            "xors_games_ended_total",
+            "counter",
+            "The ended websocket games.",
+        );
+        writeln!(
+            out,
+            "xors_games_ended_total {}",
+            self.games_ended.load(Ordering::Relaxed)
+        )
+        .ok();
+
+        out
+    }
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {name} {help}").ok();
+    writeln!(out, "# TYPE {name} {kind}").ok();
+}
+
+/// Returns the operation of the query, the first keyword of it.
+fn query_operation(sql: &str) -> &'static str {
+    match sql
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "select" => "select",
+        "insert" => "insert",
+        "update" => "update",
+        "delete" => "delete",
+        _ => "other",
+    }
+}
+
+/// Returns the method of the request, the non-standard methods are `OTHER`.
+fn request_method(req: &Request) -> &'static str {
+    [
+        "GET", "POST", "PUT", "DELETE", "PATCH", "HEAD", "OPTIONS", "CONNECT", "TRACE",
+    ]
+    .into_iter()
+    .find(|method| *method == req.method().as_str())
+    .unwrap_or("OTHER")
+}
+
+/// A route pattern of the router, e.g. `/game/<uuid>/moves`.
+#[derive(Debug)]
+struct RoutePattern {
+    /// The pattern, with the path parameters names only.
+    label: String,
+    segments: Vec<String>,
+}
+
+impl RoutePattern {
+    fn new(path: &str) -> Self {
+        let segments: Vec<String> = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| {
+                // Drop the parameter constraint, e.g. `<id:num>` is `<id>`
+                match segment.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+                    Some(param) => format!("<{}>", param.split(':').next().unwrap_or_default()),
+                    None => segment.to_owned(),
+                }
+            })
+            .collect();
+        Self {
+            label: format!("/{}", segments.join("/")),
+            segments,
+        }
+    }
+
+    /// Whether the pattern is the matched route of the path, its parameters are the matched parameters.
+    ///
+    /// The parameters are compared by their names, so `/game/decode` isn't the route of a `/game/<uuid>` match.
+    fn matches(&self, path: &[&str], params: &PathParams) -> bool {
+        let mut params_count = 0;
+        for (idx, segment) in self.segments.iter().enumerate() {
+            match segment.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+                // The rest parameter matches the remaining segments
+                Some(name) if name.starts_with('*') => {
+                    return params.contains_key(name) && params.len() == params_count + 1;
+                }
+                Some(name) => {
+                    if idx >= path.len() || !params.contains_key(name) {
+                        return false;
+                    }
+                    params_count += 1;
+                }
+                None if path.get(idx) != Some(&segment.as_str()) => return false,
+                None => {}
+            }
+        }
+        self.segments.len() == path.len() && params.len() == params_count
+    }
+}
+
+/// Records the latency of the requests, labeled by the route pattern that matches the request.
+///
+/// The labels are the route patterns of the router, so the routes count is bounded. The requests that don't
+/// match any pattern are labeled with [`UNMATCHED_ROUTE`].
+pub(crate) struct RecordRequest {
+    routes: Vec<RoutePattern>,
+}
+
+impl RecordRequest {
+    /// Create a new requests recorder, with the route patterns of the router.
+    pub(crate) fn new(router: &Router) -> Self {
+        let mut routes = Vec::new();
+        collect_routes(router, "", &mut routes);
+        routes.sort_by(|a, b| a.label.cmp(&b.label));
+        routes.dedup_by(|a, b| a.label == b.label);
+        Self { routes }
+    }
+
+    /// Returns the route pattern that matched the request.
+    fn request_route(&self, req: &Request) -> &str {
+        let path = req
+            .uri()
+            .path()
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>();
+        self.routes
+            .iter()
+            .find(|route| route.matches(&path, req.params()))
+            .map_or(UNMATCHED_ROUTE, |route| route.label.as_str())
+    }
+}
+
+/// Collects the patterns of the routes that have a goal, the path filters are joined with their parents.
+///
+/// Salvo doesn't expose the path of the path filters, so it's read from their `Debug` output. The
+/// `all_routes_labeled` metrics test fails if it changes and the routes are labeled with [`UNMATCHED_ROUTE`].
+fn collect_routes(router: &Router, base: &str, routes: &mut Vec<RoutePattern>) {
+    let path = router
+        .filters()
+        .iter()
+        .find_map(|filter| {
+            format!("{filter:?}")
+                .strip_prefix("path:")
+                .map(|path| format!("{base}/{}", path.trim_matches('/')))
+        })
+        .unwrap_or_else(|| base.to_owned());
+    if router.goal.is_some() {
+        routes.push(RoutePattern::new(&path));
+    }
+    for router in router.routers() {
+        collect_routes(router, &path, routes);
+    }
+}
+
+#[async_trait]
+impl Handler for RecordRequest {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        let started_at = Instant::now();
+        ctrl.call_next(req, depot, res).await;
+
+        METRICS.request_finished(
+            request_method(req),
+            self.request_route(req),
+            res.status_code.unwrap_or(StatusCode::OK).as_u16(),
+            started_at.elapsed(),
+        );
+    }
+}
+
+/// Rejects the requests without the metrics bearer token, in the `Authorization` header.
+pub(crate) struct MetricsGuard(String);
+
+/// Returns a hoop that rejects the requests without the `Bearer <token>` authorization.
+pub(crate) fn require_token(token: String) -> MetricsGuard {
+    MetricsGuard(token)
+}
+
+#[async_trait]
+impl Handler for MetricsGuard {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        _depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        if !req
+            .header::<String>(header::AUTHORIZATION)
+            .and_then(|value| value.strip_prefix("Bearer ").map(str::to_owned))
+            .is_some_and(|token| token.as_bytes().ct_eq(self.0.as_bytes()).into())
+        {
+            ctrl.skip_rest();
+            res.render(ApiError::Forbidden);
+        }
+    }
+}
+
+/// Get the server metrics.
+///
+/// This endpoint will return the server metrics in the Prometheus text format, the requests latency per route,
+/// the websocket games and players, the signups, the failed signins, the captcha generation duration and the
+/// database queries duration.
+///
+/// The endpoint is enabled only if the server has a metrics token, and it requires it as a bearer token in the
+/// `Authorization` header.
+///
+/// **Note**: This endpoint is not rate limited.
+#[endpoint(
+    operation_id = "get_server_metrics",
+    tags("Server"),
+    responses(
+        (status_code = 200, description = "The server metrics", content_type = "text/plain", body = String),
+        (status_code = 403, description = "The metrics token is missing or invalid", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn metrics(res: &mut Response) {
+    res.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(METRICS_CONTENT_TYPE),
+    );
+    res.write_body(METRICS.render().await).ok();
+}
@@ -19,23 +19,55 @@ use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
+use once_cell::sync::Lazy;
 use salvo::http::ResBody;
 use salvo::hyper::header::HeaderName;
-use salvo::jwt_auth::{ConstDecoder, HeaderFinder};
+use salvo::jwt_auth::HeaderFinder;
 use salvo::oapi::security::{Http, HttpAuthScheme};
 use salvo::oapi::{Info, License, SecurityScheme};
 use salvo::rate_limiter::*;
 use salvo::{catcher::Catcher, http::HeaderValue, hyper::header, logging::Logger, prelude::*};
 use salvo_captcha::*;
 
-use crate::schemas::MessageSchema;
+use crate::{
+    db_utils,
+    errors::{status_error_code, ApiError},
+    i18n,
+    schemas::{CaptchaProviderKind, MessageSchema, UserRole},
+};
 
+pub mod admin;
+pub mod audit;
+pub mod bot;
+pub mod captcha_provider;
+pub mod captcha_storage;
 pub mod exts;
+pub mod friend;
 pub mod game;
 pub mod jwt;
+pub mod matchmaking;
+pub mod metrics;
+pub mod notification;
+pub mod risk;
+pub mod season;
+pub mod server;
+pub mod tournament;
 pub mod user;
+pub mod webhook;
 pub mod xo;
 
+/// The seconds between the expired rows prunes, if it's not specified.
+pub const DEFAULT_PRUNE_INTERVAL: u64 = 60;
+
+/// The seconds before the private games invites expire, if it's not specified.
+pub const DEFAULT_INVITE_TTL: u64 = 600;
+
+/// The seconds before the token expiry that the `token_expiring` event is sent in, if it's not specified.
+pub const DEFAULT_TOKEN_EXPIRING_BEFORE: u64 = 60;
+
+/// The rated games a user needs to be ranked, if it's not specified.
+pub const DEFAULT_MIN_RATED_GAMES: u64 = 5;
+
 pub fn write_json_body(res: &mut Response, json_body: impl serde::Serialize) {
     res.write_body(serde_json::to_string(&json_body).unwrap())
         .ok();
@@ -44,7 +76,10 @@ pub fn write_json_body(res: &mut Response, json_body: impl serde::Serialize) {
 #[handler]
 async fn handle404(res: &mut Response, ctrl: &mut FlowCtrl) {
     if let Some(StatusCode::NOT_FOUND) = res.status_code {
-        write_json_body(res, MessageSchema::new("Not Found".to_owned()));
+        write_json_body(
+            res,
+            MessageSchema::new("Not Found".to_owned()).with_code("not_found"),
+        );
         ctrl.skip_rest();
     }
 }
@@ -56,7 +91,8 @@ async fn handle_server_errors(res: &mut Response, ctrl: &mut FlowCtrl) {
         if res.status_code == Some(StatusCode::TOO_MANY_REQUESTS) {
             write_json_body(
                 res,
-                MessageSchema::new("Too many requests, please try again later".to_owned()),
+                MessageSchema::new("Too many requests, please try again later".to_owned())
+                    .with_code("too_many_requests"),
             );
             ctrl.skip_rest();
         } else if let ResBody::Error(err) = &res.body {
@@ -75,7 +111,8 @@ async fn handle_server_errors(res: &mut Response, ctrl: &mut FlowCtrl) {
                         .last()
                         .unwrap_or_default()
                         .trim()
-                )),
+                ))
+                .with_code(status_error_code(&err.name)),
             );
             ctrl.skip_rest();
         } else {
@@ -86,6 +123,49 @@ async fn handle_server_errors(res: &mut Response, ctrl: &mut FlowCtrl) {
     }
 }
 
+/// Translates the error message of the response to the language of the `Accept-Language` header, the message is
+/// kept if it's not translated. See [`i18n`].
+#[handler]
+async fn localize_messages(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    ctrl: &mut FlowCtrl,
+) {
+    ctrl.call_next(req, depot, res).await;
+
+    let language = req
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(i18n::DEFAULT_LANGUAGE, i18n::negotiate_language);
+    if language == i18n::DEFAULT_LANGUAGE
+        || matches!(res.status_code, Some(status) if status.is_success())
+    {
+        return;
+    }
+    let ResBody::Once(body) = &res.body else {
+        return;
+    };
+    let Ok(mut message) = serde_json::from_slice::<serde_json::Value>(body) else {
+        return;
+    };
+    let Some(code) = message["code"].as_str() else {
+        return;
+    };
+    let args = res
+        .extensions
+        .get::<i18n::MessageArgs>()
+        .cloned()
+        .unwrap_or_default();
+    if let Some(translated) = i18n::translate(language, code, &args) {
+        message["message"] = translated.into();
+        res.replace_body(ResBody::Once(message.to_string().into()));
+        res.headers_mut()
+            .insert(header::CONTENT_LANGUAGE, HeaderValue::from_static(language));
+    }
+}
+
 #[handler]
 async fn add_server_headers(res: &mut Response) {
     let headers = res.headers_mut();
@@ -97,34 +177,215 @@ async fn add_server_headers(res: &mut Response) {
     headers.insert("X-Powered-By", HeaderValue::from_static("Rust/Salvo"));
 }
 
+/// Limit the captcha creation with the instance-wide budget of the captcha guard.
+#[handler]
+async fn limit_captcha_creation(depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+    let captcha_guard = depot.obtain::<Arc<risk::CaptchaGuard>>().unwrap();
+    if let Err(retry_after) = captcha_guard.take_creation() {
+        log::warn!("The global captcha creation budget is exhausted");
+        // Round up, so the client never retries before the budget is refilled
+        let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+        res.headers_mut().insert(
+            header::RETRY_AFTER,
+            HeaderValue::from_str(&retry_after.to_string()).expect("Is valid header value"),
+        );
+        res.status_code(StatusCode::TOO_MANY_REQUESTS);
+        ctrl.skip_rest();
+    }
+}
+
+/// Reject the revoked tokens, the tokens of the logged out sessions, the consumed refresh tokens,
+/// the tokens of the users that their refresh token is reused and the tokens of the banned users.
+#[handler]
+async fn reject_revoked_tokens(depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+    let conn = depot.obtain::<Arc<sea_orm::DatabaseConnection>>().unwrap();
+    let Some(jwt) = depot.jwt_auth_data::<jwt::JwtClaims>() else {
+        return;
+    };
+
+    if let Err(err) = db_utils::check_token_revocation(conn, &jwt.claims).await {
+        ctrl.skip_rest();
+        res.render(err);
+    }
+}
+
+/// Rejects the users that their role is lower than the required role, see [`require_role`].
+pub(crate) struct RoleGuard(UserRole);
+
+/// Returns a hoop that rejects the users that their role is lower than `role`, with the token role.
+///
+/// It must be after the `JwtAuth` middleware, the refreshed tokens have the current role of the user.
+pub(crate) fn require_role(role: UserRole) -> RoleGuard {
+    RoleGuard(role)
+}
+
+#[async_trait]
+impl Handler for RoleGuard {
+    async fn handle(
+        &self,
+        _req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        if !depot
+            .jwt_auth_data::<jwt::JwtClaims>()
+            .is_some_and(|jwt| jwt.claims.role >= self.0)
+        {
+            ctrl.skip_rest();
+            res.render(ApiError::Forbidden);
+        }
+    }
+}
+
+/// The API service configuration, the defaults are the server defaults.
+pub struct ServiceConfig {
+    /// The max in-progress games of the server.
+    pub max_online_games: usize,
+    pub game_clock: xo::GameClock,
+    /// Whether two players can't have more than one in-progress game against each other.
+    pub unique_pair_games: bool,
+    /// The max games a player can start in the last 24 hours, if any.
+    pub daily_games_limit: Option<u64>,
+    /// The random moves the rounds start with.
+    pub random_opening_moves: u8,
+    pub matchmaking: matchmaking::MatchmakingPolicy,
+    pub chat_limiter: xo::ChatLimiter,
+    /// Whether the games chat messages are saved with the game.
+    pub persist_chat: bool,
+    pub token_expiry_policy: xo::TokenExpiryPolicy,
+    /// The seconds before the token expiry that the `token_expiring` event is sent in, with the
+    /// [`xo::TokenExpiryPolicy::Reauthenticate`] policy, the default is [`DEFAULT_TOKEN_EXPIRING_BEFORE`].
+    pub token_expiring_before: Option<u64>,
+    /// The allowed origins of the websocket handshakes, all the origins are allowed if it's `None`.
+    pub ws_allowed_origins: Option<Vec<String>>,
+    pub duplicate_connection_policy: xo::DuplicateConnectionPolicy,
+    pub captcha_guard: risk::CaptchaGuard,
+    /// Whether the `/auth/captcha/preview` endpoint is enabled.
+    pub captcha_preview: bool,
+    /// The bearer token of the `/metrics` endpoint, the endpoint is disabled if it's `None`.
+    pub metrics_token: Option<String>,
+    /// The seconds after the last password confirmation that the sensitive actions are allowed in, if required.
+    pub fresh_auth_period: Option<u64>,
+    pub token_lifetimes: jwt::TokenLifetimes,
+    pub auth_rate_limits: risk::AuthRateLimits,
+    pub password_policy: crate::utils::PasswordPolicy,
+    /// The seconds between the expired rows prunes, the default is [`DEFAULT_PRUNE_INTERVAL`].
+    pub prune_interval: Option<u64>,
+    /// The seconds before the private games invites expire, the default is [`DEFAULT_INVITE_TTL`].
+    pub invite_ttl: Option<u64>,
+    /// The webhooks registration policy, it should be the policy of the installed [`webhook::WebhookSender`].
+    pub webhook_policy: webhook::WebhookPolicy,
+    /// The rated games a user needs to be ranked, see `/user/{uuid}/rank`.
+    pub min_rated_games: u64,
+    /// The order of the users with the same rating in the leaderboard and the ranks.
+    pub leaderboard_tiebreakers: Vec<user::Tiebreaker>,
+}
+
+impl Default for ServiceConfig {
+    fn default() -> Self {
+        Self {
+            max_online_games: 100,
+            game_clock: Default::default(),
+            unique_pair_games: false,
+            daily_games_limit: None,
+            random_opening_moves: 0,
+            matchmaking: Default::default(),
+            chat_limiter: Default::default(),
+            persist_chat: false,
+            token_expiry_policy: Default::default(),
+            token_expiring_before: None,
+            ws_allowed_origins: None,
+            duplicate_connection_policy: Default::default(),
+            captcha_guard: Default::default(),
+            captcha_preview: false,
+            metrics_token: None,
+            fresh_auth_period: None,
+            token_lifetimes: Default::default(),
+            auth_rate_limits: Default::default(),
+            password_policy: Default::default(),
+            prune_interval: None,
+            invite_ttl: None,
+            webhook_policy: Default::default(),
+            min_rated_games: DEFAULT_MIN_RATED_GAMES,
+            leaderboard_tiebreakers: user::DEFAULT_LEADERBOARD_TIEBREAKERS.to_vec(),
+        }
+    }
+}
+
 pub fn service(
-    conn: sea_orm::DatabaseConnection,
-    max_online_games: usize,
-    move_period: i64,
-    secret_key: String,
+    mut conn: sea_orm::DatabaseConnection,
+    jwt_keys: jwt::JwtKeys,
+    config: ServiceConfig,
 ) -> (Service, OpenApi) {
-    let auth_handler: JwtAuth<jwt::JwtClaims, _> =
-        JwtAuth::new(ConstDecoder::from_secret(secret_key.as_bytes()))
-            .finders(vec![Box::new(
-                HeaderFinder::new().header_names(vec![header::AUTHORIZATION]),
-            )])
-            .force_passed(false);
-    let captcha_middleware = Captcha::<CacacheStorage, CaptchaHeaderFinder<String, String>>::new(
-        CacacheStorage::new("chapcha_cache"),
-        CaptchaHeaderFinder::new()
-            .token_header(HeaderName::from_str("X-Captcha-Token").expect("Is valid header name"))
-            .answer_header(HeaderName::from_str("X-Captcha-Answer").expect("Is valid header name")),
-    )
-    .skipper(|_: &mut Request, _: &Depot| {
-        // Skip the captcha middleware if we are in the test environment
-        // The captcha logic is tested in the `salvo_captcha` crate
-        if matches!(env::var("XORS_API_TEST"), Ok(val) if val == "true") {
-            return true;
-        }
-        false
-    });
+    let ServiceConfig {
+        max_online_games,
+        game_clock,
+        unique_pair_games,
+        daily_games_limit,
+        random_opening_moves,
+        matchmaking,
+        chat_limiter,
+        persist_chat,
+        token_expiry_policy,
+        token_expiring_before,
+        ws_allowed_origins,
+        duplicate_connection_policy,
+        captcha_guard,
+        captcha_preview,
+        metrics_token,
+        fresh_auth_period,
+        token_lifetimes,
+        auth_rate_limits,
+        password_policy,
+        prune_interval,
+        invite_ttl,
+        webhook_policy,
+        min_rated_games,
+        leaderboard_tiebreakers,
+    } = config;
+    let auth_handler: JwtAuth<jwt::JwtClaims, _> = JwtAuth::new(jwt_keys.clone())
+        .finders(vec![Box::new(
+            HeaderFinder::new().header_names(vec![header::AUTHORIZATION]),
+        )])
+        .force_passed(false);
+    let captcha_guard = Arc::new(captcha_guard);
+    // The sign routes and the guest upgrade route have separate middlewares, over the same on-disk answers storage
+    let new_captcha_middleware = || {
+        Captcha::<
+            captcha_storage::CaptchaAnswerStorage,
+            CaptchaHeaderFinder<String, captcha_storage::CaptchaAnswer>,
+        >::new(
+            captcha_storage::CaptchaAnswerStorage::new(CacacheStorage::new("chapcha_cache")),
+            CaptchaHeaderFinder::new()
+                .token_header(
+                    HeaderName::from_str("X-Captcha-Token").expect("Is valid header name"),
+                )
+                .answer_header(
+                    HeaderName::from_str("X-Captcha-Answer").expect("Is valid header name"),
+                ),
+        )
+        .skipper({
+            let captcha_guard = captcha_guard.clone();
+            move |req: &mut Request, _: &Depot| {
+                // Skip the image captcha if we are in the test environment
+                // The captcha logic is tested in the `salvo_captcha` crate
+                if captcha_guard.provider().kind() == CaptchaProviderKind::Image
+                    && matches!(env::var("XORS_API_TEST"), Ok(val) if val == "true")
+                {
+                    return true;
+                }
+                captcha_guard.can_skip(req)
+            }
+        })
+    };
+    let captcha_middleware = new_captcha_middleware();
+    let upgrade_captcha_middleware = new_captcha_middleware();
 
     let captcha_storage = Arc::new(captcha_middleware.storage().clone());
+    let trust_proxy = auth_rate_limits.trust_proxy();
+    conn.set_metric_callback(|info| metrics::METRICS.query_executed(info));
+    let conn = Arc::new(conn);
 
     let unauth_limiter = RateLimiter::new(
         SlidingGuard::new(),
@@ -133,6 +394,13 @@ pub fn service(
         CelledQuota::per_minute(30, 1),
     )
     .add_headers(true);
+    let usernames_limiter = RateLimiter::new(
+        SlidingGuard::new(),
+        MokaStore::<String, SlidingGuard>::new(),
+        RemoteIpIssuer,
+        CelledQuota::per_minute(5, 1),
+    )
+    .add_headers(true);
     let auth_limiter = RateLimiter::new(
         FixedGuard::new(),
         MokaStore::new(),
@@ -141,15 +409,80 @@ pub fn service(
     )
     .add_headers(true);
 
+    let mut unlimited_router = Router::new()
+        .hoop(add_server_headers)
+        .push(Router::with_path("time").get(server::time))
+        .push(Router::with_path("health").get(server::health))
+        .push(Router::with_path(".well-known/jwks.json").get(jwt::jwks));
+    if captcha_preview {
+        log::warn!("The captcha preview endpoint is enabled, it's for the UI development only");
+        unlimited_router = unlimited_router
+            .push(Router::with_path("auth/captcha/preview").get(jwt::captcha_preview));
+    }
+    if let Some(metrics_token) = metrics_token {
+        unlimited_router = unlimited_router.push(
+            Router::with_path("metrics")
+                .hoop(metrics::require_token(metrics_token))
+                .get(metrics::metrics),
+        );
+    } else {
+        log::info!("The metrics token is not set, the metrics endpoint is disabled");
+    }
+
+    // The image captcha is created only if it's the captcha provider
+    let mut image_captcha_router = Router::with_path("captcha");
+    if captcha_guard.provider().kind() == CaptchaProviderKind::Image {
+        image_captcha_router = image_captcha_router
+            .hoop(auth_rate_limits.captcha)
+            .hoop(limit_captcha_creation)
+            .get(jwt::captcha);
+    } else {
+        log::info!(
+            "The captcha provider is `{:?}`, the image captcha endpoint is disabled",
+            captcha_guard.provider().kind()
+        );
+    }
+
     let router = Router::new()
         .hoop(Logger::new())
+        .hoop(localize_messages)
         .hoop(
-            affix::inject(Arc::new(conn))
+            affix::inject(conn.clone())
                 .inject(captcha_storage.clone())
-                .insert("secret_key", Arc::new(secret_key))
+                .inject(captcha_guard)
+                .inject(Arc::new(chat_limiter))
+                .inject(Arc::new(token_lifetimes))
+                .inject(Arc::new(password_policy))
+                .inject(Arc::new(jwt_keys))
+                .inject(Arc::new(game_clock))
+                .inject(Arc::new(matchmaking))
+                .inject(Arc::new(webhook_policy))
                 .insert("max_online_games", Arc::new(max_online_games))
-                .insert("move_period", Arc::new(move_period)),
+                .insert("unique_pair_games", Arc::new(unique_pair_games))
+                .insert("daily_games_limit", Arc::new(daily_games_limit))
+                .insert("min_rated_games", Arc::new(min_rated_games))
+                .insert("leaderboard_tiebreakers", Arc::new(leaderboard_tiebreakers))
+                .insert("random_opening_moves", Arc::new(random_opening_moves))
+                .insert("token_expiry_policy", Arc::new(token_expiry_policy))
+                .insert(
+                    "token_expiring_before",
+                    Arc::new(token_expiring_before.unwrap_or(DEFAULT_TOKEN_EXPIRING_BEFORE)),
+                )
+                .insert("ws_allowed_origins", Arc::new(ws_allowed_origins))
+                .insert(
+                    "duplicate_connection_policy",
+                    Arc::new(duplicate_connection_policy),
+                )
+                .insert("fresh_auth_period", Arc::new(fresh_auth_period))
+                .insert(
+                    "invite_ttl",
+                    Arc::new(invite_ttl.unwrap_or(DEFAULT_INVITE_TTL)),
+                )
+                .insert("persist_chat", Arc::new(persist_chat))
+                .insert("trust_proxy", Arc::new(trust_proxy)),
         )
+        // Unlimited routes
+        .push(unlimited_router)
         // Unauthorized routes
         .push(
             Router::new()
@@ -158,33 +491,205 @@ pub fn service(
                 .push(
                     Router::with_path("auth")
                         .push(
-                            Router::with_hoop(captcha_middleware)
-                                .push(Router::with_path("signup").post(jwt::signup))
-                                .push(Router::with_path("signin").post(jwt::signin)),
+                            Router::with_hoop(auth_rate_limits.sign).push(
+                                Router::with_hoop(captcha_middleware)
+                                    .push(Router::with_path("guest").post(jwt::signin_guest))
+                                    .push(Router::with_path("signup").post(jwt::signup))
+                                    .push(Router::with_path("signin").post(jwt::signin)),
+                            ),
                         )
-                        .push(Router::with_path("captcha").get(jwt::captcha)),
+                        .push(Router::with_path("captcha/provider").get(jwt::captcha_provider))
+                        .push(image_captcha_router)
+                        .push(
+                            Router::with_path("usernames-available")
+                                .hoop(usernames_limiter)
+                                .post(jwt::usernames_available),
+                        ),
                 )
-                .push(Router::with_path("user").get(user::get_user_info))
+                .push(
+                    Router::with_path("user")
+                        .get(user::get_user_info)
+                        .push(Router::with_path("search").get(user::search_users))
+                        .push(Router::with_path("<uuid>/streaks").get(user::get_user_streaks))
+                        .push(Router::with_path("<uuid>/stats").get(user::get_user_stats))
+                        .push(Router::with_path("<uuid>/games").get(user::get_user_games))
+                        .push(Router::with_path("<uuid>/heatmap").get(user::get_user_heatmap))
+                        .push(Router::with_path("<uuid>/activity").get(user::get_user_activity))
+                        .push(
+                            Router::with_path("<uuid>/achievements")
+                                .get(user::get_user_achievements),
+                        )
+                        .push(Router::with_path("<uuid>/rating").get(user::get_user_rating))
+                        .push(Router::with_path("<uuid>/rank").get(user::get_user_rank)),
+                )
+                .push(Router::with_path("leaderboard").get(user::get_leaderboard))
+                .push(Router::with_path("seasons").get(season::get_seasons))
                 .push(Router::with_path("profiles/<uuid>").get(user::get_user_profile_image))
+                .push(Router::with_path("game/decode").post(game::decode_board))
+                .push(Router::with_path("game/evaluate").post(game::evaluate_board))
+                .push(Router::with_path("game/validate").post(game::validate_game))
                 .push(Router::with_path("game/<uuid>").get(game::get_game_by_uuid))
-                .push(Router::with_path("games").get(game::get_lastest_games)),
+                .push(Router::with_path("game/<uuid>/analysis").get(game::get_game_analysis))
+                .push(Router::with_path("game/<uuid>/replay").get(game::get_game_replay))
+                .push(Router::with_path("tournaments/<uuid>").get(tournament::get_tournament))
+                .push(Router::with_path("games").get(game::get_lastest_games))
+                .push(Router::with_path("games/recent").get(game::get_recent_games)),
         )
         // Authorized routes
         .push(
             Router::new()
                 .hoop(auth_limiter)
                 .hoop(auth_handler)
+                .hoop(reject_revoked_tokens)
                 .hoop(add_server_headers)
                 .push(
-                    Router::with_path("auth").push(Router::with_path("refresh").get(jwt::refresh)),
+                    Router::with_path("auth")
+                        .push(
+                            Router::with_path("refresh")
+                                .get(jwt::refresh)
+                                .push(Router::with_path("info").get(jwt::refresh_info)),
+                        )
+                        .push(Router::with_path("logout").post(jwt::logout))
+                        .push(Router::with_path("revoke-all").post(jwt::revoke_all_tokens))
+                        .push(Router::with_path("password").put(jwt::change_password))
+                        .push(
+                            Router::with_path("confirm")
+                                .hoop(auth_rate_limits.confirm)
+                                .post(jwt::confirm_auth),
+                        )
+                        .push(
+                            Router::with_path("upgrade")
+                                .hoop(upgrade_captcha_middleware)
+                                .post(jwt::upgrade_guest),
+                        )
+                        .push(
+                            Router::with_path("me")
+                                .get(jwt::current_user)
+                                .delete(jwt::delete_account),
+                        ),
                 )
                 .push(
                     Router::with_path("user")
                         .put(user::update_user)
                         .delete(user::delete_user)
                         .push(Router::with_path("reset_password").post(user::reset_user_password))
-                        .push(Router::with_path("me").get(user::get_me)),
+                        .push(Router::with_path("me").get(user::get_me))
+                        .push(
+                            Router::with_path("export")
+                                .get(user::get_data_export)
+                                .post(user::create_data_export)
+                                .push(
+                                    Router::with_path("download").get(user::download_data_export),
+                                ),
+                        )
+                        .push(
+                            Router::with_path("username")
+                                .hoop(require_role(UserRole::User))
+                                .put(user::change_username),
+                        )
+                        .push(Router::with_path("2fa/enable").post(user::enable_totp))
+                        .push(Router::with_path("2fa/confirm").post(user::confirm_totp))
+                        .push(Router::with_path("2fa/disable").post(user::disable_totp))
+                        .push(
+                            Router::with_path("<uuid>/role")
+                                .hoop(require_role(UserRole::Admin))
+                                .put(user::set_user_role),
+                        ),
                 )
+                .push(
+                    Router::with_path("blocks")
+                        .get(user::get_blocked_users)
+                        .push(
+                            Router::with_path("<uuid>")
+                                .post(user::block_user)
+                                .delete(user::unblock_user),
+                        ),
+                )
+                .push(
+                    Router::with_path("friends")
+                        .get(friend::get_friends)
+                        .push(
+                            Router::with_path("requests")
+                                .get(friend::get_friend_requests)
+                                .post(friend::send_friend_request)
+                                .push(
+                                    Router::with_path("<uuid>/accept")
+                                        .post(friend::accept_friend_request),
+                                )
+                                .push(
+                                    Router::with_path("<uuid>/decline")
+                                        .post(friend::decline_friend_request),
+                                ),
+                        )
+                        .push(Router::with_path("<uuid>").delete(friend::remove_friend))
+                        .push(Router::with_path("<uuid>/invite").post(friend::invite_friend)),
+                )
+                .push(
+                    Router::with_path("notifications")
+                        .get(notification::get_notifications)
+                        .push(Router::with_path("stream").get(notification::stream_notifications))
+                        .push(
+                            Router::with_path("read")
+                                .post(notification::mark_all_notifications_read),
+                        )
+                        .push(
+                            Router::with_path("<uuid>/read")
+                                .post(notification::mark_notification_read),
+                        ),
+                )
+                .push(
+                    Router::with_path("game/matchmake")
+                        .push(Router::with_path("pause").post(game::pause_matchmaking))
+                        .push(Router::with_path("resume").post(game::resume_matchmaking)),
+                )
+                .push(
+                    Router::with_path("admin")
+                        .hoop(require_role(UserRole::Moderator))
+                        .push(
+                            Router::with_path("users/<uuid>/ban")
+                                .post(admin::ban_user)
+                                .delete(admin::unban_user),
+                        )
+                        .push(Router::with_path("users/<uuid>/warn").post(admin::warn_user))
+                        .push(Router::with_path("games/<uuid>/end").post(admin::end_game))
+                        .push(Router::with_path("signups").get(admin::get_recent_signups))
+                        .push(
+                            Router::with_path("audit")
+                                .hoop(require_role(UserRole::Admin))
+                                .get(admin::get_audit_log),
+                        )
+                        .push(
+                            Router::with_path("captchas")
+                                .hoop(require_role(UserRole::Admin))
+                                .delete(admin::delete_captchas),
+                        )
+                        .push(
+                            Router::with_path("jwt-keys/rotate")
+                                .hoop(require_role(UserRole::Admin))
+                                .post(admin::rotate_jwt_key),
+                        )
+                        .push(
+                            Router::with_path("webhooks")
+                                .hoop(require_role(UserRole::Admin))
+                                .get(webhook::get_webhooks)
+                                .post(webhook::create_webhook)
+                                .push(Router::with_path("<uuid>").delete(webhook::delete_webhook))
+                                .push(
+                                    Router::with_path("<uuid>/deliveries")
+                                        .get(webhook::get_webhook_deliveries),
+                                ),
+                        ),
+                )
+                .push(
+                    Router::with_path("tournaments")
+                        .hoop(require_role(UserRole::User))
+                        .post(tournament::create_tournament)
+                        .push(Router::with_path("<uuid>/join").post(tournament::join_tournament)),
+                )
+                .push(Router::with_path("game/invite").post(game::create_invite))
+                .push(Router::with_path("game/join/<code>").post(game::join_invite))
+                .push(Router::with_path("game/<uuid>/moves").get(game::get_game_moves))
+                .push(Router::with_path("game/<uuid>/spectate").goal(xo::spectate))
                 .push(Router::with_path("xo").goal(xo::user_connected)),
         );
 
@@ -218,18 +723,28 @@ pub fn service(
                 .keywords("XORS, XO, Game, Multiplayer, API, Rust, Salvo, SeaORM")
                 .into_router("/api-doc/swagger-ui"),
         );
+    // The requests are labeled by the route patterns, so the recorder is created after the routes
+    let record_request = metrics::RecordRequest::new(&router);
+    let router = router.hoop(record_request);
+
+    Lazy::force(&server::SERVER_STARTED_AT);
 
     tokio::spawn({
-        log::info!("Start the captcha cleaner...");
+        log::info!("Start the captcha and the expired rows cleaner...");
         let cleanner_storage = captcha_storage.clone();
         async move {
             let captcha_expired_after = Duration::from_secs(60 * 5);
-            let clean_interval = Duration::from_secs(60);
+            let clean_interval =
+                Duration::from_secs(prune_interval.unwrap_or(DEFAULT_PRUNE_INTERVAL));
 
             loop {
                 if let Err(err) = cleanner_storage.clear_expired(captcha_expired_after).await {
                     log::error!("Failed to clean captcha storage: {err}")
                 }
+                match db_utils::prune_expired(&conn).await {
+                    Ok(pruned) => log::info!("Pruned {pruned} expired rows"),
+                    Err(err) => log::error!("Failed to prune the expired rows: {err}"),
+                }
                 tokio::time::sleep(clean_interval).await;
             }
         }
@@ -238,6 +753,7 @@ pub fn service(
     (
         Service::new(router).catcher(
             Catcher::default()
+                .hoop(localize_messages)
                 .hoop(handle404)
                 .hoop(handle_server_errors),
         ),
@@ -0,0 +1,271 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use entity::prelude::*;
+use futures_util::StreamExt;
+use once_cell::sync::Lazy;
+use salvo::{
+    oapi::extract::{PathParam, QueryParam},
+    prelude::*,
+    sse::{SseEvent, SseKeepAlive},
+};
+use sea_orm::DatabaseConnection;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use uuid::Uuid;
+
+use crate::{db_utils, errors::ApiResult, schemas::*};
+
+use super::exts::UserExt;
+
+/// The notifications streams of the users, a user can have multiple streams.
+static STREAMS: Lazy<Mutex<HashMap<Uuid, Vec<UnboundedSender<NotificationSchema>>>>> =
+    Lazy::new(Mutex::default);
+
+/// Stores the notification of the user then sends it to the user's notifications streams.
+/// The storing errors are logged, the notification is not sent if it's not stored.
+pub(crate) async fn notify(conn: &DatabaseConnection, user_uuid: Uuid, data: NotificationData) {
+    match db_utils::create_notification(conn, user_uuid, &data).await {
+        Ok(notification) => {
+            let notification = NotificationSchema::from(notification);
+            if let Some(streams) = STREAMS
+                .lock()
+                .expect("The lock is not poisoned")
+                .get_mut(&user_uuid)
+            {
+                streams.retain(|tx| tx.send(notification.clone()).is_ok());
+            }
+        }
+        Err(err) => log::error!("Failed to notify the user {user_uuid}: {err}"),
+    }
+}
+
+/// Notifies the players of the tournament match that it's ready.
+async fn notify_tournament_match(
+    conn: &DatabaseConnection,
+    tournament: &TournamentModel,
+    tournament_match: &TournamentMatchModel,
+) {
+    let (Some(x_player), Some(o_player)) = (tournament_match.x_player, tournament_match.o_player)
+    else {
+        return;
+    };
+    for (player, opponent) in [(x_player, o_player), (o_player, x_player)] {
+        notify(
+            conn,
+            player,
+            NotificationData::TournamentMatch {
+                tournament: tournament.uuid,
+                tournament_name: tournament.name.clone(),
+                round: tournament_match.round,
+                opponent,
+            },
+        )
+        .await;
+    }
+}
+
+/// Notifies the players of the tournament first round matches, after its bracket is generated.
+pub(crate) async fn tournament_started(conn: &DatabaseConnection, tournament_uuid: Uuid) {
+    let tournament = match db_utils::get_tournament(conn, tournament_uuid).await {
+        Ok(tournament) => tournament,
+        Err(err) => {
+            log::error!("Failed to get the tournament {tournament_uuid}: {err}");
+            return;
+        }
+    };
+    match db_utils::get_tournament_matches(conn, tournament_uuid).await {
+        Ok(matches) => {
+            for tournament_match in matches.iter().filter(|m| m.round == 1) {
+                notify_tournament_match(conn, &tournament, tournament_match).await;
+            }
+        }
+        Err(err) => log::error!("Failed to get the tournament {tournament_uuid} matches: {err}"),
+    }
+}
+
+/// Notifies the players of their next match after the ended tournament game: the replayed match if the game
+/// ended with a draw, otherwise the next round match if it's ready now.
+pub(crate) async fn tournament_game_ended(conn: &DatabaseConnection, game_uuid: Uuid) {
+    let next_match = match db_utils::get_next_tournament_match(conn, game_uuid).await {
+        Ok(Some(next_match)) => next_match,
+        Ok(None) => return,
+        Err(err) => {
+            log::error!("Failed to get the next tournament match of the game {game_uuid}: {err}");
+            return;
+        }
+    };
+    match db_utils::get_tournament(conn, next_match.tournament_uuid).await {
+        Ok(tournament) => notify_tournament_match(conn, &tournament, &next_match).await,
+        Err(err) => log::error!("Failed to get the tournament of the game {game_uuid}: {err}"),
+    }
+}
+
+/// Stream the notifications.
+///
+/// This endpoint will stream the user's new notifications as server-sent events, the event name is the
+/// notification kind and the event id is the notification uuid, the event data is the notification. The stream is
+/// closed when the token expires, reconnect with a new token and get the missed notifications from the
+/// `/notifications` endpoint.
+#[endpoint(
+    operation_id = "stream_notifications",
+    tags("Notifications"),
+    responses(
+        (status_code = 200, description = "The notifications stream", content_type = "text/event-stream", body = NotificationSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn stream_notifications(depot: &mut Depot, res: &mut Response) -> ApiResult<()> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+    let expires_in = depot.jwt_claims().exp - chrono::Utc::now().timestamp();
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    {
+        let mut streams = STREAMS.lock().expect("The lock is not poisoned");
+        let user_streams = streams.entry(user.uuid).or_default();
+        user_streams.retain(|tx| !tx.is_closed());
+        user_streams.push(tx);
+    }
+    let events = UnboundedReceiverStream::new(rx)
+        .map(|notification| {
+            SseEvent::default()
+                .name(notification.kind.to_string())
+                .id(notification.uuid.to_string())
+                .json(&notification)
+        })
+        .take_until(tokio::time::sleep(Duration::from_secs(
+            u64::try_from(expires_in).unwrap_or_default(),
+        )));
+    SseKeepAlive::new(events).stream(res);
+    Ok(())
+}
+
+/// Get the notifications.
+///
+/// This endpoint will return the user's notifications, sorted by their date (newest first). Each page has 20
+/// notifications.
+#[endpoint(
+    operation_id = "get_notifications",
+    tags("Notifications"),
+    parameters(
+        ("unread" = Option<bool>, Query, description = "Only the unread notifications, default is `false`"),
+        ("page" = Option<u64>, Query, description = "The page number, starting from 0, default is `0`"),
+    ),
+    responses(
+        (status_code = 200, description = "The notifications", content_type = "application/json", body = Vec<NotificationSchema>),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn get_notifications(
+    depot: &mut Depot,
+    unread: QueryParam<bool, false>,
+    page: QueryParam<u64, false>,
+) -> ApiResult<Json<Vec<NotificationSchema>>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    Ok(Json(
+        db_utils::get_notifications(
+            conn.as_ref(),
+            user.uuid,
+            unread.into_inner().unwrap_or_default(),
+            page.into_inner().unwrap_or_default(),
+        )
+        .await?
+        .into_iter()
+        .map(NotificationSchema::from)
+        .collect(),
+    ))
+}
+
+/// Mark a notification as read.
+///
+/// This endpoint will mark the user's notification as read, marking an already read notification does nothing.
+#[endpoint(
+    operation_id = "mark_notification_read",
+    tags("Notifications"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The uuid of the notification"),
+    ),
+    responses(
+        (status_code = 200, description = "The notification after marking it as read", content_type = "application/json", body = NotificationSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "Notification not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn mark_notification_read(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<NotificationSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    db_utils::mark_notification_read(conn.as_ref(), user.uuid, uuid.into_inner())
+        .await
+        .map(|notification| Json(notification.into()))
+}
+
+/// Mark all the notifications as read.
+///
+/// This endpoint will mark all the user's unread notifications as read.
+#[endpoint(
+    operation_id = "mark_all_notifications_read",
+    tags("Notifications"),
+    responses(
+        (status_code = 200, description = "The notifications have been marked as read", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn mark_all_notifications_read(depot: &mut Depot) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    let marked = db_utils::mark_all_notifications_read(conn.as_ref(), user.uuid).await?;
+    Ok(Json(MessageSchema::new(format!(
+        "{marked} notifications have been marked as read"
+    ))))
+}
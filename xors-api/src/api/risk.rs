@@ -0,0 +1,550 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash,
+    net::IpAddr,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use salvo::{async_trait, jwt_auth::JwtAuthDepotExt, Depot, FlowCtrl, Handler, Request, Response};
+use salvo_captcha::CaptchaDepotExt;
+use uuid::Uuid;
+
+use crate::{
+    errors::{ApiError, ApiResult},
+    schemas::CaptchaProviderKind,
+};
+
+use super::captcha_provider::{CaptchaProvider, ImageCaptchaProvider};
+use super::jwt::JwtClaims;
+
+/// How long the captcha degraded mode stays active after the last captcha failure.
+/// After it, the captcha is required again.
+pub const DEGRADED_MODE_PERIOD: Duration = Duration::from_secs(60 * 5);
+
+/// The signup risk assessor, it's decide if the signup request is suspicious and should solve a captcha.
+///
+/// Implement it to customize the signals, and pass it to the [`service`](super::service).
+pub trait RiskAssessor: Send + Sync + 'static {
+    /// Returns whether if the request is suspicious, suspicious requests must solve a captcha.
+    fn is_suspicious(&self, req: &Request) -> bool;
+}
+
+/// A risk assessor that consider all requests suspicious, so the captcha is always required.
+/// This is the default one.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AlwaysSuspicious;
+
+/// A risk assessor that consider the request suspicious if:
+/// - The client IP is unknown or in the bad IPs list.
+/// - The client IP made more than `max_attempts` signup attempts in the `window`.
+/// - The client IP made another signup attempt less than `min_interval` ago.
+///
+/// The client IP is the connection IP, or the `X-Forwarded-For` one with `trust_proxy`, see [`client_ip`].
+#[derive(Debug)]
+pub struct ActivityRiskAssessor {
+    max_attempts: usize,
+    window: Duration,
+    min_interval: Duration,
+    bad_ips: HashSet<IpAddr>,
+    trust_proxy: bool,
+    attempts: Mutex<HashMap<IpAddr, VecDeque<Instant>>>,
+}
+
+/// What to do when the captcha subsystem is failing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CaptchaFailurePolicy {
+    /// Keep requiring the captcha, the captcha routes will fail until it's back. (default)
+    #[default]
+    FailClosed,
+    /// Temporarily skip the captcha for the requests that pass a lower-friction activity check.
+    Degrade,
+}
+
+/// The captcha guard, decides which requests can skip the captcha and verifies the others with the captcha provider.
+/// It's also track the captcha failures to enter the degraded mode, if the failure policy allow it.
+pub struct CaptchaGuard {
+    risk_assessor: Arc<dyn RiskAssessor>,
+    provider: Arc<dyn CaptchaProvider>,
+    failure_policy: CaptchaFailurePolicy,
+    /// The lower-friction check used in the degraded mode.
+    fallback: ActivityRiskAssessor,
+    last_failure: Mutex<Option<Instant>>,
+    /// The instance-wide captcha creation budget.
+    creation_budget: Mutex<TokenBucket>,
+}
+
+/// A per-IP rate limiter, allows `max_requests` requests in a sliding `window` for each client IP.
+///
+/// Use it as a hoop, the requests over the limit are rejected with `429 Too Many Requests` and a `Retry-After`
+/// header, and they are not counted.
+#[derive(Debug)]
+pub struct IpRateLimiter {
+    requests: SlidingWindow<IpAddr>,
+    trust_proxy: bool,
+}
+
+/// A per-user rate limiter, allows `max_requests` requests in a sliding `window` for each user.
+///
+/// Use it as a hoop after the `JwtAuth` middleware, like the [`IpRateLimiter`].
+#[derive(Debug)]
+pub struct UserRateLimiter {
+    requests: SlidingWindow<Uuid>,
+}
+
+/// The rate limits of the auth routes, pass it to the [`service`](super::service).
+#[derive(Debug)]
+pub struct AuthRateLimits {
+    /// The per-IP limit of the signup and signin routes.
+    pub sign: IpRateLimiter,
+    /// The per-IP limit of the captcha route.
+    pub captcha: IpRateLimiter,
+    /// The per-user limit of the password confirmation route.
+    pub confirm: UserRateLimiter,
+}
+
+/// Allows `max_requests` requests in a sliding `window` for each key.
+#[derive(Debug)]
+struct SlidingWindow<K> {
+    max_requests: usize,
+    window: Duration,
+    requests: Mutex<HashMap<K, VecDeque<Instant>>>,
+}
+
+/// A token bucket, refills `rate` tokens per second up to `capacity` tokens.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RiskAssessor for AlwaysSuspicious {
+    fn is_suspicious(&self, _: &Request) -> bool {
+        true
+    }
+}
+
+impl ActivityRiskAssessor {
+    /// Create a new activity risk assessor.
+    pub fn new(
+        max_attempts: usize,
+        window: Duration,
+        min_interval: Duration,
+        bad_ips: HashSet<IpAddr>,
+    ) -> Self {
+        Self {
+            max_attempts,
+            window,
+            min_interval,
+            bad_ips,
+            trust_proxy: false,
+            attempts: Mutex::default(),
+        }
+    }
+
+    /// Sets whether to trust the `X-Forwarded-For` header, see [`client_ip`].
+    pub fn with_trust_proxy(self, trust_proxy: bool) -> Self {
+        Self {
+            trust_proxy,
+            ..self
+        }
+    }
+}
+
+impl RiskAssessor for ActivityRiskAssessor {
+    fn is_suspicious(&self, req: &Request) -> bool {
+        let Some(ip) = client_ip(req, self.trust_proxy) else {
+            return true;
+        };
+        if self.bad_ips.contains(&ip) {
+            log::info!("Signup from a bad IP: {ip}");
+            return true;
+        }
+
+        let now = Instant::now();
+        let mut attempts = self.attempts.lock().expect("The lock is not poisoned");
+        // Forget the attempts that are out of the window
+        attempts.retain(|_, ip_attempts| {
+            ip_attempts.retain(|attempt| now.duration_since(*attempt) < self.window);
+            !ip_attempts.is_empty()
+        });
+
+        let ip_attempts = attempts.entry(ip).or_default();
+        let is_rapid = ip_attempts
+            .back()
+            .is_some_and(|last| now.duration_since(*last) < self.min_interval);
+        ip_attempts.push_back(now);
+
+        is_rapid || ip_attempts.len() > self.max_attempts
+    }
+}
+
+/// Returns the client IP of the request.
+///
+/// With `trust_proxy` the last IP of the `X-Forwarded-For` header is used if it's present, which is the one added
+/// by the trusted proxy, the previous ones are sent by the client and can be spoofed. Only enable it when the
+/// server is behind a trusted proxy, otherwise the clients can spoof their IP.
+pub fn client_ip(req: &Request, trust_proxy: bool) -> Option<IpAddr> {
+    if trust_proxy {
+        let forwarded_ip = req
+            .headers()
+            .get_all("X-Forwarded-For")
+            .iter()
+            .rev()
+            .find_map(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next()?.trim().parse().ok());
+        if forwarded_ip.is_some() {
+            return forwarded_ip;
+        }
+    }
+    req.remote_addr().clone().into_std().map(|addr| addr.ip())
+}
+
+/// Returns the client IP of the request, the proxy is trusted if the server's `trust_proxy` is in the depot.
+pub(crate) fn depot_client_ip(req: &Request, depot: &Depot) -> Option<IpAddr> {
+    let trust_proxy = depot
+        .get::<Arc<bool>>("trust_proxy")
+        .is_ok_and(|trust_proxy| **trust_proxy);
+    client_ip(req, trust_proxy)
+}
+
+impl<K: Hash + Eq> SlidingWindow<K> {
+    fn new(max_requests: usize, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            requests: Mutex::default(),
+        }
+    }
+
+    /// Record a request of the `key`.
+    /// Returns the duration to wait before retrying if the limit is exceeded.
+    fn hit(&self, key: K) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut requests = self.requests.lock().expect("The lock is not poisoned");
+        // Forget the requests that are out of the window
+        requests.retain(|_, key_requests| {
+            key_requests.retain(|request| now.duration_since(*request) < self.window);
+            !key_requests.is_empty()
+        });
+
+        let key_requests = requests.entry(key).or_default();
+        if key_requests.len() >= self.max_requests {
+            return Err(key_requests.front().map_or(self.window, |oldest| {
+                self.window.saturating_sub(now.duration_since(*oldest))
+            }));
+        }
+        key_requests.push_back(now);
+        Ok(())
+    }
+}
+
+/// Rejects the request that exceeded the rate limit with `429 Too Many Requests`.
+fn reject_limited(req: &Request, res: &mut Response, ctrl: &mut FlowCtrl, retry_after: Duration) {
+    log::warn!("The rate limit of `{}` is exceeded", req.uri().path());
+    // Round up, so the client never retries before the window is freed
+    let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    ctrl.skip_rest();
+    res.render(ApiError::TooManyRequests(retry_after));
+}
+
+impl IpRateLimiter {
+    /// Create a new per-IP rate limiter.
+    pub fn new(max_requests: usize, window: Duration) -> Self {
+        Self {
+            requests: SlidingWindow::new(max_requests, window),
+            trust_proxy: false,
+        }
+    }
+
+    /// Sets whether to trust the `X-Forwarded-For` header, see [`client_ip`].
+    pub fn with_trust_proxy(self, trust_proxy: bool) -> Self {
+        Self {
+            trust_proxy,
+            ..self
+        }
+    }
+
+    /// Record a request from the `ip`.
+    /// Returns the duration to wait before retrying if the limit is exceeded.
+    pub fn hit(&self, ip: IpAddr) -> Result<(), Duration> {
+        self.requests.hit(ip)
+    }
+}
+
+#[async_trait]
+impl Handler for IpRateLimiter {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        _depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        let Some(ip) = client_ip(req, self.trust_proxy) else {
+            return;
+        };
+        if let Err(retry_after) = self.hit(ip) {
+            log::warn!("The client {ip} is rate limited");
+            reject_limited(req, res, ctrl, retry_after);
+        }
+    }
+}
+
+impl UserRateLimiter {
+    /// Create a new per-user rate limiter.
+    pub fn new(max_requests: usize, window: Duration) -> Self {
+        Self {
+            requests: SlidingWindow::new(max_requests, window),
+        }
+    }
+
+    /// Record a request from the user.
+    /// Returns the duration to wait before retrying if the limit is exceeded.
+    pub fn hit(&self, user_uuid: Uuid) -> Result<(), Duration> {
+        self.requests.hit(user_uuid)
+    }
+}
+
+#[async_trait]
+impl Handler for UserRateLimiter {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        let Some(user_uuid) = depot
+            .jwt_auth_data::<JwtClaims>()
+            .map(|jwt| jwt.claims.uuid)
+        else {
+            return;
+        };
+        if let Err(retry_after) = self.hit(user_uuid) {
+            log::warn!("The user {user_uuid} is rate limited");
+            reject_limited(req, res, ctrl, retry_after);
+        }
+    }
+}
+
+impl AuthRateLimits {
+    /// Returns whether the `X-Forwarded-For` header is trusted, see [`client_ip`].
+    pub(crate) fn trust_proxy(&self) -> bool {
+        self.sign.trust_proxy
+    }
+
+    /// Sets whether to trust the `X-Forwarded-For` header for all the limits, see [`client_ip`].
+    pub fn with_trust_proxy(self, trust_proxy: bool) -> Self {
+        Self {
+            sign: self.sign.with_trust_proxy(trust_proxy),
+            captcha: self.captcha.with_trust_proxy(trust_proxy),
+            ..self
+        }
+    }
+}
+
+impl Default for AuthRateLimits {
+    fn default() -> Self {
+        Self {
+            sign: IpRateLimiter::new(10, Duration::from_secs(60)),
+            captcha: IpRateLimiter::new(20, Duration::from_secs(60)),
+            confirm: UserRateLimiter::new(5, Duration::from_secs(15 * 60)),
+        }
+    }
+}
+
+impl FromStr for CaptchaFailurePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail_closed" => Ok(Self::FailClosed),
+            "degrade" => Ok(Self::Degrade),
+            _ => Err(format!(
+                "Invalid captcha failure policy `{s}`, must be `fail_closed` or `degrade`"
+            )),
+        }
+    }
+}
+
+impl CaptchaGuard {
+    /// Create a new captcha guard.
+    pub fn new(risk_assessor: Arc<dyn RiskAssessor>, failure_policy: CaptchaFailurePolicy) -> Self {
+        Self {
+            risk_assessor,
+            provider: Arc::new(ImageCaptchaProvider),
+            failure_policy,
+            fallback: ActivityRiskAssessor::new(
+                3,
+                Duration::from_secs(60 * 60),
+                Duration::from_secs(10),
+                HashSet::new(),
+            ),
+            last_failure: Mutex::default(),
+            creation_budget: Mutex::new(TokenBucket::new(20, 40)),
+        }
+    }
+
+    /// Sets the global captcha creation rate, `per_second` captchas per second with a `burst` capacity.
+    pub fn with_creation_rate(self, per_second: u32, burst: u32) -> Self {
+        Self {
+            creation_budget: Mutex::new(TokenBucket::new(per_second, burst)),
+            ..self
+        }
+    }
+
+    /// Sets whether the degraded mode check trusts the `X-Forwarded-For` header, see [`client_ip`].
+    pub fn with_trust_proxy(self, trust_proxy: bool) -> Self {
+        Self {
+            fallback: self.fallback.with_trust_proxy(trust_proxy),
+            ..self
+        }
+    }
+
+    /// Sets the captcha provider, the default is the [`ImageCaptchaProvider`].
+    pub fn with_provider(self, provider: Arc<dyn CaptchaProvider>) -> Self {
+        Self { provider, ..self }
+    }
+
+    /// Returns the captcha provider.
+    pub fn provider(&self) -> &dyn CaptchaProvider {
+        self.provider.as_ref()
+    }
+
+    /// Verifies the captcha of the request with the captcha provider, unless the request skipped it.
+    ///
+    /// The provider failures are reported, and its successes for the image captcha are reported by its creation.
+    pub async fn verify(&self, req: &Request, depot: &Depot) -> ApiResult<()> {
+        if matches!(
+            depot.get_captcha_state(),
+            Some(salvo_captcha::CaptchaState::Skipped)
+        ) {
+            return Ok(());
+        }
+        match self.provider.verify(req, depot).await {
+            Err(ApiError::InternalServer) => {
+                self.captcha_failed();
+                Err(ApiError::InternalServer)
+            }
+            Ok(()) if self.provider.kind() != CaptchaProviderKind::Image => {
+                self.captcha_succeeded();
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Take a captcha creation from the global budget.
+    /// Returns the duration to wait before retrying if the budget is exhausted.
+    pub fn take_creation(&self) -> Result<(), Duration> {
+        self.creation_budget
+            .lock()
+            .expect("The lock is not poisoned")
+            .take()
+    }
+
+    /// Returns the captcha failure policy.
+    pub fn failure_policy(&self) -> CaptchaFailurePolicy {
+        self.failure_policy
+    }
+
+    /// Returns whether if the degraded mode is active.
+    pub fn is_degraded(&self) -> bool {
+        self.last_failure
+            .lock()
+            .expect("The lock is not poisoned")
+            .is_some_and(|last_failure| last_failure.elapsed() < DEGRADED_MODE_PERIOD)
+    }
+
+    /// Report a captcha failure, will activate the degraded mode if the failure policy allow it.
+    pub fn captcha_failed(&self) {
+        log::error!("The captcha subsystem is failing");
+        if self.failure_policy == CaptchaFailurePolicy::Degrade {
+            let mut last_failure = self.last_failure.lock().expect("The lock is not poisoned");
+            if !last_failure.is_some_and(|last| last.elapsed() < DEGRADED_MODE_PERIOD) {
+                log::warn!("Captcha degraded mode is active, the captcha will be skipped for unsuspicious requests");
+            }
+            *last_failure = Some(Instant::now());
+        }
+    }
+
+    /// Report a captcha success, will deactivate the degraded mode if it's active.
+    pub fn captcha_succeeded(&self) {
+        if self
+            .last_failure
+            .lock()
+            .expect("The lock is not poisoned")
+            .take()
+            .is_some_and(|last| last.elapsed() < DEGRADED_MODE_PERIOD)
+        {
+            log::warn!("The captcha subsystem is back, captcha degraded mode is inactive");
+        }
+    }
+
+    /// Returns whether if the request can skip the captcha.
+    pub fn can_skip(&self, req: &Request) -> bool {
+        if self.is_degraded() {
+            return !self.fallback.is_suspicious(req);
+        }
+        // Only the unsuspicious signups and guests creations can skip the captcha
+        let path = req.uri().path();
+        (path.ends_with("/auth/signup") || path.ends_with("/auth/guest"))
+            && !self.risk_assessor.is_suspicious(req)
+    }
+}
+
+impl Default for CaptchaGuard {
+    fn default() -> Self {
+        Self::new(Arc::new(AlwaysSuspicious), CaptchaFailurePolicy::default())
+    }
+}
+
+impl TokenBucket {
+    /// Create a new full token bucket.
+    fn new(rate: u32, capacity: u32) -> Self {
+        Self {
+            rate: f64::from(rate),
+            capacity: f64::from(capacity.max(1)),
+            tokens: f64::from(capacity.max(1)),
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take a token, returns the duration until the next token if the bucket is empty.
+    fn take(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        self.tokens = (self.tokens
+            + now.duration_since(self.last_refill).as_secs_f64() * self.rate)
+            .min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        if self.rate == 0.0 {
+            return Err(Duration::MAX);
+        }
+        Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+    }
+}
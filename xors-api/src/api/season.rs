@@ -0,0 +1,128 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{db_utils, errors::ApiResult, schemas::*};
+
+use super::user::{Tiebreaker, DEFAULT_LEADERBOARD_TIEBREAKERS};
+
+use chrono::Duration;
+use salvo::prelude::*;
+use salvo::{oapi::endpoint, writing::Json};
+use sea_orm::DatabaseConnection;
+
+use std::sync::Arc;
+
+/// The days of each season, if it's not specified.
+pub const DEFAULT_SEASON_LENGTH: i64 = 90;
+
+/// The ratings soft reset factor at the seasons end, if it's not specified.
+pub const DEFAULT_SEASON_RESET_FACTOR: f64 = 0.5;
+
+/// The longest sleep of the seasons handler, it checks the current season at least once in this period.
+const SEASON_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// The ranked seasons policy.
+#[derive(Debug, Clone)]
+pub struct SeasonPolicy {
+    /// The season length.
+    pub length: Duration,
+    /// How much the ratings move towards the initial rating at the season end, between 0 and 1.
+    pub reset_factor: f64,
+    /// The tiebreakers of the archived leaderboards, they should be the live leaderboard tiebreakers.
+    pub tiebreakers: Vec<Tiebreaker>,
+}
+
+impl SeasonPolicy {
+    /// Checks that the policy makes sense, the length must be positive and the reset factor between 0 and 1.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.length <= Duration::zero() {
+            return Err("The season length must be positive".to_owned());
+        }
+        if !(0.0..=1.0).contains(&self.reset_factor) {
+            return Err("The season reset factor must be between 0 and 1".to_owned());
+        }
+        Ok(())
+    }
+}
+
+impl Default for SeasonPolicy {
+    fn default() -> Self {
+        Self {
+            length: Duration::days(DEFAULT_SEASON_LENGTH),
+            reset_factor: DEFAULT_SEASON_RESET_FACTOR,
+            tiebreakers: DEFAULT_LEADERBOARD_TIEBREAKERS.to_vec(),
+        }
+    }
+}
+
+/// Seasons handler, starts the first season and ends the current season when its end date passes, then starts the
+/// next one. See [`db_utils::end_season`].
+pub async fn seasons_handler(conn: DatabaseConnection, policy: SeasonPolicy) {
+    async fn inner(conn: &DatabaseConnection, policy: &SeasonPolicy) -> ApiResult<()> {
+        log::info!("Starting seasons handler");
+
+        loop {
+            let season = match db_utils::get_current_season(conn).await? {
+                Some(season) => season,
+                None => db_utils::start_season(conn, policy.length).await?,
+            };
+            let now = chrono::Utc::now().naive_utc();
+            if season.ends_at <= now {
+                db_utils::end_season(conn, season, policy).await?;
+                continue;
+            }
+            tokio::time::sleep(
+                (season.ends_at - now)
+                    .to_std()
+                    .unwrap_or_default()
+                    .min(SEASON_CHECK_INTERVAL),
+            )
+            .await;
+        }
+    }
+
+    loop {
+        if let Err(err) = inner(&conn, &policy).await {
+            log::error!("Seasons handler error: {err}");
+            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+        }
+    }
+}
+
+/// Get the ranked seasons.
+///
+/// This endpoint will return the seasons, the newest first. The current season is the one without `ended_at`,
+/// the leaderboards of the ended seasons are archived, see `/leaderboard?season={number}`.
+#[endpoint(
+    operation_id = "get_seasons",
+    tags("User"),
+    responses(
+        (status_code = 200, description = "The seasons", content_type = "application/json", body = Vec<SeasonSchema>),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn get_seasons(depot: &mut Depot) -> ApiResult<Json<Vec<SeasonSchema>>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+
+    Ok(Json(
+        db_utils::get_seasons(conn)
+            .await?
+            .into_iter()
+            .map(SeasonSchema::from)
+            .collect(),
+    ))
+}
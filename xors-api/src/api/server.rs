@@ -0,0 +1,88 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
+use salvo::oapi::endpoint;
+use salvo::writing::Json;
+use salvo::{http::StatusCode, Depot, Response};
+
+use super::risk::CaptchaGuard;
+use crate::schemas::*;
+
+/// The instant the server started at, used as the monotonic clock origin.
+pub(crate) static SERVER_STARTED_AT: Lazy<Instant> = Lazy::new(Instant::now);
+
+/// Get the server time.
+///
+/// This endpoint will return the current server time, use it to compute the offset between the client clock and the server clock.
+/// The offset should be used when rendering the move clocks, e.g. the `auto_play_after` timestamp of the `your_turn` event.
+///
+/// **Note**: This endpoint is not rate limited.
+#[endpoint(
+    operation_id = "get_server_time",
+    tags("Server"),
+    responses(
+        (status_code = 200, description = "The server time", content_type = "application/json", body = ServerTimeSchema),
+    ),
+)]
+pub async fn time() -> Json<ServerTimeSchema> {
+    let now = chrono::Utc::now();
+
+    Json(ServerTimeSchema::new(
+        now.timestamp(),
+        now.timestamp_millis(),
+        SERVER_STARTED_AT.elapsed().as_millis() as u64,
+    ))
+}
+
+/// Get the server health.
+///
+/// This endpoint will return the server health, the status is `degraded` if some subsystems are failing,
+/// e.g. the captcha degraded mode is active, and `unavailable` with `503` if the database is unreachable,
+/// so the orchestrators can stop routing the traffic to the server.
+///
+/// **Note**: This endpoint is not rate limited.
+#[endpoint(
+    operation_id = "get_server_health",
+    tags("Server"),
+    responses(
+        (status_code = 200, description = "The server health", content_type = "application/json", body = HealthSchema),
+        (status_code = 503, description = "The database is unreachable", content_type = "application/json", body = HealthSchema),
+    ),
+)]
+pub async fn health(depot: &mut Depot, res: &mut Response) -> Json<HealthSchema> {
+    let captcha_degraded = depot.obtain::<Arc<CaptchaGuard>>().unwrap().is_degraded();
+    let database = depot
+        .obtain::<Arc<sea_orm::DatabaseConnection>>()
+        .unwrap()
+        .ping()
+        .await
+        .map_err(|err| log::error!("The database is unreachable: {err}"))
+        .is_ok();
+    let status = if !database {
+        res.status_code(StatusCode::SERVICE_UNAVAILABLE);
+        HealthStatus::Unavailable
+    } else if captcha_degraded {
+        HealthStatus::Degraded
+    } else {
+        HealthStatus::Ok
+    };
+
+    Json(HealthSchema::new(status, database, captcha_degraded))
+}
@@ -0,0 +1,152 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{db_utils, errors::ApiResult, schemas::*, utils};
+
+use salvo::oapi::extract::{JsonBody, PathParam};
+use salvo::prelude::*;
+use salvo::{oapi::endpoint, writing::Json};
+use sea_orm::DatabaseConnection;
+use uuid::Uuid;
+
+use std::sync::Arc;
+
+use super::{exts::UserExt, notification};
+
+/// Returns the tournament schema of the tournament uuid.
+pub(crate) async fn tournament_schema(
+    conn: &DatabaseConnection,
+    tournament_uuid: Uuid,
+) -> ApiResult<TournamentSchema> {
+    let matches = db_utils::get_tournament_matches(conn, tournament_uuid).await?;
+    let match_games = db_utils::get_tournament_match_games(
+        conn,
+        matches
+            .iter()
+            .map(|tournament_match| tournament_match.id)
+            .collect(),
+    )
+    .await?;
+    Ok(TournamentSchema::from_tournament(
+        db_utils::get_tournament(conn, tournament_uuid).await?,
+        db_utils::get_tournament_players(conn, tournament_uuid).await?,
+        matches,
+        match_games,
+    ))
+}
+
+/// Create a tournament.
+///
+/// The tournament registration is open after creating it, the host is not registered automatically.
+/// When the registration is full, the single-elimination bracket is generated with the players in a random order.
+#[endpoint(
+    operation_id = "create_tournament",
+    tags("Tournament"),
+    responses(
+        (status_code = 200, description = "The tournament has been created", content_type = "application/json", body = TournamentSchema),
+        (status_code = 400, description = "The tournament name or size is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn create_tournament(
+    depot: &mut Depot,
+    new_tournament: JsonBody<NewTournamentSchema>,
+) -> ApiResult<Json<TournamentSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap().as_ref();
+    let user = depot.user(conn).await?;
+    let new_tournament = new_tournament.into_inner();
+    utils::validate_new_tournament(&new_tournament)?;
+
+    let tournament = db_utils::create_tournament(conn, user.uuid, new_tournament).await?;
+    Ok(Json(TournamentSchema::from_tournament(
+        tournament,
+        Vec::new(),
+        Vec::new(),
+        Vec::new(),
+    )))
+}
+
+/// Register in a tournament.
+///
+/// The registration is closed when it's full, the last registered player generates the bracket. The players
+/// receive a `tournament_match` notification when their matches are ready.
+/// Play your matches with the `play_tournament` XO websocket event.
+#[endpoint(
+    operation_id = "join_tournament",
+    tags("Tournament"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The tournament uuid"),
+    ),
+    responses(
+        (status_code = 200, description = "The tournament after the registration", content_type = "application/json", body = TournamentSchema),
+        (status_code = 400, description = "The uuid is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "Tournament not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "The tournament registration is closed", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "You are already registered in the tournament", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn join_tournament(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<TournamentSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap().as_ref();
+    let user = depot.user(conn).await?;
+    let tournament_uuid = uuid.into_inner();
+
+    if db_utils::join_tournament(conn, tournament_uuid, user.uuid).await? {
+        notification::tournament_started(conn, tournament_uuid).await;
+    }
+    tournament_schema(conn, tournament_uuid).await.map(Json)
+}
+
+/// Get a tournament.
+///
+/// This endpoint will return the tournament with its players, bracket matches and standings.
+#[endpoint(
+    operation_id = "get_tournament",
+    tags("Tournament"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The tournament uuid"),
+    ),
+    responses(
+        (status_code = 200, description = "The tournament", content_type = "application/json", body = TournamentSchema),
+        (status_code = 400, description = "The uuid is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "Tournament not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn get_tournament(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<TournamentSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap().as_ref();
+
+    tournament_schema(conn, uuid.into_inner()).await.map(Json)
+}
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::api::exts::*;
+use crate::api::{audit::AuditEvent, exts::*};
 use crate::{db_utils, utils};
 use crate::{
     errors::{ApiError, ApiResult},
@@ -23,6 +23,9 @@ use crate::{
 
 use base64::Engine;
 use entity::prelude::*;
+use futures_util::StreamExt;
+use salvo::http::HeaderValue;
+use salvo::hyper::header;
 use salvo::oapi::extract::{JsonBody, PathParam, QueryParam};
 use salvo::prelude::*;
 use salvo::{oapi::endpoint, writing::Json};
@@ -30,8 +33,51 @@ use sea_orm::DatabaseConnection;
 use uuid::Uuid;
 
 use std::path::Path;
+use std::str::FromStr;
 use std::sync::Arc;
 
+/// The leaderboard tiebreakers, if they're not specified.
+pub const DEFAULT_LEADERBOARD_TIEBREAKERS: [Tiebreaker; 4] = [
+    Tiebreaker::MostWins,
+    Tiebreaker::FewestGames,
+    Tiebreaker::EarliestRating,
+    Tiebreaker::Uuid,
+];
+
+/// A leaderboard tiebreaker, it orders the users with the same rating. The tiebreakers are applied in their
+/// configured order, the users that are still tied are ordered by their join date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tiebreaker {
+    /// The user with more won games first.
+    MostWins,
+    /// The user with fewer finished games first.
+    FewestGames,
+    /// The user that reached its rating first, by its last rating change, first.
+    EarliestRating,
+    /// The older account first.
+    OldestAccount,
+    /// The user with the smaller uuid first.
+    Uuid,
+}
+
+impl FromStr for Tiebreaker {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "most_wins" => Ok(Self::MostWins),
+            "fewest_games" => Ok(Self::FewestGames),
+            "earliest_rating" => Ok(Self::EarliestRating),
+            "oldest_account" => Ok(Self::OldestAccount),
+            "uuid" => Ok(Self::Uuid),
+            _ => Err(format!(
+                "Invalid tiebreaker `{s}`, expected `most_wins`, `fewest_games`, `earliest_rating`, \
+                 `oldest_account` or `uuid`"
+            )),
+        }
+    }
+}
+
 /// Get me, the user that make the request.
 ///
 /// This endpoint will return the user that make the request.
@@ -84,6 +130,56 @@ pub async fn get_user_info(
         .map(Json)
 }
 
+/// Search for users.
+///
+/// This endpoint will return the users that their usernames contain the search query (case insensitive),
+/// ranked by the relevance, the exact username first, then the usernames that start with the query, then the rest.
+/// Each page has 10 users.
+#[endpoint(
+    operation_id = "search_users",
+    tags("User"),
+    parameters(
+        ("q" = String, Query, description = "The search query, at least 2 characters"),
+        ("names" = Option<bool>, Query, description = "Search in the display names too, default is `false`"),
+        ("page" = Option<u64>, Query, description = "The page number, starting from 0, default is `0`"),
+    ),
+    responses(
+        (status_code = 200, description = "The found users", content_type = "application/json", body = Vec<UserSchema>),
+        (status_code = 400, description = "Invalid search query", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn search_users(
+    depot: &mut Depot,
+    q: QueryParam<String, true>,
+    names: QueryParam<bool, false>,
+    page: QueryParam<u64, false>,
+) -> ApiResult<Json<Vec<UserSchema>>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let query = q.into_inner().trim().to_lowercase();
+
+    if query.chars().count() < 2 {
+        return Err(ApiError::InvalidSearchQuery);
+    }
+
+    let users = db_utils::search_users(
+        conn,
+        &query,
+        names.into_inner().unwrap_or_default(),
+        page.into_inner().unwrap_or_default(),
+    )
+    .await?;
+    Ok(Json(
+        futures_util::stream::iter(users)
+            .then(|user| UserSchema::from_active_model(conn, user.into_active_model()))
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<ApiResult<_>>()?,
+    ))
+}
+
 /// Delete the user's account.
 ///
 /// This endpoint will delete the user's account and all the user's data. Forever.
@@ -96,6 +192,7 @@ pub async fn get_user_info(
         (status_code = 400, description = "Invalid password: The password is incorrect", content_type = "application/json", body = MessageSchema),
         (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
         (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "Reauthentication required", content_type = "application/json", body = MessageSchema),
         (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
         (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
         (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
@@ -108,14 +205,13 @@ pub async fn delete_user(
 ) -> ApiResult<Json<MessageSchema>> {
     let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
     let user = depot.user(conn.as_ref()).await?;
+    depot.require_fresh_auth()?;
 
-    if bcrypt::verify(
-        delete_user_schema.into_inner().password,
+    if utils::PasswordHasher::current().verify(
+        &delete_user_schema.into_inner().password,
         user.password_hash.as_ref(),
     )? {
-        UserEntity::delete(user.into_active_model())
-            .exec(conn.as_ref())
-            .await?;
+        db_utils::delete_user(conn.as_ref(), user.uuid).await?;
 
         Ok(Json(MessageSchema::new(
             "The user's account has been deleted".to_owned(),
@@ -178,6 +274,194 @@ pub async fn update_user(
     UserSchema::from_active_model(conn, user).await.map(Json)
 }
 
+/// Change the user's username.
+///
+/// This endpoint will change the username, it has the signup username rules and it must not be registered by another
+/// user (the usernames are not case sensitive, so only its case can be changed). The uuid and the tokens are kept, use
+/// the new username to signin. The guests can't change their username, they choose it when they upgrade.
+#[endpoint(
+    operation_id = "change_username",
+    tags("User"),
+    responses(
+        (status_code = 200, description = "The username has been changed", content_type = "application/json", body = UserSchema),
+        (status_code = 400, description = "The username is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The username does not change", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is a guest", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The username already exists", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn change_username(
+    req: &mut Request,
+    depot: &mut Depot,
+    new_username: JsonBody<ChangeUsernameSchema>,
+) -> ApiResult<Json<UserSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    // Note: The guests are rejected by the `require_role` middleware.
+    let user = depot.user(conn.as_ref()).await?;
+    let new_username = new_username.into_inner().username;
+    // The surrounding spaces are trimmed, the other spaces are rejected
+    utils::validate_user_signin(new_username.trim())?;
+
+    let old_username = user.username.clone();
+    let user = db_utils::change_username(conn.as_ref(), user, &new_username).await?;
+    AuditEvent::new(AuditAction::UsernameChange, req, depot)
+        .actor(*user.uuid.as_ref())
+        .target(old_username)
+        .record(conn)
+        .await;
+    UserSchema::from_active_model(conn, user).await.map(Json)
+}
+
+/// Request a data export.
+///
+/// This endpoint will start generating the export of everything the server stores about the user: the profile, the
+/// games with their moves and chat messages, the achievements, the rating changes, the friends, the blocked users,
+/// the notifications and the audit events of the user's actions. The export is generated in the background, check its
+/// status with `GET /user/export` and download it with `/user/export/download` when it's ready. The previous export
+/// is replaced, and the generated export is deleted after 24 hours.
+#[endpoint(
+    operation_id = "create_data_export",
+    tags("User"),
+    responses(
+        (status_code = 200, description = "The export is being generated", content_type = "application/json", body = DataExportSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "A data export is already being generated", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn create_data_export(depot: &mut Depot) -> ApiResult<Json<DataExportSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    let export = db_utils::create_data_export(conn.as_ref(), user.uuid).await?;
+    tokio::spawn({
+        let conn = conn.as_ref().clone();
+        let export = export.clone();
+        async move {
+            let data = match db_utils::export_user_data(&conn, export.user_uuid).await {
+                Ok(data) => serde_json::to_string(&data).map_err(ApiError::from),
+                Err(err) => Err(err),
+            };
+            let data = data
+                .map_err(|err| {
+                    log::error!(
+                        "Failed to export the data of user {}: {err}",
+                        export.user_uuid
+                    )
+                })
+                .ok();
+            if let Err(err) = db_utils::complete_data_export(&conn, export, data).await {
+                log::error!("Failed to complete the data export: {err}");
+            }
+        }
+    });
+    Ok(Json(DataExportSchema::from(export)))
+}
+
+/// Get the data export status.
+///
+/// This endpoint will return the status of the user's latest data export, see `POST /user/export`.
+#[endpoint(
+    operation_id = "get_data_export",
+    tags("User"),
+    responses(
+        (status_code = 200, description = "The data export status", content_type = "application/json", body = DataExportSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "Data export not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn get_data_export(depot: &mut Depot) -> ApiResult<Json<DataExportSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    db_utils::get_data_export(conn.as_ref(), user.uuid)
+        .await
+        .map(DataExportSchema::from)
+        .map(Json)
+}
+
+/// Download the data export.
+///
+/// This endpoint will return the user's ready data export as a JSON file, or as a gzip compressed JSON file if `gzip`
+/// is `true`.
+#[endpoint(
+    operation_id = "download_data_export",
+    tags("User"),
+    parameters(
+        ("gzip" = Option<bool>, Query, description = "Compress the export with gzip, default is `false`"),
+    ),
+    responses(
+        (status_code = 200, description = "The data export file", content_type = "application/json", body = UserDataExportSchema),
+        (status_code = 200, description = "The gzip compressed data export file", content_type = "application/gzip", body = Vec<u8>),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "Data export not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "The data export is not ready", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn download_data_export(
+    depot: &mut Depot,
+    res: &mut Response,
+    gzip: QueryParam<bool, false>,
+) -> ApiResult<()> {
+    use std::io::Write;
+
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    let export = db_utils::get_data_export(conn.as_ref(), user.uuid).await?;
+    let Some(data) = export.data else {
+        return Err(ApiError::DataExportNotReady);
+    };
+    let file_name = format!("xors-export-{}.json", user.username);
+    let (content_type, file_name, body) = if gzip.into_inner().unwrap_or_default() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(data.as_bytes())
+            .and_then(|_| encoder.finish())
+            .map(|body| ("application/gzip", format!("{file_name}.gz"), body))
+            .map_err(|err| {
+                log::error!("Failed to compress the data export: {err}");
+                ApiError::InternalServer
+            })?
+    } else {
+        ("application/json", file_name, data.into_bytes())
+    };
+
+    let headers = res.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("attachment; filename=\"{file_name}\""))
+            .map_err(|_| ApiError::InternalServer)?,
+    );
+    res.write_body(body).ok();
+    Ok(())
+}
+
 /// Reset the user's password.
 #[endpoint(
     operation_id = "reset_user_password",
@@ -190,6 +474,7 @@ pub async fn update_user(
         (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
         (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
         (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "Reauthentication required", content_type = "application/json", body = MessageSchema),
         (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
         (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
         (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
@@ -202,14 +487,22 @@ pub async fn reset_user_password(
 ) -> ApiResult<Json<MessageSchema>> {
     let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
     let user = depot.user(conn.as_ref()).await?;
+    depot.require_fresh_auth()?;
     let reset_password = reset_password.into_inner();
 
-    utils::validate_password(&reset_password.new_password)?;
-    if bcrypt::verify(&reset_password.new_password, user.password_hash.as_ref())? {
+    utils::validate_password(
+        &reset_password.new_password,
+        depot.obtain::<Arc<utils::PasswordPolicy>>().unwrap(),
+    )?;
+    if utils::PasswordHasher::current()
+        .verify(&reset_password.new_password, user.password_hash.as_ref())?
+    {
         return Err(ApiError::PasswordNotChanged);
     }
 
-    if bcrypt::verify(&reset_password.old_password, user.password_hash.as_ref())? {
+    if utils::PasswordHasher::current()
+        .verify(&reset_password.old_password, user.password_hash.as_ref())?
+    {
         db_utils::reset_password(conn.as_ref(), user, &reset_password.new_password).await?;
         Ok(Json(MessageSchema::new(
             "The user's password has been reset".to_owned(),
@@ -221,6 +514,181 @@ pub async fn reset_user_password(
     }
 }
 
+/// Change the user's role.
+///
+/// This endpoint will change the role of the user, only the admins can change the roles. The user's tokens keep the old
+/// role until they are refreshed.
+#[endpoint(
+    operation_id = "set_user_role",
+    tags("User"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The user's uuid"),
+    ),
+    responses(
+        (status_code = 200, description = "The user's role has been changed", content_type = "application/json", body = UserSchema),
+        (status_code = 400, description = "The uuid is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is not an admin", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn set_user_role(
+    req: &mut Request,
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+    role: JsonBody<UserRoleSchema>,
+) -> ApiResult<Json<UserSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    // Note: The role is checked by the `require_role` middleware.
+    let admin = depot.user(conn.as_ref()).await?;
+    let user_uuid = uuid.into_inner();
+
+    let mut user = db_utils::get_user(conn.as_ref(), user_uuid).await?;
+    user.role = Set(role.into_inner().role.to_string());
+    let user = user.save(conn.as_ref()).await?;
+    AuditEvent::new(AuditAction::RoleChange, req, depot)
+        .actor(admin.uuid)
+        .target(user_uuid)
+        .record(conn)
+        .await;
+    UserSchema::from_active_model(conn, user).await.map(Json)
+}
+
+/// Get the user's streaks.
+///
+/// This endpoint will return the user's current and longest wins and losts streaks, a draw ends the current streak.
+#[endpoint(
+    operation_id = "get_user_streaks",
+    tags("User"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The requested user's uuid"),
+    ),
+    responses(
+        (status_code = 200, description = "The user's streaks", content_type = "application/json", body = StreaksSchema),
+        (status_code = 400, description = "The uuid is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn get_user_streaks(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<StreaksSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+
+    db_utils::get_user(conn, uuid.into_inner())
+        .await
+        .map(|user| Json(StreaksSchema::from(user)))
+}
+
+/// Get the user's stats.
+///
+/// This endpoint will return the user's results, wins streaks, average game duration and rating with its trend. The
+/// stats are updated when the user's games end, the games against the AI opponent don't count.
+#[endpoint(
+    operation_id = "get_user_stats",
+    tags("User"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The requested user's uuid"),
+    ),
+    responses(
+        (status_code = 200, description = "The user's stats", content_type = "application/json", body = UserStatsSchema),
+        (status_code = 400, description = "The uuid is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn get_user_stats(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<UserStatsSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user_uuid = uuid.into_inner();
+
+    let user = db_utils::get_user(conn, user_uuid).await?;
+    Ok(Json(UserStatsSchema::new(
+        user,
+        db_utils::get_user_recent_ratings(conn, user_uuid).await?,
+    )))
+}
+
+/// Get the user's games.
+///
+/// This endpoint will return the user's ended games, sorted by the end date (newest first). Each page has 20 games,
+/// pass the `next_cursor` of the page as `before` to get the next one.
+/// - `opponent`: Only the games against the opponent.
+/// - `result`: Only the games with the result from the user's side, `win`, `loss` or `draw`.
+/// - `from` and `to`: Only the games that ended in the days range, both are included.
+///
+/// **Note**: If the O player or the X player is deleted, the game will return it as deleted user, which it's uuid is `00000000-0000-0000-0000-000000000000` and username is `Deleted`.
+#[endpoint(
+    operation_id = "get_user_games",
+    tags("User"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The requested user's uuid"),
+        ("opponent" = Option<Uuid>, Query, description = "The opponent's uuid"),
+        ("result" = Option<GameResult>, Query, description = "The game result from the user's side"),
+        ("from" = Option<chrono::NaiveDate>, Query, description = "The first day of the range"),
+        ("to" = Option<chrono::NaiveDate>, Query, description = "The last day of the range"),
+        ("before" = Option<Uuid>, Query, description = "The cursor of the page, the `next_cursor` of the previous page"),
+    ),
+    responses(
+        (status_code = 200, description = "The user's games", content_type = "application/json", body = UserGamesSchema),
+        (status_code = 400, description = "The uuid, the result or the date range is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "The cursor game is not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn get_user_games(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+    opponent: QueryParam<Uuid, false>,
+    result: QueryParam<String, false>,
+    from: QueryParam<chrono::NaiveDate, false>,
+    to: QueryParam<chrono::NaiveDate, false>,
+    before: QueryParam<Uuid, false>,
+) -> ApiResult<Json<UserGamesSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user_uuid = uuid.into_inner();
+    // Parsed here, the invalid results are rejected instead of ignored
+    let result = result
+        .into_inner()
+        .map(|result| GameResult::from_str(&result))
+        .transpose()?;
+
+    db_utils::get_user(conn, user_uuid).await?;
+    let games = db_utils::get_user_games(
+        conn,
+        user_uuid,
+        opponent.into_inner(),
+        result,
+        from.into_inner(),
+        to.into_inner(),
+        before.into_inner(),
+    )
+    .await?;
+    let next_cursor = (games.len() as u64 == db_utils::USER_GAMES_PAGE_SIZE)
+        .then(|| games.last().map(|game| game.uuid))
+        .flatten();
+    Ok(Json(UserGamesSchema {
+        games: futures_util::stream::iter(games)
+            .then(|game| GameSchema::from_game(conn, game))
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<ApiResult<_>>()?,
+        next_cursor,
+    }))
+}
+
 /// Returns the user's profile image.
 #[endpoint(
     operation_id = "get_user_profile_image",
@@ -252,3 +720,485 @@ pub async fn get_user_profile_image(uuid: PathParam<Uuid>) -> ApiResult<Json<Ima
         .encode(std::fs::read(image_path).map_err(|_| ApiError::InternalServer)?);
     Ok(Json(ImageSchema::new(image_base64)))
 }
+
+/// Get the user's first moves heatmap.
+///
+/// This endpoint will return how often the user's first move of a round landed on each cell, aggregated from the ended games.
+/// A user without games will have a zeroed heatmap.
+#[endpoint(
+    operation_id = "get_user_heatmap",
+    tags("User"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The requested user's uuid"),
+    ),
+    responses(
+        (status_code = 200, description = "The user's heatmap", content_type = "application/json", body = HeatmapSchema),
+        (status_code = 400, description = "The uuid is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn get_user_heatmap(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<HeatmapSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user_uuid = uuid.into_inner();
+
+    db_utils::get_user(conn, user_uuid).await?;
+    db_utils::get_user_heatmap(conn, user_uuid).await.map(Json)
+}
+
+/// Get the user's activity calendar.
+///
+/// This endpoint will return the number of the games the user started in each day of the range, the days without games have a zero count.
+/// - `from`: The first day of the range, in UTC. Default is 364 days before `to`.
+/// - `to`: The last day of the range, in UTC. Default is today.
+///
+/// The range can be at most 366 days.
+#[endpoint(
+    operation_id = "get_user_activity",
+    tags("User"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The requested user's uuid"),
+        ("from" = Option<chrono::NaiveDate>, Query, description = "The first day of the range, default is 364 days before `to`"),
+        ("to" = Option<chrono::NaiveDate>, Query, description = "The last day of the range, default is today"),
+    ),
+    responses(
+        (status_code = 200, description = "The user's activity", content_type = "application/json", body = Vec<ActivityDaySchema>),
+        (status_code = 400, description = "The uuid or the date range is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn get_user_activity(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+    from: QueryParam<chrono::NaiveDate, false>,
+    to: QueryParam<chrono::NaiveDate, false>,
+) -> ApiResult<Json<Vec<ActivityDaySchema>>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user_uuid = uuid.into_inner();
+    let to = to
+        .into_inner()
+        .unwrap_or_else(|| chrono::Utc::now().date_naive());
+    let from = from
+        .into_inner()
+        .unwrap_or_else(|| to - chrono::Duration::days(364));
+
+    db_utils::get_user(conn, user_uuid).await?;
+    db_utils::get_user_activity(conn, user_uuid, from, to)
+        .await
+        .map(Json)
+}
+
+/// Get the user's achievements.
+///
+/// This endpoint will return the achievements awarded to the user, the first awarded first.
+/// The achievements are awarded automatically when the user's games end:
+/// - `first_game`: Finish the first game.
+/// - `first_win`: Win the first game.
+/// - `five_win_streak`: Win 5 games in a row.
+/// - `ten_win_streak`: Win 10 games in a row.
+/// - `hundred_games`: Finish 100 games.
+#[endpoint(
+    operation_id = "get_user_achievements",
+    tags("User"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The requested user's uuid"),
+    ),
+    responses(
+        (status_code = 200, description = "The user's achievements", content_type = "application/json", body = Vec<AchievementSchema>),
+        (status_code = 400, description = "The uuid is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn get_user_achievements(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<Vec<AchievementSchema>>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user_uuid = uuid.into_inner();
+
+    db_utils::get_user(conn, user_uuid).await?;
+    Ok(Json(
+        db_utils::get_user_achievements(conn, user_uuid)
+            .await?
+            .into_iter()
+            .map(AchievementSchema::from)
+            .collect(),
+    ))
+}
+
+/// Get the user's rating.
+///
+/// This endpoint will return the user's current ELO rating and its changes, the oldest first.
+/// The new users start with a `1200` rating, and the ratings of both players are updated when their game ends.
+#[endpoint(
+    operation_id = "get_user_rating",
+    tags("User"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The requested user's uuid"),
+    ),
+    responses(
+        (status_code = 200, description = "The user's rating", content_type = "application/json", body = RatingSchema),
+        (status_code = 400, description = "The uuid is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn get_user_rating(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<RatingSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user_uuid = uuid.into_inner();
+
+    let user = db_utils::get_user(conn, user_uuid).await?;
+    Ok(Json(RatingSchema {
+        rating: user.rating.unwrap(),
+        history: db_utils::get_user_rating_changes(conn, user_uuid)
+            .await?
+            .into_iter()
+            .map(RatingChangeSchema::from)
+            .collect(),
+    }))
+}
+
+/// Get the user's rank.
+///
+/// This endpoint will return the user's position among the ranked users in the leaderboard order and its
+/// percentile, the percentage of the ranked users at or below its position. The users need a minimum of rated
+/// games to be ranked, the server's `min_rated_games`, the guests and the users with fewer rated games are
+/// `unranked`.
+#[endpoint(
+    operation_id = "get_user_rank",
+    tags("User"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The requested user's uuid"),
+    ),
+    responses(
+        (status_code = 200, description = "The user's rank", content_type = "application/json", body = RankSchema),
+        (status_code = 400, description = "The uuid is invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn get_user_rank(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<RankSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let min_rated_games = **depot.get::<Arc<u64>>("min_rated_games").unwrap();
+    let tiebreakers = depot
+        .get::<Arc<Vec<Tiebreaker>>>("leaderboard_tiebreakers")
+        .unwrap()
+        .clone();
+    let user_uuid = uuid.into_inner();
+
+    let user = db_utils::get_user(conn, user_uuid).await?;
+    let rated_games = db_utils::count_user_rated_games(conn, user_uuid).await?;
+    if user.role.as_ref() == &UserRole::Guest.to_string() || rated_games < min_rated_games {
+        return Ok(Json(RankSchema::unranked(rated_games, min_rated_games)));
+    }
+    Ok(Json(
+        match db_utils::get_user_rank(conn, user_uuid, min_rated_games, &tiebreakers).await? {
+            Some((rank, ranked_users)) => {
+                RankSchema::ranked(rank, ranked_users, rated_games, min_rated_games)
+            }
+            // The user's rated games are deleted in the meantime
+            None => RankSchema::unranked(rated_games, min_rated_games),
+        },
+    ))
+}
+
+/// Get the leaderboard.
+///
+/// This endpoint will return the users sorted by their rating, the highest first. Each page has 20 users.
+/// The users with the same rating are ordered by the server's tiebreakers, by default the user with more wins, then
+/// with fewer games, then that reached the rating first, then with the smaller uuid.
+/// With `season`, it returns the archived leaderboard of the ended season, with the ratings and the results at the
+/// season end. The leaderboard of the current season is the live one.
+#[endpoint(
+    operation_id = "get_leaderboard",
+    tags("User"),
+    parameters(
+        ("page" = Option<u64>, Query, description = "The page number, starting from 0, default is `0`"),
+        ("season" = Option<u32>, Query, description = "The season number, default is the current season, see `/seasons`"),
+    ),
+    responses(
+        (status_code = 200, description = "The leaderboard page", content_type = "application/json", body = Vec<LeaderboardEntrySchema>),
+        (status_code = 404, description = "Season not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+)]
+pub async fn get_leaderboard(
+    depot: &mut Depot,
+    page: QueryParam<u64, false>,
+    season: QueryParam<u32, false>,
+) -> ApiResult<Json<Vec<LeaderboardEntrySchema>>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let tiebreakers = depot
+        .get::<Arc<Vec<Tiebreaker>>>("leaderboard_tiebreakers")
+        .unwrap()
+        .clone();
+    let page = page.into_inner().unwrap_or_default();
+
+    if let Some(season) = season.into_inner() {
+        let season = db_utils::get_season(conn, season as i32).await?;
+        if season.ended_at.is_some() {
+            return Ok(Json(
+                db_utils::get_season_leaderboard(conn, season.number, page)
+                    .await?
+                    .into_iter()
+                    .map(|(standing, user)| LeaderboardEntrySchema::from_standing(standing, user))
+                    .collect(),
+            ));
+        }
+    }
+
+    Ok(Json(
+        db_utils::get_leaderboard(conn, page, &tiebreakers)
+            .await?
+            .into_iter()
+            .zip(page * db_utils::LEADERBOARD_PAGE_SIZE + 1..)
+            .map(|(user, rank)| LeaderboardEntrySchema::new(rank, user))
+            .collect(),
+    ))
+}
+
+/// Get the users blocked by the user that make the request.
+///
+/// The latest blocked user comes first.
+#[endpoint(
+    operation_id = "get_blocked_users",
+    tags("User"),
+    responses(
+        (status_code = 200, description = "The blocked users", content_type = "application/json", body = Vec<UserSchema>),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn get_blocked_users(depot: &mut Depot) -> ApiResult<Json<Vec<UserSchema>>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    let users = db_utils::get_blocked_users(conn, user.uuid).await?;
+    Ok(Json(
+        futures_util::stream::iter(users)
+            .then(|user| UserSchema::from_active_model(conn, user.into_active_model()))
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<ApiResult<_>>()?,
+    ))
+}
+
+/// Block a user.
+///
+/// The blocked user will not be matched with the user, and can't chat with the user.
+/// Blocking an already blocked user does nothing.
+#[endpoint(
+    operation_id = "block_user",
+    tags("User"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The uuid of the user to block"),
+    ),
+    responses(
+        (status_code = 200, description = "The user has been blocked", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "You can't block yourself", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn block_user(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    db_utils::block_user(conn, user.uuid, uuid.into_inner()).await?;
+    Ok(Json(MessageSchema::new(
+        "The user has been blocked".to_owned(),
+    )))
+}
+
+/// Unblock a user.
+///
+/// Unblocking a not blocked user does nothing.
+#[endpoint(
+    operation_id = "unblock_user",
+    tags("User"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The uuid of the user to unblock"),
+    ),
+    responses(
+        (status_code = 200, description = "The user has been unblocked", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn unblock_user(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    db_utils::unblock_user(conn, user.uuid, uuid.into_inner()).await?;
+    Ok(Json(MessageSchema::new(
+        "The user has been unblocked".to_owned(),
+    )))
+}
+
+/// Enable the two-factor authentication.
+///
+/// This endpoint will return a new TOTP secret and its `otpauth` URI to add it to an authenticator app, the
+/// two-factor authentication is activated after confirming a code of it with `/user/2fa/confirm`. Enabling it again
+/// before confirming replaces the secret.
+#[endpoint(
+    operation_id = "enable_totp",
+    tags("User"),
+    responses(
+        (status_code = 200, description = "The TOTP secret", content_type = "application/json", body = TotpSecretSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "Reauthentication required", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "The two-factor authentication is already enabled", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn enable_totp(depot: &mut Depot) -> ApiResult<Json<TotpSecretSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+    depot.require_fresh_auth()?;
+
+    if user.totp_enabled {
+        return Err(ApiError::TotpAlreadyEnabled);
+    }
+    let secret = utils::generate_totp_secret();
+    let otpauth_uri = utils::totp_uri(&user.username, &secret);
+    db_utils::set_totp_secret(conn.as_ref(), user, secret.clone()).await?;
+    Ok(Json(TotpSecretSchema::new(secret, otpauth_uri)))
+}
+
+/// Confirm the two-factor authentication.
+///
+/// This endpoint will activate the two-factor authentication after checking a code of the secret, and return the
+/// recovery codes. The recovery codes are shown once, each one can be used once instead of the TOTP code.
+#[endpoint(
+    operation_id = "confirm_totp",
+    tags("User"),
+    responses(
+        (status_code = 200, description = "The two-factor authentication has been enabled", content_type = "application/json", body = RecoveryCodesSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The two-factor authentication code is incorrect", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "The two-factor authentication is already enabled", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "There is no two-factor authentication to confirm", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn confirm_totp(
+    depot: &mut Depot,
+    confirm: JsonBody<ConfirmTotpSchema>,
+) -> ApiResult<Json<RecoveryCodesSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+
+    if user.totp_enabled {
+        return Err(ApiError::TotpAlreadyEnabled);
+    }
+    let Some(secret) = user.totp_secret.as_deref() else {
+        return Err(ApiError::TotpNotPending);
+    };
+    if !db_utils::use_totp_code(conn.as_ref(), user.uuid, secret, &confirm.into_inner().code)
+        .await?
+    {
+        return Err(ApiError::InvalidTotpCode);
+    }
+
+    let recovery_codes = utils::generate_recovery_codes();
+    db_utils::enable_totp(conn.as_ref(), user, &recovery_codes).await?;
+    Ok(Json(RecoveryCodesSchema::new(recovery_codes)))
+}
+
+/// Disable the two-factor authentication.
+///
+/// This endpoint will disable the two-factor authentication after checking a code of the authenticator app or a
+/// recovery code, the secret and the recovery codes are deleted.
+#[endpoint(
+    operation_id = "disable_totp",
+    tags("User"),
+    responses(
+        (status_code = 200, description = "The two-factor authentication has been disabled", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "Reauthentication required", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The two-factor authentication code is incorrect", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "User not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 409, description = "The two-factor authentication is not enabled", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn disable_totp(
+    depot: &mut Depot,
+    disable: JsonBody<DisableTotpSchema>,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    let user = depot.user(conn.as_ref()).await?;
+    depot.require_fresh_auth()?;
+
+    if !user.totp_enabled {
+        return Err(ApiError::TotpNotEnabled);
+    }
+    let secret = user
+        .totp_secret
+        .as_deref()
+        .expect("The enabled two-factor authentication has a secret");
+    let code = disable.into_inner().code;
+    if !db_utils::use_totp_code(conn.as_ref(), user.uuid, secret, &code).await?
+        && !db_utils::use_recovery_code(conn.as_ref(), user.uuid, &code).await?
+    {
+        return Err(ApiError::InvalidTotpCode);
+    }
+
+    db_utils::disable_totp(conn.as_ref(), user).await?;
+    Ok(Json(MessageSchema::new(
+        "The two-factor authentication has been disabled".to_owned(),
+    )))
+}
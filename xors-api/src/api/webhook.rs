@@ -0,0 +1,516 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    future::Future,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use entity::prelude::*;
+use http_body_util::Full;
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::{
+    client::legacy::{
+        connect::{dns::Name, HttpConnector},
+        Client,
+    },
+    rt::TokioExecutor,
+};
+use once_cell::sync::OnceCell;
+use salvo::{
+    hyper::{header, Method},
+    oapi::extract::{JsonBody, PathParam, QueryParam},
+    prelude::*,
+};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{db_utils, errors::ApiResult, schemas::*, utils};
+
+use super::{audit::AuditEvent, exts::UserExt};
+
+/// The maximum delivery attempts of a webhook payload.
+pub const MAX_WEBHOOK_ATTEMPTS: i16 = 5;
+
+/// The timeout of a delivery attempt.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The error of the attempts to the endpoints that resolve to the non-public addresses.
+const NON_PUBLIC_ADDRESS_ERROR: &str = "The endpoint resolves to a non-public address";
+
+/// The installed webhooks sender, the events are not sent without it.
+static WEBHOOK_SENDER: OnceCell<WebhookSender> = OnceCell::new();
+
+/// The webhooks registration and delivery policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WebhookPolicy {
+    /// The delay before the first retry of a failed attempt, it's doubled after each attempt.
+    pub retry_delay: Duration,
+    /// Whether the endpoints can be HTTP URLs and resolve to the loopback, private and link-local addresses,
+    /// for the local receivers.
+    pub allow_local_endpoints: bool,
+}
+
+impl WebhookPolicy {
+    /// Returns the delay before the retry of the failed attempt, it's doubled after each attempt.
+    fn retry_delay(&self, attempts: i16) -> Duration {
+        self.retry_delay * 2u32.pow(attempts.saturating_sub(1) as u32)
+    }
+}
+
+impl Default for WebhookPolicy {
+    fn default() -> Self {
+        Self {
+            retry_delay: Duration::from_secs(1),
+            allow_local_endpoints: false,
+        }
+    }
+}
+
+/// The DNS resolver of the webhooks client, it rejects the hosts that resolve to the non-public addresses,
+/// when resolving them for the connection, so the host can't change its addresses after the check.
+#[derive(Debug, Clone, Copy)]
+struct PublicResolver {
+    allow_local_endpoints: bool,
+}
+
+impl tower_service::Service<Name> for PublicResolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let allow_local_endpoints = self.allow_local_endpoints;
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .collect::<Vec<_>>();
+            if !allow_local_endpoints
+                && addrs
+                    .iter()
+                    .any(|addr| !utils::is_public_address(addr.ip()))
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    NON_PUBLIC_ADDRESS_ERROR,
+                ));
+            }
+            Ok(addrs.into_iter())
+        })
+    }
+}
+
+/// The webhooks sender, it delivers the signed payloads with its policy.
+pub struct WebhookSender {
+    client: Client<HttpsConnector<HttpConnector<PublicResolver>>, Full<Bytes>>,
+    policy: WebhookPolicy,
+}
+
+impl WebhookSender {
+    /// Creates the sender with the native root CA certificates, returns an error if there are none.
+    pub fn new(policy: WebhookPolicy) -> Result<Self, String> {
+        let mut http = HttpConnector::new_with_resolver(PublicResolver {
+            allow_local_endpoints: policy.allow_local_endpoints,
+        });
+        http.enforce_http(false);
+        let https = HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .map_err(|err| format!("No native root CA certificates found: {err}"))?;
+        let https = if policy.allow_local_endpoints {
+            https.https_or_http()
+        } else {
+            https.https_only()
+        };
+        Ok(Self {
+            client: Client::builder(TokioExecutor::new())
+                .build(https.enable_http1().wrap_connector(http)),
+            policy,
+        })
+    }
+
+    /// Sets the sender that the server uses, returns the sender back if there is already an installed one.
+    pub fn install(self) -> Result<(), Box<Self>> {
+        WEBHOOK_SENDER.set(self).map_err(Box::new)
+    }
+
+    /// Sends one attempt of the signed payload, returns the response status code or the error.
+    pub async fn send(
+        &self,
+        url: &str,
+        secret: &str,
+        event: WebhookEvent,
+        delivery_uuid: Uuid,
+        payload: &str,
+    ) -> Result<u16, String> {
+        let uri = url
+            .parse::<salvo::http::uri::Uri>()
+            .map_err(|err| err.to_string())?;
+        // The IP addresses hosts are not resolved
+        if !self.policy.allow_local_endpoints
+            && uri
+                .host()
+                .and_then(utils::host_address)
+                .is_some_and(|ip| !utils::is_public_address(ip))
+        {
+            return Err(NON_PUBLIC_ADDRESS_ERROR.to_owned());
+        }
+        let request = salvo::hyper::Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::USER_AGENT, "XORS-Webhook")
+            .header("X-Xors-Event", event.to_string())
+            .header("X-Xors-Delivery", delivery_uuid.to_string())
+            .header(
+                "X-Xors-Signature",
+                format!(
+                    "sha256={}",
+                    utils::webhook_signature(secret, payload.as_bytes())
+                ),
+            )
+            .body(Full::new(Bytes::from(payload.to_owned())))
+            .map_err(|err| err.to_string())?;
+
+        match tokio::time::timeout(WEBHOOK_TIMEOUT, self.client.request(request)).await {
+            Ok(Ok(response)) => Ok(response.status().as_u16()),
+            Ok(Err(err)) => Err(error_chain(&err)),
+            Err(_) => Err("The endpoint didn't respond in time".to_owned()),
+        }
+    }
+}
+
+/// Returns the error with its sources, the client errors hide the connection error in the sources.
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(&format!(": {err}"));
+        source = err.source();
+    }
+    message
+}
+
+/// Delivers the payload to the webhook, the failed attempts are retried with a backoff until
+/// [`MAX_WEBHOOK_ATTEMPTS`] attempts.
+async fn deliver(
+    conn: DatabaseConnection,
+    sender: &WebhookSender,
+    webhook: WebhookModel,
+    delivery: WebhookDeliveryModel,
+) {
+    let event = delivery.event.parse().expect("Is valid webhook event");
+    let mut delivery = delivery;
+    loop {
+        let (status_code, error) = match sender
+            .send(
+                &webhook.url,
+                &webhook.secret,
+                event,
+                delivery.uuid,
+                &delivery.payload,
+            )
+            .await
+        {
+            Ok(status_code) => (Some(status_code), None),
+            Err(err) => (None, Some(err)),
+        };
+        delivery = match db_utils::record_webhook_attempt(&conn, delivery, status_code, error).await
+        {
+            Ok(delivery) => delivery,
+            Err(err) => {
+                log::error!(
+                    "Failed to record the webhook {} attempt: {err}",
+                    webhook.uuid
+                );
+                return;
+            }
+        };
+        if delivery.delivered_at.is_some() {
+            return;
+        }
+        if delivery.attempts >= MAX_WEBHOOK_ATTEMPTS {
+            log::warn!(
+                "The webhook {} delivery {} failed after {} attempts",
+                webhook.uuid,
+                delivery.uuid,
+                delivery.attempts
+            );
+            return;
+        }
+        tokio::time::sleep(sender.policy.retry_delay(delivery.attempts)).await;
+    }
+}
+
+/// Sends the event to the webhooks that receive it, in the background. Each webhook has its delivery.
+pub(crate) fn dispatch(conn: &DatabaseConnection, event: WebhookEvent, data: impl Serialize) {
+    let Some(sender) = WEBHOOK_SENDER.get() else {
+        log::debug!(
+            "No webhooks sender is installed, the {} event is not sent",
+            event.to_string()
+        );
+        return;
+    };
+    let data = match serde_json::to_value(data) {
+        Ok(data) => data,
+        Err(err) => {
+            log::error!(
+                "Failed to serialize the {} webhook data: {err}",
+                event.to_string()
+            );
+            return;
+        }
+    };
+    let conn = conn.clone();
+    tokio::spawn(async move {
+        let webhooks = match db_utils::get_event_webhooks(&conn, event).await {
+            Ok(webhooks) => webhooks,
+            Err(err) => {
+                log::error!("Failed to get the {} webhooks: {err}", event.to_string());
+                return;
+            }
+        };
+        let created_at = chrono::Utc::now().naive_utc();
+        for webhook in webhooks {
+            let payload = WebhookPayload::new(Uuid::new_v4(), event, created_at, data.clone());
+            match db_utils::create_webhook_delivery(&conn, webhook.uuid, &payload).await {
+                Ok(delivery) => {
+                    tokio::spawn(deliver(conn.clone(), sender, webhook, delivery));
+                }
+                Err(err) => {
+                    log::error!(
+                        "Failed to create the webhook {} delivery: {err}",
+                        webhook.uuid
+                    )
+                }
+            }
+        }
+    });
+}
+
+/// Sends the `game.finished` event of the ended game, and the `tournament.completed` event if it's the
+/// tournament final.
+pub(crate) async fn game_finished(conn: &DatabaseConnection, game_uuid: Uuid) {
+    let game = match db_utils::get_game::<true>(conn, &game_uuid).await {
+        Ok(game) => GameSchema::from_game(conn, game).await,
+        Err(err) => Err(err),
+    };
+    match game {
+        Ok(game) => dispatch(conn, WebhookEvent::GameFinished, game),
+        Err(err) => log::error!("Failed to get the ended game {game_uuid}: {err}"),
+    }
+
+    match db_utils::get_game_tournament(conn, game_uuid).await {
+        Ok(Some(tournament)) if tournament.ended_at.is_some() => {
+            match super::tournament::tournament_schema(conn, tournament.uuid).await {
+                Ok(tournament) => dispatch(conn, WebhookEvent::TournamentCompleted, tournament),
+                Err(err) => log::error!("Failed to get the tournament {}: {err}", tournament.uuid),
+            }
+        }
+        Ok(_) => {}
+        Err(err) => log::error!("Failed to get the tournament of the game {game_uuid}: {err}"),
+    }
+}
+
+/// Register a webhook.
+///
+/// This endpoint will register an HTTPS endpoint that receives the events, the events are sent as signed
+/// JSON `POST` requests. The request has the `X-Xors-Event` header of the event, the `X-Xors-Delivery` header
+/// of the delivery uuid and the `X-Xors-Signature` header, it's `sha256=` followed by the lowercase hex of the
+/// body HMAC-SHA256 with the webhook secret. The secret is only returned here, keep it.
+///
+/// The endpoint host must resolve to public addresses only, not loopback, private, link-local or unspecified
+/// ones. It's resolved again on each delivery attempt, the attempts to the non-public addresses fail.
+///
+/// The delivery is successful when the endpoint responds with a `2xx` status code, otherwise it's retried
+/// with a backoff, 5 attempts at most. Only the admins can register the webhooks.
+#[endpoint(
+    operation_id = "create_webhook",
+    tags("Webhooks"),
+    request_body(
+        content = NewWebhookSchema,
+        description = "The webhook endpoint and events",
+        content_type = "application/json",
+    ),
+    responses(
+        (status_code = 200, description = "The webhook has been registered, with its secret", content_type = "application/json", body = WebhookSchema),
+        (status_code = 400, description = "The webhook URL or events are invalid", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is not an admin", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn create_webhook(
+    req: &mut Request,
+    depot: &mut Depot,
+    new_webhook: JsonBody<NewWebhookSchema>,
+) -> ApiResult<Json<WebhookSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    // Note: The role is checked by the `require_role` middleware.
+    let admin = depot.user(conn.as_ref()).await?;
+    let new_webhook = new_webhook.into_inner();
+    let policy = depot.obtain::<Arc<WebhookPolicy>>().unwrap();
+    utils::validate_new_webhook(&new_webhook, policy.allow_local_endpoints).await?;
+
+    let secret = utils::generate_webhook_secret();
+    let webhook =
+        db_utils::create_webhook(conn.as_ref(), admin.uuid, &new_webhook, secret.clone()).await?;
+    AuditEvent::new(AuditAction::WebhookCreation, req, depot)
+        .actor(admin.uuid)
+        .target(webhook.uuid)
+        .record(conn)
+        .await;
+    Ok(Json(WebhookSchema {
+        secret: Some(secret),
+        ..WebhookSchema::from(webhook)
+    }))
+}
+
+/// Get the webhooks.
+///
+/// This endpoint will return all the registered webhooks without their secrets, the oldest first. Only the
+/// admins can get the webhooks.
+#[endpoint(
+    operation_id = "get_webhooks",
+    tags("Webhooks"),
+    responses(
+        (status_code = 200, description = "The webhooks", content_type = "application/json", body = Vec<WebhookSchema>),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is not an admin", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn get_webhooks(depot: &mut Depot) -> ApiResult<Json<Vec<WebhookSchema>>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    // Note: The role is checked by the `require_role` middleware.
+    depot.user(conn.as_ref()).await?;
+
+    Ok(Json(
+        db_utils::get_webhooks(conn.as_ref())
+            .await?
+            .into_iter()
+            .map(WebhookSchema::from)
+            .collect(),
+    ))
+}
+
+/// Delete a webhook.
+///
+/// This endpoint will delete the webhook with its deliveries log, the pending retries are dropped. Only the
+/// admins can delete the webhooks.
+#[endpoint(
+    operation_id = "delete_webhook",
+    tags("Webhooks"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The webhook uuid"),
+    ),
+    responses(
+        (status_code = 200, description = "The webhook has been deleted", content_type = "application/json", body = MessageSchema),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is not an admin", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "Webhook not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn delete_webhook(
+    req: &mut Request,
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+) -> ApiResult<Json<MessageSchema>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    // Note: The role is checked by the `require_role` middleware.
+    let admin = depot.user(conn.as_ref()).await?;
+    let webhook_uuid = uuid.into_inner();
+
+    db_utils::delete_webhook(conn.as_ref(), webhook_uuid).await?;
+    AuditEvent::new(AuditAction::WebhookDeletion, req, depot)
+        .actor(admin.uuid)
+        .target(webhook_uuid)
+        .record(conn)
+        .await;
+    Ok(Json(MessageSchema::new(
+        "The webhook has been deleted".to_owned(),
+    )))
+}
+
+/// Get the webhook deliveries.
+///
+/// This endpoint will return the deliveries log of the webhook, the newest first. Each delivery has the sent
+/// payload, the attempts count and the last attempt result. Each page has 20 deliveries. Only the admins can
+/// get the deliveries.
+#[endpoint(
+    operation_id = "get_webhook_deliveries",
+    tags("Webhooks"),
+    parameters(
+        ("uuid" = Uuid, Path, description = "The webhook uuid"),
+        ("page" = Option<u64>, Query, description = "The page number, starting from 0, default is `0`"),
+    ),
+    responses(
+        (status_code = 200, description = "The webhook deliveries", content_type = "application/json", body = Vec<WebhookDeliverySchema>),
+        (status_code = 400, description = "The token is not a user token", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "The token is expired", content_type = "application/json", body = MessageSchema),
+        (status_code = 401, description = "Unauthorized, missing JWT", content_type = "application/json", body = MessageSchema),
+        (status_code = 403, description = "The user is not an admin", content_type = "application/json", body = MessageSchema),
+        (status_code = 404, description = "Webhook not found", content_type = "application/json", body = MessageSchema),
+        (status_code = 500, description = "Internal server error", content_type = "application/json", body = MessageSchema),
+        (status_code = 429, description = "Too many requests", content_type = "application/json", body = MessageSchema),
+    ),
+    security(("bearerAuth" = [])),
+)]
+pub async fn get_webhook_deliveries(
+    depot: &mut Depot,
+    uuid: PathParam<Uuid>,
+    page: QueryParam<u64, false>,
+) -> ApiResult<Json<Vec<WebhookDeliverySchema>>> {
+    let conn = depot.obtain::<Arc<DatabaseConnection>>().unwrap();
+    // Note: The role is checked by the `require_role` middleware.
+    depot.user(conn.as_ref()).await?;
+    let webhook = db_utils::get_webhook(conn.as_ref(), uuid.into_inner()).await?;
+
+    Ok(Json(
+        db_utils::get_webhook_deliveries(
+            conn.as_ref(),
+            webhook.uuid,
+            page.into_inner().unwrap_or_default(),
+        )
+        .await?
+        .into_iter()
+        .map(WebhookDeliverySchema::from)
+        .collect(),
+    ))
+}
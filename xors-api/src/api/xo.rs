@@ -17,7 +17,11 @@
 use std::{
     collections::{HashMap, VecDeque},
     str::FromStr,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
 };
 
 use chrono::Duration;
@@ -31,15 +35,25 @@ use tokio::sync::{mpsc, RwLock};
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use uuid::Uuid;
 
-use crate::{db_utils, errors::ApiResult, schemas::*, utils};
+use crate::{
+    db_utils,
+    errors::{ApiError, ApiResult},
+    schemas::*,
+    utils,
+};
+
+use super::{
+    bot,
+    exts::*,
+    jwt::{JwtClaims, JwtKeys},
+    matchmaking::{self, MatchmakingPolicy, PAUSED_SEARCH, SEARCH_FOR_GAME},
+};
 
-use super::exts::*;
+/// The sender of the websocket messages.
+pub type MessageSender = Arc<mpsc::UnboundedSender<Result<Message, salvo::Error>>>;
 
 /// The XO playe type
-pub type Player = (
-    Arc<Uuid>,
-    Arc<mpsc::UnboundedSender<Result<Message, salvo::Error>>>,
-);
+pub type Player = (Arc<Uuid>, MessageSender);
 
 /// The XO games, for each game there is the game uuid and tow channels for the players.
 ///
@@ -47,44 +61,323 @@ pub type Player = (
 pub type Games = HashMap<Uuid, (Player, Player)>;
 
 static ONLINE_GAMES: Lazy<RwLock<Games>> = Lazy::new(RwLock::default);
-static SEARCH_FOR_GAME: Lazy<RwLock<VecDeque<Player>>> = Lazy::new(|| RwLock::new(VecDeque::new()));
-
-/// XO winning combinations.
-pub const WINNING_COMBINATIONS: &[(usize, usize, usize)] = &[
-    (0, 1, 2), // Rows
-    (3, 4, 5),
-    (6, 7, 8),
-    (0, 3, 6), // Columns
-    (1, 4, 7),
-    (2, 5, 8),
-    (0, 4, 8), // Diagonals
-    (2, 4, 6),
-];
+
+/// The XO games spectators, for each game there is the game uuid and the spectators channels.
+pub type Spectators = HashMap<Uuid, Vec<MessageSender>>;
+
+static SPECTATORS: Lazy<RwLock<Spectators>> = Lazy::new(RwLock::default);
+
+/// The open connections of each player, by the player uuid.
+static CONNECTIONS: Lazy<Mutex<HashMap<Uuid, PlayerConnections>>> = Lazy::new(Mutex::default);
+
+/// The id of the next connection.
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// The player's open connections.
+#[derive(Debug)]
+struct PlayerConnections {
+    /// The player's events sender, the events sent to it are forwarded to all the connections.
+    events_tx: MessageSender,
+    /// The connections senders, by the connection id.
+    sockets: Vec<(u64, MessageSender)>,
+}
+
+/// The players that are ready to play their tournament match, waiting for their opponent, by the match id.
+static TOURNAMENT_READY: Lazy<RwLock<HashMap<i32, Player>>> = Lazy::new(RwLock::default);
+
+/// The in-progress games of the previous server run, waiting for their players to reconnect, by the game uuid.
+static RESUMABLE_GAMES: Lazy<RwLock<HashMap<Uuid, ResumableGame>>> = Lazy::new(RwLock::default);
+
+/// The in-progress games paused for their disconnected player to reconnect, by the game uuid.
+static PAUSED_GAMES: Lazy<RwLock<HashMap<Uuid, PausedGame>>> = Lazy::new(RwLock::default);
+
+/// Whether the server is shutting down, the players events are rejected after it.
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// The last presence broadcast to the friends of each player, the offline players are not in it.
+static PRESENCES: Lazy<Mutex<HashMap<Uuid, Presence>>> = Lazy::new(Mutex::default);
+
+/// An in-progress game paused for its disconnected player to reconnect.
+#[derive(Debug, Clone, Copy)]
+struct PausedGame {
+    /// The disconnected player.
+    player: Uuid,
+    /// The game ends if the player doesn't reconnect before it.
+    reconnect_before: chrono::NaiveDateTime,
+}
+
+/// An in-progress game of the previous server run.
+#[derive(Debug)]
+struct ResumableGame {
+    x_player: Uuid,
+    o_player: Uuid,
+    /// The players that reconnected to the game.
+    reconnected: Vec<Player>,
+}
+
+/// The chat rate limit window, the player can send the max chat messages in it.
+pub const CHAT_RATE_WINDOW: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// The chat limits violations that mute the player in the game chat.
+pub const CHAT_MUTE_AFTER_VIOLATIONS: usize = 3;
+
+/// How long the player stays muted in the game chat.
+pub const CHAT_MUTE_DURATION: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// The websocket close code sent when the player's token expires.
+pub const TOKEN_EXPIRED_CLOSE_CODE: u16 = 4001;
+
+/// The websocket close code sent to the older connections when the player opens a new one,
+/// with the [`DuplicateConnectionPolicy::Single`] policy.
+pub const REPLACED_CLOSE_CODE: u16 = 4002;
+
+/// The websocket close code sent to the connections of the player when a moderator bans it.
+pub const BANNED_CLOSE_CODE: u16 = 4003;
+
+/// The websocket close code sent to the connections of the players and the spectators when the server shuts down.
+pub const SERVER_SHUTDOWN_CLOSE_CODE: u16 = 4004;
+
+/// How the XO websocket behaves when the player's token expires mid-session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TokenExpiryPolicy {
+    /// Close the connection with the [`TOKEN_EXPIRED_CLOSE_CODE`] code when the token expires.
+    #[default]
+    Close,
+    /// Send a `token_expiring` event before the token expires, the player can present a refreshed
+    /// token with the `reauthenticate` event. If not, the connection will be closed like [`TokenExpiryPolicy::Close`].
+    Reauthenticate,
+}
+
+impl FromStr for TokenExpiryPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "close" => Ok(Self::Close),
+            "reauthenticate" => Ok(Self::Reauthenticate),
+            _ => Err(format!(
+                "Invalid token expiry policy `{s}`, expected `close` or `reauthenticate`"
+            )),
+        }
+    }
+}
+
+/// How the XO websocket handles the multiple connections of the same player (e.g. two tabs).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateConnectionPolicy {
+    /// Keep all the connections, the player's events are sent to all of them and the player can
+    /// play from any of them. The player is disconnected when the last connection is closed.
+    #[default]
+    AllowMultiple,
+    /// Keep only the newest connection, the older connections are closed with the [`REPLACED_CLOSE_CODE`] code.
+    Single,
+}
+
+impl FromStr for DuplicateConnectionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow_multiple" => Ok(Self::AllowMultiple),
+            "single" => Ok(Self::Single),
+            _ => Err(format!(
+                "Invalid duplicate connection policy `{s}`, expected `allow_multiple` or `single`"
+            )),
+        }
+    }
+}
+
+/// What the server does when the player doesn't play before its move period ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MoveTimeoutPolicy {
+    /// Play a random move for the player, with the `auto_play` event.
+    #[default]
+    AutoPlay,
+    /// End the game, the other player wins with the `timeout` reason.
+    Forfeit,
+}
+
+impl FromStr for MoveTimeoutPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto_play" => Ok(Self::AutoPlay),
+            "forfeit" => Ok(Self::Forfeit),
+            _ => Err(format!(
+                "Invalid move timeout policy `{s}`, expected `auto_play` or `forfeit`"
+            )),
+        }
+    }
+}
+
+/// The XO games clocks, the per-move period, the optional per-game time of each player and the optional
+/// reconnection window of the disconnected players.
+#[derive(Debug, Clone, Copy)]
+pub struct GameClock {
+    /// The seconds the player has to play its move.
+    move_period: i64,
+    /// The total seconds each player has for all its moves in the game, if any.
+    game_time: Option<i64>,
+    move_timeout_policy: MoveTimeoutPolicy,
+    /// The seconds the game is paused for the disconnected player to reconnect, if any.
+    reconnect_window: Option<i64>,
+}
+
+impl GameClock {
+    /// Create a new game clock with `move_period` seconds for each move, without a per-game time
+    /// and without a reconnection window, the disconnected player loses the game immediately.
+    pub fn new(move_period: i64) -> Self {
+        Self {
+            move_period,
+            game_time: None,
+            move_timeout_policy: MoveTimeoutPolicy::default(),
+            reconnect_window: None,
+        }
+    }
+
+    /// Gives each player `game_time` seconds for all its moves in the game, the player that runs out
+    /// of it loses the game with the `timeout` reason.
+    pub fn with_game_time(mut self, game_time: i64) -> Self {
+        self.game_time = Some(game_time);
+        self
+    }
+
+    /// Sets what the server does when the player doesn't play before its move period ends.
+    pub fn with_move_timeout_policy(mut self, policy: MoveTimeoutPolicy) -> Self {
+        self.move_timeout_policy = policy;
+        self
+    }
+
+    /// Pauses the game for `reconnect_window` seconds when a player disconnects, if it reconnects before
+    /// the window ends the game is resumed, otherwise it loses the game.
+    pub fn with_reconnect_window(mut self, reconnect_window: i64) -> Self {
+        self.reconnect_window = Some(reconnect_window);
+        self
+    }
+
+    /// Returns when the current turn of the game started, the move period started with it.
+    fn turn_started_at(&self, game: &GameModel) -> Option<chrono::NaiveDateTime> {
+        game.auto_play_after
+            .map(|auto_play_after| auto_play_after - Duration::seconds(self.move_period))
+    }
+
+    /// Returns the X and O players time left in the game in milliseconds, if the game has the game time.
+    /// The time of the player whose turn it is goes down since the turn started.
+    fn time_left(&self, game: &GameModel, turn: XoSymbol) -> (Option<i64>, Option<i64>) {
+        let elapsed = self.turn_started_at(game).map_or(0, |started_at| {
+            (chrono::Utc::now().naive_utc() - started_at)
+                .num_milliseconds()
+                .max(0)
+        });
+        let time_left = |time_left: Option<i64>, symbol: XoSymbol| {
+            time_left.map(|time_left| {
+                if symbol == turn {
+                    (time_left - elapsed).max(0)
+                } else {
+                    time_left
+                }
+            })
+        };
+        (
+            time_left(game.x_time_left, XoSymbol::X),
+            time_left(game.o_time_left, XoSymbol::O),
+        )
+    }
+
+    /// Returns whether the player whose turn it is lost the game by the time, it ran out of its game time,
+    /// or its move period ended with the [`MoveTimeoutPolicy::Forfeit`] policy.
+    fn is_timed_out(&self, game: &GameModel, turn: XoSymbol) -> bool {
+        let (x_time_left, o_time_left) = self.time_left(game, turn);
+        let time_left = if turn == XoSymbol::X {
+            x_time_left
+        } else {
+            o_time_left
+        };
+        time_left == Some(0)
+            || (self.move_timeout_policy == MoveTimeoutPolicy::Forfeit
+                && game
+                    .auto_play_after
+                    .is_some_and(|after| chrono::Utc::now().naive_utc() >= after))
+    }
+}
+
+impl Default for GameClock {
+    fn default() -> Self {
+        Self::new(10)
+    }
+}
 
 /// Player data
 #[derive(derive_new::new)]
 pub(crate) struct PlayerData {
     pub uuid: Arc<Uuid>,
-    pub tx: Arc<mpsc::UnboundedSender<Result<Message, salvo::Error>>>,
+    pub tx: MessageSender,
     pub symbol: XoSymbol,
 }
 
+/// Returns whether the websocket handshake origin is allowed.
+///
+/// The requests without the `Origin` header are allowed, only the browsers send it. All the origins
+/// are allowed if there is no allowlist.
+fn is_allowed_origin(req: &Request, allowed_origins: Option<&[String]>) -> bool {
+    let (Some(allowed_origins), Some(origin)) = (allowed_origins, req.header::<String>("Origin"))
+    else {
+        return true;
+    };
+    allowed_origins
+        .iter()
+        .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(&origin))
+}
+
 #[handler]
 pub async fn user_connected(
     req: &mut Request,
     res: &mut Response,
     depot: &mut Depot,
 ) -> ApiResult<()> {
+    let ws_allowed_origins = depot
+        .get::<Arc<Option<Vec<String>>>>("ws_allowed_origins")
+        .unwrap()
+        .clone();
+    if !is_allowed_origin(req, ws_allowed_origins.as_deref()) {
+        log::error!("Rejected a websocket handshake from a disallowed origin");
+        return Err(ApiError::DisallowedOrigin);
+    }
+
     let conn = depot
         .obtain::<Arc<sea_orm::DatabaseConnection>>()
         .unwrap()
         .clone();
     let user_uuid = Arc::new(depot.user(&conn).await?.uuid);
+    let token_exp = Arc::new(AtomicI64::new(depot.jwt_claims().exp));
+    let jwt_keys = depot.obtain::<Arc<JwtKeys>>().unwrap().clone();
+    let token_expiry_policy = *depot
+        .get::<Arc<TokenExpiryPolicy>>("token_expiry_policy")
+        .unwrap()
+        .clone();
+    let token_expiring_before = **depot.get::<Arc<u64>>("token_expiring_before").unwrap();
     let max_online_games = depot.get::<Arc<usize>>("max_online_games").unwrap().clone();
-    let move_period = depot.get::<Arc<i64>>("move_period").unwrap().clone();
+    let clock = **depot.obtain::<Arc<GameClock>>().unwrap();
+    let unique_pair_games = depot.get::<Arc<bool>>("unique_pair_games").unwrap().clone();
+    let daily_games_limit = *depot
+        .get::<Arc<Option<u64>>>("daily_games_limit")
+        .unwrap()
+        .clone();
+    let random_opening_moves = *depot
+        .get::<Arc<u8>>("random_opening_moves")
+        .unwrap()
+        .clone();
+    let matchmaking_policy = **depot.obtain::<Arc<MatchmakingPolicy>>().unwrap();
+    let chat_limiter = depot.obtain::<Arc<ChatLimiter>>().unwrap().clone();
+    let persist_chat = **depot.get::<Arc<bool>>("persist_chat").unwrap();
+    let duplicate_connection_policy = *depot
+        .get::<Arc<DuplicateConnectionPolicy>>("duplicate_connection_policy")
+        .unwrap()
+        .clone();
 
     WebSocketUpgrade::new()
-        .upgrade(req, res, |ws| async move {
+        .upgrade(req, res, move |ws| async move {
             log::debug!("User connected to the XO websocket: {user_uuid}");
 
             let (user_ws_tx, mut user_ws_rx) = ws.split();
@@ -98,8 +391,36 @@ pub async fn user_connected(
                 }
             });
             tokio::task::spawn(fut);
+            let (connection_id, events_tx) =
+                connection_opened(*user_uuid, tx.clone(), duplicate_connection_policy);
+            if let Err(err) =
+                rejoin_game(&conn, (user_uuid.clone(), events_tx.clone()), clock).await
+            {
+                log::error!("Failed to rejoin the player {user_uuid} to its game: {err}");
+            }
+            if let Err(err) =
+                reconnect_game(&conn, (user_uuid.clone(), events_tx.clone()), clock).await
+            {
+                log::error!("Failed to resume the paused game of the player {user_uuid}: {err}");
+            }
+            presence_changed(&conn, *user_uuid).await;
             let fut = async move {
-                while let Some(result) = user_ws_rx.next().await {
+                let token_expiry = token_expiry_watcher(
+                    token_expiry_policy,
+                    token_expiring_before,
+                    token_exp.clone(),
+                    tx.clone(),
+                );
+                tokio::pin!(token_expiry);
+                loop {
+                    let result = tokio::select! {
+                        result = user_ws_rx.next() => result,
+                        // The connection is closed, stop reading it and unregister the player
+                        () = &mut token_expiry => break,
+                    };
+                    let Some(result) = result else {
+                        break;
+                    };
                     let msg = match result {
                         Ok(msg) => msg,
                         Err(e) => {
@@ -108,7 +429,9 @@ pub async fn user_connected(
                         }
                     };
                     // Handle message
-                    if msg.is_text() {
+                    if msg.is_close() {
+                        break;
+                    } else if msg.is_text() {
                         log::debug!("Received a text message: {msg:?}");
                         if let Ok(event) = serde_json::from_str::<XoClientEvent>(
                             msg.to_str().expect("The message is text"),
@@ -117,16 +440,30 @@ pub async fn user_connected(
                             if let Err(err) = handle_event(
                                 event,
                                 &conn,
+                                &jwt_keys,
+                                token_expiry_policy,
+                                &token_exp,
                                 max_online_games.as_ref(),
-                                *move_period,
+                                clock,
+                                *unique_pair_games,
+                                daily_games_limit,
+                                random_opening_moves,
+                                matchmaking_policy,
+                                &chat_limiter,
+                                persist_chat,
+                                events_tx.clone(),
                                 tx.clone(),
                                 user_uuid.clone(),
                             )
                             .await
                             {
-                                tx.send_server_event(XoServerEventData::Error(ErrorData::Other(
-                                    err.to_string(),
-                                )));
+                                tx.send_server_event(XoServerEventData::Error(match err {
+                                    ApiError::AlreadyPlayingEachOther(game_id) => {
+                                        ErrorData::AlreadyPlayingEachOther { game_id }
+                                    }
+                                    ApiError::NotInSearch => ErrorData::NotInSearch,
+                                    err => ErrorData::Other(err.to_string()),
+                                }));
                             }
                         } else {
                             tx.send_server_event(XoServerEventData::Error(ErrorData::UnknownEvent));
@@ -136,9 +473,12 @@ pub async fn user_connected(
                     }
                 }
 
-                player_disconnected(&conn, (user_uuid.clone(), tx.clone()))
-                    .await
-                    .ok();
+                if connection_closed(&user_uuid, connection_id) {
+                    player_disconnected(&conn, (user_uuid.clone(), events_tx), clock)
+                        .await
+                        .ok();
+                    presence_changed(&conn, *user_uuid).await;
+                }
             };
             tokio::task::spawn(fut);
         })
@@ -146,21 +486,449 @@ pub async fn user_connected(
     Ok(())
 }
 
+/// Spectates an in-progress game, the spectator receives the current game state with the
+/// `game_state` event, then the game moves, rounds, chat and game over events.
+///
+/// The spectator can't send events, its connection is closed when the game is over.
+#[handler]
+pub async fn spectate(req: &mut Request, res: &mut Response, depot: &mut Depot) -> ApiResult<()> {
+    let ws_allowed_origins = depot
+        .get::<Arc<Option<Vec<String>>>>("ws_allowed_origins")
+        .unwrap()
+        .clone();
+    if !is_allowed_origin(req, ws_allowed_origins.as_deref()) {
+        log::error!("Rejected a websocket handshake from a disallowed origin");
+        return Err(ApiError::DisallowedOrigin);
+    }
+
+    let conn = depot
+        .obtain::<Arc<sea_orm::DatabaseConnection>>()
+        .unwrap()
+        .clone();
+    let user_uuid = depot.user(&conn).await?.uuid;
+    let token_exp = Arc::new(AtomicI64::new(depot.jwt_claims().exp));
+    let clock = **depot.obtain::<Arc<GameClock>>().unwrap();
+    let Some(game_uuid) = req.param::<Uuid>("uuid") else {
+        return Err(ApiError::GameNotFound);
+    };
+    if ONLINE_GAMES.get_game_players(&game_uuid).await.is_none() {
+        // Only the in-progress games of this server run can be spectated
+        return Err(ApiError::GameNotFound);
+    }
+
+    WebSocketUpgrade::new()
+        .upgrade(req, res, move |ws| async move {
+            log::debug!("User {user_uuid} is spectating the game {game_uuid}");
+
+            let (user_ws_tx, mut user_ws_rx) = ws.split();
+
+            let (tx, rx) = mpsc::unbounded_channel();
+            let rx = UnboundedReceiverStream::new(rx);
+            let tx = Arc::new(tx);
+            let fut = rx.forward(user_ws_tx).map(|result| {
+                if let Err(e) = result {
+                    log::error!("websocket send error: {e}");
+                }
+            });
+            tokio::task::spawn(fut);
+
+            match db_utils::get_game::<false>(&conn, &game_uuid).await {
+                Ok(game) if game.ended_at.is_none() => {
+                    let board = Board::from_str(&game.board).expect("The board is valid");
+                    let (x_time_left, o_time_left) = clock.time_left(&game, board.turn());
+                    tx.send_server_event(XoServerEventData::GameState(GameResumedData::new(
+                        game_uuid,
+                        game.x_player,
+                        game.o_player,
+                        game.round,
+                        board,
+                        RoundsResult::from_str(&game.rounds_result)
+                            .expect("The rounds result is valid"),
+                        x_time_left,
+                        o_time_left,
+                    )));
+                    SPECTATORS.add_spectator(game_uuid, tx.clone()).await;
+                }
+                Ok(_) => {
+                    tx.send(Ok(Message::close())).ok();
+                    return;
+                }
+                Err(err) => {
+                    log::error!("Failed to get the spectated game {game_uuid}: {err}");
+                    tx.send(Ok(Message::close())).ok();
+                    return;
+                }
+            }
+
+            let fut = async move {
+                // The spectators can't reauthenticate, the connection is closed when the token expires.
+                let token_expiry =
+                    token_expiry_watcher(TokenExpiryPolicy::Close, 0, token_exp, tx.clone());
+                tokio::pin!(token_expiry);
+                loop {
+                    let result = tokio::select! {
+                        result = user_ws_rx.next() => result,
+                        () = &mut token_expiry => break,
+                    };
+                    let Some(result) = result else {
+                        break;
+                    };
+                    let msg = match result {
+                        Ok(msg) => msg,
+                        Err(e) => {
+                            log::error!("websocket error: {e}");
+                            break;
+                        }
+                    };
+                    if msg.is_close() {
+                        break;
+                    }
+                    tx.send_server_event(XoServerEventData::Error(ErrorData::Spectating));
+                }
+                SPECTATORS.remove_spectator(&game_uuid, &tx).await;
+            };
+            tokio::task::spawn(fut);
+        })
+        .await?;
+    Ok(())
+}
+
+/// Registers the player's new connection, returns the connection id and the player's events sender.
+///
+/// With the [`DuplicateConnectionPolicy::Single`] policy, the older connections are closed with
+/// the [`REPLACED_CLOSE_CODE`] code.
+fn connection_opened(
+    user_uuid: Uuid,
+    socket_tx: MessageSender,
+    policy: DuplicateConnectionPolicy,
+) -> (u64, MessageSender) {
+    let connection_id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::SeqCst);
+    let mut connections = CONNECTIONS.lock().expect("The lock is not poisoned");
+    let player_connections = connections.entry(user_uuid).or_insert_with(|| {
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        tokio::task::spawn(forward_player_events(user_uuid, events_rx));
+        PlayerConnections {
+            events_tx: Arc::new(events_tx),
+            sockets: Vec::new(),
+        }
+    });
+
+    if policy == DuplicateConnectionPolicy::Single {
+        for (_, older_tx) in player_connections.sockets.drain(..) {
+            log::info!("Closing an older connection of player {user_uuid}");
+            older_tx
+                .send(Ok(Message::close_with(
+                    REPLACED_CLOSE_CODE,
+                    "Replaced by a new connection",
+                )))
+                .ok();
+        }
+    }
+    player_connections.sockets.push((connection_id, socket_tx));
+    (connection_id, player_connections.events_tx.clone())
+}
+
+/// Unregisters the player's connection, returns whether it was the player's last connection.
+fn connection_closed(user_uuid: &Uuid, connection_id: u64) -> bool {
+    let mut connections = CONNECTIONS.lock().expect("The lock is not poisoned");
+    let Some(player_connections) = connections.get_mut(user_uuid) else {
+        return false;
+    };
+    let Some(index) = player_connections
+        .sockets
+        .iter()
+        .position(|(id, _)| *id == connection_id)
+    else {
+        // Already closed by a newer connection
+        return false;
+    };
+
+    player_connections.sockets.remove(index);
+    if player_connections.sockets.is_empty() {
+        connections.remove(user_uuid);
+        return true;
+    }
+    false
+}
+
+/// Returns the in-progress games count and the connected players count.
+pub(crate) async fn live_counts() -> (usize, usize) {
+    let connected_players = CONNECTIONS.lock().expect("The lock is not poisoned").len();
+    (ONLINE_GAMES.online_games_count().await, connected_players)
+}
+
+/// Returns the player's current presence, by its connections and its in-progress game.
+pub(crate) async fn player_presence(player_uuid: &Uuid) -> Presence {
+    if !CONNECTIONS
+        .lock()
+        .expect("The lock is not poisoned")
+        .contains_key(player_uuid)
+    {
+        Presence::Offline
+    } else if ONLINE_GAMES.is_user_in_game(player_uuid).await {
+        Presence::InGame
+    } else {
+        Presence::Online
+    }
+}
+
+/// Sends the player's presence to its connected friends, if it changed since the last time.
+pub(crate) async fn presence_changed(conn: &sea_orm::DatabaseConnection, player_uuid: Uuid) {
+    let presence = player_presence(&player_uuid).await;
+    {
+        let mut presences = PRESENCES.lock().expect("The lock is not poisoned");
+        if presences.get(&player_uuid).copied().unwrap_or_default() == presence {
+            return;
+        }
+        if presence == Presence::Offline {
+            presences.remove(&player_uuid);
+        } else {
+            presences.insert(player_uuid, presence);
+        }
+    }
+
+    match db_utils::get_friend_uuids(conn, player_uuid).await {
+        Ok(friends) => {
+            for friend_tx in friends.iter().filter_map(player_events_tx) {
+                friend_tx.send_server_event(XoServerEventData::FriendPresence {
+                    friend: player_uuid,
+                    presence,
+                });
+            }
+        }
+        Err(err) => log::error!("Failed to get the friends of the player {player_uuid}: {err}"),
+    }
+}
+
+/// Sends the friends presences to each other, after they become friends.
+pub(crate) async fn friends_added(user_uuid: Uuid, friend_uuid: Uuid) {
+    for (to, friend) in [(user_uuid, friend_uuid), (friend_uuid, user_uuid)] {
+        if let Some(tx) = player_events_tx(&to) {
+            tx.send_server_event(XoServerEventData::FriendPresence {
+                friend,
+                presence: player_presence(&friend).await,
+            });
+        }
+    }
+}
+
+/// Closes all the connections of the banned player with the [`BANNED_CLOSE_CODE`] code, the player's game and
+/// search end as if it disconnected.
+pub(crate) fn disconnect_banned_player(user_uuid: &Uuid) {
+    if let Some(player_connections) = CONNECTIONS
+        .lock()
+        .expect("The lock is not poisoned")
+        .get(user_uuid)
+    {
+        log::info!("Closing the connections of the banned player {user_uuid}");
+        for (_, socket_tx) in &player_connections.sockets {
+            socket_tx
+                .send(Ok(Message::close_with(
+                    BANNED_CLOSE_CODE,
+                    "The user is banned",
+                )))
+                .ok();
+        }
+    }
+}
+
+/// Forwards the player's events to all of its connections.
+async fn forward_player_events(
+    user_uuid: Uuid,
+    mut events_rx: mpsc::UnboundedReceiver<Result<Message, salvo::Error>>,
+) {
+    while let Some(event) = events_rx.recv().await {
+        let Ok(msg) = event else {
+            continue;
+        };
+        let sockets = CONNECTIONS
+            .lock()
+            .expect("The lock is not poisoned")
+            .get(&user_uuid)
+            .map(|connections| {
+                connections
+                    .sockets
+                    .iter()
+                    .map(|(_, tx)| tx.clone())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        for socket_tx in sockets {
+            socket_tx.send(Ok(msg.clone())).ok();
+        }
+    }
+}
+
+/// Watches the player's token expiry, it will warn the player before the token expires if the
+/// policy is [`TokenExpiryPolicy::Reauthenticate`], and returns after closing the connection when the token expires.
+async fn token_expiry_watcher(
+    policy: TokenExpiryPolicy,
+    expiring_before: u64,
+    token_exp: Arc<AtomicI64>,
+    tx: Arc<mpsc::UnboundedSender<Result<Message, salvo::Error>>>,
+) {
+    let mut warned_for = None;
+
+    loop {
+        let exp = token_exp.load(Ordering::SeqCst);
+        let now = chrono::Utc::now().timestamp_millis();
+        if now >= exp * 1000 {
+            log::info!("The player token is expired, closing the connection");
+            tx.send(Ok(Message::close_with(
+                TOKEN_EXPIRED_CLOSE_CODE,
+                "The token is expired",
+            )))
+            .ok();
+            return;
+        }
+
+        let wake_at = if policy == TokenExpiryPolicy::Reauthenticate && warned_for != Some(exp) {
+            let warn_at = (exp - expiring_before as i64) * 1000;
+            if now >= warn_at {
+                tx.send_server_event(XoServerEventData::TokenExpiring { expires_at: exp });
+                warned_for = Some(exp);
+                continue;
+            }
+            warn_at
+        } else {
+            exp * 1000
+        };
+
+        tokio::time::sleep(tokio::time::Duration::from_millis((wake_at - now) as u64)).await;
+    }
+}
+
+/// Reauthenticate the player with a refreshed token, the token must be a valid and not revoked user jwt for the same player.
+async fn reauthenticate(
+    conn: &sea_orm::DatabaseConnection,
+    jwt_keys: &JwtKeys,
+    token_exp: &AtomicI64,
+    player: Player,
+    token: String,
+) -> ApiResult<()> {
+    log::info!("Player {} is reauthenticating", player.0);
+
+    let claims = jwt_keys.decode::<JwtClaims>(&token).map(|data| data.claims);
+    let is_revoked = match &claims {
+        Ok(claims) => match db_utils::check_token_revocation(conn, claims).await {
+            Ok(()) => false,
+            Err(ApiError::RevokedToken | ApiError::ReusedRefreshToken) => true,
+            Err(err) => return Err(err),
+        },
+        Err(_) => false,
+    };
+
+    match claims {
+        Ok(claims)
+            if !claims.is_refresh_token()
+                && !claims.is_expired()
+                && !is_revoked
+                && &claims.uuid == player.0.as_ref() =>
+        {
+            token_exp.fetch_max(claims.exp, Ordering::SeqCst);
+            player
+                .1
+                .send_server_event(XoServerEventData::Reauthenticated {
+                    new_expires_at: token_exp.load(Ordering::SeqCst),
+                });
+        }
+        _ => {
+            log::error!("Player {} sent an invalid token", player.0);
+            player
+                .1
+                .send_server_event(XoServerEventData::Error(ErrorData::InvalidToken));
+        }
+    }
+    Ok(())
+}
+
 /// Handle the XO client event.
+///
+/// The `tx` is the player's events sender, sent to all of its connections, and the `socket_tx` is the
+/// sender of the connection that sent the event.
+#[allow(clippy::too_many_arguments)]
 async fn handle_event(
     event: XoClientEvent,
     conn: &Arc<sea_orm::DatabaseConnection>,
+    jwt_keys: &JwtKeys,
+    token_expiry_policy: TokenExpiryPolicy,
+    token_exp: &AtomicI64,
     max_online_games: &usize,
-    move_period: i64,
-    tx: Arc<mpsc::UnboundedSender<Result<Message, salvo::Error>>>,
+    clock: GameClock,
+    unique_pair_games: bool,
+    daily_games_limit: Option<u64>,
+    random_opening_moves: u8,
+    matchmaking_policy: MatchmakingPolicy,
+    chat_limiter: &ChatLimiter,
+    persist_chat: bool,
+    tx: MessageSender,
+    socket_tx: MessageSender,
     user: Arc<Uuid>,
 ) -> ApiResult<()> {
+    if SHUTTING_DOWN.load(Ordering::SeqCst) {
+        socket_tx.send_server_event(XoServerEventData::Error(ErrorData::ServerShuttingDown));
+        return Ok(());
+    }
+
     match (event.event, event.data) {
         (XoClientEventKind::Search, None) => {
-            search_for_game(conn, max_online_games, move_period, (user, tx)).await?
+            matchmaking::search_for_game(
+                conn,
+                max_online_games,
+                clock,
+                unique_pair_games,
+                daily_games_limit,
+                random_opening_moves,
+                matchmaking_policy,
+                (user, tx),
+            )
+            .await?
         }
-        (XoClientEventKind::Play, Some(XoClientEventsData::Play { place })) => {
-            play(conn, (user, tx), place, move_period).await?
+        (XoClientEventKind::CancelSearch, None) => matchmaking::cancel_search(&user).await?,
+        (XoClientEventKind::PlayBot, Some(XoClientEventsData::PlayBot { difficulty })) => {
+            play_bot(
+                conn,
+                max_online_games,
+                clock,
+                daily_games_limit,
+                random_opening_moves,
+                (user, tx),
+                difficulty,
+            )
+            .await?
+        }
+        (
+            XoClientEventKind::PlayTournament,
+            Some(XoClientEventsData::PlayTournament { tournament }),
+        ) => {
+            play_tournament(
+                conn,
+                max_online_games,
+                clock,
+                random_opening_moves,
+                (user, tx),
+                tournament,
+            )
+            .await?
+        }
+        (XoClientEventKind::JoinCode, Some(XoClientEventsData::JoinCode { code })) => {
+            if let Some(err) = join_invite(
+                conn,
+                max_online_games,
+                clock,
+                unique_pair_games,
+                daily_games_limit,
+                random_opening_moves,
+                (user, tx.clone()),
+                &code,
+            )
+            .await?
+            {
+                tx.send_server_event(XoServerEventData::Error(err));
+            }
+        }
+        (XoClientEventKind::Play, Some(XoClientEventsData::Play { place, seen_moves })) => {
+            play(conn, (user, tx), place, seen_moves, clock).await?
         }
         (XoClientEventKind::Wellcome, Some(XoClientEventsData::Wellcome { public_key })) => {
             wellcome(conn, (user, tx), public_key).await?
@@ -171,7 +939,26 @@ async fn handle_event(
                 encrypted_message,
                 signature,
             }),
-        ) => chat(conn, (user, tx), encrypted_message, signature).await?,
+        ) => {
+            chat(
+                conn,
+                chat_limiter,
+                persist_chat,
+                (user, tx),
+                encrypted_message,
+                signature,
+            )
+            .await?
+        }
+        (XoClientEventKind::Reauthenticate, Some(XoClientEventsData::Reauthenticate { token })) => {
+            if token_expiry_policy == TokenExpiryPolicy::Reauthenticate {
+                reauthenticate(conn, jwt_keys, token_exp, (user, socket_tx), token).await?
+            } else {
+                socket_tx.send_server_event(XoServerEventData::Error(
+                    ErrorData::ReauthenticationDisabled,
+                ));
+            }
+        }
         _ => {
             tx.send_server_event(XoServerEventData::Error(ErrorData::InvalidBody));
         }
@@ -180,81 +967,474 @@ async fn handle_event(
     Ok(())
 }
 
-/// Search for a game.
-async fn search_for_game(
+/// Returns whether if the player started `daily_games_limit` games or more in the last 24 hours.
+async fn is_daily_limit_reached(
     conn: &sea_orm::DatabaseConnection,
-    max_online_games: &usize,
-    move_period: i64,
-    player: Player,
-) -> ApiResult<()> {
-    log::info!("Player {} is searching for a game", player.0);
+    player_uuid: &Uuid,
+    daily_games_limit: Option<u64>,
+) -> ApiResult<bool> {
+    let Some(daily_games_limit) = daily_games_limit else {
+        return Ok(false);
+    };
+    let since = chrono::Utc::now().naive_utc() - chrono::Duration::hours(24);
 
-    if ONLINE_GAMES.is_user_in_game(&player.0).await {
-        log::error!("Player {} is already in a game", player.0);
-        player
-            .1
-            .send_server_event(XoServerEventData::Error(ErrorData::AlreadyInGame));
+    Ok(db_utils::count_user_games_since(conn, *player_uuid, since).await? >= daily_games_limit)
+}
+
+/// The chat limiter, limits the chat messages rate and size of each player in each game.
+///
+/// A player that violates the limits [`CHAT_MUTE_AFTER_VIOLATIONS`] times in a game will be muted
+/// in the game chat for [`CHAT_MUTE_DURATION`]. The violations are forgotten after [`CHAT_MUTE_DURATION`]
+/// without new violations.
+#[derive(Debug)]
+pub struct ChatLimiter {
+    max_messages: usize,
+    max_message_size: usize,
+    /// The chat activity of each player in each game, by the game uuid and the player uuid.
+    activities: Mutex<HashMap<(Uuid, Uuid), ChatActivity>>,
+}
+
+/// The player chat activity in a game.
+#[derive(Debug, Default)]
+struct ChatActivity {
+    messages: VecDeque<Instant>,
+    violations: usize,
+    last_violation: Option<Instant>,
+    muted_until: Option<Instant>,
+}
+
+impl ChatLimiter {
+    /// Create a new chat limiter, `max_messages` messages in the [`CHAT_RATE_WINDOW`] and
+    /// `max_message_size` bytes for each message.
+    pub fn new(max_messages: usize, max_message_size: usize) -> Self {
+        Self {
+            max_messages,
+            max_message_size,
+            activities: Mutex::default(),
+        }
+    }
+
+    /// Check if the player can send a chat message of `message_size` bytes in the game.
+    /// Returns the error to send to the player if the message is rejected.
+    pub fn check(
+        &self,
+        game_uuid: Uuid,
+        player_uuid: Uuid,
+        message_size: usize,
+    ) -> Result<(), ErrorData> {
+        let now = Instant::now();
+        let mut activities = self.activities.lock().expect("The lock is not poisoned");
+        // Forget the inactive players
+        activities.retain(|_, activity| {
+            activity
+                .messages
+                .retain(|message| now.duration_since(*message) < CHAT_RATE_WINDOW);
+            !activity.messages.is_empty()
+                || activity.muted_until.is_some_and(|until| until > now)
+                || activity
+                    .last_violation
+                    .is_some_and(|last| now.duration_since(last) < CHAT_MUTE_DURATION)
+        });
+
+        let activity = activities.entry((game_uuid, player_uuid)).or_default();
+        if activity.muted_until.is_some_and(|until| until > now) {
+            return Err(ErrorData::ChatMuted);
+        }
+
+        let error = if message_size > self.max_message_size {
+            ErrorData::ChatMessageTooLarge
+        } else if activity.messages.len() >= self.max_messages {
+            ErrorData::ChatRateLimited
+        } else {
+            activity.messages.push_back(now);
+            return Ok(());
+        };
+
+        activity.violations += 1;
+        activity.last_violation = Some(now);
+        if activity.violations >= CHAT_MUTE_AFTER_VIOLATIONS {
+            log::info!("Player {player_uuid} is muted in the game {game_uuid} chat");
+            activity.violations = 0;
+            activity.muted_until = Some(now + CHAT_MUTE_DURATION);
+        }
+        Err(error)
+    }
+}
+
+impl Default for ChatLimiter {
+    fn default() -> Self {
+        Self::new(5, 8192)
+    }
+}
+
+/// Returns the error that prevents the player from starting a new game, if any.
+pub(super) async fn start_game_error(
+    conn: &sea_orm::DatabaseConnection,
+    max_online_games: &usize,
+    daily_games_limit: Option<u64>,
+    player_uuid: &Uuid,
+) -> ApiResult<Option<ErrorData>> {
+    let err = if ONLINE_GAMES.is_user_in_game(player_uuid).await
+        || is_resumable_player(player_uuid).await
+    {
+        log::error!("Player {player_uuid} is already in a game");
+        ErrorData::AlreadyInGame
     } else if &ONLINE_GAMES.online_games_count().await >= max_online_games {
         log::error!(
-            "Player {} can't join the game because the max online games is reached",
-            player.0
+            "Player {player_uuid} can't join the game because the max online games is reached"
         );
+        ErrorData::MaxGamesReached
+    } else if is_daily_limit_reached(conn, player_uuid, daily_games_limit).await? {
+        log::error!("Player {player_uuid} reached the daily games limit");
+        ErrorData::DailyLimitReached
+    } else if SEARCH_FOR_GAME.is_user_in_search(player_uuid).await {
+        log::error!("Player {player_uuid} is already in the search queue");
+        ErrorData::AlreadyInSearch
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(err))
+}
+
+/// Starts a new game against the AI opponent, the player's symbol is random.
+async fn play_bot(
+    conn: &Arc<sea_orm::DatabaseConnection>,
+    max_online_games: &usize,
+    clock: GameClock,
+    daily_games_limit: Option<u64>,
+    random_opening_moves: u8,
+    player: Player,
+    difficulty: BotDifficulty,
+) -> ApiResult<()> {
+    log::info!(
+        "Player {} wants to play against the {} bot",
+        player.0,
+        difficulty.to_string()
+    );
+
+    if let Some(err) =
+        start_game_error(conn, max_online_games, daily_games_limit, &player.0).await?
+    {
+        player.1.send_server_event(XoServerEventData::Error(err));
+        return Ok(());
+    }
+
+    let bot_uuid = Uuid::new_v4();
+    let bot = spawn_bot(conn.as_ref().clone(), bot_uuid, difficulty, clock);
+    let (x_player, o_player) = if rand::random() {
+        (player, bot)
+    } else {
+        (bot, player)
+    };
+    start_game(
+        conn,
+        clock,
+        false,
+        random_opening_moves,
+        x_player,
+        o_player,
+        Some((bot_uuid, difficulty)),
+        GameVariant::default(),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Plays the player's next match in the tournament, the game starts when both players of the match are ready.
+/// If the opponent is not ready yet, the player waits for it.
+///
+/// The tournament games don't count in the daily games limit.
+async fn play_tournament(
+    conn: &sea_orm::DatabaseConnection,
+    max_online_games: &usize,
+    clock: GameClock,
+    random_opening_moves: u8,
+    player: Player,
+    tournament_uuid: Uuid,
+) -> ApiResult<()> {
+    log::info!(
+        "Player {} is ready to play in the tournament {tournament_uuid}",
+        player.0
+    );
+
+    let Some(tournament_match) =
+        db_utils::get_player_tournament_match(conn, tournament_uuid, *player.0).await?
+    else {
         player
             .1
-            .send_server_event(XoServerEventData::Error(ErrorData::MaxGamesReached));
-    } else if SEARCH_FOR_GAME.is_user_in_search(player.0.as_ref()).await {
-        log::error!("Player {} is already in the search queue", player.0);
-        player
-            .1
-            .send_server_event(XoServerEventData::Error(ErrorData::AlreadyInSearch));
-    } else if SEARCH_FOR_GAME.search_users_count().await > 0 {
-        let Some(other_player) = SEARCH_FOR_GAME.pop_front().await else {
-            unreachable!("The search queue is not empty")
-        };
-        let player = PlayerData::new(player.0, player.1, XoSymbol::O);
-        let other_player = PlayerData::new(other_player.0, other_player.1, XoSymbol::X);
+            .send_server_event(XoServerEventData::Error(ErrorData::NoTournamentMatch));
+        return Ok(());
+    };
+    if let Some(err) = start_game_error(conn, max_online_games, None, &player.0).await? {
+        player.1.send_server_event(XoServerEventData::Error(err));
+        return Ok(());
+    }
 
-        log::info!(
-            "Player {} found a game with player {}",
-            player.uuid,
-            other_player.uuid
-        );
+    let mut ready = TOURNAMENT_READY.write().await;
+    let opponent = match ready.remove(&tournament_match.id) {
+        Some(opponent) if opponent.0 != player.0 => opponent,
+        _ => {
+            ready.insert(tournament_match.id, player);
+            return Ok(());
+        }
+    };
+    if start_game_error(conn, max_online_games, None, &opponent.0)
+        .await?
+        .is_some()
+    {
+        // The opponent started another game while waiting
+        ready.insert(tournament_match.id, player);
+        return Ok(());
+    }
+    drop(ready);
 
-        let game =
-            db_utils::create_game(conn, *other_player.uuid, *player.uuid, move_period).await?;
+    let (x_player, o_player) = if tournament_match.x_player == Some(*player.0) {
+        (player, opponent)
+    } else {
+        (opponent, player)
+    };
+    let game_uuid = start_game(
+        conn,
+        clock,
+        false,
+        random_opening_moves,
+        x_player,
+        o_player,
+        None,
+        GameVariant::default(),
+    )
+    .await?;
+    db_utils::set_tournament_match_game(conn, tournament_match.id, game_uuid).await
+}
 
-        ONLINE_GAMES
-            .add_game(
-                *game.uuid.as_ref(),
-                (other_player.uuid.clone(), other_player.tx.clone()),
-                (player.uuid.clone(), player.tx.clone()),
-            )
-            .await;
+/// Returns the player's events sender, if the player is connected to the XO websocket.
+pub(crate) fn player_events_tx(player_uuid: &Uuid) -> Option<MessageSender> {
+    CONNECTIONS
+        .lock()
+        .expect("The lock is not poisoned")
+        .get(player_uuid)
+        .map(|connections| connections.events_tx.clone())
+}
 
-        ONLINE_GAMES
-            .broadcast_messages(
-                *game.uuid.as_ref(),
-                &[
-                    XoServerEventData::GameFound {
-                        x_player: *other_player.uuid,
-                        o_player: *player.uuid,
-                    },
-                    XoServerEventData::RoundStart { round: 1 },
-                ],
-            )
-            .await;
+/// Joins the private game of the invite code, the invite host is the X player.
+///
+/// Returns the error that prevents the player from joining the game, if any. The invite is used once,
+/// it's deleted when the game starts.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn join_invite(
+    conn: &sea_orm::DatabaseConnection,
+    max_online_games: &usize,
+    clock: GameClock,
+    unique_pair_games: bool,
+    daily_games_limit: Option<u64>,
+    random_opening_moves: u8,
+    player: Player,
+    code: &str,
+) -> ApiResult<Option<ErrorData>> {
+    log::info!("Player {} is joining an invite", player.0);
 
-        other_player
-            .tx
-            .send_server_event(XoServerEventData::YourTurn {
-                auto_play_after: game.auto_play_after.as_ref().unwrap().timestamp(),
-            });
+    let invite = match db_utils::get_invite(conn, code).await {
+        Ok(invite) => invite,
+        Err(ApiError::InvalidInviteCode) => return Ok(Some(ErrorData::InvalidInviteCode)),
+        Err(err) => return Err(err),
+    };
+    if invite.host_uuid == *player.0 {
+        return Ok(Some(ErrorData::CannotJoinOwnInvite));
+    }
+    if let Some(err) =
+        start_game_error(conn, max_online_games, daily_games_limit, &player.0).await?
+    {
+        return Ok(Some(err));
+    }
+    let Some(host_tx) = player_events_tx(&invite.host_uuid) else {
+        log::error!("The invite host {} is not connected", invite.host_uuid);
+        return Ok(Some(ErrorData::InviteHostUnavailable));
+    };
+    if start_game_error(conn, max_online_games, daily_games_limit, &invite.host_uuid)
+        .await?
+        .is_some()
+    {
+        log::error!("The invite host {} can't start a game", invite.host_uuid);
+        return Ok(Some(ErrorData::InviteHostUnavailable));
+    }
+    if !db_utils::delete_invite(conn, invite.id).await? {
+        // Another player joined the invite first
+        return Ok(Some(ErrorData::InvalidInviteCode));
+    }
+
+    start_game(
+        conn,
+        clock,
+        unique_pair_games,
+        random_opening_moves,
+        (Arc::new(invite.host_uuid), host_tx),
+        player,
+        None,
+        GameVariant::from(&invite),
+    )
+    .await?;
+    Ok(None)
+}
+
+/// Spawns the AI opponent virtual player, returns it. The bot plays when it's its turn, and stops when the
+/// game is over.
+fn spawn_bot(
+    conn: sea_orm::DatabaseConnection,
+    bot_uuid: Uuid,
+    difficulty: BotDifficulty,
+    clock: GameClock,
+) -> Player {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let bot: Player = (Arc::new(bot_uuid), Arc::new(tx));
+
+    let player = bot.clone();
+    tokio::task::spawn(async move {
+        while let Some(Ok(msg)) = rx.recv().await {
+            let Some(event) = msg
+                .to_str()
+                .ok()
+                .and_then(|msg| serde_json::from_str::<XoServerEvent>(msg).ok())
+            else {
+                continue;
+            };
+            match event.event {
+                XoServerEventKind::YourTurn => {
+                    if let Err(err) = bot_play(&conn, player.clone(), difficulty, clock).await {
+                        log::error!("The bot {bot_uuid} failed to play: {err}");
+                    }
+                }
+                XoServerEventKind::GameOver => break,
+                _ => {}
+            }
+        }
+        log::debug!("The bot {bot_uuid} stopped");
+    });
+    bot
+}
+
+/// Plays the AI opponent move in its game.
+async fn bot_play(
+    conn: &sea_orm::DatabaseConnection,
+    bot: Player,
+    difficulty: BotDifficulty,
+    clock: GameClock,
+) -> ApiResult<()> {
+    let Some((game_uuid, _)) = ONLINE_GAMES.get_user_game(&bot.0).await else {
+        return Ok(());
+    };
+    let game = db_utils::get_game::<false>(conn, &game_uuid).await?;
+    let board = Board::from_str(&game.board).expect("The board is valid");
+    let place = bot::choose_move(&board, difficulty);
+
+    log::info!("The bot {} is playing in place {place}", bot.0);
+    play(conn, bot, place, None, clock).await
+}
+
+/// Starts a new game between the two players, the `x_player` is the player that was waiting in the search queue.
+/// Returns the game uuid.
+///
+/// The game is against the AI opponent if the `bot` uuid and difficulty are provided, the bot is one of the
+/// players. The AI opponent only plays the classic variant.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn start_game(
+    conn: &sea_orm::DatabaseConnection,
+    clock: GameClock,
+    unique_pair_games: bool,
+    random_opening_moves: u8,
+    x_player: Player,
+    o_player: Player,
+    bot: Option<(Uuid, BotDifficulty)>,
+    variant: GameVariant,
+) -> ApiResult<Uuid> {
+    let player = PlayerData::new(o_player.0, o_player.1, XoSymbol::O);
+    let other_player = PlayerData::new(x_player.0, x_player.1, XoSymbol::X);
+
+    log::info!(
+        "Player {} found a game with player {}",
+        player.uuid,
+        other_player.uuid
+    );
+
+    let game = if let Some((bot_uuid, difficulty)) = bot {
+        db_utils::create_bot_game(
+            conn,
+            *other_player.uuid,
+            *player.uuid,
+            clock.move_period,
+            random_opening_moves,
+            bot_uuid,
+            difficulty,
+        )
+        .await?
+    } else {
+        db_utils::create_game(
+            conn,
+            *other_player.uuid,
+            *player.uuid,
+            clock.move_period,
+            unique_pair_games,
+            random_opening_moves,
+        )
+        .await?
+    };
+    let game = if let Some(game_time) = clock.game_time {
+        db_utils::set_game_time(conn, game, game_time).await?
+    } else {
+        game
+    };
+    let game = if variant.is_classic() {
+        game
     } else {
-        log::info!("Player {} is added to the search queue", player.0);
-        SEARCH_FOR_GAME.add_user(player).await;
+        db_utils::set_game_variant(conn, game, variant).await?
+    };
+    let board = Board::from_str(game.board.as_ref()).expect("The board is valid");
+
+    ONLINE_GAMES
+        .add_game(
+            conn,
+            *game.uuid.as_ref(),
+            (other_player.uuid.clone(), other_player.tx.clone()),
+            (player.uuid.clone(), player.tx.clone()),
+        )
+        .await;
+
+    ONLINE_GAMES
+        .broadcast_messages(
+            *game.uuid.as_ref(),
+            &[
+                XoServerEventData::GameFound {
+                    x_player: *other_player.uuid,
+                    o_player: *player.uuid,
+                    variant,
+                },
+                XoServerEventData::RoundStart {
+                    round: 1,
+                    opening: opening_places(&board),
+                },
+            ],
+        )
+        .await;
+
+    let data = XoServerEventData::YourTurn {
+        auto_play_after: game.auto_play_after.as_ref().unwrap().timestamp(),
+        time_left: clock.game_time.map(|game_time| game_time * 1000),
+    };
+    if board.turn() == other_player.symbol {
+        other_player.tx.send_server_event(data);
+    } else {
+        player.tx.send_server_event(data);
     }
 
-    Ok(())
+    Ok(*game.uuid.as_ref())
+}
+
+/// Returns the places of the random opening moves of a new round board.
+fn opening_places(board: &Board) -> Vec<u8> {
+    board
+        .played_cells()
+        .iter()
+        .map(|place| *place as u8)
+        .collect()
 }
 
 /// Play a move.
@@ -262,10 +1442,17 @@ async fn play(
     conn: &sea_orm::DatabaseConnection,
     player: Player,
     place: u8,
-    move_period: i64,
+    seen_moves: Option<u8>,
+    clock: GameClock,
 ) -> ApiResult<()> {
     if let Some((game_uuid, versus_player)) = ONLINE_GAMES.get_user_game(&player.0).await {
         log::info!("Player {} is playing in place {}", player.0, place);
+        if PAUSED_GAMES.read().await.contains_key(&game_uuid) {
+            player
+                .1
+                .send_server_event(XoServerEventData::Error(ErrorData::GamePaused));
+            return Ok(());
+        }
 
         let mut game = db_utils::get_game::<false>(conn, &game_uuid).await?;
         let mut board = Board::from_str(&game.board).expect("The board is valid");
@@ -274,19 +1461,40 @@ async fn play(
         } else {
             (XoSymbol::O, XoSymbol::X)
         };
+        if board.turn() == player_symbol && clock.is_timed_out(&game, player_symbol) {
+            // The move came after the player ran out of its time, before the auto play handler ends the game
+            return player_timed_out(conn, game_uuid, &player).await;
+        }
 
         let player = PlayerData::new(player.0, player.1, player_symbol);
         let versus_player = PlayerData::new(versus_player.0, versus_player.1, versus_symbol);
 
-        if utils::check_move_validity(&board, &player, place) {
-            versus_player
+        if let Err(err) = board.make_move(place, player.symbol, seen_moves) {
+            log::error!("Player {} move is rejected: {err}", player.uuid);
+            player
                 .tx
-                .send_server_event(XoServerEventData::Play(PlayData::new(place, *player.uuid)));
+                .send_server_event(XoServerEventData::Error(ErrorData::InvalidMove(err)));
+        } else {
+            (game.x_time_left, game.o_time_left) = clock.time_left(&game, player.symbol);
+            let time_left = if player.symbol == XoSymbol::X {
+                game.x_time_left
+            } else {
+                game.o_time_left
+            };
+            let play_data = XoServerEventData::Play(PlayData::new(place, *player.uuid, time_left));
+            versus_player.tx.send_server_event(play_data.clone());
+            SPECTATORS.broadcast_message(game_uuid, play_data).await;
             game.auto_play_after =
-                Some((chrono::Utc::now() + Duration::seconds(move_period)).naive_utc());
-            board.set_cell(place, player.symbol);
+                Some((chrono::Utc::now() + Duration::seconds(clock.move_period)).naive_utc());
             let mut rounds_result =
                 RoundsResult::from_str(&game.rounds_result).expect("The rounds result is valid");
+            let sequence = rounds_result
+                .boards()
+                .iter()
+                .chain([&board])
+                .map(|board| board.played_cells().len() as u32)
+                .sum();
+            db_utils::save_game_move(conn, game.uuid, sequence, game.round, place).await?;
 
             if board.is_win(&player.symbol) {
                 log::info!("Player {} won the round {}", player.uuid, game.round);
@@ -296,6 +1504,8 @@ async fn play(
                 rounds_result.draws += 1;
             }
 
+            // The player whose turn is next, if the game is not over
+            let mut next_turn = None;
             // Check if the game is over.
             // Game is over when the board is end and the round is 3 or the round is 2 and one of the players won 2 rounds.
             if board.is_end()
@@ -307,12 +1517,12 @@ async fn play(
                 let game_over_data =
                     utils::game_over_data(game.uuid, &rounds_result, &player, &versus_player);
 
+                let game_over = XoServerEventData::GameOver(game_over_data.clone());
                 ONLINE_GAMES
-                    .broadcast_message(
-                        game.uuid,
-                        XoServerEventData::GameOver(game_over_data.clone()),
-                    )
+                    .broadcast_message(game.uuid, game_over.clone())
                     .await;
+                SPECTATORS.broadcast_message(game.uuid, game_over).await;
+                SPECTATORS.remove_game(&game.uuid).await;
                 ONLINE_GAMES
                     .remove_game(
                         conn,
@@ -324,49 +1534,58 @@ async fn play(
             } else if board.is_end() {
                 // ^^ Check if the round is over
                 rounds_result.add_board(board.clone());
-                ONLINE_GAMES
-                    .broadcast_messages(
-                        game.uuid,
-                        &[
-                            XoServerEventData::RoundEnd(RoundData::new(
-                                game.round,
-                                if board.is_win(&player.symbol) {
-                                    Some(*player.uuid)
-                                } else {
-                                    None
-                                },
-                            )),
-                            XoServerEventData::RoundStart {
-                                round: game.round + 1,
-                            },
-                        ],
-                    )
-                    .await;
+                let next_board =
+                    Board::random_variant_opening(board.variant(), game.opening_moves as u8);
+                let messages = [
+                    XoServerEventData::RoundEnd(RoundData::new(
+                        game.round,
+                        if board.is_win(&player.symbol) {
+                            Some(*player.uuid)
+                        } else {
+                            None
+                        },
+                    )),
+                    XoServerEventData::RoundStart {
+                        round: game.round + 1,
+                        opening: opening_places(&next_board),
+                    },
+                ];
+                ONLINE_GAMES.broadcast_messages(game.uuid, &messages).await;
+                SPECTATORS.broadcast_messages(game.uuid, &messages).await;
 
-                board = Board::default();
+                board = next_board;
                 game.round += 1;
-                let data = XoServerEventData::YourTurn {
-                    auto_play_after: game.auto_play_after.unwrap().timestamp(),
-                };
-                if player.symbol == XoSymbol::X {
-                    player.tx.send_server_event(data);
+                next_turn = Some(if player.symbol == board.turn() {
+                    player.tx.clone()
                 } else {
-                    versus_player.tx.send_server_event(data);
-                }
+                    versus_player.tx.clone()
+                });
             } else {
-                versus_player
-                    .tx
-                    .send_server_event(XoServerEventData::YourTurn {
-                        auto_play_after: game.auto_play_after.unwrap().timestamp(),
-                    });
+                next_turn = Some(versus_player.tx.clone());
             }
 
+            let next_time_left = if board.turn() == XoSymbol::X {
+                game.x_time_left
+            } else {
+                game.o_time_left
+            };
             let mut game = game.into_active_model();
             game.board = Set(board.to_string());
             game.rounds_result = Set(rounds_result.to_string());
             game.round = Set(game.round.unwrap());
             game.auto_play_after = Set(game.auto_play_after.unwrap());
+            game.x_time_left = Set(game.x_time_left.unwrap());
+            game.o_time_left = Set(game.o_time_left.unwrap());
+            let auto_play_after = game.auto_play_after.as_ref().map(|time| time.timestamp());
             game.save(conn).await?;
+
+            // Sent after saving the game, so the player (and the bot) plays on the saved board
+            if let (Some(tx), Some(auto_play_after)) = (next_turn, auto_play_after) {
+                tx.send_server_event(XoServerEventData::YourTurn {
+                    auto_play_after,
+                    time_left: next_time_left,
+                });
+            }
         }
     } else {
         player
@@ -433,8 +1652,12 @@ async fn wellcome(
 }
 
 /// Chat event handler.
+///
+/// The message is saved with the game if `persist_chat` is `true`, it appears in the game replay.
 async fn chat(
     conn: &sea_orm::DatabaseConnection,
+    chat_limiter: &ChatLimiter,
+    persist_chat: bool,
     player: Player,
     encrypted_message: String,
     signature: String,
@@ -443,6 +1666,18 @@ async fn chat(
 
     if let Some((game_uuid, versus_player)) = ONLINE_GAMES.get_user_game(&player.0).await {
         log::info!("Player {} is in a game", player.0);
+        if let Err(err) = chat_limiter.check(game_uuid, *player.0, encrypted_message.len()) {
+            log::error!("Player {} chat message is rejected: {err:?}", player.0);
+            player.1.send_server_event(XoServerEventData::Error(err));
+            return Ok(());
+        }
+        if db_utils::is_blocked(conn, *versus_player.0, *player.0).await? {
+            log::error!("Player {} is blocked by the other player", player.0);
+            player
+                .1
+                .send_server_event(XoServerEventData::Error(ErrorData::Blocked));
+            return Ok(());
+        }
         if PGPMessage::from_string(&encrypted_message).is_err() {
             log::error!("Player {} sent an invalid encrypted message", player.0);
             player
@@ -486,10 +1721,22 @@ async fn chat(
             return Ok(());
         }
 
-        versus_player.tx.send_server_event(XoServerEventData::Chat {
+        if persist_chat {
+            db_utils::save_chat_message(
+                conn,
+                game_uuid,
+                *player.uuid,
+                encrypted_message.clone(),
+                signature.clone(),
+            )
+            .await?;
+        }
+        let chat = XoServerEventData::Chat {
             encrypted_message,
             signature,
-        });
+        };
+        versus_player.tx.send_server_event(chat.clone());
+        SPECTATORS.broadcast_message(game_uuid, chat).await;
     } else {
         player
             .1
@@ -499,25 +1746,92 @@ async fn chat(
 }
 
 /// The user disconnected handler.
-async fn player_disconnected(conn: &sea_orm::DatabaseConnection, player: Player) -> ApiResult<()> {
+/// Ends the game of the player that ran out of its time, the other player wins with the
+/// [`GameOverReason::Timeout`] reason. The players and the spectators receive the `game_over` event.
+async fn player_timed_out(
+    conn: &sea_orm::DatabaseConnection,
+    game_uuid: Uuid,
+    player: &Player,
+) -> ApiResult<()> {
+    let Some((x_player, o_player)) = ONLINE_GAMES.get_game_players(&game_uuid).await else {
+        return Ok(());
+    };
+    log::info!(
+        "Player {} ran out of its time in game {game_uuid}",
+        player.0
+    );
+
+    let winner = if x_player.0 == player.0 {
+        *o_player.0
+    } else {
+        *x_player.0
+    };
+    let game_over = XoServerEventData::GameOver(GameOverData::new(
+        game_uuid,
+        Some(winner),
+        GameOverReason::Timeout,
+    ));
+    ONLINE_GAMES
+        .broadcast_message(game_uuid, game_over.clone())
+        .await;
+    SPECTATORS.broadcast_message(game_uuid, game_over).await;
+    SPECTATORS.remove_game(&game_uuid).await;
+    ONLINE_GAMES
+        .remove_game(conn, &game_uuid, Some(winner), &GameOverReason::Timeout)
+        .await
+}
+
+async fn player_disconnected(
+    conn: &sea_orm::DatabaseConnection,
+    player: Player,
+    clock: GameClock,
+) -> ApiResult<()> {
     log::info!("Player {} disconnected", player.0);
+    if SHUTTING_DOWN.load(Ordering::SeqCst) {
+        // The player's game is saved, it's resumed after the restart
+        return Ok(());
+    }
+
+    // If the player disconnected while waiting for its tournament match opponent, then it's not ready anymore.
+    TOURNAMENT_READY
+        .write()
+        .await
+        .retain(|_, ready| ready.0 != player.0);
 
-    // If the player disconnected while in a game, then the other player will win.
+    // If the player disconnected while in a game, then the game is paused for it to reconnect,
+    // or the other player will win if there is no reconnection window.
     if let Some((game_uuid, versus_player)) = ONLINE_GAMES.get_user_game(&player.0).await {
         log::info!("Player {} disconnected while in a game", player.0);
 
-        versus_player
-            .1
-            .send_server_event(XoServerEventData::GameOver(GameOverData::new(
+        let winner = if PAUSED_GAMES.write().await.remove(&game_uuid).is_some() {
+            // Both players disconnected
+            None
+        } else if let Some(reconnect_window) = clock.reconnect_window {
+            return pause_game(
+                conn,
                 game_uuid,
-                Some(*versus_player.0),
-                GameOverReason::PlayerDisconnected,
-            )));
+                &player,
+                &versus_player,
+                clock,
+                reconnect_window,
+            )
+            .await;
+        } else {
+            Some(*versus_player.0)
+        };
+        let game_over = XoServerEventData::GameOver(GameOverData::new(
+            game_uuid,
+            winner,
+            GameOverReason::PlayerDisconnected,
+        ));
+        versus_player.1.send_server_event(game_over.clone());
+        SPECTATORS.broadcast_message(game_uuid, game_over).await;
+        SPECTATORS.remove_game(&game_uuid).await;
         ONLINE_GAMES
             .remove_game(
                 conn,
                 &game_uuid,
-                Some(*versus_player.0),
+                winner,
                 &GameOverReason::PlayerDisconnected,
             )
             .await?;
@@ -529,15 +1843,497 @@ async fn player_disconnected(conn: &sea_orm::DatabaseConnection, player: Player)
         );
 
         SEARCH_FOR_GAME.remove_user(&player.0).await;
+        PAUSED_SEARCH.write().await.remove(player.0.as_ref());
+    } else {
+        // ^ If the player disconnected while waiting for the other player of its previous game, then it's not reconnected anymore.
+        for game in RESUMABLE_GAMES.write().await.values_mut() {
+            game.reconnected
+                .retain(|reconnected| reconnected.0 != player.0);
+        }
     }
     Ok(())
 }
 
-/// Auto play handler.
+/// Pauses the game of the disconnected player for `reconnect_window` seconds, the other player and the
+/// spectators receive the `opponent_disconnected` event. The player turn time before the disconnection
+/// is counted, and the turn restarts when it reconnects.
+async fn pause_game(
+    conn: &sea_orm::DatabaseConnection,
+    game_uuid: Uuid,
+    player: &Player,
+    versus_player: &Player,
+    clock: GameClock,
+    reconnect_window: i64,
+) -> ApiResult<()> {
+    log::info!(
+        "Pausing the game {game_uuid} for {reconnect_window} seconds, until player {} reconnects",
+        player.0
+    );
+    save_game_clocks(conn, &game_uuid, clock).await?;
+
+    let reconnect_before = (chrono::Utc::now() + Duration::seconds(reconnect_window)).naive_utc();
+    PAUSED_GAMES.write().await.insert(
+        game_uuid,
+        PausedGame {
+            player: *player.0,
+            reconnect_before,
+        },
+    );
+    let event = XoServerEventData::OpponentDisconnected {
+        player: *player.0,
+        reconnect_before: reconnect_before.timestamp(),
+    };
+    versus_player.1.send_server_event(event.clone());
+    SPECTATORS.broadcast_message(game_uuid, event).await;
+
+    let conn = conn.clone();
+    tokio::task::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_secs(reconnect_window as u64)).await;
+        if let Err(err) = expire_paused_game(&conn, game_uuid, reconnect_before).await {
+            log::error!("Failed to end the paused game {game_uuid}: {err}");
+        }
+    });
+    Ok(())
+}
+
+/// Stops the game turn, the player turn time until now is counted in its time left and the turn restarts
+/// when the game is resumed.
+async fn save_game_clocks(
+    conn: &sea_orm::DatabaseConnection,
+    game_uuid: &Uuid,
+    clock: GameClock,
+) -> ApiResult<()> {
+    let game = db_utils::get_game::<false>(conn, game_uuid).await?;
+    let board = Board::from_str(&game.board).expect("The board is valid");
+    let (x_time_left, o_time_left) = clock.time_left(&game, board.turn());
+    let mut game = game.into_active_model();
+    // The games without a move deadline are skipped by the auto play handler
+    game.auto_play_after = Set(None);
+    game.x_time_left = Set(x_time_left);
+    game.o_time_left = Set(o_time_left);
+    game.save(conn).await?;
+    Ok(())
+}
+
+/// Ends the paused game if its player didn't reconnect before `reconnect_before`, the other player wins.
+async fn expire_paused_game(
+    conn: &sea_orm::DatabaseConnection,
+    game_uuid: Uuid,
+    reconnect_before: chrono::NaiveDateTime,
+) -> ApiResult<()> {
+    let paused_game = {
+        let mut paused_games = PAUSED_GAMES.write().await;
+        match paused_games.get(&game_uuid) {
+            // Not resumed, and not paused again after a resume
+            Some(paused_game) if paused_game.reconnect_before == reconnect_before => {
+                paused_games.remove(&game_uuid)
+            }
+            _ => None,
+        }
+    };
+    let (Some(paused_game), Some((x_player, o_player))) =
+        (paused_game, ONLINE_GAMES.get_game_players(&game_uuid).await)
+    else {
+        return Ok(());
+    };
+    log::info!(
+        "Player {} didn't reconnect to the game {game_uuid}",
+        paused_game.player
+    );
+
+    let versus_player = if *x_player.0 == paused_game.player {
+        o_player
+    } else {
+        x_player
+    };
+    let game_over = XoServerEventData::GameOver(GameOverData::new(
+        game_uuid,
+        Some(*versus_player.0),
+        GameOverReason::PlayerDisconnected,
+    ));
+    versus_player.1.send_server_event(game_over.clone());
+    SPECTATORS.broadcast_message(game_uuid, game_over).await;
+    SPECTATORS.remove_game(&game_uuid).await;
+    ONLINE_GAMES
+        .remove_game(
+            conn,
+            &game_uuid,
+            Some(*versus_player.0),
+            &GameOverReason::PlayerDisconnected,
+        )
+        .await
+}
+
+/// Resumes the paused game of the reconnected player if any, the player receives the `game_resumed` event
+/// and the other player and the spectators receive the `opponent_reconnected` event.
+async fn reconnect_game(
+    conn: &sea_orm::DatabaseConnection,
+    player: Player,
+    clock: GameClock,
+) -> ApiResult<()> {
+    let game_uuid = {
+        let mut paused_games = PAUSED_GAMES.write().await;
+        let Some(game_uuid) = paused_games
+            .iter()
+            .find(|(_, paused_game)| &paused_game.player == player.0.as_ref())
+            .map(|(game_uuid, _)| *game_uuid)
+        else {
+            return Ok(());
+        };
+        paused_games.remove(&game_uuid);
+        game_uuid
+    };
+    log::info!("Player {} reconnected to the game {game_uuid}", player.0);
+
+    ONLINE_GAMES
+        .replace_player(&game_uuid, player.clone())
+        .await;
+    let Some((x_player, o_player)) = ONLINE_GAMES.get_game_players(&game_uuid).await else {
+        return Ok(());
+    };
+    let game = db_utils::get_game::<false>(conn, &game_uuid).await?;
+    let board = Board::from_str(&game.board).expect("The board is valid");
+    let rounds_result =
+        RoundsResult::from_str(&game.rounds_result).expect("The rounds result is valid");
+    let auto_play_after = (chrono::Utc::now() + Duration::seconds(clock.move_period)).naive_utc();
+    let (round, x_time_left, o_time_left) = (game.round, game.x_time_left, game.o_time_left);
+
+    let mut game = game.into_active_model();
+    game.auto_play_after = Set(Some(auto_play_after));
+    game.save(conn).await?;
+
+    player
+        .1
+        .send_server_event(XoServerEventData::GameResumed(GameResumedData::new(
+            game_uuid,
+            *x_player.0,
+            *o_player.0,
+            round,
+            board.clone(),
+            rounds_result,
+            x_time_left,
+            o_time_left,
+        )));
+    let event = XoServerEventData::OpponentReconnected { player: *player.0 };
+    let versus_player = if x_player.0 == player.0 {
+        &o_player
+    } else {
+        &x_player
+    };
+    versus_player.1.send_server_event(event.clone());
+    SPECTATORS.broadcast_message(game_uuid, event).await;
+
+    send_your_turn(
+        &x_player,
+        &o_player,
+        board.turn(),
+        auto_play_after,
+        x_time_left,
+        o_time_left,
+    );
+    Ok(())
+}
+
+/// Sends the `your_turn` event to the player whose turn it is.
+fn send_your_turn(
+    x_player: &Player,
+    o_player: &Player,
+    turn: XoSymbol,
+    auto_play_after: chrono::NaiveDateTime,
+    x_time_left: Option<i64>,
+    o_time_left: Option<i64>,
+) {
+    let (player, time_left) = if turn == XoSymbol::X {
+        (x_player, x_time_left)
+    } else {
+        (o_player, o_time_left)
+    };
+    player.1.send_server_event(XoServerEventData::YourTurn {
+        auto_play_after: auto_play_after.timestamp(),
+        time_left,
+    });
+}
+
+/// Ends the in-progress game without a winner, with the [`GameOverReason::ForceEnded`] reason. The players and
+/// the spectators receive the `game_over` event.
+pub(crate) async fn force_end_game(
+    conn: &sea_orm::DatabaseConnection,
+    game_uuid: Uuid,
+) -> ApiResult<()> {
+    // Checks that the game is in progress
+    db_utils::get_game::<false>(conn, &game_uuid).await?;
+    log::warn!("Force ending the game {game_uuid}");
+    PAUSED_GAMES.write().await.remove(&game_uuid);
+
+    if ONLINE_GAMES.get_game_players(&game_uuid).await.is_none() {
+        // The game of the previous server run, it's not resumed yet
+        RESUMABLE_GAMES.write().await.remove(&game_uuid);
+        db_utils::end_game(conn, &game_uuid, None, &GameOverReason::ForceEnded).await?;
+        super::notification::tournament_game_ended(conn, game_uuid).await;
+        return Ok(());
+    }
+    let game_over = XoServerEventData::GameOver(GameOverData::new(
+        game_uuid,
+        None,
+        GameOverReason::ForceEnded,
+    ));
+    ONLINE_GAMES
+        .broadcast_message(game_uuid, game_over.clone())
+        .await;
+    SPECTATORS.broadcast_message(game_uuid, game_over).await;
+    SPECTATORS.remove_game(&game_uuid).await;
+    ONLINE_GAMES
+        .remove_game(conn, &game_uuid, None, &GameOverReason::ForceEnded)
+        .await
+}
+
+/// Loads the in-progress games of the previous server run, to resume them when their players reconnect.
+/// Returns the number of the loaded games.
+///
+/// The game state is saved after each move, so a game continues from its last move when both players
+/// reconnect within the `grace_period`. After it, if only one player reconnected it wins the game, and
+/// if none of them, the game ends without a winner. The AI opponent of the games against it is always
+/// reconnected, with the `move_period`.
+pub async fn resume_games(
+    conn: sea_orm::DatabaseConnection,
+    grace_period: std::time::Duration,
+    clock: GameClock,
+) -> ApiResult<usize> {
+    let mut loaded_games = Vec::new();
+    {
+        let mut resumable_games = RESUMABLE_GAMES.write().await;
+        for game in db_utils::get_online_games(&conn).await? {
+            if ONLINE_GAMES.get_game_players(&game.uuid).await.is_some()
+                || resumable_games.contains_key(&game.uuid)
+            {
+                continue;
+            }
+            let mut reconnected = Vec::new();
+            if let (Some(difficulty), Some(bot_uuid)) = (&game.bot_difficulty, game.bot_uuid) {
+                // The bot is always connected, the game is resumed when the player reconnects
+                reconnected.push(spawn_bot(
+                    conn.clone(),
+                    bot_uuid,
+                    difficulty.parse().expect("Is valid bot difficulty"),
+                    clock,
+                ));
+            }
+            resumable_games.insert(
+                game.uuid,
+                ResumableGame {
+                    x_player: game.x_player,
+                    o_player: game.o_player,
+                    reconnected,
+                },
+            );
+            loaded_games.push(game.uuid);
+        }
+    }
+    log::info!(
+        "Loaded {} in-progress games, waiting {grace_period:?} for their players to reconnect",
+        loaded_games.len()
+    );
+
+    let count = loaded_games.len();
+    tokio::task::spawn(async move {
+        tokio::time::sleep(grace_period).await;
+        for game_uuid in loaded_games {
+            if let Err(err) = expire_resumable_game(&conn, game_uuid).await {
+                log::error!("Failed to end the unresumed game {game_uuid}: {err}");
+            }
+        }
+    });
+    Ok(count)
+}
+
+/// Prepares the XO games for the server shutdown, returns the number of the saved in-progress games.
+///
+/// The players events are rejected after it, so no game starts and no move is played. The clocks of the
+/// in-progress games are saved like the paused games, then the games are loaded after the restart by
+/// [`resume_games`] and continue with the same time left. The connected players and spectators receive
+/// the `server_shutdown` event with the `grace_period` of the restart, then their connections are closed
+/// with the [`SERVER_SHUTDOWN_CLOSE_CODE`] code.
+pub async fn shutdown(
+    conn: &sea_orm::DatabaseConnection,
+    clock: GameClock,
+    grace_period: std::time::Duration,
+) -> usize {
+    SHUTTING_DOWN.store(true, Ordering::SeqCst);
+    log::info!("Shutting down the XO games");
+
+    // The games are not ended when their players disconnect
+    let games = std::mem::take(&mut *ONLINE_GAMES.write().await);
+    PAUSED_GAMES.write().await.clear();
+    RESUMABLE_GAMES.write().await.clear();
+    SEARCH_FOR_GAME.write().await.clear();
+    PAUSED_SEARCH.write().await.clear();
+    TOURNAMENT_READY.write().await.clear();
+
+    let mut saved_games = 0;
+    for game_uuid in games.keys() {
+        match save_game_clocks(conn, game_uuid, clock).await {
+            Ok(()) => saved_games += 1,
+            Err(err) => log::error!("Failed to save the game {game_uuid}: {err}"),
+        }
+    }
+    log::info!("Saved {saved_games} in-progress games");
+
+    let event = XoServerEventData::ServerShutdown {
+        reconnect_within: grace_period.as_secs(),
+    };
+    let connections = std::mem::take(&mut *CONNECTIONS.lock().expect("The lock is not poisoned"));
+    let spectators = std::mem::take(&mut *SPECTATORS.write().await);
+    for socket_tx in connections
+        .into_values()
+        .flat_map(|player_connections| {
+            player_connections
+                .sockets
+                .into_iter()
+                .map(|(_, socket_tx)| socket_tx)
+        })
+        .chain(spectators.into_values().flatten())
+    {
+        socket_tx.send_server_event(event.clone());
+        socket_tx
+            .send(Ok(Message::close_with(
+                SERVER_SHUTDOWN_CLOSE_CODE,
+                "The server is shutting down",
+            )))
+            .ok();
+    }
+    saved_games
+}
+
+/// Returns whether the player has a game of the previous server run that is not resumed yet.
+async fn is_resumable_player(player_uuid: &Uuid) -> bool {
+    RESUMABLE_GAMES
+        .read()
+        .await
+        .values()
+        .any(|game| &game.x_player == player_uuid || &game.o_player == player_uuid)
+}
+
+/// Rejoins the player to its game of the previous server run if any, the game is resumed when both players rejoin.
+async fn rejoin_game(
+    conn: &sea_orm::DatabaseConnection,
+    player: Player,
+    clock: GameClock,
+) -> ApiResult<()> {
+    let game = {
+        let mut resumable_games = RESUMABLE_GAMES.write().await;
+        let Some((&game_uuid, game)) = resumable_games.iter_mut().find(|(_, game)| {
+            &game.x_player == player.0.as_ref() || &game.o_player == player.0.as_ref()
+        }) else {
+            return Ok(());
+        };
+        if !game
+            .reconnected
+            .iter()
+            .any(|reconnected| reconnected.0 == player.0)
+        {
+            log::info!("Player {} rejoined the game {game_uuid}", player.0);
+            game.reconnected.push(player);
+        }
+        if game.reconnected.len() < 2 {
+            return Ok(());
+        }
+        (
+            game_uuid,
+            resumable_games
+                .remove(&game_uuid)
+                .expect("The game is in the resumable games"),
+        )
+    };
+    let (game_uuid, ResumableGame { reconnected, .. }) = game;
+    log::info!("Resuming the game {game_uuid}");
+
+    let game = db_utils::get_game::<false>(conn, &game_uuid).await?;
+    let (x_player, o_player) = if reconnected[0].0.as_ref() == &game.x_player {
+        (reconnected[0].clone(), reconnected[1].clone())
+    } else {
+        (reconnected[1].clone(), reconnected[0].clone())
+    };
+    let board = Board::from_str(&game.board).expect("The board is valid");
+    let rounds_result =
+        RoundsResult::from_str(&game.rounds_result).expect("The rounds result is valid");
+    let auto_play_after = (chrono::Utc::now() + Duration::seconds(clock.move_period)).naive_utc();
+    let round = game.round;
+    // The turn is restarted, so the player's time left is not counted while the server was down
+    let (x_time_left, o_time_left) = (game.x_time_left, game.o_time_left);
+
+    let mut game = game.into_active_model();
+    game.auto_play_after = Set(Some(auto_play_after));
+    game.save(conn).await?;
+
+    ONLINE_GAMES
+        .add_game(conn, game_uuid, x_player.clone(), o_player.clone())
+        .await;
+    ONLINE_GAMES
+        .broadcast_message(
+            game_uuid,
+            XoServerEventData::GameResumed(GameResumedData::new(
+                game_uuid,
+                *x_player.0,
+                *o_player.0,
+                round,
+                board.clone(),
+                rounds_result,
+                x_time_left,
+                o_time_left,
+            )),
+        )
+        .await;
+
+    send_your_turn(
+        &x_player,
+        &o_player,
+        board.turn(),
+        auto_play_after,
+        x_time_left,
+        o_time_left,
+    );
+    Ok(())
+}
+
+/// Ends the game of the previous server run if it's not resumed in the grace period.
+async fn expire_resumable_game(
+    conn: &sea_orm::DatabaseConnection,
+    game_uuid: Uuid,
+) -> ApiResult<()> {
+    let Some(game) = RESUMABLE_GAMES.write().await.remove(&game_uuid) else {
+        // Already resumed
+        return Ok(());
+    };
+    log::info!("The game {game_uuid} is not resumed in the grace period");
+
+    let winner = match game.reconnected.as_slice() {
+        [player] => {
+            player
+                .1
+                .send_server_event(XoServerEventData::GameOver(GameOverData::new(
+                    game_uuid,
+                    Some(*player.0),
+                    GameOverReason::PlayerDisconnected,
+                )));
+            Some(*player.0)
+        }
+        _ => None,
+    };
+    db_utils::end_game(
+        conn,
+        &game_uuid,
+        winner,
+        &GameOverReason::PlayerDisconnected,
+    )
+    .await
+}
+
+/// Auto play handler, plays for the players whose move period ended and ends the games of the players
+/// that ran out of their time.
 /// ### Note
 /// This function will run while there is at least one online game, if not then it will wait 5 seconds and check again.
-pub async fn auto_play_handler(conn: sea_orm::DatabaseConnection, move_period: i64) {
-    async fn inner(conn: &sea_orm::DatabaseConnection, move_period: i64) -> ApiResult<()> {
+pub async fn auto_play_handler(conn: sea_orm::DatabaseConnection, clock: GameClock) {
+    async fn inner(conn: &sea_orm::DatabaseConnection, clock: GameClock) -> ApiResult<()> {
         log::info!("Starting auto play handler");
 
         loop {
@@ -546,16 +2342,22 @@ pub async fn auto_play_handler(conn: sea_orm::DatabaseConnection, move_period: i
                 for game in games {
                     if let Some(auto_play_after) = game.auto_play_after {
                         let board = Board::from_str(&game.board).expect("The board is valid");
-                        if chrono::Utc::now().naive_utc() >= auto_play_after {
-                            let players = ONLINE_GAMES
-                                .get_game_players(&game.uuid)
-                                .await
-                                .expect("The game should be in the online games");
+                        let timed_out = clock.is_timed_out(&game, board.turn());
+                        if timed_out || chrono::Utc::now().naive_utc() >= auto_play_after {
+                            let Some(players) = ONLINE_GAMES.get_game_players(&game.uuid).await
+                            else {
+                                // The game of the previous server run, it's not resumed yet
+                                continue;
+                            };
                             let player = if board.turn() == XoSymbol::X {
                                 players.0.clone()
                             } else {
                                 players.1.clone()
                             };
+                            if timed_out {
+                                player_timed_out(conn, game.uuid, &player).await?;
+                                continue;
+                            }
 
                             let place = board
                             .empty_cells()
@@ -572,7 +2374,7 @@ pub async fn auto_play_handler(conn: sea_orm::DatabaseConnection, move_period: i
                                 player.0,
                                 game.uuid
                             );
-                            play(conn, player.clone(), place, move_period).await?;
+                            play(conn, player.clone(), place, None, clock).await?;
                         }
                     }
                 }
@@ -585,7 +2387,7 @@ pub async fn auto_play_handler(conn: sea_orm::DatabaseConnection, move_period: i
     }
 
     loop {
-        if let Err(err) = inner(&conn, move_period).await {
+        if let Err(err) = inner(&conn, clock).await {
             log::error!("Auto play handler error: {err}");
             tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
         }
@@ -14,15 +14,15 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::env;
+use std::{env, sync::Arc, time::Duration};
 
 use migration::{Migrator, MigratorTrait};
 use salvo::prelude::*;
-use xors_api::*;
+use xors_api::{api::captcha_provider::*, *};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    pretty_env_logger::init();
+    logging::init();
 
     let host = env::var("XORS_API_HOST").expect("`XORS_API_HOST` environment variable must be set");
     let port = env::var("XORS_API_PORT").expect("`XORS_API_PORT` environment variable must be set");
@@ -31,8 +31,54 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .expect("`XORS_API_DATABASE_URL` environment variable must be set"),
     )
     .await?;
-    let secret_key = env::var("XORS_API_SECRET_KEY")
-        .expect("`XORS_API_SECRET_KEY` environment variable must be set");
+    let secret_key = env::var("XORS_API_SECRET_KEY").ok();
+    let jwt_algorithm = env::var("XORS_API_JWT_ALGORITHM")
+        .map(|value| {
+            value.parse::<api::jwt::JwtAlgorithm>().expect(
+                "`XORS_API_JWT_ALGORITHM` environment variable must be `HS256`, `RS256` or `EdDSA`",
+            )
+        })
+        .unwrap_or_default();
+    let jwt_keys = match jwt_algorithm {
+        api::jwt::JwtAlgorithm::HS256 => api::jwt::JwtKeys::hs256(
+            secret_key
+                .as_deref()
+                .expect("`XORS_API_SECRET_KEY` environment variable must be set"),
+        )
+        .with_previous(
+            env::var("XORS_API_PREVIOUS_SECRET_KEYS")
+                .unwrap_or_default()
+                .split(',')
+                .filter(|secret| !secret.trim().is_empty())
+                .map(|secret| api::jwt::JwtKey::hs256(secret.trim()))
+                .collect(),
+        ),
+        api::jwt::JwtAlgorithm::RS256 | api::jwt::JwtAlgorithm::EdDSA => {
+            let path = env::var("XORS_API_JWT_PRIVATE_KEY_FILE").expect(
+                "`XORS_API_JWT_PRIVATE_KEY_FILE` environment variable must be set with the `RS256` and `EdDSA` algorithms",
+            );
+            api::jwt::JwtKeys::from_file(jwt_algorithm, path)
+                .unwrap_or_else(|err| panic!("{err}"))
+                .with_previous(
+                    env::var("XORS_API_JWT_PREVIOUS_KEY_FILES")
+                        .unwrap_or_default()
+                        .split(',')
+                        .filter(|path| !path.trim().is_empty())
+                        .map(|path| {
+                            let path = path.trim();
+                            let private_key = std::fs::read_to_string(path).unwrap_or_else(|err| {
+                                panic!(
+                                    "Failed to read the previous JWT private key `{path}`: {err}"
+                                )
+                            });
+                            api::jwt::JwtKey::from_pem(&private_key).unwrap_or_else(|err| {
+                                panic!("Invalid previous JWT private key `{path}`: {err}")
+                            })
+                        })
+                        .collect(),
+                )
+        }
+    };
     let max_online_games = env::var("XORS_API_MAX_ONLINE_GAMES")
         .expect("`XORS_API_MAX_ONLINE_GAMES` environment variable must be set")
         .parse::<usize>()
@@ -44,10 +90,556 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     if move_period < 0 {
         panic!("`XORS_API_MOVE_PERIOD` environment variable must be a positive number");
     }
+    let mut game_clock = api::xo::GameClock::new(move_period).with_move_timeout_policy(
+        env::var("XORS_API_MOVE_TIMEOUT_POLICY")
+            .map(|value| {
+                value
+                    .parse::<api::xo::MoveTimeoutPolicy>()
+                    .expect("`XORS_API_MOVE_TIMEOUT_POLICY` environment variable must be `auto_play` or `forfeit`")
+            })
+            .unwrap_or_default(),
+    );
+    if let Ok(value) = env::var("XORS_API_GAME_TIME") {
+        let game_time = value
+            .parse::<i64>()
+            .ok()
+            .filter(|game_time| game_time > &0)
+            .expect("`XORS_API_GAME_TIME` environment variable must be a positive number");
+        game_clock = game_clock.with_game_time(game_time);
+    }
+    let reconnect_window = env::var("XORS_API_RECONNECT_WINDOW")
+        .map(|value| {
+            value
+                .parse::<i64>()
+                .ok()
+                .filter(|window| window >= &0)
+                .expect(
+                    "`XORS_API_RECONNECT_WINDOW` environment variable must be a positive number",
+                )
+        })
+        .unwrap_or(60);
+    if reconnect_window > 0 {
+        game_clock = game_clock.with_reconnect_window(reconnect_window);
+    }
+    let unique_pair_games = env::var("XORS_API_UNIQUE_PAIR_GAMES")
+        .map(|value| {
+            value
+                .parse::<bool>()
+                .expect("`XORS_API_UNIQUE_PAIR_GAMES` environment variable must be a boolean")
+        })
+        .unwrap_or_default();
+    let daily_games_limit = env::var("XORS_API_DAILY_GAMES_LIMIT").ok().map(|value| {
+        value
+            .parse::<u64>()
+            .expect("`XORS_API_DAILY_GAMES_LIMIT` environment variable must be a number")
+    });
+    let min_rated_games = env::var("XORS_API_MIN_RATED_GAMES")
+        .map(|value| {
+            value
+                .parse::<u64>()
+                .expect("`XORS_API_MIN_RATED_GAMES` environment variable must be a number")
+        })
+        .unwrap_or(api::DEFAULT_MIN_RATED_GAMES);
+    let random_opening_moves = env::var("XORS_API_RANDOM_OPENING_MOVES")
+        .map(|value| {
+            value
+                .parse::<u8>()
+                .ok()
+                .filter(|moves| moves <= &schemas::MAX_OPENING_MOVES)
+                .unwrap_or_else(|| {
+                    panic!(
+                        "`XORS_API_RANDOM_OPENING_MOVES` environment variable must be a number between 0 and {}",
+                        schemas::MAX_OPENING_MOVES
+                    )
+                })
+        })
+        .unwrap_or_default();
+    let matchmaking_tolerance = |name: &str, default: u64| {
+        env::var(name)
+            .map(|value| {
+                value
+                    .parse::<u64>()
+                    .unwrap_or_else(|_| panic!("`{name}` environment variable must be a number"))
+            })
+            .unwrap_or(default)
+    };
+    let default_matchmaking = api::matchmaking::MatchmakingPolicy::default();
+    let matchmaking = api::matchmaking::MatchmakingPolicy {
+        initial_tolerance: matchmaking_tolerance(
+            "XORS_API_MATCHMAKING_INITIAL_TOLERANCE",
+            default_matchmaking.initial_tolerance,
+        ),
+        tolerance_growth: matchmaking_tolerance(
+            "XORS_API_MATCHMAKING_TOLERANCE_GROWTH",
+            default_matchmaking.tolerance_growth,
+        ),
+        max_tolerance: matchmaking_tolerance(
+            "XORS_API_MATCHMAKING_MAX_TOLERANCE",
+            default_matchmaking.max_tolerance,
+        ),
+        // Zero disables the queue timeout
+        queue_timeout: env::var("XORS_API_MATCHMAKING_QUEUE_TIMEOUT")
+            .map(|value| {
+                value.parse::<u64>().expect(
+                    "`XORS_API_MATCHMAKING_QUEUE_TIMEOUT` environment variable must be a number",
+                )
+            })
+            .map_or(default_matchmaking.queue_timeout, |timeout| {
+                (timeout > 0).then_some(timeout)
+            }),
+    };
+    if let Err(err) = matchmaking.validate() {
+        panic!("Invalid matchmaking policy: {err}");
+    }
+    let persist_chat = env::var("XORS_API_PERSIST_CHAT")
+        .map(|value| {
+            value
+                .parse::<bool>()
+                .expect("`XORS_API_PERSIST_CHAT` environment variable must be a boolean")
+        })
+        .unwrap_or_default();
+    let chat_limiter = api::xo::ChatLimiter::new(
+        env::var("XORS_API_CHAT_MAX_MESSAGES")
+            .map(|value| {
+                value
+                    .parse::<usize>()
+                    .expect("`XORS_API_CHAT_MAX_MESSAGES` environment variable must be a number")
+            })
+            .unwrap_or(5),
+        env::var("XORS_API_CHAT_MAX_MESSAGE_SIZE")
+            .map(|value| {
+                value.parse::<usize>().expect(
+                    "`XORS_API_CHAT_MAX_MESSAGE_SIZE` environment variable must be a number",
+                )
+            })
+            .unwrap_or(8192),
+    );
+    let token_expiry_policy = env::var("XORS_API_WS_TOKEN_EXPIRY_POLICY")
+        .map(|value| {
+            value
+                .parse::<api::xo::TokenExpiryPolicy>()
+                .expect("`XORS_API_WS_TOKEN_EXPIRY_POLICY` environment variable must be `close` or `reauthenticate`")
+        })
+        .unwrap_or_default();
+    let token_expiring_before = env::var("XORS_API_WS_TOKEN_EXPIRING_BEFORE")
+        .ok()
+        .map(|value| match value.parse::<u64>() {
+            Ok(before) if before > 0 => before,
+            _ => panic!(
+                "`XORS_API_WS_TOKEN_EXPIRING_BEFORE` environment variable must be a positive number"
+            ),
+        });
+    let ws_allowed_origins = env::var("XORS_API_WS_ALLOWED_ORIGINS").ok().map(|origins| {
+        origins
+            .split(',')
+            .map(|origin| origin.trim().trim_end_matches('/').to_owned())
+            .filter(|origin| !origin.is_empty())
+            .collect::<Vec<_>>()
+    });
+    let duplicate_connection_policy = env::var("XORS_API_WS_DUPLICATE_CONNECTION_POLICY")
+        .map(|value| {
+            value
+                .parse::<api::xo::DuplicateConnectionPolicy>()
+                .unwrap_or_else(|err| {
+                    panic!("`XORS_API_WS_DUPLICATE_CONNECTION_POLICY` environment variable: {err}")
+                })
+        })
+        .unwrap_or_default();
+    let restart_grace_period = env::var("XORS_API_RESTART_GRACE_PERIOD")
+        .map(|value| {
+            value
+                .parse::<u64>()
+                .expect("`XORS_API_RESTART_GRACE_PERIOD` environment variable must be a number")
+        })
+        .unwrap_or(60);
+    let trust_proxy = env::var("XORS_API_TRUST_PROXY")
+        .map(|value| {
+            value
+                .parse::<bool>()
+                .expect("`XORS_API_TRUST_PROXY` environment variable must be a boolean")
+        })
+        .unwrap_or_default();
+    let risk_assessor: Arc<dyn api::risk::RiskAssessor> =
+        match env::var("XORS_API_CAPTCHA_MODE").as_deref() {
+            Ok("always") | Err(_) => Arc::new(api::risk::AlwaysSuspicious),
+            Ok("adaptive") => Arc::new(api::risk::ActivityRiskAssessor::new(
+                env::var("XORS_API_CAPTCHA_MAX_SIGNUPS")
+                    .map(|value| {
+                        value
+                            .parse::<usize>()
+                            .expect("`XORS_API_CAPTCHA_MAX_SIGNUPS` environment variable must be a number")
+                    })
+                    .unwrap_or(3),
+                Duration::from_secs(
+                    env::var("XORS_API_CAPTCHA_SIGNUPS_WINDOW")
+                        .map(|value| match value.parse::<u64>() {
+                            Ok(window) if window > 0 => window,
+                            _ => panic!("`XORS_API_CAPTCHA_SIGNUPS_WINDOW` environment variable must be a positive number"),
+                        })
+                        .unwrap_or(60 * 60),
+                ),
+                Duration::from_secs(
+                    env::var("XORS_API_CAPTCHA_MIN_SIGNUP_INTERVAL")
+                        .map(|value| {
+                            value
+                                .parse::<u64>()
+                                .expect("`XORS_API_CAPTCHA_MIN_SIGNUP_INTERVAL` environment variable must be a number")
+                        })
+                        .unwrap_or(10),
+                ),
+                env::var("XORS_API_CAPTCHA_BAD_IPS")
+                    .unwrap_or_default()
+                    .split(',')
+                    .filter(|ip| !ip.trim().is_empty())
+                    .map(|ip| {
+                        ip.trim()
+                            .parse()
+                            .expect("`XORS_API_CAPTCHA_BAD_IPS` environment variable must be a comma separated IPs")
+                    })
+                    .collect(),
+            ).with_trust_proxy(trust_proxy)),
+            Ok(_) => panic!(
+                "`XORS_API_CAPTCHA_MODE` environment variable must be `always` or `adaptive`"
+            ),
+        };
+
+    let captcha_failure_policy = env::var("XORS_API_CAPTCHA_FAILURE_POLICY")
+        .map(|value| {
+            value
+                .parse::<api::risk::CaptchaFailurePolicy>()
+                .unwrap_or_else(|err| {
+                    panic!("`XORS_API_CAPTCHA_FAILURE_POLICY` environment variable: {err}")
+                })
+        })
+        .unwrap_or_default();
+    let captcha_max_per_second = env::var("XORS_API_CAPTCHA_MAX_PER_SECOND")
+        .map(|value| {
+            value
+                .parse::<u32>()
+                .expect("`XORS_API_CAPTCHA_MAX_PER_SECOND` environment variable must be a number")
+        })
+        .unwrap_or(20);
+    let captcha_provider_key = |name: &str| {
+        env::var(name).unwrap_or_else(|_| {
+            panic!("`{name}` environment variable must be set for the hCaptcha and reCAPTCHA providers")
+        })
+    };
+    fn captcha_provider_failed(err: &str) -> ! {
+        log::error!("Failed to create the captcha provider: {err}");
+        eprintln!("Failed to create the captcha provider: {err}");
+        std::process::exit(1);
+    }
+    let captcha_provider: Arc<dyn CaptchaProvider> = match env::var("XORS_API_CAPTCHA_PROVIDER")
+        .map(|value| {
+            value
+                .parse::<schemas::CaptchaProviderKind>()
+                .unwrap_or_else(|err| {
+                    panic!("`XORS_API_CAPTCHA_PROVIDER` environment variable: {err}")
+                })
+        })
+        .unwrap_or_default()
+    {
+        schemas::CaptchaProviderKind::Image => Arc::new(ImageCaptchaProvider),
+        schemas::CaptchaProviderKind::Hcaptcha => Arc::new(
+            HCaptchaProvider::new(
+                captcha_provider_key("XORS_API_CAPTCHA_SITE_KEY"),
+                captcha_provider_key("XORS_API_CAPTCHA_SECRET"),
+            )
+            .unwrap_or_else(|err| captcha_provider_failed(&err)),
+        ),
+        schemas::CaptchaProviderKind::Recaptcha => {
+            let min_score = env::var("XORS_API_RECAPTCHA_MIN_SCORE")
+                .map(|value| match value.parse::<f64>() {
+                    Ok(score) if (0.0..=1.0).contains(&score) => score,
+                    _ => panic!(
+                        "`XORS_API_RECAPTCHA_MIN_SCORE` environment variable must be between `0.0` and `1.0`"
+                    ),
+                })
+                .unwrap_or(DEFAULT_RECAPTCHA_MIN_SCORE);
+            Arc::new(
+                RecaptchaProvider::new(
+                    captcha_provider_key("XORS_API_CAPTCHA_SITE_KEY"),
+                    captcha_provider_key("XORS_API_CAPTCHA_SECRET"),
+                )
+                .unwrap_or_else(|err| captcha_provider_failed(&err))
+                .with_min_score(min_score),
+            )
+        }
+    };
+    if captcha_failure_policy == api::risk::CaptchaFailurePolicy::Degrade {
+        log::warn!("The captcha degraded mode is allowed, the captcha will be skipped for unsuspicious requests while it's failing");
+    }
+
+    let captcha_preview = env::var("XORS_API_CAPTCHA_PREVIEW")
+        .map(|value| {
+            value
+                .parse::<bool>()
+                .expect("`XORS_API_CAPTCHA_PREVIEW` environment variable must be a boolean")
+        })
+        .unwrap_or_default();
+    if captcha_preview && !cfg!(debug_assertions) {
+        panic!("`XORS_API_CAPTCHA_PREVIEW` can't be enabled in the release builds, it's for the UI development only");
+    }
+
+    let metrics_token = env::var("XORS_API_METRICS_TOKEN").ok();
+    if metrics_token.as_ref().is_some_and(|token| token.len() < 32) {
+        panic!("`XORS_API_METRICS_TOKEN` environment variable must be at least 32 characters");
+    }
+
+    let fresh_auth_period = env::var("XORS_API_FRESH_AUTH_PERIOD").ok().map(|value| {
+        value
+            .parse::<u64>()
+            .expect("`XORS_API_FRESH_AUTH_PERIOD` environment variable must be a number")
+    });
+    let prune_interval =
+        env::var("XORS_API_PRUNE_INTERVAL")
+            .ok()
+            .map(|value| match value.parse::<u64>() {
+                Ok(interval) if interval > 0 => interval,
+                _ => panic!(
+                    "`XORS_API_PRUNE_INTERVAL` environment variable must be a positive number"
+                ),
+            });
+    let invite_ttl = env::var("XORS_API_INVITE_TTL")
+        .ok()
+        .map(|value| match value.parse::<u64>() {
+            Ok(ttl) if ttl > 0 => ttl,
+            _ => panic!("`XORS_API_INVITE_TTL` environment variable must be a positive number"),
+        });
+
+    let leaderboard_tiebreakers = env::var("XORS_API_LEADERBOARD_TIEBREAKERS")
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|tiebreaker| !tiebreaker.is_empty())
+                .map(str::parse::<api::user::Tiebreaker>)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap_or_else(|err| {
+                    panic!("`XORS_API_LEADERBOARD_TIEBREAKERS` environment variable: {err}")
+                })
+        })
+        .unwrap_or_else(|_| api::user::DEFAULT_LEADERBOARD_TIEBREAKERS.to_vec());
+
+    let default_season_policy = api::season::SeasonPolicy::default();
+    let season_policy = api::season::SeasonPolicy {
+        length: env::var("XORS_API_SEASON_LENGTH")
+            .map(|value| {
+                chrono::Duration::days(value.parse::<i64>().expect(
+                    "`XORS_API_SEASON_LENGTH` environment variable must be a number of days",
+                ))
+            })
+            .unwrap_or(default_season_policy.length),
+        reset_factor: env::var("XORS_API_SEASON_RESET_FACTOR")
+            .map(|value| {
+                value
+                    .parse::<f64>()
+                    .expect("`XORS_API_SEASON_RESET_FACTOR` environment variable must be a number")
+            })
+            .unwrap_or(default_season_policy.reset_factor),
+        tiebreakers: leaderboard_tiebreakers.clone(),
+    };
+    if let Err(err) = season_policy.validate() {
+        panic!("Invalid season policy: {err}");
+    }
+
+    let token_lifetime =
+        |name: &str, default: chrono::Duration| {
+            env::var(name)
+                .map(|value| {
+                    chrono::Duration::seconds(value.parse::<i64>().unwrap_or_else(|_| {
+                        panic!("`{name}` environment variable must be a number")
+                    }))
+                })
+                .unwrap_or(default)
+        };
+    let default_lifetimes = api::jwt::TokenLifetimes::default();
+    let token_lifetimes = api::jwt::TokenLifetimes {
+        jwt: token_lifetime("XORS_API_JWT_LIFETIME", default_lifetimes.jwt),
+        refresh: token_lifetime("XORS_API_REFRESH_TOKEN_LIFETIME", default_lifetimes.refresh),
+        short_refresh: token_lifetime(
+            "XORS_API_SHORT_REFRESH_TOKEN_LIFETIME",
+            default_lifetimes.short_refresh,
+        ),
+        refresh_active_after: token_lifetime(
+            "XORS_API_REFRESH_TOKEN_ACTIVE_AFTER",
+            default_lifetimes.refresh_active_after,
+        ),
+        refresh_grace_period: token_lifetime(
+            "XORS_API_REFRESH_TOKEN_GRACE_PERIOD",
+            default_lifetimes.refresh_grace_period,
+        ),
+    };
+    if let Err(err) = token_lifetimes.validate() {
+        panic!("Invalid tokens lifetimes: {err}");
+    }
+
+    let rate_limit = |name: &str, default: usize| {
+        env::var(name)
+            .map(|value| {
+                value
+                    .parse::<usize>()
+                    .unwrap_or_else(|_| panic!("`{name}` environment variable must be a number"))
+            })
+            .unwrap_or(default)
+    };
+    let auth_rate_limits = api::risk::AuthRateLimits {
+        sign: api::risk::IpRateLimiter::new(
+            rate_limit("XORS_API_SIGN_RATE_LIMIT", 10),
+            Duration::from_secs(rate_limit("XORS_API_SIGN_RATE_LIMIT_WINDOW", 60) as u64),
+        ),
+        captcha: api::risk::IpRateLimiter::new(
+            rate_limit("XORS_API_CAPTCHA_RATE_LIMIT", 20),
+            Duration::from_secs(rate_limit("XORS_API_CAPTCHA_RATE_LIMIT_WINDOW", 60) as u64),
+        ),
+        confirm: api::risk::UserRateLimiter::new(
+            rate_limit("XORS_API_CONFIRM_RATE_LIMIT", 5),
+            Duration::from_secs(rate_limit("XORS_API_CONFIRM_RATE_LIMIT_WINDOW", 900) as u64),
+        ),
+    }
+    .with_trust_proxy(trust_proxy);
+
+    let password_flag = |name: &str, default: bool| {
+        env::var(name)
+            .map(|value| {
+                value
+                    .parse::<bool>()
+                    .unwrap_or_else(|_| panic!("`{name}` environment variable must be a boolean"))
+            })
+            .unwrap_or(default)
+    };
+    let password_length = |name: &str, default: usize| {
+        env::var(name)
+            .map(|value| {
+                value
+                    .parse::<usize>()
+                    .unwrap_or_else(|_| panic!("`{name}` environment variable must be a number"))
+            })
+            .unwrap_or(default)
+    };
+    let default_policy = utils::PasswordPolicy::default();
+    let password_policy = utils::PasswordPolicy {
+        min_length: password_length("XORS_API_PASSWORD_MIN_LENGTH", default_policy.min_length),
+        max_length: password_length("XORS_API_PASSWORD_MAX_LENGTH", default_policy.max_length),
+        require_uppercase: password_flag(
+            "XORS_API_PASSWORD_REQUIRE_UPPERCASE",
+            default_policy.require_uppercase,
+        ),
+        require_lowercase: password_flag(
+            "XORS_API_PASSWORD_REQUIRE_LOWERCASE",
+            default_policy.require_lowercase,
+        ),
+        require_digit: password_flag(
+            "XORS_API_PASSWORD_REQUIRE_DIGIT",
+            default_policy.require_digit,
+        ),
+        require_symbol: password_flag(
+            "XORS_API_PASSWORD_REQUIRE_SYMBOL",
+            default_policy.require_symbol,
+        ),
+        block_common: password_flag(
+            "XORS_API_PASSWORD_BLOCK_COMMON",
+            default_policy.block_common,
+        ),
+        blocklist: env::var("XORS_API_PASSWORD_BLOCKLIST_FILE")
+            .map(|path| {
+                std::fs::read_to_string(&path)
+                    .unwrap_or_else(|err| {
+                        panic!("Failed to read the password blocklist `{path}`: {err}")
+                    })
+                    .lines()
+                    .map(|password| password.trim().to_lowercase())
+                    .filter(|password| !password.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        min_score: env::var("XORS_API_PASSWORD_MIN_SCORE")
+            .map(|value| {
+                value
+                    .parse::<f64>()
+                    .expect("`XORS_API_PASSWORD_MIN_SCORE` environment variable must be a number")
+            })
+            .unwrap_or(default_policy.min_score),
+    };
+    if let Err(err) = password_policy.validate() {
+        panic!("Invalid password policy: {err}");
+    }
+
+    let password_cost = |name: &str, default: u32| {
+        env::var(name)
+            .map(|value| {
+                value
+                    .parse::<u32>()
+                    .unwrap_or_else(|_| panic!("`{name}` environment variable must be a number"))
+            })
+            .unwrap_or(default)
+    };
+    let default_hasher = utils::PasswordHasher::default();
+    let password_hasher = utils::PasswordHasher {
+        memory_cost: password_cost("XORS_API_PASSWORD_MEMORY_COST", default_hasher.memory_cost),
+        time_cost: password_cost("XORS_API_PASSWORD_TIME_COST", default_hasher.time_cost),
+        parallelism: password_cost("XORS_API_PASSWORD_PARALLELISM", default_hasher.parallelism),
+    };
+    if let Err(err) = password_hasher.validate() {
+        panic!("Invalid password hasher costs: {err}");
+    }
+    password_hasher
+        .install()
+        .expect("The password hasher is installed once");
+
+    let webhook_policy = api::webhook::WebhookPolicy::default();
+    match api::webhook::WebhookSender::new(webhook_policy) {
+        Ok(webhook_sender) => {
+            if webhook_sender.install().is_err() {
+                panic!("The webhook sender is installed once");
+            }
+        }
+        Err(err) => {
+            log::error!("Failed to create the webhooks sender: {err}");
+            eprintln!("Failed to create the webhooks sender: {err}");
+            std::process::exit(1);
+        }
+    }
+
+    let mut skipped_checks = env::var("XORS_API_SKIP_STARTUP_CHECKS")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|check| !check.trim().is_empty())
+        .map(|check| {
+            check
+                .trim()
+                .parse::<startup::StartupCheck>()
+                .unwrap_or_else(|err| {
+                    panic!("`XORS_API_SKIP_STARTUP_CHECKS` environment variable: {err}")
+                })
+        })
+        .collect::<Vec<_>>();
+    if jwt_algorithm != api::jwt::JwtAlgorithm::HS256 {
+        // The tokens are not signed with the secret key
+        skipped_checks.push(startup::StartupCheck::SecretKey);
+    }
 
     log::debug!("Connected to the database");
     Migrator::up(&connection, None).await?;
 
+    if let Err(err) = startup::self_check(
+        &connection,
+        secret_key.as_deref().unwrap_or_default(),
+        &skipped_checks,
+    )
+    .await
+    {
+        log::error!("Startup check failed: {err}");
+        eprintln!("Startup check failed: {err}");
+        std::process::exit(1);
+    }
+    api::xo::resume_games(
+        connection.clone(),
+        Duration::from_secs(restart_grace_period),
+        game_clock,
+    )
+    .await?;
+
     log::info!("Starting API on http://{host}:{port}");
     log::info!("XO websocket is available at ws://{host}:{port}/xo");
     log::info!("The OpenAPI spec is available at http://{host}:{port}/api-doc/openapi.json");
@@ -56,22 +648,100 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
     log::info!("Press Ctrl+C to stop the API");
 
+    let shutdown_connection = connection.clone();
+
     let server_connection = connection.clone();
     let acceptor = salvo::conn::TcpListener::new(format!("{host}:{port}"))
         .bind()
         .await;
+    let server = Server::new(acceptor);
+    let server_handle = server.handle();
     let server_handler = tokio::spawn(async move {
-        Server::new(acceptor)
-            .serve(api::service(server_connection, max_online_games, move_period, secret_key).0)
+        server
+            .serve(
+                api::service(
+                    server_connection,
+                    jwt_keys,
+                    api::ServiceConfig {
+                        max_online_games,
+                        game_clock,
+                        unique_pair_games,
+                        daily_games_limit,
+                        random_opening_moves,
+                        matchmaking,
+                        chat_limiter,
+                        token_expiry_policy,
+                        token_expiring_before,
+                        ws_allowed_origins,
+                        duplicate_connection_policy,
+                        captcha_guard: api::risk::CaptchaGuard::new(
+                            risk_assessor,
+                            captcha_failure_policy,
+                        )
+                        .with_creation_rate(captcha_max_per_second, captcha_max_per_second * 2)
+                        .with_provider(captcha_provider)
+                        .with_trust_proxy(trust_proxy),
+                        captcha_preview,
+                        metrics_token,
+                        fresh_auth_period,
+                        token_lifetimes,
+                        auth_rate_limits,
+                        password_policy,
+                        prune_interval,
+                        invite_ttl,
+                        persist_chat,
+                        webhook_policy,
+                        min_rated_games,
+                        leaderboard_tiebreakers,
+                    },
+                )
+                .0,
+            )
             .await
     });
+    let seasons_connection = connection.clone();
     let auto_play_handler = tokio::spawn(async move {
-        api::xo::auto_play_handler(connection, move_period).await;
+        api::xo::auto_play_handler(connection, game_clock).await;
+    });
+    let seasons_handler = tokio::spawn(async move {
+        api::season::seasons_handler(seasons_connection, season_policy).await;
+    });
+
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        log::info!("API is shutting down");
+        api::xo::shutdown(
+            &shutdown_connection,
+            game_clock,
+            Duration::from_secs(restart_grace_period),
+        )
+        .await;
+        // The in-flight requests have 10 seconds to finish
+        server_handle.stop_graceful(Duration::from_secs(10));
     });
 
     server_handler.await?;
-    auto_play_handler.await?;
-    log::info!("API is shutting down");
+    auto_play_handler.abort();
+    seasons_handler.abort();
+    log::info!("API is stopped");
 
     Ok(())
 }
+
+/// Waits for the `SIGTERM` signal or Ctrl+C.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen to the `SIGTERM` signal")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+}
@@ -24,7 +24,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         std::process::exit(1);
     };
 
-    let openapi = xors_api::api::service(get_connection().await?, 100, 10, get_secret_key()).1;
+    let openapi =
+        xors_api::api::service(get_connection().await?, get_jwt_keys(), Default::default()).1;
     std::fs::write(openapi_path, openapi.to_pretty_json()?)?;
 
     Ok(())
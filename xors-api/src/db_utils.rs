@@ -16,24 +16,97 @@
 
 use std::fs;
 
-use crate::api::jwt::JwtClaims;
+use crate::api::{
+    jwt::{JwtClaims, JwtKeys, TokenLifetimes},
+    season::SeasonPolicy,
+    user::Tiebreaker,
+};
 use crate::errors::{ApiError, ApiResult};
 use crate::{schemas::*, utils};
 use base64::Engine;
 use chrono::Duration;
 use entity::prelude::*;
-use jsonwebtoken::Header;
+use sea_orm::sea_query::{Alias, Query};
+use sea_orm::{
+    prelude::Expr, Condition, ConnectionTrait, QueryTrait, TransactionTrait, UpdateMany,
+};
 use uuid::Uuid;
 
+/// The number of users in each search page.
+pub const SEARCH_PAGE_SIZE: u64 = 10;
+
+/// The number of games in each recent games page.
+pub const RECENT_GAMES_PAGE_SIZE: u64 = 20;
+
+/// The number of users in each leaderboard page.
+pub const LEADERBOARD_PAGE_SIZE: u64 = 20;
+
+/// The number of users in each recent signups page.
+pub const SIGNUPS_PAGE_SIZE: u64 = 20;
+
+/// The number of games in each user games page.
+pub const USER_GAMES_PAGE_SIZE: u64 = 20;
+
+/// The number of the last games in the user rating trend.
+pub const RATING_TREND_GAMES: u64 = 10;
+
+/// The number of events in each audit log page.
+pub const AUDIT_PAGE_SIZE: u64 = 50;
+
+/// The number of notifications in each notifications page.
+pub const NOTIFICATIONS_PAGE_SIZE: u64 = 20;
+
+/// The webhook deliveries count of each page.
+pub const WEBHOOK_DELIVERIES_PAGE_SIZE: u64 = 20;
+
+/// The hours that the generated data exports are kept.
+pub const DATA_EXPORT_TTL_HOURS: i64 = 24;
+
+/// The minutes that a pending data export blocks the new ones, after it's considered lost (e.g. the server
+/// restarted while generating it).
+const DATA_EXPORT_TIMEOUT_MINUTES: i64 = 30;
+
+/// The maximum days of the user activity range.
+pub const MAX_ACTIVITY_DAYS: i64 = 366;
+
+/// The days without games after them the guest users are deleted, see [`prune_inactive_guests`].
+pub const GUEST_INACTIVITY_DAYS: i64 = 7;
+
 /// Creates a new user in the database.
 pub async fn create_user(
     conn: &sea_orm::DatabaseConnection,
     new_user: NewUserSchema,
 ) -> ApiResult<UserSchema> {
+    create_user_with_role(conn, new_user, UserRole::User).await
+}
+
+/// Creates a new guest user, with a random username and an unknown password so it can't signin.
+/// The guest plays the unranked games only until it's upgraded, see [`upgrade_guest`].
+pub async fn create_guest(conn: &sea_orm::DatabaseConnection) -> ApiResult<UserSchema> {
+    create_user_with_role(
+        conn,
+        NewUserSchema {
+            first_name: "Guest".to_owned(),
+            last_name: None,
+            username: format!("guest_{}", &Uuid::new_v4().simple().to_string()[..12]),
+            password: Uuid::new_v4().to_string(),
+        },
+        UserRole::Guest,
+    )
+    .await
+}
+
+async fn create_user_with_role(
+    conn: &sea_orm::DatabaseConnection,
+    mut new_user: NewUserSchema,
+    role: UserRole,
+) -> ApiResult<UserSchema> {
+    new_user.username = new_user.username.trim().to_owned();
+    let normalized_username = utils::normalize_username(&new_user.username);
     log::info!("Creating user: {}", new_user.username);
 
     if UserEntity::find()
-        .filter(UserColumn::Username.eq(new_user.username.clone()))
+        .filter(UserColumn::NormalizedUsername.eq(normalized_username.clone()))
         .count(conn)
         .await?
         != 0
@@ -42,7 +115,7 @@ pub async fn create_user(
         Err(ApiError::UsernameAlreadyExists(new_user.username))
     } else {
         log::info!("Hashing password for user: {}", new_user.username);
-        let password_hash = bcrypt::hash(&new_user.password, 4)?;
+        let password_hash = utils::PasswordHasher::current().hash(&new_user.password)?;
 
         log::info!("Getting a new uuid for user: {}", new_user.username);
         let uuid = loop {
@@ -66,7 +139,9 @@ pub async fn create_user(
                 last_name: Set(new_user.last_name),
                 profile_image_path: Set("/profiles/default".to_owned()),
                 username: Set(new_user.username),
+                normalized_username: Set(normalized_username),
                 password_hash: Set(password_hash),
+                role: Set(role.to_string()),
                 created_at: Set(chrono::Utc::now().naive_utc()),
                 ..Default::default()
             }
@@ -77,48 +152,615 @@ pub async fn create_user(
     }
 }
 
-/// Signin a user and returns a JWT token with a refresh token.
-pub async fn signin_user(user: UserSchema, secret_key: &str) -> ApiResult<UserSigninSchema> {
+/// Upgrades the guest user to a regular user with the new credentials, the guest's games are kept.
+pub async fn upgrade_guest(
+    conn: &sea_orm::DatabaseConnection,
+    guest: UserModel,
+    mut new_user: NewUserSchema,
+) -> ApiResult<UserSchema> {
+    if guest.role != UserRole::Guest.to_string() {
+        return Err(ApiError::NotGuest);
+    }
+    new_user.username = new_user.username.trim().to_owned();
+    let normalized_username = utils::normalize_username(&new_user.username);
+    log::info!(
+        "Upgrading guest {} to user: {}",
+        guest.uuid,
+        new_user.username
+    );
+
+    if UserEntity::find()
+        .filter(UserColumn::NormalizedUsername.eq(normalized_username.clone()))
+        .filter(UserColumn::Uuid.ne(guest.uuid))
+        .count(conn)
+        .await?
+        != 0
+    {
+        log::error!("Username already exists: {}", new_user.username);
+        return Err(ApiError::UsernameAlreadyExists(new_user.username));
+    }
+
+    let mut user = guest.into_active_model();
+    user.first_name = Set(new_user.first_name);
+    user.last_name = Set(new_user.last_name);
+    user.username = Set(new_user.username);
+    user.normalized_username = Set(normalized_username);
+    user.password_hash = Set(utils::PasswordHasher::current().hash(&new_user.password)?);
+    user.role = Set(UserRole::User.to_string());
+    UserSchema::from_active_model(conn, user.save(conn).await?).await
+}
+
+/// Signin a user and returns a JWT token with a refresh token, the refresh token is shorter without `remember_me`.
+pub async fn signin_user(
+    conn: &sea_orm::DatabaseConnection,
+    user: UserSchema,
+    jwt_keys: &JwtKeys,
+    lifetimes: &TokenLifetimes,
+    remember_me: bool,
+) -> ApiResult<UserSigninSchema> {
     log::info!("Logging in user: {}", user.username);
+    issue_tokens(
+        conn,
+        user,
+        jwt_keys,
+        lifetimes,
+        lifetimes.refresh_lifetime(remember_me),
+        chrono::Utc::now().timestamp(),
+    )
+    .await
+}
+
+/// Returns a JWT token with a refresh token that lives `refresh_lifetime`, `auth_time` is the last password
+/// authentication date.
+pub(crate) async fn issue_tokens(
+    conn: &sea_orm::DatabaseConnection,
+    user: UserSchema,
+    jwt_keys: &JwtKeys,
+    lifetimes: &TokenLifetimes,
+    refresh_lifetime: chrono::Duration,
+    auth_time: i64,
+) -> ApiResult<UserSigninSchema> {
+    let token_version = UserEntity::find()
+        .filter(UserColumn::Uuid.eq(user.uuid))
+        .one(conn)
+        .await?
+        .ok_or(ApiError::UserNotFound)?
+        .token_version;
+
+    Ok(TokensIdentity {
+        jwt_jti: Uuid::new_v4(),
+        refresh_jti: Uuid::new_v4(),
+        issued_at: chrono::Utc::now().timestamp(),
+        token_version,
+        auth_time,
+        refresh_lifetime,
+    }
+    .encode(user, jwt_keys, lifetimes))
+}
+
+/// The claims of a JWT token and its refresh token that are not derived from the user or the lifetimes, the same
+/// identity is always encoded to the same tokens.
+struct TokensIdentity {
+    jwt_jti: Uuid,
+    refresh_jti: Uuid,
+    issued_at: i64,
+    token_version: i32,
+    auth_time: i64,
+    refresh_lifetime: chrono::Duration,
+}
+
+impl TokensIdentity {
+    /// Returns the successor tokens identity of the refresh token, issued now.
+    fn successor_of(claims: &JwtClaims) -> Self {
+        Self {
+            jwt_jti: Uuid::new_v4(),
+            refresh_jti: Uuid::new_v4(),
+            issued_at: chrono::Utc::now().timestamp(),
+            token_version: claims.ver,
+            // The refreshed session keeps its last password authentication date and refresh token lifetime
+            auth_time: claims.auth_time,
+            refresh_lifetime: chrono::Duration::seconds(claims.exp - claims.iat),
+        }
+    }
+
+    /// Encodes the JWT token and the refresh token of the user.
+    fn encode(
+        &self,
+        user: UserSchema,
+        jwt_keys: &JwtKeys,
+        lifetimes: &TokenLifetimes,
+    ) -> UserSigninSchema {
+        let issued_at = timestamp_to_datetime(self.issued_at);
+
+        let jwt = jwt_keys
+            .encode(&JwtClaims::new(
+                user.uuid,
+                self.jwt_jti,
+                user.role,
+                self.token_version,
+                self.issued_at,
+                self.auth_time,
+                None,
+                (issued_at + lifetimes.jwt).timestamp(),
+            ))
+            .expect("JWT encode failed");
+
+        let refresh_token = jwt_keys
+            .encode(&JwtClaims::new(
+                user.uuid,
+                self.refresh_jti,
+                user.role,
+                self.token_version,
+                self.issued_at,
+                self.auth_time,
+                Some((issued_at + lifetimes.refresh_active_after).timestamp()),
+                (issued_at + self.refresh_lifetime).timestamp(),
+            ))
+            .expect("JWT encode failed");
+
+        UserSigninSchema {
+            user,
+            jwt,
+            refresh_token,
+        }
+    }
+}
+
+/// Returns a JWT token without a refresh token, its last password authentication date is now.
+pub(crate) fn issue_access_token(
+    user: &UserModel,
+    jwt_keys: &JwtKeys,
+    lifetimes: &TokenLifetimes,
+) -> ApiResult<String> {
     let now = chrono::Utc::now().naive_utc();
-    let jwt_exp = if matches!(std::env::var("XORS_API_TEST"), Ok(status) if status == "true") {
-        (now + chrono::Duration::seconds(2)).timestamp()
-    } else {
-        (now + chrono::Duration::hours(1)).timestamp()
-    };
+    Ok(jwt_keys
+        .encode(&JwtClaims::new(
+            user.uuid,
+            Uuid::new_v4(),
+            user.role.parse().unwrap_or_default(),
+            user.token_version,
+            now.timestamp(),
+            now.timestamp(),
+            None,
+            (now + lifetimes.jwt).timestamp(),
+        ))
+        .expect("JWT encode failed"))
+}
 
-    let refresh_exp = if matches!(std::env::var("XORS_API_TEST"), Ok(status) if status == "true") {
-        (now + chrono::Duration::seconds(5)).timestamp()
-    } else {
-        (now + chrono::Duration::hours(3)).timestamp()
-    };
+/// Revokes the token, it will be rejected until it expires.
+///
+/// Revoking an already revoked token does nothing.
+pub async fn revoke_token(conn: &sea_orm::DatabaseConnection, claims: &JwtClaims) -> ApiResult<()> {
+    log::info!("Revoking the token {} of user {}", claims.jti, claims.uuid);
+
+    if !is_token_revoked(conn, claims.jti).await? {
+        revoked_token_model(claims, false).save(conn).await?;
+    }
+    Ok(())
+}
+
+/// Consumes the refresh token of the user, so it can't be used again, and returns its successor tokens.
+///
+/// The consumed refresh token can be presented again once in the `refresh_grace_period`, it gets the same
+/// successor tokens. Otherwise it's stolen or replayed, so all the user tokens are revoked. Only the successor
+/// tokens identity is stored, they are re-issued from it.
+pub async fn consume_refresh_token(
+    conn: &sea_orm::DatabaseConnection,
+    claims: &JwtClaims,
+    user: UserSchema,
+    jwt_keys: &JwtKeys,
+    lifetimes: &TokenLifetimes,
+) -> ApiResult<UserSigninSchema> {
+    log::info!(
+        "Consuming the refresh token {} of user {}",
+        claims.jti,
+        claims.uuid
+    );
+
+    let successor = TokensIdentity::successor_of(claims);
+    let mut consumed_token = revoked_token_model(claims, true);
+    if lifetimes.refresh_grace_period > chrono::Duration::zero() {
+        consumed_token.successor_jwt_jti = Set(Some(successor.jwt_jti));
+        consumed_token.successor_refresh_jti = Set(Some(successor.refresh_jti));
+        consumed_token.successor_issued_at = Set(Some(timestamp_to_datetime(successor.issued_at)));
+        consumed_token.reusable_until = Set(Some(
+            chrono::Utc::now().naive_utc() + lifetimes.refresh_grace_period,
+        ));
+    }
+    match consumed_token.insert(conn).await {
+        Ok(_) => Ok(successor.encode(user, jwt_keys, lifetimes)),
+        Err(err)
+            if matches!(
+                err.sql_err(),
+                Some(sea_orm::SqlErr::UniqueConstraintViolation(_))
+            ) =>
+        {
+            // The token is consumed by a concurrent refresh
+            Ok(reuse_consumed_refresh_token(conn, claims)
+                .await?
+                .encode(user, jwt_keys, lifetimes))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Returns the successor tokens identity of the consumed refresh token and ends its grace period, so it's reused
+/// once. If it's not in its grace period, all the user tokens are revoked.
+async fn reuse_consumed_refresh_token(
+    conn: &sea_orm::DatabaseConnection,
+    claims: &JwtClaims,
+) -> ApiResult<TokensIdentity> {
+    let consumed_token = RevokedTokenEntity::find()
+        .filter(RevokedTokenColumn::Jti.eq(claims.jti))
+        .filter(RevokedTokenColumn::Consumed.eq(true))
+        .one(conn)
+        .await?;
+    if let Some(RevokedTokenModel {
+        successor_jwt_jti: Some(jwt_jti),
+        successor_refresh_jti: Some(refresh_jti),
+        successor_issued_at: Some(issued_at),
+        ..
+    }) = consumed_token
+    {
+        // Only one of the concurrent reuses ends the grace period
+        let reused = clear_successor_columns(RevokedTokenEntity::update_many())
+            .filter(RevokedTokenColumn::Jti.eq(claims.jti))
+            .filter(RevokedTokenColumn::ReusableUntil.gte(chrono::Utc::now().naive_utc()))
+            .exec(conn)
+            .await?
+            .rows_affected
+            == 1;
+        if reused {
+            log::info!(
+                "The consumed refresh token {} of user {} is reused in its grace period",
+                claims.jti,
+                claims.uuid
+            );
+            return Ok(TokensIdentity {
+                jwt_jti,
+                refresh_jti,
+                issued_at: issued_at.timestamp(),
+                ..TokensIdentity::successor_of(claims)
+            });
+        }
+    }
+    reject_reused_refresh_token(conn, claims).await
+}
+
+/// Sets the successor columns of the consumed refresh tokens to null, it ends their grace period.
+fn clear_successor_columns(
+    update: UpdateMany<RevokedTokenEntity>,
+) -> UpdateMany<RevokedTokenEntity> {
+    update
+        .col_expr(
+            RevokedTokenColumn::SuccessorJwtJti,
+            Expr::value(Option::<Uuid>::None),
+        )
+        .col_expr(
+            RevokedTokenColumn::SuccessorRefreshJti,
+            Expr::value(Option::<Uuid>::None),
+        )
+        .col_expr(
+            RevokedTokenColumn::SuccessorIssuedAt,
+            Expr::value(Option::<chrono::NaiveDateTime>::None),
+        )
+        .col_expr(
+            RevokedTokenColumn::ReusableUntil,
+            Expr::value(Option::<chrono::NaiveDateTime>::None),
+        )
+}
+
+/// Revokes all the user tokens, the consumed refresh token is stolen or replayed.
+async fn reject_reused_refresh_token<T>(
+    conn: &sea_orm::DatabaseConnection,
+    claims: &JwtClaims,
+) -> ApiResult<T> {
+    log::warn!(
+        "The consumed refresh token {} of user {} is reused",
+        claims.jti,
+        claims.uuid
+    );
+    revoke_user_tokens(conn, claims.uuid).await?;
+    Err(ApiError::ReusedRefreshToken)
+}
+
+/// Returns the revoked token model of the token.
+fn revoked_token_model(claims: &JwtClaims, consumed: bool) -> RevokedTokenActiveModel {
+    RevokedTokenActiveModel {
+        jti: Set(claims.jti),
+        expires_at: Set(timestamp_to_datetime(claims.exp)),
+        revoked_at: Set(chrono::Utc::now().naive_utc()),
+        consumed: Set(consumed),
+        ..Default::default()
+    }
+}
+
+/// Converts the jwt timestamp to a date time.
+fn timestamp_to_datetime(timestamp: i64) -> chrono::NaiveDateTime {
+    chrono::NaiveDateTime::from_timestamp_opt(timestamp, 0).unwrap_or(chrono::NaiveDateTime::MAX)
+}
+
+/// Revokes all the tokens of the user that issued until now, by increasing the user's tokens version.
+pub async fn revoke_user_tokens(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<()> {
+    log::warn!("Revoking all the tokens of user {user_uuid}");
+
+    UserEntity::update_many()
+        .col_expr(
+            UserColumn::TokenVersion,
+            Expr::col(UserColumn::TokenVersion).add(1),
+        )
+        .filter(UserColumn::Uuid.eq(user_uuid))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Checks that the token is not revoked and its user is not banned. A consumed refresh token presented again
+/// revokes all the user tokens, unless it's in its grace period, see [`consume_refresh_token`].
+pub async fn check_token_revocation(
+    conn: &sea_orm::DatabaseConnection,
+    claims: &JwtClaims,
+) -> ApiResult<()> {
+    if let Some(revoked_token) = RevokedTokenEntity::find()
+        .filter(RevokedTokenColumn::Jti.eq(claims.jti))
+        .one(conn)
+        .await?
+    {
+        if !revoked_token.consumed {
+            return Err(ApiError::RevokedToken);
+        }
+        // The refresh endpoint returns the successor tokens of the consumed refresh token in its grace period
+        if revoked_token
+            .reusable_until
+            .map_or(true, |until| until < chrono::Utc::now().naive_utc())
+        {
+            return reject_reused_refresh_token(conn, claims).await;
+        }
+    }
+
+    match UserEntity::find()
+        .filter(UserColumn::Uuid.eq(claims.uuid))
+        .one(conn)
+        .await?
+    {
+        // The tokens of the deleted users are revoked with them
+        None => Err(ApiError::RevokedToken),
+        Some(user) if claims.ver < user.token_version => Err(ApiError::RevokedToken),
+        Some(user) if user.banned_at.is_some() => Err(ApiError::BannedUser),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Returns whether if the token is revoked or not.
+pub async fn is_token_revoked(conn: &sea_orm::DatabaseConnection, jti: Uuid) -> ApiResult<bool> {
+    Ok(RevokedTokenEntity::find()
+        .filter(RevokedTokenColumn::Jti.eq(jti))
+        .count(conn)
+        .await?
+        > 0)
+}
 
-    let refresh_active_after = if matches!(std::env::var("XORS_API_TEST"), Ok(status) if status == "true")
+/// Deletes the revoked tokens that are expired, they are rejected anyway. Returns the number of deleted tokens.
+pub async fn prune_revoked_tokens(conn: &impl ConnectionTrait) -> ApiResult<u64> {
+    Ok(RevokedTokenEntity::delete_many()
+        .filter(RevokedTokenColumn::ExpiresAt.lte(chrono::Utc::now().naive_utc()))
+        .exec(conn)
+        .await?
+        .rows_affected)
+}
+
+/// Ends the grace periods of the consumed refresh tokens that passed them. Returns the number of the updated tokens.
+pub async fn clear_expired_successors(conn: &impl ConnectionTrait) -> ApiResult<u64> {
+    Ok(clear_successor_columns(RevokedTokenEntity::update_many())
+        .filter(RevokedTokenColumn::ReusableUntil.lt(chrono::Utc::now().naive_utc()))
+        .exec(conn)
+        .await?
+        .rows_affected)
+}
+
+/// Deletes the expired rows in a single transaction, it's run periodically by the server.
+/// Returns the number of deleted rows.
+pub async fn prune_expired(conn: &sea_orm::DatabaseConnection) -> ApiResult<u64> {
+    let txn = conn.begin().await?;
+    clear_expired_successors(&txn).await?;
+    let pruned = prune_revoked_tokens(&txn).await?
+        + prune_expired_invites(&txn).await?
+        + prune_inactive_guests(&txn).await?
+        + prune_expired_data_exports(&txn).await?;
+    txn.commit().await?;
+    Ok(pruned)
+}
+
+/// Deletes the guest users that didn't play for [`GUEST_INACTIVITY_DAYS`] days, with their data.
+/// The guests that are in an in-progress game are kept. Returns the number of the deleted guests.
+pub async fn prune_inactive_guests(conn: &impl ConnectionTrait) -> ApiResult<u64> {
+    let inactive_since = chrono::Utc::now().naive_utc() - Duration::days(GUEST_INACTIVITY_DAYS);
+    let guests = UserEntity::find()
+        .filter(UserColumn::Role.eq(UserRole::Guest.to_string()))
+        .filter(UserColumn::CreatedAt.lt(inactive_since))
+        .all(conn)
+        .await?;
+
+    let mut pruned = 0;
+    for guest in guests {
+        let is_active = GameEntity::find()
+            .filter(
+                Condition::any()
+                    .add(GameColumn::XPlayer.eq(guest.uuid))
+                    .add(GameColumn::OPlayer.eq(guest.uuid)),
+            )
+            .filter(
+                Condition::any()
+                    .add(GameColumn::CreatedAt.gte(inactive_since))
+                    .add(GameColumn::EndedAt.is_null()),
+            )
+            .count(conn)
+            .await?
+            != 0;
+        if is_active {
+            continue;
+        }
+
+        log::info!("Deleting the inactive guest {}", guest.uuid);
+        delete_user_blocks(conn, guest.uuid).await?;
+        delete_user_friendships(conn, guest.uuid).await?;
+        delete_user_notifications(conn, guest.uuid).await?;
+        delete_user_achievements(conn, guest.uuid).await?;
+        delete_user_rating_changes(conn, guest.uuid).await?;
+        delete_user_invites(conn, guest.uuid).await?;
+        delete_user_registrations(conn, guest.uuid).await?;
+        delete_user_chat_messages(conn, guest.uuid).await?;
+        delete_user_recovery_codes(conn, guest.uuid).await?;
+        delete_user_data_exports(conn, guest.uuid).await?;
+        pruned += UserEntity::delete_many()
+            .filter(UserColumn::Uuid.eq(guest.uuid))
+            .exec(conn)
+            .await?
+            .rows_affected;
+    }
+    Ok(pruned)
+}
+
+/// Deletes the user and the user's data in a single transaction, the user's games are kept.
+/// The user's tokens are rejected after it, see [`check_token_revocation`].
+pub async fn delete_user(conn: &sea_orm::DatabaseConnection, user_uuid: Uuid) -> ApiResult<()> {
+    log::warn!("Deleting user {user_uuid}");
+
+    let txn = conn.begin().await?;
+    delete_user_blocks(&txn, user_uuid).await?;
+    delete_user_friendships(&txn, user_uuid).await?;
+    delete_user_notifications(&txn, user_uuid).await?;
+    delete_user_achievements(&txn, user_uuid).await?;
+    delete_user_rating_changes(&txn, user_uuid).await?;
+    delete_user_season_standings(&txn, user_uuid).await?;
+    delete_user_invites(&txn, user_uuid).await?;
+    delete_user_registrations(&txn, user_uuid).await?;
+    delete_user_chat_messages(&txn, user_uuid).await?;
+    delete_user_recovery_codes(&txn, user_uuid).await?;
+    delete_user_data_exports(&txn, user_uuid).await?;
+    if UserEntity::delete_many()
+        .filter(UserColumn::Uuid.eq(user_uuid))
+        .exec(&txn)
+        .await?
+        .rows_affected
+        == 0
+    {
+        // The transaction is rolled back when it's dropped
+        return Err(ApiError::UserNotFound);
+    }
+    txn.commit().await?;
+    Ok(())
+}
+
+/// Changes the username of the user, the new username must not be registered by another user. The case of
+/// the username can be changed.
+pub async fn change_username(
+    conn: &sea_orm::DatabaseConnection,
+    user: UserModel,
+    username: &str,
+) -> ApiResult<UserActiveModel> {
+    let username = username.trim();
+    let normalized_username = utils::normalize_username(username);
+    if user.username == username {
+        return Err(ApiError::UsernameNotChanged);
+    }
+    if UserEntity::find()
+        .filter(UserColumn::NormalizedUsername.eq(normalized_username.clone()))
+        .filter(UserColumn::Uuid.ne(user.uuid))
+        .count(conn)
+        .await?
+        != 0
     {
-        Some((now + chrono::Duration::seconds(3)).timestamp())
+        return Err(ApiError::UsernameAlreadyExists(username.to_owned()));
+    }
+    log::info!("Changing the username of {} to {username}", user.uuid);
+
+    let mut user = user.into_active_model();
+    user.username = Set(username.to_owned());
+    user.normalized_username = Set(normalized_username);
+    Ok(user.save(conn).await?)
+}
+
+/// Bans or unbans the user, banning an already banned user keeps its ban date.
+pub async fn set_user_ban(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+    banned: bool,
+) -> ApiResult<UserActiveModel> {
+    let mut user = get_user(conn, user_uuid).await?;
+    if banned == user.banned_at.as_ref().is_some() {
+        return Ok(user);
+    }
+    if banned {
+        log::warn!("Banning user {user_uuid}");
+        user.banned_at = Set(Some(chrono::Utc::now().naive_utc()));
     } else {
-        Some((now + chrono::Duration::minutes(58)).timestamp())
-    };
+        log::warn!("Unbanning user {user_uuid}");
+        user.banned_at = Set(None);
+    }
+    Ok(user.save(conn).await?)
+}
 
-    let jwt = jsonwebtoken::encode(
-        &Header::default(),
-        &JwtClaims::new(user.uuid, None, jwt_exp),
-        &jsonwebtoken::EncodingKey::from_secret(secret_key.as_bytes()),
-    )
-    .expect("JWT encode failed");
+/// Returns the recently signed up users (newest first).
+pub async fn get_recent_signups(
+    conn: &sea_orm::DatabaseConnection,
+    page: u64,
+) -> ApiResult<Vec<UserModel>> {
+    Ok(UserEntity::find()
+        .order_by(UserColumn::CreatedAt, Order::Desc)
+        .order_by(UserColumn::Id, Order::Desc)
+        .offset(page * SIGNUPS_PAGE_SIZE)
+        .limit(SIGNUPS_PAGE_SIZE)
+        .all(conn)
+        .await?)
+}
 
-    let refresh_token = jsonwebtoken::encode(
-        &Header::default(),
-        &JwtClaims::new(user.uuid, refresh_active_after, refresh_exp),
-        &jsonwebtoken::EncodingKey::from_secret(secret_key.as_bytes()),
-    )
-    .expect("JWT encode failed");
+/// Creates a new audit event in the database.
+pub async fn create_audit_event(
+    conn: &sea_orm::DatabaseConnection,
+    event: crate::api::audit::AuditEvent,
+) -> ApiResult<()> {
+    log::info!("Recording the `{}` audit event", event.action.to_string());
 
-    Ok(UserSigninSchema {
-        user,
-        jwt,
-        refresh_token,
-    })
+    AuditEventActiveModel {
+        action: Set(event.action.to_string()),
+        actor_uuid: Set(event.actor),
+        target: Set(event.target),
+        ip: Set(event.ip),
+        user_agent: Set(event.user_agent),
+        created_at: Set(chrono::Utc::now().naive_utc()),
+        ..Default::default()
+    }
+    .save(conn)
+    .await?;
+    Ok(())
+}
+
+/// Returns the audit events (newest first), filtered by the action and the actor if they are provided.
+pub async fn get_audit_events(
+    conn: &sea_orm::DatabaseConnection,
+    action: Option<AuditAction>,
+    actor: Option<Uuid>,
+    page: u64,
+) -> ApiResult<Vec<AuditEventModel>> {
+    let mut query = AuditEventEntity::find();
+    if let Some(action) = action {
+        query = query.filter(AuditEventColumn::Action.eq(action.to_string()));
+    }
+    if let Some(actor) = actor {
+        query = query.filter(AuditEventColumn::ActorUuid.eq(actor));
+    }
+
+    Ok(query
+        .order_by(AuditEventColumn::CreatedAt, Order::Desc)
+        .order_by(AuditEventColumn::Id, Order::Desc)
+        .offset(page * AUDIT_PAGE_SIZE)
+        .limit(AUDIT_PAGE_SIZE)
+        .all(conn)
+        .await?)
 }
 
 /// Get a user from the database by uuid.
@@ -143,15 +785,146 @@ pub async fn get_user_by_username(
 ) -> ApiResult<UserActiveModel> {
     log::info!("Getting user by username");
 
-    UserEntity::find()
-        .filter(UserColumn::Username.eq(username))
-        .one(conn)
-        .await?
+    let username = username.trim();
+    let mut users = UserEntity::find()
+        .filter(
+            Condition::any()
+                .add(UserColumn::Username.eq(username))
+                .add(UserColumn::NormalizedUsername.eq(utils::normalize_username(username))),
+        )
+        .all(conn)
+        .await?;
+    // The exact username first, the old usernames that only differ in case from another one are found by it only
+    users.sort_by_key(|user| user.username != username);
+    users
+        .into_iter()
+        .next()
         .map(|u| u.into_active_model())
         .ok_or(ApiError::UserNotFound)
 }
 
+/// Returns the normalized form of the given usernames that are already taken, see [`utils::normalize_username`].
+pub async fn get_taken_usernames(
+    conn: &sea_orm::DatabaseConnection,
+    usernames: Vec<String>,
+) -> ApiResult<Vec<String>> {
+    log::info!("Getting the taken usernames");
+
+    Ok(UserEntity::find()
+        .select_only()
+        .column(UserColumn::NormalizedUsername)
+        .filter(
+            UserColumn::NormalizedUsername.is_in(
+                usernames
+                    .iter()
+                    .map(|username| utils::normalize_username(username)),
+            ),
+        )
+        .into_tuple()
+        .all(conn)
+        .await?)
+}
+
+/// Returns the user's first moves heatmap, aggregated from the rounds of the ended classic games.
+pub async fn get_user_heatmap(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<HeatmapSchema> {
+    log::info!("Getting the heatmap of the user: {user_uuid}");
+
+    let games: Vec<(String, Uuid)> = GameEntity::find()
+        .select_only()
+        .column(GameColumn::RoundsResult)
+        .column(GameColumn::XPlayer)
+        .filter(
+            Condition::any()
+                .add(GameColumn::XPlayer.eq(user_uuid))
+                .add(GameColumn::OPlayer.eq(user_uuid)),
+        )
+        .filter(GameColumn::EndedAt.is_not_null())
+        .filter(GameColumn::BoardSize.eq(3))
+        .filter(GameColumn::WinLength.eq(3))
+        .into_tuple()
+        .all(conn)
+        .await?;
+
+    let mut heatmap = HeatmapSchema::default();
+    for (rounds_result, x_player) in games {
+        let rounds_result: RoundsResult = rounds_result.parse().expect("Is valid rounds result");
+        // X always plays the first move of the round
+        let first_move = usize::from(x_player != user_uuid);
+        for board in rounds_result.boards() {
+            if let Some(place) = board.played_cells().get(first_move) {
+                heatmap.cells[*place as usize] += 1;
+                heatmap.rounds += 1;
+            }
+        }
+    }
+    Ok(heatmap)
+}
+
+/// Returns the number of the games the user started in each day from `from` to `to` (inclusive).
+/// The days without games have a zero count.
+pub async fn get_user_activity(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> ApiResult<Vec<ActivityDaySchema>> {
+    log::info!("Getting the activity of the user {user_uuid} from {from} to {to}");
+
+    if from > to || (to - from).num_days() >= MAX_ACTIVITY_DAYS {
+        return Err(ApiError::InvalidDateRange);
+    }
+
+    let days: std::collections::HashMap<chrono::NaiveDate, i64> = GameEntity::find()
+        .select_only()
+        .column_as(Expr::cust("DATE(created_at)"), "day")
+        .column_as(GameColumn::Id.count(), "games")
+        .filter(
+            Condition::any()
+                .add(GameColumn::XPlayer.eq(user_uuid))
+                .add(GameColumn::OPlayer.eq(user_uuid)),
+        )
+        .filter(GameColumn::CreatedAt.gte(from.and_time(chrono::NaiveTime::MIN)))
+        .filter(GameColumn::CreatedAt.lt((to + Duration::days(1)).and_time(chrono::NaiveTime::MIN)))
+        .group_by(Expr::cust("DATE(created_at)"))
+        .into_tuple()
+        .all(conn)
+        .await?
+        .into_iter()
+        .collect();
+
+    Ok(from
+        .iter_days()
+        .take_while(|day| day <= &to)
+        .map(|day| ActivityDaySchema::new(day, days.get(&day).copied().unwrap_or_default() as u64))
+        .collect())
+}
+
+/// Update the user's streaks with the game result, `Some(true)` for a win, `Some(false)` for a lost and `None` for a draw.
+///
+/// The current streak is positive for a wins streak, negative for a losts streak and zero after a draw.
+fn update_streaks(user: &mut UserActiveModel, won: Option<bool>) {
+    let current_streak = match won {
+        Some(true) => user.current_streak.as_ref().max(&0) + 1,
+        Some(false) => user.current_streak.as_ref().min(&0) - 1,
+        None => 0,
+    };
+
+    user.current_streak = Set(current_streak);
+    if &current_streak > user.longest_win_streak.as_ref() {
+        user.longest_win_streak = Set(current_streak);
+    }
+    if &-current_streak > user.longest_loss_streak.as_ref() {
+        user.longest_loss_streak = Set(-current_streak);
+    }
+}
+
 /// End a game in the database. This will set the `ended_at` column to the current time and remove the `board` column.
+///
+/// The players results, achievements and ratings are updated in the same transaction, except for the games
+/// against the AI opponent.
 pub async fn end_game(
     conn: &sea_orm::DatabaseConnection,
     game_uuid: &Uuid,
@@ -163,93 +936,1820 @@ pub async fn end_game(
     let mut game = get_game::<false>(conn, game_uuid)
         .await
         .map(IntoActiveModel::into_active_model)?;
+    let now = chrono::Utc::now().naive_utc();
 
     game.winner = Set(winner);
     game.reason = Set(Some(reason.to_string()));
-    game.ended_at = Set(Some(chrono::Utc::now().naive_utc()));
+    game.ended_at = Set(Some(now));
     game.board = Set(String::new());
+    if game.bot_difficulty.as_ref().is_some() {
+        // The games against the AI opponent don't count in the player results and rating
+        game.save(conn).await?;
+        return Ok(());
+    }
 
     let mut x_player = get_user(conn, *game.x_player.as_ref()).await?;
     let mut o_player = get_user(conn, *game.o_player.as_ref()).await?;
-    if Some(*x_player.uuid.as_ref()) == winner {
+    let guest_role = UserRole::Guest.to_string();
+    if x_player.role.as_ref() == &guest_role || o_player.role.as_ref() == &guest_role {
+        // The games of the guests are unranked, they don't count in the results and rating of both players
+        game.save(conn).await?;
+        return Ok(());
+    }
+    let x_score = if Some(*x_player.uuid.as_ref()) == winner {
         x_player.wins = Set(x_player.wins.as_ref() + 1);
         o_player.losts = Set(o_player.losts.as_ref() + 1);
+        update_streaks(&mut x_player, Some(true));
+        update_streaks(&mut o_player, Some(false));
+        1.0
     } else if Some(*o_player.uuid.as_ref()) == winner {
         o_player.wins = Set(o_player.wins.as_ref() + 1);
         x_player.losts = Set(x_player.losts.as_ref() + 1);
+        update_streaks(&mut o_player, Some(true));
+        update_streaks(&mut x_player, Some(false));
+        0.0
     } else {
         x_player.draw = Set(x_player.draw.as_ref() + 1);
         o_player.draw = Set(o_player.draw.as_ref() + 1);
-    }
+        update_streaks(&mut x_player, None);
+        update_streaks(&mut o_player, None);
+        0.5
+    };
+    let (x_change, o_change) = utils::rating_changes(
+        *x_player.rating.as_ref(),
+        *o_player.rating.as_ref(),
+        x_score,
+    );
+
+    let duration = (now - *game.created_at.as_ref()).num_seconds();
 
-    game.save(conn).await?;
-    x_player.save(conn).await?;
-    o_player.save(conn).await?;
+    let txn = conn.begin().await?;
+    game.save(&txn).await?;
+    for (player, change) in [(&mut x_player, x_change), (&mut o_player, o_change)] {
+        player.rating = Set(player.rating.as_ref() + change);
+        player.games_duration = Set(player.games_duration.as_ref() + duration);
+        RatingChangeActiveModel {
+            user_uuid: Set(*player.uuid.as_ref()),
+            game_uuid: Set(*game_uuid),
+            rating: Set(*player.rating.as_ref()),
+            change: Set(change),
+            changed_at: Set(now),
+            ..Default::default()
+        }
+        .save(&txn)
+        .await?;
+    }
+    award_achievements(&txn, &x_player).await?;
+    award_achievements(&txn, &o_player).await?;
+    x_player.save(&txn).await?;
+    o_player.save(&txn).await?;
+    // After saving the players, the tournament tiebreak uses their new ratings
+    advance_tournament(&txn, *game_uuid, winner).await?;
+    txn.commit().await?;
 
     Ok(())
 }
 
-/// Create a new game in the database.
-pub async fn create_game(
-    conn: &sea_orm::DatabaseConnection,
-    x_player: Uuid,
-    o_player: Uuid,
-    move_period: i64,
-) -> ApiResult<GameActiveModel> {
-    log::info!("Creating game");
+/// Awards the user the earned achievements that it doesn't have yet.
+async fn award_achievements(conn: &impl ConnectionTrait, user: &UserActiveModel) -> ApiResult<()> {
+    let user_uuid = *user.uuid.as_ref();
+    let awarded: Vec<String> = AchievementEntity::find()
+        .select_only()
+        .column(AchievementColumn::Kind)
+        .filter(AchievementColumn::UserUuid.eq(user_uuid))
+        .into_tuple()
+        .all(conn)
+        .await?;
+
+    let now = chrono::Utc::now().naive_utc();
+    for achievement in Achievement::ALL {
+        let kind = achievement.to_string();
+        if achievement.is_earned(user) && !awarded.contains(&kind) {
+            log::info!("Awarding the achievement {kind} to user {user_uuid}");
+            AchievementActiveModel {
+                user_uuid: Set(user_uuid),
+                kind: Set(kind),
+                awarded_at: Set(now),
+                ..Default::default()
+            }
+            .save(conn)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the user's achievements, the first awarded first.
+pub async fn get_user_achievements(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<Vec<AchievementModel>> {
+    log::info!("Getting the achievements of user {user_uuid}");
+
+    Ok(AchievementEntity::find()
+        .filter(AchievementColumn::UserUuid.eq(user_uuid))
+        .order_by(AchievementColumn::AwardedAt, Order::Asc)
+        .order_by(AchievementColumn::Id, Order::Asc)
+        .all(conn)
+        .await?)
+}
+
+/// Deletes the user's achievements.
+pub async fn delete_user_achievements(
+    conn: &impl ConnectionTrait,
+    user_uuid: Uuid,
+) -> ApiResult<()> {
+    log::info!("Deleting the achievements of user {user_uuid}");
+
+    AchievementEntity::delete_many()
+        .filter(AchievementColumn::UserUuid.eq(user_uuid))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Returns the user's rating changes, the oldest first.
+pub async fn get_user_rating_changes(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<Vec<RatingChangeModel>> {
+    log::info!("Getting the rating changes of user {user_uuid}");
+
+    Ok(RatingChangeEntity::find()
+        .filter(RatingChangeColumn::UserUuid.eq(user_uuid))
+        .order_by(RatingChangeColumn::ChangedAt, Order::Asc)
+        .order_by(RatingChangeColumn::Id, Order::Asc)
+        .all(conn)
+        .await?)
+}
+
+/// Returns the ratings of the user's last games, the oldest first.
+pub async fn get_user_recent_ratings(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<Vec<i64>> {
+    log::info!("Getting the recent ratings of user {user_uuid}");
+
+    let mut ratings: Vec<i64> = RatingChangeEntity::find()
+        .select_only()
+        .column(RatingChangeColumn::Rating)
+        .filter(RatingChangeColumn::UserUuid.eq(user_uuid))
+        .order_by(RatingChangeColumn::ChangedAt, Order::Desc)
+        .order_by(RatingChangeColumn::Id, Order::Desc)
+        .limit(RATING_TREND_GAMES)
+        .into_tuple()
+        .all(conn)
+        .await?;
+    ratings.reverse();
+    Ok(ratings)
+}
+
+/// Deletes the user's rating changes.
+pub async fn delete_user_rating_changes(
+    conn: &impl ConnectionTrait,
+    user_uuid: Uuid,
+) -> ApiResult<()> {
+    log::info!("Deleting the rating changes of user {user_uuid}");
+
+    RatingChangeEntity::delete_many()
+        .filter(RatingChangeColumn::UserUuid.eq(user_uuid))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Saves a played move of the game, with the current time.
+pub async fn save_game_move(
+    conn: &sea_orm::DatabaseConnection,
+    game_uuid: Uuid,
+    sequence: u32,
+    round: i16,
+    place: u8,
+) -> ApiResult<()> {
+    log::info!("Saving the move {sequence} of game {game_uuid}");
+
+    GameMoveActiveModel {
+        game_uuid: Set(game_uuid),
+        sequence: Set(sequence as i32),
+        round: Set(round),
+        place: Set(place as i16),
+        played_at: Set(chrono::Utc::now().naive_utc()),
+        ..Default::default()
+    }
+    .save(conn)
+    .await?;
+    Ok(())
+}
+
+/// Returns the saved moves of the game, in order.
+pub async fn get_game_moves(
+    conn: &sea_orm::DatabaseConnection,
+    game_uuid: Uuid,
+) -> ApiResult<Vec<GameMoveModel>> {
+    log::info!("Getting the saved moves of game {game_uuid}");
+
+    Ok(GameMoveEntity::find()
+        .filter(GameMoveColumn::GameUuid.eq(game_uuid))
+        .order_by(GameMoveColumn::Sequence, Order::Asc)
+        .all(conn)
+        .await?)
+}
+
+/// Saves the chat message of the game, the message is saved as it's sent, encrypted for the other player.
+pub async fn save_chat_message(
+    conn: &sea_orm::DatabaseConnection,
+    game_uuid: Uuid,
+    sender: Uuid,
+    encrypted_message: String,
+    signature: String,
+) -> ApiResult<()> {
+    log::info!("Saving a chat message of game {game_uuid}");
+
+    ChatMessageActiveModel {
+        game_uuid: Set(game_uuid),
+        sender: Set(sender),
+        encrypted_message: Set(encrypted_message),
+        signature: Set(signature),
+        sent_at: Set(chrono::Utc::now().naive_utc()),
+        ..Default::default()
+    }
+    .save(conn)
+    .await?;
+    Ok(())
+}
+
+/// Returns the saved chat messages of the game, in order.
+pub async fn get_chat_messages(
+    conn: &sea_orm::DatabaseConnection,
+    game_uuid: Uuid,
+) -> ApiResult<Vec<ChatMessageModel>> {
+    log::info!("Getting the saved chat messages of game {game_uuid}");
+
+    Ok(ChatMessageEntity::find()
+        .filter(ChatMessageColumn::GameUuid.eq(game_uuid))
+        .order_by(ChatMessageColumn::Id, Order::Asc)
+        .all(conn)
+        .await?)
+}
+
+/// Deletes the user's sent chat messages.
+pub async fn delete_user_chat_messages(
+    conn: &impl ConnectionTrait,
+    user_uuid: Uuid,
+) -> ApiResult<()> {
+    ChatMessageEntity::delete_many()
+        .filter(ChatMessageColumn::Sender.eq(user_uuid))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Returns the leaderboard order of the users, their rating (highest first) then the tiebreakers, the users that
+/// are still tied are ordered by their join date.
+fn leaderboard_order(tiebreakers: &[Tiebreaker]) -> Vec<(&'static str, Order)> {
+    std::iter::once(("rating", Order::Desc))
+        .chain(tiebreakers.iter().map(|tiebreaker| match tiebreaker {
+            Tiebreaker::MostWins => ("wins", Order::Desc),
+            Tiebreaker::FewestGames => ("wins + losts + draw", Order::Asc),
+            Tiebreaker::EarliestRating => (
+                r#"COALESCE((SELECT MAX(changed_at) FROM rating_change WHERE rating_change.user_uuid = "user".uuid), "user".created_at)"#,
+                Order::Asc,
+            ),
+            Tiebreaker::OldestAccount => ("id", Order::Asc),
+            Tiebreaker::Uuid => ("uuid", Order::Asc),
+        }))
+        .chain(std::iter::once(("id", Order::Asc)))
+        .collect()
+}
+
+/// Orders the users query in the leaderboard order, see [`leaderboard_order`].
+fn order_by_leaderboard(
+    mut query: sea_orm::Select<UserEntity>,
+    tiebreakers: &[Tiebreaker],
+) -> sea_orm::Select<UserEntity> {
+    for (expr, order) in leaderboard_order(tiebreakers) {
+        query = query.order_by(Expr::cust(expr), order);
+    }
+    query
+}
+
+/// Returns the users sorted by their rating (highest first), the users with the same rating are sorted by
+/// the tiebreakers. The guests are not ranked.
+pub async fn get_leaderboard(
+    conn: &sea_orm::DatabaseConnection,
+    page: u64,
+    tiebreakers: &[Tiebreaker],
+) -> ApiResult<Vec<UserModel>> {
+    log::info!("Getting the leaderboard");
+
+    Ok(order_by_leaderboard(
+        UserEntity::find().filter(UserColumn::Role.ne(UserRole::Guest.to_string())),
+        tiebreakers,
+    )
+    .offset(page * LEADERBOARD_PAGE_SIZE)
+    .limit(LEADERBOARD_PAGE_SIZE)
+    .all(conn)
+    .await?)
+}
+
+/// Returns the user's rated games, the games that changed its rating.
+pub async fn count_user_rated_games(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<u64> {
+    Ok(RatingChangeEntity::find()
+        .filter(RatingChangeColumn::UserUuid.eq(user_uuid))
+        .count(conn)
+        .await?)
+}
+
+/// Returns the user's position and the number of the ranked users, the users with `min_rated_games` rated games
+/// or more, in the leaderboard order with the tiebreakers. `None` if the user is not ranked.
+///
+/// The position is computed with a window query, the users are not loaded.
+pub async fn get_user_rank(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+    min_rated_games: u64,
+    tiebreakers: &[Tiebreaker],
+) -> ApiResult<Option<(u64, u64)>> {
+    log::info!("Getting the rank of user {user_uuid}");
+
+    let order = leaderboard_order(tiebreakers)
+        .into_iter()
+        .map(|(expr, order)| match order {
+            Order::Desc => format!("{expr} DESC"),
+            _ => format!("{expr} ASC"),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut ranking = UserEntity::find()
+        .select_only()
+        .column(UserColumn::Uuid)
+        .column_as(
+            Expr::cust(format!("ROW_NUMBER() OVER (ORDER BY {order})")),
+            "rank",
+        )
+        .column_as(Expr::cust("COUNT(*) OVER ()"), "ranked_users")
+        .filter(UserColumn::Role.ne(UserRole::Guest.to_string()));
+    if min_rated_games > 0 {
+        ranking = ranking.filter(
+            UserColumn::Uuid.in_subquery(
+                Query::select()
+                    .column(RatingChangeColumn::UserUuid)
+                    .from(RatingChangeEntity)
+                    .group_by_col(RatingChangeColumn::UserUuid)
+                    .and_having(Expr::cust_with_values(
+                        "COUNT(*) >= $1",
+                        [min_rated_games as i64],
+                    ))
+                    .to_owned(),
+            ),
+        );
+    }
+    let statement = Query::select()
+        .column(Alias::new("rank"))
+        .column(Alias::new("ranked_users"))
+        .from_subquery(ranking.into_query(), Alias::new("ranking"))
+        .and_where(Expr::col(Alias::new("uuid")).eq(user_uuid))
+        .to_owned();
+
+    let Some(row) = conn
+        .query_one(conn.get_database_backend().build(&statement))
+        .await?
+    else {
+        return Ok(None);
+    };
+    Ok(Some((
+        row.try_get::<i64>("", "rank")? as u64,
+        row.try_get::<i64>("", "ranked_users")? as u64,
+    )))
+}
+
+/// Returns the standings of the past season leaderboard page with their users, the standings of the deleted users
+/// are skipped.
+pub async fn get_season_leaderboard(
+    conn: &sea_orm::DatabaseConnection,
+    season: i32,
+    page: u64,
+) -> ApiResult<Vec<(SeasonStandingModel, UserModel)>> {
+    log::info!("Getting the leaderboard of season {season}");
+
+    let standings = SeasonStandingEntity::find()
+        .filter(SeasonStandingColumn::Season.eq(season))
+        .order_by(SeasonStandingColumn::Rank, Order::Asc)
+        .offset(page * LEADERBOARD_PAGE_SIZE)
+        .limit(LEADERBOARD_PAGE_SIZE)
+        .all(conn)
+        .await?;
+    let users = UserEntity::find()
+        .filter(UserColumn::Uuid.is_in(standings.iter().map(|standing| standing.user_uuid)))
+        .all(conn)
+        .await?;
+
+    Ok(standings
+        .into_iter()
+        .filter_map(|standing| {
+            let user = users
+                .iter()
+                .find(|user| user.uuid == standing.user_uuid)?
+                .clone();
+            Some((standing, user))
+        })
+        .collect())
+}
+
+/// Returns the seasons, the newest first.
+pub async fn get_seasons(conn: &sea_orm::DatabaseConnection) -> ApiResult<Vec<SeasonModel>> {
+    Ok(SeasonEntity::find()
+        .order_by(SeasonColumn::Number, Order::Desc)
+        .all(conn)
+        .await?)
+}
+
+/// Returns the season by its number.
+pub async fn get_season(conn: &sea_orm::DatabaseConnection, number: i32) -> ApiResult<SeasonModel> {
+    SeasonEntity::find()
+        .filter(SeasonColumn::Number.eq(number))
+        .one(conn)
+        .await?
+        .ok_or(ApiError::SeasonNotFound)
+}
+
+/// Returns the current season, the season that is not ended yet. `None` if there is no season yet.
+pub async fn get_current_season(
+    conn: &sea_orm::DatabaseConnection,
+) -> ApiResult<Option<SeasonModel>> {
+    Ok(SeasonEntity::find()
+        .filter(SeasonColumn::EndedAt.is_null())
+        .order_by(SeasonColumn::Number, Order::Desc)
+        .one(conn)
+        .await?)
+}
+
+/// Starts a new season that ends after `length`, its number follows the latest season.
+pub async fn start_season(conn: &impl ConnectionTrait, length: Duration) -> ApiResult<SeasonModel> {
+    let number = SeasonEntity::find()
+        .order_by(SeasonColumn::Number, Order::Desc)
+        .one(conn)
+        .await?
+        .map_or(1, |season| season.number + 1);
+    log::info!("Starting season {number}");
+
+    let starts_at = chrono::Utc::now().naive_utc();
+    Ok(SeasonActiveModel {
+        number: Set(number),
+        starts_at: Set(starts_at),
+        ends_at: Set(starts_at + length),
+        ended_at: Set(None),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await?)
+}
+
+/// Ends the season and starts the next one, returns the next season.
+///
+/// The users that played a ranked game in the season are archived in its leaderboard, ordered by the policy
+/// tiebreakers if they have the same rating, the top of them get the end-of-season rewards, see
+/// [`SeasonReward::for_rank`]. Then the ratings are soft reset, see [`utils::season_reset_rating`].
+pub async fn end_season(
+    conn: &sea_orm::DatabaseConnection,
+    season: SeasonModel,
+    policy: &SeasonPolicy,
+) -> ApiResult<SeasonModel> {
+    log::info!("Ending season {}", season.number);
+
+    let txn = conn.begin().await?;
+    let players: Vec<Uuid> = RatingChangeEntity::find()
+        .select_only()
+        .column(RatingChangeColumn::UserUuid)
+        .distinct()
+        .filter(RatingChangeColumn::ChangedAt.gte(season.starts_at))
+        .into_tuple()
+        .all(&txn)
+        .await?;
+    let players = order_by_leaderboard(
+        UserEntity::find()
+            .filter(UserColumn::Uuid.is_in(players))
+            .filter(UserColumn::Role.ne(UserRole::Guest.to_string())),
+        &policy.tiebreakers,
+    )
+    .all(&txn)
+    .await?;
+    for (player, rank) in players.into_iter().zip(1..) {
+        SeasonStandingActiveModel {
+            season: Set(season.number),
+            user_uuid: Set(player.uuid),
+            rank: Set(rank as i64),
+            rating: Set(player.rating),
+            wins: Set(player.wins),
+            losts: Set(player.losts),
+            draw: Set(player.draw),
+            reward: Set(SeasonReward::for_rank(rank).map(|reward| reward.to_string())),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+    }
+
+    for user in UserEntity::find()
+        .filter(UserColumn::Rating.ne(utils::INITIAL_RATING))
+        .all(&txn)
+        .await?
+    {
+        let rating = utils::season_reset_rating(user.rating, policy.reset_factor);
+        let mut user = user.into_active_model();
+        user.rating = Set(rating);
+        user.update(&txn).await?;
+    }
+
+    let mut season = season.into_active_model();
+    season.ended_at = Set(Some(chrono::Utc::now().naive_utc()));
+    season.update(&txn).await?;
+    let next_season = start_season(&txn, policy.length).await?;
+    txn.commit().await?;
+    Ok(next_season)
+}
+
+/// Returns the user's archived standings that have a reward, the newest season first.
+pub async fn get_user_season_rewards(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<Vec<SeasonStandingModel>> {
+    Ok(SeasonStandingEntity::find()
+        .filter(SeasonStandingColumn::UserUuid.eq(user_uuid))
+        .filter(SeasonStandingColumn::Reward.is_not_null())
+        .order_by(SeasonStandingColumn::Season, Order::Desc)
+        .all(conn)
+        .await?)
+}
+
+/// Deletes the user's archived season standings.
+pub async fn delete_user_season_standings(
+    conn: &impl ConnectionTrait,
+    user_uuid: Uuid,
+) -> ApiResult<()> {
+    log::info!("Deleting the season standings of user {user_uuid}");
+
+    SeasonStandingEntity::delete_many()
+        .filter(SeasonStandingColumn::UserUuid.eq(user_uuid))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Create a new game in the database.
+///
+/// If `unique_pair` is `true`, the game will not be created if the players already have an in-progress game against each other.
+/// The first `opening_moves` moves of each round are random moves played by the server, see [`Board::random_opening`].
+pub async fn create_game(
+    conn: &sea_orm::DatabaseConnection,
+    x_player: Uuid,
+    o_player: Uuid,
+    move_period: i64,
+    unique_pair: bool,
+    opening_moves: u8,
+) -> ApiResult<GameActiveModel> {
+    log::info!("Creating game");
+
+    if unique_pair {
+        if let Some(game) = get_active_game_between(conn, x_player, o_player).await? {
+            log::error!(
+                "The players are already playing each other in game: {}",
+                game.uuid
+            );
+            return Err(ApiError::AlreadyPlayingEachOther(game.uuid));
+        }
+    }
+
+    let uuid = loop {
+        let uuid = Uuid::new_v4();
+        if GameEntity::find()
+            .filter(GameColumn::Uuid.eq(uuid))
+            .count(conn)
+            .await?
+            == 0
+        {
+            break uuid;
+        }
+    };
+
+    let now = chrono::Utc::now().naive_utc();
+    Ok(GameActiveModel {
+        uuid: Set(uuid),
+        round: Set(1i16),
+        auto_play_after: Set(Some(now + Duration::seconds(move_period))),
+        rounds_result: Set(RoundsResult::default().to_string()),
+        x_player: Set(x_player),
+        o_player: Set(o_player),
+        x_start_chat: Set(false),
+        o_start_chat: Set(false),
+        board: Set(Board::random_opening(opening_moves).to_string()),
+        winner: Set(None),
+        reason: Set(None),
+        created_at: Set(now),
+        opening_moves: Set(opening_moves as i16),
+        ..Default::default()
+    }
+    .save(conn)
+    .await?)
+}
+
+/// Create a new game against the AI opponent, the bot player is a virtual player that is not a user, its uuid
+/// is `bot_uuid` and it's one of the players.
+pub async fn create_bot_game(
+    conn: &sea_orm::DatabaseConnection,
+    x_player: Uuid,
+    o_player: Uuid,
+    move_period: i64,
+    opening_moves: u8,
+    bot_uuid: Uuid,
+    difficulty: BotDifficulty,
+) -> ApiResult<GameActiveModel> {
+    let mut game = create_game(conn, x_player, o_player, move_period, false, opening_moves).await?;
+    game.bot_difficulty = Set(Some(difficulty.to_string()));
+    game.bot_uuid = Set(Some(bot_uuid));
+    Ok(game.save(conn).await?)
+}
+
+/// Gives each player of the new game `game_time` seconds for all its moves, the time left is saved in milliseconds.
+pub async fn set_game_time(
+    conn: &sea_orm::DatabaseConnection,
+    mut game: GameActiveModel,
+    game_time: i64,
+) -> ApiResult<GameActiveModel> {
+    game.x_time_left = Set(Some(game_time * 1000));
+    game.o_time_left = Set(Some(game_time * 1000));
+    Ok(game.save(conn).await?)
+}
+
+/// Sets the variant of the new game, the first round board is replaced with a board of the variant.
+pub async fn set_game_variant(
+    conn: &sea_orm::DatabaseConnection,
+    mut game: GameActiveModel,
+    variant: GameVariant,
+) -> ApiResult<GameActiveModel> {
+    let opening_moves = *game.opening_moves.as_ref() as u8;
+    game.board = Set(Board::random_variant_opening(variant, opening_moves).to_string());
+    game.board_size = Set(variant.size as i16);
+    game.win_length = Set(variant.win_length as i16);
+    Ok(game.save(conn).await?)
+}
+
+/// Creates a new invite of the host for a private game of the variant, that expires after `ttl` seconds.
+/// The previous invites of the host are replaced.
+pub async fn create_invite(
+    conn: &sea_orm::DatabaseConnection,
+    host_uuid: Uuid,
+    ttl: u64,
+    variant: GameVariant,
+) -> ApiResult<InviteModel> {
+    log::info!("Creating an invite for the host {host_uuid}");
+
+    let code = loop {
+        let code = utils::generate_invite_code();
+        if InviteEntity::find()
+            .filter(InviteColumn::Code.eq(code.clone()))
+            .count(conn)
+            .await?
+            == 0
+        {
+            break code;
+        }
+    };
+
+    let now = chrono::Utc::now().naive_utc();
+    let txn = conn.begin().await?;
+    delete_user_invites(&txn, host_uuid).await?;
+    let invite = InviteActiveModel {
+        code: Set(code),
+        host_uuid: Set(host_uuid),
+        created_at: Set(now),
+        expires_at: Set(now + Duration::seconds(ttl as i64)),
+        board_size: Set(variant.size as i16),
+        win_length: Set(variant.win_length as i16),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+    txn.commit().await?;
+    Ok(invite)
+}
+
+/// Returns the invite of the code, if it's not expired.
+pub async fn get_invite(conn: &sea_orm::DatabaseConnection, code: &str) -> ApiResult<InviteModel> {
+    log::info!("Getting the invite of a code");
+
+    InviteEntity::find()
+        .filter(InviteColumn::Code.eq(utils::normalize_invite_code(code)))
+        .filter(InviteColumn::ExpiresAt.gt(chrono::Utc::now().naive_utc()))
+        .one(conn)
+        .await?
+        .ok_or(ApiError::InvalidInviteCode)
+}
+
+/// Deletes the invite, returns whether it's deleted. An invite is only used once, only the first of the
+/// concurrent deletions succeeds.
+pub async fn delete_invite(conn: &sea_orm::DatabaseConnection, invite_id: i32) -> ApiResult<bool> {
+    Ok(InviteEntity::delete_by_id(invite_id)
+        .exec(conn)
+        .await?
+        .rows_affected
+        != 0)
+}
+
+/// Deletes the user's invites.
+pub async fn delete_user_invites(conn: &impl ConnectionTrait, user_uuid: Uuid) -> ApiResult<()> {
+    InviteEntity::delete_many()
+        .filter(InviteColumn::HostUuid.eq(user_uuid))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Deletes the expired invites, returns the number of the deleted invites.
+pub async fn prune_expired_invites(conn: &impl ConnectionTrait) -> ApiResult<u64> {
+    Ok(InviteEntity::delete_many()
+        .filter(InviteColumn::ExpiresAt.lte(chrono::Utc::now().naive_utc()))
+        .exec(conn)
+        .await?
+        .rows_affected)
+}
+
+/// Creates a new tournament, its registration is open until it's full.
+pub async fn create_tournament(
+    conn: &sea_orm::DatabaseConnection,
+    host_uuid: Uuid,
+    new_tournament: NewTournamentSchema,
+) -> ApiResult<TournamentModel> {
+    log::info!("Creating a tournament by the host {host_uuid}");
+
+    let uuid = loop {
+        let uuid = Uuid::new_v4();
+        if TournamentEntity::find()
+            .filter(TournamentColumn::Uuid.eq(uuid))
+            .count(conn)
+            .await?
+            == 0
+        {
+            break uuid;
+        }
+    };
+
+    Ok(TournamentActiveModel {
+        uuid: Set(uuid),
+        name: Set(new_tournament.name.trim().to_owned()),
+        host_uuid: Set(host_uuid),
+        size: Set(i16::from(new_tournament.size)),
+        winner: Set(None),
+        created_at: Set(chrono::Utc::now().naive_utc()),
+        started_at: Set(None),
+        ended_at: Set(None),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await?)
+}
+
+/// Returns the tournament by its uuid.
+pub async fn get_tournament(
+    conn: &impl ConnectionTrait,
+    tournament_uuid: Uuid,
+) -> ApiResult<TournamentModel> {
+    log::info!("Getting the tournament {tournament_uuid}");
+
+    TournamentEntity::find()
+        .filter(TournamentColumn::Uuid.eq(tournament_uuid))
+        .one(conn)
+        .await?
+        .ok_or(ApiError::TournamentNotFound)
+}
+
+/// Returns the tournament players, in the registration order.
+pub async fn get_tournament_players(
+    conn: &impl ConnectionTrait,
+    tournament_uuid: Uuid,
+) -> ApiResult<Vec<TournamentPlayerModel>> {
+    Ok(TournamentPlayerEntity::find()
+        .filter(TournamentPlayerColumn::TournamentUuid.eq(tournament_uuid))
+        .order_by_asc(TournamentPlayerColumn::Id)
+        .all(conn)
+        .await?)
+}
+
+/// Returns the tournament bracket matches, sorted by the round then the slot.
+pub async fn get_tournament_matches(
+    conn: &sea_orm::DatabaseConnection,
+    tournament_uuid: Uuid,
+) -> ApiResult<Vec<TournamentMatchModel>> {
+    Ok(TournamentMatchEntity::find()
+        .filter(TournamentMatchColumn::TournamentUuid.eq(tournament_uuid))
+        .order_by_asc(TournamentMatchColumn::Round)
+        .order_by_asc(TournamentMatchColumn::Slot)
+        .all(conn)
+        .await?)
+}
+
+/// Registers the user in the tournament. When the registration is full, the single-elimination bracket
+/// is generated with the players in a random order, the first round matches have their players and the
+/// next rounds matches are filled while their previous matches winners advance. Returns whether the
+/// bracket is generated.
+pub async fn join_tournament(
+    conn: &sea_orm::DatabaseConnection,
+    tournament_uuid: Uuid,
+    user_uuid: Uuid,
+) -> ApiResult<bool> {
+    log::info!("Registering the user {user_uuid} in the tournament {tournament_uuid}");
+
+    let txn = conn.begin().await?;
+    // Lock the tournament row, the concurrent registrations can't overfill it
+    let tournament = TournamentEntity::find()
+        .filter(TournamentColumn::Uuid.eq(tournament_uuid))
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or(ApiError::TournamentNotFound)?;
+    if tournament.started_at.is_some() {
+        return Err(ApiError::TournamentRegistrationClosed);
+    }
+    let mut players = get_tournament_players(&txn, tournament_uuid).await?;
+    if players.iter().any(|player| player.user_uuid == user_uuid) {
+        return Err(ApiError::AlreadyRegistered);
+    }
+
+    let now = chrono::Utc::now().naive_utc();
+    players.push(
+        TournamentPlayerActiveModel {
+            tournament_uuid: Set(tournament_uuid),
+            user_uuid: Set(user_uuid),
+            registered_at: Set(now),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?,
+    );
+
+    let is_full = players.len() == tournament.size as usize;
+    if is_full {
+        log::info!("Generating the bracket of the tournament {tournament_uuid}");
+        rand::seq::SliceRandom::shuffle(players.as_mut_slice(), &mut rand::thread_rng());
+
+        let rounds = utils::tournament_rounds(tournament.size as u8);
+        for round in 1..=rounds {
+            let matches = tournament.size >> round;
+            for slot in 0..matches {
+                let slot_player = |index: i16| {
+                    (round == 1).then(|| players[(slot * 2 + index) as usize].user_uuid)
+                };
+                TournamentMatchActiveModel {
+                    tournament_uuid: Set(tournament_uuid),
+                    round: Set(round),
+                    slot: Set(slot),
+                    x_player: Set(slot_player(0)),
+                    o_player: Set(slot_player(1)),
+                    game_uuid: Set(None),
+                    winner: Set(None),
+                    ..Default::default()
+                }
+                .insert(&txn)
+                .await?;
+            }
+        }
+
+        let mut tournament = tournament.into_active_model();
+        tournament.started_at = Set(Some(now));
+        tournament.save(&txn).await?;
+    }
+    txn.commit().await?;
+    Ok(is_full)
+}
+
+/// Returns the player's next match in the tournament, the match that has both players and its game didn't
+/// start yet.
+pub async fn get_player_tournament_match(
+    conn: &sea_orm::DatabaseConnection,
+    tournament_uuid: Uuid,
+    player_uuid: Uuid,
+) -> ApiResult<Option<TournamentMatchModel>> {
+    Ok(TournamentMatchEntity::find()
+        .filter(TournamentMatchColumn::TournamentUuid.eq(tournament_uuid))
+        .filter(
+            Condition::any()
+                .add(TournamentMatchColumn::XPlayer.eq(player_uuid))
+                .add(TournamentMatchColumn::OPlayer.eq(player_uuid)),
+        )
+        .filter(TournamentMatchColumn::XPlayer.is_not_null())
+        .filter(TournamentMatchColumn::OPlayer.is_not_null())
+        .filter(TournamentMatchColumn::GameUuid.is_null())
+        .filter(TournamentMatchColumn::Winner.is_null())
+        .one(conn)
+        .await?)
+}
+
+/// Returns the match that the players of the ended tournament match game play next: the same match if the game
+/// ended with a draw and the match is replayed, otherwise the next round match if the game winner advanced to it
+/// and both of its players are known now.
+pub async fn get_next_tournament_match(
+    conn: &sea_orm::DatabaseConnection,
+    game_uuid: Uuid,
+) -> ApiResult<Option<TournamentMatchModel>> {
+    let Some(tournament_match) = get_game_tournament_match(conn, game_uuid).await? else {
+        return Ok(None);
+    };
+    if tournament_match.winner.is_none() {
+        // The game ended with a draw, the match is replayed
+        return Ok(Some(tournament_match).filter(|m| m.game_uuid.is_none()));
+    }
+
+    Ok(TournamentMatchEntity::find()
+        .filter(TournamentMatchColumn::TournamentUuid.eq(tournament_match.tournament_uuid))
+        .filter(TournamentMatchColumn::Round.eq(tournament_match.round + 1))
+        .filter(TournamentMatchColumn::Slot.eq(tournament_match.slot / 2))
+        .filter(TournamentMatchColumn::XPlayer.is_not_null())
+        .filter(TournamentMatchColumn::OPlayer.is_not_null())
+        .filter(TournamentMatchColumn::GameUuid.is_null())
+        .one(conn)
+        .await?)
+}
+
+/// Returns the tournament of the game, if it's a tournament match game.
+pub async fn get_game_tournament(
+    conn: &sea_orm::DatabaseConnection,
+    game_uuid: Uuid,
+) -> ApiResult<Option<TournamentModel>> {
+    let Some(tournament_match) = get_game_tournament_match(conn, game_uuid).await? else {
+        return Ok(None);
+    };
+    get_tournament(conn, tournament_match.tournament_uuid)
+        .await
+        .map(Some)
+}
+
+/// Returns the tournament match of the game, if it's one of the match games.
+async fn get_game_tournament_match(
+    conn: &impl ConnectionTrait,
+    game_uuid: Uuid,
+) -> ApiResult<Option<TournamentMatchModel>> {
+    let Some(match_game) = TournamentMatchGameEntity::find()
+        .filter(TournamentMatchGameColumn::GameUuid.eq(game_uuid))
+        .one(conn)
+        .await?
+    else {
+        return Ok(None);
+    };
+    Ok(TournamentMatchEntity::find_by_id(match_game.match_id)
+        .one(conn)
+        .await?)
+}
+
+/// Returns the games of the tournament matches, the oldest first.
+pub async fn get_tournament_match_games(
+    conn: &sea_orm::DatabaseConnection,
+    match_ids: Vec<i32>,
+) -> ApiResult<Vec<TournamentMatchGameModel>> {
+    Ok(TournamentMatchGameEntity::find()
+        .filter(TournamentMatchGameColumn::MatchId.is_in(match_ids))
+        .order_by_asc(TournamentMatchGameColumn::Id)
+        .all(conn)
+        .await?)
+}
+
+/// Links the game to its tournament match, it's the match's current game.
+pub async fn set_tournament_match_game(
+    conn: &sea_orm::DatabaseConnection,
+    match_id: i32,
+    game_uuid: Uuid,
+) -> ApiResult<()> {
+    let txn = conn.begin().await?;
+    TournamentMatchEntity::update_many()
+        .col_expr(TournamentMatchColumn::GameUuid, Expr::value(game_uuid))
+        .filter(TournamentMatchColumn::Id.eq(match_id))
+        .exec(&txn)
+        .await?;
+    TournamentMatchGameActiveModel {
+        match_id: Set(match_id),
+        game_uuid: Set(game_uuid),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+    txn.commit().await?;
+    Ok(())
+}
+
+/// Advances the winner of the tournament match game to its next round match, the winner of the final
+/// match wins the tournament.
+///
+/// If the game ended with a draw, the match is replayed with the swapped sides. After
+/// [`utils::MAX_TOURNAMENT_MATCH_DRAWS`] draws the higher rated player wins the match, the O player of the last
+/// game if their ratings are equal.
+async fn advance_tournament(
+    conn: &impl ConnectionTrait,
+    game_uuid: Uuid,
+    winner: Option<Uuid>,
+) -> ApiResult<()> {
+    let Some(tournament_match) = get_game_tournament_match(conn, game_uuid).await? else {
+        return Ok(());
+    };
+    let tournament = get_tournament(conn, tournament_match.tournament_uuid).await?;
+    let (round, slot) = (tournament_match.round, tournament_match.slot);
+    let (x_player, o_player) = (tournament_match.x_player, tournament_match.o_player);
+    let mut tournament_match = tournament_match.into_active_model();
+
+    let winner = match winner {
+        Some(winner) => winner,
+        None => {
+            // All the match games before the last one ended with a draw
+            let draws = TournamentMatchGameEntity::find()
+                .filter(TournamentMatchGameColumn::MatchId.eq(*tournament_match.id.as_ref()))
+                .count(conn)
+                .await?;
+            if draws < utils::MAX_TOURNAMENT_MATCH_DRAWS {
+                log::info!(
+                    "The tournament match game {game_uuid} ended with a draw, the match is replayed"
+                );
+                tournament_match.game_uuid = Set(None);
+                tournament_match.x_player = Set(o_player);
+                tournament_match.o_player = Set(x_player);
+                tournament_match.save(conn).await?;
+                return Ok(());
+            }
+
+            let players = UserEntity::find()
+                .filter(UserColumn::Uuid.is_in(x_player.into_iter().chain(o_player)))
+                .all(conn)
+                .await?;
+            let rating = |player: Option<Uuid>| {
+                players
+                    .iter()
+                    .find(|user| Some(user.uuid) == player)
+                    .map(|user| user.rating)
+            };
+            let Some(winner) = (if rating(x_player) > rating(o_player) {
+                x_player
+            } else {
+                o_player
+            }) else {
+                return Ok(());
+            };
+            log::info!(
+                "The tournament match game {game_uuid} ended with a draw, the player {winner} wins the match \
+                 by the tiebreak after {draws} draws"
+            );
+            winner
+        }
+    };
+    tournament_match.winner = Set(Some(winner));
+    tournament_match.save(conn).await?;
+
+    if round == utils::tournament_rounds(tournament.size as u8) {
+        log::info!("The player {winner} won the tournament {}", tournament.uuid);
+        let mut tournament = tournament.into_active_model();
+        tournament.winner = Set(Some(winner));
+        tournament.ended_at = Set(Some(chrono::Utc::now().naive_utc()));
+        tournament.save(conn).await?;
+        return Ok(());
+    }
+
+    let player_column = if slot % 2 == 0 {
+        TournamentMatchColumn::XPlayer
+    } else {
+        TournamentMatchColumn::OPlayer
+    };
+    TournamentMatchEntity::update_many()
+        .col_expr(player_column, Expr::value(winner))
+        .filter(TournamentMatchColumn::TournamentUuid.eq(tournament.uuid))
+        .filter(TournamentMatchColumn::Round.eq(round + 1))
+        .filter(TournamentMatchColumn::Slot.eq(slot / 2))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Deletes the user's registrations in the tournaments that their registration is still open.
+pub async fn delete_user_registrations(
+    conn: &impl ConnectionTrait,
+    user_uuid: Uuid,
+) -> ApiResult<()> {
+    TournamentPlayerEntity::delete_many()
+        .filter(TournamentPlayerColumn::UserUuid.eq(user_uuid))
+        .filter(
+            TournamentPlayerColumn::TournamentUuid.in_subquery(
+                sea_orm::sea_query::Query::select()
+                    .column(TournamentColumn::Uuid)
+                    .from(TournamentEntity)
+                    .and_where(TournamentColumn::StartedAt.is_null())
+                    .to_owned(),
+            ),
+        )
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Returns the number of games the user started since the given time.
+pub async fn count_user_games_since(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+    since: chrono::NaiveDateTime,
+) -> ApiResult<u64> {
+    log::info!("Counting the games of the user {user_uuid} since {since}");
+
+    Ok(GameEntity::find()
+        .filter(
+            Condition::any()
+                .add(GameColumn::XPlayer.eq(user_uuid))
+                .add(GameColumn::OPlayer.eq(user_uuid)),
+        )
+        .filter(GameColumn::CreatedAt.gt(since))
+        .count(conn)
+        .await?)
+}
+
+/// Get a game from the database by uuid.
+pub async fn get_game<const IS_END: bool>(
+    conn: &sea_orm::DatabaseConnection,
+    game_uuid: &Uuid,
+) -> ApiResult<GameModel> {
+    log::info!("Getting game: {}", game_uuid);
+
+    GameEntity::find()
+        .filter(if IS_END {
+            GameColumn::Uuid
+                .eq(*game_uuid)
+                .and(GameColumn::EndedAt.is_not_null())
+        } else {
+            GameColumn::Uuid
+                .eq(*game_uuid)
+                .and(GameColumn::EndedAt.is_null())
+        })
+        .one(conn)
+        .await?
+        .ok_or(ApiError::GameNotFound)
+}
+
+/// Returns the in-progress game between the two players, if there is one.
+pub async fn get_active_game_between(
+    conn: &sea_orm::DatabaseConnection,
+    player1: Uuid,
+    player2: Uuid,
+) -> ApiResult<Option<GameModel>> {
+    log::info!("Getting the in-progress game between two players");
+
+    Ok(GameEntity::find()
+        .filter(
+            GameColumn::EndedAt.is_null().and(
+                GameColumn::XPlayer
+                    .eq(player1)
+                    .and(GameColumn::OPlayer.eq(player2))
+                    .or(GameColumn::XPlayer
+                        .eq(player2)
+                        .and(GameColumn::OPlayer.eq(player1))),
+            ),
+        )
+        .one(conn)
+        .await?)
+}
+
+/// Blocks the `blocked` user for the `blocker` user, blocking an already blocked user does nothing.
+/// The friendship between them is removed.
+pub async fn block_user(
+    conn: &sea_orm::DatabaseConnection,
+    blocker: Uuid,
+    blocked: Uuid,
+) -> ApiResult<()> {
+    log::info!("Blocking user {blocked} for user {blocker}");
+
+    if blocker == blocked {
+        return Err(ApiError::CannotBlockSelf);
+    }
+    get_user(conn, blocked).await?;
+
+    if BlockEntity::find()
+        .filter(
+            BlockColumn::Blocker
+                .eq(blocker)
+                .and(BlockColumn::Blocked.eq(blocked)),
+        )
+        .count(conn)
+        .await?
+        == 0
+    {
+        BlockActiveModel {
+            blocker: Set(blocker),
+            blocked: Set(blocked),
+            created_at: Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .save(conn)
+        .await?;
+    }
+    // The blocked users can't be friends
+    remove_friend(conn, blocker, blocked).await
+}
+
+/// Unblocks the `blocked` user for the `blocker` user, unblocking a not blocked user does nothing.
+pub async fn unblock_user(
+    conn: &sea_orm::DatabaseConnection,
+    blocker: Uuid,
+    blocked: Uuid,
+) -> ApiResult<()> {
+    log::info!("Unblocking user {blocked} for user {blocker}");
+
+    BlockEntity::delete_many()
+        .filter(
+            BlockColumn::Blocker
+                .eq(blocker)
+                .and(BlockColumn::Blocked.eq(blocked)),
+        )
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Returns the users blocked by the user, the latest blocked first.
+pub async fn get_blocked_users(
+    conn: &sea_orm::DatabaseConnection,
+    blocker: Uuid,
+) -> ApiResult<Vec<UserModel>> {
+    log::info!("Getting the blocked users of user {blocker}");
+
+    let blocked: Vec<Uuid> = BlockEntity::find()
+        .select_only()
+        .column(BlockColumn::Blocked)
+        .filter(BlockColumn::Blocker.eq(blocker))
+        .order_by(BlockColumn::CreatedAt, Order::Desc)
+        .into_tuple()
+        .all(conn)
+        .await?;
+    let mut users = UserEntity::find()
+        .filter(UserColumn::Uuid.is_in(blocked.clone()))
+        .all(conn)
+        .await?;
+    users.sort_by_key(|user| blocked.iter().position(|uuid| uuid == &user.uuid));
+    Ok(users)
+}
+
+/// Returns whether the `blocker` user blocked the `blocked` user.
+pub async fn is_blocked(
+    conn: &sea_orm::DatabaseConnection,
+    blocker: Uuid,
+    blocked: Uuid,
+) -> ApiResult<bool> {
+    Ok(BlockEntity::find()
+        .filter(
+            BlockColumn::Blocker
+                .eq(blocker)
+                .and(BlockColumn::Blocked.eq(blocked)),
+        )
+        .count(conn)
+        .await?
+        > 0)
+}
+
+/// Returns the users that blocked the user or blocked by the user.
+pub async fn get_block_relations(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<Vec<Uuid>> {
+    Ok(BlockEntity::find()
+        .filter(
+            BlockColumn::Blocker
+                .eq(user_uuid)
+                .or(BlockColumn::Blocked.eq(user_uuid)),
+        )
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|block| {
+            if block.blocker == user_uuid {
+                block.blocked
+            } else {
+                block.blocker
+            }
+        })
+        .collect())
+}
+
+/// Deletes the user's blocks, the blocks made by the user and the blocks against the user.
+pub async fn delete_user_blocks(conn: &impl ConnectionTrait, user_uuid: Uuid) -> ApiResult<()> {
+    log::info!("Deleting the blocks of user {user_uuid}");
+
+    BlockEntity::delete_many()
+        .filter(
+            BlockColumn::Blocker
+                .eq(user_uuid)
+                .or(BlockColumn::Blocked.eq(user_uuid)),
+        )
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Returns the condition of the friendship rows between the two users, in both directions.
+fn friendship_between(user_uuid: Uuid, other_uuid: Uuid) -> Condition {
+    Condition::any()
+        .add(
+            FriendshipColumn::Requester
+                .eq(user_uuid)
+                .and(FriendshipColumn::Addressee.eq(other_uuid)),
+        )
+        .add(
+            FriendshipColumn::Requester
+                .eq(other_uuid)
+                .and(FriendshipColumn::Addressee.eq(user_uuid)),
+        )
+}
+
+/// Sends a friend request from the `requester` user to the `addressee` user. If the `addressee` already sent
+/// a request to the `requester`, it's accepted instead. Returns whether the users are friends now.
+pub async fn send_friend_request(
+    conn: &sea_orm::DatabaseConnection,
+    requester: Uuid,
+    addressee: Uuid,
+) -> ApiResult<bool> {
+    log::info!("Sending a friend request from user {requester} to user {addressee}");
+
+    if requester == addressee {
+        return Err(ApiError::CannotFriendSelf);
+    }
+    get_user(conn, addressee).await?;
+    if is_blocked(conn, requester, addressee).await?
+        || is_blocked(conn, addressee, requester).await?
+    {
+        return Err(ApiError::FriendRequestBlocked);
+    }
+
+    match FriendshipEntity::find()
+        .filter(friendship_between(requester, addressee))
+        .one(conn)
+        .await?
+    {
+        Some(friendship)
+            if friendship.accepted_at.is_none() && friendship.requester == addressee =>
+        {
+            let mut friendship = friendship.into_active_model();
+            friendship.accepted_at = Set(Some(chrono::Utc::now().naive_utc()));
+            friendship.save(conn).await?;
+            Ok(true)
+        }
+        Some(_) => Err(ApiError::AlreadyFriends),
+        None => {
+            FriendshipActiveModel {
+                requester: Set(requester),
+                addressee: Set(addressee),
+                created_at: Set(chrono::Utc::now().naive_utc()),
+                ..Default::default()
+            }
+            .save(conn)
+            .await?;
+            Ok(false)
+        }
+    }
+}
+
+/// Returns the pending friend request from the `requester` user to the `addressee` user.
+async fn get_friend_request(
+    conn: &sea_orm::DatabaseConnection,
+    addressee: Uuid,
+    requester: Uuid,
+) -> ApiResult<FriendshipModel> {
+    FriendshipEntity::find()
+        .filter(
+            FriendshipColumn::Requester
+                .eq(requester)
+                .and(FriendshipColumn::Addressee.eq(addressee)),
+        )
+        .filter(FriendshipColumn::AcceptedAt.is_null())
+        .one(conn)
+        .await?
+        .ok_or(ApiError::FriendRequestNotFound)
+}
+
+/// Accepts the pending friend request that the `requester` user sent to the `addressee` user.
+pub async fn accept_friend_request(
+    conn: &sea_orm::DatabaseConnection,
+    addressee: Uuid,
+    requester: Uuid,
+) -> ApiResult<()> {
+    log::info!("Accepting the friend request of user {requester} to user {addressee}");
+
+    let mut friendship = get_friend_request(conn, addressee, requester)
+        .await?
+        .into_active_model();
+    friendship.accepted_at = Set(Some(chrono::Utc::now().naive_utc()));
+    friendship.save(conn).await?;
+    Ok(())
+}
+
+/// Declines the pending friend request that the `requester` user sent to the `addressee` user, it's deleted.
+pub async fn decline_friend_request(
+    conn: &sea_orm::DatabaseConnection,
+    addressee: Uuid,
+    requester: Uuid,
+) -> ApiResult<()> {
+    log::info!("Declining the friend request of user {requester} to user {addressee}");
+
+    FriendshipEntity::delete_by_id(get_friend_request(conn, addressee, requester).await?.id)
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Removes the friendship or the pending friend request between the two users, removing a not friend does nothing.
+pub async fn remove_friend(
+    conn: &impl ConnectionTrait,
+    user_uuid: Uuid,
+    other_uuid: Uuid,
+) -> ApiResult<()> {
+    log::info!("Removing the friendship between user {user_uuid} and user {other_uuid}");
+
+    FriendshipEntity::delete_many()
+        .filter(friendship_between(user_uuid, other_uuid))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Returns the uuids of the user's friends with the dates that they became friends in, the latest first.
+async fn accepted_friendships(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<Vec<(Uuid, chrono::NaiveDateTime)>> {
+    Ok(FriendshipEntity::find()
+        .filter(
+            FriendshipColumn::Requester
+                .eq(user_uuid)
+                .or(FriendshipColumn::Addressee.eq(user_uuid)),
+        )
+        .filter(FriendshipColumn::AcceptedAt.is_not_null())
+        .order_by(FriendshipColumn::AcceptedAt, Order::Desc)
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|friendship| {
+            let friend = if friendship.requester == user_uuid {
+                friendship.addressee
+            } else {
+                friendship.requester
+            };
+            (
+                friend,
+                friendship.accepted_at.expect("The friendship is accepted"),
+            )
+        })
+        .collect())
+}
+
+/// Returns the user's friends with the dates that they became friends in, the latest first.
+pub async fn get_friends(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<Vec<(UserModel, chrono::NaiveDateTime)>> {
+    log::info!("Getting the friends of user {user_uuid}");
+
+    with_users(conn, accepted_friendships(conn, user_uuid).await?).await
+}
+
+/// Returns the uuids of the user's friends.
+pub async fn get_friend_uuids(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<Vec<Uuid>> {
+    Ok(accepted_friendships(conn, user_uuid)
+        .await?
+        .into_iter()
+        .map(|(friend, _)| friend)
+        .collect())
+}
+
+/// Returns the user's pending friend requests with their dates, the incoming and the outgoing requests,
+/// the latest first.
+#[allow(clippy::type_complexity)]
+pub async fn get_friend_requests(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<(
+    Vec<(UserModel, chrono::NaiveDateTime)>,
+    Vec<(UserModel, chrono::NaiveDateTime)>,
+)> {
+    log::info!("Getting the friend requests of user {user_uuid}");
+
+    let (incoming, outgoing): (Vec<_>, Vec<_>) = FriendshipEntity::find()
+        .filter(
+            FriendshipColumn::Requester
+                .eq(user_uuid)
+                .or(FriendshipColumn::Addressee.eq(user_uuid)),
+        )
+        .filter(FriendshipColumn::AcceptedAt.is_null())
+        .order_by(FriendshipColumn::CreatedAt, Order::Desc)
+        .all(conn)
+        .await?
+        .into_iter()
+        .partition(|friendship| friendship.addressee == user_uuid);
+    Ok((
+        with_users(
+            conn,
+            incoming
+                .into_iter()
+                .map(|request| (request.requester, request.created_at))
+                .collect(),
+        )
+        .await?,
+        with_users(
+            conn,
+            outgoing
+                .into_iter()
+                .map(|request| (request.addressee, request.created_at))
+                .collect(),
+        )
+        .await?,
+    ))
+}
+
+/// Replaces the users uuids with their users, keeping the order.
+async fn with_users<T>(
+    conn: &sea_orm::DatabaseConnection,
+    items: Vec<(Uuid, T)>,
+) -> ApiResult<Vec<(UserModel, T)>> {
+    let mut users = UserEntity::find()
+        .filter(UserColumn::Uuid.is_in(items.iter().map(|(uuid, _)| *uuid)))
+        .all(conn)
+        .await?;
+    Ok(items
+        .into_iter()
+        .filter_map(|(uuid, item)| {
+            let index = users.iter().position(|user| user.uuid == uuid)?;
+            Some((users.swap_remove(index), item))
+        })
+        .collect())
+}
+
+/// Deletes the user's friendships and friend requests, in both directions.
+pub async fn delete_user_friendships(
+    conn: &impl ConnectionTrait,
+    user_uuid: Uuid,
+) -> ApiResult<()> {
+    log::info!("Deleting the friendships of user {user_uuid}");
+
+    FriendshipEntity::delete_many()
+        .filter(
+            FriendshipColumn::Requester
+                .eq(user_uuid)
+                .or(FriendshipColumn::Addressee.eq(user_uuid)),
+        )
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Returns whether the two users are friends.
+pub async fn are_friends(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+    other_uuid: Uuid,
+) -> ApiResult<bool> {
+    Ok(FriendshipEntity::find()
+        .filter(friendship_between(user_uuid, other_uuid))
+        .filter(FriendshipColumn::AcceptedAt.is_not_null())
+        .count(conn)
+        .await?
+        != 0)
+}
 
-    let uuid = loop {
-        let uuid = Uuid::new_v4();
-        if GameEntity::find()
-            .filter(GameColumn::Uuid.eq(uuid))
-            .count(conn)
-            .await?
-            == 0
-        {
-            break uuid;
-        }
-    };
+/// Creates a new unread notification for the user.
+pub async fn create_notification(
+    conn: &impl ConnectionTrait,
+    user_uuid: Uuid,
+    data: &NotificationData,
+) -> ApiResult<NotificationModel> {
+    log::info!(
+        "Creating a `{}` notification for user {user_uuid}",
+        data.kind().to_string()
+    );
 
-    let now = chrono::Utc::now().naive_utc();
-    Ok(GameActiveModel {
-        uuid: Set(uuid),
-        round: Set(1i16),
-        auto_play_after: Set(Some(now + Duration::seconds(move_period))),
-        rounds_result: Set(RoundsResult::default().to_string()),
-        x_player: Set(x_player),
-        o_player: Set(o_player),
-        x_start_chat: Set(false),
-        o_start_chat: Set(false),
-        board: Set(Board::default().to_string()),
-        winner: Set(None),
-        reason: Set(None),
-        created_at: Set(now),
+    Ok(NotificationActiveModel {
+        uuid: Set(Uuid::new_v4()),
+        user_uuid: Set(user_uuid),
+        kind: Set(data.kind().to_string()),
+        data: Set(serde_json::to_string(data)?),
+        read_at: Set(None),
+        created_at: Set(chrono::Utc::now().naive_utc()),
         ..Default::default()
     }
-    .save(conn)
+    .insert(conn)
     .await?)
 }
 
-/// Get a game from the database by uuid.
-pub async fn get_game<const IS_END: bool>(
+/// Returns the user's notifications (newest first), only the unread ones if `unread` is true.
+pub async fn get_notifications(
     conn: &sea_orm::DatabaseConnection,
-    game_uuid: &Uuid,
-) -> ApiResult<GameModel> {
-    log::info!("Getting game: {}", game_uuid);
+    user_uuid: Uuid,
+    unread: bool,
+    page: u64,
+) -> ApiResult<Vec<NotificationModel>> {
+    log::info!("Getting the notifications of user {user_uuid}");
 
-    GameEntity::find()
-        .filter(if IS_END {
-            GameColumn::Uuid
-                .eq(*game_uuid)
-                .and(GameColumn::EndedAt.is_not_null())
-        } else {
-            GameColumn::Uuid
-                .eq(*game_uuid)
-                .and(GameColumn::EndedAt.is_null())
-        })
+    let mut query = NotificationEntity::find().filter(NotificationColumn::UserUuid.eq(user_uuid));
+    if unread {
+        query = query.filter(NotificationColumn::ReadAt.is_null());
+    }
+    Ok(query
+        .order_by(NotificationColumn::CreatedAt, Order::Desc)
+        .order_by(NotificationColumn::Id, Order::Desc)
+        .offset(page * NOTIFICATIONS_PAGE_SIZE)
+        .limit(NOTIFICATIONS_PAGE_SIZE)
+        .all(conn)
+        .await?)
+}
+
+/// Marks the user's notification as read, marking an already read notification keeps its read date.
+pub async fn mark_notification_read(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+    notification_uuid: Uuid,
+) -> ApiResult<NotificationModel> {
+    let notification = NotificationEntity::find()
+        .filter(NotificationColumn::Uuid.eq(notification_uuid))
+        .filter(NotificationColumn::UserUuid.eq(user_uuid))
         .one(conn)
         .await?
-        .ok_or(ApiError::GameNotFound)
+        .ok_or(ApiError::NotificationNotFound)?;
+    if notification.read_at.is_some() {
+        return Ok(notification);
+    }
+
+    let mut notification = notification.into_active_model();
+    notification.read_at = Set(Some(chrono::Utc::now().naive_utc()));
+    Ok(notification.update(conn).await?)
+}
+
+/// Marks all the user's unread notifications as read, returns the number of the marked notifications.
+pub async fn mark_all_notifications_read(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<u64> {
+    Ok(NotificationEntity::update_many()
+        .col_expr(
+            NotificationColumn::ReadAt,
+            Expr::value(chrono::Utc::now().naive_utc()),
+        )
+        .filter(NotificationColumn::UserUuid.eq(user_uuid))
+        .filter(NotificationColumn::ReadAt.is_null())
+        .exec(conn)
+        .await?
+        .rows_affected)
+}
+
+/// Deletes the user's notifications.
+pub async fn delete_user_notifications(
+    conn: &impl ConnectionTrait,
+    user_uuid: Uuid,
+) -> ApiResult<()> {
+    NotificationEntity::delete_many()
+        .filter(NotificationColumn::UserUuid.eq(user_uuid))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Registers a new webhook of the owner, the payloads are signed with the secret.
+pub async fn create_webhook(
+    conn: &sea_orm::DatabaseConnection,
+    owner_uuid: Uuid,
+    new_webhook: &NewWebhookSchema,
+    secret: String,
+) -> ApiResult<WebhookModel> {
+    log::info!("Creating a webhook for the owner {owner_uuid}");
+
+    let mut events: Vec<_> = WebhookEvent::ALL
+        .into_iter()
+        .filter(|event| new_webhook.events.contains(event))
+        .map(|event| event.to_string())
+        .collect();
+    events.dedup();
+    Ok(WebhookActiveModel {
+        uuid: Set(Uuid::new_v4()),
+        owner_uuid: Set(owner_uuid),
+        url: Set(new_webhook.url.trim().to_owned()),
+        secret: Set(secret),
+        events: Set(events.join(",")),
+        created_at: Set(chrono::Utc::now().naive_utc()),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await?)
+}
+
+/// Returns all the webhooks, the oldest first.
+pub async fn get_webhooks(conn: &sea_orm::DatabaseConnection) -> ApiResult<Vec<WebhookModel>> {
+    Ok(WebhookEntity::find()
+        .order_by(WebhookColumn::CreatedAt, Order::Asc)
+        .order_by(WebhookColumn::Id, Order::Asc)
+        .all(conn)
+        .await?)
+}
+
+/// Returns the webhook of the uuid.
+pub async fn get_webhook(
+    conn: &sea_orm::DatabaseConnection,
+    webhook_uuid: Uuid,
+) -> ApiResult<WebhookModel> {
+    WebhookEntity::find()
+        .filter(WebhookColumn::Uuid.eq(webhook_uuid))
+        .one(conn)
+        .await?
+        .ok_or(ApiError::WebhookNotFound)
+}
+
+/// Returns the webhooks that receive the event.
+pub async fn get_event_webhooks(
+    conn: &sea_orm::DatabaseConnection,
+    event: WebhookEvent,
+) -> ApiResult<Vec<WebhookModel>> {
+    Ok(WebhookEntity::find()
+        .filter(WebhookColumn::Events.contains(event.to_string()))
+        .all(conn)
+        .await?)
+}
+
+/// Deletes the webhook with its deliveries.
+pub async fn delete_webhook(
+    conn: &sea_orm::DatabaseConnection,
+    webhook_uuid: Uuid,
+) -> ApiResult<()> {
+    log::info!("Deleting the webhook {webhook_uuid}");
+
+    let webhook = get_webhook(conn, webhook_uuid).await?;
+    let txn = conn.begin().await?;
+    WebhookDeliveryEntity::delete_many()
+        .filter(WebhookDeliveryColumn::WebhookUuid.eq(webhook.uuid))
+        .exec(&txn)
+        .await?;
+    WebhookEntity::delete_many()
+        .filter(WebhookColumn::Uuid.eq(webhook.uuid))
+        .exec(&txn)
+        .await?;
+    txn.commit().await?;
+    Ok(())
+}
+
+/// Creates the delivery of the payload to the webhook, the delivery uuid is the payload id.
+pub async fn create_webhook_delivery(
+    conn: &sea_orm::DatabaseConnection,
+    webhook_uuid: Uuid,
+    payload: &WebhookPayload,
+) -> ApiResult<WebhookDeliveryModel> {
+    Ok(WebhookDeliveryActiveModel {
+        uuid: Set(payload.id),
+        webhook_uuid: Set(webhook_uuid),
+        event: Set(payload.event.to_string()),
+        payload: Set(serde_json::to_string(payload)?),
+        attempts: Set(0),
+        status_code: Set(None),
+        error: Set(None),
+        delivered_at: Set(None),
+        created_at: Set(payload.created_at),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await?)
+}
+
+/// Records a delivery attempt with the response status code or the error, the delivery is delivered if the
+/// status code is `2xx`.
+pub async fn record_webhook_attempt(
+    conn: &sea_orm::DatabaseConnection,
+    delivery: WebhookDeliveryModel,
+    status_code: Option<u16>,
+    error: Option<String>,
+) -> ApiResult<WebhookDeliveryModel> {
+    let attempts = delivery.attempts + 1;
+    let mut delivery = delivery.into_active_model();
+    delivery.attempts = Set(attempts);
+    delivery.status_code = Set(status_code.map(|status_code| status_code as i16));
+    delivery.error = Set(error);
+    if status_code.is_some_and(|status_code| (200..300).contains(&status_code)) {
+        delivery.delivered_at = Set(Some(chrono::Utc::now().naive_utc()));
+    }
+    Ok(delivery.update(conn).await?)
+}
+
+/// Returns the webhook deliveries (newest first).
+pub async fn get_webhook_deliveries(
+    conn: &sea_orm::DatabaseConnection,
+    webhook_uuid: Uuid,
+    page: u64,
+) -> ApiResult<Vec<WebhookDeliveryModel>> {
+    Ok(WebhookDeliveryEntity::find()
+        .filter(WebhookDeliveryColumn::WebhookUuid.eq(webhook_uuid))
+        .order_by(WebhookDeliveryColumn::CreatedAt, Order::Desc)
+        .order_by(WebhookDeliveryColumn::Id, Order::Desc)
+        .offset(page * WEBHOOK_DELIVERIES_PAGE_SIZE)
+        .limit(WEBHOOK_DELIVERIES_PAGE_SIZE)
+        .all(conn)
+        .await?)
 }
 
 /// Returns lastest 10 games from the database.
@@ -264,6 +2764,138 @@ pub async fn get_lastest_games(conn: &sea_orm::DatabaseConnection) -> ApiResult<
         .await?)
 }
 
+/// Returns the recently ended games, sorted by the end date (newest first).
+pub async fn get_recent_games(
+    conn: &sea_orm::DatabaseConnection,
+    page: u64,
+) -> ApiResult<Vec<GameModel>> {
+    log::info!("Getting the recent games");
+
+    Ok(GameEntity::find()
+        .filter(GameColumn::EndedAt.is_not_null())
+        .order_by(GameColumn::EndedAt, Order::Desc)
+        .order_by(GameColumn::Id, Order::Desc)
+        .offset(page * RECENT_GAMES_PAGE_SIZE)
+        .limit(RECENT_GAMES_PAGE_SIZE)
+        .all(conn)
+        .await?)
+}
+
+/// Returns the user's ended games (newest first), filtered by the opponent, the result and the end date range if
+/// they are provided. The page starts after the `before` game, the last game of the previous page.
+pub async fn get_user_games(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+    opponent: Option<Uuid>,
+    result: Option<GameResult>,
+    from: Option<chrono::NaiveDate>,
+    to: Option<chrono::NaiveDate>,
+    before: Option<Uuid>,
+) -> ApiResult<Vec<GameModel>> {
+    log::info!("Getting the games of user {user_uuid}");
+
+    if matches!((from, to), (Some(from), Some(to)) if from > to) {
+        return Err(ApiError::InvalidDateRange);
+    }
+
+    let mut query = GameEntity::find()
+        .filter(GameColumn::EndedAt.is_not_null())
+        .filter(
+            Condition::any()
+                .add(GameColumn::XPlayer.eq(user_uuid))
+                .add(GameColumn::OPlayer.eq(user_uuid)),
+        );
+    if let Some(opponent) = opponent {
+        query = query.filter(
+            Condition::any()
+                .add(GameColumn::XPlayer.eq(opponent))
+                .add(GameColumn::OPlayer.eq(opponent)),
+        );
+    }
+    query = match result {
+        Some(GameResult::Win) => query.filter(GameColumn::Winner.eq(user_uuid)),
+        Some(GameResult::Loss) => query.filter(
+            GameColumn::Winner
+                .is_not_null()
+                .and(GameColumn::Winner.ne(user_uuid)),
+        ),
+        Some(GameResult::Draw) => query.filter(GameColumn::Winner.is_null()),
+        None => query,
+    };
+    if let Some(from) = from {
+        query = query.filter(GameColumn::EndedAt.gte(from.and_time(chrono::NaiveTime::MIN)));
+    }
+    if let Some(to) = to {
+        query = query.filter(
+            GameColumn::EndedAt.lt((to + Duration::days(1)).and_time(chrono::NaiveTime::MIN)),
+        );
+    }
+    if let Some(before) = before {
+        let before = get_game::<true>(conn, &before).await?;
+        let ended_at = before.ended_at.expect("The game is ended");
+        query = query.filter(
+            Condition::any().add(GameColumn::EndedAt.lt(ended_at)).add(
+                GameColumn::EndedAt
+                    .eq(ended_at)
+                    .and(GameColumn::Id.lt(before.id)),
+            ),
+        );
+    }
+
+    Ok(query
+        .order_by(GameColumn::EndedAt, Order::Desc)
+        .order_by(GameColumn::Id, Order::Desc)
+        .limit(USER_GAMES_PAGE_SIZE)
+        .all(conn)
+        .await?)
+}
+
+/// Search for users by their usernames, and their display names if `with_names` is `true`.
+///
+/// The users are ranked by the relevance, the exact username first, then the username prefix, then the rest.
+/// The `query` must be normalized (lowercase).
+pub async fn search_users(
+    conn: &sea_orm::DatabaseConnection,
+    query: &str,
+    with_names: bool,
+    page: u64,
+) -> ApiResult<Vec<UserModel>> {
+    log::info!("Searching for users");
+
+    let escaped = query
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    let contains = format!("%{escaped}%");
+
+    let mut condition = Condition::any().add(Expr::cust_with_values(
+        "LOWER(username) LIKE $1",
+        [contains.clone()],
+    ));
+    if with_names {
+        condition = condition.add(Expr::cust_with_values(
+            "LOWER(first_name || ' ' || COALESCE(last_name, '')) LIKE $1",
+            [contains],
+        ));
+    }
+
+    Ok(UserEntity::find()
+        .filter(condition)
+        .order_by(
+            Expr::cust_with_values(
+                "CASE WHEN LOWER(username) = $1 THEN 0 WHEN LOWER(username) LIKE $2 THEN 1 ELSE 2 END",
+                [query.to_owned(), format!("{escaped}%")],
+            ),
+            Order::Asc,
+        )
+        .order_by(Expr::cust("LENGTH(username)"), Order::Asc)
+        .order_by(UserColumn::Username, Order::Asc)
+        .offset(page * SEARCH_PAGE_SIZE)
+        .limit(SEARCH_PAGE_SIZE)
+        .all(conn)
+        .await?)
+}
+
 /// Returns online games from the database.
 pub async fn get_online_games(conn: &sea_orm::DatabaseConnection) -> ApiResult<Vec<GameModel>> {
     log::info!("Getting online games");
@@ -284,13 +2916,155 @@ pub(crate) async fn reset_password(
     log::info!("Resetting password for user: {}", user.username);
     let mut user = user.into_active_model();
 
-    let password_hash = bcrypt::hash(new_password, 4)?;
+    let password_hash = utils::PasswordHasher::current().hash(new_password)?;
     user.password_hash = Set(password_hash);
     user.update(conn).await?;
 
     Ok(())
 }
 
+/// Rehashes the user password with the current hasher, returns the updated user.
+pub async fn rehash_password(
+    conn: &sea_orm::DatabaseConnection,
+    mut user: UserActiveModel,
+    password: &str,
+) -> ApiResult<UserActiveModel> {
+    log::info!("Rehashing password for user: {}", user.username.as_ref());
+    user.password_hash = Set(utils::PasswordHasher::current().hash(password)?);
+    Ok(user.save(conn).await?)
+}
+
+/// Sets a new pending TOTP secret for the user, the two-factor authentication is enabled after confirming it.
+pub(crate) async fn set_totp_secret(
+    conn: &sea_orm::DatabaseConnection,
+    user: UserModel,
+    secret: String,
+) -> ApiResult<()> {
+    log::info!("Setting a new TOTP secret for user: {}", user.username);
+    let mut user = user.into_active_model();
+
+    user.totp_secret = Set(Some(secret));
+    user.totp_enabled = Set(false);
+    user.totp_last_step = Set(None);
+    user.update(conn).await?;
+
+    Ok(())
+}
+
+/// Enables the user's two-factor authentication, the old recovery codes are replaced with the new ones.
+pub(crate) async fn enable_totp(
+    conn: &sea_orm::DatabaseConnection,
+    user: UserModel,
+    recovery_codes: &[String],
+) -> ApiResult<()> {
+    log::info!(
+        "Enabling the two-factor authentication for user: {}",
+        user.username
+    );
+
+    let user_uuid = user.uuid;
+    let now = chrono::Utc::now().naive_utc();
+    let txn = conn.begin().await?;
+    let mut user = user.into_active_model();
+    user.totp_enabled = Set(true);
+    user.update(&txn).await?;
+    delete_user_recovery_codes(&txn, user_uuid).await?;
+    RecoveryCodeEntity::insert_many(recovery_codes.iter().map(|code| RecoveryCodeActiveModel {
+        user_uuid: Set(user_uuid),
+        code_hash: Set(utils::hash_recovery_code(code)),
+        created_at: Set(now),
+        ..Default::default()
+    }))
+    .exec(&txn)
+    .await?;
+    txn.commit().await?;
+
+    Ok(())
+}
+
+/// Disables the user's two-factor authentication, its secret and recovery codes are deleted.
+pub(crate) async fn disable_totp(
+    conn: &sea_orm::DatabaseConnection,
+    user: UserModel,
+) -> ApiResult<()> {
+    log::info!(
+        "Disabling the two-factor authentication for user: {}",
+        user.username
+    );
+
+    let user_uuid = user.uuid;
+    let txn = conn.begin().await?;
+    let mut user = user.into_active_model();
+    user.totp_secret = Set(None);
+    user.totp_enabled = Set(false);
+    user.totp_last_step = Set(None);
+    user.update(&txn).await?;
+    delete_user_recovery_codes(&txn, user_uuid).await?;
+    txn.commit().await?;
+
+    Ok(())
+}
+
+/// Uses the user's TOTP code, returns `false` if the code is incorrect or its time step is already used, so an
+/// accepted code can't be replayed.
+pub(crate) async fn use_totp_code(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+    secret: &str,
+    code: &str,
+) -> ApiResult<bool> {
+    let Some(step) = utils::verify_totp_code(secret, code, chrono::Utc::now().timestamp()) else {
+        return Ok(false);
+    };
+    let used = UserEntity::update_many()
+        .col_expr(UserColumn::TotpLastStep, Expr::value(step))
+        .filter(UserColumn::Uuid.eq(user_uuid))
+        .filter(
+            Condition::any()
+                .add(UserColumn::TotpLastStep.is_null())
+                .add(UserColumn::TotpLastStep.lt(step)),
+        )
+        .exec(conn)
+        .await?
+        .rows_affected
+        != 0;
+    if !used {
+        log::warn!("An already used TOTP code of user {user_uuid} is presented");
+    }
+    Ok(used)
+}
+
+/// Uses the user's recovery code, returns `false` if the code is incorrect or already used.
+pub(crate) async fn use_recovery_code(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+    code: &str,
+) -> ApiResult<bool> {
+    let used = RecoveryCodeEntity::delete_many()
+        .filter(RecoveryCodeColumn::UserUuid.eq(user_uuid))
+        .filter(RecoveryCodeColumn::CodeHash.eq(utils::hash_recovery_code(code)))
+        .exec(conn)
+        .await?
+        .rows_affected
+        != 0;
+    if used {
+        log::warn!("A recovery code of user {user_uuid} is used");
+    }
+    Ok(used)
+}
+
+/// Deletes the user's recovery codes.
+pub async fn delete_user_recovery_codes(
+    conn: &impl ConnectionTrait,
+    user_uuid: Uuid,
+) -> ApiResult<()> {
+    RecoveryCodeEntity::delete_many()
+        .filter(RecoveryCodeColumn::UserUuid.eq(user_uuid))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
 /// Update the user's profile image path. Returns the path of the new profile image.
 pub(crate) fn update_profile_image_path(
     user_uuid: Uuid,
@@ -334,3 +3108,181 @@ pub async fn latest_player_games(
         .await
         .map_err(ApiError::SeaOrm)
 }
+
+/// Creates a pending data export of the user, the previous exports of the user are deleted. Only one export
+/// can be generated at a time.
+pub async fn create_data_export(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<DataExportModel> {
+    let timeout = chrono::Utc::now().naive_utc() - Duration::minutes(DATA_EXPORT_TIMEOUT_MINUTES);
+    if DataExportEntity::find()
+        .filter(DataExportColumn::UserUuid.eq(user_uuid))
+        .filter(DataExportColumn::Status.eq(DataExportStatus::Pending.to_string()))
+        .filter(DataExportColumn::CreatedAt.gt(timeout))
+        .count(conn)
+        .await?
+        != 0
+    {
+        return Err(ApiError::DataExportInProgress);
+    }
+    log::info!("Creating a data export of user {user_uuid}");
+
+    let txn = conn.begin().await?;
+    delete_user_data_exports(&txn, user_uuid).await?;
+    let export = DataExportActiveModel {
+        uuid: Set(Uuid::new_v4()),
+        user_uuid: Set(user_uuid),
+        status: Set(DataExportStatus::Pending.to_string()),
+        data: Set(None),
+        created_at: Set(chrono::Utc::now().naive_utc()),
+        completed_at: Set(None),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+    txn.commit().await?;
+    Ok(export)
+}
+
+/// Completes the data export with the generated data, the export failed if there is no data.
+pub async fn complete_data_export(
+    conn: &sea_orm::DatabaseConnection,
+    export: DataExportModel,
+    data: Option<String>,
+) -> ApiResult<DataExportModel> {
+    let status = if data.is_some() {
+        DataExportStatus::Ready
+    } else {
+        DataExportStatus::Failed
+    };
+    let mut export = export.into_active_model();
+    export.status = Set(status.to_string());
+    export.data = Set(data);
+    export.completed_at = Set(Some(chrono::Utc::now().naive_utc()));
+    Ok(export.update(conn).await?)
+}
+
+/// Returns the latest data export of the user.
+pub async fn get_data_export(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<DataExportModel> {
+    DataExportEntity::find()
+        .filter(DataExportColumn::UserUuid.eq(user_uuid))
+        .order_by(DataExportColumn::CreatedAt, Order::Desc)
+        .order_by(DataExportColumn::Id, Order::Desc)
+        .one(conn)
+        .await?
+        .ok_or(ApiError::DataExportNotFound)
+}
+
+/// Deletes the data exports of the user.
+pub async fn delete_user_data_exports(
+    conn: &impl ConnectionTrait,
+    user_uuid: Uuid,
+) -> ApiResult<()> {
+    DataExportEntity::delete_many()
+        .filter(DataExportColumn::UserUuid.eq(user_uuid))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Deletes the data exports that are completed [`DATA_EXPORT_TTL_HOURS`] hours ago, and the lost pending ones.
+/// Returns the number of the deleted exports.
+pub async fn prune_expired_data_exports(conn: &impl ConnectionTrait) -> ApiResult<u64> {
+    let expired_before = chrono::Utc::now().naive_utc() - Duration::hours(DATA_EXPORT_TTL_HOURS);
+    Ok(DataExportEntity::delete_many()
+        .filter(
+            Condition::any()
+                .add(DataExportColumn::CompletedAt.lt(expired_before))
+                .add(
+                    Condition::all()
+                        .add(DataExportColumn::CompletedAt.is_null())
+                        .add(DataExportColumn::CreatedAt.lt(expired_before)),
+                ),
+        )
+        .exec(conn)
+        .await?
+        .rows_affected)
+}
+
+/// Returns everything the server stores about the user, see [`UserDataExportSchema`].
+pub async fn export_user_data(
+    conn: &sea_orm::DatabaseConnection,
+    user_uuid: Uuid,
+) -> ApiResult<UserDataExportSchema> {
+    log::info!("Exporting the data of user {user_uuid}");
+
+    let user = get_user(conn, user_uuid).await?;
+    let two_factor_enabled = *user.totp_enabled.as_ref();
+    let banned_at = *user.banned_at.as_ref();
+    let profile = UserSchema::from_active_model(conn, user).await?;
+
+    let mut games = Vec::new();
+    for game in GameEntity::find()
+        .filter(
+            Condition::any()
+                .add(GameColumn::XPlayer.eq(user_uuid))
+                .add(GameColumn::OPlayer.eq(user_uuid)),
+        )
+        .order_by(GameColumn::CreatedAt, Order::Desc)
+        .order_by(GameColumn::Id, Order::Desc)
+        .all(conn)
+        .await?
+    {
+        let saved_moves = get_game_moves(conn, game.uuid).await?;
+        let chat_messages = get_chat_messages(conn, game.uuid).await?;
+        games.push(ReplaySchema::from_game(&game, &saved_moves, chat_messages));
+    }
+
+    Ok(UserDataExportSchema {
+        exported_at: chrono::Utc::now().naive_utc(),
+        profile,
+        two_factor_enabled,
+        banned_at,
+        games,
+        achievements: get_user_achievements(conn, user_uuid)
+            .await?
+            .into_iter()
+            .map(AchievementSchema::from)
+            .collect(),
+        rating_changes: get_user_rating_changes(conn, user_uuid)
+            .await?
+            .into_iter()
+            .map(RatingChangeSchema::from)
+            .collect(),
+        friends: accepted_friendships(conn, user_uuid)
+            .await?
+            .into_iter()
+            .map(|(friend, _)| friend)
+            .collect(),
+        blocked_users: BlockEntity::find()
+            .filter(BlockColumn::Blocker.eq(user_uuid))
+            .order_by(BlockColumn::CreatedAt, Order::Desc)
+            .all(conn)
+            .await?
+            .into_iter()
+            .map(|block| block.blocked)
+            .collect(),
+        notifications: NotificationEntity::find()
+            .filter(NotificationColumn::UserUuid.eq(user_uuid))
+            .order_by(NotificationColumn::CreatedAt, Order::Desc)
+            .order_by(NotificationColumn::Id, Order::Desc)
+            .all(conn)
+            .await?
+            .into_iter()
+            .map(NotificationSchema::from)
+            .collect(),
+        audit_events: AuditEventEntity::find()
+            .filter(AuditEventColumn::ActorUuid.eq(user_uuid))
+            .order_by(AuditEventColumn::CreatedAt, Order::Desc)
+            .order_by(AuditEventColumn::Id, Order::Desc)
+            .all(conn)
+            .await?
+            .into_iter()
+            .map(AuditEventSchema::from)
+            .collect(),
+    })
+}
@@ -16,7 +16,11 @@
 
 use salvo::{hyper::StatusCode, oapi::EndpointOutRegister, Scribe};
 
-use crate::schemas::MessageSchema;
+use crate::{
+    i18n::MessageArgs,
+    schemas::{MessageSchema, MoveErrorSchema},
+    utils::PasswordRule,
+};
 
 pub type ApiResult<T> = std::result::Result<T, ApiError>;
 
@@ -29,6 +33,8 @@ pub enum ApiError {
     #[error("{0}")]
     Bcrypt(#[from] bcrypt::BcryptError),
     #[error("{0}")]
+    PasswordHash(#[from] argon2::password_hash::Error),
+    #[error("{0}")]
     Salvo(#[from] salvo::http::StatusError),
 
     #[error("Username `{0}` already exists")]
@@ -51,10 +57,24 @@ pub enum ApiError {
     InvalidLastName,
     #[error("Invalid username: The username must be between 3 and 32 characters and start with a letter and only contain English letters, numbers, and underscores")]
     InvalidUsername,
+    #[error("Too many usernames: At most {0} usernames can be checked at once")]
+    TooManyUsernames(usize),
+    #[error("Invalid search query: The search query must be at least 2 characters")]
+    InvalidSearchQuery,
     #[error("Invalid password: {0}")]
     InvalidPassword(String),
+    #[error("Invalid password: {0}")]
+    PasswordPolicyViolation(PasswordRule),
     #[error("Invalid signin credentials: The username or password is incorrect")]
     InvalidSigninCredentials,
+    #[error("Invalid captcha difficulty: The difficulty must be `easy`, `medium` or `hard`")]
+    InvalidCaptchaDifficulty,
+    #[error(
+        "Invalid captcha length: The length must be between {} and {} characters",
+        crate::utils::MIN_CAPTCHA_LENGTH,
+        crate::utils::MAX_CAPTCHA_LENGTH
+    )]
+    InvalidCaptchaLength,
     #[error("Captcha token is invalid or expired")]
     InvalidCaptchaToken,
     #[error("The captcha answer is incorrect")]
@@ -63,15 +83,260 @@ pub enum ApiError {
     UnProvidedCaptchaToken,
     #[error("Unprovided captcha answer, the captcha answer is required")]
     UnProvidedCaptchaAnswer,
+    #[error("Unprovided captcha response, the `X-Captcha-Response` header is required")]
+    UnProvidedCaptchaResponse,
+    #[error("The captcha response is invalid or expired")]
+    InvalidCaptchaResponse,
     #[error("{0}")]
     InvalidProfileImage(String),
+    #[error("The players are already playing each other in the game `{0}`")]
+    AlreadyPlayingEachOther(uuid::Uuid),
+    #[error("Invalid move: {0}")]
+    InvalidMove(crate::schemas::MoveError),
+    #[error("You are not a player in this game")]
+    NotGamePlayer,
+    #[error("The origin is not allowed to connect to the websocket")]
+    DisallowedOrigin,
+    #[error("Invalid board encoding: {0}")]
+    InvalidBoardEncoding(String),
+    #[error("The token is revoked")]
+    RevokedToken,
+    #[error("The refresh token is already used, all your sessions are revoked, signin again")]
+    ReusedRefreshToken,
+    #[error("Forbidden: You don't have the permission to do this action")]
+    Forbidden,
+    #[error(
+        "Reauthentication required: Confirm your password with `/auth/confirm` to do this action"
+    )]
+    ReauthenticationRequired,
+    #[error("You are not in the search queue")]
+    NotInSearch,
+    #[error("You can't block yourself")]
+    CannotBlockSelf,
+    #[error("You can't send a friend request to yourself")]
+    CannotFriendSelf,
+    #[error("You can't send a friend request to this user")]
+    FriendRequestBlocked,
+    #[error("You are already friends or the friend request is already sent")]
+    AlreadyFriends,
+    #[error("The friend request is not found")]
+    FriendRequestNotFound,
+    #[error("You can only invite your friends")]
+    NotFriend,
+    #[error("Notification not found")]
+    NotificationNotFound,
+    #[error("Invalid warning message: The message must be between 1 and 500 characters")]
+    InvalidWarningMessage,
+    #[error("Invalid game variant: The board size must be between {} and {}, and the win length between {} and the board size", crate::schemas::MIN_BOARD_SIZE, crate::schemas::MAX_BOARD_SIZE, crate::schemas::MIN_BOARD_SIZE)]
+    InvalidGameVariant,
+    #[error("Only the classic 3x3 games can be analyzed")]
+    UnsupportedGameVariant,
+    #[error(
+        "Invalid webhook URL: The URL must be an absolute HTTPS URL of at most 2048 characters"
+    )]
+    InvalidWebhookUrl,
+    #[error("Invalid webhook events: The webhook must receive at least one event")]
+    InvalidWebhookEvents,
+    #[error("Webhook not found")]
+    WebhookNotFound,
+    #[error("A data export is already being generated, wait for it")]
+    DataExportInProgress,
+    #[error("Data export not found, request a new one")]
+    DataExportNotFound,
+    #[error("The data export is not ready, check its status")]
+    DataExportNotReady,
+    #[error("The tokens key can't be rotated: {0}")]
+    JwtKeyRotationFailed(String),
+    #[error("Season not found, see `/seasons` for the seasons")]
+    SeasonNotFound,
+    #[error("Invalid date range: The `from` date must not be after the `to` date, and the range must be at most {} days", crate::db_utils::MAX_ACTIVITY_DAYS)]
+    InvalidDateRange,
     #[error("The old password is same as the new password, the new password must be different from the old password")]
     PasswordNotChanged,
+    #[error("The new username is same as the current username")]
+    UsernameNotChanged,
+    #[error("The invite code is invalid or expired")]
+    InvalidInviteCode,
+    #[error("You can't join your own invite")]
+    CannotJoinOwnInvite,
+    #[error("You must be connected to the XO websocket to start a game")]
+    NotConnected,
+    #[error("The invite host is offline or can't start a game now")]
+    InviteHostUnavailable,
+    #[error("You can't start a game now: `{0}`")]
+    CannotStartGame(String),
+    #[error("Tournament not found")]
+    TournamentNotFound,
+    #[error("Invalid tournament name: The name must be between 3 and 64 characters")]
+    InvalidTournamentName,
+    #[error(
+        "Invalid tournament size: The size must be a power of two between {} and {}",
+        crate::utils::MIN_TOURNAMENT_SIZE,
+        crate::utils::MAX_TOURNAMENT_SIZE
+    )]
+    InvalidTournamentSize,
+    #[error("The tournament registration is closed")]
+    TournamentRegistrationClosed,
+    #[error("You are already registered in the tournament")]
+    AlreadyRegistered,
+    #[error("The two-factor authentication code is required, signin again with the `totp_code`")]
+    TotpRequired,
+    #[error("The two-factor authentication code is incorrect")]
+    InvalidTotpCode,
+    #[error("The two-factor authentication is already enabled")]
+    TotpAlreadyEnabled,
+    #[error("There is no two-factor authentication to confirm, enable it first")]
+    TotpNotPending,
+    #[error("The two-factor authentication is not enabled")]
+    TotpNotEnabled,
+    #[error("The user is banned")]
+    BannedUser,
+    #[error("The user is not a guest, only the guest users can be upgraded")]
+    NotGuest,
+    #[error("Invalid audit action: The action is unknown, see the `AuditAction` schema")]
+    InvalidAuditAction,
+    #[error("Invalid game result: The result must be `win`, `loss` or `draw`")]
+    InvalidGameResult,
+    #[error("Too many requests, please try again after {0} seconds")]
+    TooManyRequests(u64),
 
     #[error("Internal server error")]
     InternalServer,
 }
 
+impl ApiError {
+    /// Returns the stable machine-readable code of the error, it doesn't change when the message wording changes.
+    pub fn code(&self) -> String {
+        let code = match self {
+            Self::Salvo(err) => return status_error_code(&err.name),
+            Self::SeaOrm(_) => "database_error",
+            Self::SerdeJson(_) => "deserialization_error",
+            Self::Bcrypt(_) | Self::PasswordHash(_) => "password_hash_error",
+            Self::UsernameAlreadyExists(_) => "username_already_exists",
+            Self::NotRefreshToken => "not_refresh_token",
+            Self::NotUserJwt => "not_user_jwt",
+            Self::UnActiveRefreshToken => "inactive_refresh_token",
+            Self::ExpiredToken => "expired_token",
+            Self::UserNotFound => "user_not_found",
+            Self::GameNotFound => "game_not_found",
+            Self::InvalidFirstName => "invalid_first_name",
+            Self::InvalidLastName => "invalid_last_name",
+            Self::InvalidUsername => "invalid_username",
+            Self::TooManyUsernames(_) => "too_many_usernames",
+            Self::InvalidSearchQuery => "invalid_search_query",
+            Self::InvalidPassword(_) => "invalid_password",
+            Self::PasswordPolicyViolation(rule) => rule.code(),
+            Self::InvalidSigninCredentials => "invalid_signin_credentials",
+            Self::InvalidCaptchaDifficulty => "invalid_captcha_difficulty",
+            Self::InvalidCaptchaLength => "invalid_captcha_length",
+            Self::InvalidCaptchaToken => "invalid_captcha_token",
+            Self::InvalidCaptchaAnswer => "invalid_captcha_answer",
+            Self::UnProvidedCaptchaToken => "unprovided_captcha_token",
+            Self::UnProvidedCaptchaAnswer => "unprovided_captcha_answer",
+            Self::UnProvidedCaptchaResponse => "unprovided_captcha_response",
+            Self::InvalidCaptchaResponse => "invalid_captcha_response",
+            Self::InvalidProfileImage(_) => "invalid_profile_image",
+            Self::AlreadyPlayingEachOther(_) => "already_playing_each_other",
+            Self::InvalidMove(_) => "invalid_move",
+            Self::NotGamePlayer => "not_game_player",
+            Self::DisallowedOrigin => "disallowed_origin",
+            Self::InvalidBoardEncoding(_) => "invalid_board_encoding",
+            Self::RevokedToken => "revoked_token",
+            Self::ReusedRefreshToken => "reused_refresh_token",
+            Self::Forbidden => "forbidden",
+            Self::ReauthenticationRequired => "reauthentication_required",
+            Self::NotInSearch => "not_in_search",
+            Self::CannotBlockSelf => "cannot_block_self",
+            Self::CannotFriendSelf => "cannot_friend_self",
+            Self::FriendRequestBlocked => "friend_request_blocked",
+            Self::AlreadyFriends => "already_friends",
+            Self::FriendRequestNotFound => "friend_request_not_found",
+            Self::NotFriend => "not_friend",
+            Self::NotificationNotFound => "notification_not_found",
+            Self::InvalidWarningMessage => "invalid_warning_message",
+            Self::InvalidGameVariant => "invalid_game_variant",
+            Self::UnsupportedGameVariant => "unsupported_game_variant",
+            Self::InvalidWebhookUrl => "invalid_webhook_url",
+            Self::InvalidWebhookEvents => "invalid_webhook_events",
+            Self::WebhookNotFound => "webhook_not_found",
+            Self::DataExportInProgress => "data_export_in_progress",
+            Self::DataExportNotFound => "data_export_not_found",
+            Self::DataExportNotReady => "data_export_not_ready",
+            Self::JwtKeyRotationFailed(_) => "jwt_key_rotation_failed",
+            Self::SeasonNotFound => "season_not_found",
+            Self::InvalidDateRange => "invalid_date_range",
+            Self::PasswordNotChanged => "password_not_changed",
+            Self::UsernameNotChanged => "username_not_changed",
+            Self::InvalidInviteCode => "invalid_invite_code",
+            Self::CannotJoinOwnInvite => "cannot_join_own_invite",
+            Self::NotConnected => "not_connected",
+            Self::InviteHostUnavailable => "invite_host_unavailable",
+            Self::CannotStartGame(_) => "cannot_start_game",
+            Self::TournamentNotFound => "tournament_not_found",
+            Self::InvalidTournamentName => "invalid_tournament_name",
+            Self::InvalidTournamentSize => "invalid_tournament_size",
+            Self::TournamentRegistrationClosed => "tournament_registration_closed",
+            Self::AlreadyRegistered => "already_registered",
+            Self::TotpRequired => "totp_required",
+            Self::InvalidTotpCode => "invalid_totp_code",
+            Self::TotpAlreadyEnabled => "totp_already_enabled",
+            Self::TotpNotPending => "totp_not_pending",
+            Self::TotpNotEnabled => "totp_not_enabled",
+            Self::BannedUser => "banned_user",
+            Self::NotGuest => "not_guest",
+            Self::InvalidAuditAction => "invalid_audit_action",
+            Self::InvalidGameResult => "invalid_game_result",
+            Self::TooManyRequests(_) => "too_many_requests",
+            Self::InternalServer => "internal_server_error",
+        };
+        code.to_owned()
+    }
+
+    /// Returns the arguments of the error message, they fill the placeholders of the translated messages.
+    pub fn message_args(&self) -> MessageArgs {
+        let args = match self {
+            Self::SerdeJson(err) => vec![("error", err.to_string())],
+            Self::Bcrypt(err) => vec![("error", err.to_string())],
+            Self::PasswordHash(err) => vec![("error", err.to_string())],
+            Self::UsernameAlreadyExists(username) => vec![("username", username.clone())],
+            Self::TooManyUsernames(max) => vec![("max", max.to_string())],
+            Self::InvalidPassword(reason)
+            | Self::InvalidProfileImage(reason)
+            | Self::InvalidBoardEncoding(reason)
+            | Self::JwtKeyRotationFailed(reason)
+            | Self::CannotStartGame(reason) => vec![("reason", reason.clone())],
+            Self::PasswordPolicyViolation(PasswordRule::Length { min, max }) => {
+                vec![("min", min.to_string()), ("max", max.to_string())]
+            }
+            Self::InvalidCaptchaLength => vec![
+                ("min", crate::utils::MIN_CAPTCHA_LENGTH.to_string()),
+                ("max", crate::utils::MAX_CAPTCHA_LENGTH.to_string()),
+            ],
+            Self::AlreadyPlayingEachOther(game) => vec![("game", game.to_string())],
+            Self::InvalidGameVariant => vec![
+                ("min", crate::schemas::MIN_BOARD_SIZE.to_string()),
+                ("max", crate::schemas::MAX_BOARD_SIZE.to_string()),
+            ],
+            Self::InvalidWebhookUrl => {
+                vec![("max", crate::utils::MAX_WEBHOOK_URL_LENGTH.to_string())]
+            }
+            Self::InvalidDateRange => vec![("max", crate::db_utils::MAX_ACTIVITY_DAYS.to_string())],
+            Self::InvalidTournamentSize => vec![
+                ("min", crate::utils::MIN_TOURNAMENT_SIZE.to_string()),
+                ("max", crate::utils::MAX_TOURNAMENT_SIZE.to_string()),
+            ],
+            Self::TooManyRequests(retry_after) => vec![("retry_after", retry_after.to_string())],
+            _ => Vec::new(),
+        };
+        MessageArgs(args)
+    }
+}
+
+/// Returns the error code of the status error name, e.g. `Bad Request` is `bad_request`.
+pub(crate) fn status_error_code(name: &str) -> String {
+    name.to_lowercase().replace([' ', '-'], "_")
+}
+
 impl EndpointOutRegister for ApiError {
     fn register(_: &mut salvo::oapi::Components, _: &mut salvo::oapi::Operation) {}
 }
@@ -79,13 +344,14 @@ impl EndpointOutRegister for ApiError {
 impl Scribe for ApiError {
     fn render(self, res: &mut salvo::prelude::Response) {
         log::error!("Error: {self}");
+        res.extensions.insert(self.message_args());
 
         match &self {
             ApiError::SeaOrm(_) => {
                 res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
                 crate::api::write_json_body(
                     res,
-                    MessageSchema::new("Internal server error".to_owned()),
+                    MessageSchema::new("Internal server error".to_owned()).with_code(self.code()),
                 );
             }
             ApiError::SerdeJson(err) => {
@@ -93,21 +359,31 @@ impl Scribe for ApiError {
                 res.status_code(StatusCode::BAD_REQUEST);
                 crate::api::write_json_body(
                     res,
-                    MessageSchema::new(format!("Deserialization error: {err}")),
+                    MessageSchema::new(format!("Deserialization error: {err}"))
+                        .with_code(self.code()),
                 );
             }
             ApiError::Bcrypt(err) => {
                 res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
                 crate::api::write_json_body(
                     res,
-                    MessageSchema::new(format!("Internal server error: {err}")),
+                    MessageSchema::new(format!("Internal server error: {err}"))
+                        .with_code(self.code()),
+                );
+            }
+            ApiError::PasswordHash(err) => {
+                res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                crate::api::write_json_body(
+                    res,
+                    MessageSchema::new(format!("Internal server error: {err}"))
+                        .with_code(self.code()),
                 );
             }
             ApiError::InternalServer => {
                 res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
                 crate::api::write_json_body(
                     res,
-                    MessageSchema::new("Internal server error".to_owned()),
+                    MessageSchema::new("Internal server error".to_owned()).with_code(self.code()),
                 );
             }
             ApiError::UsernameAlreadyExists(_)
@@ -116,32 +392,125 @@ impl Scribe for ApiError {
             | ApiError::InvalidFirstName
             | ApiError::InvalidLastName
             | ApiError::InvalidUsername
+            | ApiError::TooManyUsernames(_)
+            | ApiError::InvalidSearchQuery
+            | ApiError::InvalidBoardEncoding(_)
             | ApiError::InvalidPassword(_)
+            | ApiError::PasswordPolicyViolation(_)
             | ApiError::UnProvidedCaptchaToken
             | ApiError::UnProvidedCaptchaAnswer
+            | ApiError::UnProvidedCaptchaResponse
+            | ApiError::InvalidCaptchaDifficulty
+            | ApiError::InvalidCaptchaLength
             | ApiError::PasswordNotChanged
+            | ApiError::UsernameNotChanged
+            | ApiError::CannotBlockSelf
+            | ApiError::CannotFriendSelf
+            | ApiError::CannotJoinOwnInvite
+            | ApiError::InvalidTournamentName
+            | ApiError::InvalidTournamentSize
+            | ApiError::InvalidDateRange
+            | ApiError::InvalidAuditAction
+            | ApiError::InvalidGameResult
+            | ApiError::InvalidWarningMessage
+            | ApiError::InvalidGameVariant
+            | ApiError::UnsupportedGameVariant
+            | ApiError::InvalidWebhookUrl
+            | ApiError::InvalidWebhookEvents
             | ApiError::InvalidProfileImage(_) => {
                 res.status_code(StatusCode::BAD_REQUEST);
-                crate::api::write_json_body(res, MessageSchema::new(self.to_string()));
+                crate::api::write_json_body(
+                    res,
+                    MessageSchema::new(self.to_string()).with_code(self.code()),
+                );
             }
-            ApiError::UserNotFound | ApiError::GameNotFound => {
+            ApiError::UserNotFound
+            | ApiError::GameNotFound
+            | ApiError::InvalidInviteCode
+            | ApiError::FriendRequestNotFound
+            | ApiError::NotificationNotFound
+            | ApiError::WebhookNotFound
+            | ApiError::DataExportNotFound
+            | ApiError::SeasonNotFound
+            | ApiError::TournamentNotFound => {
                 res.status_code(StatusCode::NOT_FOUND);
-                crate::api::write_json_body(res, MessageSchema::new(self.to_string()));
+                crate::api::write_json_body(
+                    res,
+                    MessageSchema::new(self.to_string()).with_code(self.code()),
+                );
+            }
+            ApiError::AlreadyPlayingEachOther(_)
+            | ApiError::NotInSearch
+            | ApiError::NotConnected
+            | ApiError::InviteHostUnavailable
+            | ApiError::CannotStartGame(_)
+            | ApiError::TournamentRegistrationClosed
+            | ApiError::TotpAlreadyEnabled
+            | ApiError::TotpNotPending
+            | ApiError::TotpNotEnabled
+            | ApiError::NotGuest
+            | ApiError::AlreadyFriends
+            | ApiError::DataExportInProgress
+            | ApiError::DataExportNotReady
+            | ApiError::JwtKeyRotationFailed(_)
+            | ApiError::AlreadyRegistered => {
+                res.status_code(StatusCode::CONFLICT);
+                crate::api::write_json_body(
+                    res,
+                    MessageSchema::new(self.to_string()).with_code(self.code()),
+                );
             }
-            ApiError::ExpiredToken => {
+            ApiError::InvalidMove(err) => {
+                res.status_code(StatusCode::CONFLICT);
+                crate::api::write_json_body(res, MoveErrorSchema::new(self.to_string(), *err));
+            }
+            ApiError::ExpiredToken | ApiError::RevokedToken | ApiError::ReusedRefreshToken => {
                 res.status_code(StatusCode::UNAUTHORIZED);
-                crate::api::write_json_body(res, MessageSchema::new(self.to_string()));
+                crate::api::write_json_body(
+                    res,
+                    MessageSchema::new(self.to_string()).with_code(self.code()),
+                );
             }
             ApiError::UnActiveRefreshToken
             | ApiError::InvalidSigninCredentials
+            | ApiError::TotpRequired
+            | ApiError::InvalidTotpCode
+            | ApiError::BannedUser
+            | ApiError::ReauthenticationRequired
+            | ApiError::Forbidden
+            | ApiError::NotGamePlayer
+            | ApiError::FriendRequestBlocked
+            | ApiError::NotFriend
+            | ApiError::DisallowedOrigin
             | ApiError::InvalidCaptchaAnswer
+            | ApiError::InvalidCaptchaResponse
             | ApiError::InvalidCaptchaToken => {
                 res.status_code(StatusCode::FORBIDDEN);
-                crate::api::write_json_body(res, MessageSchema::new(self.to_string()));
+                crate::api::write_json_body(
+                    res,
+                    MessageSchema::new(self.to_string()).with_code(self.code()),
+                );
+            }
+            ApiError::TooManyRequests(retry_after) => {
+                res.status_code(StatusCode::TOO_MANY_REQUESTS);
+                res.headers_mut().insert(
+                    salvo::http::header::RETRY_AFTER,
+                    retry_after
+                        .to_string()
+                        .parse()
+                        .expect("Is valid header value"),
+                );
+                crate::api::write_json_body(
+                    res,
+                    MessageSchema::new(self.to_string()).with_code(self.code()),
+                );
             }
             ApiError::Salvo(err) => {
                 res.status_code(err.code);
-                crate::api::write_json_body(res, MessageSchema::new(self.to_string()));
+                crate::api::write_json_body(
+                    res,
+                    MessageSchema::new(self.to_string()).with_code(self.code()),
+                );
             }
         }
     }
@@ -0,0 +1,103 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The localization of the API messages. The error messages are translated to the language of the
+//! `Accept-Language` header, the English messages (the [`ApiError`](crate::errors::ApiError) messages) are the
+//! fallback of the untranslated messages.
+
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+
+/// The default language, the messages are written in it.
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// The translations bundles, the language and its TOML bundle. The bundle keys are the error codes.
+const BUNDLES: &[(&str, &str)] = &[("ar", include_str!("../locales/ar.toml"))];
+
+static TRANSLATIONS: Lazy<HashMap<&'static str, HashMap<String, String>>> = Lazy::new(|| {
+    BUNDLES
+        .iter()
+        .map(|(language, bundle)| {
+            let bundle = bundle
+                .parse::<toml_edit::Document>()
+                .unwrap_or_else(|err| panic!("The `{language}` bundle is invalid: {err}"));
+            let messages = bundle
+                .iter()
+                .map(|(code, message)| {
+                    let message = message.as_str().unwrap_or_else(|| {
+                        panic!("The `{code}` message of the `{language}` bundle must be a string")
+                    });
+                    (code.to_owned(), message.to_owned())
+                })
+                .collect();
+            (*language, messages)
+        })
+        .collect()
+});
+
+/// The arguments of the response message, they replace the `{name}` placeholders of the translated message.
+#[derive(Debug, Clone, Default)]
+pub struct MessageArgs(pub Vec<(&'static str, String)>);
+
+/// Returns the supported languages, the default language first.
+pub fn languages() -> impl Iterator<Item = &'static str> {
+    std::iter::once(DEFAULT_LANGUAGE).chain(BUNDLES.iter().map(|(language, _)| *language))
+}
+
+/// Returns the supported language that the `Accept-Language` header value prefers, the default language if it
+/// prefers none of them. The languages are matched by their primary subtag, so `ar-SA` is `ar`.
+pub fn negotiate_language(accept_language: &str) -> &'static str {
+    let mut ranges: Vec<(&str, f32)> = accept_language
+        .split(',')
+        .filter_map(|range| {
+            let mut params = range.split(';');
+            let tag = params.next()?.trim();
+            let quality = match params.find_map(|param| param.trim().strip_prefix("q=")) {
+                Some(quality) => quality.trim().parse().ok()?,
+                None => 1.0,
+            };
+            (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+    // The sort is stable, the ranges with the same quality keep their order
+    ranges.sort_by(|(_, first), (_, second)| second.total_cmp(first));
+
+    ranges
+        .into_iter()
+        .find_map(|(tag, _)| {
+            let primary = tag.split('-').next()?;
+            languages().find(|language| language.eq_ignore_ascii_case(primary))
+        })
+        .unwrap_or(DEFAULT_LANGUAGE)
+}
+
+/// Returns the message of the code translated to the language, with the placeholders replaced by the arguments.
+/// `None` if the message is not translated or one of its placeholders has no argument.
+pub fn translate(language: &str, code: &str, args: &MessageArgs) -> Option<String> {
+    let mut template = TRANSLATIONS.get(language)?.get(code)?.as_str();
+    let mut message = String::with_capacity(template.len());
+    while let Some(start) = template.find('{') {
+        let end = start + template[start..].find('}')?;
+        let name = &template[start + 1..end];
+        let (_, value) = args.0.iter().find(|(arg, _)| *arg == name)?;
+        message.push_str(&template[..start]);
+        message.push_str(value);
+        template = &template[end + 1..];
+    }
+    message.push_str(template);
+    Some(message)
+}
@@ -19,7 +19,10 @@ use base64::engine::GeneralPurpose;
 pub mod api;
 pub mod db_utils;
 pub mod errors;
+pub mod i18n;
+pub mod logging;
 pub mod schemas;
+pub mod startup;
 pub mod utils;
 
 pub const BASE_64_ENGINE: GeneralPurpose = GeneralPurpose::new(
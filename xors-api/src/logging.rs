@@ -0,0 +1,179 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::env;
+
+use log::{Log, Metadata, Record};
+
+/// The replacement of the redacted values.
+pub const REDACTED: &str = "[redacted]";
+
+/// The sensitive fields, their values will never be written to the logs. (Case insensitive)
+const SENSITIVE_KEYS: &[&str] = &[
+    "password",
+    "captcha_answer",
+    "captcha-answer",
+    "totp_code",
+    "authorization",
+    "token",
+    "jwt",
+];
+
+/// A logger that redacts the sensitive values before passing the record to the inner logger.
+///
+/// Redacted values:
+/// - The values of the [sensitive fields](SENSITIVE_KEYS), e.g. `"password": "..."`, `password: "..."` or `password=...`.
+/// - The bearer tokens, e.g. `Bearer ...`.
+#[derive(Debug)]
+pub struct RedactingLogger<L> {
+    inner: L,
+}
+
+impl<L: Log> RedactingLogger<L> {
+    /// Create a new redacting logger.
+    pub fn new(inner: L) -> Self {
+        Self { inner }
+    }
+}
+
+impl<L: Log> Log for RedactingLogger<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = redact(&record.args().to_string());
+
+        self.inner.log(
+            &Record::builder()
+                .args(format_args!("{message}"))
+                .metadata(record.metadata().clone())
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line())
+                .build(),
+        );
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+/// Returns the message with the sensitive values replaced with [`REDACTED`].
+pub fn redact(message: &str) -> String {
+    let lowercase = message.to_ascii_lowercase();
+    let mut redacted = String::with_capacity(message.len());
+    let mut index = 0;
+
+    while index < message.len() {
+        let rest = &lowercase[index..];
+        if rest.starts_with("bearer ") {
+            let token_end = index
+                + 7
+                + message[index + 7..]
+                    .find(|c: char| !is_token_char(c))
+                    .unwrap_or(message.len() - index - 7);
+            if token_end > index + 7 {
+                redacted.push_str(&message[index..index + 7]);
+                redacted.push_str(REDACTED);
+                index = token_end;
+                continue;
+            }
+        }
+        if let Some(key) = SENSITIVE_KEYS.iter().find(|key| rest.starts_with(*key)) {
+            if let Some((value_start, value_end)) = find_value(message, index + key.len()) {
+                redacted.push_str(&message[index..value_start]);
+                redacted.push_str(REDACTED);
+                index = value_end;
+                continue;
+            }
+        }
+
+        let c = message[index..]
+            .chars()
+            .next()
+            .expect("The index is a char boundary");
+        redacted.push(c);
+        index += c.len_utf8();
+    }
+    redacted
+}
+
+/// Returns whether if the char can be a part of a bearer token.
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '=' | '+' | '/')
+}
+
+/// Find the value of the key that ends at `key_end`, the key must be followed by `:` or `=`.
+/// The value must be quoted, or unquoted after `=` (e.g. `password=...`).
+/// Returns the start and the end of the value, the quotes of a quoted value are not included.
+fn find_value(message: &str, key_end: usize) -> Option<(usize, usize)> {
+    let bytes = message.as_bytes();
+    let skip_spaces = |mut index: usize| {
+        while bytes.get(index).is_some_and(u8::is_ascii_whitespace) {
+            index += 1;
+        }
+        index
+    };
+
+    let mut index = key_end;
+    if bytes.get(index) == Some(&b'"') {
+        index += 1;
+    }
+    index = skip_spaces(index);
+    let separator = *bytes.get(index)?;
+    if !matches!(separator, b':' | b'=') {
+        return None;
+    }
+    index = skip_spaces(index + 1);
+
+    let (value_start, value_end) = if bytes.get(index) == Some(&b'"') {
+        let value_start = index + 1;
+        let mut value_end = value_start;
+        while value_end < bytes.len() && bytes[value_end] != b'"' {
+            // Skip the escaped chars
+            value_end += if bytes[value_end] == b'\\' { 2 } else { 1 };
+        }
+        (value_start, value_end.min(bytes.len()))
+    } else if separator == b'=' {
+        let value_end = message[index..]
+            .find(|c: char| c.is_whitespace() || matches!(c, ',' | '}' | ')' | ']' | '&' | ';'))
+            .map_or(message.len(), |end| index + end);
+        (index, value_end)
+    } else {
+        // Unquoted values are only redacted after `=`, so the messages like `Invalid password: ...` are kept
+        return None;
+    };
+
+    (value_end > value_start).then_some((value_start, value_end))
+}
+
+/// Initialize the logger, it's a redacting [`pretty_env_logger`] configured by the `RUST_LOG` environment variable.
+pub fn init() {
+    let mut builder = pretty_env_logger::formatted_builder();
+    if let Ok(filters) = env::var("RUST_LOG") {
+        builder.parse_filters(&filters);
+    }
+    let logger = builder.build();
+
+    log::set_max_level(logger.filter());
+    log::set_boxed_logger(Box::new(RedactingLogger::new(logger)))
+        .expect("The logger is not initialized before");
+}
@@ -0,0 +1,161 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+
+use entity::prelude::*;
+
+use crate::errors::ApiError;
+
+/// The action of an audit event.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[salvo(schema(symbol = "AuditAction", example = json!(AuditAction::Signin)))]
+pub enum AuditAction {
+    /// The user signed in.
+    Signin,
+    /// A signin with an incorrect password or two-factor authentication code.
+    FailedSignin,
+    /// The user refreshed its tokens.
+    TokenRefresh,
+    /// The user changed its password.
+    PasswordChange,
+    /// The user changed its username.
+    UsernameChange,
+    /// The user deleted its account.
+    AccountDeletion,
+    /// An admin changed the role of a user.
+    RoleChange,
+    /// A moderator banned a user.
+    UserBan,
+    /// A moderator unbanned a user.
+    UserUnban,
+    /// A moderator warned a user.
+    UserWarning,
+    /// A moderator ended a game.
+    GameEnd,
+    /// An admin deleted the stored captchas.
+    CaptchasDeletion,
+    /// An admin registered a webhook.
+    WebhookCreation,
+    /// An admin deleted a webhook.
+    WebhookDeletion,
+    /// An admin rotated the tokens key.
+    JwtKeyRotation,
+}
+
+/// The audit event schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "AuditEventSchema", example = json!(AuditEventSchema::default())))]
+pub struct AuditEventSchema {
+    /// The event id.
+    pub id: i32,
+    /// The event action.
+    pub action: AuditAction,
+    /// The user that did the action, unknown for the signins of the not existing usernames.
+    pub actor_uuid: Option<uuid::Uuid>,
+    /// The target of the action, the user or the game uuid, the username of the failed signin or the old username.
+    pub target: Option<String>,
+    /// The client IP.
+    pub ip: Option<String>,
+    /// The client user agent.
+    pub user_agent: Option<String>,
+    /// When the event happened.
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl AuditAction {
+    /// All the audit actions.
+    pub const ALL: [Self; 15] = [
+        Self::Signin,
+        Self::FailedSignin,
+        Self::TokenRefresh,
+        Self::PasswordChange,
+        Self::UsernameChange,
+        Self::AccountDeletion,
+        Self::RoleChange,
+        Self::UserBan,
+        Self::UserUnban,
+        Self::UserWarning,
+        Self::GameEnd,
+        Self::CaptchasDeletion,
+        Self::WebhookCreation,
+        Self::WebhookDeletion,
+        Self::JwtKeyRotation,
+    ];
+}
+
+impl ToString for AuditAction {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Signin => "signin".to_owned(),
+            Self::FailedSignin => "failed_signin".to_owned(),
+            Self::TokenRefresh => "token_refresh".to_owned(),
+            Self::PasswordChange => "password_change".to_owned(),
+            Self::UsernameChange => "username_change".to_owned(),
+            Self::AccountDeletion => "account_deletion".to_owned(),
+            Self::RoleChange => "role_change".to_owned(),
+            Self::UserBan => "user_ban".to_owned(),
+            Self::UserUnban => "user_unban".to_owned(),
+            Self::UserWarning => "user_warning".to_owned(),
+            Self::GameEnd => "game_end".to_owned(),
+            Self::CaptchasDeletion => "captchas_deletion".to_owned(),
+            Self::WebhookCreation => "webhook_creation".to_owned(),
+            Self::WebhookDeletion => "webhook_deletion".to_owned(),
+            Self::JwtKeyRotation => "jwt_key_rotation".to_owned(),
+        }
+    }
+}
+
+impl std::str::FromStr for AuditAction {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.to_string() == s)
+            .ok_or(ApiError::InvalidAuditAction)
+    }
+}
+
+impl From<AuditEventModel> for AuditEventSchema {
+    fn from(event: AuditEventModel) -> Self {
+        Self {
+            id: event.id,
+            action: event.action.parse().expect("Is valid audit action"),
+            actor_uuid: event.actor_uuid,
+            target: event.target,
+            ip: event.ip,
+            user_agent: event.user_agent,
+            created_at: event.created_at,
+        }
+    }
+}
+
+impl Default for AuditEventSchema {
+    fn default() -> Self {
+        Self {
+            id: 1,
+            action: AuditAction::Signin,
+            actor_uuid: Some(uuid::Uuid::new_v4()),
+            target: None,
+            ip: Some("127.0.0.1".to_owned()),
+            user_agent: Some("Mozilla/5.0".to_owned()),
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use entity::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::errors::ApiError;
+
+use super::{
+    AchievementSchema, AuditEventSchema, NotificationSchema, RatingChangeSchema, ReplaySchema,
+    UserSchema,
+};
+
+/// The data export status.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[salvo(schema(symbol = "DataExportStatus", example = json!(DataExportStatus::Ready)))]
+pub enum DataExportStatus {
+    /// The export is being generated.
+    Pending,
+    /// The export is generated, download it.
+    Ready,
+    /// The export generation failed, request a new one.
+    Failed,
+}
+
+/// The data export schema, the status of the user's data export.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "DataExportSchema", example = json!(DataExportSchema::default())))]
+pub struct DataExportSchema {
+    /// The export uuid.
+    pub uuid: Uuid,
+    /// The export status.
+    pub status: DataExportStatus,
+    /// When the export is requested.
+    pub created_at: chrono::NaiveDateTime,
+    /// When the export is generated or failed. Null if it's pending.
+    pub completed_at: Option<chrono::NaiveDateTime>,
+    /// When the generated export is deleted. Null if it's not ready.
+    pub expires_at: Option<chrono::NaiveDateTime>,
+}
+
+/// The user's data export, everything the server stores about the user.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "UserDataExportSchema"))]
+pub struct UserDataExportSchema {
+    /// When the data is exported.
+    pub exported_at: chrono::NaiveDateTime,
+    /// The user's profile.
+    pub profile: UserSchema,
+    /// Whether the two-factor authentication is enabled.
+    pub two_factor_enabled: bool,
+    /// When the user is banned. Null if the user is not banned.
+    pub banned_at: Option<chrono::NaiveDateTime>,
+    /// The user's games with their moves and chat messages, the newest first.
+    pub games: Vec<ReplaySchema>,
+    /// The user's achievements.
+    pub achievements: Vec<AchievementSchema>,
+    /// The user's rating changes.
+    pub rating_changes: Vec<RatingChangeSchema>,
+    /// The user's friends uuids.
+    pub friends: Vec<Uuid>,
+    /// The uuids of the users that the user blocked.
+    pub blocked_users: Vec<Uuid>,
+    /// The user's notifications, the newest first.
+    pub notifications: Vec<NotificationSchema>,
+    /// The audit events of the user's actions, the newest first.
+    pub audit_events: Vec<AuditEventSchema>,
+}
+
+impl DataExportStatus {
+    /// All the data export statuses.
+    pub const ALL: [Self; 3] = [Self::Pending, Self::Ready, Self::Failed];
+}
+
+impl ToString for DataExportStatus {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Pending => "pending".to_owned(),
+            Self::Ready => "ready".to_owned(),
+            Self::Failed => "failed".to_owned(),
+        }
+    }
+}
+
+impl std::str::FromStr for DataExportStatus {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|status| status.to_string() == s)
+            .ok_or(ApiError::InternalServer)
+    }
+}
+
+impl From<DataExportModel> for DataExportSchema {
+    fn from(export: DataExportModel) -> Self {
+        let status = export.status.parse().expect("Is valid data export status");
+        Self {
+            uuid: export.uuid,
+            status,
+            created_at: export.created_at,
+            completed_at: export.completed_at,
+            expires_at: export
+                .completed_at
+                .filter(|_| status == DataExportStatus::Ready)
+                .map(|completed_at| {
+                    completed_at + chrono::Duration::hours(crate::db_utils::DATA_EXPORT_TTL_HOURS)
+                }),
+        }
+    }
+}
+
+impl Default for DataExportSchema {
+    fn default() -> Self {
+        let now = chrono::Utc::now().naive_utc();
+        Self {
+            uuid: Uuid::new_v4(),
+            status: DataExportStatus::Ready,
+            created_at: now,
+            completed_at: Some(now),
+            expires_at: Some(now + chrono::Duration::hours(24)),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::UserSchema;
+
+/// The user's presence, it's driven by the user's XO websocket connections.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[salvo(schema(symbol = "Presence", example = json!(Presence::Online)))]
+pub enum Presence {
+    /// The user is not connected to the XO websocket.
+    #[default]
+    Offline,
+    /// The user is connected to the XO websocket, and it's not in a game.
+    Online,
+    /// The user is connected to the XO websocket, and it's playing a game.
+    InGame,
+}
+
+/// The new friend request schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "NewFriendRequestSchema", example = json!(NewFriendRequestSchema::new(Uuid::new_v4()))))]
+pub struct NewFriendRequestSchema {
+    /// The uuid of the user to send the friend request to.
+    pub user_uuid: Uuid,
+}
+
+/// The friend schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "FriendSchema"))]
+pub struct FriendSchema {
+    /// The friend user.
+    pub user: UserSchema,
+    /// The friend's current presence.
+    pub presence: Presence,
+    /// The date that the friend request is accepted in.
+    pub since: chrono::NaiveDateTime,
+}
+
+/// The pending friend request schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "FriendRequestSchema"))]
+pub struct FriendRequestSchema {
+    /// The other user of the request, the sender of the incoming requests and the receiver of the outgoing ones.
+    pub user: UserSchema,
+    /// The date that the request is sent in.
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// The user's pending friend requests schema, the latest first.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "FriendRequestsSchema", example = json!(FriendRequestsSchema::default())))]
+pub struct FriendRequestsSchema {
+    /// The requests sent to the user.
+    pub incoming: Vec<FriendRequestSchema>,
+    /// The requests sent by the user.
+    pub outgoing: Vec<FriendRequestSchema>,
+}
@@ -27,7 +27,7 @@ use crate::{
 use super::*;
 
 /// The game's schema. It's used to return the game's data.
-#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 #[salvo(schema(symbol = "GameSchema", example = json!(GameSchema::default())))]
 pub struct GameSchema {
     /// The game's uuid. It's unique.
@@ -44,6 +44,259 @@ pub struct GameSchema {
     pub won_reason: Option<GameOverReason>,
     /// The game's creation date.
     pub created_at: chrono::NaiveDateTime,
+    /// The game's end date. will be null if the game is in progress.
+    pub ended_at: Option<chrono::NaiveDateTime>,
+    /// The AI opponent difficulty. will be null if the game is between two players.
+    pub bot_difficulty: Option<BotDifficulty>,
+    /// The game variant, the board size and the win length.
+    pub variant: GameVariant,
+}
+
+/// The rejected move schema. It's used to return the reason of rejecting a move.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "MoveErrorSchema", example = json!(MoveErrorSchema::new("The cell is already occupied".to_owned(), MoveError::CellOccupied))))]
+pub struct MoveErrorSchema {
+    /// The error message.
+    pub message: String,
+    /// The stable error code.
+    pub code: MoveError,
+}
+
+/// The encoded board schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "EncodedBoardSchema", example = json!(EncodedBoardSchema::new("AwMBAgABAAAAAAA=".to_owned()))))]
+pub struct EncodedBoardSchema {
+    /// The compact board state, base64 of the board width, height and cells bytes.
+    /// The cell byte is `0` for an empty cell, `1` for X and `2` for O, row by row.
+    pub board: String,
+}
+
+/// The decoded board schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "DecodedBoardSchema", example = json!(DecodedBoardSchema::new(3, 3, vec![Some(XoSymbol::X), Some(XoSymbol::O), None, Some(XoSymbol::X), None, None, None, None, None], Some(XoSymbol::O), None, false))))]
+pub struct DecodedBoardSchema {
+    /// The board width.
+    pub width: u8,
+    /// The board height.
+    pub height: u8,
+    /// The board cells, row by row. Null for an empty cell.
+    pub cells: Vec<Option<XoSymbol>>,
+    /// The symbol that should play the next move. Null if the board is ended.
+    pub turn: Option<XoSymbol>,
+    /// The winner symbol. Null if there is no winner.
+    pub winner: Option<XoSymbol>,
+    /// Whether if the board is ended, someone won or it's a draw.
+    pub is_end: bool,
+}
+
+/// The validate game schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "ValidateGameSchema", example = json!(ValidateGameSchema::new(None, vec![4, 0, 8, 2, 1, 7, 6, 3, 5]))))]
+pub struct ValidateGameSchema {
+    /// The starting compact board state, see [`EncodedBoardSchema`]. Null to start from an empty board.
+    #[serde(default)]
+    pub board: Option<String>,
+    /// The played places in order, between 0 and 8 row by row. The symbols alternate starting from the board turn.
+    pub moves: Vec<u8>,
+}
+
+/// The game validation schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "GameValidationSchema"))]
+pub struct GameValidationSchema {
+    /// Whether if all the moves are legal.
+    pub is_legal: bool,
+    /// The board after the legal moves, before the first illegal move if any.
+    pub board: DecodedBoardSchema,
+    /// The index of the first illegal move in the moves list. Null if all the moves are legal.
+    pub illegal_move: Option<usize>,
+    /// The reason of rejecting the first illegal move. Null if all the moves are legal.
+    pub error: Option<MoveErrorSchema>,
+}
+
+/// The evaluate board schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "EvaluateBoardSchema", example = json!(EvaluateBoardSchema::new("AwMBAgABAAAAAAA=".to_owned(), XoSymbol::O))))]
+pub struct EvaluateBoardSchema {
+    /// The compact board state, see [`EncodedBoardSchema`].
+    pub board: String,
+    /// The symbol to move, must match the board (X always plays first).
+    pub turn: XoSymbol,
+}
+
+/// The game-theoretic value of a position, for the symbol to move.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[salvo(schema(symbol = "BoardValue", example = json!(BoardValue::Win)))]
+pub enum BoardValue {
+    /// The symbol to move can force a win.
+    Win,
+    /// Both symbols can force a draw.
+    Draw,
+    /// The other symbol can force a win.
+    Loss,
+}
+
+/// The board evaluation schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "BoardEvaluationSchema", example = json!(BoardEvaluationSchema::new(XoSymbol::O, BoardValue::Loss))))]
+pub struct BoardEvaluationSchema {
+    /// The symbol to move.
+    pub turn: XoSymbol,
+    /// The position value for the symbol to move, with a perfect play from both symbols.
+    pub value: BoardValue,
+}
+
+impl From<&Board> for DecodedBoardSchema {
+    fn from(board: &Board) -> Self {
+        let winner = [XoSymbol::X, XoSymbol::O]
+            .into_iter()
+            .find(|symbol| board.is_win(symbol));
+        let is_end = board.is_end();
+
+        let size = board.variant().size;
+        Self::new(
+            size,
+            size,
+            board.cells().to_vec(),
+            (!is_end).then(|| board.turn()),
+            winner,
+            is_end,
+        )
+    }
+}
+
+impl GameValidationSchema {
+    /// Replays the moves on the board, stops at the first illegal move.
+    pub(crate) fn replay(mut board: Board, moves: &[u8]) -> Self {
+        for (index, place) in moves.iter().enumerate() {
+            if let Err(err) = board.make_move(*place, board.turn(), None) {
+                return Self::new(
+                    false,
+                    DecodedBoardSchema::from(&board),
+                    Some(index),
+                    Some(MoveErrorSchema::new(err.to_string(), err)),
+                );
+            }
+        }
+        Self::new(true, DecodedBoardSchema::from(&board), None, None)
+    }
+}
+
+impl From<&Board> for BoardEvaluationSchema {
+    fn from(board: &Board) -> Self {
+        let value = match board.perfect_play_outcome() {
+            1 => BoardValue::Win,
+            0 => BoardValue::Draw,
+            _ => BoardValue::Loss,
+        };
+        Self::new(board.turn(), value)
+    }
+}
+
+/// The game's move schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "MoveSchema", example = json!(MoveSchema::new(1, 1, 4, XoSymbol::X, false))))]
+pub struct MoveSchema {
+    /// The move sequence in the game, starting from 1.
+    pub sequence: u32,
+    /// The round of the move.
+    pub round: i16,
+    /// The played place, between 0 and 8.
+    pub place: u32,
+    /// The symbol that played the move.
+    pub symbol: XoSymbol,
+    /// Whether the move is a random opening move played by the server.
+    pub forced: bool,
+}
+
+/// The move annotation, compared with the perfect play.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[salvo(schema(symbol = "MoveAnnotation", example = json!(MoveAnnotation::Optimal)))]
+pub enum MoveAnnotation {
+    /// The move keeps the best outcome the player can force.
+    Optimal,
+    /// The move is not the best, but the opponent can't force a win after it.
+    Neutral,
+    /// The move allows the opponent to force a win.
+    Blunder,
+}
+
+/// The game's move analysis schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "MoveAnalysisSchema", example = json!(MoveAnalysisSchema::new(MoveSchema::new(1, 1, 4, XoSymbol::X, false), MoveAnnotation::Optimal))))]
+pub struct MoveAnalysisSchema {
+    /// The analyzed move.
+    #[serde(flatten)]
+    pub played_move: MoveSchema,
+    /// The move annotation.
+    pub annotation: MoveAnnotation,
+}
+
+/// The game's replay move schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "ReplayMoveSchema", example = json!(ReplayMoveSchema::new(MoveSchema::new(1, 1, 4, XoSymbol::X, false), Some(chrono::Utc::now().naive_utc())))))]
+pub struct ReplayMoveSchema {
+    /// The played move.
+    #[serde(flatten)]
+    pub played_move: MoveSchema,
+    /// When the move is played. Will be null for the random opening moves and the moves of the games
+    /// that are played before saving the moves time.
+    pub played_at: Option<chrono::NaiveDateTime>,
+}
+
+/// The game's replay chat message schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "ReplayChatMessageSchema"))]
+pub struct ReplayChatMessageSchema {
+    /// The uuid of the player that sent the message.
+    pub sender: Uuid,
+    /// The PGP message, encrypted for the other player.
+    pub encrypted_message: String,
+    /// The sender's PGP signature of the message.
+    pub signature: String,
+    /// When the message is sent.
+    pub sent_at: chrono::NaiveDateTime,
+}
+
+/// The game's replay schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "ReplaySchema", example = json!(ReplaySchema::default())))]
+pub struct ReplaySchema {
+    /// The game's uuid.
+    pub uuid: Uuid,
+    /// The X player's uuid.
+    pub x_player: Uuid,
+    /// The O player's uuid.
+    pub o_player: Uuid,
+    /// The game's moves, in order.
+    pub moves: Vec<ReplayMoveSchema>,
+    /// The game's winner. will be null if the game ended with a draw.
+    pub winner: Option<Uuid>,
+    /// The won reason. will be null if the game ended with a draw.
+    pub won_reason: Option<GameOverReason>,
+    /// The game variant, the moves places are the cells index of its board row by row.
+    pub variant: GameVariant,
+    /// The compact text of the game, the rounds with their moves then the result, e.g.
+    /// `1. X4 O0 X8 O2 X1 O7 X5 O3 X6 2. *X2 O4 X0 O1 X7 O6 1-0`.
+    /// The random opening moves are prefixed with `*`, and the result is `1-0` if X won, `0-1`
+    /// if O won and `1/2-1/2` if there is no winner.
+    pub notation: String,
+    /// The game's chat messages, in order. Empty if the server doesn't save the chat messages.
+    pub chat: Vec<ReplayChatMessageSchema>,
+}
+
+/// The private game's invite schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "InviteSchema", example = json!(InviteSchema::default())))]
+pub struct InviteSchema {
+    /// The invite code, share it with your friend to join your private game.
+    pub code: String,
+    /// When the invite code expires.
+    pub expires_at: chrono::NaiveDateTime,
+    /// The private game variant.
+    pub variant: GameVariant,
 }
 
 impl Default for GameSchema {
@@ -56,7 +309,160 @@ impl Default for GameSchema {
             winner: None,
             won_reason: None,
             created_at: chrono::Utc::now().naive_utc(),
+            ended_at: Some(chrono::Utc::now().naive_utc()),
+            bot_difficulty: None,
+            variant: GameVariant::default(),
+        }
+    }
+}
+
+impl MoveSchema {
+    /// Returns the moves of the game, in order. The moves of in-progress game includes the current round moves.
+    pub(crate) fn from_game(game: &GameModel) -> Vec<Self> {
+        let rounds_result: RoundsResult =
+            game.rounds_result.parse().expect("Is valid rounds result");
+        let mut boards = rounds_result.boards().to_vec();
+        if game.ended_at.is_none() {
+            boards.push(game.board.parse().expect("Is valid board"))
         }
+
+        boards
+            .iter()
+            .zip(1..)
+            .flat_map(|(board, round)| {
+                board
+                    .played_cells()
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, place)| {
+                        let symbol = if index % 2 == 0 {
+                            XoSymbol::X
+                        } else {
+                            XoSymbol::O
+                        };
+                        (round, *place, symbol, index < game.opening_moves as usize)
+                    })
+            })
+            .zip(1..)
+            .map(|((round, place, symbol, forced), sequence)| {
+                Self::new(sequence, round, place, symbol, forced)
+            })
+            .collect()
+    }
+}
+
+impl Default for ReplaySchema {
+    fn default() -> Self {
+        Self {
+            uuid: Uuid::new_v4(),
+            x_player: Uuid::new_v4(),
+            o_player: Uuid::new_v4(),
+            moves: vec![ReplayMoveSchema::new(
+                MoveSchema::new(1, 1, 4, XoSymbol::X, false),
+                Some(chrono::Utc::now().naive_utc()),
+            )],
+            winner: None,
+            won_reason: None,
+            variant: GameVariant::default(),
+            notation: "1. X4 1/2-1/2".to_owned(),
+            chat: Vec::new(),
+        }
+    }
+}
+
+impl ReplaySchema {
+    /// Returns the replay of the ended game, with the saved moves time and chat messages.
+    pub(crate) fn from_game(
+        game: &GameModel,
+        saved_moves: &[GameMoveModel],
+        chat_messages: Vec<ChatMessageModel>,
+    ) -> Self {
+        let moves: Vec<_> = MoveSchema::from_game(game)
+            .into_iter()
+            .map(|played_move| {
+                let played_at = saved_moves
+                    .iter()
+                    .find(|saved| saved.sequence as u32 == played_move.sequence)
+                    .map(|saved| saved.played_at);
+                ReplayMoveSchema::new(played_move, played_at)
+            })
+            .collect();
+
+        let mut notation = String::new();
+        let mut round = 0;
+        for replay_move in &moves {
+            let played_move = &replay_move.played_move;
+            if played_move.round != round {
+                round = played_move.round;
+                notation.push_str(&format!("{round}. "));
+            }
+            if played_move.forced {
+                notation.push('*');
+            }
+            notation.push_str(&format!(
+                "{}{} ",
+                played_move.symbol.to_string(),
+                played_move.place
+            ));
+        }
+        notation.push_str(match game.winner {
+            Some(winner) if winner == game.x_player => "1-0",
+            Some(_) => "0-1",
+            None => "1/2-1/2",
+        });
+
+        Self {
+            uuid: game.uuid,
+            x_player: game.x_player,
+            o_player: game.o_player,
+            moves,
+            winner: game.winner,
+            won_reason: game
+                .reason
+                .as_ref()
+                .map(|reason| reason.parse().expect("Is valid game over reason")),
+            variant: GameVariant::from(game),
+            notation,
+            chat: chat_messages
+                .into_iter()
+                .map(|message| ReplayChatMessageSchema {
+                    sender: message.sender,
+                    encrypted_message: message.encrypted_message,
+                    signature: message.signature,
+                    sent_at: message.sent_at,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl MoveAnalysisSchema {
+    /// Returns the analysis of the game's moves, in order.
+    pub(crate) fn from_game(game: &GameModel) -> Vec<Self> {
+        let mut board = Board::default();
+        let mut round = 1;
+
+        MoveSchema::from_game(game)
+            .into_iter()
+            .map(|played_move| {
+                if played_move.round != round {
+                    board = Board::default();
+                    round = played_move.round;
+                }
+                let best_outcome = board.perfect_play_outcome();
+                board.set_cell(played_move.place as u8, played_move.symbol);
+                let outcome = -board.perfect_play_outcome();
+
+                let annotation = if outcome == best_outcome {
+                    MoveAnnotation::Optimal
+                } else if outcome < 0 {
+                    MoveAnnotation::Blunder
+                } else {
+                    MoveAnnotation::Neutral
+                };
+                Self::new(played_move, annotation)
+            })
+            .collect()
     }
 }
 
@@ -65,7 +471,15 @@ impl GameSchema {
         conn: &sea_orm::DatabaseConnection,
         game: GameModel,
     ) -> ApiResult<Self> {
+        let variant = GameVariant::from(&game);
+        let bot_difficulty = game
+            .bot_difficulty
+            .map(|difficulty| difficulty.parse().expect("Is valid bot difficulty"));
+        let bot_uuid = game.bot_uuid;
         let get_player = |player_uuid| async move {
+            if bot_uuid == Some(player_uuid) {
+                return Ok(UserSchema::bot_user(player_uuid));
+            }
             match db_utils::get_user(conn, player_uuid).await {
                 Ok(user) => UserSchema::from_active_model(conn, user).await,
                 Err(ApiError::UserNotFound) => Ok(UserSchema::deleted_user()),
@@ -73,15 +487,51 @@ impl GameSchema {
             }
         };
 
-        Ok(Self::new(
-            game.uuid,
-            get_player(game.x_player).await?,
-            get_player(game.o_player).await?,
-            game.rounds_result.parse().expect("Is valid rounds result"),
-            game.winner,
-            game.reason
+        Ok(Self {
+            uuid: game.uuid,
+            x_player: get_player(game.x_player).await?,
+            o_player: get_player(game.o_player).await?,
+            rounds_results: game.rounds_result.parse().expect("Is valid rounds result"),
+            winner: game.winner,
+            won_reason: game
+                .reason
                 .map(|reason| reason.parse().expect("Is valid game over reason")),
-            game.created_at,
-        ))
+            created_at: game.created_at,
+            ended_at: game.ended_at,
+            bot_difficulty,
+            variant,
+        })
+    }
+}
+
+impl Default for InviteSchema {
+    fn default() -> Self {
+        Self {
+            code: "XK7MP2QA".to_owned(),
+            expires_at: chrono::Utc::now().naive_utc(),
+            variant: GameVariant::default(),
+        }
+    }
+}
+
+impl From<InviteModel> for InviteSchema {
+    fn from(invite: InviteModel) -> Self {
+        Self {
+            variant: GameVariant::from(&invite),
+            code: invite.code,
+            expires_at: invite.expires_at,
+        }
+    }
+}
+
+impl From<&InviteModel> for GameVariant {
+    fn from(invite: &InviteModel) -> Self {
+        Self::new(invite.board_size as u8, invite.win_length as u8)
+    }
+}
+
+impl From<&GameModel> for GameVariant {
+    fn from(game: &GameModel) -> Self {
+        Self::new(game.board_size as u8, game.win_length as u8)
     }
 }
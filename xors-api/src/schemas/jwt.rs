@@ -19,7 +19,12 @@ use salvo::oapi::ToSchema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::{db_utils, errors::ApiResult};
+use crate::{
+    db_utils,
+    errors::{ApiError, ApiResult},
+};
+
+use super::UserRole;
 
 /// The user's schema. It's used to return the user's data.
 #[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug, ToSchema)]
@@ -35,14 +40,20 @@ pub struct UserSchema {
     pub username: String,
     /// The user's profile image endpoint path.
     pub profile_image_path: String,
+    /// The user's role.
+    pub role: UserRole,
     /// The user's wins games.
     pub wins: i64,
     /// The user's losts games.
     pub losts: i64,
     /// The user's draw games.
     pub draw: i64,
+    /// The user's rating, see `/user/{uuid}/rating`.
+    pub rating: i64,
     /// The last 10 games the player has played.
     pub latest_games: Vec<Uuid>,
+    /// The user's end-of-season rewards, the newest season first.
+    pub season_rewards: Vec<super::SeasonRewardSchema>,
     /// The user's creation date. Joined date.
     pub created_at: chrono::NaiveDateTime,
 }
@@ -82,6 +93,49 @@ pub struct SigninSchema {
     /// The user's password.
     #[salvo(schema(min_length = 8, max_length = 64))]
     pub password: String,
+    /// Whether to keep the session for longer, default is `true`. Without it the refresh token expires sooner, for the
+    /// shared devices.
+    #[serde(default)]
+    pub remember_me: Option<bool>,
+    /// The two-factor authentication code, it's required if the user enabled it. A recovery code is accepted
+    /// instead, each recovery code can be used once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub totp_code: Option<String>,
+}
+
+/// The change password schema. It's used to change the user's password.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "ChangePasswordSchema", example = json!(ChangePasswordSchema::default())))]
+pub struct ChangePasswordSchema {
+    /// The user's current password.
+    #[salvo(schema(min_length = 8, max_length = 64))]
+    pub current_password: String,
+    /// The user's new password, it's validated the same way as the signup password.
+    #[salvo(schema(min_length = 8, max_length = 64))]
+    pub new_password: String,
+}
+
+/// The confirm auth schema. It's used to confirm the user's password for the sensitive actions.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "ConfirmAuthSchema", example = json!(ConfirmAuthSchema::new("Password".to_owned()))))]
+pub struct ConfirmAuthSchema {
+    /// The user's password.
+    #[salvo(schema(min_length = 8, max_length = 64))]
+    pub password: String,
+    /// The two-factor authentication code, it's required if the user enabled it. A recovery code is accepted
+    /// instead, each recovery code can be used once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[new(default)]
+    pub totp_code: Option<String>,
+}
+
+/// The access token schema. It's returned after confirming the password, without a refresh token.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "AccessTokenSchema", example = json!(AccessTokenSchema::new("<JWT>".to_owned()))))]
+pub struct AccessTokenSchema {
+    /// The JWT token. It must be sent in the `Authorization` header.
+    /// Will expire in 1 hour.
+    pub jwt: String,
 }
 
 /// The user's signin schema. It's used to return the user's data and the JWT token.
@@ -98,6 +152,33 @@ pub struct UserSigninSchema {
     pub refresh_token: String,
 }
 
+/// The captcha difficulty.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[salvo(schema(symbol = "CaptchaDifficulty", example = json!(CaptchaDifficulty::Medium)))]
+pub enum CaptchaDifficulty {
+    /// Less noise, the answer is case-insensitive.
+    Easy,
+    /// The default difficulty, the answer is case-sensitive.
+    #[default]
+    Medium,
+    /// More noise, the answer is case-sensitive.
+    Hard,
+}
+
+impl std::str::FromStr for CaptchaDifficulty {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Self::Easy),
+            "medium" => Ok(Self::Medium),
+            "hard" => Ok(Self::Hard),
+            _ => Err(ApiError::InvalidCaptchaDifficulty),
+        }
+    }
+}
+
 /// The captcha schema. It's used to return the captcha token and the captcha image.
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 #[salvo(schema(symbol = "CaptchaSchema", example = json!(CaptchaSchema::default())))]
@@ -106,10 +187,147 @@ pub struct CaptchaSchema {
     pub captcha_token: Uuid,
     /// The captcha image. It's a base64 string.
     pub captcha_image: String,
+    /// The captcha difficulty.
+    pub difficulty: CaptchaDifficulty,
     /// The expiration date of the captcha token.
     pub expired_at: chrono::NaiveDateTime,
 }
 
+/// The captcha provider kind, the captcha that the clients must solve.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[salvo(schema(symbol = "CaptchaProviderKind", example = json!(CaptchaProviderKind::Image)))]
+pub enum CaptchaProviderKind {
+    /// The image captcha of `/auth/captcha`, sent with the `X-Captcha-Token` and `X-Captcha-Answer` headers.
+    #[default]
+    Image,
+    /// The hCaptcha widget, its response token is sent with the `X-Captcha-Response` header.
+    Hcaptcha,
+    /// The reCAPTCHA v3 widget, its response token is sent with the `X-Captcha-Response` header.
+    Recaptcha,
+}
+
+impl std::str::FromStr for CaptchaProviderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "image" => Ok(Self::Image),
+            "hcaptcha" => Ok(Self::Hcaptcha),
+            "recaptcha" => Ok(Self::Recaptcha),
+            _ => Err(format!(
+                "Invalid captcha provider `{s}`, must be `image`, `hcaptcha` or `recaptcha`"
+            )),
+        }
+    }
+}
+
+/// The captcha provider schema. It's used to tell the clients which captcha they must solve.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "CaptchaProviderSchema", example = json!(CaptchaProviderSchema::default())))]
+pub struct CaptchaProviderSchema {
+    /// The captcha provider.
+    pub provider: CaptchaProviderKind,
+    /// The public site key of the hCaptcha and reCAPTCHA widgets, `null` for the image captcha.
+    pub site_key: Option<String>,
+}
+
+/// The captcha preview schema. A captcha that is not stored, for the UI development.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "CaptchaPreviewSchema", example = json!(CaptchaPreviewSchema::default())))]
+pub struct CaptchaPreviewSchema {
+    /// The captcha image. It's a base64 string.
+    pub captcha_image: String,
+    /// The captcha answer. The text that in the captcha image.
+    pub captcha_answer: String,
+}
+
+/// The refresh token info schema. It's used to return the refresh token validity without consuming it.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "RefreshTokenInfoSchema", example = json!(RefreshTokenInfoSchema::default())))]
+pub struct RefreshTokenInfoSchema {
+    /// The timestamp of when the refresh token can be used.
+    pub active_after: i64,
+    /// The timestamp of when the refresh token expires.
+    pub exp: i64,
+    /// The seconds until the refresh token can be used, `0` if it's active.
+    pub active_in: u64,
+    /// The seconds until the refresh token expires.
+    pub expires_in: u64,
+}
+
+/// The usernames schema. It's used to check the availability of multiple usernames at once.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "UsernamesSchema", example = json!(UsernamesSchema::default())))]
+pub struct UsernamesSchema {
+    /// The candidate usernames. At most 10 usernames.
+    #[salvo(schema(min_items = 1, max_items = 10))]
+    pub usernames: Vec<String>,
+}
+
+/// The username availability schema. It's used to return the availability of a username.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "UsernameAvailabilitySchema", example = json!(UsernameAvailabilitySchema::default())))]
+pub struct UsernameAvailabilitySchema {
+    /// The candidate username.
+    pub username: String,
+    /// Whether if the username is available or not.
+    pub available: bool,
+    /// Why the username is not available, if it's not.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// The JSON web key schema. It's the public key that the tokens signed with.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "JwkSchema", example = json!(JwkSchema::default())))]
+pub struct JwkSchema {
+    /// The key type, `RSA` for the RS256 keys and `OKP` for the EdDSA keys.
+    pub kty: String,
+    /// The key usage, always `sig`.
+    #[serde(rename = "use")]
+    pub use_: String,
+    /// The algorithm of the key, `RS256` or `EdDSA`.
+    pub alg: String,
+    /// The key id, it's the `kid` header of the tokens signed with the key.
+    pub kid: String,
+    /// The RSA modulus, base64url encoded. Only for the RS256 keys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<String>,
+    /// The RSA public exponent, base64url encoded. Only for the RS256 keys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub e: Option<String>,
+    /// The curve of the key, always `Ed25519`. Only for the EdDSA keys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crv: Option<String>,
+    /// The public key, base64url encoded. Only for the EdDSA keys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<String>,
+}
+
+/// The JSON web key set schema. It's the active key and the retired keys that still validate the tokens,
+/// the shared secret keys (HS256) are not published.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, ToSchema)]
+#[salvo(schema(symbol = "JwksSchema", example = json!(JwksSchema{ keys: vec![JwkSchema::default()] })))]
+pub struct JwksSchema {
+    /// The public keys.
+    pub keys: Vec<JwkSchema>,
+}
+
+/// The tokens key rotation schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "JwtKeyRotationSchema", example = json!(JwtKeyRotationSchema::default())))]
+pub struct JwtKeyRotationSchema {
+    /// The key id of the active key, the new tokens are signed with it.
+    pub kid: String,
+    /// Whether if the key is rotated, it's not if the key file has the same key.
+    pub rotated: bool,
+    /// The key id of the retired key, it still validates the tokens until they expire.
+    pub retired_kid: Option<String>,
+    /// The date that the retired key stops validating the tokens in.
+    pub retired_until: Option<chrono::NaiveDateTime>,
+}
+
 impl UserSchema {
     /// Returns a deleted user.
     ///
@@ -122,10 +340,32 @@ impl UserSchema {
             last_name: Some("Player".to_owned()),
             username: "Deleted".to_owned(),
             profile_image_path: format!("profiles/{nil_uuid}"),
+            role: UserRole::User,
+            wins: 0,
+            losts: 0,
+            draw: 0,
+            rating: crate::utils::INITIAL_RATING,
+            latest_games: Vec::new(),
+            season_rewards: Vec::new(),
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    /// Returns the AI opponent player of a game, it's a virtual player that is not a user.
+    pub(crate) fn bot_user(uuid: Uuid) -> Self {
+        Self {
+            uuid,
+            first_name: "Bot".to_owned(),
+            last_name: None,
+            username: "Bot".to_owned(),
+            profile_image_path: format!("profiles/{uuid}"),
+            role: UserRole::User,
             wins: 0,
             losts: 0,
             draw: 0,
+            rating: crate::utils::INITIAL_RATING,
             latest_games: Vec::new(),
+            season_rewards: Vec::new(),
             created_at: chrono::Utc::now().naive_utc(),
         }
     }
@@ -143,14 +383,21 @@ impl UserSchema {
             last_name: user.last_name.unwrap(),
             username: user.username.unwrap(),
             profile_image_path: user.profile_image_path.unwrap(),
+            role: user.role.unwrap().parse().expect("Is valid role"),
             wins: user.wins.unwrap(),
             losts: user.losts.unwrap(),
             draw: user.draw.unwrap(),
+            rating: user.rating.unwrap(),
             latest_games: db_utils::latest_player_games(conn, user_uuid)
                 .await?
                 .into_iter()
                 .map(|g| g.uuid)
                 .collect(),
+            season_rewards: db_utils::get_user_season_rewards(conn, *user_uuid)
+                .await?
+                .into_iter()
+                .map(super::SeasonRewardSchema::from)
+                .collect(),
             created_at: user.created_at.unwrap(),
         })
     }
@@ -164,10 +411,13 @@ impl Default for UserSchema {
             last_name: Some("Last".to_owned()),
             username: "Username".to_owned(),
             profile_image_path: "/profiles/default".to_owned(),
+            role: UserRole::User,
             wins: 0,
             losts: 0,
             draw: 0,
+            rating: crate::utils::INITIAL_RATING,
             latest_games: vec![Uuid::new_v4()],
+            season_rewards: Vec::new(),
             created_at: chrono::Utc::now().naive_utc(),
         }
     }
@@ -199,6 +449,17 @@ impl Default for SigninSchema {
         Self {
             username: "Username".to_owned(),
             password: "Password".to_owned(),
+            remember_me: Some(true),
+            totp_code: None,
+        }
+    }
+}
+
+impl Default for ChangePasswordSchema {
+    fn default() -> Self {
+        Self {
+            current_password: "CurrentPassword".to_owned(),
+            new_password: "NewPassword".to_owned(),
         }
     }
 }
@@ -208,11 +469,84 @@ impl Default for CaptchaSchema {
         Self {
             captcha_token: Uuid::new_v4(),
             captcha_image: "<CAPTCHA_IMAGE_BASE64>".to_owned(),
+            difficulty: CaptchaDifficulty::default(),
             expired_at: chrono::Utc::now().naive_utc(),
         }
     }
 }
 
+impl Default for CaptchaPreviewSchema {
+    fn default() -> Self {
+        Self {
+            captcha_image: "<CAPTCHA_IMAGE_BASE64>".to_owned(),
+            captcha_answer: "<CAPTCHA_ANSWER>".to_owned(),
+        }
+    }
+}
+
+impl RefreshTokenInfoSchema {
+    /// Create the refresh token info, at the `now` timestamp.
+    pub fn new(active_after: i64, exp: i64, now: i64) -> Self {
+        Self {
+            active_after,
+            exp,
+            active_in: (active_after - now).max(0) as u64,
+            expires_in: (exp - now).max(0) as u64,
+        }
+    }
+}
+
+impl Default for RefreshTokenInfoSchema {
+    fn default() -> Self {
+        let now = chrono::Utc::now().timestamp();
+        Self::new(now + 60 * 58, now + 60 * 60 * 3, now)
+    }
+}
+
+impl Default for JwkSchema {
+    fn default() -> Self {
+        Self {
+            kty: "RSA".to_owned(),
+            use_: "sig".to_owned(),
+            alg: "RS256".to_owned(),
+            kid: "<KEY_ID>".to_owned(),
+            n: Some("<RSA_MODULUS>".to_owned()),
+            e: Some("AQAB".to_owned()),
+            crv: None,
+            x: None,
+        }
+    }
+}
+
+impl Default for JwtKeyRotationSchema {
+    fn default() -> Self {
+        Self {
+            kid: "<NEW_KEY_ID>".to_owned(),
+            rotated: true,
+            retired_kid: Some("<OLD_KEY_ID>".to_owned()),
+            retired_until: Some(chrono::Utc::now().naive_utc() + chrono::Duration::days(30)),
+        }
+    }
+}
+
+impl Default for UsernamesSchema {
+    fn default() -> Self {
+        Self {
+            usernames: vec!["Username".to_owned(), "Username_2".to_owned()],
+        }
+    }
+}
+
+impl Default for UsernameAvailabilitySchema {
+    fn default() -> Self {
+        Self {
+            username: "Username".to_owned(),
+            available: true,
+            reason: None,
+        }
+    }
+}
+
 impl PartialEq<NewUserSchema> for UserSchema {
     fn eq(&self, other: &NewUserSchema) -> bool {
         self.first_name == other.first_name
@@ -17,18 +17,41 @@
 use salvo::oapi::ToSchema;
 use serde::{Deserialize, Serialize};
 
+mod admin;
+mod export;
+mod friend;
 mod game;
 mod jwt;
+mod notification;
+mod season;
+mod server;
+mod tournament;
 mod user;
+mod webhook;
 mod xo;
 
-pub use {game::*, jwt::*, user::*, xo::*};
+pub use {
+    admin::*, export::*, friend::*, game::*, jwt::*, notification::*, season::*, server::*,
+    tournament::*, user::*, webhook::*, xo::*,
+};
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
-#[salvo(schema(symbol = "MessageSchema", example = json!(MessageSchema::new("Message".to_owned()))))]
+#[salvo(schema(symbol = "MessageSchema", example = json!(MessageSchema::new("Message".to_owned()).with_code("message_code"))))]
 pub struct MessageSchema {
     #[salvo(schema(example = "Message"))]
     message: String,
+    /// The stable machine-readable error code, only in the errors responses.
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+}
+
+impl MessageSchema {
+    /// Set the error code of the message.
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
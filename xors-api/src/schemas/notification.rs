@@ -0,0 +1,163 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use entity::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::errors::ApiError;
+
+use super::GameVariant;
+
+/// The notification kind.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[salvo(schema(symbol = "NotificationKind", example = json!(NotificationKind::FriendRequest)))]
+pub enum NotificationKind {
+    /// A user sent you a friend request.
+    FriendRequest,
+    /// A friend invited you to a private game.
+    GameInvite,
+    /// Your next tournament match is ready, both of its players are known.
+    TournamentMatch,
+    /// A moderator warned you.
+    AdminWarning,
+}
+
+/// The notification data, its fields depend on the notification kind.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(untagged)]
+#[salvo(schema(symbol = "NotificationData"))]
+pub enum NotificationData {
+    /// The friend request notification, with the sender of the request.
+    FriendRequest { requester: Uuid, username: String },
+    /// The game invite notification, with the invite host, the invite code to join and the game variant.
+    GameInvite {
+        host: Uuid,
+        host_username: String,
+        code: String,
+        expires_at: chrono::NaiveDateTime,
+        #[serde(default)]
+        variant: GameVariant,
+    },
+    /// The tournament match notification, with the match round and the opponent.
+    TournamentMatch {
+        tournament: Uuid,
+        tournament_name: String,
+        round: i16,
+        opponent: Uuid,
+    },
+    /// The moderator warning notification, with the warning message.
+    AdminWarning { message: String },
+}
+
+/// The notification schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "NotificationSchema", example = json!(NotificationSchema::default())))]
+pub struct NotificationSchema {
+    /// The notification uuid.
+    pub uuid: Uuid,
+    /// The notification kind.
+    pub kind: NotificationKind,
+    /// The notification data.
+    pub data: NotificationData,
+    /// When the notification is marked as read, null if it's unread.
+    pub read_at: Option<chrono::NaiveDateTime>,
+    /// When the notification is created.
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// The moderator warning schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "WarningSchema", example = json!(WarningSchema::new("Please be respectful in the chat".to_owned()))))]
+pub struct WarningSchema {
+    /// The warning message, between 1 and 500 characters.
+    pub message: String,
+}
+
+impl NotificationKind {
+    /// All the notification kinds.
+    pub const ALL: [Self; 4] = [
+        Self::FriendRequest,
+        Self::GameInvite,
+        Self::TournamentMatch,
+        Self::AdminWarning,
+    ];
+}
+
+impl NotificationData {
+    /// Returns the notification kind of the data.
+    pub fn kind(&self) -> NotificationKind {
+        match self {
+            Self::FriendRequest { .. } => NotificationKind::FriendRequest,
+            Self::GameInvite { .. } => NotificationKind::GameInvite,
+            Self::TournamentMatch { .. } => NotificationKind::TournamentMatch,
+            Self::AdminWarning { .. } => NotificationKind::AdminWarning,
+        }
+    }
+}
+
+impl ToString for NotificationKind {
+    fn to_string(&self) -> String {
+        match self {
+            Self::FriendRequest => "friend_request".to_owned(),
+            Self::GameInvite => "game_invite".to_owned(),
+            Self::TournamentMatch => "tournament_match".to_owned(),
+            Self::AdminWarning => "admin_warning".to_owned(),
+        }
+    }
+}
+
+impl std::str::FromStr for NotificationKind {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.to_string() == s)
+            .ok_or(ApiError::InternalServer)
+    }
+}
+
+impl From<NotificationModel> for NotificationSchema {
+    fn from(notification: NotificationModel) -> Self {
+        Self {
+            uuid: notification.uuid,
+            kind: notification
+                .kind
+                .parse()
+                .expect("Is valid notification kind"),
+            data: serde_json::from_str(&notification.data).expect("Is valid notification data"),
+            read_at: notification.read_at,
+            created_at: notification.created_at,
+        }
+    }
+}
+
+impl Default for NotificationSchema {
+    fn default() -> Self {
+        Self {
+            uuid: Uuid::new_v4(),
+            kind: NotificationKind::AdminWarning,
+            data: NotificationData::AdminWarning {
+                message: "Please be respectful in the chat".to_owned(),
+            },
+            read_at: None,
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+}
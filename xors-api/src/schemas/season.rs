@@ -0,0 +1,131 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use entity::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+
+/// The ranked season schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "SeasonSchema", example = json!(SeasonSchema::default())))]
+pub struct SeasonSchema {
+    /// The season number, the first season is `1`.
+    pub number: u32,
+    /// When the season started.
+    pub starts_at: chrono::NaiveDateTime,
+    /// When the season is scheduled to end.
+    pub ends_at: chrono::NaiveDateTime,
+    /// When the season ended and its leaderboard archived. Null if it's the current season.
+    pub ended_at: Option<chrono::NaiveDateTime>,
+}
+
+/// The end-of-season reward, awarded to the top players of the season leaderboard.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[salvo(schema(symbol = "SeasonReward", example = json!(SeasonReward::TopTen)))]
+pub enum SeasonReward {
+    /// The first player of the season.
+    Champion,
+    /// One of the top 10 players of the season.
+    TopTen,
+    /// One of the top 100 players of the season.
+    TopHundred,
+}
+
+/// The user season reward schema.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "SeasonRewardSchema", example = json!(SeasonRewardSchema::new(3, 7, SeasonReward::TopTen))))]
+pub struct SeasonRewardSchema {
+    /// The season number.
+    pub season: u32,
+    /// The user's final rank in the season leaderboard.
+    pub rank: u64,
+    /// The awarded reward.
+    pub reward: SeasonReward,
+}
+
+impl SeasonReward {
+    /// All the rewards.
+    pub const ALL: [Self; 3] = [Self::Champion, Self::TopTen, Self::TopHundred];
+
+    /// Returns the reward of the final rank in the season leaderboard, if any.
+    pub fn for_rank(rank: u64) -> Option<Self> {
+        match rank {
+            1 => Some(Self::Champion),
+            2..=10 => Some(Self::TopTen),
+            11..=100 => Some(Self::TopHundred),
+            _ => None,
+        }
+    }
+}
+
+impl ToString for SeasonReward {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Champion => "champion".to_owned(),
+            Self::TopTen => "top_ten".to_owned(),
+            Self::TopHundred => "top_hundred".to_owned(),
+        }
+    }
+}
+
+impl std::str::FromStr for SeasonReward {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|reward| reward.to_string() == s)
+            .ok_or(())
+    }
+}
+
+impl From<SeasonModel> for SeasonSchema {
+    fn from(season: SeasonModel) -> Self {
+        Self {
+            number: season.number as u32,
+            starts_at: season.starts_at,
+            ends_at: season.ends_at,
+            ended_at: season.ended_at,
+        }
+    }
+}
+
+impl From<SeasonStandingModel> for SeasonRewardSchema {
+    fn from(standing: SeasonStandingModel) -> Self {
+        Self::new(
+            standing.season as u32,
+            standing.rank as u64,
+            standing
+                .reward
+                .expect("The standing has a reward")
+                .parse()
+                .expect("Is valid reward"),
+        )
+    }
+}
+
+impl Default for SeasonSchema {
+    fn default() -> Self {
+        let starts_at = chrono::Utc::now().naive_utc();
+        Self {
+            number: 3,
+            starts_at,
+            ends_at: starts_at + chrono::Duration::days(90),
+            ended_at: None,
+        }
+    }
+}
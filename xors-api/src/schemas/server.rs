@@ -0,0 +1,56 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+
+/// The server time schema. It's used to synchronize the client clock with the server clock.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "ServerTimeSchema", example = json!(ServerTimeSchema::new(1707336000, 1707336000000, 3600000))))]
+pub struct ServerTimeSchema {
+    /// The current server UTC timestamp, in seconds.
+    pub timestamp: i64,
+    /// The current server UTC timestamp, in milliseconds.
+    pub timestamp_millis: i64,
+    /// Monotonic hint, the milliseconds since the server started.
+    /// Unlike the timestamp, it's never affected by the server clock adjustments.
+    pub monotonic_millis: u64,
+}
+
+/// The server health status.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[salvo(schema(symbol = "HealthStatus"))]
+pub enum HealthStatus {
+    /// All the subsystems are working.
+    Ok,
+    /// Some subsystems are failing and the server is running in a degraded mode.
+    Degraded,
+    /// The database is unreachable, the server can't serve the requests.
+    Unavailable,
+}
+
+/// The server health schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "HealthSchema", example = json!(HealthSchema::new(HealthStatus::Ok, true, false))))]
+pub struct HealthSchema {
+    /// The server health status.
+    pub status: HealthStatus,
+    /// Whether if the database is reachable.
+    pub database: bool,
+    /// Whether if the captcha degraded mode is active, the captcha is skipped for unsuspicious requests.
+    pub captcha_degraded: bool,
+}
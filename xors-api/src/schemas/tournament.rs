@@ -0,0 +1,216 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use entity::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// The new tournament schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "NewTournamentSchema", example = json!(NewTournamentSchema::new("Friday cup".to_owned(), 8))))]
+pub struct NewTournamentSchema {
+    /// The tournament name, between 3 and 64 characters.
+    pub name: String,
+    /// The number of the tournament players, a power of two between 2 and 64. The bracket is generated when
+    /// the registration is full.
+    pub size: u8,
+}
+
+/// The tournament status.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[salvo(schema(symbol = "TournamentStatus", example = json!(TournamentStatus::InProgress)))]
+pub enum TournamentStatus {
+    /// The registration is open, the tournament is waiting for its players.
+    Registration,
+    /// The bracket is generated and the matches are being played.
+    InProgress,
+    /// The final match is played, the tournament has a winner.
+    Ended,
+}
+
+/// The tournament bracket match schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "TournamentMatchSchema"))]
+pub struct TournamentMatchSchema {
+    /// The match round, the first round is 1 and the last round is the final.
+    pub round: i16,
+    /// The match slot in its round, starts from 0. The winner advances to the slot `slot / 2` of the next
+    /// round, as the X player if the slot is even, otherwise as the O player.
+    pub slot: i16,
+    /// The X player. Null until the winner of its previous round match is known.
+    pub x_player: Option<Uuid>,
+    /// The O player. Null until the winner of its previous round match is known.
+    pub o_player: Option<Uuid>,
+    /// The match's in-progress or ended game. Null if the match game didn't start yet.
+    pub game: Option<Uuid>,
+    /// The match games, the oldest first. The games before the last one ended with a draw, the match is
+    /// replayed with the swapped sides after a draw.
+    pub games: Vec<Uuid>,
+    /// The match winner. Null if the match is not played yet.
+    pub winner: Option<Uuid>,
+}
+
+/// The tournament player standing schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "TournamentStandingSchema"))]
+pub struct TournamentStandingSchema {
+    /// The player's uuid.
+    pub player: Uuid,
+    /// The won matches.
+    pub wins: u8,
+    /// The round that the player is eliminated in. Null if the player is not eliminated.
+    pub eliminated_in: Option<i16>,
+}
+
+/// The tournament schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "TournamentSchema", example = json!(TournamentSchema::default())))]
+pub struct TournamentSchema {
+    /// The tournament uuid.
+    pub uuid: Uuid,
+    /// The tournament name.
+    pub name: String,
+    /// The uuid of the user that created the tournament.
+    pub host: Uuid,
+    /// The number of the tournament players.
+    pub size: u8,
+    /// The tournament status.
+    pub status: TournamentStatus,
+    /// The registered players, in the registration order.
+    pub players: Vec<Uuid>,
+    /// The bracket matches, sorted by the round then the slot. Empty while the registration is open.
+    pub matches: Vec<TournamentMatchSchema>,
+    /// The players standings, the best first. The players with more wins are first, then the not eliminated
+    /// players, then the players that are eliminated in a later round.
+    pub standings: Vec<TournamentStandingSchema>,
+    /// The tournament winner. Null if the tournament is not ended.
+    pub winner: Option<Uuid>,
+    /// When the tournament is created.
+    pub created_at: chrono::NaiveDateTime,
+    /// When the bracket is generated. Null while the registration is open.
+    pub started_at: Option<chrono::NaiveDateTime>,
+    /// When the final match is played. Null if the tournament is not ended.
+    pub ended_at: Option<chrono::NaiveDateTime>,
+}
+
+impl Default for TournamentSchema {
+    fn default() -> Self {
+        Self {
+            uuid: Uuid::new_v4(),
+            name: "Friday cup".to_owned(),
+            host: Uuid::new_v4(),
+            size: 8,
+            status: TournamentStatus::Registration,
+            players: Vec::new(),
+            matches: Vec::new(),
+            standings: Vec::new(),
+            winner: None,
+            created_at: chrono::Utc::now().naive_utc(),
+            started_at: None,
+            ended_at: None,
+        }
+    }
+}
+
+impl TournamentMatchSchema {
+    /// Returns the tournament match schema, the `games` are the games of the tournament matches.
+    fn from_match(
+        tournament_match: TournamentMatchModel,
+        games: &[TournamentMatchGameModel],
+    ) -> Self {
+        Self {
+            round: tournament_match.round,
+            slot: tournament_match.slot,
+            x_player: tournament_match.x_player,
+            o_player: tournament_match.o_player,
+            game: tournament_match.game_uuid,
+            games: games
+                .iter()
+                .filter(|game| game.match_id == tournament_match.id)
+                .map(|game| game.game_uuid)
+                .collect(),
+            winner: tournament_match.winner,
+        }
+    }
+}
+
+impl TournamentSchema {
+    /// Returns the tournament schema, the `matches` must be sorted by the round then the slot and the
+    /// `match_games` by their order.
+    pub(crate) fn from_tournament(
+        tournament: TournamentModel,
+        players: Vec<TournamentPlayerModel>,
+        matches: Vec<TournamentMatchModel>,
+        match_games: Vec<TournamentMatchGameModel>,
+    ) -> Self {
+        let status = if tournament.ended_at.is_some() {
+            TournamentStatus::Ended
+        } else if tournament.started_at.is_some() {
+            TournamentStatus::InProgress
+        } else {
+            TournamentStatus::Registration
+        };
+        let players: Vec<Uuid> = players.into_iter().map(|player| player.user_uuid).collect();
+
+        let mut standings: Vec<_> = players
+            .iter()
+            .map(|&player| {
+                let played = matches.iter().filter(|tournament_match| {
+                    tournament_match.winner.is_some()
+                        && [tournament_match.x_player, tournament_match.o_player]
+                            .contains(&Some(player))
+                });
+                let wins = played
+                    .clone()
+                    .filter(|tournament_match| tournament_match.winner == Some(player))
+                    .count() as u8;
+                let eliminated_in = played
+                    .filter(|tournament_match| tournament_match.winner != Some(player))
+                    .map(|tournament_match| tournament_match.round)
+                    .next();
+                TournamentStandingSchema::new(player, wins, eliminated_in)
+            })
+            .collect();
+        standings.sort_by_key(|standing| {
+            (
+                std::cmp::Reverse(standing.wins),
+                standing.eliminated_in.map(std::cmp::Reverse),
+            )
+        });
+
+        Self {
+            uuid: tournament.uuid,
+            name: tournament.name,
+            host: tournament.host_uuid,
+            size: tournament.size as u8,
+            status,
+            players,
+            matches: matches
+                .into_iter()
+                .map(|tournament_match| {
+                    TournamentMatchSchema::from_match(tournament_match, &match_games)
+                })
+                .collect(),
+            standings,
+            winner: tournament.winner,
+            created_at: tournament.created_at,
+            started_at: tournament.started_at,
+            ended_at: tournament.ended_at,
+        }
+    }
+}
@@ -17,7 +17,208 @@
 use salvo::oapi::ToSchema;
 use serde::{Deserialize, Serialize};
 
-/// The delete user schema.
+use entity::prelude::*;
+
+use crate::errors::ApiError;
+
+/// The user streaks schema.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, ToSchema)]
+#[salvo(schema(symbol = "StreaksSchema", example = json!(StreaksSchema::default())))]
+pub struct StreaksSchema {
+    /// The current wins streak. Zero if the last game is not a win.
+    pub current_win_streak: i64,
+    /// The current losts streak. Zero if the last game is not a lost.
+    pub current_loss_streak: i64,
+    /// The longest wins streak.
+    pub longest_win_streak: i64,
+    /// The longest losts streak.
+    pub longest_loss_streak: i64,
+}
+
+/// The user stats schema, they are updated when the user's games end.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, ToSchema)]
+#[salvo(schema(symbol = "UserStatsSchema", example = json!(UserStatsSchema::default())))]
+pub struct UserStatsSchema {
+    /// The won games.
+    pub wins: i64,
+    /// The lost games.
+    pub losses: i64,
+    /// The draw games.
+    pub draws: i64,
+    /// The current wins streak. Zero if the last game is not a win.
+    pub current_win_streak: i64,
+    /// The longest wins streak.
+    pub longest_win_streak: i64,
+    /// The average duration of the user's games, in seconds. Null if the user has no games.
+    pub average_game_duration: Option<i64>,
+    /// The user's current rating.
+    pub rating: i64,
+    /// The user's ratings after each of its last 10 games, the oldest first.
+    pub rating_trend: Vec<i64>,
+}
+
+/// The result of a game, from the user's side.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[salvo(schema(symbol = "GameResult", example = json!(GameResult::Win)))]
+pub enum GameResult {
+    /// The user won the game.
+    Win,
+    /// The user lost the game.
+    Loss,
+    /// The game ended without a winner.
+    Draw,
+}
+
+/// The user games page schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "UserGamesSchema", example = json!(UserGamesSchema{ games: vec![super::GameSchema::default()], next_cursor: Some(uuid::Uuid::new_v4()) })))]
+pub struct UserGamesSchema {
+    /// The games of the page, the newest first.
+    pub games: Vec<super::GameSchema>,
+    /// The cursor of the next page, the `before` of it. Null if it's the last page.
+    pub next_cursor: Option<uuid::Uuid>,
+}
+
+/// The user first moves heatmap schema.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, ToSchema)]
+#[salvo(schema(symbol = "HeatmapSchema", example = json!(HeatmapSchema::default())))]
+pub struct HeatmapSchema {
+    /// How often the user's first move of a round landed on each cell, the index is the cell place (0-8).
+    pub cells: [u32; 9],
+    /// The number of the counted rounds.
+    pub rounds: u32,
+}
+
+/// The user activity of a day schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "ActivityDaySchema", example = json!(ActivityDaySchema::new(chrono::Utc::now().date_naive(), 3))))]
+pub struct ActivityDaySchema {
+    /// The day, in UTC.
+    pub date: chrono::NaiveDate,
+    /// The number of the games the user started in the day.
+    pub games: u64,
+}
+
+/// The user's role, each role has the permissions of the lower roles.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[salvo(schema(symbol = "UserRole", example = json!(UserRole::User)))]
+pub enum UserRole {
+    /// A guest user, it plays the unranked games only until it's upgraded to a regular user.
+    Guest,
+    /// A regular user.
+    #[default]
+    User,
+    /// A moderator.
+    Moderator,
+    /// An admin.
+    Admin,
+}
+
+/// The user role schema. It's used to change the user's role.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "UserRoleSchema", example = json!(UserRoleSchema::new(UserRole::Moderator))))]
+pub struct UserRoleSchema {
+    /// The user's new role.
+    pub role: UserRole,
+}
+
+/// The achievement, awarded automatically when the user's game ends.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[salvo(schema(symbol = "Achievement", example = json!(Achievement::FirstWin)))]
+pub enum Achievement {
+    /// Finish the first game.
+    FirstGame,
+    /// Win the first game.
+    FirstWin,
+    /// Win 5 games in a row.
+    FiveWinStreak,
+    /// Win 10 games in a row.
+    TenWinStreak,
+    /// Finish 100 games.
+    HundredGames,
+}
+
+/// The user achievement schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "AchievementSchema", example = json!(AchievementSchema::new(Achievement::FirstWin, chrono::Utc::now().naive_utc()))))]
+pub struct AchievementSchema {
+    /// The awarded achievement.
+    pub achievement: Achievement,
+    /// When the achievement was awarded.
+    pub awarded_at: chrono::NaiveDateTime,
+}
+
+/// The user rating change schema, the change of a finished game.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "RatingChangeSchema", example = json!(RatingChangeSchema::default())))]
+pub struct RatingChangeSchema {
+    /// The game's uuid.
+    pub game_uuid: uuid::Uuid,
+    /// The user's rating after the game.
+    pub rating: i64,
+    /// The rating change of the game, negative if the rating decreased.
+    pub change: i64,
+    /// When the game ended.
+    pub changed_at: chrono::NaiveDateTime,
+}
+
+/// The user rating schema, the current rating with its history.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "RatingSchema", example = json!(RatingSchema{ rating: 1216, history: vec![RatingChangeSchema::default()] })))]
+pub struct RatingSchema {
+    /// The user's current rating.
+    pub rating: i64,
+    /// The rating changes, the oldest first.
+    pub history: Vec<RatingChangeSchema>,
+}
+
+/// The leaderboard entry schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "LeaderboardEntrySchema", example = json!(LeaderboardEntrySchema::default())))]
+pub struct LeaderboardEntrySchema {
+    /// The user's position in the leaderboard, starting from 1.
+    pub rank: u64,
+    /// The user's uuid.
+    pub uuid: uuid::Uuid,
+    /// The user's username.
+    pub username: String,
+    /// The user's first name.
+    pub first_name: String,
+    /// The user's last name.
+    pub last_name: Option<String>,
+    /// The user's rating.
+    pub rating: i64,
+    /// The user's wins games.
+    pub wins: i64,
+    /// The user's losts games.
+    pub losts: i64,
+    /// The user's draw games.
+    pub draw: i64,
+}
+
+/// The user rank schema, the user's position among the ranked users.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "RankSchema", example = json!(RankSchema::ranked(3, 40, 12, 5))))]
+pub struct RankSchema {
+    /// Whether the user is ranked, the guests and the users with fewer rated games than `min_rated_games` are not.
+    pub ranked: bool,
+    /// The user's position among the ranked users, starting from 1. Null if the user is not ranked.
+    pub rank: Option<u64>,
+    /// The number of the ranked users. Null if the user is not ranked.
+    pub ranked_users: Option<u64>,
+    /// The percentage of the ranked users at or below the user's position. Null if the user is not ranked.
+    pub percentile: Option<f64>,
+    /// The user's rated games.
+    pub rated_games: u64,
+    /// The rated games required to be ranked.
+    pub min_rated_games: u64,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 #[salvo(schema(symbol = "DeleteUserSchema", example = json!(DeleteUserSchema::default())))]
 pub struct DeleteUserSchema {
@@ -60,6 +261,52 @@ pub struct ResetPasswordSchema {
     pub new_password: String,
 }
 
+/// Change username schema. It's used to change the user's username.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "ChangeUsernameSchema", example = json!(ChangeUsernameSchema::new("new_username".to_owned()))))]
+pub struct ChangeUsernameSchema {
+    /// The new username, it has the signup username rules.
+    #[salvo(schema(min_length = 3, max_length = 32))]
+    pub username: String,
+}
+
+/// The TOTP secret schema. It's returned when the two-factor authentication is enabled, to add it to an
+/// authenticator app.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "TotpSecretSchema", example = json!(TotpSecretSchema::default())))]
+pub struct TotpSecretSchema {
+    /// The TOTP secret, base32 encoded.
+    pub secret: String,
+    /// The `otpauth` URI of the secret, it can be shown as a QR code.
+    pub otpauth_uri: String,
+}
+
+/// The confirm TOTP schema. It's used to activate the two-factor authentication.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "ConfirmTotpSchema", example = json!(ConfirmTotpSchema::default())))]
+pub struct ConfirmTotpSchema {
+    /// The current code of the authenticator app.
+    #[salvo(schema(min_length = 6, max_length = 6))]
+    pub code: String,
+}
+
+/// The disable TOTP schema. It's used to deactivate the two-factor authentication.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "DisableTotpSchema", example = json!(DisableTotpSchema::default())))]
+pub struct DisableTotpSchema {
+    /// The current code of the authenticator app, or a recovery code.
+    #[salvo(schema(min_length = 6, max_length = 32))]
+    pub code: String,
+}
+
+/// The recovery codes schema. The codes are shown once, each one can be used once instead of the TOTP code.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "RecoveryCodesSchema", example = json!(RecoveryCodesSchema::default())))]
+pub struct RecoveryCodesSchema {
+    /// The recovery codes.
+    pub recovery_codes: Vec<String>,
+}
+
 impl Default for DeleteUserSchema {
     fn default() -> Self {
         Self {
@@ -86,3 +333,260 @@ impl Default for ResetPasswordSchema {
         }
     }
 }
+
+impl Default for TotpSecretSchema {
+    fn default() -> Self {
+        Self {
+            secret: "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP".to_owned(),
+            otpauth_uri: "otpauth://totp/XORS:Username?secret=JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP&issuer=XORS&algorithm=SHA1&digits=6&period=30".to_owned(),
+        }
+    }
+}
+
+impl Default for ConfirmTotpSchema {
+    fn default() -> Self {
+        Self {
+            code: "123456".to_owned(),
+        }
+    }
+}
+
+impl Default for DisableTotpSchema {
+    fn default() -> Self {
+        Self {
+            code: "123456".to_owned(),
+        }
+    }
+}
+
+impl Default for RecoveryCodesSchema {
+    fn default() -> Self {
+        Self {
+            recovery_codes: vec!["ABCDE-FGHJK".to_owned(), "LMNPQ-RSTUV".to_owned()],
+        }
+    }
+}
+
+impl Achievement {
+    /// All the achievements.
+    pub const ALL: [Self; 5] = [
+        Self::FirstGame,
+        Self::FirstWin,
+        Self::FiveWinStreak,
+        Self::TenWinStreak,
+        Self::HundredGames,
+    ];
+
+    /// Returns whether the user's stats earn the achievement.
+    pub fn is_earned(&self, user: &UserActiveModel) -> bool {
+        let wins = *user.wins.as_ref();
+        let games = wins + user.losts.as_ref() + user.draw.as_ref();
+        let longest_win_streak = *user.longest_win_streak.as_ref();
+
+        match self {
+            Self::FirstGame => games >= 1,
+            Self::FirstWin => wins >= 1,
+            Self::FiveWinStreak => longest_win_streak >= 5,
+            Self::TenWinStreak => longest_win_streak >= 10,
+            Self::HundredGames => games >= 100,
+        }
+    }
+}
+
+impl UserRole {
+    /// All the roles.
+    pub const ALL: [Self; 4] = [Self::Guest, Self::User, Self::Moderator, Self::Admin];
+}
+
+impl ToString for UserRole {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Guest => "guest".to_owned(),
+            Self::User => "user".to_owned(),
+            Self::Moderator => "moderator".to_owned(),
+            Self::Admin => "admin".to_owned(),
+        }
+    }
+}
+
+impl std::str::FromStr for UserRole {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|role| role.to_string() == s)
+            .ok_or(())
+    }
+}
+
+impl ToString for Achievement {
+    fn to_string(&self) -> String {
+        match self {
+            Self::FirstGame => "first_game".to_owned(),
+            Self::FirstWin => "first_win".to_owned(),
+            Self::FiveWinStreak => "five_win_streak".to_owned(),
+            Self::TenWinStreak => "ten_win_streak".to_owned(),
+            Self::HundredGames => "hundred_games".to_owned(),
+        }
+    }
+}
+
+impl std::str::FromStr for Achievement {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|achievement| achievement.to_string() == s)
+            .ok_or(())
+    }
+}
+
+impl From<AchievementModel> for AchievementSchema {
+    fn from(achievement: AchievementModel) -> Self {
+        Self::new(
+            achievement.kind.parse().expect("Is valid achievement"),
+            achievement.awarded_at,
+        )
+    }
+}
+
+impl From<RatingChangeModel> for RatingChangeSchema {
+    fn from(rating_change: RatingChangeModel) -> Self {
+        Self {
+            game_uuid: rating_change.game_uuid,
+            rating: rating_change.rating,
+            change: rating_change.change,
+            changed_at: rating_change.changed_at,
+        }
+    }
+}
+
+impl Default for RatingChangeSchema {
+    fn default() -> Self {
+        Self {
+            game_uuid: uuid::Uuid::new_v4(),
+            rating: 1216,
+            change: 16,
+            changed_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+}
+
+impl LeaderboardEntrySchema {
+    /// Create the leaderboard entry of the user at `rank`.
+    pub fn new(rank: u64, user: UserModel) -> Self {
+        Self {
+            rank,
+            uuid: user.uuid,
+            username: user.username,
+            first_name: user.first_name,
+            last_name: user.last_name,
+            rating: user.rating,
+            wins: user.wins,
+            losts: user.losts,
+            draw: user.draw,
+        }
+    }
+
+    /// Create the entry of the user from its archived standing in a past season leaderboard.
+    pub fn from_standing(standing: SeasonStandingModel, user: UserModel) -> Self {
+        Self {
+            rank: standing.rank as u64,
+            uuid: user.uuid,
+            username: user.username,
+            first_name: user.first_name,
+            last_name: user.last_name,
+            rating: standing.rating,
+            wins: standing.wins,
+            losts: standing.losts,
+            draw: standing.draw,
+        }
+    }
+}
+
+impl RankSchema {
+    /// Create the rank of the user at `rank` of `ranked_users`.
+    pub fn ranked(rank: u64, ranked_users: u64, rated_games: u64, min_rated_games: u64) -> Self {
+        let percentile = (ranked_users - rank + 1) as f64 * 100.0 / ranked_users as f64;
+        Self {
+            ranked: true,
+            rank: Some(rank),
+            ranked_users: Some(ranked_users),
+            percentile: Some((percentile * 100.0).round() / 100.0),
+            rated_games,
+            min_rated_games,
+        }
+    }
+
+    /// Create the rank of the user that is not ranked.
+    pub fn unranked(rated_games: u64, min_rated_games: u64) -> Self {
+        Self {
+            ranked: false,
+            rank: None,
+            ranked_users: None,
+            percentile: None,
+            rated_games,
+            min_rated_games,
+        }
+    }
+}
+
+impl Default for LeaderboardEntrySchema {
+    fn default() -> Self {
+        Self {
+            rank: 1,
+            uuid: uuid::Uuid::new_v4(),
+            username: "Username".to_owned(),
+            first_name: "First".to_owned(),
+            last_name: Some("Last".to_owned()),
+            rating: 1216,
+            wins: 1,
+            losts: 0,
+            draw: 0,
+        }
+    }
+}
+
+impl std::str::FromStr for GameResult {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "win" => Ok(Self::Win),
+            "loss" => Ok(Self::Loss),
+            "draw" => Ok(Self::Draw),
+            _ => Err(ApiError::InvalidGameResult),
+        }
+    }
+}
+
+impl UserStatsSchema {
+    /// Creates the stats of the user, with its last games ratings.
+    pub fn new(user: UserActiveModel, rating_trend: Vec<i64>) -> Self {
+        let games = user.wins.as_ref() + user.losts.as_ref() + user.draw.as_ref();
+        Self {
+            wins: user.wins.unwrap(),
+            losses: user.losts.unwrap(),
+            draws: user.draw.unwrap(),
+            current_win_streak: (*user.current_streak.as_ref()).max(0),
+            longest_win_streak: user.longest_win_streak.unwrap(),
+            average_game_duration: (games != 0).then(|| user.games_duration.as_ref() / games),
+            rating: user.rating.unwrap(),
+            rating_trend,
+        }
+    }
+}
+
+impl From<UserActiveModel> for StreaksSchema {
+    fn from(user: UserActiveModel) -> Self {
+        let current_streak = user.current_streak.unwrap();
+        Self {
+            current_win_streak: current_streak.max(0),
+            current_loss_streak: -current_streak.min(0),
+            longest_win_streak: user.longest_win_streak.unwrap(),
+            longest_loss_streak: user.longest_loss_streak.unwrap(),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use entity::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::errors::ApiError;
+
+/// The webhook event.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[salvo(schema(symbol = "WebhookEvent", example = json!(WebhookEvent::GameFinished)))]
+pub enum WebhookEvent {
+    /// A game is over, the data is the game.
+    #[serde(rename = "game.finished")]
+    GameFinished,
+    /// A user signed up or played as a guest, the data is the user.
+    #[serde(rename = "user.created")]
+    UserCreated,
+    /// A tournament final is over, the data is the tournament.
+    #[serde(rename = "tournament.completed")]
+    TournamentCompleted,
+}
+
+/// The new webhook schema. It's used to register a webhook.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, derive_new::new)]
+#[salvo(schema(symbol = "NewWebhookSchema", example = json!(NewWebhookSchema::new("https://example.com/xors".to_owned(), vec![WebhookEvent::GameFinished]))))]
+pub struct NewWebhookSchema {
+    /// The HTTPS endpoint that receives the events.
+    pub url: String,
+    /// The events to receive, at least one.
+    pub events: Vec<WebhookEvent>,
+}
+
+/// The webhook schema.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "WebhookSchema", example = json!(WebhookSchema::default())))]
+pub struct WebhookSchema {
+    /// The webhook uuid.
+    pub uuid: Uuid,
+    /// The admin that registered the webhook.
+    pub owner: Uuid,
+    /// The HTTPS endpoint that receives the events.
+    pub url: String,
+    /// The events that the webhook receives.
+    pub events: Vec<WebhookEvent>,
+    /// The secret of the payloads signature, only returned when the webhook is registered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// When the webhook is registered.
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// The webhook delivery schema, a delivery of an event to the webhook with its attempts result.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[salvo(schema(symbol = "WebhookDeliverySchema", example = json!(WebhookDeliverySchema::default())))]
+pub struct WebhookDeliverySchema {
+    /// The delivery uuid, it's the `X-Xors-Delivery` header.
+    pub uuid: Uuid,
+    /// The delivered event.
+    pub event: WebhookEvent,
+    /// The sent payload.
+    #[salvo(schema(value_type = Object))]
+    pub payload: serde_json::Value,
+    /// The delivery attempts count.
+    pub attempts: i16,
+    /// The response status code of the last attempt. Null if the endpoint didn't respond.
+    pub status_code: Option<u16>,
+    /// The error of the last attempt. Null if the endpoint responded.
+    pub error: Option<String>,
+    /// When the endpoint accepted the delivery with a `2xx` status code. Null if it's not delivered yet.
+    pub delivered_at: Option<chrono::NaiveDateTime>,
+    /// When the event happened.
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// The webhook payload, it's the body sent to the webhook endpoint.
+#[derive(Serialize, Deserialize, Clone, Debug, derive_new::new)]
+pub struct WebhookPayload {
+    /// The delivery uuid.
+    pub id: Uuid,
+    /// The event.
+    pub event: WebhookEvent,
+    /// When the event happened.
+    pub created_at: chrono::NaiveDateTime,
+    /// The event data.
+    pub data: serde_json::Value,
+}
+
+impl WebhookEvent {
+    /// All the webhook events.
+    pub const ALL: [Self; 3] = [
+        Self::GameFinished,
+        Self::UserCreated,
+        Self::TournamentCompleted,
+    ];
+}
+
+impl ToString for WebhookEvent {
+    fn to_string(&self) -> String {
+        match self {
+            Self::GameFinished => "game.finished".to_owned(),
+            Self::UserCreated => "user.created".to_owned(),
+            Self::TournamentCompleted => "tournament.completed".to_owned(),
+        }
+    }
+}
+
+impl std::str::FromStr for WebhookEvent {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|event| event.to_string() == s)
+            .ok_or(ApiError::InternalServer)
+    }
+}
+
+impl From<WebhookModel> for WebhookSchema {
+    fn from(webhook: WebhookModel) -> Self {
+        Self {
+            uuid: webhook.uuid,
+            owner: webhook.owner_uuid,
+            url: webhook.url,
+            events: webhook
+                .events
+                .split(',')
+                .map(|event| event.parse().expect("Is valid webhook event"))
+                .collect(),
+            secret: None,
+            created_at: webhook.created_at,
+        }
+    }
+}
+
+impl Default for WebhookSchema {
+    fn default() -> Self {
+        Self {
+            uuid: Uuid::new_v4(),
+            owner: Uuid::new_v4(),
+            url: "https://example.com/xors".to_owned(),
+            events: vec![WebhookEvent::GameFinished],
+            secret: None,
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+}
+
+impl From<WebhookDeliveryModel> for WebhookDeliverySchema {
+    fn from(delivery: WebhookDeliveryModel) -> Self {
+        Self {
+            uuid: delivery.uuid,
+            event: delivery.event.parse().expect("Is valid webhook event"),
+            payload: serde_json::from_str(&delivery.payload).expect("Is valid webhook payload"),
+            attempts: delivery.attempts,
+            status_code: delivery.status_code.map(|status_code| status_code as u16),
+            error: delivery.error,
+            delivered_at: delivery.delivered_at,
+            created_at: delivery.created_at,
+        }
+    }
+}
+
+impl Default for WebhookDeliverySchema {
+    fn default() -> Self {
+        let now = chrono::Utc::now().naive_utc();
+        Self {
+            uuid: Uuid::new_v4(),
+            event: WebhookEvent::GameFinished,
+            payload: serde_json::json!({"id": Uuid::new_v4(), "event": "game.finished", "created_at": now, "data": {}}),
+            attempts: 1,
+            status_code: Some(200),
+            error: None,
+            delivered_at: Some(now),
+            created_at: now,
+        }
+    }
+}
@@ -24,6 +24,8 @@ mod websocket {
     use serde::{Deserialize, Serialize};
     use uuid::Uuid;
 
+    use crate::schemas::Presence;
+
     /// The Xo websocket server event.
     #[derive(Serialize, Deserialize, Clone, Debug)]
     pub struct XoServerEvent {
@@ -40,6 +42,8 @@ mod websocket {
     pub enum XoServerEventKind {
         /// The game found event. Means that the server found a match for the player.
         GameFound,
+        /// The game resumed event. Means that the game of the previous server run is resumed.
+        GameResumed,
         /// The other player wellcome event.
         Wellcome,
         /// The chat from the other player.
@@ -56,6 +60,26 @@ mod websocket {
         AutoPlay,
         /// The game over event with the game over data.
         GameOver,
+        /// The token expiring event. Means that the player's token will expire soon and it should reauthenticate.
+        TokenExpiring,
+        /// The reauthenticated event. Means that the player's new token is accepted.
+        Reauthenticated,
+        /// The game state event. Means that the spectator joined the game, with the current game state.
+        GameState,
+        /// The opponent disconnected event. Means that the game is paused until the player reconnects.
+        OpponentDisconnected,
+        /// The opponent reconnected event. Means that the paused game is resumed.
+        OpponentReconnected,
+        /// The friend presence event. Means that the presence of a friend changed.
+        FriendPresence,
+        /// The server shutdown event. Means that the server is restarting, the connection will be closed.
+        ServerShutdown,
+        /// The queued event. Means that the player is waiting in the search queue.
+        Queued,
+        /// The match found event. Means that the server matched the player with an opponent, the game starts after it.
+        MatchFound,
+        /// The queue cancelled event. Means that the player left the search queue without a game.
+        QueueCancelled,
         /// The error event with the error data.
         Error,
     }
@@ -66,7 +90,15 @@ mod websocket {
     #[serde(untagged)]
     pub enum XoServerEventData {
         /// The game found event. Means that the server found a match for the player.
-        GameFound { x_player: Uuid, o_player: Uuid },
+        /// And the game variant, the board size and the win length.
+        GameFound {
+            x_player: Uuid,
+            o_player: Uuid,
+            #[serde(default)]
+            variant: super::GameVariant,
+        },
+        /// The game resumed event. Means that the game of the previous server run is resumed.
+        GameResumed(GameResumedData),
         /// The other player wellcome event.
         Wellcome { public_key: String },
         /// The chat from the other player.
@@ -75,9 +107,19 @@ mod websocket {
             signature: String,
         },
         /// The turn event. Means that it's the player's turn.
-        YourTurn { auto_play_after: i64 },
+        /// And the player's time left in the game in milliseconds, if the server enables the game time.
+        YourTurn {
+            auto_play_after: i64,
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            time_left: Option<i64>,
+        },
         /// The round start event with the round number of 3 rounds. starting from 1.
-        RoundStart { round: i16 },
+        /// And the places of the random opening moves, if the server enables the random opening.
+        RoundStart {
+            round: i16,
+            #[serde(default, skip_serializing_if = "Vec::is_empty")]
+            opening: Vec<u8>,
+        },
         /// The round end event.
         RoundEnd(RoundData),
         /// The play event. Means that the player played.
@@ -86,6 +128,37 @@ mod websocket {
         AutoPlay { place: u8 },
         /// The game over event with the game over data.
         GameOver(GameOverData),
+        /// The token expiring event. Means that the player's token will expire soon and it should reauthenticate.
+        TokenExpiring { expires_at: i64 },
+        /// The reauthenticated event. Means that the player's new token is accepted.
+        Reauthenticated { new_expires_at: i64 },
+        /// The game state event. Means that the spectator joined the game, with the current game state.
+        GameState(GameResumedData),
+        /// The opponent disconnected event. Means that the game is paused until the player reconnects,
+        /// if it doesn't reconnect before `reconnect_before` it loses the game.
+        OpponentDisconnected { player: Uuid, reconnect_before: i64 },
+        /// The opponent reconnected event. Means that the paused game is resumed.
+        OpponentReconnected { player: Uuid },
+        /// The friend presence event. Means that the presence of a friend changed.
+        FriendPresence { friend: Uuid, presence: Presence },
+        /// The server shutdown event. Means that the server is restarting, the connection will be closed.
+        /// The in-progress game is saved, the player can reconnect in `reconnect_within` seconds after the
+        /// restart to continue it.
+        ServerShutdown { reconnect_within: u64 },
+        /// The queued event. Means that the player is waiting in the search queue, with its rating.
+        /// And the timestamp of when its search times out, if the server has a queue timeout.
+        Queued {
+            rating: i64,
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            timeout_at: Option<i64>,
+        },
+        /// The match found event. Means that the server matched the player with an opponent, with its rating.
+        MatchFound {
+            opponent: Uuid,
+            opponent_rating: i64,
+        },
+        /// The queue cancelled event. Means that the player left the search queue without a game.
+        QueueCancelled { reason: QueueCancelReason },
         /// The error event with the error data.
         Error(ErrorData),
     }
@@ -112,6 +185,16 @@ mod websocket {
         Wellcome,
         /// Send a chat message to the other player.
         Chat,
+        /// Present a refreshed token to keep the connection alive.
+        Reauthenticate,
+        /// Start a game against the server AI opponent.
+        PlayBot,
+        /// Join the private game of an invite code.
+        JoinCode,
+        /// Play the next match in a tournament.
+        PlayTournament,
+        /// Cancel the search, leave the search queue.
+        CancelSearch,
     }
 
     /// The Xo client events data.
@@ -120,7 +203,13 @@ mod websocket {
     #[serde(untagged)]
     pub enum XoClientEventsData {
         /// The play data.
-        Play { place: u8 },
+        Play {
+            place: u8,
+            /// The number of the current round moves that the client has seen, if it's not the same as the
+            /// server the move will be rejected with `stale_state` error.
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            seen_moves: Option<u8>,
+        },
         /// Wellcome data, Your PGP public key.
         Wellcome { public_key: String },
         /// Chat data.
@@ -128,19 +217,30 @@ mod websocket {
             encrypted_message: String,
             signature: String,
         },
+        /// Reauthenticate data, The new JWT token.
+        Reauthenticate { token: String },
+        /// Play bot data, The AI opponent difficulty.
+        PlayBot { difficulty: BotDifficulty },
+        /// Join code data, The invite code.
+        JoinCode { code: String },
+        /// Play tournament data, The tournament uuid.
+        PlayTournament { tournament: Uuid },
     }
 
     /// The Xo play data.
     #[derive(Serialize, Deserialize, Clone, Debug, derive_new::new)]
     pub struct PlayData {
         /// The place that the player want to play in.
-        /// The place is a number between 0 and 8.
+        /// The place is the cell index row by row, between 0 and 8 in the classic board.
         /// | 0 | 1 | 2 |
         /// | 3 | 4 | 5 |
         /// | 6 | 7 | 8 |
         pub place: u8,
         /// The player's uuid.
         pub player: Uuid,
+        /// The player's time left in the game after the move in milliseconds, if the server enables the game time.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub time_left: Option<i64>,
     }
 
     /// The Xo game over data.
@@ -170,6 +270,33 @@ mod websocket {
         pub x_player: Uuid,
         /// The O player's uuid.
         pub o_player: Uuid,
+        /// The game variant.
+        #[serde(default)]
+        pub variant: super::GameVariant,
+    }
+
+    /// The Xo game resumed data.
+    #[allow(clippy::too_many_arguments)]
+    #[derive(Serialize, Deserialize, Clone, Debug, derive_new::new)]
+    pub struct GameResumedData {
+        /// The game uuid.
+        pub uuid: Uuid,
+        /// The X player's uuid.
+        pub x_player: Uuid,
+        /// The O player's uuid.
+        pub o_player: Uuid,
+        /// The current round number.
+        pub round: i16,
+        /// The current round board.
+        pub board: super::Board,
+        /// The ended rounds result.
+        pub rounds_result: super::RoundsResult,
+        /// The X player's time left in the game in milliseconds, if the server enables the game time.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub x_time_left: Option<i64>,
+        /// The O player's time left in the game in milliseconds, if the server enables the game time.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub o_time_left: Option<i64>,
     }
 
     /// The Xo game over reason.
@@ -183,6 +310,33 @@ mod websocket {
         Draw,
         /// The game is over because the player left.
         PlayerDisconnected,
+        /// The game is ended by a moderator, without a winner.
+        ForceEnded,
+        /// The game is over because the player ran out of its time.
+        Timeout,
+    }
+
+    /// The AI opponent difficulty.
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, ToSchema)]
+    #[serde(rename_all = "snake_case")]
+    #[salvo(schema(symbol = "BotDifficultySchema", example = json!(BotDifficulty::Perfect)))]
+    pub enum BotDifficulty {
+        /// The bot plays a random move.
+        Random,
+        /// The bot wins if it can, blocks the other player win, else prefers the center then the corners.
+        Heuristic,
+        /// The bot plays the perfect move (minimax), it never loses.
+        Perfect,
+    }
+
+    /// Why the player left the search queue without a game.
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum QueueCancelReason {
+        /// The player waited in the search queue until the queue timeout.
+        Timeout,
+        /// The player cancelled its search.
+        Cancelled,
     }
 
     /// The Xo error reasons
@@ -197,6 +351,8 @@ mod websocket {
         InvalidEventDataForEvent,
         /// Already in search. (You can't search for a game while you are in search)
         AlreadyInSearch,
+        /// Not in search. (You can't cancel the search while you are not in search)
+        NotInSearch,
         /// Already wellcomed. (You can't wellcome the other player while you are already wellcomed)
         AlreadyWellcomed,
         /// Chat not allowed. (You can't chat while you didn't wellcome the other player)
@@ -209,16 +365,46 @@ mod websocket {
         InvalidChatMessage,
         /// Invalid chat signature. (The chat signature is not valid PGP signature)
         InvalidChatSignature,
+        /// Chat message too large. (The chat message is larger than the max size, depends on the server configuration)
+        ChatMessageTooLarge,
+        /// Chat rate limited. (You sent the max chat messages in the last 10 seconds, depends on the server configuration)
+        ChatRateLimited,
+        /// Chat muted. (You are temporarily muted in the game chat after violating the chat limits)
+        ChatMuted,
+        /// Blocked. (You can't chat with a player that blocked you)
+        Blocked,
         /// Already in game. (You can't search for a game while you are in a game)
         AlreadyInGame,
         /// Not in game. (You can't play while you are not in a game)
         NotInGame,
-        /// Not your turn. (You can't play now)
-        NotYourTurn,
-        /// Invalid place. (The place is already taken)
-        InvalidPlace,
         /// Maximum online games reached. (Depends on the server configuration)
         MaxGamesReached,
+        /// Daily games limit reached. (You started the maximum games in the last 24 hours, depends on the server configuration)
+        DailyLimitReached,
+        /// Invalid token. (The reauthenticate token is invalid, expired or for another user)
+        InvalidToken,
+        /// Reauthentication disabled. (The server closes the connection when the token expires)
+        ReauthenticationDisabled,
+        /// Spectating. (You can't send events while spectating a game)
+        Spectating,
+        /// Invalid invite code. (The invite code is invalid, expired or already used)
+        InvalidInviteCode,
+        /// Cannot join own invite. (You can't join the private game of your own invite)
+        CannotJoinOwnInvite,
+        /// Invite host unavailable. (The invite host is not connected or can't start a game now)
+        InviteHostUnavailable,
+        /// No tournament match. (You don't have a match to play in the tournament now)
+        NoTournamentMatch,
+        /// Game paused. (You can't play while the game is paused for the other player to reconnect)
+        GamePaused,
+        /// Server shutting down. (The server is restarting, reconnect after it to continue your game)
+        ServerShuttingDown,
+        /// Already playing each other. (You already have the in-progress game `game_id` against the
+        /// only players you can be matched with now, depends on the server configuration)
+        AlreadyPlayingEachOther { game_id: Uuid },
+        /// Invalid move. (The move is rejected, see [`MoveError`](super::MoveError))
+        #[serde(untagged)]
+        InvalidMove(super::MoveError),
         /// Other error.
         #[serde(untagged)]
         Other(String),
@@ -243,6 +429,7 @@ mod websocket {
         pub fn kind(&self) -> XoServerEventKind {
             match self {
                 Self::GameFound { .. } => XoServerEventKind::GameFound,
+                Self::GameResumed(_) => XoServerEventKind::GameResumed,
                 Self::Wellcome { .. } => XoServerEventKind::Wellcome,
                 Self::Chat { .. } => XoServerEventKind::Chat,
                 Self::YourTurn { .. } => XoServerEventKind::YourTurn,
@@ -251,6 +438,16 @@ mod websocket {
                 Self::Play(_) => XoServerEventKind::Play,
                 Self::AutoPlay { .. } => XoServerEventKind::AutoPlay,
                 Self::GameOver(_) => XoServerEventKind::GameOver,
+                Self::TokenExpiring { .. } => XoServerEventKind::TokenExpiring,
+                Self::Reauthenticated { .. } => XoServerEventKind::Reauthenticated,
+                Self::GameState(_) => XoServerEventKind::GameState,
+                Self::OpponentDisconnected { .. } => XoServerEventKind::OpponentDisconnected,
+                Self::OpponentReconnected { .. } => XoServerEventKind::OpponentReconnected,
+                Self::FriendPresence { .. } => XoServerEventKind::FriendPresence,
+                Self::ServerShutdown { .. } => XoServerEventKind::ServerShutdown,
+                Self::Queued { .. } => XoServerEventKind::Queued,
+                Self::MatchFound { .. } => XoServerEventKind::MatchFound,
+                Self::QueueCancelled { .. } => XoServerEventKind::QueueCancelled,
                 Self::Error(_) => XoServerEventKind::Error,
             }
         }
@@ -268,6 +465,8 @@ mod websocket {
                 GameOverReason::PlayerWon => "Player Won".to_owned(),
                 GameOverReason::Draw => "Draw".to_owned(),
                 GameOverReason::PlayerDisconnected => "Player Disconnected".to_owned(),
+                GameOverReason::ForceEnded => "Force Ended".to_owned(),
+                GameOverReason::Timeout => "Timeout".to_owned(),
             }
         }
     }
@@ -281,6 +480,29 @@ mod websocket {
         }
     }
 
+    impl ToString for BotDifficulty {
+        fn to_string(&self) -> String {
+            match self {
+                BotDifficulty::Random => "random".to_owned(),
+                BotDifficulty::Heuristic => "heuristic".to_owned(),
+                BotDifficulty::Perfect => "perfect".to_owned(),
+            }
+        }
+    }
+
+    impl FromStr for BotDifficulty {
+        type Err = ();
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "random" => Ok(Self::Random),
+                "heuristic" => Ok(Self::Heuristic),
+                "perfect" => Ok(Self::Perfect),
+                _ => Err(()),
+            }
+        }
+    }
+
     impl FromStr for GameOverReason {
         type Err = ();
 
@@ -289,6 +511,8 @@ mod websocket {
                 "Player Won" => Ok(Self::PlayerWon),
                 "Draw" => Ok(Self::Draw),
                 "Player Disconnected" => Ok(Self::PlayerDisconnected),
+                "Force Ended" => Ok(Self::ForceEnded),
+                "Timeout" => Ok(Self::Timeout),
                 _ => Err(()),
             }
         }
@@ -297,13 +521,37 @@ mod websocket {
 
 /// API schemas.
 mod api {
-    use std::str::FromStr;
+    use std::{collections::HashMap, str::FromStr};
 
+    use rand::prelude::SliceRandom;
     use salvo::prelude::*;
     use serde::{Deserialize, Serialize};
 
+    /// The maximum random opening moves of each round.
+    pub const MAX_OPENING_MOVES: u8 = 2;
+
+    /// The minimum board size of the game variants.
+    pub const MIN_BOARD_SIZE: u8 = 3;
+
+    /// The maximum board size of the game variants.
+    pub const MAX_BOARD_SIZE: u8 = 15;
+
+    /// The game variant, the board size and the cells count in a row to win.
+    ///
+    /// The classic variant is the 3x3 board with 3 in a row.
+    #[derive(
+        Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, ToSchema, derive_new::new,
+    )]
+    #[salvo(schema(symbol = "GameVariantSchema", example = json!(GameVariant::new(15, 5))))]
+    pub struct GameVariant {
+        /// The board size, the board is `size` rows of `size` cells. Between 3 and 15.
+        pub size: u8,
+        /// The cells count in a row, column or diagonal to win. Between 3 and the board size.
+        pub win_length: u8,
+    }
+
     /// The Xo symbol.
-    #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, ToSchema)]
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, ToSchema)]
     #[salvo(schema(symbol = "XoSymbolSchema", example = json!{XoSymbol::X}))]
     pub enum XoSymbol {
         /// The X symbol.
@@ -312,13 +560,40 @@ mod api {
         O,
     }
 
+    /// The reason of rejecting a move.
+    #[derive(
+        Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, ToSchema, thiserror::Error,
+    )]
+    #[serde(rename_all = "snake_case")]
+    #[salvo(schema(symbol = "MoveErrorCode", example = json!(MoveError::CellOccupied)))]
+    pub enum MoveError {
+        /// The place is not in the board, the places are between 0 and the board cells count minus 1.
+        #[error("The place is out of the board")]
+        OutOfBounds,
+        /// The place is already played.
+        #[error("The cell is already occupied")]
+        CellOccupied,
+        /// It's the other player's turn.
+        #[error("It's not your turn")]
+        NotYourTurn,
+        /// The round is over, no more moves can be played.
+        #[error("The round is over")]
+        GameOver,
+        /// The move is based on an old board.
+        #[error("The move is based on a stale board state")]
+        StaleState,
+    }
+
     /// The XO game board.
-    #[derive(Serialize, Deserialize, Clone, Debug, Default, ToSchema)]
+    #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
     #[salvo(schema(symbol = "BoardSchema", example = json!(Board::default())))]
     pub struct Board {
-        /// The board cells.
-        #[salvo(madx = 9)]
-        cells: [Option<XoSymbol>; 9],
+        /// The board size, the board is `size` rows of `size` cells.
+        size: u8,
+        /// The cells count in a row, column or diagonal to win.
+        win_length: u8,
+        /// The board cells, row by row.
+        cells: Vec<Option<XoSymbol>>,
         /// The sequence of the played cells.
         /// It's shows the played cells in order by the index.
         played_cells: Vec<u32>, // HACK: The `u32` is used because the `u8` looks like binary in the swagger ui.
@@ -355,6 +630,11 @@ mod api {
             }
         }
 
+        /// Returns the boards of the ended rounds.
+        pub fn boards(&self) -> &[Board] {
+            &self.boards
+        }
+
         /// Add board to the rounds result.
         /// The board must be of an ended round.
         ///
@@ -369,18 +649,132 @@ mod api {
         }
     }
 
+    impl GameVariant {
+        /// Returns whether if the variant is the classic 3x3 board with 3 in a row.
+        pub fn is_classic(&self) -> bool {
+            self == &Self::default()
+        }
+
+        /// Check if the variant is valid, the board size is between [`MIN_BOARD_SIZE`] and [`MAX_BOARD_SIZE`]
+        /// and the win length is between 3 and the board size.
+        pub fn is_valid(&self) -> bool {
+            (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&self.size)
+                && (MIN_BOARD_SIZE..=self.size).contains(&self.win_length)
+        }
+    }
+
+    impl Default for GameVariant {
+        fn default() -> Self {
+            Self::new(3, 3)
+        }
+    }
+
+    impl Default for Board {
+        fn default() -> Self {
+            Self::new(GameVariant::default())
+        }
+    }
+
     impl Board {
+        /// Returns a new empty board of the variant.
+        ///
+        /// ### Panics
+        /// - Panics if the variant is invalid, see [`GameVariant::is_valid`].
+        pub fn new(variant: GameVariant) -> Self {
+            assert!(variant.is_valid(), "The game variant is invalid");
+
+            Self {
+                size: variant.size,
+                win_length: variant.win_length,
+                cells: vec![None; variant.size as usize * variant.size as usize],
+                played_cells: Vec::new(),
+            }
+        }
+
+        /// Returns the board variant.
+        pub fn variant(&self) -> GameVariant {
+            GameVariant::new(self.size, self.win_length)
+        }
+
         /// Set the board cell.
         ///
         /// ### Panics
-        /// - Panics if the index is not between 0 and 8.
+        /// - Panics if the index is out of the board.
         pub fn set_cell(&mut self, index: u8, symbol: XoSymbol) {
-            assert!(index <= 8, "The index must be between 0 and 8");
+            assert!(
+                (index as usize) < self.cells.len(),
+                "The index must be in the board"
+            );
 
             self.cells[index as usize] = Some(symbol);
             self.played_cells.push(index as u32);
         }
 
+        /// Returns a new classic board with `moves` random moves played, see [`Board::random_variant_opening`].
+        ///
+        /// ### Panics
+        /// - Panics if the moves is more than [`MAX_OPENING_MOVES`].
+        pub fn random_opening(moves: u8) -> Self {
+            Self::random_variant_opening(GameVariant::default(), moves)
+        }
+
+        /// Returns a new board of the variant with `moves` random moves played, starting from X. For the random opening.
+        ///
+        /// ### Panics
+        /// - Panics if the moves is more than [`MAX_OPENING_MOVES`].
+        /// - Panics if the variant is invalid, see [`GameVariant::is_valid`].
+        pub fn random_variant_opening(variant: GameVariant, moves: u8) -> Self {
+            assert!(
+                moves <= MAX_OPENING_MOVES,
+                "The opening moves must be at most {MAX_OPENING_MOVES}"
+            );
+
+            let mut board = Self::new(variant);
+            for _ in 0..moves {
+                let place = *board
+                    .empty_cells()
+                    .choose(&mut rand::thread_rng())
+                    .expect("The opening moves can't fill the board");
+                board.set_cell(place, board.turn());
+            }
+            board
+        }
+
+        /// Play a move in the board, returns the reason if the move is rejected.
+        ///
+        /// If `seen_moves` is provided, it must be the number of the played cells, otherwise the move is
+        /// based on a stale board state.
+        pub fn make_move(
+            &mut self,
+            place: u8,
+            symbol: XoSymbol,
+            seen_moves: Option<u8>,
+        ) -> Result<(), MoveError> {
+            if self.is_end() {
+                return Err(MoveError::GameOver);
+            }
+            if seen_moves.is_some_and(|seen_moves| seen_moves as usize != self.played_cells.len()) {
+                return Err(MoveError::StaleState);
+            }
+            if self.turn() != symbol {
+                return Err(MoveError::NotYourTurn);
+            }
+            if place as usize >= self.cells.len() {
+                return Err(MoveError::OutOfBounds);
+            }
+            if !self.is_empty_cell(place) {
+                return Err(MoveError::CellOccupied);
+            }
+
+            self.set_cell(place, symbol);
+            Ok(())
+        }
+
+        /// Returns the played cells, in order.
+        pub fn played_cells(&self) -> &[u32] {
+            &self.played_cells
+        }
+
         /// Check if the cell is empty.
         pub fn is_empty_cell(&self, index: u8) -> bool {
             self.cells[index as usize].is_none()
@@ -388,7 +782,7 @@ mod api {
 
         /// Returns the empty cells.
         pub fn empty_cells(&self) -> Vec<u8> {
-            (0..=8)
+            (0..self.cells.len() as u8)
                 .filter(|&index| self.is_empty_cell(index))
                 .collect::<Vec<_>>()
         }
@@ -404,6 +798,11 @@ mod api {
             self.is_draw() || self.is_win(&XoSymbol::X) || self.is_win(&XoSymbol::O)
         }
 
+        /// Returns the board cells, row by row.
+        pub fn cells(&self) -> &[Option<XoSymbol>] {
+            &self.cells
+        }
+
         /// Returns the symbol turn.
         ///
         /// ## Explanation
@@ -423,16 +822,64 @@ mod api {
             self.is_full() && !self.is_win(&XoSymbol::X) && !self.is_win(&XoSymbol::O)
         }
 
-        /// Check if the symbol is win.
+        /// Check if the symbol is win, it has `win_length` cells in a row, column or diagonal.
         pub fn is_win(&self, symbol: &XoSymbol) -> bool {
-            crate::api::xo::WINNING_COMBINATIONS
-                .iter()
-                .any(|&(a, b, c)| {
-                    self.cells[a].as_ref() == Some(symbol)
-                        && self.cells[b].as_ref() == Some(symbol)
-                        && self.cells[c].as_ref() == Some(symbol)
+            let size = self.size as isize;
+            let is_symbol = |row: isize, column: isize| {
+                (0..size).contains(&row)
+                    && (0..size).contains(&column)
+                    && self.cells[(row * size + column) as usize].as_ref() == Some(symbol)
+            };
+
+            (0..size)
+                .flat_map(|row| (0..size).map(move |column| (row, column)))
+                .any(|(row, column)| {
+                    // Right, down, down-right and down-left
+                    [(0, 1), (1, 0), (1, 1), (1, -1)]
+                        .iter()
+                        .any(|(row_step, column_step)| {
+                            (0..self.win_length as isize).all(|step| {
+                                is_symbol(row + row_step * step, column + column_step * step)
+                            })
+                        })
                 })
         }
+
+        /// Returns the board outcome with a perfect play (minimax), for the symbol turn.
+        /// `1` if it can force a win, `0` for a draw and `-1` if the other symbol can force a win.
+        ///
+        /// **Note**: The minimax is only practical for the classic board, see [`GameVariant::is_classic`].
+        pub fn perfect_play_outcome(&self) -> i8 {
+            self.minimax(&mut HashMap::new())
+        }
+
+        /// The minimax of the board, memoized by the board cells.
+        fn minimax(&self, memo: &mut HashMap<Vec<Option<XoSymbol>>, i8>) -> i8 {
+            if self.is_win(&XoSymbol::X) || self.is_win(&XoSymbol::O) {
+                // The other symbol played the last move
+                return -1;
+            }
+            if self.is_full() {
+                return 0;
+            }
+            if let Some(outcome) = memo.get(&self.cells) {
+                return *outcome;
+            }
+
+            let turn = self.turn();
+            let outcome = self
+                .empty_cells()
+                .into_iter()
+                .map(|index| {
+                    let mut board = self.clone();
+                    board.set_cell(index, turn);
+                    -board.minimax(memo)
+                })
+                .max()
+                .expect("The board is not full");
+            memo.insert(self.cells.clone(), outcome);
+            outcome
+        }
     }
 
     impl ToString for XoSymbol {
@@ -444,24 +891,54 @@ mod api {
         }
     }
 
+    impl ToString for GameVariant {
+        fn to_string(&self) -> String {
+            format!("{}x{}", self.size, self.win_length)
+        }
+    }
+
+    /// The game variant, the board size and the win length separated by `x`.
+    ///
+    /// # Errors
+    /// - Will return `Err(())` if the variant is not in the format or it's invalid, see [`GameVariant::is_valid`].
+    ///
+    /// # Examples
+    /// - "15x5" is valid.
+    impl FromStr for GameVariant {
+        type Err = ();
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let (size, win_length) = s.split_once('x').ok_or(())?;
+            let variant = Self::new(
+                size.parse().map_err(|_| ())?,
+                win_length.parse().map_err(|_| ())?,
+            );
+            variant.is_valid().then_some(variant).ok_or(())
+        }
+    }
+
     impl ToString for Board {
         fn to_string(&self) -> String {
-            format!(
-                "{}:{}",
-                self.cells
-                    .iter()
-                    .map(|cell| match cell {
-                        Some(symbol) => symbol.to_string(),
-                        None => "-".to_owned(),
-                    })
-                    .collect::<Vec<_>>()
-                    .join(""),
-                self.played_cells
-                    .iter()
-                    .map(|cell| cell.to_string())
-                    .collect::<Vec<_>>()
-                    .join("")
-            )
+            let cells = self
+                .cells
+                .iter()
+                .map(|cell| match cell {
+                    Some(symbol) => symbol.to_string(),
+                    None => "-".to_owned(),
+                })
+                .collect::<Vec<_>>()
+                .join("");
+            let played_cells = self.played_cells.iter().map(|cell| cell.to_string());
+
+            if self.variant().is_classic() {
+                format!("{cells}:{}", played_cells.collect::<Vec<_>>().join(""))
+            } else {
+                format!(
+                    "{}:{cells}:{}",
+                    self.variant().to_string(),
+                    played_cells.collect::<Vec<_>>().join(".")
+                )
+            }
         }
     }
 
@@ -481,42 +958,52 @@ mod api {
         }
     }
 
-    /// The XO symbol. The symbol is either "X" or "O" and "-" for empty cell.
+    /// The XO board. The cells symbol is either "X" or "O" and "-" for empty cell, then the played cells in order.
+    /// The classic board played cells are digits, the other variants boards are prefixed with the variant and
+    /// their played cells are separated by `.`.
     ///
     /// # Errors
-    /// - Will return `Err(())`  if there is an invalid symbol or the string length is not 9.
+    /// - Will return `Err(())`  if there is an invalid symbol or the cells count is not the variant cells count.
     /// - Will return `Err(())` if the played cells is invalid
     ///
     /// # Examples
     /// - "XOXOXOXOX:03214658" is valid.
+    /// - "4x3:X----O----------:0.5" is valid.
     impl FromStr for Board {
         type Err = ();
 
         fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let (variant, s) = match s.split(':').count() {
+                2 => (GameVariant::default(), s),
+                3 => {
+                    let (variant, s) = s.split_once(':').expect("There is a `:`");
+                    (GameVariant::from_str(variant)?, s)
+                }
+                _ => return Err(()),
+            };
             let (str_board, played_cells) = s.split_once(':').ok_or(())?;
             let board_chars = str_board.chars().collect::<Vec<_>>();
 
-            if board_chars.len() != 9 {
-                return Err(());
-            }
-            if played_cells.chars().count() > 9 || played_cells.chars().any(|c| !c.is_ascii_digit())
-            {
+            let played_cells = if variant.is_classic() {
+                played_cells
+                    .chars()
+                    .map(|c| c.to_digit(10).map(|index| index as usize).ok_or(()))
+                    .collect::<Result<Vec<_>, _>>()?
+            } else if played_cells.is_empty() {
+                Vec::new()
+            } else {
+                played_cells
+                    .split('.')
+                    .map(|index| index.parse::<usize>().map_err(|_| ()))
+                    .collect::<Result<Vec<_>, _>>()?
+            };
+
+            let mut board = Self::new(variant);
+            if board_chars.len() != board.cells.len() || played_cells.len() > board.cells.len() {
                 return Err(());
             }
-
-            let mut board = Self::default();
-            for index in played_cells
-                .chars()
-                .map(|c| c.to_digit(10).expect("The digit is valid") as usize)
-            {
-                if index > 8 {
-                    return Err(());
-                }
-
-                let symbol = match board_chars
-                    .get(index)
-                    .expect("The index is valid because the played cells is valid")
-                {
+            for index in played_cells {
+                let symbol = match board_chars.get(index).ok_or(())? {
                     'X' => XoSymbol::X,
                     'O' => XoSymbol::O,
                     _ => return Err(()),
@@ -0,0 +1,92 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::str::FromStr;
+
+use migration::{Migrator, MigratorTrait};
+
+/// The minimum length of the secret key.
+pub const MIN_SECRET_KEY_LENGTH: usize = 32;
+
+/// The startup checks, the server will not start if one of them failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartupCheck {
+    /// The database is reachable.
+    Database,
+    /// All the migrations are applied.
+    Migrations,
+    /// The secret key is at least [`MIN_SECRET_KEY_LENGTH`] characters.
+    SecretKey,
+}
+
+/// The startup check failure.
+#[derive(Debug, thiserror::Error)]
+pub enum StartupError {
+    #[error("The database is unreachable: {0}")]
+    UnreachableDatabase(sea_orm::DbErr),
+    #[error("Failed to get the migrations status: {0}")]
+    MigrationsStatus(sea_orm::DbErr),
+    #[error("There is {0} pending migrations, run the migrations first")]
+    PendingMigrations(usize),
+    #[error("The secret key must be at least {MIN_SECRET_KEY_LENGTH} characters")]
+    ShortSecretKey,
+}
+
+impl FromStr for StartupCheck {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "database" => Ok(Self::Database),
+            "migrations" => Ok(Self::Migrations),
+            "secret_key" => Ok(Self::SecretKey),
+            _ => Err(format!(
+                "Invalid startup check `{s}`, expected `database`, `migrations` or `secret_key`"
+            )),
+        }
+    }
+}
+
+/// Run the startup checks, except the skipped ones. Returns the first failure.
+pub async fn self_check(
+    conn: &sea_orm::DatabaseConnection,
+    secret_key: &str,
+    skipped: &[StartupCheck],
+) -> Result<(), StartupError> {
+    let is_enabled = |check| !skipped.contains(&check);
+
+    if is_enabled(StartupCheck::Database) {
+        log::info!("Checking the database connection");
+        conn.ping()
+            .await
+            .map_err(StartupError::UnreachableDatabase)?;
+    }
+    if is_enabled(StartupCheck::Migrations) {
+        log::info!("Checking the database migrations");
+        let pending = Migrator::get_pending_migrations(conn)
+            .await
+            .map_err(StartupError::MigrationsStatus)?
+            .len();
+        if pending != 0 {
+            return Err(StartupError::PendingMigrations(pending));
+        }
+    }
+    if is_enabled(StartupCheck::SecretKey) && secret_key.chars().count() < MIN_SECRET_KEY_LENGTH {
+        return Err(StartupError::ShortSecretKey);
+    }
+
+    Ok(())
+}
@@ -14,11 +14,14 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::cmp::Ordering;
+use std::{cmp::Ordering, collections::HashSet, net::IpAddr};
 
+use argon2::{password_hash::SaltString, PasswordHasher as _, PasswordVerifier as _};
 use base64::Engine;
 use image::GenericImageView;
+use once_cell::sync::OnceCell;
 use passwords::{analyzer, scorer};
+use rand::prelude::SliceRandom;
 use uuid::Uuid;
 
 use crate::{
@@ -43,61 +46,286 @@ pub fn validate_user_name<const IS_FIRST_NAME: bool>(name: &str) -> ApiResult<()
     Ok(())
 }
 
-/// Validates the user's password.
+/// The longest accepted password, the bcrypt hashes of the old accounts ignore the rest of the password.
+pub const MAX_PASSWORD_LENGTH: usize = 72;
+
+/// The password policy, the rules that the new passwords must follow.
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    /// The minimum password length, in characters.
+    pub min_length: usize,
+    /// The maximum password length, in characters. At most [`MAX_PASSWORD_LENGTH`].
+    pub max_length: usize,
+    /// Whether the password must contain at least one uppercase letter.
+    pub require_uppercase: bool,
+    /// Whether the password must contain at least one lowercase letter.
+    pub require_lowercase: bool,
+    /// Whether the password must contain at least one number.
+    pub require_digit: bool,
+    /// Whether the password must contain at least one symbol.
+    pub require_symbol: bool,
+    /// Whether to reject the common passwords.
+    pub block_common: bool,
+    /// More passwords to reject, in lowercase. They are compared case-insensitively.
+    pub blocklist: HashSet<String>,
+    /// The minimum password strength score, between 0 and 100. 0 disables the check.
+    pub min_score: f64,
+}
+
+/// The password policy rule that the password violates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordRule {
+    /// The password length is out of the policy bounds.
+    Length { min: usize, max: usize },
+    /// The password contains spaces.
+    Spaces,
+    /// The password doesn't contain an uppercase letter.
+    Uppercase,
+    /// The password doesn't contain a lowercase letter.
+    Lowercase,
+    /// The password doesn't contain a number.
+    Digit,
+    /// The password doesn't contain a symbol.
+    Symbol,
+    /// The password is common or in the blocklist.
+    Common,
+    /// The password strength score is lower than the policy minimum.
+    Weak,
+}
+
+impl PasswordPolicy {
+    /// Checks that the policy makes sense, the length bounds must be valid and the score between 0 and 100.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_length == 0 || self.min_length > self.max_length {
+            return Err(
+                "The minimum password length must be between 1 and the maximum length".to_owned(),
+            );
+        }
+        if self.max_length > MAX_PASSWORD_LENGTH {
+            return Err(format!(
+                "The maximum password length must be at most {MAX_PASSWORD_LENGTH}"
+            ));
+        }
+        if !(0.0..=100.0).contains(&self.min_score) {
+            return Err("The minimum password score must be between 0 and 100".to_owned());
+        }
+        Ok(())
+    }
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 64,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_digit: true,
+            require_symbol: true,
+            block_common: true,
+            blocklist: HashSet::new(),
+            min_score: 80.0,
+        }
+    }
+}
+
+impl PasswordRule {
+    /// Returns the stable code of the rule, it's the error code of the violation.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Length { .. } => "password_length",
+            Self::Spaces => "password_contains_spaces",
+            Self::Uppercase => "password_missing_uppercase",
+            Self::Lowercase => "password_missing_lowercase",
+            Self::Digit => "password_missing_digit",
+            Self::Symbol => "password_missing_symbol",
+            Self::Common => "password_common",
+            Self::Weak => "password_too_weak",
+        }
+    }
+}
+
+impl std::fmt::Display for PasswordRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Length { min, max } => {
+                write!(f, "The password must be between {min} and {max} characters")
+            }
+            Self::Spaces => write!(f, "The password contains spaces"),
+            Self::Uppercase => write!(f, "The password must contain at least one uppercase letter"),
+            Self::Lowercase => write!(f, "The password must contain at least one lowercase letter"),
+            Self::Digit => write!(f, "The password must contain at least one number"),
+            Self::Symbol => write!(f, "The password must contain at least one symbol"),
+            Self::Common => write!(f, "The password is common"),
+            Self::Weak => write!(f, "The password is too weak"),
+        }
+    }
+}
+
+/// Validates the user's new password with the password policy.
 ///
 /// This will be check for:
-/// - The password's length. It must be between 8 and 64 characters.
+/// - The password's length. It must be between the policy bounds.
 /// - The password's spaces. It can't contain spaces.
-/// - The password's uppercase and lowercase letters. It must contain at least one uppercase and lowercase letter.
-/// - The password's numbers. It must contain at least one number.
-/// - The password's symbols. It must contain at least one symbol.
-/// - The password's commonness. It can't be a common password.
-/// - The password's strength. It must be at least 80% strong.
+/// - The password's uppercase and lowercase letters, numbers and symbols, if the policy requires them.
+/// - The password's commonness. It can't be a common password or in the policy blocklist.
+/// - The password's strength. It must be at least as strong as the policy minimum score.
 #[must_use = "This function returns a `ApiResult<()>` instead of panicking"]
-pub fn validate_password(password: &str) -> ApiResult<()> {
+pub fn validate_password(password: &str, policy: &PasswordPolicy) -> ApiResult<()> {
     let password_analyzer = analyzer::analyze(password);
+    let violation = |rule| Err(ApiError::PasswordPolicyViolation(rule));
 
-    if password.chars().count() > 64 || password.chars().count() < 8 {
-        return Err(ApiError::InvalidPassword(
-            "The password must be between 8 and 64 characters".to_owned(),
-        ));
+    let length = password.chars().count();
+    if length < policy.min_length || length > policy.max_length {
+        return violation(PasswordRule::Length {
+            min: policy.min_length,
+            max: policy.max_length,
+        });
     }
     if password.chars().any(|c: char| c.is_whitespace()) {
-        return Err(ApiError::InvalidPassword(
-            "The password contains spaces".to_owned(),
-        ));
+        return violation(PasswordRule::Spaces);
     }
-    if password_analyzer.uppercase_letters_count() == 0
-        || password_analyzer.lowercase_letters_count() == 0
-    {
-        return Err(ApiError::InvalidPassword(
-            "The password must contain at least one uppercase and lowercase letter".to_owned(),
-        ));
+    if policy.require_uppercase && password_analyzer.uppercase_letters_count() == 0 {
+        return violation(PasswordRule::Uppercase);
     }
-    if password_analyzer.numbers_count() == 0 {
-        return Err(ApiError::InvalidPassword(
-            "The password must contain at least one number".to_owned(),
-        ));
+    if policy.require_lowercase && password_analyzer.lowercase_letters_count() == 0 {
+        return violation(PasswordRule::Lowercase);
     }
-    if password_analyzer.symbols_count() == 0 {
-        return Err(ApiError::InvalidPassword(
-            "The password must contain at least one symbol".to_owned(),
-        ));
+    if policy.require_digit && password_analyzer.numbers_count() == 0 {
+        return violation(PasswordRule::Digit);
     }
-    if password_analyzer.is_common() {
-        return Err(ApiError::InvalidPassword(
-            "The password is common".to_owned(),
-        ));
+    if policy.require_symbol && password_analyzer.symbols_count() == 0 {
+        return violation(PasswordRule::Symbol);
     }
-    if scorer::score(&password_analyzer) < 80.0 {
-        return Err(ApiError::InvalidPassword(
-            "The password is too weak".to_owned(),
-        ));
+    if (policy.block_common && password_analyzer.is_common())
+        || policy.blocklist.contains(&password.to_lowercase())
+    {
+        return violation(PasswordRule::Common);
+    }
+    if scorer::score(&password_analyzer) < policy.min_score {
+        return violation(PasswordRule::Weak);
     }
 
     Ok(())
 }
 
+/// Validates the signin password length only, the password policy can change after the users signup, so the old
+/// passwords must still work. The password can't be empty or longer than [`MAX_PASSWORD_LENGTH`].
+#[must_use = "This function returns a `ApiResult<()>` instead of panicking"]
+pub fn validate_signin_password(password: &str) -> ApiResult<()> {
+    let length = password.chars().count();
+    if length == 0 || length > MAX_PASSWORD_LENGTH {
+        return Err(ApiError::PasswordPolicyViolation(PasswordRule::Length {
+            min: 1,
+            max: MAX_PASSWORD_LENGTH,
+        }));
+    }
+    Ok(())
+}
+
+/// The configured passwords hasher, installed once at the server startup.
+static PASSWORD_HASHER: OnceCell<PasswordHasher> = OnceCell::new();
+
+/// The passwords hasher, it hashes the new passwords with Argon2id and verifies the Argon2 and the old bcrypt hashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordHasher {
+    /// The Argon2 memory cost, in KiB.
+    pub memory_cost: u32,
+    /// The Argon2 time cost, the number of the iterations.
+    pub time_cost: u32,
+    /// The Argon2 parallelism, the number of the lanes.
+    pub parallelism: u32,
+}
+
+impl PasswordHasher {
+    /// Checks that the Argon2 cost parameters are valid.
+    pub fn validate(&self) -> Result<(), String> {
+        self.params().map(|_| ()).map_err(|err| err.to_string())
+    }
+
+    /// Sets the hasher that the server uses, returns the hasher back if there is already an installed one.
+    pub fn install(self) -> Result<(), Self> {
+        PASSWORD_HASHER.set(self)
+    }
+
+    /// Returns the installed hasher, or the default one if no hasher is installed.
+    pub fn current() -> &'static Self {
+        PASSWORD_HASHER.get_or_init(Self::default)
+    }
+
+    fn params(&self) -> Result<argon2::Params, argon2::Error> {
+        argon2::Params::new(self.memory_cost, self.time_cost, self.parallelism, None)
+    }
+
+    fn argon2(&self) -> ApiResult<argon2::Argon2<'static>> {
+        Ok(argon2::Argon2::new(
+            argon2::Algorithm::Argon2id,
+            argon2::Version::V0x13,
+            self.params().map_err(argon2::password_hash::Error::from)?,
+        ))
+    }
+
+    /// Hashes the password with Argon2id, returns the hash in the PHC string format.
+    pub fn hash(&self, password: &str) -> ApiResult<String> {
+        let salt = SaltString::generate(&mut rand::rngs::OsRng);
+        Ok(self
+            .argon2()?
+            .hash_password(password.as_bytes(), &salt)?
+            .to_string())
+    }
+
+    /// Verifies the password against the hash, the hash can be an Argon2 hash or an old bcrypt hash.
+    pub fn verify(&self, password: &str, hash: &str) -> ApiResult<bool> {
+        if is_bcrypt_hash(hash) {
+            return Ok(bcrypt::verify(password, hash)?);
+        }
+        match argon2::Argon2::default()
+            .verify_password(password.as_bytes(), &argon2::PasswordHash::new(hash)?)
+        {
+            Ok(()) => Ok(true),
+            Err(argon2::password_hash::Error::Password) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Whether the hash should be replaced with a new one, because it's not an Argon2id hash or it's hashed with
+    /// different cost parameters.
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        let Ok(hash) = argon2::PasswordHash::new(hash) else {
+            return true;
+        };
+        hash.algorithm != argon2::Algorithm::Argon2id.ident()
+            || argon2::Params::try_from(&hash).map_or(true, |params| {
+                (params.m_cost(), params.t_cost(), params.p_cost())
+                    != (self.memory_cost, self.time_cost, self.parallelism)
+            })
+    }
+}
+
+impl Default for PasswordHasher {
+    /// The OWASP recommended costs.
+    fn default() -> Self {
+        Self {
+            memory_cost: 19 * 1024,
+            time_cost: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+/// Whether the hash is a bcrypt hash, the accounts created before Argon2id have them.
+fn is_bcrypt_hash(hash: &str) -> bool {
+    ["$2a$", "$2b$", "$2x$", "$2y$"]
+        .iter()
+        .any(|prefix| hash.starts_with(prefix))
+}
+
+/// Returns the canonical form of the username, the usernames that only differ in case or the surrounding spaces have
+/// the same canonical form, so they can't be registered twice.
+pub fn normalize_username(username: &str) -> String {
+    username.trim().to_lowercase()
+}
+
 /// Validates a user signin.
 ///
 /// This will be check for:
@@ -125,14 +353,15 @@ pub fn validate_user_signin(usersignin: &str) -> ApiResult<()> {
 /// - The user's username.
 /// - The user's password.
 #[must_use = "This function returns a `ApiResult<()>` instead of panicking"]
-pub fn validate_user_registration(user: &NewUserSchema) -> ApiResult<()> {
+pub fn validate_user_registration(user: &NewUserSchema, policy: &PasswordPolicy) -> ApiResult<()> {
     validate_user_name::<true>(&user.first_name)?;
     user.last_name
         .as_deref()
         .map(validate_user_name::<false>)
         .transpose()?;
-    validate_password(&user.password)?;
-    validate_user_signin(&user.username)?;
+    validate_password(&user.password, policy)?;
+    // The surrounding spaces are trimmed, the other spaces are rejected
+    validate_user_signin(user.username.trim())?;
     Ok(())
 }
 
@@ -157,38 +386,384 @@ pub(crate) fn game_over_data(
     }
 }
 
-/// Check if the player played a valid move. Returns true if the move is valid, false otherwise.
+/// The rating of the new users.
+pub const INITIAL_RATING: i64 = 1200;
+
+/// The ELO K-factor, the maximum rating change of a game.
+pub const RATING_K_FACTOR: f64 = 32.0;
+
+/// Returns the ELO rating changes of the X and O players after a game, they sum to zero.
 ///
-/// Note: This function will send an error message to the player if the move is invalid.
-pub(crate) fn check_move_validity(board: &Board, player: &PlayerData, place: u8) -> bool {
-    if board.turn() != player.symbol {
-        log::error!("Player {} is playing while it's not his turn", player.uuid);
+/// The `x_score` is `1.0` if the X player won, `0.0` if the O player won and `0.5` for a draw.
+pub fn rating_changes(x_rating: i64, o_rating: i64, x_score: f64) -> (i64, i64) {
+    let x_expected = 1.0 / (1.0 + 10f64.powf((o_rating - x_rating) as f64 / 400.0));
+    let x_change = (RATING_K_FACTOR * (x_score - x_expected)).round() as i64;
+    (x_change, -x_change)
+}
 
-        let _ = player.tx.send(Ok(
-            XoServerEventData::Error(ErrorData::NotYourTurn).to_message()
-        ));
-    } else if place > 8 || !board.is_empty_cell(place) {
-        log::error!(
-            "Player {} is playing in an non empty cell or invalid place",
-            player.uuid
-        );
-
-        let _ = player.tx.send(Ok(
-            XoServerEventData::Error(ErrorData::InvalidPlace).to_message()
+/// Returns the rating after the season soft reset, it moves the rating towards [`INITIAL_RATING`] by the
+/// `reset_factor`, `0.0` keeps the rating and `1.0` resets it to the initial rating.
+pub fn season_reset_rating(rating: i64, reset_factor: f64) -> i64 {
+    rating - ((rating - INITIAL_RATING) as f64 * reset_factor).round() as i64
+}
+
+/// The smallest tournament size.
+pub const MIN_TOURNAMENT_SIZE: u8 = 2;
+
+/// The largest tournament size.
+pub const MAX_TOURNAMENT_SIZE: u8 = 64;
+
+/// The draws of a tournament match after which the match goes to the higher rated player, each draw before
+/// them replays the match with the swapped sides.
+pub const MAX_TOURNAMENT_MATCH_DRAWS: u64 = 3;
+
+/// Validates the new tournament, the name must be between 3 and 64 characters and the size must be a
+/// power of two between [`MIN_TOURNAMENT_SIZE`] and [`MAX_TOURNAMENT_SIZE`].
+pub fn validate_new_tournament(new_tournament: &NewTournamentSchema) -> ApiResult<()> {
+    if !(3..=64).contains(&new_tournament.name.trim().chars().count()) {
+        return Err(ApiError::InvalidTournamentName);
+    }
+    if !new_tournament.size.is_power_of_two()
+        || !(MIN_TOURNAMENT_SIZE..=MAX_TOURNAMENT_SIZE).contains(&new_tournament.size)
+    {
+        return Err(ApiError::InvalidTournamentSize);
+    }
+    Ok(())
+}
+
+/// Returns the game variant of the board size and the win length, the size is `3` by default and the win
+/// length is the size by default, at most `5` (e.g. 5 in a row on the 15x15 board).
+pub fn game_variant(size: Option<u8>, win_length: Option<u8>) -> ApiResult<GameVariant> {
+    let size = size.unwrap_or(MIN_BOARD_SIZE);
+    let variant = GameVariant::new(size, win_length.unwrap_or(size.min(5)));
+    if !variant.is_valid() {
+        return Err(ApiError::InvalidGameVariant);
+    }
+    Ok(variant)
+}
+
+/// Returns the number of the rounds of the tournament single-elimination bracket, the last round is the final.
+pub fn tournament_rounds(size: u8) -> i16 {
+    size.trailing_zeros() as i16
+}
+
+/// The characters of the invite codes, without the similar looking characters (`0`, `O`, `1` and `I`).
+const INVITE_CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// The length of the invite codes.
+pub const INVITE_CODE_LENGTH: usize = 8;
+
+/// Returns a new random invite code.
+pub(crate) fn generate_invite_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..INVITE_CODE_LENGTH)
+        .map(|_| *INVITE_CODE_CHARS.choose(&mut rng).expect("Is not empty") as char)
+        .collect()
+}
+
+/// Returns the invite code in its stored form, the codes are not case sensitive.
+pub fn normalize_invite_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+/// The TOTP secret length in bytes, 160 bits as RFC 4226 recommends.
+const TOTP_SECRET_LENGTH: usize = 20;
+
+/// The TOTP period in seconds.
+pub const TOTP_PERIOD: i64 = 30;
+
+/// The TOTP code digits.
+pub const TOTP_DIGITS: usize = 6;
+
+/// The TOTP issuer, the authenticator apps show it with the username.
+const TOTP_ISSUER: &str = "XORS";
+
+/// The number of the recovery codes that generated when the two-factor authentication is enabled.
+pub const RECOVERY_CODES_COUNT: usize = 10;
+
+/// The length of the recovery codes, without the dash.
+pub const RECOVERY_CODE_LENGTH: usize = 10;
+
+/// Returns a new random TOTP secret, encoded as base32 without padding.
+pub(crate) fn generate_totp_secret() -> String {
+    let secret: [u8; TOTP_SECRET_LENGTH] = rand::random();
+    data_encoding::BASE32_NOPAD.encode(&secret)
+}
+
+/// Returns the `otpauth` URI of the TOTP secret, the authenticator apps can scan it as a QR code.
+pub(crate) fn totp_uri(username: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{TOTP_ISSUER}:{username}?secret={secret}&issuer={TOTP_ISSUER}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_PERIOD}"
+    )
+}
+
+/// Returns the TOTP code of the base32 secret at the timestamp (RFC 6238), `None` if the secret is invalid.
+pub fn totp_code(secret: &str, timestamp: i64) -> Option<String> {
+    use hmac::Mac;
+
+    let key = data_encoding::BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let counter = timestamp.div_euclid(TOTP_PERIOD) as u64;
+    let mut mac = hmac::Hmac::<sha1::Sha1>::new_from_slice(&key).ok()?;
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let code =
+        u32::from_be_bytes(hash[offset..offset + 4].try_into().expect("Is 4 bytes")) & 0x7fff_ffff;
+    Some(format!(
+        "{:0TOTP_DIGITS$}",
+        code % 10u32.pow(TOTP_DIGITS as u32)
+    ))
+}
+
+/// Returns the time step of the TOTP code at the timestamp, `None` if it's incorrect. The codes of the previous
+/// and the next periods are accepted for the clock drift, and they are compared in constant time.
+pub(crate) fn verify_totp_code(secret: &str, code: &str, timestamp: i64) -> Option<i64> {
+    use subtle::ConstantTimeEq;
+
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS {
+        return None;
+    }
+    let step = timestamp.div_euclid(TOTP_PERIOD);
+    (step - 1..=step + 1).find(|step| {
+        totp_code(secret, step * TOTP_PERIOD)
+            .is_some_and(|valid| valid.as_bytes().ct_eq(code.as_bytes()).into())
+    })
+}
+
+/// Returns new random recovery codes, formatted as two dash separated halves.
+pub(crate) fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODES_COUNT)
+        .map(|_| {
+            let code: String = (0..RECOVERY_CODE_LENGTH)
+                .map(|_| *INVITE_CODE_CHARS.choose(&mut rng).expect("Is not empty") as char)
+                .collect();
+            format!(
+                "{}-{}",
+                &code[..RECOVERY_CODE_LENGTH / 2],
+                &code[RECOVERY_CODE_LENGTH / 2..]
+            )
+        })
+        .collect()
+}
+
+/// Returns the stored hash of the recovery code, the codes are not case sensitive and the dashes are ignored.
+///
+/// The codes are random, so a fast hash is enough unlike the passwords.
+pub(crate) fn hash_recovery_code(code: &str) -> String {
+    use sha2::Digest;
+
+    let code = code.trim().to_uppercase().replace('-', "");
+    data_encoding::HEXLOWER.encode(&sha2::Sha256::digest(code))
+}
+
+/// The webhook secret length in bytes.
+const WEBHOOK_SECRET_LENGTH: usize = 32;
+
+/// The maximum length of the webhook URL.
+pub const MAX_WEBHOOK_URL_LENGTH: usize = 2048;
+
+/// Validates the new webhook, the URL must be an absolute HTTPS URL of at most [`MAX_WEBHOOK_URL_LENGTH`]
+/// characters, its host must resolve to public addresses only, and it must receive one event at least.
+///
+/// The HTTP URLs and the non-public addresses are allowed with `allow_local_endpoints`, for the local receivers.
+pub async fn validate_new_webhook(
+    new_webhook: &NewWebhookSchema,
+    allow_local_endpoints: bool,
+) -> ApiResult<()> {
+    let url = new_webhook.url.trim();
+    let valid_scheme =
+        url.starts_with("https://") || (allow_local_endpoints && url.starts_with("http://"));
+    let host = url
+        .parse::<salvo::http::uri::Uri>()
+        .ok()
+        .and_then(|uri| uri.host().map(str::to_owned))
+        .filter(|host| !host.is_empty());
+    let Some(host) = host else {
+        return Err(ApiError::InvalidWebhookUrl);
+    };
+    if !valid_scheme || url.len() > MAX_WEBHOOK_URL_LENGTH || url.chars().any(char::is_whitespace) {
+        return Err(ApiError::InvalidWebhookUrl);
+    }
+    if new_webhook.events.is_empty() {
+        return Err(ApiError::InvalidWebhookEvents);
+    }
+    if !allow_local_endpoints && !resolves_to_public_addresses(&host).await {
+        return Err(ApiError::InvalidWebhookUrl);
+    }
+    Ok(())
+}
+
+/// Whether the address can be reached from the internet, it's not a loopback, private, link-local or
+/// unspecified address. The webhooks endpoints can't have the other addresses, so they can't reach the
+/// server's internal network.
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                // The shared address space, 100.64.0.0/10
+                || (ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_address(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    // The unique local addresses, fc00::/7
+                    || ip.segments()[0] & 0xfe00 == 0xfc00
+                    // The link-local addresses, fe80::/10
+                    || ip.segments()[0] & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Returns the address of the URL host if it's an IP address, the IPv6 hosts are in brackets.
+pub(crate) fn host_address(host: &str) -> Option<IpAddr> {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .ok()
+}
+
+/// Resolves the host, returns whether it resolves to the public addresses only.
+async fn resolves_to_public_addresses(host: &str) -> bool {
+    if let Some(ip) = host_address(host) {
+        return is_public_address(ip);
+    }
+    match tokio::net::lookup_host((host, 0)).await {
+        Ok(addrs) => {
+            let addrs = addrs.collect::<Vec<_>>();
+            !addrs.is_empty() && addrs.iter().all(|addr| is_public_address(addr.ip()))
+        }
+        Err(_) => false,
+    }
+}
+
+/// Returns a new random webhook secret, encoded as lowercase hex.
+pub(crate) fn generate_webhook_secret() -> String {
+    let secret: [u8; WEBHOOK_SECRET_LENGTH] = rand::random();
+    data_encoding::HEXLOWER.encode(&secret)
+}
+
+/// Returns the signature of the webhook payload, the lowercase hex of its HMAC-SHA256 with the secret.
+pub fn webhook_signature(secret: &str, payload: &[u8]) -> String {
+    use hmac::Mac;
+
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts any key length");
+    mac.update(payload);
+    data_encoding::HEXLOWER.encode(&mac.finalize().into_bytes())
+}
+
+/// Decodes a compact board state and checks that it's a legal position.
+///
+/// The compact board is base64 of the board width, height and cells bytes, the cell byte is `0`
+/// for an empty cell, `1` for X and `2` for O. Only the 3x3 boards are supported.
+pub(crate) fn decode_compact_board(encoded: &str) -> ApiResult<DecodedBoardSchema> {
+    parse_compact_board(encoded).map(|board| DecodedBoardSchema::from(&board))
+}
+
+/// Parses a compact board state into a board and checks that it's a legal position.
+///
+/// See [`decode_compact_board`] for the compact board format.
+pub(crate) fn parse_compact_board(encoded: &str) -> ApiResult<Board> {
+    let invalid = |reason: &str| ApiError::InvalidBoardEncoding(reason.to_owned());
+
+    let bytes = crate::BASE_64_ENGINE
+        .decode(encoded.trim())
+        .map_err(|_| invalid("Invalid base64 string"))?;
+    let [width, height, cells @ ..] = bytes.as_slice() else {
+        return Err(invalid("The board dimensions are missing"));
+    };
+    if (*width, *height) != (3, 3) {
+        return Err(invalid("Only the 3x3 boards are supported"));
+    }
+    if cells.len() != 9 {
+        return Err(invalid(
+            "The cells count must be the board width times the height",
         ));
-    } else if board.is_end() {
-        log::error!("Player {} is playing while the round is over", player.uuid);
+    }
 
-        let _ = player.tx.send(Ok(
-            XoServerEventData::Error(ErrorData::NotYourTurn).to_message()
+    let mut board = Board::default();
+    for (index, cell) in (0..).zip(cells) {
+        match cell {
+            0 => {}
+            1 => board.set_cell(index, XoSymbol::X),
+            2 => board.set_cell(index, XoSymbol::O),
+            _ => return Err(invalid("The cell must be `0`, `1` or `2`")),
+        }
+    }
+
+    let x_count = cells.iter().filter(|cell| **cell == 1).count();
+    let o_count = cells.iter().filter(|cell| **cell == 2).count();
+    if x_count != o_count && x_count != o_count + 1 {
+        return Err(invalid(
+            "Illegal position, X plays first so X must have the same or one more cells than O",
         ));
-    } else {
-        return true;
     }
-    false
+    match (board.is_win(&XoSymbol::X), board.is_win(&XoSymbol::O)) {
+        (true, true) => return Err(invalid("Illegal position, both X and O won")),
+        (true, false) if x_count == o_count => {
+            return Err(invalid("Illegal position, O played after X won"))
+        }
+        (false, true) if x_count != o_count => {
+            return Err(invalid("Illegal position, X played after O won"))
+        }
+        _ => {}
+    }
+
+    Ok(board)
+}
+
+/// The minimum captcha length.
+pub const MIN_CAPTCHA_LENGTH: u8 = 4;
+
+/// The maximum captcha length.
+pub const MAX_CAPTCHA_LENGTH: u8 = 7;
+
+/// The captcha characters, the characters that look alike (e.g. `1` and `l`, `c` and `C`) are excluded.
+const CAPTCHA_CHARS: &str = "23456789ABCDEFGHJKMNPQRSTUVWXYZabdefghmnqrt";
+
+/// Generate a captcha of `length` characters, returns its answer and its png image, 220x120 pixels.
+/// Returns `None` if the captcha crate failed to create the image.
+pub(crate) fn generate_captcha(
+    difficulty: CaptchaDifficulty,
+    length: u8,
+) -> ApiResult<Option<(String, Vec<u8>)>> {
+    use ::captcha::filters::{Dots, Grid, Noise, Wave};
+
+    if !(MIN_CAPTCHA_LENGTH..=MAX_CAPTCHA_LENGTH).contains(&length) {
+        return Err(ApiError::InvalidCaptchaLength);
+    }
+    // The same filters of the `Amelia` captcha
+    let (noise, grid, dots, dot_min_radius) = match difficulty {
+        CaptchaDifficulty::Easy => (0.2, 8, 10, 3),
+        CaptchaDifficulty::Medium => (0.3, 6, 15, 4),
+        CaptchaDifficulty::Hard => (0.5, 4, 20, 5),
+    };
+
+    let mut captcha = ::captcha::Captcha::new();
+    captcha
+        .set_chars(&CAPTCHA_CHARS.chars().collect::<Vec<_>>())
+        .add_chars(length.into())
+        .apply_filter(Noise::new(noise))
+        .apply_filter(Grid::new(grid, grid))
+        .apply_filter(Wave::new(2.0, 10.0))
+        .view(220, 120)
+        .apply_filter(Dots::new(dots).max_radius(7).min_radius(dot_min_radius));
+    Ok(captcha.as_tuple())
 }
 
 /// Handle the captcha state and return an error if the captcha state is invalid. Otherwise, return Ok.
+///
+/// The storage errors are [`ApiError::InternalServer`], the captcha guard reports them.
 pub(crate) fn handle_captcha_state(captcha_state: &salvo_captcha::CaptchaState) -> ApiResult<()> {
     use salvo_captcha::CaptchaState::*;
 
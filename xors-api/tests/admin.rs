@@ -0,0 +1,415 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+include!("mod.rs");
+
+use sea_orm::{ActiveModelTrait, Set};
+use xors_api::api::jwt::TokenLifetimes;
+
+/// Creates a new user with the role and returns its signin data.
+async fn new_user(
+    conn: &sea_orm::DatabaseConnection,
+    username: &str,
+    role: UserRole,
+) -> UserSigninSchema {
+    let user = create_player(conn, username).await;
+    let mut active_user = db_utils::get_user(conn, user.uuid).await.unwrap();
+    active_user.role = Set(role.to_string());
+    active_user.save(conn).await.unwrap();
+
+    // The tests send several requests with the same token
+    let lifetimes = TokenLifetimes {
+        jwt: Duration::seconds(30),
+        refresh: Duration::seconds(60),
+        short_refresh: Duration::seconds(50),
+        refresh_active_after: Duration::seconds(40),
+        refresh_grace_period: Duration::zero(),
+    };
+    db_utils::signin_user(
+        conn,
+        UserSchema { role, ..user },
+        &get_jwt_keys(),
+        &lifetimes,
+        true,
+    )
+    .await
+    .expect("Failed to signin user")
+}
+
+/// Sends an authorized request to the API.
+async fn send_as(service: &Service, path: &str, method: Method, token: &str) -> Response {
+    send(
+        service,
+        path,
+        method,
+        None::<&()>,
+        vec![(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+        )],
+    )
+    .await
+}
+
+async fn error_code(res: &mut Response) -> String {
+    res.take_json::<serde_json::Value>().await.unwrap()["code"]
+        .as_str()
+        .unwrap()
+        .to_owned()
+}
+
+mod ban_user {
+    use super::*;
+
+    #[tokio::test]
+    async fn banned_user_is_rejected() {
+        let conn = get_connection().await.unwrap();
+        let service = get_service().await.unwrap();
+        let moderator = new_user(&conn, "BanModerator", UserRole::Moderator).await;
+        let user = new_user(&conn, "BannedUser", UserRole::User).await;
+        let ban_path = format!("admin/users/{}/ban", user.user.uuid);
+
+        let res = send_as(&service, &ban_path, Method::POST, &moderator.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        let mut res = send_as(&service, "auth/me", Method::GET, &user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "banned_user");
+
+        let mut res = send(
+            &service,
+            "auth/signin",
+            Method::POST,
+            Some(&SigninSchema {
+                username: user.user.username.clone(),
+                password: PLAYER_PASSWORD.to_owned(),
+                remember_me: None,
+                totp_code: None,
+            }),
+            vec![],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "banned_user");
+
+        let res = send_as(&service, &ban_path, Method::DELETE, &moderator.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let res = send_as(&service, "auth/me", Method::GET, &user.jwt).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The tokens should be accepted after the unban {res:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn requires_higher_role() {
+        let conn = get_connection().await.unwrap();
+        let service = get_service().await.unwrap();
+        let user = new_user(&conn, "BanGuardUser", UserRole::User).await;
+        let moderator = new_user(&conn, "BanGuardModerator", UserRole::Moderator).await;
+        let admin = new_user(&conn, "BanGuardAdmin", UserRole::Admin).await;
+
+        let res = send_as(
+            &service,
+            &format!("admin/users/{}/ban", moderator.user.uuid),
+            Method::POST,
+            &user.jwt,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+
+        for target in [&moderator, &admin] {
+            let res = send_as(
+                &service,
+                &format!("admin/users/{}/ban", target.user.uuid),
+                Method::POST,
+                &moderator.jwt,
+            )
+            .await;
+            assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        }
+
+        let res = send_as(
+            &service,
+            &format!("admin/users/{}/ban", Uuid::new_v4()),
+            Method::POST,
+            &admin.jwt,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::NOT_FOUND), "{res:?}");
+    }
+}
+
+mod warn_user {
+    use super::*;
+
+    /// Sends the warning to the user.
+    async fn warn(service: &Service, token: &str, user_uuid: Uuid, message: &str) -> Response {
+        send(
+            service,
+            &format!("admin/users/{user_uuid}/warn"),
+            Method::POST,
+            Some(&WarningSchema::new(message.to_owned())),
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn warned_user_is_notified() {
+        let conn = get_connection().await.unwrap();
+        let service = get_service().await.unwrap();
+        let moderator = new_user(&conn, "WarnModerator", UserRole::Moderator).await;
+        let user = new_user(&conn, "WarnedUser", UserRole::User).await;
+
+        let res = warn(&service, &moderator.jwt, user.user.uuid, " Be nice ").await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        let mut res = send_as(&service, "notifications", Method::GET, &user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let notifications = res.take_json::<Vec<NotificationSchema>>().await.unwrap();
+        assert_eq!(notifications.len(), 1);
+        assert!(matches!(
+            &notifications[0].data,
+            NotificationData::AdminWarning { message } if message == "Be nice"
+        ));
+    }
+
+    #[tokio::test]
+    async fn invalid_warning() {
+        let conn = get_connection().await.unwrap();
+        let service = get_service().await.unwrap();
+        let user = new_user(&conn, "WarnGuardUser", UserRole::User).await;
+        let moderator = new_user(&conn, "WarnGuardModerator", UserRole::Moderator).await;
+        let admin = new_user(&conn, "WarnGuardAdmin", UserRole::Admin).await;
+
+        for message in ["  ", &"a".repeat(501)] {
+            let mut res = warn(&service, &moderator.jwt, user.user.uuid, message).await;
+            assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{res:?}");
+            assert_eq!(error_code(&mut res).await, "invalid_warning_message");
+        }
+        let res = warn(&service, &user.jwt, moderator.user.uuid, "Be nice").await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        let res = warn(&service, &moderator.jwt, admin.user.uuid, "Be nice").await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+    }
+}
+
+mod force_end_game {
+    use super::*;
+
+    #[tokio::test]
+    async fn end_game_without_winner() {
+        let conn = get_connection().await.unwrap();
+        let service = get_service().await.unwrap();
+        let moderator = new_user(&conn, "EndGameModerator", UserRole::Moderator).await;
+        let x_player = new_user(&conn, "EndGameXPlayer", UserRole::User).await;
+        let o_player = new_user(&conn, "EndGameOPlayer", UserRole::User).await;
+        let game =
+            db_utils::create_game(&conn, x_player.user.uuid, o_player.user.uuid, 10, false, 0)
+                .await
+                .unwrap();
+        let path = format!("admin/games/{}/end", game.uuid.as_ref());
+
+        let res = send_as(&service, &path, Method::POST, &x_player.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+
+        let res = send_as(&service, &path, Method::POST, &moderator.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let game = db_utils::get_game::<true>(&conn, game.uuid.as_ref())
+            .await
+            .expect("The game should be ended");
+        assert_eq!(game.winner, None);
+        assert_eq!(game.reason.as_deref(), Some("Force Ended"));
+
+        let res = send_as(&service, &path, Method::POST, &moderator.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::NOT_FOUND), "{res:?}");
+    }
+}
+
+mod get_recent_signups {
+    use super::*;
+
+    #[tokio::test]
+    async fn newest_first() {
+        let conn = get_connection().await.unwrap();
+        let service = get_service().await.unwrap();
+        let moderator = new_user(&conn, "SignupsModerator", UserRole::Moderator).await;
+        let first = new_user(&conn, "SignupsFirst", UserRole::User).await;
+        let second = new_user(&conn, "SignupsSecond", UserRole::User).await;
+
+        let res = send_as(&service, "admin/signups", Method::GET, &first.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+
+        let mut res = send_as(&service, "admin/signups", Method::GET, &moderator.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let signups: Vec<Uuid> = res
+            .take_json::<Vec<UserSchema>>()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|user| user.uuid)
+            .collect();
+        let position = |uuid| signups.iter().position(|signup| *signup == uuid);
+        assert!(
+            position(second.user.uuid).unwrap() < position(first.user.uuid).unwrap(),
+            "The newest signup should come first {signups:?}"
+        );
+    }
+}
+
+mod delete_captchas {
+    use super::*;
+    use salvo_captcha::{CacacheStorage, CaptchaStorage};
+
+    #[tokio::test]
+    async fn admin_deletes_captchas() {
+        let conn = get_connection().await.unwrap();
+        let service = get_service().await.unwrap();
+        let moderator = new_user(&conn, "CaptchasModerator", UserRole::Moderator).await;
+        let admin = new_user(&conn, "CaptchasAdmin", UserRole::Admin).await;
+
+        let mut res = send(&service, "auth/captcha", Method::GET, None::<&()>, vec![]).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let token = res
+            .take_json::<CaptchaSchema>()
+            .await
+            .unwrap()
+            .captcha_token
+            .to_string();
+        let storage = CacacheStorage::new("chapcha_cache");
+        assert!(storage.get_answer(&token).await.unwrap().is_some());
+
+        let res = send_as(&service, "admin/captchas", Method::DELETE, &moderator.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        assert!(storage.get_answer(&token).await.unwrap().is_some());
+
+        let res = send_as(&service, "admin/captchas", Method::DELETE, &admin.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        assert!(storage.get_answer(&token).await.unwrap().is_none());
+    }
+}
+
+mod get_audit_log {
+    use super::*;
+
+    async fn signin(service: &Service, username: &str, password: &str) -> Response {
+        send(
+            service,
+            "auth/signin",
+            Method::POST,
+            Some(&SigninSchema {
+                username: username.to_owned(),
+                password: password.to_owned(),
+                remember_me: None,
+                totp_code: None,
+            }),
+            vec![(
+                header::USER_AGENT,
+                HeaderValue::from_static("xors-tests/1.0"),
+            )],
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn records_signins() {
+        let conn = get_connection().await.unwrap();
+        let service = get_service().await.unwrap();
+        let admin = new_user(&conn, "AuditAdmin", UserRole::Admin).await;
+        let user = new_user(&conn, "AuditUser", UserRole::User).await;
+
+        let res = signin(&service, "AuditUser", "WrongPassword1#").await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        let res = signin(&service, "AuditUser", PLAYER_PASSWORD).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        let mut res = send_as(
+            &service,
+            &format!("admin/audit?actor={}", user.user.uuid),
+            Method::GET,
+            &admin.jwt,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let events: Vec<AuditEventSchema> = res.take_json().await.unwrap();
+        assert_eq!(
+            events.iter().map(|event| event.action).collect::<Vec<_>>(),
+            vec![AuditAction::Signin, AuditAction::FailedSignin],
+            "The events should be sorted by the newest first"
+        );
+        assert_eq!(events[1].target.as_deref(), Some("AuditUser"));
+        assert_eq!(events[0].ip.as_deref(), Some("127.1.1.2"));
+        assert_eq!(events[0].user_agent.as_deref(), Some("xors-tests/1.0"));
+    }
+
+    #[tokio::test]
+    async fn filters_by_action() {
+        let conn = get_connection().await.unwrap();
+        let service = get_service().await.unwrap();
+        let admin = new_user(&conn, "AuditBanAdmin", UserRole::Admin).await;
+        let user = new_user(&conn, "AuditBannedUser", UserRole::User).await;
+
+        let res = send_as(
+            &service,
+            &format!("admin/users/{}/ban", user.user.uuid),
+            Method::POST,
+            &admin.jwt,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        let mut res = send_as(
+            &service,
+            &format!("admin/audit?action=user_ban&actor={}", admin.user.uuid),
+            Method::GET,
+            &admin.jwt,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let events: Vec<AuditEventSchema> = res.take_json().await.unwrap();
+        assert_eq!(events.len(), 1, "{events:?}");
+        assert_eq!(events[0].action, AuditAction::UserBan);
+        assert_eq!(
+            events[0].target.as_deref(),
+            Some(user.user.uuid.to_string().as_str())
+        );
+
+        let mut res = send_as(
+            &service,
+            "admin/audit?action=unknown",
+            Method::GET,
+            &admin.jwt,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "invalid_audit_action");
+    }
+
+    #[tokio::test]
+    async fn requires_admin() {
+        let conn = get_connection().await.unwrap();
+        let service = get_service().await.unwrap();
+        let moderator = new_user(&conn, "AuditModerator", UserRole::Moderator).await;
+
+        let res = send_as(&service, "admin/audit", Method::GET, &moderator.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+    }
+}
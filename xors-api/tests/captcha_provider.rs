@@ -0,0 +1,321 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+include!("mod.rs");
+
+use std::sync::Arc;
+
+use salvo::conn::Acceptor;
+use xors_api::{
+    api::{
+        captcha_provider::*,
+        risk::{CaptchaFailurePolicy, CaptchaGuard},
+    },
+    errors::ApiError,
+};
+
+/// A provider that accepts the `pass` response, and fails with the `down` response.
+struct StaticProvider;
+
+#[salvo::async_trait]
+impl CaptchaProvider for StaticProvider {
+    fn kind(&self) -> CaptchaProviderKind {
+        CaptchaProviderKind::Hcaptcha
+    }
+
+    fn site_key(&self) -> Option<&str> {
+        Some("static-site-key")
+    }
+
+    async fn verify(&self, req: &Request, _: &Depot) -> ApiResult<()> {
+        match req.header::<String>(CAPTCHA_RESPONSE_HEADER).as_deref() {
+            None => Err(ApiError::UnProvidedCaptchaResponse),
+            Some("pass") => Ok(()),
+            Some("down") => Err(ApiError::InternalServer),
+            Some(_) => Err(ApiError::InvalidCaptchaResponse),
+        }
+    }
+}
+
+/// A local `siteverify` endpoint, the `human` response has a high score and the `bot` response has a low one.
+#[handler]
+async fn site_verify(req: &mut Request, res: &mut Response) {
+    let secret = req.form::<String>("secret").await;
+    let response = req.form::<String>("response").await;
+    let body = match (secret.as_deref(), response.as_deref()) {
+        (Some("secret"), Some("human")) => r#"{"success": true, "score": 0.9}"#,
+        (Some("secret"), Some("bot")) => r#"{"success": true, "score": 0.1}"#,
+        _ => r#"{"success": false, "error-codes": ["invalid-input-response"]}"#,
+    };
+    res.render(Text::Json(body));
+}
+
+/// Serves the local `siteverify` endpoint, returns its url.
+async fn serve_site_verify() -> String {
+    let acceptor = salvo::conn::TcpListener::new("127.0.0.1:0").bind().await;
+    let addr = acceptor.holdings()[0]
+        .local_addr
+        .clone()
+        .into_std()
+        .expect("The address is a socket address");
+    tokio::spawn(Server::new(acceptor).serve(Router::with_path("siteverify").post(site_verify)));
+    format!("http://{addr}/siteverify")
+}
+
+/// Returns the service with the captcha guard.
+async fn get_guarded_service(captcha_guard: CaptchaGuard) -> Service {
+    std::env::set_var("XORS_API_TEST", "true");
+    xors_api::api::service(
+        get_connection().await.expect("Failed to get connection"),
+        get_jwt_keys(),
+        xors_api::api::ServiceConfig {
+            captcha_guard,
+            ..test_service_config()
+        },
+    )
+    .0
+}
+
+/// Sends a signup request with the captcha response, returns the response.
+async fn signup(service: &Service, username: &str, captcha_response: Option<&str>) -> Response {
+    send(
+        service,
+        "auth/signup",
+        Method::POST,
+        Some(&NewUserSchema {
+            first_name: "Captcha".to_owned(),
+            last_name: None,
+            username: username.to_owned(),
+            password: "fdlkFDLKF#$3213!".to_owned(),
+        }),
+        captcha_response
+            .map(|response| {
+                vec![(
+                    HeaderName::from_static("x-captcha-response"),
+                    HeaderValue::from_str(response).unwrap(),
+                )]
+            })
+            .unwrap_or_default(),
+    )
+    .await
+}
+
+/// Asserts the response status code and error code.
+async fn assert_error(mut res: Response, status_code: StatusCode, code: &str) {
+    assert_eq!(res.status_code, Some(status_code), "{res:?}");
+    assert_eq!(
+        res.take_json::<serde_json::Value>().await.unwrap()["code"],
+        code
+    );
+}
+
+#[cfg(test)]
+mod captcha_provider {
+    use super::*;
+
+    #[tokio::test]
+    async fn default_provider() {
+        let service = get_guarded_service(CaptchaGuard::default()).await;
+
+        let mut res = send(
+            &service,
+            "auth/captcha/provider",
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let provider: CaptchaProviderSchema =
+            serde_json::from_str(&res.take_string().await.unwrap()).unwrap();
+        assert_eq!(provider.provider, CaptchaProviderKind::Image);
+        assert_eq!(provider.site_key, None);
+
+        let res = send(&service, "auth/captcha", Method::GET, None::<&()>, vec![]).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn custom_provider() {
+        let service =
+            get_guarded_service(CaptchaGuard::default().with_provider(Arc::new(StaticProvider)))
+                .await;
+
+        let mut res = send(
+            &service,
+            "auth/captcha/provider",
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        let provider: CaptchaProviderSchema =
+            serde_json::from_str(&res.take_string().await.unwrap()).unwrap();
+        assert_eq!(provider.provider, CaptchaProviderKind::Hcaptcha);
+        assert_eq!(provider.site_key.as_deref(), Some("static-site-key"));
+
+        let res = send(&service, "auth/captcha", Method::GET, None::<&()>, vec![]).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::NOT_FOUND),
+            "The image captcha should be disabled {res:?}"
+        );
+
+        assert_error(
+            signup(&service, "StaticCaptcha", None).await,
+            StatusCode::BAD_REQUEST,
+            "unprovided_captcha_response",
+        )
+        .await;
+        assert_error(
+            signup(&service, "StaticCaptcha", Some("wrong")).await,
+            StatusCode::FORBIDDEN,
+            "invalid_captcha_response",
+        )
+        .await;
+        let res = signup(&service, "StaticCaptcha", Some("pass")).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn guest_captcha() {
+        let service =
+            get_guarded_service(CaptchaGuard::default().with_provider(Arc::new(StaticProvider)))
+                .await;
+        let guest = |captcha_response: Option<&'static str>| {
+            send::<()>(
+                &service,
+                "auth/guest",
+                Method::POST,
+                None,
+                captcha_response
+                    .map(|response| {
+                        vec![(
+                            HeaderName::from_static("x-captcha-response"),
+                            HeaderValue::from_static(response),
+                        )]
+                    })
+                    .unwrap_or_default(),
+            )
+        };
+
+        assert_error(
+            guest(None).await,
+            StatusCode::BAD_REQUEST,
+            "unprovided_captcha_response",
+        )
+        .await;
+        assert_error(
+            guest(Some("wrong")).await,
+            StatusCode::FORBIDDEN,
+            "invalid_captcha_response",
+        )
+        .await;
+        let res = guest(Some("pass")).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn failing_provider() {
+        let guard = CaptchaGuard::new(
+            Arc::new(xors_api::api::risk::AlwaysSuspicious),
+            CaptchaFailurePolicy::Degrade,
+        )
+        .with_provider(Arc::new(StaticProvider));
+        let service = get_guarded_service(guard).await;
+
+        assert_error(
+            signup(&service, "DownCaptcha", Some("down")).await,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_server_error",
+        )
+        .await;
+        let res = signup(&service, "DownCaptcha", None).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The captcha should be skipped in the degraded mode {res:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn recaptcha_score() {
+        let verify_url = serve_site_verify().await;
+        let service = get_guarded_service(
+            CaptchaGuard::default().with_provider(Arc::new(
+                RecaptchaProvider::new("site", "secret")
+                    .expect("Failed to create the captcha provider")
+                    .with_verify_url(verify_url),
+            )),
+        )
+        .await;
+
+        assert_error(
+            signup(&service, "RecaptchaBot", Some("bot")).await,
+            StatusCode::FORBIDDEN,
+            "invalid_captcha_response",
+        )
+        .await;
+        assert_error(
+            signup(&service, "RecaptchaBot", Some("expired")).await,
+            StatusCode::FORBIDDEN,
+            "invalid_captcha_response",
+        )
+        .await;
+        let res = signup(&service, "RecaptchaHuman", Some("human")).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn hcaptcha_verification() {
+        let verify_url = serve_site_verify().await;
+        let service = get_guarded_service(
+            CaptchaGuard::default().with_provider(Arc::new(
+                HCaptchaProvider::new("site", "secret")
+                    .expect("Failed to create the captcha provider")
+                    .with_verify_url(verify_url),
+            )),
+        )
+        .await;
+
+        assert_error(
+            signup(&service, "HcaptchaUser", Some("expired")).await,
+            StatusCode::FORBIDDEN,
+            "invalid_captcha_response",
+        )
+        .await;
+        // hCaptcha has no score
+        let res = signup(&service, "HcaptchaUser", Some("bot")).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    }
+
+    #[test]
+    fn provider_kind_from_str() {
+        assert_eq!(
+            "image".parse::<CaptchaProviderKind>(),
+            Ok(CaptchaProviderKind::Image)
+        );
+        assert_eq!(
+            "hcaptcha".parse::<CaptchaProviderKind>(),
+            Ok(CaptchaProviderKind::Hcaptcha)
+        );
+        assert_eq!(
+            "recaptcha".parse::<CaptchaProviderKind>(),
+            Ok(CaptchaProviderKind::Recaptcha)
+        );
+        assert!("turnstile".parse::<CaptchaProviderKind>().is_err());
+    }
+}
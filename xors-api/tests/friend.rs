@@ -0,0 +1,237 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+include!("mod.rs");
+
+/// Creates a new user with the given username, returns its uuid and jwt.
+async fn new_user(username: &str) -> (Uuid, String) {
+    let conn = get_connection().await.expect("Failed to get connection");
+    let user = db_utils::create_user(
+        &conn,
+        NewUserSchema {
+            username: username.to_owned(),
+            first_name: "Friend".to_owned(),
+            password: "fdkDFLKJL4859#$&".to_owned(),
+            ..Default::default()
+        },
+    )
+    .await
+    .expect("Failed to create user");
+    let signin = db_utils::signin_user(&conn, user, &get_jwt_keys(), &test_token_lifetimes(), true)
+        .await
+        .expect("Failed to signin user");
+    (signin.user.uuid, signin.jwt)
+}
+
+/// Returns the authorization header of the jwt.
+fn auth_header(jwt: &str) -> Vec<(HeaderName, HeaderValue)> {
+    vec![(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {jwt}")).unwrap(),
+    )]
+}
+
+async fn send_request(service: &Service, jwt: &str, user_uuid: Uuid) -> Response {
+    send(
+        service,
+        "friends/requests",
+        Method::POST,
+        Some(&NewFriendRequestSchema::new(user_uuid)),
+        auth_header(jwt),
+    )
+    .await
+}
+
+async fn get_friends(service: &Service, jwt: &str) -> Vec<FriendSchema> {
+    let mut res = send::<()>(service, "friends", Method::GET, None, auth_header(jwt)).await;
+    assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    res.take_json().await.unwrap()
+}
+
+async fn get_requests(service: &Service, jwt: &str) -> FriendRequestsSchema {
+    let mut res = send::<()>(
+        service,
+        "friends/requests",
+        Method::GET,
+        None,
+        auth_header(jwt),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    res.take_json().await.unwrap()
+}
+
+async fn assert_code(res: &mut Response, status_code: StatusCode, code: &str) {
+    assert_eq!(res.status_code, Some(status_code), "{res:?}");
+    let body = res.take_json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["code"], code, "{body}");
+}
+
+#[cfg(test)]
+mod requests {
+    use super::*;
+
+    #[tokio::test]
+    async fn accept_request() {
+        let service = get_service().await.expect("Failed to get service");
+        let (requester, requester_jwt) = new_user("FriendAcceptRequester").await;
+        let (addressee, addressee_jwt) = new_user("FriendAcceptAddressee").await;
+
+        let res = send_request(&service, &requester_jwt, addressee).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let requests = get_requests(&service, &addressee_jwt).await;
+        assert_eq!(requests.incoming.len(), 1);
+        assert_eq!(requests.incoming[0].user.uuid, requester);
+        assert!(requests.outgoing.is_empty());
+        let requests = get_requests(&service, &requester_jwt).await;
+        assert_eq!(requests.outgoing[0].user.uuid, addressee);
+        assert!(get_friends(&service, &requester_jwt).await.is_empty());
+
+        let res = send::<()>(
+            &service,
+            &format!("friends/requests/{requester}/accept"),
+            Method::POST,
+            None,
+            auth_header(&addressee_jwt),
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        let friends = get_friends(&service, &requester_jwt).await;
+        assert_eq!(friends.len(), 1);
+        assert_eq!(friends[0].user.uuid, addressee);
+        assert_eq!(friends[0].presence, Presence::Offline);
+        assert_eq!(
+            get_friends(&service, &addressee_jwt).await[0].user.uuid,
+            requester
+        );
+        assert!(get_requests(&service, &addressee_jwt)
+            .await
+            .incoming
+            .is_empty());
+
+        let mut res = send_request(&service, &addressee_jwt, requester).await;
+        assert_code(&mut res, StatusCode::CONFLICT, "already_friends").await;
+    }
+
+    #[tokio::test]
+    async fn mutual_requests_are_accepted() {
+        let service = get_service().await.expect("Failed to get service");
+        let (user1, user1_jwt) = new_user("FriendMutual1").await;
+        let (user2, user2_jwt) = new_user("FriendMutual2").await;
+
+        send_request(&service, &user1_jwt, user2).await;
+        let res = send_request(&service, &user2_jwt, user1).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        assert_eq!(get_friends(&service, &user1_jwt).await[0].user.uuid, user2);
+    }
+
+    #[tokio::test]
+    async fn decline_request() {
+        let service = get_service().await.expect("Failed to get service");
+        let (requester, requester_jwt) = new_user("FriendDeclineRequester").await;
+        let (addressee, addressee_jwt) = new_user("FriendDeclineAddressee").await;
+
+        send_request(&service, &requester_jwt, addressee).await;
+        let res = send::<()>(
+            &service,
+            &format!("friends/requests/{requester}/decline"),
+            Method::POST,
+            None,
+            auth_header(&addressee_jwt),
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        assert!(get_requests(&service, &requester_jwt)
+            .await
+            .outgoing
+            .is_empty());
+
+        let mut res = send::<()>(
+            &service,
+            &format!("friends/requests/{requester}/accept"),
+            Method::POST,
+            None,
+            auth_header(&addressee_jwt),
+        )
+        .await;
+        assert_code(&mut res, StatusCode::NOT_FOUND, "friend_request_not_found").await;
+    }
+
+    #[tokio::test]
+    async fn invalid_requests() {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let service = get_service().await.expect("Failed to get service");
+        let (user, user_jwt) = new_user("FriendInvalidUser").await;
+        let (blocker, _) = new_user("FriendInvalidBlocker").await;
+        db_utils::block_user(&conn, blocker, user).await.unwrap();
+
+        let mut res = send_request(&service, &user_jwt, user).await;
+        assert_code(&mut res, StatusCode::BAD_REQUEST, "cannot_friend_self").await;
+        let mut res = send_request(&service, &user_jwt, blocker).await;
+        assert_code(&mut res, StatusCode::FORBIDDEN, "friend_request_blocked").await;
+        let mut res = send_request(&service, &user_jwt, Uuid::new_v4()).await;
+        assert_code(&mut res, StatusCode::NOT_FOUND, "user_not_found").await;
+    }
+}
+
+#[cfg(test)]
+mod remove {
+    use super::*;
+
+    #[tokio::test]
+    async fn remove_friend() {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let service = get_service().await.expect("Failed to get service");
+        let (user, user_jwt) = new_user("FriendRemoveUser").await;
+        let (friend, friend_jwt) = new_user("FriendRemoveFriend").await;
+        db_utils::send_friend_request(&conn, user, friend)
+            .await
+            .unwrap();
+        db_utils::accept_friend_request(&conn, friend, user)
+            .await
+            .unwrap();
+
+        let res = send::<()>(
+            &service,
+            &format!("friends/{friend}"),
+            Method::DELETE,
+            None,
+            auth_header(&user_jwt),
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        assert!(get_friends(&service, &user_jwt).await.is_empty());
+        assert!(get_friends(&service, &friend_jwt).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn blocking_removes_friend() {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let service = get_service().await.expect("Failed to get service");
+        let (user, user_jwt) = new_user("FriendBlockUser").await;
+        let (friend, _) = new_user("FriendBlockFriend").await;
+        db_utils::send_friend_request(&conn, user, friend)
+            .await
+            .unwrap();
+        db_utils::accept_friend_request(&conn, friend, user)
+            .await
+            .unwrap();
+
+        db_utils::block_user(&conn, friend, user).await.unwrap();
+        assert!(get_friends(&service, &user_jwt).await.is_empty());
+    }
+}
@@ -48,7 +48,7 @@ mod get_game {
         .await
         .expect("Failed to create player x");
 
-        let game = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10)
+        let game = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10, false, 0)
             .await
             .expect("Failed to create game");
         db_utils::end_game(&conn, game.uuid.as_ref(), None, &GameOverReason::Draw)
@@ -146,7 +146,7 @@ mod get_game {
         .await
         .expect("Failed to create player o");
 
-        let game = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10)
+        let game = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10, false, 0)
             .await
             .expect("Failed to create game");
         db_utils::end_game(&conn, game.uuid.as_ref(), None, &GameOverReason::Draw)
@@ -213,7 +213,7 @@ mod get_game {
         .await
         .expect("Failed to create player x");
 
-        let game = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10)
+        let game = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10, false, 0)
             .await
             .expect("Failed to create game");
 
@@ -267,10 +267,10 @@ mod lastest_games {
         .await
         .expect("Failed to create player o");
 
-        let game1 = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10)
+        let game1 = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10, false, 0)
             .await
             .expect("Failed to create game");
-        let game2 = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10)
+        let game2 = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10, false, 0)
             .await
             .expect("Failed to create game");
         db_utils::end_game(&conn, game1.uuid.as_ref(), None, &GameOverReason::Draw)
@@ -330,10 +330,10 @@ mod lastest_games {
         .await
         .expect("Failed to create player o");
 
-        let game1 = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10)
+        let game1 = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10, false, 0)
             .await
             .expect("Failed to create game");
-        let game2 = db_utils::create_game(&conn, Uuid::new_v4(), Uuid::new_v4(), 10)
+        let game2 = db_utils::create_game(&conn, Uuid::new_v4(), Uuid::new_v4(), 10, false, 0)
             .await
             .expect("Failed to create game");
         db_utils::end_game(&conn, game1.uuid.as_ref(), None, &GameOverReason::Draw)
@@ -361,3 +361,1266 @@ mod lastest_games {
         );
     }
 }
+
+mod create_game {
+    use super::*;
+
+    #[tokio::test]
+    async fn duplicate_pair() {
+        let conn = get_connection().await.expect("Failed to get connection");
+
+        let player_x = db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                username: "duplicate_pair_x_player".to_owned(),
+                first_name: "Player".to_owned(),
+                password: "fdkDFLKJL4859#$&".to_owned(),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to create player x");
+
+        let player_o = db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                username: "duplicate_pair_o_player".to_owned(),
+                first_name: "Player".to_owned(),
+                password: "fdkDFLKJL4859#$&".to_owned(),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to create player o");
+
+        let game = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10, true, 0)
+            .await
+            .expect("Failed to create game");
+
+        // The same pair with swapped symbols is still the same pair
+        let err = db_utils::create_game(&conn, player_o.uuid, player_x.uuid, 10, true, 0)
+            .await
+            .expect_err("The second game between the same pair should be rejected");
+        assert!(
+            matches!(err, xors_api::errors::ApiError::AlreadyPlayingEachOther(uuid) if &uuid == game.uuid.as_ref()),
+            "The error should contain the existing game uuid {err:?}"
+        );
+
+        // Without the enforcement the game can be created
+        let second_game = db_utils::create_game(&conn, player_o.uuid, player_x.uuid, 10, false, 0)
+            .await
+            .expect("Failed to create game");
+
+        // After all the pair games end, the pair can play again
+        for game_uuid in [game.uuid.as_ref(), second_game.uuid.as_ref()] {
+            db_utils::end_game(&conn, game_uuid, None, &GameOverReason::Draw)
+                .await
+                .expect("Failed to end game");
+        }
+        db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10, true, 0)
+            .await
+            .expect("The pair should be able to play again");
+    }
+}
+
+#[cfg(test)]
+mod get_game_moves {
+    use super::*;
+
+    /// Creates an in-progress game, the first round is won by X and the second round has two moves.
+    async fn create_played_game(
+        conn: &sea_orm::DatabaseConnection,
+        x_player: &UserSchema,
+        o_player: &UserSchema,
+    ) -> Uuid {
+        let mut game = db_utils::create_game(conn, x_player.uuid, o_player.uuid, 10, false, 0)
+            .await
+            .expect("Failed to create game");
+        let game_uuid = *game.uuid.as_ref();
+        game.round = sea_orm::Set(2);
+        game.rounds_result = sea_orm::Set("X XXXOO----:03142".to_owned());
+        game.board = sea_orm::Set("XO-------:01".to_owned());
+        sea_orm::ActiveModelTrait::save(game, conn)
+            .await
+            .expect("Failed to update the game");
+        game_uuid
+    }
+
+    async fn get_moves(
+        service: &Service,
+        path: &str,
+        jwt: &str,
+    ) -> (Option<StatusCode>, Option<Vec<MoveSchema>>) {
+        let mut res = send(
+            service,
+            path,
+            Method::GET,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {jwt}")).unwrap(),
+            )],
+        )
+        .await;
+        let moves = serde_json::from_str(&res.take_string().await.unwrap()).ok();
+        (res.status_code, moves)
+    }
+
+    #[tokio::test]
+    async fn moves_since() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player_x = create_player(&conn, "moves_since_x_player").await;
+        let player_o = create_player(&conn, "moves_since_o_player").await;
+        let game_uuid = create_played_game(&conn, &player_x, &player_o).await;
+        let jwt = db_utils::signin_user(
+            &conn,
+            player_o,
+            &get_jwt_keys(),
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .unwrap()
+        .jwt;
+
+        let (status, moves) = get_moves(&service, &format!("game/{game_uuid}/moves"), &jwt).await;
+        assert_eq!(
+            status,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code"
+        );
+        let moves = moves.expect("Failed to parse the moves");
+        assert_eq!(
+            moves
+                .iter()
+                .map(|m| (m.sequence, m.round, m.place, m.symbol))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, 1, 0, XoSymbol::X),
+                (2, 1, 3, XoSymbol::O),
+                (3, 1, 1, XoSymbol::X),
+                (4, 1, 4, XoSymbol::O),
+                (5, 1, 2, XoSymbol::X),
+                (6, 2, 0, XoSymbol::X),
+                (7, 2, 1, XoSymbol::O),
+            ]
+        );
+
+        let (_, moves) =
+            get_moves(&service, &format!("game/{game_uuid}/moves?since=5"), &jwt).await;
+        assert_eq!(
+            moves
+                .expect("Failed to parse the moves")
+                .iter()
+                .map(|m| m.sequence)
+                .collect::<Vec<_>>(),
+            vec![6, 7]
+        );
+
+        let (status, moves) =
+            get_moves(&service, &format!("game/{game_uuid}/moves?since=100"), &jwt).await;
+        assert_eq!(status, Some(StatusCode::OK));
+        assert!(
+            moves.expect("Failed to parse the moves").is_empty(),
+            "The out of range `since` should return empty list"
+        );
+    }
+
+    #[tokio::test]
+    async fn not_game_player() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player_x = create_player(&conn, "moves_not_player_x_player").await;
+        let player_o = create_player(&conn, "moves_not_player_o_player").await;
+        let other_player = create_player(&conn, "moves_not_player_other").await;
+        let game_uuid = create_played_game(&conn, &player_x, &player_o).await;
+        let jwt = db_utils::signin_user(
+            &conn,
+            other_player,
+            &get_jwt_keys(),
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .unwrap()
+        .jwt;
+
+        let (status, _) = get_moves(&service, &format!("game/{game_uuid}/moves"), &jwt).await;
+        assert_eq!(
+            status,
+            Some(StatusCode::FORBIDDEN),
+            "The response should have a `403 FORBIDDEN` status code"
+        );
+
+        // The moves of the ended games are available for everyone
+        db_utils::end_game(&conn, &game_uuid, None, &GameOverReason::Draw)
+            .await
+            .expect("Failed to end game");
+        let (status, moves) = get_moves(&service, &format!("game/{game_uuid}/moves"), &jwt).await;
+        assert_eq!(status, Some(StatusCode::OK));
+        assert_eq!(moves.expect("Failed to parse the moves").len(), 5);
+    }
+
+    #[tokio::test]
+    async fn game_not_found() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_player(&conn, "moves_not_found_player").await;
+        let jwt = db_utils::signin_user(
+            &conn,
+            player,
+            &get_jwt_keys(),
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .unwrap()
+        .jwt;
+
+        let (status, _) =
+            get_moves(&service, &format!("game/{}/moves", Uuid::new_v4()), &jwt).await;
+        assert_eq!(
+            status,
+            Some(StatusCode::NOT_FOUND),
+            "The response should have a `404 NOT_FOUND` status code"
+        );
+    }
+}
+
+#[cfg(test)]
+mod make_move {
+    use super::*;
+    use salvo::Scribe;
+    use xors_api::errors::ApiError;
+
+    #[test]
+    fn move_errors() {
+        let mut board = Board::default();
+
+        assert_eq!(
+            board.make_move(9, XoSymbol::X, None),
+            Err(MoveError::OutOfBounds)
+        );
+        assert_eq!(
+            board.make_move(0, XoSymbol::O, None),
+            Err(MoveError::NotYourTurn)
+        );
+        assert_eq!(
+            board.make_move(0, XoSymbol::X, Some(1)),
+            Err(MoveError::StaleState)
+        );
+        assert_eq!(board.make_move(0, XoSymbol::X, Some(0)), Ok(()));
+        assert_eq!(
+            board.make_move(0, XoSymbol::O, None),
+            Err(MoveError::CellOccupied)
+        );
+
+        let mut board: Board = "XXXOO----:03142".parse().unwrap();
+        assert_eq!(
+            board.make_move(5, XoSymbol::O, None),
+            Err(MoveError::GameOver)
+        );
+    }
+
+    #[tokio::test]
+    async fn move_errors_codes() {
+        for (err, code) in [
+            (MoveError::OutOfBounds, "out_of_bounds"),
+            (MoveError::CellOccupied, "cell_occupied"),
+            (MoveError::NotYourTurn, "not_your_turn"),
+            (MoveError::GameOver, "game_over"),
+            (MoveError::StaleState, "stale_state"),
+        ] {
+            assert_eq!(
+                serde_json::to_value(ErrorData::InvalidMove(err)).unwrap(),
+                code,
+                "The error frame should have the stable code"
+            );
+
+            let mut res = Response::new();
+            ApiError::InvalidMove(err).render(&mut res);
+            assert_eq!(
+                res.status_code,
+                Some(StatusCode::CONFLICT),
+                "The response should have a `409 CONFLICT` status code {res:?}"
+            );
+            let body: MoveErrorSchema = serde_json::from_str(&res.take_string().await.unwrap())
+                .expect("Failed to parse the move error");
+            assert_eq!(body.code, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod decode_board {
+    use super::*;
+    use base64::Engine;
+
+    /// Sends the compact board of the given bytes to the decode endpoint.
+    async fn decode(service: &Service, bytes: &[u8]) -> Response {
+        send(
+            service,
+            "game/decode",
+            Method::POST,
+            Some(&EncodedBoardSchema::new(
+                xors_api::BASE_64_ENGINE.encode(bytes),
+            )),
+            vec![],
+        )
+        .await
+    }
+
+    async fn assert_invalid(service: &Service, bytes: &[u8]) {
+        let res = decode(service, bytes).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::BAD_REQUEST),
+            "The response should have a `BAD_REQUEST` status code {bytes:?} {res:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn in_progress_board() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let mut res = decode(&service, &[3, 3, 1, 2, 0, 1, 0, 0, 0, 0, 0]).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        let board: DecodedBoardSchema =
+            serde_json::from_str(&res.take_string().await.unwrap()).expect("Failed to parse board");
+        assert_eq!((board.width, board.height), (3, 3));
+        assert_eq!(
+            board.cells,
+            vec![
+                Some(XoSymbol::X),
+                Some(XoSymbol::O),
+                None,
+                Some(XoSymbol::X),
+                None,
+                None,
+                None,
+                None,
+                None
+            ]
+        );
+        assert_eq!(board.turn, Some(XoSymbol::O));
+        assert_eq!(board.winner, None);
+        assert!(!board.is_end);
+    }
+
+    #[tokio::test]
+    async fn won_board() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let mut res = decode(&service, &[3, 3, 1, 1, 1, 2, 2, 0, 0, 0, 0]).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        let board: DecodedBoardSchema =
+            serde_json::from_str(&res.take_string().await.unwrap()).expect("Failed to parse board");
+        assert_eq!(board.turn, None);
+        assert_eq!(board.winner, Some(XoSymbol::X));
+        assert!(board.is_end);
+    }
+
+    #[tokio::test]
+    async fn invalid_encoding() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let res = send(
+            &service,
+            "game/decode",
+            Method::POST,
+            Some(&EncodedBoardSchema::new("not base64!".to_owned())),
+            vec![],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST));
+        // Missing dimensions
+        assert_invalid(&service, &[3]).await;
+        // Unsupported dimensions
+        assert_invalid(
+            &service,
+            &[4, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        )
+        .await;
+        // Missing cells
+        assert_invalid(&service, &[3, 3, 0, 0, 0]).await;
+        // Invalid cell
+        assert_invalid(&service, &[3, 3, 3, 0, 0, 0, 0, 0, 0, 0, 0]).await;
+    }
+
+    #[tokio::test]
+    async fn illegal_board() {
+        let service = get_service().await.expect("Failed to get service");
+
+        // O played first
+        assert_invalid(&service, &[3, 3, 2, 0, 0, 0, 0, 0, 0, 0, 0]).await;
+        // X played twice in a row
+        assert_invalid(&service, &[3, 3, 1, 1, 0, 0, 0, 0, 0, 0, 0]).await;
+        // Both won
+        assert_invalid(&service, &[3, 3, 1, 1, 1, 2, 2, 2, 0, 0, 0]).await;
+        // O played after X won
+        assert_invalid(&service, &[3, 3, 1, 1, 1, 2, 2, 0, 2, 0, 0]).await;
+        // X played after O won
+        assert_invalid(&service, &[3, 3, 1, 1, 0, 2, 2, 2, 1, 1, 0]).await;
+    }
+}
+
+#[cfg(test)]
+mod get_game_analysis {
+    use super::*;
+
+    /// Creates a game, the first round is won by X and the second round is a draw.
+    async fn create_played_game(conn: &sea_orm::DatabaseConnection, prefix: &str) -> Uuid {
+        let x_player = create_player(conn, &format!("{prefix}_x_player")).await;
+        let o_player = create_player(conn, &format!("{prefix}_o_player")).await;
+        let mut game = db_utils::create_game(conn, x_player.uuid, o_player.uuid, 10, false, 0)
+            .await
+            .expect("Failed to create game");
+        let game_uuid = *game.uuid.as_ref();
+        game.round = sea_orm::Set(2);
+        game.rounds_result = sea_orm::Set("X- XXXOO----:03142,XXOOOXXOX:035482671".to_owned());
+        sea_orm::ActiveModelTrait::save(game, conn)
+            .await
+            .expect("Failed to update the game");
+        game_uuid
+    }
+
+    #[tokio::test]
+    async fn analysis() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let game_uuid = create_played_game(&conn, "analysis").await;
+        db_utils::end_game(&conn, &game_uuid, None, &GameOverReason::Draw)
+            .await
+            .expect("Failed to end game");
+
+        let mut res = send(
+            &service,
+            &format!("game/{game_uuid}/analysis"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        assert!(res
+            .headers()
+            .get(header::CACHE_CONTROL)
+            .is_some_and(|value| value.to_str().unwrap().contains("immutable")));
+
+        let analysis: Vec<MoveAnalysisSchema> =
+            serde_json::from_str(&res.take_string().await.unwrap())
+                .expect("Failed to parse analysis");
+        use MoveAnnotation::*;
+        assert_eq!(
+            analysis
+                .iter()
+                .map(|analysis| analysis.annotation)
+                .collect::<Vec<_>>(),
+            vec![
+                // Round 1: X0 O3 X1 O4 X2
+                Optimal, Blunder, Optimal, Optimal, Optimal,
+                // Round 2: X0 O3 X5 O4 X8 O2 X6 O7 X1
+                Optimal, Blunder, Neutral, Blunder, Neutral, Optimal, Optimal, Optimal, Optimal,
+            ]
+        );
+        assert_eq!(
+            analysis
+                .iter()
+                .map(|analysis| analysis.played_move.sequence)
+                .collect::<Vec<_>>(),
+            (1..=14).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn in_progress_game() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let game_uuid = create_played_game(&conn, "analysis_in_progress").await;
+
+        let res = send(
+            &service,
+            &format!("game/{game_uuid}/analysis"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::NOT_FOUND),
+            "The in-progress game analysis should not be available {res:?}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod get_game_replay {
+    use super::*;
+
+    /// Creates a game, the first round is won by X and the second round is a draw.
+    async fn create_played_game(
+        conn: &sea_orm::DatabaseConnection,
+        prefix: &str,
+    ) -> (Uuid, UserSchema) {
+        let x_player = create_player(conn, &format!("{prefix}_x_player")).await;
+        let o_player = create_player(conn, &format!("{prefix}_o_player")).await;
+        let mut game = db_utils::create_game(conn, x_player.uuid, o_player.uuid, 10, false, 0)
+            .await
+            .expect("Failed to create game");
+        let game_uuid = *game.uuid.as_ref();
+        game.round = sea_orm::Set(2);
+        game.rounds_result = sea_orm::Set("X- XXXOO----:03142,XXOOOXXOX:035482671".to_owned());
+        sea_orm::ActiveModelTrait::save(game, conn)
+            .await
+            .expect("Failed to update the game");
+        (game_uuid, x_player)
+    }
+
+    #[tokio::test]
+    async fn replay() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let (game_uuid, x_player) = create_played_game(&conn, "replay").await;
+        for (sequence, round, place) in [(1, 1, 0), (2, 1, 3)] {
+            db_utils::save_game_move(&conn, game_uuid, sequence, round, place)
+                .await
+                .expect("Failed to save the move");
+        }
+        db_utils::end_game(
+            &conn,
+            &game_uuid,
+            Some(x_player.uuid),
+            &GameOverReason::PlayerWon,
+        )
+        .await
+        .expect("Failed to end game");
+
+        let mut res = send(
+            &service,
+            &format!("game/{game_uuid}/replay"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        let replay: ReplaySchema = serde_json::from_str(&res.take_string().await.unwrap())
+            .expect("Failed to parse replay");
+        assert_eq!(replay.uuid, game_uuid);
+        assert_eq!(replay.winner, Some(x_player.uuid));
+        assert_eq!(
+            replay
+                .moves
+                .iter()
+                .map(|replay_move| replay_move.played_move.sequence)
+                .collect::<Vec<_>>(),
+            (1..=14).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            replay
+                .moves
+                .iter()
+                .map(|replay_move| replay_move.played_at.is_some())
+                .collect::<Vec<_>>(),
+            [true, true]
+                .into_iter()
+                .chain([false; 12])
+                .collect::<Vec<_>>(),
+            "Only the saved moves should have their time"
+        );
+        assert_eq!(
+            replay.notation,
+            "1. X0 O3 X1 O4 X2 2. X0 O3 X5 O4 X8 O2 X6 O7 X1 1-0"
+        );
+    }
+
+    #[tokio::test]
+    async fn replay_chat() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let (game_uuid, x_player) = create_played_game(&conn, "replay_chat").await;
+        for message in ["first", "second"] {
+            db_utils::save_chat_message(
+                &conn,
+                game_uuid,
+                x_player.uuid,
+                format!("{message} encrypted message"),
+                format!("{message} signature"),
+            )
+            .await
+            .expect("Failed to save the chat message");
+        }
+        db_utils::end_game(&conn, &game_uuid, None, &GameOverReason::Draw)
+            .await
+            .expect("Failed to end game");
+
+        let mut res = send(
+            &service,
+            &format!("game/{game_uuid}/replay"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        let replay: ReplaySchema = serde_json::from_str(&res.take_string().await.unwrap())
+            .expect("Failed to parse replay");
+        assert_eq!(
+            replay
+                .chat
+                .iter()
+                .map(|message| (message.sender, message.encrypted_message.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (x_player.uuid, "first encrypted message"),
+                (x_player.uuid, "second encrypted message")
+            ]
+        );
+        assert_eq!(replay.chat[1].signature, "second signature");
+    }
+
+    #[tokio::test]
+    async fn in_progress_game() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let (game_uuid, _) = create_played_game(&conn, "replay_in_progress").await;
+
+        let res = send(
+            &service,
+            &format!("game/{game_uuid}/replay"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::NOT_FOUND),
+            "The in-progress game replay should not be available {res:?}"
+        );
+    }
+}
+
+mod evaluate_board {
+    use super::*;
+    use base64::Engine;
+
+    /// Sends the compact board of the given bytes and the symbol to move to the evaluate endpoint.
+    async fn evaluate(service: &Service, bytes: &[u8], turn: XoSymbol) -> Response {
+        send(
+            service,
+            "game/evaluate",
+            Method::POST,
+            Some(&EvaluateBoardSchema::new(
+                xors_api::BASE_64_ENGINE.encode(bytes),
+                turn,
+            )),
+            vec![],
+        )
+        .await
+    }
+
+    async fn assert_value(service: &Service, bytes: &[u8], turn: XoSymbol, value: BoardValue) {
+        let mut res = evaluate(service, bytes, turn).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {bytes:?} {res:?}"
+        );
+        let evaluation: BoardEvaluationSchema =
+            serde_json::from_str(&res.take_string().await.unwrap())
+                .expect("Failed to parse evaluation");
+        assert_eq!(evaluation.turn, turn);
+        assert_eq!(evaluation.value, value, "Wrong value of {bytes:?}");
+    }
+
+    #[tokio::test]
+    async fn positions_values() {
+        let service = get_service().await.expect("Failed to get service");
+
+        // The empty board is a draw
+        assert_value(
+            &service,
+            &[3, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            XoSymbol::X,
+            BoardValue::Draw,
+        )
+        .await;
+        // X can complete the first row
+        assert_value(
+            &service,
+            &[3, 3, 1, 1, 0, 2, 2, 0, 0, 0, 0],
+            XoSymbol::X,
+            BoardValue::Win,
+        )
+        .await;
+        // O can't stop the X fork
+        assert_value(
+            &service,
+            &[3, 3, 1, 2, 0, 1, 0, 0, 0, 0, 0],
+            XoSymbol::O,
+            BoardValue::Loss,
+        )
+        .await;
+        // X won
+        assert_value(
+            &service,
+            &[3, 3, 1, 1, 1, 2, 2, 0, 0, 0, 0],
+            XoSymbol::O,
+            BoardValue::Loss,
+        )
+        .await;
+        // Full board without a winner
+        assert_value(
+            &service,
+            &[3, 3, 1, 2, 1, 1, 2, 2, 2, 1, 1],
+            XoSymbol::O,
+            BoardValue::Draw,
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn wrong_turn() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let res = evaluate(&service, &[3, 3, 1, 2, 0, 1, 0, 0, 0, 0, 0], XoSymbol::X).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::BAD_REQUEST),
+            "The response should have a `400 Bad Request` status code {res:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn illegal_board() {
+        let service = get_service().await.expect("Failed to get service");
+
+        // X and O won
+        let res = evaluate(&service, &[3, 3, 1, 1, 1, 2, 2, 2, 0, 0, 0], XoSymbol::X).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::BAD_REQUEST),
+            "The response should have a `400 Bad Request` status code {res:?}"
+        );
+    }
+}
+
+mod validate_game {
+    use super::*;
+    use base64::Engine;
+
+    /// Sends the starting board bytes and the moves to the validate endpoint.
+    async fn validate(
+        service: &Service,
+        bytes: Option<&[u8]>,
+        moves: Vec<u8>,
+    ) -> GameValidationSchema {
+        let mut res = send(
+            service,
+            "game/validate",
+            Method::POST,
+            Some(&ValidateGameSchema::new(
+                bytes.map(|bytes| xors_api::BASE_64_ENGINE.encode(bytes)),
+                moves,
+            )),
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        serde_json::from_str(&res.take_string().await.unwrap()).expect("Failed to parse validation")
+    }
+
+    #[tokio::test]
+    async fn valid_full_game() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let validation = validate(&service, None, vec![4, 0, 8, 2, 1, 7, 6, 3, 5]).await;
+        assert!(validation.is_legal);
+        assert_eq!(validation.illegal_move, None);
+        assert!(validation.error.is_none());
+        assert!(validation.board.is_end);
+        assert_eq!(validation.board.winner, None, "The game is a draw");
+        assert_eq!(validation.board.turn, None);
+
+        // Continue from a position, O to move
+        let validation = validate(
+            &service,
+            Some(&[3, 3, 1, 2, 0, 1, 0, 0, 0, 0, 0]),
+            vec![6, 4, 2, 8],
+        )
+        .await;
+        assert!(validation.is_legal);
+        assert!(validation.board.is_end);
+        assert_eq!(validation.board.winner, Some(XoSymbol::X));
+    }
+
+    #[tokio::test]
+    async fn illegal_mid_sequence_move() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let validation = validate(&service, None, vec![4, 0, 4, 2]).await;
+        assert!(!validation.is_legal);
+        assert_eq!(validation.illegal_move, Some(2));
+        assert_eq!(validation.error.unwrap().code, MoveError::CellOccupied);
+        assert_eq!(
+            validation.board.cells,
+            vec![
+                Some(XoSymbol::O),
+                None,
+                None,
+                None,
+                Some(XoSymbol::X),
+                None,
+                None,
+                None,
+                None
+            ],
+            "The board should be before the illegal move"
+        );
+        assert_eq!(validation.board.turn, Some(XoSymbol::X));
+
+        let validation = validate(&service, None, vec![4, 9]).await;
+        assert_eq!(validation.illegal_move, Some(1));
+        assert_eq!(validation.error.unwrap().code, MoveError::OutOfBounds);
+    }
+
+    #[tokio::test]
+    async fn moves_after_game_over() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let validation = validate(&service, None, vec![0, 3, 1, 4, 2, 5]).await;
+        assert!(!validation.is_legal);
+        assert_eq!(validation.illegal_move, Some(5));
+        assert_eq!(validation.error.unwrap().code, MoveError::GameOver);
+        assert_eq!(validation.board.winner, Some(XoSymbol::X));
+
+        // Moves on an ended board
+        let validation =
+            validate(&service, Some(&[3, 3, 1, 1, 1, 2, 2, 0, 0, 0, 0]), vec![8]).await;
+        assert_eq!(validation.illegal_move, Some(0));
+        assert_eq!(validation.error.unwrap().code, MoveError::GameOver);
+    }
+
+    #[tokio::test]
+    async fn invalid_starting_board() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let res = send(
+            &service,
+            "game/validate",
+            Method::POST,
+            Some(&ValidateGameSchema::new(
+                Some(xors_api::BASE_64_ENGINE.encode([3, 3, 2, 0, 0, 0, 0, 0, 0, 0, 0])),
+                vec![0],
+            )),
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::BAD_REQUEST),
+            "The response should have a `400 Bad Request` status code {res:?}"
+        );
+    }
+}
+
+mod get_recent_games {
+    use super::*;
+
+    #[tokio::test]
+    async fn recent_games() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player_x = create_player(&conn, "recent_games_x_player").await;
+        let player_o = create_player(&conn, "recent_games_o_player").await;
+
+        let first_ended = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10, false, 0)
+            .await
+            .expect("Failed to create game");
+        let last_ended = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10, false, 0)
+            .await
+            .expect("Failed to create game");
+        let in_progress = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10, false, 0)
+            .await
+            .expect("Failed to create game");
+        db_utils::end_game(
+            &conn,
+            first_ended.uuid.as_ref(),
+            None,
+            &GameOverReason::Draw,
+        )
+        .await
+        .expect("Failed to end game");
+        db_utils::end_game(
+            &conn,
+            last_ended.uuid.as_ref(),
+            Some(player_x.uuid),
+            &GameOverReason::PlayerWon,
+        )
+        .await
+        .expect("Failed to end game");
+
+        let mut res = send(&service, "games/recent", Method::GET, None::<&()>, vec![]).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "Status code should be 200 {res:?}"
+        );
+        assert_eq!(
+            res.headers().get(header::CACHE_CONTROL).unwrap(),
+            "public, max-age=10"
+        );
+        let games: Vec<GameSchema> =
+            serde_json::from_str(&res.take_string().await.unwrap()).expect("Failed to parse games");
+
+        assert!(
+            games
+                .windows(2)
+                .all(|pair| pair[0].ended_at >= pair[1].ended_at),
+            "The games should be sorted by the end date"
+        );
+        let position = |uuid: &Uuid| games.iter().position(|game| &game.uuid == uuid);
+        assert!(
+            position(last_ended.uuid.as_ref()) < position(first_ended.uuid.as_ref())
+                && position(last_ended.uuid.as_ref()).is_some(),
+            "The last ended game should come first {games:?}"
+        );
+        assert!(
+            position(in_progress.uuid.as_ref()).is_none(),
+            "The in-progress game should not be in the recent games"
+        );
+    }
+
+    #[tokio::test]
+    async fn pagination() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let mut res = send(
+            &service,
+            "games/recent?page=100000",
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "Status code should be 200 {res:?}"
+        );
+        let games: Vec<GameSchema> =
+            serde_json::from_str(&res.take_string().await.unwrap()).expect("Failed to parse games");
+        assert!(games.is_empty(), "The far page should be empty");
+    }
+}
+
+#[cfg(test)]
+mod random_opening {
+    use super::*;
+
+    #[test]
+    fn legal_openings() {
+        assert!(Board::random_opening(0).played_cells().is_empty());
+        for moves in 1..=MAX_OPENING_MOVES {
+            for _ in 0..20 {
+                let board = Board::random_opening(moves);
+                let played_cells = board.played_cells();
+
+                assert_eq!(played_cells.len(), moves as usize);
+                assert!(played_cells.iter().all(|place| *place <= 8));
+                assert!(
+                    played_cells[1..]
+                        .iter()
+                        .all(|place| place != &played_cells[0]),
+                    "The opening moves should be in different cells {board:?}"
+                );
+                // The board is valid, X played first
+                let parsed: Board = board.to_string().parse().expect("The board is valid");
+                assert_eq!(parsed.played_cells(), played_cells);
+                assert!(!board.is_end());
+                let turn = if moves % 2 == 0 {
+                    XoSymbol::X
+                } else {
+                    XoSymbol::O
+                };
+                assert_eq!(board.turn(), turn);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "The opening moves must be at most")]
+    fn too_many_opening_moves() {
+        Board::random_opening(MAX_OPENING_MOVES + 1);
+    }
+
+    #[tokio::test]
+    async fn forced_moves_are_recorded() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player_x = create_player(&conn, "random_opening_x_player").await;
+        let player_o = create_player(&conn, "random_opening_o_player").await;
+        let game = db_utils::create_game(&conn, player_x.uuid, player_o.uuid, 10, false, 2)
+            .await
+            .expect("Failed to create game");
+        assert_eq!(game.opening_moves.as_ref(), &2);
+        let jwt = db_utils::signin_user(
+            &conn,
+            player_x,
+            &get_jwt_keys(),
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .unwrap()
+        .jwt;
+
+        let mut res = send(
+            &service,
+            &format!("game/{}/moves", game.uuid.as_ref()),
+            Method::GET,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {jwt}")).unwrap(),
+            )],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        let moves: Vec<MoveSchema> = serde_json::from_str(&res.take_string().await.unwrap())
+            .expect("Failed to parse the moves");
+
+        assert_eq!(moves.len(), 2);
+        assert!(
+            moves.iter().all(|game_move| game_move.forced),
+            "The opening moves should be flagged as forced {moves:?}"
+        );
+        assert_eq!(moves[0].symbol, XoSymbol::X);
+        assert_eq!(moves[1].symbol, XoSymbol::O);
+        assert_ne!(moves[0].place, moves[1].place);
+    }
+}
+
+#[cfg(test)]
+mod bot_moves {
+    use super::*;
+    use std::str::FromStr;
+    use xors_api::api::bot::choose_move;
+
+    fn board(board: &str) -> Board {
+        Board::from_str(board).expect("Valid board")
+    }
+
+    #[test]
+    fn random_move() {
+        let board = board("XX-OO----:0314");
+        for _ in 0..20 {
+            assert!(board
+                .empty_cells()
+                .contains(&choose_move(&board, BotDifficulty::Random)));
+        }
+    }
+
+    #[test]
+    fn heuristic_moves() {
+        // Wins
+        assert_eq!(
+            choose_move(&board("XX-OO----:0314"), BotDifficulty::Heuristic),
+            2
+        );
+        // Blocks the X win
+        assert_eq!(
+            choose_move(&board("XX--O----:041"), BotDifficulty::Heuristic),
+            2
+        );
+        // Prefers the center then the corners
+        assert_eq!(choose_move(&Board::default(), BotDifficulty::Heuristic), 4);
+        assert!([0, 2, 6, 8].contains(&choose_move(
+            &board("----X----:4"),
+            BotDifficulty::Heuristic
+        )));
+    }
+
+    #[test]
+    fn perfect_moves() {
+        assert_eq!(
+            choose_move(&board("XX-OO----:0314"), BotDifficulty::Perfect),
+            2
+        );
+        assert_eq!(
+            choose_move(&board("XX--O----:041"), BotDifficulty::Perfect),
+            2
+        );
+        // The O edge reply to the X corner loses, the perfect bot plays the center
+        assert_eq!(
+            choose_move(&board("X--------:0"), BotDifficulty::Perfect),
+            4
+        );
+
+        // The perfect play against itself is always a draw
+        let mut board = Board::default();
+        while !board.is_end() {
+            board.set_cell(choose_move(&board, BotDifficulty::Perfect), board.turn());
+        }
+        assert!(board.is_draw());
+    }
+}
+
+#[cfg(test)]
+mod game_variant {
+    use super::*;
+    use std::str::FromStr;
+
+    /// Returns a board of the variant with the places played, the symbols alternate starting from X.
+    fn play(variant: GameVariant, places: &[u8]) -> Board {
+        let mut board = Board::new(variant);
+        for place in places {
+            board
+                .make_move(*place, board.turn(), None)
+                .expect("The move is legal");
+        }
+        board
+    }
+
+    #[test]
+    fn valid_variants() {
+        assert!(GameVariant::default().is_classic());
+        assert!(GameVariant::new(15, 5).is_valid());
+        assert!(GameVariant::new(4, 4).is_valid());
+        assert!(!GameVariant::new(2, 2).is_valid());
+        assert!(!GameVariant::new(16, 5).is_valid());
+        assert!(!GameVariant::new(5, 6).is_valid());
+        assert!(!GameVariant::new(5, 2).is_valid());
+    }
+
+    #[test]
+    fn win_length() {
+        let variant = GameVariant::new(15, 5);
+        // Row: X plays 0..=4, O plays the next row
+        let row = play(variant, &[0, 15, 1, 16, 2, 17, 3, 18, 4]);
+        assert!(row.is_win(&XoSymbol::X));
+        assert!(!row.is_win(&XoSymbol::O));
+        // Four in a row is not a win
+        let four = play(variant, &[0, 15, 1, 16, 2, 17, 3]);
+        assert!(!four.is_end());
+        // Column
+        let column = play(variant, &[7, 8, 22, 9, 37, 10, 52, 11, 67]);
+        assert!(column.is_win(&XoSymbol::X));
+        // Diagonals
+        let diagonal = play(variant, &[0, 1, 16, 2, 32, 3, 48, 5, 64]);
+        assert!(diagonal.is_win(&XoSymbol::X));
+        let anti_diagonal = play(variant, &[14, 0, 28, 1, 42, 2, 56, 3, 70]);
+        assert!(anti_diagonal.is_win(&XoSymbol::X));
+        // The row doesn't wrap to the next row
+        let wrapped = play(variant, &[12, 0, 13, 1, 14, 2, 15, 3, 16]);
+        assert!(!wrapped.is_win(&XoSymbol::X));
+
+        assert_eq!(
+            Board::new(variant).make_move(225, XoSymbol::X, None),
+            Err(MoveError::OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn encoding() {
+        let classic = play(GameVariant::default(), &[0, 1]);
+        assert_eq!(classic.to_string(), "XO-------:01");
+
+        let board = play(GameVariant::new(4, 3), &[0, 5, 15]);
+        let encoded = board.to_string();
+        assert_eq!(encoded, "4x3:X----O---------X:0.5.15");
+        let decoded = Board::from_str(&encoded).expect("The board is valid");
+        assert_eq!(decoded.variant(), GameVariant::new(4, 3));
+        assert_eq!(decoded.played_cells(), board.played_cells());
+        assert_eq!(decoded.turn(), XoSymbol::O);
+
+        let empty = Board::new(GameVariant::new(15, 5)).to_string();
+        assert!(Board::from_str(&empty)
+            .expect("The board is valid")
+            .played_cells()
+            .is_empty());
+
+        for invalid in [
+            "4x3:X----O---------X:0.5.16",
+            "4x3:X----O--------X:0.5.15",
+            "2x2:X---:0",
+            "4x3:X----O---------X:0.5x",
+        ] {
+            assert!(Board::from_str(invalid).is_err(), "{invalid} is invalid");
+        }
+    }
+
+    #[tokio::test]
+    async fn variant_game() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let x_player = create_player(&conn, "variant_game_x_player").await;
+        let o_player = create_player(&conn, "variant_game_o_player").await;
+        let game = db_utils::create_game(&conn, x_player.uuid, o_player.uuid, 10, false, 1)
+            .await
+            .expect("Failed to create game");
+        let game = db_utils::set_game_variant(&conn, game, GameVariant::new(15, 5))
+            .await
+            .expect("Failed to set the game variant");
+        let board = Board::from_str(game.board.as_ref()).expect("The board is valid");
+        assert_eq!(board.variant(), GameVariant::new(15, 5));
+        assert_eq!(board.played_cells().len(), 1);
+
+        let game_uuid = *game.uuid.as_ref();
+        db_utils::end_game(&conn, &game_uuid, None, &GameOverReason::Draw)
+            .await
+            .expect("Failed to end game");
+
+        let mut res = send(
+            &service,
+            &format!("game/{game_uuid}"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+        let game = res.take_json::<GameSchema>().await.unwrap();
+        assert_eq!(game.variant, GameVariant::new(15, 5));
+
+        let res = send(
+            &service,
+            &format!("game/{game_uuid}/analysis"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::BAD_REQUEST),
+            "Only the classic games can be analyzed {res:?}"
+        );
+    }
+}
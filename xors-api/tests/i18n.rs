@@ -0,0 +1,149 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+include!("mod.rs");
+
+use xors_api::i18n::{self, MessageArgs};
+
+fn accept_language(value: &'static str) -> Vec<(HeaderName, HeaderValue)> {
+    vec![(header::ACCEPT_LANGUAGE, HeaderValue::from_static(value))]
+}
+
+async fn get_user(service: &Service, headers: Vec<(HeaderName, HeaderValue)>) -> Response {
+    let res = send(
+        service,
+        &format!("user?uuid={}", Uuid::new_v4()),
+        Method::GET,
+        None::<&()>,
+        headers,
+    )
+    .await;
+    assert_eq!(
+        res.status_code,
+        Some(StatusCode::NOT_FOUND),
+        "The response should have a `404 Not Found` status code {res:?}"
+    );
+    res
+}
+
+#[tokio::test]
+async fn translated_error() {
+    let service = get_service().await.expect("Failed to get service");
+
+    let mut res = get_user(&service, accept_language("ar-SA,en;q=0.8")).await;
+    assert_eq!(
+        res.headers().get(header::CONTENT_LANGUAGE),
+        Some(&HeaderValue::from_static("ar"))
+    );
+    let body = res.take_json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["code"], "user_not_found", "The code is not translated");
+    assert_eq!(body["message"], "المستخدم غير موجود");
+}
+
+#[tokio::test]
+async fn english_fallback() {
+    let service = get_service().await.expect("Failed to get service");
+
+    for headers in [
+        vec![],
+        accept_language("en"),
+        accept_language("fr-FR, en;q=0.9, ar;q=0.8"),
+        accept_language("de, ar;q=0"),
+        accept_language("*"),
+    ] {
+        let mut res = get_user(&service, headers).await;
+        assert_eq!(res.headers().get(header::CONTENT_LANGUAGE), None);
+        let body = res.take_json::<serde_json::Value>().await.unwrap();
+        assert_eq!(body["code"], "user_not_found");
+        assert_eq!(body["message"], "User not found");
+    }
+}
+
+#[tokio::test]
+async fn translated_validation_error() {
+    let service = get_service().await.expect("Failed to get service");
+
+    let mut res = send(
+        &service,
+        "auth/signup",
+        Method::POST,
+        Some(&NewUserSchema {
+            first_name: "First".to_owned(),
+            last_name: None,
+            username: "localized_signup".to_owned(),
+            password: "aB1#".to_owned(),
+        }),
+        accept_language("ar"),
+    )
+    .await;
+    assert_eq!(
+        res.status_code,
+        Some(StatusCode::BAD_REQUEST),
+        "The response should have a `400 Bad Request` status code {res:?}"
+    );
+    let body = res.take_json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["code"], "password_length");
+    assert_eq!(
+        body["message"], "كلمة المرور غير صالحة: يجب أن تكون كلمة المرور بين 8 و64 حرفًا",
+        "The placeholders should be replaced with the policy bounds"
+    );
+}
+
+#[tokio::test]
+async fn translated_catcher_error() {
+    let service = get_service().await.expect("Failed to get service");
+
+    let mut res = send(
+        &service,
+        "unknown/route",
+        Method::GET,
+        None::<&()>,
+        accept_language("ar"),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::NOT_FOUND));
+    let body = res.take_json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["code"], "not_found");
+    assert_eq!(body["message"], "غير موجود");
+}
+
+#[test]
+fn negotiate_language() {
+    assert_eq!(i18n::negotiate_language("ar"), "ar");
+    assert_eq!(i18n::negotiate_language("AR-sa"), "ar");
+    assert_eq!(i18n::negotiate_language("en-US,ar;q=0.9"), "en");
+    assert_eq!(i18n::negotiate_language("en;q=0.5, ar;q=0.9"), "ar");
+    assert_eq!(i18n::negotiate_language("fr, ar;q=0.1"), "ar");
+    assert_eq!(i18n::negotiate_language("ar;q=0"), "en");
+    assert_eq!(i18n::negotiate_language("ar;q=invalid"), "en");
+    assert_eq!(i18n::negotiate_language(""), "en");
+}
+
+#[test]
+fn translate() {
+    let args = MessageArgs(vec![("username", "Username".to_owned())]);
+    assert_eq!(
+        i18n::translate("ar", "username_already_exists", &args).as_deref(),
+        Some("اسم المستخدم `Username` موجود مسبقًا")
+    );
+    assert_eq!(
+        i18n::translate("ar", "username_already_exists", &MessageArgs::default()),
+        None,
+        "The message without its arguments should not be translated"
+    );
+    assert_eq!(i18n::translate("ar", "unknown_code", &args), None);
+    assert_eq!(i18n::translate("en", "user_not_found", &args), None);
+}
@@ -439,6 +439,8 @@ mod signin {
         let signin_schema = SigninSchema {
             username: user.username.clone(),
             password: "fdkjhKFHDKH347(#*&".to_owned(),
+            remember_me: None,
+            totp_code: None,
         };
 
         let mut res = send(
@@ -469,6 +471,98 @@ mod signin {
         );
     }
 
+    #[tokio::test]
+    async fn signin_rehashes_bcrypt_hash() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+
+        let user = xors_api::db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: "Bcrypt3489398".to_owned(),
+                password: "fdkjhKFHDKH347(#*&".to_owned(),
+            },
+        )
+        .await
+        .expect("Failed to create user");
+        let mut user_model = db_utils::get_user(&conn, user.uuid)
+            .await
+            .expect("Failed to get user");
+        user_model.password_hash =
+            sea_orm::Set(bcrypt::hash("fdkjhKFHDKH347(#*&", 4).expect("Failed to hash password"));
+        sea_orm::ActiveModelTrait::save(user_model, &conn)
+            .await
+            .expect("Failed to save user");
+
+        let res = send(
+            &service,
+            "auth/signin",
+            Method::POST,
+            Some(&SigninSchema {
+                username: user.username.clone(),
+                password: "fdkjhKFHDKH347(#*&".to_owned(),
+                remember_me: None,
+                totp_code: None,
+            }),
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The bcrypt hash should still work {res:?}"
+        );
+
+        let password_hash = db_utils::get_user(&conn, user.uuid)
+            .await
+            .expect("Failed to get user")
+            .password_hash
+            .unwrap();
+        assert!(
+            password_hash.starts_with("$argon2id$"),
+            "The hash should be rehashed to Argon2id: {password_hash}"
+        );
+        assert!(xors_api::utils::PasswordHasher::current()
+            .verify("fdkjhKFHDKH347(#*&", &password_hash)
+            .expect("Failed to verify password"));
+    }
+
+    #[tokio::test]
+    async fn password_hasher() {
+        install_test_password_hasher();
+        let hasher = xors_api::utils::PasswordHasher::current();
+        let argon2_hash = hasher.hash("password").expect("Failed to hash password");
+        let bcrypt_hash = bcrypt::hash("password", 4).expect("Failed to hash password");
+
+        assert!(hasher.verify("password", &argon2_hash).unwrap());
+        assert!(!hasher.verify("wrong", &argon2_hash).unwrap());
+        assert!(hasher.verify("password", &bcrypt_hash).unwrap());
+        assert!(!hasher.verify("wrong", &bcrypt_hash).unwrap());
+        assert!(!hasher.needs_rehash(&argon2_hash));
+        assert!(hasher.needs_rehash(&bcrypt_hash));
+
+        let costlier = xors_api::utils::PasswordHasher {
+            time_cost: hasher.time_cost + 1,
+            ..*hasher
+        };
+        assert!(
+            costlier.needs_rehash(&argon2_hash),
+            "The hash with other costs should be rehashed"
+        );
+        assert!(
+            costlier.verify("password", &argon2_hash).unwrap(),
+            "The costs are read from the hash"
+        );
+        assert!(xors_api::utils::PasswordHasher {
+            memory_cost: 0,
+            ..*hasher
+        }
+        .validate()
+        .is_err());
+    }
+
     #[tokio::test]
     async fn signin_with_invalid_username() {
         let service = get_service().await.expect("Failed to get service");
@@ -489,6 +583,8 @@ mod signin {
         let signin_schema = SigninSchema {
             username: "InvalidUsername".to_owned(),
             password: "fdkjhKFHDKH347(#*&".to_owned(),
+            remember_me: None,
+            totp_code: None,
         };
 
         let res = send(
@@ -527,6 +623,8 @@ mod signin {
         let signin_schema = SigninSchema {
             username: user.username,
             password: "InvalidPassword".to_owned(),
+            remember_me: None,
+            totp_code: None,
         };
 
         let res = send(
@@ -540,8 +638,8 @@ mod signin {
 
         assert_eq!(
             res.status_code,
-            Some(StatusCode::BAD_REQUEST),
-            "The response should have a `400 BAD_REQUEST` status code {res:?}"
+            Some(StatusCode::FORBIDDEN),
+            "The signin only checks the password length, the response should have a `403 FORBIDDEN` status code {res:?}"
         );
     }
 
@@ -552,6 +650,8 @@ mod signin {
         let signin_schema = SigninSchema {
             username: "InvalidUsername".to_owned(),
             password: "InvalidPassword".to_owned(),
+            remember_me: None,
+            totp_code: None,
         };
 
         let res = send(
@@ -565,8 +665,8 @@ mod signin {
 
         assert_eq!(
             res.status_code,
-            Some(StatusCode::BAD_REQUEST),
-            "The response should have a `400 BAD_REQUEST` status code {res:?}"
+            Some(StatusCode::FORBIDDEN),
+            "The response should have a `403 FORBIDDEN` status code {res:?}"
         );
     }
 }
@@ -578,7 +678,7 @@ mod refresh {
     async fn refresh_with_valid_refresh_token() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::create_user(
             &conn,
@@ -592,10 +692,16 @@ mod refresh {
         .await
         .expect("Failed to create user");
 
-        let (jwt, refresh_token) = xors_api::db_utils::signin_user(user.clone(), &secret_key)
-            .await
-            .map(|user| (user.jwt, user.refresh_token))
-            .expect("Failed to signin user");
+        let (jwt, refresh_token) = xors_api::db_utils::signin_user(
+            &conn,
+            user.clone(),
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .map(|user| (user.jwt, user.refresh_token))
+        .expect("Failed to signin user");
 
         // Sleep until the refresh token available
         tokio::time::sleep(Duration::seconds(3).to_std().unwrap()).await;
@@ -646,7 +752,7 @@ mod refresh {
     async fn unavailable_refresh_token() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::create_user(
             &conn,
@@ -660,10 +766,16 @@ mod refresh {
         .await
         .expect("Failed to create user");
 
-        let refresh_token = xors_api::db_utils::signin_user(user.clone(), &secret_key)
-            .await
-            .map(|user| user.refresh_token)
-            .expect("Failed to signin user");
+        let refresh_token = xors_api::db_utils::signin_user(
+            &conn,
+            user.clone(),
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .map(|user| user.refresh_token)
+        .expect("Failed to signin user");
 
         let res = send(
             &service,
@@ -689,7 +801,7 @@ mod refresh {
     async fn expired_refresh_token() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::create_user(
             &conn,
@@ -703,10 +815,16 @@ mod refresh {
         .await
         .expect("Failed to create user");
 
-        let refresh_token = xors_api::db_utils::signin_user(user.clone(), &secret_key)
-            .await
-            .map(|user| user.refresh_token)
-            .expect("Failed to signin user");
+        let refresh_token = xors_api::db_utils::signin_user(
+            &conn,
+            user.clone(),
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .map(|user| user.refresh_token)
+        .expect("Failed to signin user");
 
         // Sleep until the refresh token expired
         tokio::time::sleep(Duration::seconds(5).to_std().unwrap()).await;
@@ -796,7 +914,7 @@ mod refresh {
     async fn refresh_with_jwt() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::create_user(
             &conn,
@@ -810,10 +928,16 @@ mod refresh {
         .await
         .expect("Failed to create user");
 
-        let jwt = xors_api::db_utils::signin_user(user.clone(), &secret_key)
-            .await
-            .map(|user| user.jwt)
-            .expect("Failed to signin user");
+        let jwt = xors_api::db_utils::signin_user(
+            &conn,
+            user.clone(),
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .map(|user| user.jwt)
+        .expect("Failed to signin user");
 
         // Sleep until the refresh token available
         tokio::time::sleep(Duration::seconds(3).to_std().unwrap()).await;
@@ -838,3 +962,2300 @@ mod refresh {
         );
     }
 }
+
+#[cfg(test)]
+mod refresh_info {
+    use super::*;
+
+    async fn signin(username: &str) -> UserSigninSchema {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let user = db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: username.to_owned(),
+                password: "fdkjhKFHDKH347(#*&".to_owned(),
+            },
+        )
+        .await
+        .expect("Failed to create user");
+        db_utils::signin_user(&conn, user, &get_jwt_keys(), &test_token_lifetimes(), true)
+            .await
+            .expect("Failed to signin user")
+    }
+
+    async fn refresh_info(service: &Service, token: &str) -> Response {
+        send(
+            service,
+            "auth/refresh/info",
+            Method::GET,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn not_active_refresh_token() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("RefreshInfoNotActive").await;
+
+        let mut res = refresh_info(&service, &user.refresh_token).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let info: RefreshTokenInfoSchema = res.take_json().await.unwrap();
+        assert!((2..=3).contains(&info.active_in), "{info:?}");
+        assert!((4..=5).contains(&info.expires_in), "{info:?}");
+        assert_eq!(info.exp - info.active_after, 2);
+
+        // The refresh token is not consumed
+        let res = refresh_info(&service, &user.refresh_token).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+    }
+
+    #[tokio::test]
+    async fn active_refresh_token() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("RefreshInfoActive").await;
+
+        // Sleep until the refresh token available
+        tokio::time::sleep(Duration::seconds(3).to_std().unwrap()).await;
+        let mut res = refresh_info(&service, &user.refresh_token).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let info: RefreshTokenInfoSchema = res.take_json().await.unwrap();
+        assert_eq!(info.active_in, 0);
+        assert!(info.expires_in <= 2, "{info:?}");
+    }
+
+    #[tokio::test]
+    async fn expired_refresh_token() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("RefreshInfoExpired").await;
+
+        // Sleep until the refresh token expired
+        tokio::time::sleep(Duration::seconds(5).to_std().unwrap()).await;
+        let res = refresh_info(&service, &user.refresh_token).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn info_with_jwt() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("RefreshInfoJwt").await;
+
+        let mut res = refresh_info(&service, &user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{res:?}");
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["message"],
+            "The token is not a refresh token"
+        );
+    }
+}
+
+#[cfg(test)]
+mod refresh_rotation {
+    use super::*;
+
+    async fn signin(username: &str) -> UserSigninSchema {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let user = db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: username.to_owned(),
+                password: "fdkjhKFHDKH347(#*&".to_owned(),
+            },
+        )
+        .await
+        .expect("Failed to create user");
+        db_utils::signin_user(&conn, user, &get_jwt_keys(), &test_token_lifetimes(), true)
+            .await
+            .expect("Failed to signin user")
+    }
+
+    async fn send_with_token(service: &Service, path: &str, token: &str) -> Response {
+        send(
+            service,
+            path,
+            Method::GET,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn rotate_refresh_token() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("RefreshRotationUser").await;
+
+        // Sleep until the refresh token available
+        tokio::time::sleep(Duration::seconds(3).to_std().unwrap()).await;
+        let mut res = send_with_token(&service, "auth/refresh", &user.refresh_token).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let new_user: UserSigninSchema = res.take_json().await.unwrap();
+        assert_ne!(new_user.refresh_token, user.refresh_token);
+
+        let res = send_with_token(&service, "user/me", &new_user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let res = send_with_token(&service, "auth/refresh/info", &new_user.refresh_token).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The new refresh token should be valid {res:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn reused_refresh_token() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("RefreshReuseUser").await;
+
+        // Sleep until the refresh token available
+        tokio::time::sleep(Duration::seconds(3).to_std().unwrap()).await;
+        let mut res = send_with_token(&service, "auth/refresh", &user.refresh_token).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let new_user: UserSigninSchema = res.take_json().await.unwrap();
+
+        let mut res = send_with_token(&service, "auth/refresh", &user.refresh_token).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED), "{res:?}");
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["message"],
+            "The refresh token is already used, all your sessions are revoked, signin again"
+        );
+
+        // The reuse revokes the newer tokens too
+        let res = send_with_token(&service, "user/me", &new_user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED), "{res:?}");
+        let mut res = send_with_token(&service, "auth/refresh/info", &new_user.refresh_token).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED), "{res:?}");
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["message"],
+            "The token is revoked"
+        );
+    }
+
+    #[tokio::test]
+    async fn concurrent_refreshes() {
+        std::env::set_var("XORS_API_TEST", "true");
+        let service = xors_api::api::service(
+            get_connection().await.expect("Failed to get connection"),
+            get_jwt_keys(),
+            xors_api::api::ServiceConfig {
+                token_lifetimes: xors_api::api::jwt::TokenLifetimes {
+                    refresh_grace_period: Duration::seconds(5),
+                    ..test_token_lifetimes()
+                },
+                ..test_service_config()
+            },
+        )
+        .0;
+        let user = signin("ConcurrentRefreshUser").await;
+
+        // Sleep until the refresh token available
+        tokio::time::sleep(Duration::seconds(3).to_std().unwrap()).await;
+        let (mut first, mut second) = tokio::join!(
+            send_with_token(&service, "auth/refresh", &user.refresh_token),
+            send_with_token(&service, "auth/refresh", &user.refresh_token),
+        );
+        assert_eq!(first.status_code, Some(StatusCode::OK), "{first:?}");
+        assert_eq!(second.status_code, Some(StatusCode::OK), "{second:?}");
+        let first: UserSigninSchema = first.take_json().await.unwrap();
+        let second: UserSigninSchema = second.take_json().await.unwrap();
+        assert_eq!(
+            first.jwt, second.jwt,
+            "The refreshes should get the same tokens"
+        );
+        assert_eq!(first.refresh_token, second.refresh_token);
+
+        let res = send_with_token(&service, "user/me", &first.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        // The consumed refresh token is accepted once in its grace period
+        let mut res = send_with_token(&service, "auth/refresh", &user.refresh_token).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED), "{res:?}");
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["message"],
+            "The refresh token is already used, all your sessions are revoked, signin again"
+        );
+        let res = send_with_token(&service, "user/me", &first.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED), "{res:?}");
+    }
+}
+
+#[cfg(test)]
+mod logout {
+    use super::*;
+    use xors_api::api::jwt::JwtClaims;
+
+    async fn signin(username: &str) -> UserSigninSchema {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let user = db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: username.to_owned(),
+                password: "fdkjhKFHDKH347(#*&".to_owned(),
+            },
+        )
+        .await
+        .expect("Failed to create user");
+        db_utils::signin_user(&conn, user, &get_jwt_keys(), &test_token_lifetimes(), true)
+            .await
+            .expect("Failed to signin user")
+    }
+
+    async fn send_with_token(
+        service: &Service,
+        path: &str,
+        method: Method,
+        token: &str,
+    ) -> Response {
+        send(
+            service,
+            path,
+            method,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn logout_revokes_jwt() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("LogoutJwtUser").await;
+        let other_user = signin("LogoutOtherUser").await;
+
+        let res = send_with_token(&service, "auth/logout", Method::POST, &user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        let mut res = send_with_token(&service, "user/me", Method::GET, &user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["message"],
+            "The token is revoked"
+        );
+        let res = send_with_token(&service, "auth/logout", Method::POST, &user.jwt).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::UNAUTHORIZED),
+            "The revoked token can't logout again"
+        );
+
+        let res = send_with_token(&service, "user/me", Method::GET, &other_user.jwt).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The other tokens should not be revoked"
+        );
+    }
+
+    #[tokio::test]
+    async fn logout_revokes_refresh_token() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("LogoutRefreshUser").await;
+
+        let res = send_with_token(&service, "auth/logout", Method::POST, &user.refresh_token).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        // Sleep until the refresh token available
+        tokio::time::sleep(Duration::seconds(3).to_std().unwrap()).await;
+        let mut res =
+            send_with_token(&service, "auth/refresh", Method::GET, &user.refresh_token).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["message"],
+            "The token is revoked"
+        );
+    }
+
+    #[tokio::test]
+    async fn revoke_all_tokens() {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("RevokeAllUser").await;
+        let other_session = db_utils::signin_user(
+            &conn,
+            user.user.clone(),
+            &get_jwt_keys(),
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .expect("Failed to signin user");
+        let other_user = signin("RevokeAllOtherUser").await;
+
+        let res = send_with_token(&service, "auth/revoke-all", Method::POST, &user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        for token in [&user.jwt, &other_session.jwt, &other_session.refresh_token] {
+            let res = send_with_token(&service, "user/me", Method::GET, token).await;
+            assert_eq!(
+                res.status_code,
+                Some(StatusCode::UNAUTHORIZED),
+                "All the user tokens should be revoked"
+            );
+        }
+        let res = send_with_token(&service, "user/me", Method::GET, &other_user.jwt).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The other users tokens should not be revoked"
+        );
+
+        let new_session = db_utils::signin_user(
+            &conn,
+            user.user,
+            &get_jwt_keys(),
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .expect("Failed to signin user");
+        let res = send_with_token(&service, "user/me", Method::GET, &new_session.jwt).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The new tokens should not be revoked"
+        );
+    }
+
+    #[tokio::test]
+    async fn prune_expired_revoked_tokens() {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let now = chrono::Utc::now().timestamp();
+        let expired = JwtClaims::new(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            UserRole::User,
+            0,
+            now - 70,
+            now - 70,
+            None,
+            now - 10,
+        );
+        let active = JwtClaims::new(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            UserRole::User,
+            0,
+            now,
+            now,
+            None,
+            now + 60,
+        );
+        db_utils::revoke_token(&conn, &expired).await.unwrap();
+        db_utils::revoke_token(&conn, &active).await.unwrap();
+
+        // Note: The cleaner of the other tests services may prune the expired token first
+        db_utils::prune_revoked_tokens(&conn).await.unwrap();
+        assert!(!db_utils::is_token_revoked(&conn, expired.jti)
+            .await
+            .unwrap());
+        assert!(db_utils::is_token_revoked(&conn, active.jti).await.unwrap());
+    }
+}
+
+#[cfg(test)]
+mod change_password {
+    use super::*;
+
+    const PASSWORD: &str = "fdkjhKFHDKH347(#*&";
+    const NEW_PASSWORD: &str = "kdjfhUEHF983(#*&";
+
+    async fn signin(username: &str) -> UserSigninSchema {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let user = db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: username.to_owned(),
+                password: PASSWORD.to_owned(),
+            },
+        )
+        .await
+        .expect("Failed to create user");
+        db_utils::signin_user(&conn, user, &get_jwt_keys(), &test_token_lifetimes(), true)
+            .await
+            .expect("Failed to signin user")
+    }
+
+    async fn change_password(
+        service: &Service,
+        token: &str,
+        current_password: &str,
+        new_password: &str,
+    ) -> Response {
+        send(
+            service,
+            "auth/password",
+            Method::PUT,
+            Some(&serde_json::json!({
+                "current_password": current_password,
+                "new_password": new_password,
+            })),
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    async fn send_with_token(service: &Service, path: &str, token: &str) -> Response {
+        send(
+            service,
+            path,
+            Method::GET,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    async fn signin_with(service: &Service, username: &str, password: &str) -> Response {
+        send(
+            service,
+            "auth/signin",
+            Method::POST,
+            Some(&SigninSchema {
+                username: username.to_owned(),
+                password: password.to_owned(),
+                remember_me: None,
+                totp_code: None,
+            }),
+            vec![],
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn change_password_revokes_tokens() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("ChangePasswordUser").await;
+
+        let res = change_password(&service, &user.jwt, PASSWORD, NEW_PASSWORD).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        let mut res = send_with_token(&service, "user/me", &user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["message"],
+            "The token is revoked"
+        );
+
+        // Sleep until the refresh token available
+        tokio::time::sleep(Duration::seconds(3).to_std().unwrap()).await;
+        let mut res = send_with_token(&service, "auth/refresh", &user.refresh_token).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::UNAUTHORIZED),
+            "The old refresh token should stop working"
+        );
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["message"],
+            "The token is revoked"
+        );
+
+        let res = signin_with(&service, "ChangePasswordUser", PASSWORD).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN));
+        let mut res = signin_with(&service, "ChangePasswordUser", NEW_PASSWORD).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let new_user: UserSigninSchema = res.take_json().await.unwrap();
+        let res = send_with_token(&service, "user/me", &new_user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn incorrect_current_password() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("ChangePasswordWrongUser").await;
+
+        let mut res = change_password(&service, &user.jwt, NEW_PASSWORD, NEW_PASSWORD).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN));
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["message"],
+            "Invalid signin credentials: The username or password is incorrect"
+        );
+
+        let res = send_with_token(&service, "user/me", &user.jwt).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The tokens should not be revoked"
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_new_password() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("ChangePasswordInvalidUser").await;
+
+        let res = change_password(&service, &user.jwt, PASSWORD, "short").await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST));
+        let res = change_password(&service, &user.jwt, PASSWORD, PASSWORD).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::BAD_REQUEST),
+            "The new password must be different"
+        );
+        let res = change_password(&service, &user.refresh_token, PASSWORD, NEW_PASSWORD).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::BAD_REQUEST),
+            "The refresh token can't change the password"
+        );
+
+        let res = send_with_token(&service, "user/me", &user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+    }
+}
+
+#[cfg(test)]
+mod current_user {
+    use super::*;
+
+    async fn current_user(service: &Service, token: &str) -> Response {
+        send(
+            service,
+            "auth/me",
+            Method::GET,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    async fn signin(username: &str) -> UserSigninSchema {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let user = db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: username.to_owned(),
+                password: "fdkjhKFHDKH347(#*&".to_owned(),
+            },
+        )
+        .await
+        .expect("Failed to create user");
+        db_utils::signin_user(&conn, user, &get_jwt_keys(), &test_token_lifetimes(), true)
+            .await
+            .expect("Failed to signin user")
+    }
+
+    #[tokio::test]
+    async fn current_user_profile() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("CurrentUserProfile").await;
+
+        let mut res = current_user(&service, &user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let profile = res.take_json::<serde_json::Value>().await.unwrap();
+        assert_eq!(profile["uuid"], user.user.uuid.to_string());
+        assert_eq!(profile["username"], "CurrentUserProfile");
+        assert_eq!(profile["role"], "user");
+        assert!(profile["created_at"].is_string(), "{profile}");
+        assert!(
+            profile.get("password_hash").is_none() && profile.get("password").is_none(),
+            "The password hash should not be returned {profile}"
+        );
+    }
+
+    #[tokio::test]
+    async fn current_user_with_refresh_token() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("CurrentUserRefreshToken").await;
+
+        let res = current_user(&service, &user.refresh_token).await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{res:?}");
+    }
+}
+
+#[cfg(test)]
+mod jwks {
+    use super::*;
+    use xors_api::api::jwt::{JwtAlgorithm, JwtClaims, JwtKey, JwtKeys, TokenLifetimes};
+
+    const PRIVATE_KEY: &str = include_str!("keys/jwt_rs256.pem");
+    const NEXT_PRIVATE_KEY: &str = include_str!("keys/jwt_rs256_next.pem");
+    const EDDSA_PRIVATE_KEY: &str = include_str!("keys/jwt_eddsa.pem");
+
+    fn rs256_keys() -> JwtKeys {
+        JwtKeys::rs256(PRIVATE_KEY).expect("Invalid test private key")
+    }
+
+    /// Returns a service that signs the tokens with the RS256 algorithm.
+    async fn get_rs256_service() -> Service {
+        get_keys_service(rs256_keys()).await
+    }
+
+    /// Returns the tokens lifetimes of the rotation tests, they outlive the tests requests.
+    fn lifetimes() -> TokenLifetimes {
+        TokenLifetimes {
+            jwt: Duration::seconds(30),
+            refresh: Duration::seconds(60),
+            short_refresh: Duration::seconds(50),
+            refresh_active_after: Duration::seconds(40),
+            refresh_grace_period: Duration::zero(),
+        }
+    }
+
+    /// Returns a service that signs the tokens with the keys.
+    async fn get_keys_service(jwt_keys: JwtKeys) -> Service {
+        std::env::set_var("XORS_API_TEST", "true");
+
+        xors_api::api::service(
+            get_connection().await.expect("Failed to get connection"),
+            jwt_keys,
+            xors_api::api::ServiceConfig {
+                token_lifetimes: lifetimes(),
+                ..test_service_config()
+            },
+        )
+        .0
+    }
+
+    async fn create_user(username: &str) -> UserSchema {
+        let conn = get_connection().await.expect("Failed to get connection");
+        db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: username.to_owned(),
+                password: "fdkjhKFHDKH347(#*&".to_owned(),
+            },
+        )
+        .await
+        .expect("Failed to create user")
+    }
+
+    async fn get_jwks(service: &Service) -> serde_json::Value {
+        let mut res = send(
+            service,
+            ".well-known/jwks.json",
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        res.take_json::<serde_json::Value>().await.unwrap()
+    }
+
+    async fn current_user(service: &Service, token: &str) -> Response {
+        send(
+            service,
+            "auth/me",
+            Method::GET,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn empty_jwks_with_hs256() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let jwks = get_jwks(&service).await;
+        assert_eq!(jwks, serde_json::json!({ "keys": [] }));
+    }
+
+    #[tokio::test]
+    async fn rs256_token_verifies_against_jwks() {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let service = get_rs256_service().await;
+        let user = db_utils::signin_user(
+            &conn,
+            create_user("JwksRs256Token").await,
+            &rs256_keys(),
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .expect("Failed to signin user");
+
+        let jwks = get_jwks(&service).await;
+        let jwk = &jwks["keys"][0];
+        assert_eq!(jwks["keys"].as_array().map(Vec::len), Some(1), "{jwks}");
+        assert_eq!(jwk["kty"], "RSA");
+        assert_eq!(jwk["use"], "sig");
+        assert_eq!(jwk["alg"], "RS256");
+
+        let header = jsonwebtoken::decode_header(&user.jwt).unwrap();
+        assert_eq!(header.alg, jsonwebtoken::Algorithm::RS256);
+        assert_eq!(header.kid.as_deref(), jwk["kid"].as_str());
+
+        let claims = jsonwebtoken::decode::<JwtClaims>(
+            &user.jwt,
+            &jsonwebtoken::DecodingKey::from_rsa_components(
+                jwk["n"].as_str().unwrap(),
+                jwk["e"].as_str().unwrap(),
+            )
+            .unwrap(),
+            &jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::RS256),
+        )
+        .expect("The token should be verified with the published key")
+        .claims;
+        assert_eq!(claims.uuid, user.user.uuid);
+
+        let res = current_user(&service, &user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn hs256_token_rejected_with_rs256() {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let service = get_rs256_service().await;
+        let user = db_utils::signin_user(
+            &conn,
+            create_user("JwksHs256Token").await,
+            &get_jwt_keys(),
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .expect("Failed to signin user");
+
+        let res = current_user(&service, &user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn invalid_private_key() {
+        assert!(JwtKeys::rs256("invalid key").is_err());
+        assert!(JwtKeys::rs256("").is_err());
+        assert!(JwtKeys::eddsa("invalid key").is_err());
+        assert!(JwtKeys::eddsa(PRIVATE_KEY).is_err());
+        assert!(JwtKeys::rs256(EDDSA_PRIVATE_KEY).is_err());
+    }
+
+    #[tokio::test]
+    async fn eddsa_token_verifies_against_jwks() {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let keys = JwtKeys::eddsa(EDDSA_PRIVATE_KEY).expect("Invalid test private key");
+        let service = get_keys_service(keys.clone()).await;
+        let user = db_utils::signin_user(
+            &conn,
+            create_user("JwksEdDsaToken").await,
+            &keys,
+            &lifetimes(),
+            true,
+        )
+        .await
+        .expect("Failed to signin user");
+
+        let jwks = get_jwks(&service).await;
+        let jwk = &jwks["keys"][0];
+        assert_eq!(jwk["kty"], "OKP");
+        assert_eq!(jwk["crv"], "Ed25519");
+        assert_eq!(jwk["alg"], "EdDSA");
+        assert!(jwk.get("n").is_none(), "{jwk}");
+
+        let header = jsonwebtoken::decode_header(&user.jwt).unwrap();
+        assert_eq!(header.alg, jsonwebtoken::Algorithm::EdDSA);
+        assert_eq!(header.kid.as_deref(), jwk["kid"].as_str());
+        jsonwebtoken::decode::<JwtClaims>(
+            &user.jwt,
+            &jsonwebtoken::DecodingKey::from_ed_components(jwk["x"].as_str().unwrap()).unwrap(),
+            &jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::EdDSA),
+        )
+        .expect("The token should be verified with the published key");
+
+        let res = current_user(&service, &user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn token_without_kid() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = create_user("JwksNoKidToken").await;
+        let now = chrono::Utc::now().timestamp();
+        let token = jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS256),
+            &JwtClaims::new(
+                user.uuid,
+                Uuid::new_v4(),
+                user.role,
+                0,
+                now,
+                now,
+                None,
+                now + 60,
+            ),
+            &jsonwebtoken::EncodingKey::from_secret(get_secret_key().as_bytes()),
+        )
+        .unwrap();
+
+        let res = current_user(&service, &token).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The tokens issued before the key ids should be accepted {res:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn previous_keys() {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let previous = JwtKeys::hs256("a previous secret key with 32 characters");
+        let user = db_utils::signin_user(
+            &conn,
+            create_user("JwksPreviousKey").await,
+            &previous,
+            &lifetimes(),
+            true,
+        )
+        .await
+        .expect("Failed to signin user");
+
+        let service = get_keys_service(get_jwt_keys().with_previous(vec![JwtKey::hs256(
+            "a previous secret key with 32 characters",
+        )]))
+        .await;
+        let res = current_user(&service, &user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        assert_eq!(
+            get_jwks(&service).await,
+            serde_json::json!({ "keys": [] }),
+            "The shared secrets should not be published"
+        );
+
+        let res = current_user(&get_service().await.unwrap(), &user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn key_rotation() {
+        let key_file = std::env::temp_dir().join("xors_jwt_key_rotation_test.pem");
+        std::fs::write(&key_file, PRIVATE_KEY).unwrap();
+        let keys = JwtKeys::from_file(JwtAlgorithm::RS256, &key_file).unwrap();
+        let old_kid = keys.kid();
+        let service = get_keys_service(keys.clone()).await;
+
+        let conn = get_connection().await.expect("Failed to get connection");
+        let admin = create_user("JwksRotationAdmin").await;
+        let mut active_admin = db_utils::get_user(&conn, admin.uuid).await.unwrap();
+        active_admin.role = sea_orm::Set(UserRole::Admin.to_string());
+        sea_orm::ActiveModelTrait::save(active_admin, &conn)
+            .await
+            .unwrap();
+        let admin = db_utils::signin_user(
+            &conn,
+            UserSchema {
+                role: UserRole::Admin,
+                ..admin
+            },
+            &keys,
+            &lifetimes(),
+            true,
+        )
+        .await
+        .expect("Failed to signin user");
+        let rotate = |token: String| {
+            let service = &service;
+            async move {
+                let mut res = send(
+                    service,
+                    "admin/jwt-keys/rotate",
+                    Method::POST,
+                    None::<&()>,
+                    vec![(
+                        header::AUTHORIZATION,
+                        HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                    )],
+                )
+                .await;
+                assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+                res.take_json::<JwtKeyRotationSchema>().await.unwrap()
+            }
+        };
+
+        let rotation = rotate(admin.jwt.clone()).await;
+        assert!(!rotation.rotated, "The key file has the same key");
+        assert_eq!(rotation.kid, old_kid);
+
+        std::fs::write(&key_file, NEXT_PRIVATE_KEY).unwrap();
+        let rotation = rotate(admin.jwt.clone()).await;
+        assert!(rotation.rotated);
+        assert_ne!(rotation.kid, old_kid);
+        assert_eq!(rotation.retired_kid.as_deref(), Some(old_kid.as_str()));
+        assert!(rotation.retired_until.is_some());
+
+        let res = current_user(&service, &admin.jwt).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The issued tokens should be valid after the rotation {res:?}"
+        );
+        let jwks = get_jwks(&service).await;
+        assert_eq!(jwks["keys"].as_array().map(Vec::len), Some(2), "{jwks}");
+        assert_eq!(jwks["keys"][0]["kid"].as_str(), Some(rotation.kid.as_str()));
+
+        let user = db_utils::signin_user(
+            &conn,
+            create_user("JwksRotatedToken").await,
+            &keys,
+            &lifetimes(),
+            true,
+        )
+        .await
+        .expect("Failed to signin user");
+        let header = jsonwebtoken::decode_header(&user.jwt).unwrap();
+        assert_eq!(header.kid, Some(rotation.kid));
+
+        // Without a retire period, the retired key stops validating the tokens
+        keys.rotate(JwtKey::eddsa(EDDSA_PRIVATE_KEY).unwrap(), Duration::zero());
+        let res = current_user(&service, &user.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        std::fs::remove_file(&key_file).ok();
+    }
+
+    #[tokio::test]
+    async fn rotation_without_key_file() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let admin = create_user("JwksHs256Admin").await;
+        let mut active_admin = db_utils::get_user(&conn, admin.uuid).await.unwrap();
+        active_admin.role = sea_orm::Set(UserRole::Admin.to_string());
+        sea_orm::ActiveModelTrait::save(active_admin, &conn)
+            .await
+            .unwrap();
+        let admin = db_utils::signin_user(
+            &conn,
+            UserSchema {
+                role: UserRole::Admin,
+                ..admin
+            },
+            &get_jwt_keys(),
+            &lifetimes(),
+            true,
+        )
+        .await
+        .expect("Failed to signin user");
+
+        let res = send(
+            &service,
+            "admin/jwt-keys/rotate",
+            Method::POST,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", admin.jwt)).unwrap(),
+            )],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::CONFLICT), "{res:?}");
+    }
+}
+
+#[cfg(test)]
+mod delete_account {
+    use super::*;
+
+    const PASSWORD: &str = "fdkjhKFHDKH347(#*&";
+
+    async fn signin(username: &str) -> UserSigninSchema {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let user = db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: username.to_owned(),
+                password: PASSWORD.to_owned(),
+            },
+        )
+        .await
+        .expect("Failed to create user");
+        db_utils::signin_user(&conn, user, &get_jwt_keys(), &test_token_lifetimes(), true)
+            .await
+            .expect("Failed to signin user")
+    }
+
+    async fn delete_account(service: &Service, token: &str, password: &str) -> Response {
+        send(
+            service,
+            "auth/me",
+            Method::DELETE,
+            Some(&DeleteUserSchema {
+                password: password.to_owned(),
+            }),
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    async fn signin_with(service: &Service, username: &str) -> Response {
+        send(
+            service,
+            "auth/signin",
+            Method::POST,
+            Some(&SigninSchema {
+                username: username.to_owned(),
+                password: PASSWORD.to_owned(),
+                remember_me: None,
+                totp_code: None,
+            }),
+            vec![],
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn delete_account_success() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("DeleteAccountUser").await;
+
+        let res = delete_account(&service, &user.jwt, PASSWORD).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        let res = signin_with(&service, "DeleteAccountUser").await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+
+        let mut res = send(
+            &service,
+            "user/me",
+            Method::GET,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", user.jwt)).unwrap(),
+            )],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED), "{res:?}");
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["message"],
+            "The token is revoked"
+        );
+
+        // Sleep until the refresh token available
+        tokio::time::sleep(Duration::seconds(3).to_std().unwrap()).await;
+        let res = send(
+            &service,
+            "auth/refresh",
+            Method::GET,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", user.refresh_token)).unwrap(),
+            )],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn delete_account_with_invalid_password() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("DeleteAccountInvalidPassword").await;
+
+        let res = delete_account(&service, &user.jwt, "InvalidPassword").await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+
+        let res = signin_with(&service, "DeleteAccountInvalidPassword").await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The account should not be deleted {res:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_account_with_refresh_token() {
+        let service = get_service().await.expect("Failed to get service");
+        let user = signin("DeleteAccountRefreshToken").await;
+
+        let mut res = delete_account(&service, &user.refresh_token, PASSWORD).await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{res:?}");
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["message"],
+            "The token is not user jwt"
+        );
+    }
+}
+
+#[cfg(test)]
+mod fresh_auth {
+    use super::*;
+    use xors_api::api::jwt::JwtClaims;
+
+    const PASSWORD: &str = "fdkjhKFHDKH347(#*&";
+
+    /// Returns a service that requires a password confirmation in the last minute.
+    async fn get_fresh_auth_service() -> Service {
+        std::env::set_var("XORS_API_TEST", "true");
+
+        xors_api::api::service(
+            get_connection().await.expect("Failed to get connection"),
+            get_jwt_keys(),
+            xors_api::api::ServiceConfig {
+                fresh_auth_period: Some(60),
+                ..test_service_config()
+            },
+        )
+        .0
+    }
+
+    async fn create_user(username: &str) -> UserSchema {
+        let conn = get_connection().await.expect("Failed to get connection");
+        db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: username.to_owned(),
+                password: PASSWORD.to_owned(),
+            },
+        )
+        .await
+        .expect("Failed to create user")
+    }
+
+    /// Returns a token of the user that confirmed the password two minutes ago.
+    fn stale_token(user_uuid: Uuid, active_after: Option<i64>) -> String {
+        let now = chrono::Utc::now().timestamp();
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &JwtClaims::new(
+                user_uuid,
+                Uuid::new_v4(),
+                UserRole::User,
+                0,
+                now,
+                now - 120,
+                active_after,
+                now + 60,
+            ),
+            &jsonwebtoken::EncodingKey::from_secret(get_secret_key().as_bytes()),
+        )
+        .unwrap()
+    }
+
+    async fn send_with_token<T: Serialize>(
+        service: &Service,
+        path: &str,
+        method: Method,
+        body: Option<&T>,
+        token: &str,
+    ) -> Response {
+        send(
+            service,
+            path,
+            method,
+            body,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    async fn delete_user(service: &Service, token: &str) -> Response {
+        send_with_token(
+            service,
+            "user",
+            Method::DELETE,
+            Some(&serde_json::json!({ "password": PASSWORD })),
+            token,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn stale_session_is_blocked() {
+        let service = get_fresh_auth_service().await;
+        let user = create_user("FreshAuthStaleUser").await;
+        let token = stale_token(user.uuid, None);
+
+        let mut res = delete_user(&service, &token).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["message"],
+            "Reauthentication required: Confirm your password with `/auth/confirm` to do this action"
+        );
+        let res = send_with_token(
+            &service,
+            "auth/password",
+            Method::PUT,
+            Some(&serde_json::json!({
+                "current_password": PASSWORD,
+                "new_password": "kdjfhUEHF983(#*&",
+            })),
+            &token,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+
+        let res = send_with_token(&service, "user/me", Method::GET, None::<&()>, &token).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The other actions should be allowed"
+        );
+
+        // Not required by default
+        let service = get_service().await.expect("Failed to get service");
+        let res = send_with_token(
+            &service,
+            "auth/password",
+            Method::PUT,
+            Some(&serde_json::json!({
+                "current_password": PASSWORD,
+                "new_password": "kdjfhUEHF983(#*&",
+            })),
+            &token,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn confirmed_session_succeeds() {
+        let service = get_fresh_auth_service().await;
+        let user = create_user("FreshAuthConfirmUser").await;
+        let token = stale_token(user.uuid, None);
+
+        let res = send_with_token(
+            &service,
+            "auth/confirm",
+            Method::POST,
+            Some(&ConfirmAuthSchema::new("WrongPassword".to_owned())),
+            &token,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+
+        let mut res = send_with_token(
+            &service,
+            "auth/confirm",
+            Method::POST,
+            Some(&ConfirmAuthSchema::new(PASSWORD.to_owned())),
+            &token,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let confirmed: AccessTokenSchema = res.take_json().await.unwrap();
+
+        let res = delete_user(&service, &confirmed.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn confirm_requires_totp_code() {
+        let service = get_fresh_auth_service().await;
+        let user = create_user("FreshAuthTotpUser").await;
+        let token = stale_token(user.uuid, None);
+
+        let mut res = send_with_token(
+            &service,
+            "auth/confirm",
+            Method::POST,
+            Some(&ConfirmAuthSchema::new(PASSWORD.to_owned())),
+            &token,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let confirmed: AccessTokenSchema = res.take_json().await.unwrap();
+
+        let mut res = send_with_token(
+            &service,
+            "user/2fa/enable",
+            Method::POST,
+            None::<&()>,
+            &confirmed.jwt,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let secret: TotpSecretSchema = res.take_json().await.unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let res = send_with_token(
+            &service,
+            "user/2fa/confirm",
+            Method::POST,
+            Some(&ConfirmTotpSchema::new(
+                xors_api::utils::totp_code(&secret.secret, now).unwrap(),
+            )),
+            &confirmed.jwt,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        let mut res = send_with_token(
+            &service,
+            "auth/confirm",
+            Method::POST,
+            Some(&ConfirmAuthSchema::new(PASSWORD.to_owned())),
+            &token,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["code"],
+            "totp_required"
+        );
+
+        // The code of the next period, the current one is already used
+        let mut confirm = ConfirmAuthSchema::new(PASSWORD.to_owned());
+        confirm.totp_code = xors_api::utils::totp_code(&secret.secret, now + 30);
+        let res = send_with_token(
+            &service,
+            "auth/confirm",
+            Method::POST,
+            Some(&confirm),
+            &token,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn refresh_keeps_auth_time() {
+        let service = get_fresh_auth_service().await;
+        let user = create_user("FreshAuthRefreshUser").await;
+        let refresh_token = stale_token(user.uuid, Some(chrono::Utc::now().timestamp() - 1));
+
+        let mut res = send_with_token(
+            &service,
+            "auth/refresh",
+            Method::GET,
+            None::<&()>,
+            &refresh_token,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let refreshed: UserSigninSchema = res.take_json().await.unwrap();
+
+        let res = delete_user(&service, &refreshed.jwt).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::FORBIDDEN),
+            "Refreshing the tokens doesn't confirm the password"
+        );
+    }
+}
+
+#[cfg(test)]
+mod remember_me {
+    use super::*;
+
+    async fn refresh_token_window(
+        service: &Service,
+        username: &str,
+        remember_me: Option<bool>,
+    ) -> i64 {
+        let conn = get_connection().await.expect("Failed to get connection");
+        db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: username.to_owned(),
+                password: "fdkjhKFHDKH347(#*&".to_owned(),
+            },
+        )
+        .await
+        .expect("Failed to create user");
+
+        let mut res = send(
+            service,
+            "auth/signin",
+            Method::POST,
+            Some(&SigninSchema {
+                username: username.to_owned(),
+                password: "fdkjhKFHDKH347(#*&".to_owned(),
+                remember_me,
+                totp_code: None,
+            }),
+            vec![],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let user: UserSigninSchema = res.take_json().await.unwrap();
+
+        let mut res = send(
+            service,
+            "auth/refresh/info",
+            Method::GET,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", user.refresh_token)).unwrap(),
+            )],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let info: RefreshTokenInfoSchema = res.take_json().await.unwrap();
+        info.exp - info.active_after
+    }
+
+    #[tokio::test]
+    async fn remember_me_by_default() {
+        let service = get_service().await.expect("Failed to get service");
+
+        assert_eq!(
+            refresh_token_window(&service, "RememberMeDefault", None).await,
+            2
+        );
+        assert_eq!(
+            refresh_token_window(&service, "RememberMeTrue", Some(true)).await,
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn short_refresh_token_without_remember_me() {
+        let service = get_service().await.expect("Failed to get service");
+
+        assert_eq!(
+            refresh_token_window(&service, "RememberMeFalse", Some(false)).await,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_lifetimes() {
+        let lifetimes = xors_api::api::jwt::TokenLifetimes::default();
+        assert!(lifetimes.validate().is_ok());
+
+        let jwt_outlives_refresh = xors_api::api::jwt::TokenLifetimes {
+            jwt: lifetimes.refresh + Duration::seconds(1),
+            ..lifetimes
+        };
+        assert!(jwt_outlives_refresh.validate().is_err());
+
+        let never_active = xors_api::api::jwt::TokenLifetimes {
+            refresh_active_after: lifetimes.short_refresh,
+            ..lifetimes
+        };
+        assert!(never_active.validate().is_err());
+
+        let negative_grace = xors_api::api::jwt::TokenLifetimes {
+            refresh_grace_period: Duration::seconds(-1),
+            ..lifetimes
+        };
+        assert!(negative_grace.validate().is_err());
+    }
+}
+
+#[cfg(test)]
+mod password_policy {
+    use super::*;
+    use xors_api::utils::{
+        validate_password, validate_signin_password, PasswordPolicy, PasswordRule,
+    };
+
+    const PASSWORD: &str = "fdkjhKFHDKH347(#*&";
+
+    fn violation(password: &str, policy: &PasswordPolicy) -> Option<PasswordRule> {
+        match validate_password(password, policy) {
+            Ok(()) => None,
+            Err(xors_api::errors::ApiError::PasswordPolicyViolation(rule)) => Some(rule),
+            Err(err) => panic!("Unexpected error: {err}"),
+        }
+    }
+
+    /// Returns a service with the given password policy.
+    async fn get_policy_service(password_policy: PasswordPolicy) -> Service {
+        std::env::set_var("XORS_API_TEST", "true");
+
+        xors_api::api::service(
+            get_connection().await.expect("Failed to get connection"),
+            get_jwt_keys(),
+            xors_api::api::ServiceConfig {
+                password_policy,
+                ..test_service_config()
+            },
+        )
+        .0
+    }
+
+    #[test]
+    fn default_policy() {
+        let policy = PasswordPolicy::default();
+        assert!(policy.validate().is_ok());
+        assert_eq!(violation(PASSWORD, &policy), None);
+    }
+
+    #[test]
+    fn length_rule() {
+        let policy = PasswordPolicy {
+            min_length: 20,
+            ..Default::default()
+        };
+        assert_eq!(
+            violation(PASSWORD, &policy),
+            Some(PasswordRule::Length { min: 20, max: 64 })
+        );
+        assert_eq!(
+            violation(&format!("{PASSWORD}aB3$"), &policy),
+            None,
+            "22 characters password"
+        );
+        assert_eq!(
+            violation(&PASSWORD.repeat(4), &PasswordPolicy::default()),
+            Some(PasswordRule::Length { min: 8, max: 64 })
+        );
+    }
+
+    #[test]
+    fn spaces_rule() {
+        assert_eq!(
+            violation("fdkjh KFHDKH347(#*&", &PasswordPolicy::default()),
+            Some(PasswordRule::Spaces)
+        );
+    }
+
+    #[test]
+    fn character_classes_rules() {
+        let policy = PasswordPolicy {
+            min_score: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            violation("fdkjhkfhdkh347(#*&", &policy),
+            Some(PasswordRule::Uppercase)
+        );
+        assert_eq!(
+            violation("FDKJHKFHDKH347(#*&", &policy),
+            Some(PasswordRule::Lowercase)
+        );
+        assert_eq!(
+            violation("fdkjhKFHDKHsjd(#*&", &policy),
+            Some(PasswordRule::Digit)
+        );
+        assert_eq!(
+            violation("fdkjhKFHDKH347sdfA", &policy),
+            Some(PasswordRule::Symbol)
+        );
+
+        let relaxed = PasswordPolicy {
+            require_uppercase: false,
+            require_lowercase: false,
+            require_digit: false,
+            require_symbol: false,
+            min_score: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(violation("fdkjhkfhdkhsjdlkf", &relaxed), None);
+    }
+
+    #[test]
+    fn common_rule() {
+        let policy = PasswordPolicy {
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            min_score: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(violation("password", &policy), Some(PasswordRule::Common));
+        assert_eq!(
+            violation(
+                "password",
+                &PasswordPolicy {
+                    block_common: false,
+                    ..policy.clone()
+                }
+            ),
+            None
+        );
+
+        let blocklist = PasswordPolicy {
+            blocklist: [PASSWORD.to_lowercase()].into_iter().collect(),
+            ..Default::default()
+        };
+        assert_eq!(violation(PASSWORD, &blocklist), Some(PasswordRule::Common));
+    }
+
+    #[test]
+    fn weak_rule() {
+        let policy = PasswordPolicy {
+            min_score: 100.0,
+            ..Default::default()
+        };
+        assert_eq!(violation("fdkjhK7(", &policy), Some(PasswordRule::Weak));
+    }
+
+    #[test]
+    fn invalid_policy() {
+        for policy in [
+            PasswordPolicy {
+                min_length: 0,
+                ..Default::default()
+            },
+            PasswordPolicy {
+                min_length: 30,
+                max_length: 20,
+                ..Default::default()
+            },
+            PasswordPolicy {
+                max_length: 100,
+                ..Default::default()
+            },
+            PasswordPolicy {
+                min_score: 101.0,
+                ..Default::default()
+            },
+        ] {
+            assert!(policy.validate().is_err(), "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn signin_password_length_only() {
+        assert!(validate_signin_password("1234").is_ok());
+        assert!(validate_signin_password("").is_err());
+        assert!(validate_signin_password(&"a".repeat(73)).is_err());
+    }
+
+    #[tokio::test]
+    async fn signup_enforces_policy() {
+        let service = get_policy_service(PasswordPolicy {
+            min_length: 20,
+            ..Default::default()
+        })
+        .await;
+
+        let mut res = send(
+            &service,
+            "auth/signup",
+            Method::POST,
+            Some(&NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: "PasswordPolicySignup".to_owned(),
+                password: PASSWORD.to_owned(),
+            }),
+            vec![],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{res:?}");
+        let body = res.take_json::<serde_json::Value>().await.unwrap();
+        assert_eq!(body["code"], "password_length");
+        assert_eq!(
+            body["message"],
+            "Invalid password: The password must be between 20 and 64 characters"
+        );
+    }
+
+    #[tokio::test]
+    async fn signin_ignores_policy() {
+        let conn = get_connection().await.expect("Failed to get connection");
+        db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: "PasswordPolicySignin".to_owned(),
+                password: PASSWORD.to_owned(),
+            },
+        )
+        .await
+        .expect("Failed to create user");
+        let service = get_policy_service(PasswordPolicy {
+            min_length: 20,
+            ..Default::default()
+        })
+        .await;
+
+        let res = send(
+            &service,
+            "auth/signin",
+            Method::POST,
+            Some(&SigninSchema {
+                username: "PasswordPolicySignin".to_owned(),
+                password: PASSWORD.to_owned(),
+                remember_me: None,
+                totp_code: None,
+            }),
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The old passwords should still signin {res:?}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod username_normalization {
+    use super::*;
+
+    const PASSWORD: &str = "fdkjhKFHDKH347(#*&";
+
+    async fn create_user(username: &str) -> UserSchema {
+        let conn = get_connection().await.expect("Failed to get connection");
+        db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: username.to_owned(),
+                password: PASSWORD.to_owned(),
+            },
+        )
+        .await
+        .expect("Failed to create user")
+    }
+
+    async fn signup(service: &Service, username: &str) -> Response {
+        send(
+            service,
+            "auth/signup",
+            Method::POST,
+            Some(&NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: username.to_owned(),
+                password: PASSWORD.to_owned(),
+            }),
+            vec![],
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn case_collision() {
+        let service = get_service().await.expect("Failed to get service");
+        create_user("NormalizeCase").await;
+
+        for username in ["normalizecase", "NORMALIZECASE"] {
+            let mut res = signup(&service, username).await;
+            assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{res:?}");
+            assert_eq!(
+                res.take_json::<serde_json::Value>().await.unwrap()["message"],
+                format!("Username `{username}` already exists")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn whitespace_collision() {
+        let service = get_service().await.expect("Failed to get service");
+        create_user("NormalizeSpaces").await;
+
+        let mut res = signup(&service, " normalizespaces ").await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{res:?}");
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["code"],
+            "username_already_exists"
+        );
+
+        let mut res = signup(&service, "Normalize  Spaces").await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{res:?}");
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["code"],
+            "invalid_username"
+        );
+    }
+
+    #[tokio::test]
+    async fn confusable_username() {
+        let service = get_service().await.expect("Failed to get service");
+        create_user("NormalizeConfusable").await;
+
+        // The `о` is the Cyrillic small letter o
+        let mut res = signup(&service, "N\u{043e}rmalizeConfusable").await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{res:?}");
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["code"],
+            "invalid_username"
+        );
+    }
+
+    #[tokio::test]
+    async fn case_insensitive_signin() {
+        let service = get_service().await.expect("Failed to get service");
+        create_user("NormalizeSignin").await;
+
+        let mut res = send(
+            &service,
+            "auth/signin",
+            Method::POST,
+            Some(&SigninSchema {
+                username: " NORMALIZESIGNIN".to_owned(),
+                password: PASSWORD.to_owned(),
+                remember_me: None,
+                totp_code: None,
+            }),
+            vec![],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let user: UserSigninSchema = res.take_json().await.unwrap();
+        assert_eq!(
+            user.user.username, "NormalizeSignin",
+            "The display username should be kept"
+        );
+    }
+
+    #[tokio::test]
+    async fn taken_normalized_username() {
+        let service = get_service().await.expect("Failed to get service");
+        create_user("NormalizeAvailable").await;
+
+        let mut res = send(
+            &service,
+            "auth/usernames-available",
+            Method::POST,
+            Some(&UsernamesSchema {
+                usernames: vec!["normalizeavailable".to_owned()],
+            }),
+            vec![],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let availability = res.take_json::<serde_json::Value>().await.unwrap();
+        assert_eq!(availability[0]["available"], false, "{availability}");
+    }
+}
+
+#[cfg(test)]
+mod usernames_available {
+    use super::*;
+
+    #[tokio::test]
+    async fn usernames_availability() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+
+        db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                username: "usernames_available_taken".to_owned(),
+                first_name: "First".to_owned(),
+                password: "fdlkFDLKF#$3213!".to_owned(),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to create user");
+
+        let mut res = send(
+            &service,
+            "auth/usernames-available",
+            Method::POST,
+            Some(&UsernamesSchema {
+                usernames: vec![
+                    "usernames_available_taken".to_owned(),
+                    "usernames_available_free".to_owned(),
+                    "1usernames_available".to_owned(),
+                ],
+            }),
+            vec![],
+        )
+        .await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+
+        let availability: Vec<UsernameAvailabilitySchema> = serde_json::from_str(
+            &res.take_string()
+                .await
+                .expect("Could not get the response body"),
+        )
+        .expect("Failed to parse response body");
+
+        assert_eq!(availability.len(), 3);
+        assert!(!availability[0].available);
+        assert!(
+            availability[0]
+                .reason
+                .as_deref()
+                .is_some_and(|r| r.contains("already exists")),
+            "The taken username should have the already exists reason {availability:?}"
+        );
+        assert!(availability[1].available);
+        assert!(availability[1].reason.is_none());
+        assert!(!availability[2].available);
+        assert!(
+            availability[2]
+                .reason
+                .as_deref()
+                .is_some_and(|r| r.starts_with("Invalid username")),
+            "The invalid username should have the validation reason {availability:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn too_many_usernames() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let res = send(
+            &service,
+            "auth/usernames-available",
+            Method::POST,
+            Some(&UsernamesSchema {
+                usernames: (0..11).map(|i| format!("Username{i}")).collect(),
+            }),
+            vec![],
+        )
+        .await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::BAD_REQUEST),
+            "The response should have a `400 BAD_REQUEST` status code {res:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn rate_limited() {
+        let service = get_service().await.expect("Failed to get service");
+        let usernames = UsernamesSchema::default();
+
+        for _ in 0..5 {
+            let res = send(
+                &service,
+                "auth/usernames-available",
+                Method::POST,
+                Some(&usernames),
+                vec![],
+            )
+            .await;
+            assert_eq!(
+                res.status_code,
+                Some(StatusCode::OK),
+                "The response should have a `200 OK` status code {res:?}"
+            );
+        }
+
+        let res = send(
+            &service,
+            "auth/usernames-available",
+            Method::POST,
+            Some(&usernames),
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::TOO_MANY_REQUESTS),
+            "The response should have a `429 TOO_MANY_REQUESTS` status code {res:?}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod captcha_options {
+    use super::*;
+    use salvo_captcha::{CacacheStorage, CaptchaStorage};
+    use xors_api::api::captcha_storage::{CaptchaAnswer, CaptchaAnswerStorage};
+
+    async fn create_captcha(service: &Service, query: &str) -> Response {
+        send(
+            service,
+            &format!("auth/captcha{query}"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn default_difficulty() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let mut res = create_captcha(&service, "").await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let captcha: CaptchaSchema = res.take_json().await.unwrap();
+        assert_eq!(captcha.difficulty, CaptchaDifficulty::Medium);
+    }
+
+    #[tokio::test]
+    async fn chosen_difficulty_and_length() {
+        let service = get_service().await.expect("Failed to get service");
+
+        for (query, difficulty) in [
+            ("?difficulty=easy&length=4", CaptchaDifficulty::Easy),
+            ("?difficulty=medium&length=7", CaptchaDifficulty::Medium),
+            ("?difficulty=hard", CaptchaDifficulty::Hard),
+        ] {
+            let mut res = create_captcha(&service, query).await;
+            assert_eq!(res.status_code, Some(StatusCode::OK), "{query} {res:?}");
+            let captcha: CaptchaSchema = res.take_json().await.unwrap();
+            assert_eq!(captcha.difficulty, difficulty);
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_options() {
+        let service = get_service().await.expect("Failed to get service");
+
+        for query in [
+            "?difficulty=impossible",
+            "?length=3",
+            "?length=8",
+            "?length=five",
+        ] {
+            let res = create_captcha(&service, query).await;
+            assert_eq!(
+                res.status_code,
+                Some(StatusCode::BAD_REQUEST),
+                "{query} {res:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn answer_case_sensitivity() {
+        let storage = CaptchaAnswerStorage::new(CacacheStorage::new(
+            std::env::temp_dir().join("xors_captcha_options_test"),
+        ));
+
+        let token = storage
+            .store_answer(CaptchaAnswer::new("AbCd".to_owned(), false))
+            .await
+            .unwrap();
+        let answer = storage.get_answer(&token).await.unwrap().unwrap();
+        assert_eq!(answer, CaptchaAnswer::from("abcd".to_owned()));
+        assert_ne!(answer, CaptchaAnswer::from("abce".to_owned()));
+
+        let token = storage
+            .store_answer(CaptchaAnswer::new("AbCd".to_owned(), true))
+            .await
+            .unwrap();
+        let answer = storage.get_answer(&token).await.unwrap().unwrap();
+        assert_eq!(answer, CaptchaAnswer::from("AbCd".to_owned()));
+        assert_ne!(answer, CaptchaAnswer::from("abcd".to_owned()));
+    }
+}
+
+#[cfg(test)]
+mod captcha_preview {
+    use super::*;
+
+    #[tokio::test]
+    async fn disabled_by_default() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let res = send::<()>(&service, "auth/captcha/preview", Method::GET, None, vec![]).await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::NOT_FOUND),
+            "The response should have a `404 Not Found` status code {res:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn preview_captcha() {
+        std::env::set_var("XORS_API_TEST", "true");
+        let service = xors_api::api::service(
+            get_connection().await.expect("Failed to get connection"),
+            get_jwt_keys(),
+            xors_api::api::ServiceConfig {
+                captcha_preview: true,
+                ..test_service_config()
+            },
+        )
+        .0;
+
+        // The preview is not rate limited
+        for _ in 0..31 {
+            let mut res =
+                send::<()>(&service, "auth/captcha/preview", Method::GET, None, vec![]).await;
+            assert_eq!(
+                res.status_code,
+                Some(StatusCode::OK),
+                "The response should have a `200 OK` status code {res:?}"
+            );
+
+            let captcha: CaptchaPreviewSchema = serde_json::from_str(
+                &res.take_string()
+                    .await
+                    .expect("Could not get the response body"),
+            )
+            .expect("Failed to parse response body");
+            assert!(captcha.captcha_image.starts_with("data:image/png;base64,"));
+            assert!(!captcha.captcha_answer.is_empty());
+        }
+    }
+}
+
+#[cfg(test)]
+mod guest {
+    use super::*;
+
+    const PASSWORD: &str = "fdkjhKFHDKH347(#*&";
+
+    async fn signin_guest(service: &Service) -> UserSigninSchema {
+        let mut res = send::<()>(service, "auth/guest", Method::POST, None, vec![]).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        res.take_json::<UserSigninSchema>().await.unwrap()
+    }
+
+    async fn upgrade(service: &Service, token: &str, username: &str) -> Response {
+        send(
+            service,
+            "auth/upgrade",
+            Method::POST,
+            Some(&NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: username.to_owned(),
+                password: PASSWORD.to_owned(),
+            }),
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    async fn backdate_guest(conn: &sea_orm::DatabaseConnection, guest_uuid: Uuid) {
+        let mut guest = db_utils::get_user(conn, guest_uuid).await.unwrap();
+        guest.created_at = sea_orm::Set(
+            chrono::Utc::now().naive_utc() - Duration::days(db_utils::GUEST_INACTIVITY_DAYS + 1),
+        );
+        sea_orm::ActiveModelTrait::save(guest, conn).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn guest_signin() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let guest = signin_guest(&service).await;
+        assert_eq!(guest.user.role, UserRole::Guest);
+        assert!(guest.user.username.starts_with("guest_"), "{guest:?}");
+        assert_ne!(signin_guest(&service).await.user.uuid, guest.user.uuid);
+    }
+
+    #[tokio::test]
+    async fn upgrade_guest_keeps_games() {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let service = get_service().await.expect("Failed to get service");
+        let guest = signin_guest(&service).await;
+        let opponent = signin_guest(&service).await;
+        let game = db_utils::create_game(&conn, guest.user.uuid, opponent.user.uuid, 10, false, 0)
+            .await
+            .unwrap();
+        db_utils::end_game(
+            &conn,
+            game.uuid.as_ref(),
+            Some(guest.user.uuid),
+            &GameOverReason::PlayerWon,
+        )
+        .await
+        .unwrap();
+
+        let mut res = upgrade(&service, &guest.jwt, "UpgradedGuest").await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let user = res.take_json::<UserSigninSchema>().await.unwrap().user;
+        assert_eq!(user.uuid, guest.user.uuid);
+        assert_eq!(user.username, "UpgradedGuest");
+        assert_eq!(user.role, UserRole::User);
+        assert_eq!(user.latest_games, vec![*game.uuid.as_ref()]);
+        // The guest games are unranked
+        assert_eq!(
+            (user.wins, user.rating),
+            (0, xors_api::utils::INITIAL_RATING)
+        );
+
+        let res = send(
+            &service,
+            "auth/signin",
+            Method::POST,
+            Some(&SigninSchema {
+                username: "UpgradedGuest".to_owned(),
+                password: PASSWORD.to_owned(),
+                remember_me: None,
+                totp_code: None,
+            }),
+            vec![],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn upgrade_regular_user() {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let service = get_service().await.expect("Failed to get service");
+        let user = db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: "UpgradeRegularUser".to_owned(),
+                password: PASSWORD.to_owned(),
+            },
+        )
+        .await
+        .unwrap();
+        let user =
+            db_utils::signin_user(&conn, user, &get_jwt_keys(), &test_token_lifetimes(), true)
+                .await
+                .unwrap();
+
+        let mut res = upgrade(&service, &user.jwt, "UpgradeRegularUser2").await;
+        assert_eq!(res.status_code, Some(StatusCode::CONFLICT), "{res:?}");
+        let body = res.take_json::<serde_json::Value>().await.unwrap();
+        assert_eq!(body["code"], "not_guest", "{body}");
+    }
+
+    #[tokio::test]
+    async fn upgrade_with_taken_username() {
+        let service = get_service().await.expect("Failed to get service");
+        let guest = signin_guest(&service).await;
+        let other_guest = signin_guest(&service).await;
+
+        let res = upgrade(&service, &guest.jwt, &other_guest.user.username).await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn prune_inactive_guests() {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let inactive = db_utils::create_guest(&conn).await.unwrap();
+        let playing = db_utils::create_guest(&conn).await.unwrap();
+        let recent = db_utils::create_guest(&conn).await.unwrap();
+        backdate_guest(&conn, inactive.uuid).await;
+        backdate_guest(&conn, playing.uuid).await;
+        db_utils::create_game(&conn, playing.uuid, recent.uuid, 10, false, 0)
+            .await
+            .unwrap();
+
+        db_utils::prune_inactive_guests(&conn).await.unwrap();
+        assert!(db_utils::get_user(&conn, inactive.uuid).await.is_err());
+        assert!(db_utils::get_user(&conn, playing.uuid).await.is_ok());
+        assert!(db_utils::get_user(&conn, recent.uuid).await.is_ok());
+    }
+}
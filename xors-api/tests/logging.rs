@@ -0,0 +1,145 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+include!("mod.rs");
+
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use xors_api::logging::*;
+
+/// The captured log messages.
+static CAPTURED_LOGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// A logger that captures the log messages.
+struct CaptureLogger;
+
+impl log::Log for CaptureLogger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        CAPTURED_LOGS
+            .lock()
+            .unwrap()
+            .push(record.args().to_string());
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the redacting capture logger, once for all the tests.
+fn capture_logs() {
+    static INSTALLED: Lazy<()> = Lazy::new(|| {
+        log::set_boxed_logger(Box::new(RedactingLogger::new(CaptureLogger)))
+            .expect("Failed to set the logger");
+        log::set_max_level(log::LevelFilter::Trace);
+    });
+    Lazy::force(&INSTALLED);
+}
+
+#[cfg(test)]
+mod redact {
+    use super::*;
+
+    #[test]
+    fn sensitive_fields() {
+        assert_eq!(
+            redact(r#"{"username":"awiteb","password":"s3cr\"et"}"#),
+            r#"{"username":"awiteb","password":"[redacted]"}"#
+        );
+        assert_eq!(
+            redact(r#"NewUserSchema { username: "awiteb", password: "secret" }"#),
+            r#"NewUserSchema { username: "awiteb", password: "[redacted]" }"#
+        );
+        assert_eq!(
+            redact(r#"{"x-captcha-answer": "abc", "Authorization": "Bearer eyJ.a.b"}"#),
+            r#"{"x-captcha-answer": "[redacted]", "Authorization": "[redacted]"}"#
+        );
+        assert_eq!(
+            redact("ChangePassword { old_password: \"old\", new_password: \"new\" }"),
+            "ChangePassword { old_password: \"[redacted]\", new_password: \"[redacted]\" }"
+        );
+        assert_eq!(
+            redact("/auth/signin?totp_code=123456&username=awiteb"),
+            "/auth/signin?totp_code=[redacted]&username=awiteb"
+        );
+        assert_eq!(
+            redact("Authorization header: Bearer eyJhbGciOi.eyJ1dWlk.c2lnbg"),
+            "Authorization header: Bearer [redacted]"
+        );
+    }
+
+    #[test]
+    fn non_sensitive_messages() {
+        for message in [
+            "Creating user: awiteb",
+            "Invalid password: The password must be at least 8 characters",
+            "The player token is expired, closing the connection",
+            "Ending game: 5b1f0bd0-9ab4-4f24-9a6c-2d1c9d3e0b8e",
+        ] {
+            assert_eq!(redact(message), message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod logger {
+    use super::*;
+
+    #[tokio::test]
+    async fn password_never_logged() {
+        capture_logs();
+        let service = get_service().await.expect("Failed to get service");
+        let password = "fdkDFLKJL4859#$&logged";
+        let new_user = NewUserSchema {
+            username: "redacted_logs".to_owned(),
+            first_name: "Player".to_owned(),
+            password: password.to_owned(),
+            ..Default::default()
+        };
+
+        log::trace!(
+            "Signup request body: {}",
+            serde_json::to_string(&new_user).unwrap()
+        );
+        log::debug!("Signup schema: {new_user:?}");
+        let res = send(
+            &service,
+            "auth/signup",
+            Method::POST,
+            Some(&new_user),
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+
+        let logs = CAPTURED_LOGS.lock().unwrap();
+        assert!(
+            logs.iter().all(|message| !message.contains(password)),
+            "The password should never be logged"
+        );
+        assert!(
+            logs.iter().any(|message| message.contains(REDACTED)),
+            "The password should be redacted"
+        );
+    }
+}
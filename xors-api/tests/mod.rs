@@ -70,8 +70,20 @@ where
     service.call(req).await
 }
 
+/// Installs the passwords hasher with the smallest costs, to keep the tests fast.
+pub fn install_test_password_hasher() {
+    // Another test may have installed it already
+    let _ = xors_api::utils::PasswordHasher {
+        memory_cost: argon2::Params::MIN_M_COST,
+        time_cost: argon2::Params::MIN_T_COST,
+        parallelism: argon2::Params::MIN_P_COST,
+    }
+    .install();
+}
+
 /// Returns database connection.
 pub async fn get_connection() -> ApiResult<sea_orm::DatabaseConnection> {
+    install_test_password_hasher();
     Ok(sea_orm::Database::connect(
         env::var("XORS_API_DATABASE_URL")
             .expect("`XORS_API_DATABASE_URL` environment variable must be set"),
@@ -84,10 +96,74 @@ pub fn get_secret_key() -> String {
     env::var("XORS_API_SECRET_KEY").expect("`XORS_API_SECRET_KEY` environment variable must be set")
 }
 
+/// Returns the tokens keys, signed with the secret key.
+pub fn get_jwt_keys() -> xors_api::api::jwt::JwtKeys {
+    xors_api::api::jwt::JwtKeys::hs256(&get_secret_key())
+}
+
+/// Returns the tokens lifetimes of the tests, they are short so the tests can wait for the tokens to expire.
+pub fn test_token_lifetimes() -> xors_api::api::jwt::TokenLifetimes {
+    xors_api::api::jwt::TokenLifetimes {
+        jwt: Duration::seconds(2),
+        refresh: Duration::seconds(5),
+        short_refresh: Duration::seconds(4),
+        refresh_active_after: Duration::seconds(3),
+        refresh_grace_period: Duration::zero(),
+    }
+}
+
+/// Returns the service config of the tests.
+pub fn test_service_config() -> xors_api::api::ServiceConfig {
+    xors_api::api::ServiceConfig {
+        token_lifetimes: test_token_lifetimes(),
+        token_expiring_before: Some(1),
+        ..Default::default()
+    }
+}
+
 /// Returns the service.
 pub async fn get_service() -> ApiResult<Service> {
     // Set the test environment variable to true
     std::env::set_var("XORS_API_TEST", "true");
 
-    Ok(xors_api::api::service(get_connection().await?, 100, 10, get_secret_key()).0)
+    Ok(xors_api::api::service(
+        get_connection().await?,
+        get_jwt_keys(),
+        test_service_config(),
+    )
+    .0)
+}
+
+/// The password of the players created by [`create_player`].
+pub const PLAYER_PASSWORD: &str = "fdkDFLKJL4859#$&";
+
+/// Creates a player with the given username.
+pub async fn create_player(conn: &sea_orm::DatabaseConnection, username: &str) -> UserSchema {
+    db_utils::create_user(
+        conn,
+        NewUserSchema {
+            username: username.to_owned(),
+            first_name: "Player".to_owned(),
+            password: PLAYER_PASSWORD.to_owned(),
+            ..Default::default()
+        },
+    )
+    .await
+    .expect("Failed to create player")
+}
+
+/// Creates a player with the given username then signs it in, with the tests tokens lifetimes.
+pub async fn create_signed_in_player(
+    conn: &sea_orm::DatabaseConnection,
+    username: &str,
+) -> UserSigninSchema {
+    db_utils::signin_user(
+        conn,
+        create_player(conn, username).await,
+        &get_jwt_keys(),
+        &test_token_lifetimes(),
+        true,
+    )
+    .await
+    .expect("Failed to signin player")
 }
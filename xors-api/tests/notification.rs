@@ -0,0 +1,310 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+include!("mod.rs");
+
+use futures_util::StreamExt;
+use xors_api::api::jwt::TokenLifetimes;
+
+/// Creates a new user with the given username, returns its uuid and jwt.
+async fn new_user(username: &str) -> (Uuid, String) {
+    let conn = get_connection().await.expect("Failed to get connection");
+    let user = db_utils::create_user(
+        &conn,
+        NewUserSchema {
+            username: username.to_owned(),
+            first_name: "Notified".to_owned(),
+            password: "fdkDFLKJL4859#$&".to_owned(),
+            ..Default::default()
+        },
+    )
+    .await
+    .expect("Failed to create user");
+    // The tests send several requests with the same token
+    let lifetimes = TokenLifetimes {
+        jwt: Duration::seconds(30),
+        refresh: Duration::seconds(60),
+        short_refresh: Duration::seconds(50),
+        refresh_active_after: Duration::seconds(40),
+        refresh_grace_period: Duration::zero(),
+    };
+    let signin = db_utils::signin_user(&conn, user, &get_jwt_keys(), &lifetimes, true)
+        .await
+        .expect("Failed to signin user");
+    (signin.user.uuid, signin.jwt)
+}
+
+/// Returns the authorization header of the jwt.
+fn auth_header(jwt: &str) -> Vec<(HeaderName, HeaderValue)> {
+    vec![(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {jwt}")).unwrap(),
+    )]
+}
+
+async fn get_notifications(service: &Service, jwt: &str, query: &str) -> Vec<NotificationSchema> {
+    let mut res = send::<()>(
+        service,
+        &format!("notifications{query}"),
+        Method::GET,
+        None,
+        auth_header(jwt),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    res.take_json().await.unwrap()
+}
+
+async fn send_friend_request(service: &Service, jwt: &str, user_uuid: Uuid) {
+    let res = send(
+        service,
+        "friends/requests",
+        Method::POST,
+        Some(&NewFriendRequestSchema::new(user_uuid)),
+        auth_header(jwt),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+}
+
+async fn assert_code(res: &mut Response, status_code: StatusCode, code: &str) {
+    assert_eq!(res.status_code, Some(status_code), "{res:?}");
+    let body = res.take_json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["code"], code, "{body}");
+}
+
+/// Makes the two users friends, the first user sends the request and the second accepts it.
+async fn make_friends(
+    service: &Service,
+    (user_uuid, jwt): (Uuid, &str),
+    (other_uuid, other_jwt): (Uuid, &str),
+) {
+    send_friend_request(service, jwt, other_uuid).await;
+    let res = send::<()>(
+        service,
+        &format!("friends/requests/{user_uuid}/accept"),
+        Method::POST,
+        None,
+        auth_header(other_jwt),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+}
+
+#[tokio::test]
+async fn friend_request_notification() {
+    let service = get_service().await.unwrap();
+    let (requester, requester_jwt) = new_user("NotifRequester").await;
+    let (addressee, addressee_jwt) = new_user("NotifAddressee").await;
+    assert!(get_notifications(&service, &addressee_jwt, "")
+        .await
+        .is_empty());
+
+    send_friend_request(&service, &requester_jwt, addressee).await;
+
+    let notifications = get_notifications(&service, &addressee_jwt, "").await;
+    assert_eq!(notifications.len(), 1);
+    assert_eq!(notifications[0].kind, NotificationKind::FriendRequest);
+    assert!(notifications[0].read_at.is_none());
+    assert!(matches!(
+        &notifications[0].data,
+        NotificationData::FriendRequest { requester: uuid, username }
+            if *uuid == requester && username == "NotifRequester"
+    ));
+    assert!(get_notifications(&service, &requester_jwt, "")
+        .await
+        .is_empty());
+}
+
+#[tokio::test]
+async fn mark_read() {
+    let service = get_service().await.unwrap();
+    let (_, requester1_jwt) = new_user("MarkReadRequester1").await;
+    let (_, requester2_jwt) = new_user("MarkReadRequester2").await;
+    let (addressee, addressee_jwt) = new_user("MarkReadAddressee").await;
+    send_friend_request(&service, &requester1_jwt, addressee).await;
+    send_friend_request(&service, &requester2_jwt, addressee).await;
+
+    let notifications = get_notifications(&service, &addressee_jwt, "").await;
+    assert_eq!(notifications.len(), 2);
+    let read_path = format!("notifications/{}/read", notifications[1].uuid);
+    let mut res = send::<()>(
+        &service,
+        &read_path,
+        Method::POST,
+        None,
+        auth_header(&addressee_jwt),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    let read = res.take_json::<NotificationSchema>().await.unwrap();
+    assert_eq!(read.uuid, notifications[1].uuid);
+    assert!(read.read_at.is_some());
+
+    let unread = get_notifications(&service, &addressee_jwt, "?unread=true").await;
+    assert_eq!(unread.len(), 1);
+    assert_eq!(unread[0].uuid, notifications[0].uuid);
+
+    // Only the notification user can mark it
+    let mut res = send::<()>(
+        &service,
+        &format!("notifications/{}/read", unread[0].uuid),
+        Method::POST,
+        None,
+        auth_header(&requester1_jwt),
+    )
+    .await;
+    assert_code(&mut res, StatusCode::NOT_FOUND, "notification_not_found").await;
+
+    let res = send::<()>(
+        &service,
+        "notifications/read",
+        Method::POST,
+        None,
+        auth_header(&addressee_jwt),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    assert!(get_notifications(&service, &addressee_jwt, "?unread=true")
+        .await
+        .is_empty());
+    let notifications = get_notifications(&service, &addressee_jwt, "").await;
+    assert_eq!(notifications.len(), 2);
+    assert_eq!(notifications[1].read_at, read.read_at);
+}
+
+#[tokio::test]
+async fn invite_friend() {
+    let service = get_service().await.unwrap();
+    let (host, host_jwt) = new_user("InviteFriendHost").await;
+    let (friend, friend_jwt) = new_user("InviteFriendFriend").await;
+    let (stranger, _) = new_user("InviteFriendStranger").await;
+    make_friends(&service, (host, &host_jwt), (friend, &friend_jwt)).await;
+
+    let mut res = send::<()>(
+        &service,
+        &format!("friends/{stranger}/invite"),
+        Method::POST,
+        None,
+        auth_header(&host_jwt),
+    )
+    .await;
+    assert_code(&mut res, StatusCode::FORBIDDEN, "not_friend").await;
+
+    let mut res = send::<()>(
+        &service,
+        &format!("friends/{friend}/invite"),
+        Method::POST,
+        None,
+        auth_header(&host_jwt),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    let invite = res.take_json::<InviteSchema>().await.unwrap();
+
+    // The friend request notification then the invite notification
+    let notifications = get_notifications(&service, &friend_jwt, "").await;
+    assert_eq!(notifications.len(), 2);
+    assert_eq!(notifications[0].kind, NotificationKind::GameInvite);
+    assert!(matches!(
+        &notifications[0].data,
+        NotificationData::GameInvite { host: uuid, code, .. }
+            if *uuid == host && *code == invite.code
+    ));
+}
+
+#[tokio::test]
+async fn tournament_match_notifications() {
+    let service = get_service().await.unwrap();
+    let (_, host_jwt) = new_user("NotifTournamentHost").await;
+    let (player1, player1_jwt) = new_user("NotifTournamentPlayer1").await;
+    let (player2, player2_jwt) = new_user("NotifTournamentPlayer2").await;
+    let mut res = send(
+        &service,
+        "tournaments",
+        Method::POST,
+        Some(&NewTournamentSchema::new("Notified cup".to_owned(), 2)),
+        auth_header(&host_jwt),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    let tournament = res.take_json::<TournamentSchema>().await.unwrap().uuid;
+
+    for jwt in [&player1_jwt, &player2_jwt] {
+        let res = send::<()>(
+            &service,
+            &format!("tournaments/{tournament}/join"),
+            Method::POST,
+            None,
+            auth_header(jwt),
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    }
+
+    for (jwt, player, opponent) in [
+        (&player1_jwt, player1, player2),
+        (&player2_jwt, player2, player1),
+    ] {
+        let notifications = get_notifications(&service, jwt, "").await;
+        assert_eq!(notifications.len(), 1, "{player}");
+        assert!(matches!(
+            &notifications[0].data,
+            NotificationData::TournamentMatch { tournament: uuid, round: 1, opponent: other, .. }
+                if *uuid == tournament && *other == opponent
+        ));
+    }
+}
+
+#[tokio::test]
+async fn stream() {
+    let service = get_service().await.unwrap();
+    let (_, requester_jwt) = new_user("StreamRequester").await;
+    let (addressee, addressee_jwt) = new_user("StreamAddressee").await;
+
+    let mut res = send::<()>(
+        &service,
+        "notifications/stream",
+        Method::GET,
+        None,
+        auth_header(&addressee_jwt),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    assert_eq!(
+        res.headers().get(header::CONTENT_TYPE).unwrap(),
+        "text/event-stream"
+    );
+    send_friend_request(&service, &requester_jwt, addressee).await;
+
+    let mut body = res.take_body();
+    let event = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        let mut event = String::new();
+        while !event.ends_with("\n\n") {
+            let frame = body.next().await.unwrap().unwrap();
+            event.push_str(&String::from_utf8_lossy(frame.data_ref().unwrap()));
+        }
+        event
+    })
+    .await
+    .expect("The notification is not streamed");
+    let notification = &get_notifications(&service, &addressee_jwt, "").await[0];
+    assert!(event.contains("event:friend_request"), "{event}");
+    assert!(
+        event.contains(&format!("id:{}", notification.uuid)),
+        "{event}"
+    );
+}
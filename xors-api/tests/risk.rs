@@ -0,0 +1,468 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+include!("mod.rs");
+
+use std::collections::HashSet;
+use std::time::Duration as StdDuration;
+use xors_api::api::risk::*;
+
+/// Returns a signup request from the given IP.
+fn signup_request(ip: Ipv4Addr) -> Request {
+    let mut req = RequestBuilder::new(format!("{API_URL}/auth/signup"), Method::POST).build();
+    *req.remote_addr_mut() = SocketAddr::IPv4(SocketAddrV4::new(ip, 5802));
+    req
+}
+
+#[cfg(test)]
+mod activity_risk_assessor {
+    use super::*;
+
+    #[test]
+    fn many_signups() {
+        let assessor = ActivityRiskAssessor::new(
+            2,
+            StdDuration::from_secs(60),
+            StdDuration::ZERO,
+            HashSet::new(),
+        );
+        let ip = Ipv4Addr::new(127, 2, 2, 1);
+
+        assert!(!assessor.is_suspicious(&signup_request(ip)));
+        assert!(!assessor.is_suspicious(&signup_request(ip)));
+        assert!(
+            assessor.is_suspicious(&signup_request(ip)),
+            "The third signup in the window should be suspicious"
+        );
+        assert!(
+            !assessor.is_suspicious(&signup_request(Ipv4Addr::new(127, 2, 2, 2))),
+            "The other IPs should not be affected"
+        );
+    }
+
+    #[test]
+    fn signups_out_of_window() {
+        let assessor = ActivityRiskAssessor::new(
+            1,
+            StdDuration::from_millis(100),
+            StdDuration::ZERO,
+            HashSet::new(),
+        );
+        let ip = Ipv4Addr::new(127, 2, 2, 1);
+
+        assert!(!assessor.is_suspicious(&signup_request(ip)));
+        std::thread::sleep(StdDuration::from_millis(150));
+        assert!(
+            !assessor.is_suspicious(&signup_request(ip)),
+            "The old signups should be forgotten"
+        );
+    }
+
+    #[test]
+    fn rapid_signups() {
+        let assessor = ActivityRiskAssessor::new(
+            10,
+            StdDuration::from_secs(60),
+            StdDuration::from_secs(10),
+            HashSet::new(),
+        );
+        let ip = Ipv4Addr::new(127, 2, 2, 1);
+
+        assert!(!assessor.is_suspicious(&signup_request(ip)));
+        assert!(
+            assessor.is_suspicious(&signup_request(ip)),
+            "The rapid signup should be suspicious"
+        );
+    }
+
+    #[test]
+    fn bad_ip() {
+        let bad_ip = Ipv4Addr::new(127, 2, 2, 3);
+        let assessor = ActivityRiskAssessor::new(
+            10,
+            StdDuration::from_secs(60),
+            StdDuration::ZERO,
+            HashSet::from([bad_ip.into()]),
+        );
+
+        assert!(assessor.is_suspicious(&signup_request(bad_ip)));
+        assert!(!assessor.is_suspicious(&signup_request(Ipv4Addr::new(127, 2, 2, 4))));
+    }
+
+    #[test]
+    fn trusted_forwarded_for() {
+        let assessor = ActivityRiskAssessor::new(
+            1,
+            StdDuration::from_secs(60),
+            StdDuration::ZERO,
+            HashSet::new(),
+        )
+        .with_trust_proxy(true);
+        // All the requests come from the proxy IP
+        let forwarded_request = |client_ip: &str| {
+            let mut req = signup_request(Ipv4Addr::new(127, 2, 2, 5));
+            req.headers_mut().insert(
+                HeaderName::from_static("x-forwarded-for"),
+                HeaderValue::from_str(client_ip).unwrap(),
+            );
+            req
+        };
+
+        assert!(!assessor.is_suspicious(&forwarded_request("203.0.113.1")));
+        assert!(
+            !assessor.is_suspicious(&forwarded_request("203.0.113.1, 203.0.113.2")),
+            "The signups are counted per IP added by the proxy"
+        );
+        assert!(
+            assessor.is_suspicious(&forwarded_request("198.51.100.1, 203.0.113.1")),
+            "The spoofed client IPs should be ignored"
+        );
+    }
+
+    #[test]
+    fn always_suspicious() {
+        assert!(AlwaysSuspicious.is_suspicious(&signup_request(Ipv4Addr::new(127, 2, 2, 1))));
+    }
+}
+
+#[cfg(test)]
+mod captcha_guard {
+    use super::*;
+
+    /// Returns a signin request from the given IP.
+    fn signin_request(ip: Ipv4Addr) -> Request {
+        let mut req = RequestBuilder::new(format!("{API_URL}/auth/signin"), Method::POST).build();
+        *req.remote_addr_mut() = SocketAddr::IPv4(SocketAddrV4::new(ip, 5802));
+        req
+    }
+
+    #[test]
+    fn guest_risk() {
+        let guard = CaptchaGuard::new(
+            std::sync::Arc::new(ActivityRiskAssessor::new(
+                1,
+                StdDuration::from_secs(60),
+                StdDuration::ZERO,
+                HashSet::new(),
+            )),
+            CaptchaFailurePolicy::FailClosed,
+        );
+        let ip = Ipv4Addr::new(127, 2, 3, 3);
+        let guest_request = || {
+            let mut req =
+                RequestBuilder::new(format!("{API_URL}/auth/guest"), Method::POST).build();
+            *req.remote_addr_mut() = SocketAddr::IPv4(SocketAddrV4::new(ip, 5802));
+            req
+        };
+
+        assert!(
+            guard.can_skip(&guest_request()),
+            "The unsuspicious guest should skip the captcha"
+        );
+        assert!(
+            !guard.can_skip(&guest_request()),
+            "The guests past the limit should solve the captcha"
+        );
+        assert!(
+            !guard.can_skip(&signup_request(ip)),
+            "The guests and the signups are counted together"
+        );
+    }
+
+    #[test]
+    fn fail_closed() {
+        let guard = CaptchaGuard::new(
+            std::sync::Arc::new(AlwaysSuspicious),
+            CaptchaFailurePolicy::FailClosed,
+        );
+
+        guard.captcha_failed();
+        assert!(
+            !guard.is_degraded(),
+            "The fail closed policy should never degrade"
+        );
+        assert!(!guard.can_skip(&signup_request(Ipv4Addr::new(127, 2, 3, 1))));
+    }
+
+    #[test]
+    fn degrade() {
+        let guard = CaptchaGuard::new(
+            std::sync::Arc::new(AlwaysSuspicious),
+            CaptchaFailurePolicy::Degrade,
+        );
+        let ip = Ipv4Addr::new(127, 2, 3, 1);
+
+        assert!(!guard.is_degraded());
+        assert!(!guard.can_skip(&signup_request(ip)));
+
+        guard.captcha_failed();
+        assert!(guard.is_degraded(), "The captcha failure should degrade");
+        assert!(
+            guard.can_skip(&signin_request(ip)),
+            "The unsuspicious request should skip the captcha in the degraded mode"
+        );
+        assert!(
+            !guard.can_skip(&signup_request(ip)),
+            "The rapid request should not skip the captcha in the degraded mode"
+        );
+
+        guard.captcha_succeeded();
+        assert!(!guard.is_degraded(), "The captcha success should recover");
+        assert!(!guard.can_skip(&signup_request(Ipv4Addr::new(127, 2, 3, 2))));
+    }
+
+    #[tokio::test]
+    async fn creation_burst() {
+        std::env::set_var("XORS_API_TEST", "true");
+        let service = xors_api::api::service(
+            get_connection().await.expect("Failed to get connection"),
+            get_jwt_keys(),
+            xors_api::api::ServiceConfig {
+                captcha_guard: CaptchaGuard::default().with_creation_rate(1, 2),
+                ..test_service_config()
+            },
+        )
+        .0;
+
+        for _ in 0..2 {
+            let res = send(&service, "auth/captcha", Method::GET, None::<&()>, vec![]).await;
+            assert_eq!(
+                res.status_code,
+                Some(StatusCode::OK),
+                "The response should have a `200 OK` status code {res:?}"
+            );
+        }
+        let res = send(&service, "auth/captcha", Method::GET, None::<&()>, vec![]).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::TOO_MANY_REQUESTS),
+            "The burst should hit the global captcha creation cap {res:?}"
+        );
+        assert_eq!(
+            res.headers()
+                .get(salvo::hyper::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok()),
+            Some("1")
+        );
+    }
+
+    #[test]
+    fn creation_refill() {
+        let guard = CaptchaGuard::default().with_creation_rate(20, 1);
+
+        assert!(guard.take_creation().is_ok());
+        assert!(guard.take_creation().is_err());
+        std::thread::sleep(StdDuration::from_millis(60));
+        assert!(
+            guard.take_creation().is_ok(),
+            "The budget should be refilled after a while"
+        );
+    }
+
+    #[test]
+    fn failure_policy_from_str() {
+        assert_eq!(
+            "fail_closed".parse::<CaptchaFailurePolicy>(),
+            Ok(CaptchaFailurePolicy::FailClosed)
+        );
+        assert_eq!(
+            "degrade".parse::<CaptchaFailurePolicy>(),
+            Ok(CaptchaFailurePolicy::Degrade)
+        );
+        assert!("fallback".parse::<CaptchaFailurePolicy>().is_err());
+        assert_eq!(
+            CaptchaFailurePolicy::default(),
+            CaptchaFailurePolicy::FailClosed
+        );
+    }
+}
+
+#[cfg(test)]
+mod ip_rate_limiter {
+    use super::*;
+
+    /// Returns a service with the given auth rate limits.
+    async fn service_with(auth_rate_limits: AuthRateLimits) -> Service {
+        std::env::set_var("XORS_API_TEST", "true");
+        xors_api::api::service(
+            get_connection().await.expect("Failed to get connection"),
+            get_jwt_keys(),
+            xors_api::api::ServiceConfig {
+                auth_rate_limits,
+                ..test_service_config()
+            },
+        )
+        .0
+    }
+
+    async fn signin(service: &Service, forwarded_for: Option<&'static str>) -> Response {
+        send(
+            service,
+            "auth/signin",
+            Method::POST,
+            Some(&SigninSchema {
+                username: "IpRateLimiterUnknown".to_owned(),
+                password: "fdkjhKFHDKH347(#*&".to_owned(),
+                remember_me: None,
+                totp_code: None,
+            }),
+            forwarded_for
+                .map(|ip| {
+                    (
+                        HeaderName::from_static("x-forwarded-for"),
+                        HeaderValue::from_static(ip),
+                    )
+                })
+                .into_iter()
+                .collect(),
+        )
+        .await
+    }
+
+    #[test]
+    fn hit_window() {
+        let limiter = IpRateLimiter::new(2, StdDuration::from_millis(100));
+        let ip = "127.0.0.1".parse().unwrap();
+
+        assert!(limiter.hit(ip).is_ok());
+        assert!(limiter.hit(ip).is_ok());
+        let retry_after = limiter.hit(ip).unwrap_err();
+        assert!(
+            retry_after <= StdDuration::from_millis(100),
+            "{retry_after:?}"
+        );
+        assert!(
+            limiter.hit("127.0.0.2".parse().unwrap()).is_ok(),
+            "The limit is per IP"
+        );
+
+        std::thread::sleep(StdDuration::from_millis(110));
+        assert!(
+            limiter.hit(ip).is_ok(),
+            "The requests should age out of the window"
+        );
+    }
+
+    #[tokio::test]
+    async fn signin_past_limit() {
+        let service = service_with(AuthRateLimits {
+            sign: IpRateLimiter::new(2, StdDuration::from_secs(60)),
+            ..Default::default()
+        })
+        .await;
+
+        for _ in 0..2 {
+            let res = signin(&service, None).await;
+            assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        }
+        let mut res = signin(&service, None).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::TOO_MANY_REQUESTS),
+            "{res:?}"
+        );
+        let retry_after = res
+            .headers()
+            .get(header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok()?.parse::<u64>().ok())
+            .expect("The `Retry-After` header should be set");
+        assert!((59..=60).contains(&retry_after), "{retry_after}");
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["code"],
+            "too_many_requests"
+        );
+    }
+
+    #[tokio::test]
+    async fn route_groups_limits() {
+        let service = service_with(AuthRateLimits {
+            sign: IpRateLimiter::new(5, StdDuration::from_secs(60)),
+            captcha: IpRateLimiter::new(1, StdDuration::from_secs(60)),
+            ..Default::default()
+        })
+        .await;
+
+        let res = send(&service, "auth/captcha", Method::GET, None::<&()>, vec![]).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let res = send(&service, "auth/captcha", Method::GET, None::<&()>, vec![]).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::TOO_MANY_REQUESTS),
+            "{res:?}"
+        );
+
+        // The captcha limit is separated from the signin limit
+        let res = signin(&service, None).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn forwarded_for_behind_trusted_proxy() {
+        let service = service_with(
+            AuthRateLimits {
+                sign: IpRateLimiter::new(1, StdDuration::from_secs(60)),
+                ..Default::default()
+            }
+            .with_trust_proxy(true),
+        )
+        .await;
+
+        let res = signin(&service, Some("10.1.1.1")).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        let res = signin(&service, Some("10.9.9.9, 10.1.1.1")).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::TOO_MANY_REQUESTS),
+            "The spoofed leftmost IP should not reset the limit {res:?}"
+        );
+        let res = signin(&service, Some("10.1.1.2")).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn forwarded_for_without_trusted_proxy() {
+        let service = service_with(AuthRateLimits {
+            sign: IpRateLimiter::new(1, StdDuration::from_secs(60)),
+            ..Default::default()
+        })
+        .await;
+
+        let res = signin(&service, Some("10.1.1.1")).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        let res = signin(&service, Some("10.1.1.2")).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::TOO_MANY_REQUESTS),
+            "The spoofed `X-Forwarded-For` should be ignored {res:?}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod user_rate_limiter {
+    use super::*;
+
+    #[test]
+    fn hit_per_user() {
+        let limiter = UserRateLimiter::new(1, StdDuration::from_secs(60));
+        let user_uuid = Uuid::new_v4();
+
+        assert!(limiter.hit(user_uuid).is_ok());
+        let retry_after = limiter.hit(user_uuid).unwrap_err();
+        assert!(retry_after <= StdDuration::from_secs(60), "{retry_after:?}");
+        assert!(limiter.hit(Uuid::new_v4()).is_ok(), "The limit is per user");
+    }
+}
@@ -0,0 +1,182 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+include!("mod.rs");
+
+use xors_api::{api::season::SeasonPolicy, utils};
+
+async fn get_json(service: &Service, path: &str) -> serde_json::Value {
+    let mut res = send(service, path, Method::GET, None::<&()>, vec![]).await;
+    assert_eq!(
+        res.status_code,
+        Some(StatusCode::OK),
+        "The response should have a `200 OK` status code {res:?}"
+    );
+    res.take_json::<serde_json::Value>().await.unwrap()
+}
+
+#[tokio::test]
+async fn season_rollover() {
+    let service = get_service().await.expect("Failed to get service");
+    let conn = get_connection().await.expect("Failed to get connection");
+    let policy = SeasonPolicy {
+        length: chrono::Duration::days(30),
+        reset_factor: 0.5,
+        ..Default::default()
+    };
+
+    let season = db_utils::start_season(&conn, policy.length)
+        .await
+        .expect("Failed to start season");
+    let champion = create_player(&conn, "season_champion").await;
+    let runner_up = create_player(&conn, "season_runner_up").await;
+    let idle = create_player(&conn, "season_idle").await;
+    let game = db_utils::create_game(&conn, champion.uuid, runner_up.uuid, 10, false, 0)
+        .await
+        .expect("Failed to create game");
+    db_utils::end_game(
+        &conn,
+        game.uuid.as_ref(),
+        Some(champion.uuid),
+        &GameOverReason::PlayerWon,
+    )
+    .await
+    .expect("Failed to end game");
+    let mut active_champion = db_utils::get_user(&conn, champion.uuid).await.unwrap();
+    active_champion.rating = sea_orm::Set(3216);
+    sea_orm::ActiveModelTrait::save(active_champion, &conn)
+        .await
+        .unwrap();
+
+    let next_season = db_utils::end_season(&conn, season.clone(), &policy)
+        .await
+        .expect("Failed to end season");
+    assert_eq!(next_season.number, season.number + 1);
+    assert_eq!(next_season.ended_at, None);
+    assert_eq!(next_season.ends_at - next_season.starts_at, policy.length);
+
+    let rating = |uuid: Uuid| {
+        let conn = &conn;
+        async move {
+            *db_utils::get_user(conn, uuid)
+                .await
+                .unwrap()
+                .rating
+                .as_ref()
+        }
+    };
+    assert_eq!(
+        rating(champion.uuid).await,
+        2208,
+        "The rating is soft reset"
+    );
+    assert_eq!(
+        rating(runner_up.uuid).await,
+        1192,
+        "The rating is soft reset"
+    );
+    assert_eq!(rating(idle.uuid).await, utils::INITIAL_RATING);
+
+    let archived = get_json(&service, &format!("leaderboard?season={}", season.number)).await;
+    let archived = archived.as_array().unwrap();
+    assert_eq!(archived.len(), 2, "Only the season players are archived");
+    assert_eq!(archived[0]["uuid"], champion.uuid.to_string());
+    assert_eq!(archived[0]["rank"], 1);
+    assert_eq!(archived[0]["rating"], 3216);
+    assert_eq!(archived[0]["wins"], 1);
+    assert_eq!(archived[1]["uuid"], runner_up.uuid.to_string());
+    assert_eq!(archived[1]["rank"], 2);
+    assert_eq!(archived[1]["rating"], 1184);
+
+    let current = get_json(
+        &service,
+        &format!("leaderboard?season={}", next_season.number),
+    )
+    .await;
+    let entry = current
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["uuid"] == champion.uuid.to_string())
+        .expect("The current season leaderboard is the live one");
+    assert_eq!(entry["rating"], 2208);
+
+    let profile = get_json(&service, &format!("user?uuid={}", champion.uuid)).await;
+    assert_eq!(
+        profile["season_rewards"],
+        serde_json::json!([{"season": season.number, "rank": 1, "reward": "champion"}])
+    );
+    let profile = get_json(&service, &format!("user?uuid={}", runner_up.uuid)).await;
+    assert_eq!(profile["season_rewards"][0]["reward"], "top_ten");
+    let profile = get_json(&service, &format!("user?uuid={}", idle.uuid)).await;
+    assert_eq!(profile["season_rewards"], serde_json::json!([]));
+
+    let seasons = get_json(&service, "seasons").await;
+    assert_eq!(seasons[0]["number"], next_season.number);
+    assert_eq!(seasons[0]["ended_at"], serde_json::Value::Null);
+    assert_eq!(seasons[1]["number"], season.number);
+    assert!(seasons[1]["ended_at"].is_string());
+}
+
+#[tokio::test]
+async fn unknown_season() {
+    let service = get_service().await.expect("Failed to get service");
+
+    let mut res = send(
+        &service,
+        "leaderboard?season=90000",
+        Method::GET,
+        None::<&()>,
+        vec![],
+    )
+    .await;
+    assert_eq!(
+        res.status_code,
+        Some(StatusCode::NOT_FOUND),
+        "The response should have a `404 Not Found` status code {res:?}"
+    );
+    assert_eq!(
+        res.take_json::<serde_json::Value>().await.unwrap()["code"],
+        "season_not_found"
+    );
+}
+
+#[test]
+fn season_reset() {
+    assert_eq!(utils::season_reset_rating(1600, 0.5), 1400);
+    assert_eq!(utils::season_reset_rating(1000, 0.5), 1100);
+    assert_eq!(utils::season_reset_rating(1600, 0.0), 1600);
+    assert_eq!(utils::season_reset_rating(1600, 1.0), utils::INITIAL_RATING);
+
+    assert_eq!(SeasonReward::for_rank(1), Some(SeasonReward::Champion));
+    assert_eq!(SeasonReward::for_rank(10), Some(SeasonReward::TopTen));
+    assert_eq!(SeasonReward::for_rank(100), Some(SeasonReward::TopHundred));
+    assert_eq!(SeasonReward::for_rank(101), None);
+
+    assert!(SeasonPolicy::default().validate().is_ok());
+    assert!(SeasonPolicy {
+        reset_factor: 1.5,
+        ..Default::default()
+    }
+    .validate()
+    .is_err());
+    assert!(SeasonPolicy {
+        length: chrono::Duration::zero(),
+        ..Default::default()
+    }
+    .validate()
+    .is_err());
+}
@@ -0,0 +1,532 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+include!("mod.rs");
+
+#[cfg(test)]
+mod time {
+    use super::*;
+
+    #[tokio::test]
+    async fn get_time_success() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let before = chrono::Utc::now().timestamp_millis();
+        let mut res = send(&service, "time", Method::GET, None::<&()>, vec![]).await;
+        let after = chrono::Utc::now().timestamp_millis();
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        let time: ServerTimeSchema = serde_json::from_str(&res.take_string().await.unwrap())
+            .expect("Failed to parse server time schema");
+        assert!(
+            (before..=after).contains(&time.timestamp_millis),
+            "The server time should be between the request time {time:?}"
+        );
+        assert_eq!(time.timestamp, time.timestamp_millis / 1000);
+    }
+
+    #[tokio::test]
+    async fn get_time_not_rate_limited() {
+        let service = get_service().await.expect("Failed to get service");
+
+        for _ in 0..50 {
+            let res = send(&service, "time", Method::GET, None::<&()>, vec![]).await;
+            assert_eq!(
+                res.status_code,
+                Some(StatusCode::OK),
+                "The response should have a `200 OK` status code {res:?}"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod health {
+    use super::*;
+
+    #[tokio::test]
+    async fn get_health_success() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let mut res = send(&service, "health", Method::GET, None::<&()>, vec![]).await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        let health: HealthSchema = serde_json::from_str(&res.take_string().await.unwrap())
+            .expect("Failed to parse server health schema");
+        assert_eq!(health.status, HealthStatus::Ok);
+        assert!(health.database);
+        assert!(!health.captcha_degraded);
+    }
+
+    #[tokio::test]
+    async fn get_health_unreachable_database() {
+        std::env::set_var("XORS_API_TEST", "true");
+        let service = xors_api::api::service(
+            // A severed connection, all the queries fail
+            sea_orm::DatabaseConnection::Disconnected,
+            get_jwt_keys(),
+            test_service_config(),
+        )
+        .0;
+
+        let mut res = send(&service, "health", Method::GET, None::<&()>, vec![]).await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::SERVICE_UNAVAILABLE),
+            "The response should have a `503 SERVICE UNAVAILABLE` status code {res:?}"
+        );
+        let health: HealthSchema = serde_json::from_str(&res.take_string().await.unwrap())
+            .expect("Failed to parse server health schema");
+        assert_eq!(health.status, HealthStatus::Unavailable);
+        assert!(!health.database);
+    }
+}
+
+#[cfg(test)]
+mod metrics {
+    use super::*;
+
+    const METRICS_TOKEN: &str = "test-metrics-token-0123456789abcdef";
+
+    /// Returns the service, with the metrics endpoint enabled.
+    fn get_metrics_service(conn: sea_orm::DatabaseConnection) -> Service {
+        get_metrics_service_with_openapi(conn).0
+    }
+
+    /// Returns the service with the metrics endpoint enabled, and its OpenAPI spec.
+    fn get_metrics_service_with_openapi(
+        conn: sea_orm::DatabaseConnection,
+    ) -> (Service, salvo::oapi::OpenApi) {
+        std::env::set_var("XORS_API_TEST", "true");
+        xors_api::api::service(
+            conn,
+            get_jwt_keys(),
+            xors_api::api::ServiceConfig {
+                metrics_token: Some(METRICS_TOKEN.to_owned()),
+                ..test_service_config()
+            },
+        )
+    }
+
+    /// Returns the metrics authorization header.
+    fn metrics_auth() -> Vec<(HeaderName, HeaderValue)> {
+        vec![(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {METRICS_TOKEN}")).unwrap(),
+        )]
+    }
+
+    /// Returns the value of the sample, if it's exposed.
+    fn sample_value(metrics: &str, sample: &str) -> Option<f64> {
+        metrics
+            .lines()
+            .find_map(|line| line.strip_prefix(sample)?.strip_prefix(' '))
+            .map(|value| value.parse().expect("The sample value is a number"))
+    }
+
+    #[tokio::test]
+    async fn get_metrics_success() {
+        let service =
+            get_metrics_service(get_connection().await.expect("Failed to get connection"));
+
+        send(&service, "time", Method::GET, None::<&()>, vec![]).await;
+        send(
+            &service,
+            &format!("game/{}", Uuid::new_v4()),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        send(
+            &service,
+            "auth/signin",
+            Method::POST,
+            Some(&SigninSchema {
+                username: "MetricsNobody".to_owned(),
+                password: "fdkjhKFHDKH347(#*&".to_owned(),
+                remember_me: None,
+                totp_code: None,
+            }),
+            vec![],
+        )
+        .await;
+        send(&service, "game/validate", Method::GET, None::<&()>, vec![]).await;
+        let mut res = send(
+            &service,
+            "metrics",
+            Method::GET,
+            None::<&()>,
+            metrics_auth(),
+        )
+        .await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        assert!(res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .is_some_and(|content_type| content_type
+                .to_str()
+                .unwrap()
+                .starts_with("text/plain; version=0.0.4")));
+        let metrics = res.take_string().await.unwrap();
+        assert!(
+            sample_value(
+                &metrics,
+                r#"xors_http_request_duration_seconds_count{method="GET",route="/time",status="200"}"#
+            )
+            .is_some_and(|count| count >= 1.0),
+            "The time request should be recorded {metrics}"
+        );
+        assert!(
+            sample_value(
+                &metrics,
+                r#"xors_http_request_duration_seconds_count{method="GET",route="/game/<uuid>",status="404"}"#
+            )
+            .is_some_and(|count| count >= 1.0),
+            "The path parameters should be replaced with their names {metrics}"
+        );
+        assert!(
+            sample_value(
+                &metrics,
+                r#"xors_http_request_duration_seconds_count{method="GET",route="/game/<uuid>",status="400"}"#
+            )
+            .is_some_and(|count| count >= 1.0),
+            "The request should be labeled with the matched route pattern {metrics}"
+        );
+        assert!(
+            !metrics.contains(r#"route="/game/validate""#),
+            "The `GET` request isn't matched by the `POST` route {metrics}"
+        );
+        assert!(
+            sample_value(
+                &metrics,
+                r#"xors_failed_signins_total{reason="credentials"}"#
+            )
+            .is_some_and(|count| count >= 1.0),
+            "The failed signin should be recorded {metrics}"
+        );
+        assert!(
+            sample_value(
+                &metrics,
+                r#"xors_db_query_duration_seconds_count{operation="select"}"#
+            )
+            .is_some_and(|count| count >= 1.0),
+            "The database queries should be recorded {metrics}"
+        );
+        assert!(sample_value(&metrics, "xors_online_games").is_some());
+        assert!(sample_value(&metrics, "xors_connected_players").is_some());
+    }
+
+    #[tokio::test]
+    async fn all_routes_labeled() {
+        let (service, openapi) = get_metrics_service_with_openapi(
+            get_connection().await.expect("Failed to get connection"),
+        );
+        let openapi = serde_json::to_value(&openapi).unwrap();
+
+        // Request every endpoint of the spec, the spec paths are the route patterns with `{param}` parameters
+        let mut labels = Vec::new();
+        for (path, item) in openapi["paths"].as_object().unwrap() {
+            let request_path = path
+                .split('/')
+                .filter(|segment| !segment.is_empty())
+                .map(|segment| match segment.strip_prefix('{') {
+                    Some("uuid}") => Uuid::new_v4().to_string(),
+                    Some(_) => "XORS2345".to_owned(),
+                    None => segment.to_owned(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            for method in ["get", "post", "put", "delete", "patch"] {
+                if item.get(method).is_some() {
+                    let method = Method::from_bytes(method.to_uppercase().as_bytes()).unwrap();
+                    send(&service, &request_path, method, None::<&()>, vec![]).await;
+                }
+            }
+            labels.push(path.replace('{', "<").replace('}', ">"));
+        }
+        let mut res = send(
+            &service,
+            "metrics",
+            Method::GET,
+            None::<&()>,
+            metrics_auth(),
+        )
+        .await;
+        let metrics = res.take_string().await.unwrap();
+
+        assert!(!labels.is_empty());
+        for label in labels {
+            assert!(
+                metrics.contains(&format!(r#"route="{label}""#)),
+                "The route `{label}` should be labeled with its pattern, not `unmatched` {metrics}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn get_metrics_disabled() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let res = send(
+            &service,
+            "metrics",
+            Method::GET,
+            None::<&()>,
+            metrics_auth(),
+        )
+        .await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::NOT_FOUND),
+            "The response should have a `404 NOT FOUND` status code {res:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn get_metrics_invalid_token() {
+        let service =
+            get_metrics_service(get_connection().await.expect("Failed to get connection"));
+
+        let res = send(&service, "metrics", Method::GET, None::<&()>, vec![]).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::FORBIDDEN),
+            "The response should have a `403 FORBIDDEN` status code {res:?}"
+        );
+        let res = send(
+            &service,
+            "metrics",
+            Method::GET,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_static("Bearer invalid"),
+            )],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::FORBIDDEN),
+            "The response should have a `403 FORBIDDEN` status code {res:?}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod prune_expired {
+    use super::*;
+    use xors_api::api::jwt::JwtClaims;
+
+    #[tokio::test]
+    async fn prune_expired_rows() {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let now = chrono::Utc::now().timestamp();
+        let expired = JwtClaims::new(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            UserRole::User,
+            0,
+            now - 70,
+            now - 70,
+            None,
+            now - 10,
+        );
+        let fresh = JwtClaims::new(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            UserRole::User,
+            0,
+            now,
+            now,
+            None,
+            now + 60,
+        );
+        db_utils::revoke_token(&conn, &expired).await.unwrap();
+        db_utils::revoke_token(&conn, &fresh).await.unwrap();
+
+        // Note: The cleaner of the other tests services may prune the expired token first
+        db_utils::prune_expired(&conn).await.unwrap();
+        assert!(!db_utils::is_token_revoked(&conn, expired.jti)
+            .await
+            .unwrap());
+        assert!(db_utils::is_token_revoked(&conn, fresh.jti).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn clear_expired_successors() {
+        use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+
+        let conn = get_connection().await.expect("Failed to get connection");
+        let now = chrono::Utc::now().naive_utc();
+        let consumed = RevokedTokenActiveModel {
+            jti: Set(Uuid::new_v4()),
+            expires_at: Set(now + Duration::hours(1)),
+            revoked_at: Set(now - Duration::minutes(1)),
+            consumed: Set(true),
+            successor_jwt_jti: Set(Some(Uuid::new_v4())),
+            successor_refresh_jti: Set(Some(Uuid::new_v4())),
+            successor_issued_at: Set(Some(now - Duration::minutes(1))),
+            reusable_until: Set(Some(now - Duration::seconds(50))),
+            ..Default::default()
+        }
+        .insert(&conn)
+        .await
+        .unwrap();
+
+        db_utils::prune_expired(&conn).await.unwrap();
+        let consumed = RevokedTokenEntity::find()
+            .filter(RevokedTokenColumn::Jti.eq(consumed.jti))
+            .one(&conn)
+            .await
+            .unwrap()
+            .expect("The consumed token is not expired");
+        assert_eq!(consumed.successor_jwt_jti, None);
+        assert_eq!(consumed.successor_refresh_jti, None);
+        assert_eq!(consumed.successor_issued_at, None);
+        assert_eq!(consumed.reusable_until, None);
+    }
+}
+
+#[cfg(test)]
+mod error_codes {
+    use super::*;
+
+    async fn assert_code(res: &mut Response, status_code: StatusCode, code: &str) {
+        assert_eq!(res.status_code, Some(status_code), "{res:?}");
+        let body = res.take_json::<serde_json::Value>().await.unwrap();
+        assert_eq!(body["code"], code, "{body}");
+        assert!(body["message"].is_string(), "{body}");
+    }
+
+    #[tokio::test]
+    async fn api_errors_codes() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let mut res = send(
+            &service,
+            "auth/signin",
+            Method::POST,
+            Some(&SigninSchema {
+                username: "ErrorCodesUnknown".to_owned(),
+                password: "fdkjhKFHDKH347(#*&".to_owned(),
+                remember_me: None,
+                totp_code: None,
+            }),
+            vec![],
+        )
+        .await;
+        assert_code(
+            &mut res,
+            StatusCode::FORBIDDEN,
+            "invalid_signin_credentials",
+        )
+        .await;
+
+        let mut res = send(
+            &service,
+            "user/00000000-0000-0000-0000-000000000001/streaks",
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_code(&mut res, StatusCode::NOT_FOUND, "user_not_found").await;
+
+        let mut res = send(
+            &service,
+            "auth/captcha?length=100",
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_code(&mut res, StatusCode::BAD_REQUEST, "invalid_captcha_length").await;
+    }
+
+    #[tokio::test]
+    async fn server_errors_codes() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let mut res = send(&service, "not/a/route", Method::GET, None::<&()>, vec![]).await;
+        assert_code(&mut res, StatusCode::NOT_FOUND, "not_found").await;
+
+        let mut res = send(
+            &service,
+            "user/me",
+            Method::GET,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_static("Bearer invalid"),
+            )],
+        )
+        .await;
+        // The invalid tokens are forbidden by the `JwtAuth` middleware
+        assert_code(&mut res, StatusCode::FORBIDDEN, "forbidden").await;
+    }
+
+    #[tokio::test]
+    async fn success_messages_have_no_code() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let user = db_utils::create_user(
+            &conn,
+            NewUserSchema {
+                first_name: "First".to_owned(),
+                last_name: None,
+                username: "ErrorCodesLogout".to_owned(),
+                password: "fdkjhKFHDKH347(#*&".to_owned(),
+            },
+        )
+        .await
+        .expect("Failed to create user");
+        let user =
+            db_utils::signin_user(&conn, user, &get_jwt_keys(), &test_token_lifetimes(), true)
+                .await
+                .expect("Failed to signin user");
+
+        let mut res = send(
+            &service,
+            "auth/logout",
+            Method::POST,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", user.jwt)).unwrap(),
+            )],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let body = res.take_json::<serde_json::Value>().await.unwrap();
+        assert!(body.get("code").is_none(), "{body}");
+    }
+}
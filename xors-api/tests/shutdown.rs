@@ -0,0 +1,191 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+include!("mod.rs");
+
+use futures_util::{SinkExt, StreamExt};
+use salvo::conn::Acceptor;
+use tokio_tungstenite::{
+    tungstenite::{client::IntoClientRequest, Message as WsMessage},
+    MaybeTlsStream, WebSocketStream,
+};
+use xors_api::api::xo::{GameClock, SERVER_SHUTDOWN_CLOSE_CODE};
+
+// The shutdown rejects the events of all the servers in the process, so its tests are not with the XO tests.
+
+type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Serve the API on a random port with the given game clock, returns the XO websocket url.
+async fn serve(game_clock: GameClock) -> String {
+    std::env::set_var("XORS_API_TEST", "true");
+
+    let acceptor = salvo::conn::TcpListener::new("127.0.0.1:0").bind().await;
+    let addr = acceptor.holdings()[0]
+        .local_addr
+        .clone()
+        .into_std()
+        .expect("The address is a socket address");
+    let service = xors_api::api::service(
+        get_connection().await.expect("Failed to get connection"),
+        get_jwt_keys(),
+        xors_api::api::ServiceConfig {
+            game_clock,
+            ..test_service_config()
+        },
+    )
+    .0;
+    tokio::spawn(Server::new(acceptor).serve(service));
+
+    format!("ws://{addr}/xo")
+}
+
+/// Creates a new user and returns its signin data, its token outlives the test.
+async fn new_player(username: &str) -> UserSigninSchema {
+    let conn = get_connection().await.expect("Failed to get connection");
+    let user = create_player(&conn, username).await;
+    let lifetimes = xors_api::api::jwt::TokenLifetimes {
+        jwt: Duration::seconds(30),
+        refresh: Duration::seconds(60),
+        short_refresh: Duration::seconds(50),
+        refresh_active_after: Duration::seconds(40),
+        refresh_grace_period: Duration::zero(),
+    };
+    db_utils::signin_user(&conn, user, &get_jwt_keys(), &lifetimes, true)
+        .await
+        .expect("Failed to signin user")
+}
+
+/// Connects to the XO websocket with the given jwt.
+async fn connect(url: &str, jwt: &str) -> WsStream {
+    let mut req = url.into_client_request().expect("Valid websocket url");
+    req.headers_mut().insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {jwt}")).unwrap(),
+    );
+    tokio_tungstenite::connect_async(req)
+        .await
+        .map(|(ws, _)| ws)
+        .expect("Failed to connect to the XO websocket")
+}
+
+/// Returns the next message, the test will fail if there is no message in 5 seconds.
+async fn next_message(ws: &mut WsStream) -> WsMessage {
+    tokio::time::timeout(std::time::Duration::from_secs(5), ws.next())
+        .await
+        .expect("No message received in 5 seconds")
+        .expect("The websocket is closed")
+        .expect("Failed to receive a message")
+}
+
+/// Returns the next server event.
+async fn next_event(ws: &mut WsStream) -> serde_json::Value {
+    let msg = next_message(ws).await;
+    serde_json::from_str(msg.to_text().expect("The message should be text"))
+        .expect("The message should be a json event")
+}
+
+async fn send_event(ws: &mut WsStream, event: serde_json::Value) {
+    ws.send(WsMessage::text(event.to_string())).await.unwrap();
+}
+
+#[tokio::test]
+async fn shutdown_and_resume() {
+    let conn = get_connection().await.expect("Failed to get connection");
+    let clock = GameClock::new(10).with_game_time(30);
+    let url = serve(clock).await;
+    let x_player = new_player("shutdown_x_player").await;
+    let o_player = new_player("shutdown_o_player").await;
+
+    let mut x_ws = connect(&url, &x_player.jwt).await;
+    let mut o_ws = connect(&url, &o_player.jwt).await;
+    send_event(&mut x_ws, serde_json::json!({"event": "search"})).await;
+    assert_eq!(next_event(&mut x_ws).await["event"], "queued");
+    send_event(&mut o_ws, serde_json::json!({"event": "search"})).await;
+    for ws in [&mut x_ws, &mut o_ws] {
+        assert_eq!(next_event(ws).await["event"], "match_found");
+        assert_eq!(next_event(ws).await["event"], "game_found");
+        assert_eq!(next_event(ws).await["event"], "round_start");
+    }
+    assert_eq!(next_event(&mut x_ws).await["event"], "your_turn");
+    let game_uuid = db_utils::get_online_games(&conn)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|game| game.x_player == x_player.user.uuid)
+        .expect("The game should be in progress")
+        .uuid;
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    assert_eq!(
+        xors_api::api::xo::shutdown(&conn, clock, std::time::Duration::from_secs(10)).await,
+        1
+    );
+    for ws in [&mut x_ws, &mut o_ws] {
+        let event = next_event(ws).await;
+        assert_eq!(event["event"], "server_shutdown");
+        assert_eq!(event["data"]["reconnect_within"], 10);
+        match next_message(ws).await {
+            WsMessage::Close(Some(frame)) => {
+                assert_eq!(u16::from(frame.code), SERVER_SHUTDOWN_CLOSE_CODE)
+            }
+            msg => panic!("The connection should be closed with the shutdown code {msg:?}"),
+        }
+    }
+
+    // The players disconnection doesn't end the game
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let game = db_utils::get_game::<false>(&conn, &game_uuid)
+        .await
+        .expect("The game should be in progress");
+    assert_eq!(game.auto_play_after, None, "The turn should be stopped");
+    let x_time_left = game.x_time_left.unwrap();
+    assert!(
+        (25_000..30_000).contains(&x_time_left),
+        "The X player turn time should be counted {x_time_left}"
+    );
+    assert_eq!(game.o_time_left, Some(30_000));
+
+    // The new games are rejected
+    let mut ws = connect(&url, &new_player("shutdown_searching_player").await.jwt).await;
+    send_event(&mut ws, serde_json::json!({"event": "search"})).await;
+    let event = next_event(&mut ws).await;
+    assert_eq!(event["event"], "error");
+    assert_eq!(event["data"], "server_shutting_down");
+
+    // The restart recovery
+    assert!(
+        xors_api::api::xo::resume_games(conn.clone(), std::time::Duration::from_secs(10), clock)
+            .await
+            .expect("Failed to resume the games")
+            >= 1
+    );
+    let mut x_ws = connect(&url, &x_player.jwt).await;
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let mut o_ws = connect(&url, &o_player.jwt).await;
+    for ws in [&mut x_ws, &mut o_ws] {
+        let event = next_event(ws).await;
+        assert_eq!(event["event"], "game_resumed");
+        assert_eq!(event["data"]["uuid"], game_uuid.to_string());
+        assert_eq!(
+            event["data"]["x_time_left"], x_time_left,
+            "The game should continue with the saved time left"
+        );
+        assert_eq!(event["data"]["o_time_left"], 30_000);
+    }
+    let event = next_event(&mut x_ws).await;
+    assert_eq!(event["event"], "your_turn");
+    assert_eq!(event["data"]["time_left"], x_time_left);
+}
@@ -0,0 +1,54 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+include!("mod.rs");
+
+use xors_api::startup::*;
+
+#[cfg(test)]
+mod self_check {
+    use super::*;
+
+    #[tokio::test]
+    async fn valid_startup() {
+        let conn = get_connection().await.expect("Failed to get connection");
+
+        self_check(&conn, &get_secret_key(), &[])
+            .await
+            .expect("The startup checks should pass");
+    }
+
+    #[tokio::test]
+    async fn short_secret_key() {
+        let conn = get_connection().await.expect("Failed to get connection");
+
+        assert!(matches!(
+            self_check(&conn, "short", &[]).await,
+            Err(StartupError::ShortSecretKey)
+        ));
+        self_check(&conn, "short", &[StartupCheck::SecretKey])
+            .await
+            .expect("The skipped secret key check should pass");
+    }
+
+    #[test]
+    fn parse_startup_check() {
+        assert_eq!("database".parse(), Ok(StartupCheck::Database));
+        assert_eq!("migrations".parse(), Ok(StartupCheck::Migrations));
+        assert_eq!("secret_key".parse(), Ok(StartupCheck::SecretKey));
+        assert!("mailer".parse::<StartupCheck>().is_err());
+    }
+}
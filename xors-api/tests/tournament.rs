@@ -0,0 +1,362 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+include!("mod.rs");
+
+/// Creates a new user with the given username, returns its uuid and jwt.
+async fn new_player(username: &str) -> (Uuid, String) {
+    let conn = get_connection().await.expect("Failed to get connection");
+    let signin = create_signed_in_player(&conn, username).await;
+    (signin.user.uuid, signin.jwt)
+}
+
+/// Returns the authorization header of the jwt.
+fn auth_header(jwt: &str) -> Vec<(HeaderName, HeaderValue)> {
+    vec![(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {jwt}")).unwrap(),
+    )]
+}
+
+/// Creates a tournament with the given size, returns its uuid.
+async fn create_tournament(service: &Service, jwt: &str, size: u8) -> Uuid {
+    let mut res = send(
+        service,
+        "tournaments",
+        Method::POST,
+        Some(&NewTournamentSchema::new("Friday cup".to_owned(), size)),
+        auth_header(jwt),
+    )
+    .await;
+    assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+    res.take_json::<TournamentSchema>().await.unwrap().uuid
+}
+
+/// Registers the player in the tournament, returns the response.
+async fn join_tournament(service: &Service, jwt: &str, tournament_uuid: Uuid) -> Response {
+    send(
+        service,
+        &format!("tournaments/{tournament_uuid}/join"),
+        Method::POST,
+        None::<&()>,
+        auth_header(jwt),
+    )
+    .await
+}
+
+/// Returns the tournament.
+async fn get_tournament(service: &Service, tournament_uuid: Uuid) -> TournamentSchema {
+    let mut res = send(
+        service,
+        &format!("tournaments/{tournament_uuid}"),
+        Method::GET,
+        None::<&()>,
+        vec![],
+    )
+    .await;
+    assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+    res.take_json().await.unwrap()
+}
+
+#[cfg(test)]
+mod create_tournament {
+    use super::*;
+
+    #[tokio::test]
+    async fn create() {
+        let service = get_service().await.unwrap();
+        let (host, jwt) = new_player("tournament_create_host").await;
+
+        let tournament = get_tournament(&service, create_tournament(&service, &jwt, 8).await).await;
+        assert_eq!(tournament.host, host);
+        assert_eq!(tournament.size, 8);
+        assert_eq!(tournament.status, TournamentStatus::Registration);
+        assert!(tournament.players.is_empty());
+        assert!(tournament.matches.is_empty());
+    }
+
+    #[tokio::test]
+    async fn invalid_tournament() {
+        let service = get_service().await.unwrap();
+        let (_, jwt) = new_player("tournament_invalid_host").await;
+
+        for (name, size, code) in [
+            ("Friday cup", 6, "invalid_tournament_size"),
+            ("Friday cup", 1, "invalid_tournament_size"),
+            ("Friday cup", 128, "invalid_tournament_size"),
+            ("Fc", 8, "invalid_tournament_name"),
+        ] {
+            let mut res = send(
+                &service,
+                "tournaments",
+                Method::POST,
+                Some(&NewTournamentSchema::new(name.to_owned(), size)),
+                auth_header(&jwt),
+            )
+            .await;
+            assert_eq!(res.status_code.unwrap(), StatusCode::BAD_REQUEST);
+            let body: serde_json::Value = res.take_json().await.unwrap();
+            assert_eq!(body["code"], code);
+        }
+    }
+}
+
+#[cfg(test)]
+mod join_tournament {
+    use super::*;
+
+    #[tokio::test]
+    async fn bracket_generated_when_full() {
+        let service = get_service().await.unwrap();
+        let (_, host_jwt) = new_player("tournament_full_host").await;
+        let tournament_uuid = create_tournament(&service, &host_jwt, 4).await;
+
+        let mut players = Vec::new();
+        for i in 0..4 {
+            let (uuid, jwt) = new_player(&format!("tournament_full_player_{i}")).await;
+            let res = join_tournament(&service, &jwt, tournament_uuid).await;
+            assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+            players.push(uuid);
+        }
+
+        let tournament = get_tournament(&service, tournament_uuid).await;
+        assert_eq!(tournament.status, TournamentStatus::InProgress);
+        assert_eq!(tournament.players, players);
+        assert_eq!(tournament.matches.len(), 3);
+        let mut first_round_players: Vec<_> = tournament.matches[..2]
+            .iter()
+            .flat_map(|m| [m.x_player.unwrap(), m.o_player.unwrap()])
+            .collect();
+        first_round_players.sort();
+        players.sort();
+        assert_eq!(first_round_players, players);
+        assert_eq!(tournament.matches[2].round, 2);
+        assert!(tournament.matches[2].x_player.is_none());
+
+        let (_, late_jwt) = new_player("tournament_full_late").await;
+        let res = join_tournament(&service, &late_jwt, tournament_uuid).await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn already_registered() {
+        let service = get_service().await.unwrap();
+        let (_, jwt) = new_player("tournament_already_registered").await;
+        let tournament_uuid = create_tournament(&service, &jwt, 4).await;
+
+        let res = join_tournament(&service, &jwt, tournament_uuid).await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+        let mut res = join_tournament(&service, &jwt, tournament_uuid).await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::CONFLICT);
+        let body: serde_json::Value = res.take_json().await.unwrap();
+        assert_eq!(body["code"], "already_registered");
+    }
+
+    #[tokio::test]
+    async fn tournament_not_found() {
+        let service = get_service().await.unwrap();
+        let (_, jwt) = new_player("tournament_join_not_found").await;
+
+        let res = join_tournament(&service, &jwt, Uuid::new_v4()).await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::NOT_FOUND);
+    }
+}
+
+#[cfg(test)]
+mod get_tournament {
+    use super::*;
+
+    /// Plays the match game of the tournament, the X player wins.
+    async fn play_match(conn: &sea_orm::DatabaseConnection, tournament_uuid: Uuid, x_player: Uuid) {
+        let tournament_match =
+            db_utils::get_player_tournament_match(conn, tournament_uuid, x_player)
+                .await
+                .unwrap()
+                .expect("The player has a match");
+        let game = db_utils::create_game(
+            conn,
+            tournament_match.x_player.unwrap(),
+            tournament_match.o_player.unwrap(),
+            10,
+            false,
+            0,
+        )
+        .await
+        .unwrap();
+        db_utils::set_tournament_match_game(conn, tournament_match.id, *game.uuid.as_ref())
+            .await
+            .unwrap();
+        db_utils::end_game(
+            conn,
+            game.uuid.as_ref(),
+            Some(x_player),
+            &GameOverReason::PlayerDisconnected,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn winners_advance() {
+        let service = get_service().await.unwrap();
+        let conn = get_connection().await.unwrap();
+        let (_, host_jwt) = new_player("tournament_advance_host").await;
+        let tournament_uuid = create_tournament(&service, &host_jwt, 4).await;
+        for i in 0..4 {
+            let (_, jwt) = new_player(&format!("tournament_advance_player_{i}")).await;
+            join_tournament(&service, &jwt, tournament_uuid).await;
+        }
+
+        let tournament = get_tournament(&service, tournament_uuid).await;
+        let (first, second) = (
+            tournament.matches[0].x_player.unwrap(),
+            tournament.matches[1].x_player.unwrap(),
+        );
+        play_match(&conn, tournament_uuid, first).await;
+        play_match(&conn, tournament_uuid, second).await;
+
+        let tournament = get_tournament(&service, tournament_uuid).await;
+        assert_eq!(tournament.matches[0].winner, Some(first));
+        assert_eq!(tournament.matches[2].x_player, Some(first));
+        assert_eq!(tournament.matches[2].o_player, Some(second));
+        assert_eq!(tournament.status, TournamentStatus::InProgress);
+
+        play_match(&conn, tournament_uuid, first).await;
+        let tournament = get_tournament(&service, tournament_uuid).await;
+        assert_eq!(tournament.status, TournamentStatus::Ended);
+        assert_eq!(tournament.winner, Some(first));
+        assert!(tournament.ended_at.is_some());
+        assert_eq!(tournament.standings[0].player, first);
+        assert_eq!(tournament.standings[0].wins, 2);
+        assert_eq!(tournament.standings[0].eliminated_in, None);
+        assert_eq!(tournament.standings[1].player, second);
+        assert_eq!(tournament.standings[1].eliminated_in, Some(2));
+        assert!(tournament.standings[2..]
+            .iter()
+            .all(|standing| standing.wins == 0 && standing.eliminated_in == Some(1)));
+    }
+
+    /// Plays the match game of the tournament with a draw, returns the played match and the game uuid.
+    async fn draw_match(
+        conn: &sea_orm::DatabaseConnection,
+        tournament_uuid: Uuid,
+        player: Uuid,
+    ) -> (TournamentMatchModel, Uuid) {
+        let tournament_match = db_utils::get_player_tournament_match(conn, tournament_uuid, player)
+            .await
+            .unwrap()
+            .expect("The player has a match");
+        let game = db_utils::create_game(
+            conn,
+            tournament_match.x_player.unwrap(),
+            tournament_match.o_player.unwrap(),
+            10,
+            false,
+            0,
+        )
+        .await
+        .unwrap();
+        db_utils::set_tournament_match_game(conn, tournament_match.id, *game.uuid.as_ref())
+            .await
+            .unwrap();
+        db_utils::end_game(conn, game.uuid.as_ref(), None, &GameOverReason::Draw)
+            .await
+            .unwrap();
+        (tournament_match, *game.uuid.as_ref())
+    }
+
+    #[tokio::test]
+    async fn draw_replays_match() {
+        let service = get_service().await.unwrap();
+        let conn = get_connection().await.unwrap();
+        let (_, host_jwt) = new_player("tournament_draw_host").await;
+        let tournament_uuid = create_tournament(&service, &host_jwt, 2).await;
+        let (x_player, x_jwt) = new_player("tournament_draw_x").await;
+        let (_, o_jwt) = new_player("tournament_draw_o").await;
+        join_tournament(&service, &x_jwt, tournament_uuid).await;
+        join_tournament(&service, &o_jwt, tournament_uuid).await;
+
+        let (drawn_match, game_uuid) = draw_match(&conn, tournament_uuid, x_player).await;
+
+        let tournament = get_tournament(&service, tournament_uuid).await;
+        assert_eq!(tournament.status, TournamentStatus::InProgress);
+        assert_eq!(tournament.matches[0].game, None);
+        assert_eq!(tournament.matches[0].games, vec![game_uuid]);
+        assert_eq!(tournament.matches[0].winner, None);
+        assert_eq!(
+            tournament.matches[0].x_player, drawn_match.o_player,
+            "The sides are swapped in the replay"
+        );
+        assert_eq!(tournament.matches[0].o_player, drawn_match.x_player);
+
+        // The drawn game is still linked to its match, and the match is replayed
+        assert_eq!(
+            db_utils::get_game_tournament(&conn, game_uuid)
+                .await
+                .unwrap()
+                .map(|tournament| tournament.uuid),
+            Some(tournament_uuid)
+        );
+        assert_eq!(
+            db_utils::get_next_tournament_match(&conn, game_uuid)
+                .await
+                .unwrap()
+                .map(|replayed_match| replayed_match.id),
+            Some(drawn_match.id)
+        );
+    }
+
+    #[tokio::test]
+    async fn draws_tiebreak() {
+        let service = get_service().await.unwrap();
+        let conn = get_connection().await.unwrap();
+        let (_, host_jwt) = new_player("tournament_tiebreak_host").await;
+        let tournament_uuid = create_tournament(&service, &host_jwt, 2).await;
+        let (player, jwt) = new_player("tournament_tiebreak_x").await;
+        let (_, opponent_jwt) = new_player("tournament_tiebreak_o").await;
+        join_tournament(&service, &jwt, tournament_uuid).await;
+        join_tournament(&service, &opponent_jwt, tournament_uuid).await;
+
+        let mut games = Vec::new();
+        let mut last_match = None;
+        for _ in 0..xors_api::utils::MAX_TOURNAMENT_MATCH_DRAWS {
+            let (drawn_match, game_uuid) = draw_match(&conn, tournament_uuid, player).await;
+            games.push(game_uuid);
+            last_match = Some(drawn_match);
+        }
+
+        // The players have the same rating, the O player of the last game wins
+        let tournament = get_tournament(&service, tournament_uuid).await;
+        assert_eq!(tournament.status, TournamentStatus::Ended);
+        assert_eq!(tournament.winner, last_match.unwrap().o_player);
+        assert_eq!(tournament.matches[0].games, games);
+    }
+
+    #[tokio::test]
+    async fn tournament_not_found() {
+        let service = get_service().await.unwrap();
+
+        let res = send(
+            &service,
+            &format!("tournaments/{}", Uuid::new_v4()),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::NOT_FOUND);
+    }
+}
@@ -24,9 +24,10 @@ mod get_me {
     async fn get_me_success() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::signin_user(
+            &conn,
             xors_api::db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -38,7 +39,9 @@ mod get_me {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -71,9 +74,10 @@ mod get_me {
     async fn get_me_with_refresh_token() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::signin_user(
+            &conn,
             xors_api::db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -85,7 +89,9 @@ mod get_me {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -154,9 +160,10 @@ mod get_user {
     async fn get_user_success() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::signin_user(
+            &conn,
             xors_api::db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -168,7 +175,9 @@ mod get_user {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -255,9 +264,10 @@ mod update_user {
     async fn update_user_first_name() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::signin_user(
+            &conn,
             xors_api::db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -269,7 +279,9 @@ mod update_user {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -312,9 +324,10 @@ mod update_user {
     async fn update_user_last_name() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::signin_user(
+            &conn,
             xors_api::db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -326,7 +339,9 @@ mod update_user {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -370,9 +385,10 @@ mod update_user {
     async fn update_user_with_invalid_first_name() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::signin_user(
+            &conn,
             xors_api::db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -384,7 +400,9 @@ mod update_user {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -482,9 +500,10 @@ mod update_user {
     async fn update_user_with_invalid_last_name() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::signin_user(
+            &conn,
             xors_api::db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -496,7 +515,9 @@ mod update_user {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -592,9 +613,10 @@ mod update_user {
     async fn update_user_with_null_first_name() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::signin_user(
+            &conn,
             xors_api::db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -606,7 +628,9 @@ mod update_user {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -689,9 +713,10 @@ mod update_user {
     async fn update_user_with_refresh_token() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::signin_user(
+            &conn,
             xors_api::db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -703,7 +728,9 @@ mod update_user {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -740,9 +767,10 @@ mod delete_user {
     async fn delete_user_success() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::signin_user(
+            &conn,
             xors_api::db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -754,7 +782,9 @@ mod delete_user {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -791,9 +821,10 @@ mod delete_user {
     async fn delete_user_with_invalid_password() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::signin_user(
+            &conn,
             xors_api::db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -805,7 +836,9 @@ mod delete_user {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -873,9 +906,10 @@ mod delete_user {
     async fn delete_user_with_refresh_token() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         let user = xors_api::db_utils::signin_user(
+            &conn,
             xors_api::db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -887,7 +921,9 @@ mod delete_user {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -922,12 +958,13 @@ mod reset_password {
     async fn reset_password_success() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         const OLD_PASSWORD: &str = "kdfkl(#0()$fkLKJF";
         const NEW_PASSWORD: &str = "kdfkl(#0()$fkLKJf";
 
         let user = db_utils::signin_user(
+            &conn,
             db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -939,7 +976,9 @@ mod reset_password {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -969,7 +1008,8 @@ mod reset_password {
             .await
             .expect("Failed to get user");
         assert!(
-            bcrypt::verify(NEW_PASSWORD, user_with_new_password.password_hash.as_ref())
+            xors_api::utils::PasswordHasher::current()
+                .verify(NEW_PASSWORD, user_with_new_password.password_hash.as_ref())
                 .expect("Failed to verify password"),
             "Password should be updated"
         );
@@ -979,12 +1019,13 @@ mod reset_password {
     async fn reset_password_with_invalid_old_password() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         const OLD_PASSWORD: &str = "kdfkl(#0()$fkLKJF";
         const NEW_PASSWORD: &str = "kdfkl(#0()$fkLKJf";
 
         let user = db_utils::signin_user(
+            &conn,
             db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -996,7 +1037,9 @@ mod reset_password {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -1076,11 +1119,12 @@ mod reset_password {
     async fn reset_password_with_same_password() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         const OLD_PASSWORD: &str = "kdfkl(#0()$fkLKJF";
 
         let user = db_utils::signin_user(
+            &conn,
             db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -1092,7 +1136,9 @@ mod reset_password {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -1123,11 +1169,12 @@ mod reset_password {
     async fn reset_password_with_invalid_new_password() {
         let service = get_service().await.expect("Failed to get service");
         let conn = get_connection().await.expect("Failed to get connection");
-        let secret_key = get_secret_key();
+        let jwt_keys = get_jwt_keys();
 
         const OLD_PASSWORD: &str = "kdfkl(#0()$fkLKJF";
 
         let user = db_utils::signin_user(
+            &conn,
             db_utils::create_user(
                 &conn,
                 NewUserSchema {
@@ -1139,7 +1186,9 @@ mod reset_password {
             )
             .await
             .expect("Failed to create user"),
-            &secret_key,
+            &jwt_keys,
+            &test_token_lifetimes(),
+            true,
         )
         .await
         .expect("Failed to signin user");
@@ -1208,3 +1257,1977 @@ mod reset_password {
         );
     }
 }
+
+#[cfg(test)]
+mod search_users {
+    use super::*;
+
+    async fn create_users(conn: &sea_orm::DatabaseConnection, users: &[(&str, &str)]) {
+        for (username, first_name) in users {
+            db_utils::create_user(
+                conn,
+                NewUserSchema {
+                    username: (*username).to_owned(),
+                    first_name: (*first_name).to_owned(),
+                    password: "fdkDFLKJL4859#$&".to_owned(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("Failed to create user");
+        }
+    }
+
+    async fn search(service: &Service, query: &str) -> Vec<String> {
+        let mut res = send(
+            service,
+            &format!("user/search?{query}"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        serde_json::from_str::<Vec<UserSchema>>(&res.take_string().await.unwrap())
+            .expect("Failed to parse users")
+            .into_iter()
+            .map(|user| user.username)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn search_ranking() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        create_users(
+            &conn,
+            &[
+                ("Xsrchrank", "First"),
+                ("Srchrank_long", "First"),
+                ("srchrank", "First"),
+                ("Srchranks", "First"),
+            ],
+        )
+        .await;
+
+        assert_eq!(
+            search(&service, "q=SrchRank").await,
+            vec!["srchrank", "Srchranks", "Srchrank_long", "Xsrchrank"],
+            "The exact username should be first, then the prefixes then the rest"
+        );
+    }
+
+    #[tokio::test]
+    async fn search_escape_wildcards() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        create_users(&conn, &[("srchesc_one", "First"), ("srchescXone", "First")]).await;
+
+        assert_eq!(search(&service, "q=srchesc_").await, vec!["srchesc_one"]);
+    }
+
+    #[tokio::test]
+    async fn search_display_names() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        create_users(&conn, &[("srchname_user", "Qwxzy")]).await;
+
+        assert!(search(&service, "q=qwxzy").await.is_empty());
+        assert_eq!(
+            search(&service, "q=qwxzy&names=true").await,
+            vec!["srchname_user"]
+        );
+    }
+
+    #[tokio::test]
+    async fn search_pagination() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let usernames = (0..12)
+            .map(|i| format!("srchpage_{i:02}"))
+            .collect::<Vec<_>>();
+        create_users(
+            &conn,
+            &usernames
+                .iter()
+                .map(|username| (username.as_str(), "First"))
+                .collect::<Vec<_>>(),
+        )
+        .await;
+
+        assert_eq!(search(&service, "q=srchpage").await, usernames[..10]);
+        assert_eq!(search(&service, "q=srchpage&page=1").await, usernames[10..]);
+    }
+
+    #[tokio::test]
+    async fn search_short_query() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let res = send(
+            &service,
+            "user/search?q=a",
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::BAD_REQUEST),
+            "The response should have a `BAD_REQUEST` status code {res:?}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod get_user_streaks {
+    use super::*;
+
+    async fn get_streaks(service: &Service, user_uuid: Uuid) -> StreaksSchema {
+        let mut res = send(
+            service,
+            &format!("user/{user_uuid}/streaks"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        serde_json::from_str(&res.take_string().await.unwrap()).expect("Failed to parse streaks")
+    }
+
+    #[tokio::test]
+    async fn streaks() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_player(&conn, "streaks_player").await;
+        let opponent = create_player(&conn, "streaks_opponent").await;
+
+        let streaks = get_streaks(&service, player.uuid).await;
+        assert_eq!(
+            (
+                streaks.current_win_streak,
+                streaks.current_loss_streak,
+                streaks.longest_win_streak,
+                streaks.longest_loss_streak
+            ),
+            (0, 0, 0, 0),
+            "The user without games should have zero streaks"
+        );
+
+        // W W D L L L W
+        for winner in [
+            Some(player.uuid),
+            Some(player.uuid),
+            None,
+            Some(opponent.uuid),
+            Some(opponent.uuid),
+            Some(opponent.uuid),
+            Some(player.uuid),
+        ] {
+            let game = db_utils::create_game(&conn, player.uuid, opponent.uuid, 10, false, 0)
+                .await
+                .expect("Failed to create game");
+            let reason = if winner.is_some() {
+                GameOverReason::PlayerWon
+            } else {
+                GameOverReason::Draw
+            };
+            db_utils::end_game(&conn, game.uuid.as_ref(), winner, &reason)
+                .await
+                .expect("Failed to end game");
+        }
+
+        let streaks = get_streaks(&service, player.uuid).await;
+        assert_eq!(
+            (
+                streaks.current_win_streak,
+                streaks.current_loss_streak,
+                streaks.longest_win_streak,
+                streaks.longest_loss_streak
+            ),
+            (1, 0, 2, 3)
+        );
+        let streaks = get_streaks(&service, opponent.uuid).await;
+        assert_eq!(
+            (
+                streaks.current_win_streak,
+                streaks.current_loss_streak,
+                streaks.longest_win_streak,
+                streaks.longest_loss_streak
+            ),
+            (0, 1, 3, 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn user_not_found() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let res = send(
+            &service,
+            &format!("user/{}/streaks", Uuid::new_v4()),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::NOT_FOUND),
+            "The response should have a `NOT_FOUND` status code {res:?}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod get_user_heatmap {
+    use super::*;
+
+    async fn get_heatmap(service: &Service, user_uuid: Uuid) -> HeatmapSchema {
+        let mut res = send(
+            service,
+            &format!("user/{user_uuid}/heatmap"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        serde_json::from_str(&res.take_string().await.unwrap()).expect("Failed to parse heatmap")
+    }
+
+    #[tokio::test]
+    async fn heatmap() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let x_player = create_player(&conn, "heatmap_x_player").await;
+        let o_player = create_player(&conn, "heatmap_o_player").await;
+
+        let heatmap = get_heatmap(&service, x_player.uuid).await;
+        assert_eq!((heatmap.cells, heatmap.rounds), ([0; 9], 0));
+
+        let mut game = db_utils::create_game(&conn, x_player.uuid, o_player.uuid, 10, false, 0)
+            .await
+            .expect("Failed to create game");
+        let game_uuid = *game.uuid.as_ref();
+        game.rounds_result = sea_orm::Set("XX XXXOO----:03142,O-X-X-X-O:40286".to_owned());
+        sea_orm::ActiveModelTrait::save(game, &conn)
+            .await
+            .expect("Failed to update the game");
+        // The in-progress games are not counted
+        let heatmap = get_heatmap(&service, x_player.uuid).await;
+        assert_eq!(heatmap.rounds, 0);
+
+        db_utils::end_game(
+            &conn,
+            &game_uuid,
+            Some(x_player.uuid),
+            &GameOverReason::PlayerWon,
+        )
+        .await
+        .expect("Failed to end game");
+
+        let heatmap = get_heatmap(&service, x_player.uuid).await;
+        assert_eq!(
+            (heatmap.cells, heatmap.rounds),
+            ([1, 0, 0, 0, 1, 0, 0, 0, 0], 2)
+        );
+        let heatmap = get_heatmap(&service, o_player.uuid).await;
+        assert_eq!(
+            (heatmap.cells, heatmap.rounds),
+            ([1, 0, 0, 1, 0, 0, 0, 0, 0], 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn user_not_found() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let res = send(
+            &service,
+            &format!("user/{}/heatmap", Uuid::new_v4()),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::NOT_FOUND),
+            "The response should have a `NOT_FOUND` status code {res:?}"
+        );
+    }
+}
+
+mod blocks {
+    use super::*;
+
+    async fn send_blocks(
+        service: &Service,
+        path: &str,
+        method: Method,
+        player: &UserSigninSchema,
+    ) -> Response {
+        send(
+            service,
+            path,
+            method,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", player.jwt)).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    async fn get_blocked_users(service: &Service, player: &UserSigninSchema) -> Vec<Uuid> {
+        let mut res = send_blocks(service, "blocks", Method::GET, player).await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        serde_json::from_str::<Vec<UserSchema>>(&res.take_string().await.unwrap())
+            .expect("Failed to parse blocked users")
+            .into_iter()
+            .map(|user| user.uuid)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn block_and_unblock() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_signed_in_player(&conn, "blocks_player").await;
+        let first = create_signed_in_player(&conn, "blocks_first").await;
+        let second = create_signed_in_player(&conn, "blocks_second").await;
+
+        assert!(
+            get_blocked_users(&service, &player).await.is_empty(),
+            "The new user should not have blocked users"
+        );
+
+        for blocked in [&first, &second, &first] {
+            let res = send_blocks(
+                &service,
+                &format!("blocks/{}", blocked.user.uuid),
+                Method::POST,
+                &player,
+            )
+            .await;
+            assert_eq!(
+                res.status_code,
+                Some(StatusCode::OK),
+                "The response should have a `200 OK` status code {res:?}"
+            );
+        }
+        assert_eq!(
+            get_blocked_users(&service, &player).await,
+            vec![second.user.uuid, first.user.uuid],
+            "The latest blocked user should come first, and blocking twice should not duplicate it"
+        );
+        assert!(
+            get_blocked_users(&service, &first).await.is_empty(),
+            "The blocks should be one-directional"
+        );
+
+        let res = send_blocks(
+            &service,
+            &format!("blocks/{}", first.user.uuid),
+            Method::DELETE,
+            &player,
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        assert_eq!(
+            get_blocked_users(&service, &player).await,
+            vec![second.user.uuid],
+        );
+    }
+
+    #[tokio::test]
+    async fn block_self() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_signed_in_player(&conn, "blocks_self").await;
+
+        let res = send_blocks(
+            &service,
+            &format!("blocks/{}", player.user.uuid),
+            Method::POST,
+            &player,
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::BAD_REQUEST),
+            "The response should have a `400 Bad Request` status code {res:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn block_not_found_user() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_signed_in_player(&conn, "blocks_not_found").await;
+
+        let res = send_blocks(
+            &service,
+            &format!("blocks/{}", Uuid::new_v4()),
+            Method::POST,
+            &player,
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::NOT_FOUND),
+            "The response should have a `404 Not Found` status code {res:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn blocks_without_auth() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let res = send(&service, "blocks", Method::GET, None::<&()>, vec![]).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::UNAUTHORIZED),
+            "The response should have a `401 Unauthorized` status code {res:?}"
+        );
+    }
+}
+
+mod get_user_achievements {
+    use super::*;
+
+    async fn get_achievements(service: &Service, user_uuid: Uuid) -> Vec<Achievement> {
+        let mut res = send(
+            service,
+            &format!("user/{user_uuid}/achievements"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        serde_json::from_str::<Vec<AchievementSchema>>(&res.take_string().await.unwrap())
+            .expect("Failed to parse achievements")
+            .into_iter()
+            .map(|achievement| achievement.achievement)
+            .collect()
+    }
+
+    async fn play_game(conn: &sea_orm::DatabaseConnection, x: Uuid, o: Uuid, winner: Uuid) {
+        let game = db_utils::create_game(conn, x, o, 10, false, 0)
+            .await
+            .expect("Failed to create game");
+        db_utils::end_game(
+            conn,
+            game.uuid.as_ref(),
+            Some(winner),
+            &GameOverReason::PlayerWon,
+        )
+        .await
+        .expect("Failed to end game");
+    }
+
+    #[tokio::test]
+    async fn awarded_once() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_player(&conn, "achievements_player").await;
+        let opponent = create_player(&conn, "achievements_opponent").await;
+
+        assert!(
+            get_achievements(&service, player.uuid).await.is_empty(),
+            "The user without games should not have achievements"
+        );
+
+        play_game(&conn, player.uuid, opponent.uuid, player.uuid).await;
+        assert_eq!(
+            get_achievements(&service, player.uuid).await,
+            vec![Achievement::FirstGame, Achievement::FirstWin]
+        );
+        assert_eq!(
+            get_achievements(&service, opponent.uuid).await,
+            vec![Achievement::FirstGame]
+        );
+
+        for _ in 0..4 {
+            play_game(&conn, opponent.uuid, player.uuid, player.uuid).await;
+        }
+        assert_eq!(
+            get_achievements(&service, player.uuid).await,
+            vec![
+                Achievement::FirstGame,
+                Achievement::FirstWin,
+                Achievement::FiveWinStreak
+            ],
+            "The achievements should be awarded exactly once"
+        );
+    }
+
+    #[tokio::test]
+    async fn user_not_found() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let res = send(
+            &service,
+            &format!("user/{}/achievements", Uuid::new_v4()),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::NOT_FOUND),
+            "The response should have a `404 Not Found` status code {res:?}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod get_user_rating {
+    use super::*;
+
+    async fn get_rating(service: &Service, user_uuid: Uuid) -> serde_json::Value {
+        let mut res = send(
+            service,
+            &format!("user/{user_uuid}/rating"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        res.take_json::<serde_json::Value>().await.unwrap()
+    }
+
+    pub async fn play_game(
+        conn: &sea_orm::DatabaseConnection,
+        x: Uuid,
+        o: Uuid,
+        winner: Option<Uuid>,
+    ) -> Uuid {
+        let game = db_utils::create_game(conn, x, o, 10, false, 0)
+            .await
+            .expect("Failed to create game");
+        let reason = if winner.is_some() {
+            GameOverReason::PlayerWon
+        } else {
+            GameOverReason::Draw
+        };
+        db_utils::end_game(conn, game.uuid.as_ref(), winner, &reason)
+            .await
+            .expect("Failed to end game");
+        *game.uuid.as_ref()
+    }
+
+    #[tokio::test]
+    async fn rating_changes() {
+        assert_eq!(xors_api::utils::rating_changes(1200, 1200, 1.0), (16, -16));
+        assert_eq!(xors_api::utils::rating_changes(1200, 1200, 0.5), (0, 0));
+        assert_eq!(xors_api::utils::rating_changes(1200, 1200, 0.0), (-16, 16));
+
+        let (underdog_win, _) = xors_api::utils::rating_changes(1000, 1400, 1.0);
+        let (favorite_win, _) = xors_api::utils::rating_changes(1400, 1000, 1.0);
+        assert!(underdog_win > 16 && favorite_win < 16 && favorite_win > 0);
+    }
+
+    #[tokio::test]
+    async fn rating_updated_after_game() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_player(&conn, "rating_player").await;
+        let opponent = create_player(&conn, "rating_opponent").await;
+        assert_eq!(player.rating, 1200);
+
+        let rating = get_rating(&service, player.uuid).await;
+        assert_eq!(rating["rating"], 1200);
+        assert_eq!(rating["history"], serde_json::json!([]));
+
+        let won_game = play_game(&conn, player.uuid, opponent.uuid, Some(player.uuid)).await;
+        let drawn_game = play_game(&conn, opponent.uuid, player.uuid, None).await;
+
+        let rating = get_rating(&service, player.uuid).await;
+        assert_eq!(rating["rating"], 1215, "{rating}");
+        assert_eq!(rating["history"][0]["game_uuid"], won_game.to_string());
+        assert_eq!(rating["history"][0]["rating"], 1216);
+        assert_eq!(rating["history"][0]["change"], 16);
+        assert_eq!(rating["history"][1]["game_uuid"], drawn_game.to_string());
+        assert_eq!(rating["history"][1]["change"], -1);
+
+        let rating = get_rating(&service, opponent.uuid).await;
+        assert_eq!(rating["rating"], 1185, "{rating}");
+        assert_eq!(rating["history"][0]["change"], -16);
+        assert_eq!(rating["history"][1]["change"], 1);
+
+        let user = UserSchema::from_active_model(
+            &conn,
+            db_utils::get_user(&conn, player.uuid).await.unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(user.rating, 1215);
+    }
+
+    #[tokio::test]
+    async fn user_not_found() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let res = send(
+            &service,
+            &format!("user/{}/rating", Uuid::new_v4()),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::NOT_FOUND),
+            "The response should have a `404 Not Found` status code {res:?}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod get_leaderboard {
+    use super::*;
+
+    pub async fn get_leaderboard(service: &Service, page: u64) -> Vec<serde_json::Value> {
+        let mut res = send(
+            service,
+            &format!("leaderboard?page={page}"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        res.take_json::<Vec<serde_json::Value>>().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn sorted_by_rating() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let mut players = Vec::new();
+        for username in ["leaderboard_winner", "leaderboard_loser"] {
+            players.push(
+                db_utils::create_user(
+                    &conn,
+                    NewUserSchema {
+                        username: username.to_owned(),
+                        first_name: "Player".to_owned(),
+                        password: "fdkDFLKJL4859#$&".to_owned(),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .expect("Failed to create player"),
+            );
+        }
+        let (winner, loser) = (players[0].uuid, players[1].uuid);
+        let game = db_utils::create_game(&conn, winner, loser, 10, false, 0)
+            .await
+            .expect("Failed to create game");
+        db_utils::end_game(
+            &conn,
+            game.uuid.as_ref(),
+            Some(winner),
+            &GameOverReason::PlayerWon,
+        )
+        .await
+        .expect("Failed to end game");
+
+        let mut entries = Vec::new();
+        for page in 0.. {
+            let page_entries = get_leaderboard(&service, page).await;
+            if page_entries.is_empty() {
+                break;
+            }
+            assert!(page_entries.len() <= 20, "Each page has 20 users at most");
+            entries.extend(page_entries);
+        }
+
+        for (idx, entry) in entries.iter().enumerate() {
+            assert_eq!(entry["rank"], idx as u64 + 1, "{entry}");
+        }
+        assert!(
+            entries
+                .windows(2)
+                .all(|pair| pair[0]["rating"].as_i64() >= pair[1]["rating"].as_i64()),
+            "The leaderboard should be sorted by the rating"
+        );
+        let position = |uuid: Uuid| {
+            entries
+                .iter()
+                .position(|entry| entry["uuid"] == uuid.to_string())
+                .expect("The user should be in the leaderboard")
+        };
+        assert!(position(winner) < position(loser));
+        assert_eq!(entries[position(winner)]["rating"], 1216);
+    }
+}
+
+#[cfg(test)]
+mod get_user_rank {
+    use super::get_user_rating::play_game;
+    use super::*;
+
+    pub async fn get_rank(service: &Service, user_uuid: Uuid) -> serde_json::Value {
+        let mut res = send(
+            service,
+            &format!("user/{user_uuid}/rank"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        res.take_json::<serde_json::Value>().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn unranked_before_min_games() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_player(&conn, "rank_new_player").await;
+        let opponent = create_player(&conn, "rank_new_opponent").await;
+        play_game(&conn, player.uuid, opponent.uuid, Some(player.uuid)).await;
+
+        let rank = get_rank(&service, player.uuid).await;
+        assert_eq!(rank["ranked"], false, "{rank}");
+        assert_eq!(rank["rank"], serde_json::Value::Null);
+        assert_eq!(rank["percentile"], serde_json::Value::Null);
+        assert_eq!(rank["rated_games"], 1);
+        assert_eq!(
+            rank["min_rated_games"],
+            xors_api::api::DEFAULT_MIN_RATED_GAMES
+        );
+    }
+
+    #[tokio::test]
+    async fn ranked_by_rating() {
+        std::env::set_var("XORS_API_TEST", "true");
+        let service = xors_api::api::service(
+            get_connection().await.expect("Failed to get connection"),
+            get_jwt_keys(),
+            xors_api::api::ServiceConfig {
+                min_rated_games: 2,
+                ..test_service_config()
+            },
+        )
+        .0;
+        let conn = get_connection().await.expect("Failed to get connection");
+        let winner = create_player(&conn, "rank_winner").await;
+        let loser = create_player(&conn, "rank_loser").await;
+        play_game(&conn, winner.uuid, loser.uuid, Some(winner.uuid)).await;
+        assert_eq!(get_rank(&service, winner.uuid).await["ranked"], false);
+
+        play_game(&conn, loser.uuid, winner.uuid, Some(winner.uuid)).await;
+        let winner_rank = get_rank(&service, winner.uuid).await;
+        let loser_rank = get_rank(&service, loser.uuid).await;
+        for rank in [&winner_rank, &loser_rank] {
+            assert_eq!(rank["ranked"], true, "{rank}");
+            assert_eq!(rank["rated_games"], 2);
+            assert_eq!(rank["min_rated_games"], 2);
+            let (position, ranked_users) = (
+                rank["rank"].as_u64().unwrap(),
+                rank["ranked_users"].as_u64().unwrap(),
+            );
+            assert!((1..=ranked_users).contains(&position), "{rank}");
+            let percentile = (ranked_users - position + 1) as f64 * 100.0 / ranked_users as f64;
+            assert!(
+                (rank["percentile"].as_f64().unwrap() - percentile).abs() < 0.01,
+                "{rank}"
+            );
+        }
+        assert!(
+            winner_rank["rank"].as_u64() < loser_rank["rank"].as_u64(),
+            "The higher rated player is ranked first {winner_rank} {loser_rank}"
+        );
+    }
+
+    #[tokio::test]
+    async fn guest_unranked() {
+        std::env::set_var("XORS_API_TEST", "true");
+        let service = xors_api::api::service(
+            get_connection().await.expect("Failed to get connection"),
+            get_jwt_keys(),
+            xors_api::api::ServiceConfig {
+                min_rated_games: 0,
+                ..test_service_config()
+            },
+        )
+        .0;
+        let conn = get_connection().await.expect("Failed to get connection");
+        let guest = db_utils::create_guest(&conn)
+            .await
+            .expect("Failed to create guest");
+
+        let rank = get_rank(&service, guest.uuid).await;
+        assert_eq!(rank["ranked"], false, "{rank}");
+        assert_eq!(rank["rank"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn user_not_found() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let res = send(
+            &service,
+            &format!("user/{}/rank", Uuid::new_v4()),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::NOT_FOUND),
+            "The response should have a `404 Not Found` status code {res:?}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod leaderboard_tiebreakers {
+    use super::get_leaderboard::get_leaderboard;
+    use super::get_user_rank::get_rank;
+    use super::*;
+    use xors_api::api::user::Tiebreaker;
+
+    /// Creates players with the same rating and different results, returns them in the creation order.
+    async fn create_tied_players(
+        conn: &sea_orm::DatabaseConnection,
+        prefix: &str,
+        rating: i64,
+    ) -> Vec<Uuid> {
+        let mut players = Vec::new();
+        // (wins, losts), the first player has the older join date
+        for (idx, (wins, losts)) in [(3, 2), (3, 1), (5, 3), (1, 1), (1, 1)]
+            .into_iter()
+            .enumerate()
+        {
+            let player = create_player(conn, &format!("{prefix}_{idx}")).await;
+            let mut user = db_utils::get_user(conn, player.uuid).await.unwrap();
+            user.rating = sea_orm::Set(rating);
+            user.wins = sea_orm::Set(wins);
+            user.losts = sea_orm::Set(losts);
+            if idx == 3 {
+                user.created_at = sea_orm::Set(chrono::Utc::now().naive_utc() - Duration::hours(1));
+            }
+            sea_orm::ActiveModelTrait::save(user, conn)
+                .await
+                .expect("Failed to update the player");
+            players.push(player.uuid);
+        }
+        players
+    }
+
+    /// Returns the service with the given tiebreakers, all the users are ranked.
+    async fn get_service_with_tiebreakers(tiebreakers: Vec<Tiebreaker>) -> Service {
+        std::env::set_var("XORS_API_TEST", "true");
+        xors_api::api::service(
+            get_connection().await.expect("Failed to get connection"),
+            get_jwt_keys(),
+            xors_api::api::ServiceConfig {
+                min_rated_games: 0,
+                leaderboard_tiebreakers: tiebreakers,
+                ..test_service_config()
+            },
+        )
+        .0
+    }
+
+    /// Asserts that the players are in the given order in the leaderboard and the ranks, one after another.
+    async fn assert_ranked_in_order(service: &Service, players: &[Uuid]) {
+        let mut entries = Vec::new();
+        for page in 0.. {
+            let page_entries = get_leaderboard(service, page).await;
+            if page_entries.is_empty() {
+                break;
+            }
+            entries.extend(page_entries);
+        }
+        let leaderboard_ranks = players
+            .iter()
+            .map(|uuid| {
+                entries
+                    .iter()
+                    .find(|entry| entry["uuid"] == uuid.to_string())
+                    .expect("The player should be in the leaderboard")["rank"]
+                    .as_u64()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let first_rank = leaderboard_ranks[0];
+        assert_eq!(
+            leaderboard_ranks,
+            (first_rank..first_rank + players.len() as u64).collect::<Vec<_>>(),
+            "The players should be in the tiebreakers order"
+        );
+
+        for (uuid, rank) in players.iter().zip(leaderboard_ranks) {
+            let user_rank = get_rank(service, *uuid).await;
+            assert_eq!(user_rank["rank"], rank, "{user_rank}");
+        }
+    }
+
+    #[tokio::test]
+    async fn default_tiebreakers() {
+        let service = get_service_with_tiebreakers(
+            xors_api::api::user::DEFAULT_LEADERBOARD_TIEBREAKERS.to_vec(),
+        )
+        .await;
+        let conn = get_connection().await.expect("Failed to get connection");
+        let players = create_tied_players(&conn, "default_tiebreak", 9100).await;
+
+        // More wins, then fewer games, then the earliest rating. The last two players have no rating changes, they
+        // reached the rating when they joined
+        assert_ranked_in_order(
+            &service,
+            &[players[2], players[1], players[0], players[3], players[4]],
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn configured_tiebreakers() {
+        let service = get_service_with_tiebreakers(vec![Tiebreaker::FewestGames]).await;
+        let conn = get_connection().await.expect("Failed to get connection");
+        let players = create_tied_players(&conn, "fewest_games_tiebreak", 9200).await;
+
+        // Fewer games, the players that are still tied are ordered by their join date
+        assert_ranked_in_order(
+            &service,
+            &[players[3], players[4], players[1], players[0], players[2]],
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn parse_tiebreakers() {
+        assert_eq!("most_wins".parse::<Tiebreaker>(), Ok(Tiebreaker::MostWins));
+        assert_eq!(
+            "fewest_games".parse::<Tiebreaker>(),
+            Ok(Tiebreaker::FewestGames)
+        );
+        assert_eq!(
+            "earliest_rating".parse::<Tiebreaker>(),
+            Ok(Tiebreaker::EarliestRating)
+        );
+        assert_eq!(
+            "oldest_account".parse::<Tiebreaker>(),
+            Ok(Tiebreaker::OldestAccount)
+        );
+        assert_eq!("uuid".parse::<Tiebreaker>(), Ok(Tiebreaker::Uuid));
+        assert!("rating".parse::<Tiebreaker>().is_err());
+    }
+}
+
+#[cfg(test)]
+mod get_user_activity {
+    use super::*;
+
+    /// Creates a game between the players, started at the given time.
+    async fn create_game_at(
+        conn: &sea_orm::DatabaseConnection,
+        x: Uuid,
+        o: Uuid,
+        created_at: chrono::NaiveDateTime,
+    ) {
+        let mut game = db_utils::create_game(conn, x, o, 10, false, 0)
+            .await
+            .expect("Failed to create game");
+        game.created_at = sea_orm::Set(created_at);
+        sea_orm::ActiveModelTrait::save(game, conn)
+            .await
+            .expect("Failed to update the game");
+    }
+
+    #[tokio::test]
+    async fn zero_filled_days() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_player(&conn, "activity_player").await;
+        let opponent = create_player(&conn, "activity_opponent").await;
+
+        let day = chrono::NaiveDate::from_ymd_opt(2026, 3, 10).unwrap();
+        for (days, hour) in [(0, 1), (0, 23), (2, 12), (5, 0)] {
+            create_game_at(
+                &conn,
+                player.uuid,
+                opponent.uuid,
+                (day + Duration::days(days))
+                    .and_hms_opt(hour, 0, 0)
+                    .unwrap(),
+            )
+            .await;
+        }
+
+        let mut res = send(
+            &service,
+            &format!(
+                "user/{}/activity?from=2026-03-09&to=2026-03-13",
+                player.uuid
+            ),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        let activity: Vec<ActivityDaySchema> =
+            serde_json::from_str(&res.take_string().await.unwrap())
+                .expect("Failed to parse activity");
+
+        assert_eq!(
+            activity
+                .iter()
+                .map(|activity_day| (activity_day.date.to_string(), activity_day.games))
+                .collect::<Vec<_>>(),
+            vec![
+                ("2026-03-09".to_owned(), 0),
+                ("2026-03-10".to_owned(), 2),
+                ("2026-03-11".to_owned(), 0),
+                ("2026-03-12".to_owned(), 1),
+                ("2026-03-13".to_owned(), 0),
+            ],
+            "The days without games should have a zero count"
+        );
+    }
+
+    #[tokio::test]
+    async fn default_range() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_player(&conn, "activity_default_player").await;
+
+        let mut res = send(
+            &service,
+            &format!("user/{}/activity", player.uuid),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        let activity: Vec<ActivityDaySchema> =
+            serde_json::from_str(&res.take_string().await.unwrap())
+                .expect("Failed to parse activity");
+
+        assert_eq!(activity.len(), 365);
+        assert_eq!(
+            activity.last().unwrap().date,
+            chrono::Utc::now().date_naive()
+        );
+        assert!(activity.iter().all(|activity_day| activity_day.games == 0));
+    }
+
+    #[tokio::test]
+    async fn invalid_range() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_player(&conn, "activity_invalid_player").await;
+
+        for query in [
+            "from=2026-03-10&to=2026-03-09",
+            "from=2025-01-01&to=2026-01-02",
+        ] {
+            let res = send(
+                &service,
+                &format!("user/{}/activity?{query}", player.uuid),
+                Method::GET,
+                None::<&()>,
+                vec![],
+            )
+            .await;
+            assert_eq!(
+                res.status_code,
+                Some(StatusCode::BAD_REQUEST),
+                "The response should have a `400 Bad Request` status code {res:?}"
+            );
+        }
+
+        let res = send(
+            &service,
+            &format!("user/{}/activity", Uuid::new_v4()),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::NOT_FOUND),
+            "The response should have a `404 Not Found` status code {res:?}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod set_user_role {
+    use super::*;
+    use sea_orm::{ActiveModelTrait, Set};
+    use xors_api::api::jwt::JwtClaims;
+
+    async fn create_player_with_role(
+        conn: &sea_orm::DatabaseConnection,
+        username: &str,
+        role: UserRole,
+    ) -> UserSchema {
+        let user = create_player(conn, username).await;
+        set_role(conn, user.uuid, role).await;
+        UserSchema { role, ..user }
+    }
+
+    async fn set_role(conn: &sea_orm::DatabaseConnection, user_uuid: Uuid, role: UserRole) {
+        let mut user = db_utils::get_user(conn, user_uuid).await.unwrap();
+        user.role = Set(role.to_string());
+        user.save(conn).await.unwrap();
+    }
+
+    async fn change_role(
+        service: &Service,
+        token: &str,
+        user_uuid: Uuid,
+        role: UserRole,
+    ) -> Response {
+        send(
+            service,
+            &format!("user/{user_uuid}/role"),
+            Method::PUT,
+            Some(&UserRoleSchema::new(role)),
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    fn token_role(token: &str) -> UserRole {
+        jsonwebtoken::decode::<JwtClaims>(
+            token,
+            &jsonwebtoken::DecodingKey::from_secret(get_secret_key().as_bytes()),
+            &jsonwebtoken::Validation::default(),
+        )
+        .unwrap()
+        .claims
+        .role
+    }
+
+    #[tokio::test]
+    async fn user_is_rejected() {
+        let conn = get_connection().await.unwrap();
+        let service = get_service().await.unwrap();
+        let user = create_player_with_role(&conn, "RoleGuardUser", UserRole::User).await;
+        let moderator =
+            create_player_with_role(&conn, "RoleGuardModerator", UserRole::Moderator).await;
+        let user_tokens = db_utils::signin_user(
+            &conn,
+            user.clone(),
+            &get_jwt_keys(),
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .unwrap();
+        let moderator_tokens = db_utils::signin_user(
+            &conn,
+            moderator,
+            &get_jwt_keys(),
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(token_role(&user_tokens.jwt), UserRole::User);
+
+        let mut res = change_role(&service, &user_tokens.jwt, user.uuid, UserRole::Admin).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["message"],
+            "Forbidden: You don't have the permission to do this action"
+        );
+        let res = change_role(&service, &moderator_tokens.jwt, user.uuid, UserRole::Admin).await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::FORBIDDEN),
+            "The moderator is not an admin"
+        );
+        let role = db_utils::get_user(&conn, user.uuid).await.unwrap().role;
+        assert_eq!(role.as_ref(), "user");
+    }
+
+    #[tokio::test]
+    async fn admin_passes() {
+        let conn = get_connection().await.unwrap();
+        let service = get_service().await.unwrap();
+        let admin = create_player_with_role(&conn, "RoleGuardAdmin", UserRole::Admin).await;
+        let user = create_player_with_role(&conn, "RoleGuardPromoted", UserRole::User).await;
+        let admin_tokens =
+            db_utils::signin_user(&conn, admin, &get_jwt_keys(), &test_token_lifetimes(), true)
+                .await
+                .unwrap();
+        assert_eq!(token_role(&admin_tokens.jwt), UserRole::Admin);
+
+        let mut res =
+            change_role(&service, &admin_tokens.jwt, user.uuid, UserRole::Moderator).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let user: UserSchema = res.take_json().await.unwrap();
+        assert_eq!(user.role, UserRole::Moderator);
+
+        let res = change_role(&service, &admin_tokens.jwt, Uuid::new_v4(), UserRole::User).await;
+        assert_eq!(res.status_code, Some(StatusCode::NOT_FOUND), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn refresh_reads_current_role() {
+        let conn = get_connection().await.unwrap();
+        let service = get_service().await.unwrap();
+        let admin = create_player_with_role(&conn, "RoleGuardDemoted", UserRole::Admin).await;
+        let admin_tokens = db_utils::signin_user(
+            &conn,
+            admin.clone(),
+            &get_jwt_keys(),
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(token_role(&admin_tokens.refresh_token), UserRole::Admin);
+        set_role(&conn, admin.uuid, UserRole::User).await;
+
+        // Sleep until the refresh token available
+        tokio::time::sleep(Duration::seconds(3).to_std().unwrap()).await;
+        let mut res = send(
+            &service,
+            "auth/refresh",
+            Method::GET,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", admin_tokens.refresh_token)).unwrap(),
+            )],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let tokens: UserSigninSchema = res.take_json().await.unwrap();
+        assert_eq!(token_role(&tokens.jwt), UserRole::User);
+        assert_eq!(token_role(&tokens.refresh_token), UserRole::User);
+
+        let res = change_role(&service, &tokens.jwt, admin.uuid, UserRole::Admin).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+    }
+}
+
+mod two_factor {
+    use super::*;
+
+    async fn send_2fa<T: Serialize>(
+        service: &Service,
+        path: &str,
+        player: &UserSigninSchema,
+        body: Option<&T>,
+    ) -> Response {
+        send(
+            service,
+            path,
+            Method::POST,
+            body,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", player.jwt)).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    async fn signin(service: &Service, username: &str, totp_code: Option<String>) -> Response {
+        send(
+            service,
+            "auth/signin",
+            Method::POST,
+            Some(&SigninSchema {
+                username: username.to_owned(),
+                password: PLAYER_PASSWORD.to_owned(),
+                remember_me: None,
+                totp_code,
+            }),
+            vec![],
+        )
+        .await
+    }
+
+    async fn error_code(res: &mut Response) -> String {
+        res.take_json::<serde_json::Value>().await.unwrap()["code"]
+            .as_str()
+            .unwrap()
+            .to_owned()
+    }
+
+    /// Enables the two-factor authentication of the player, returns the secret and the recovery codes.
+    async fn enable(service: &Service, player: &UserSigninSchema) -> (String, Vec<String>) {
+        let mut res = send_2fa(service, "user/2fa/enable", player, None::<&()>).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let secret: TotpSecretSchema = res.take_json().await.unwrap();
+        assert!(secret.otpauth_uri.starts_with("otpauth://totp/"));
+        assert!(secret.otpauth_uri.contains(&secret.secret));
+
+        let code = xors_api::utils::totp_code(&secret.secret, chrono::Utc::now().timestamp());
+        let mut res = send_2fa(
+            service,
+            "user/2fa/confirm",
+            player,
+            Some(&ConfirmTotpSchema::new(code.unwrap())),
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let recovery_codes: RecoveryCodesSchema = res.take_json().await.unwrap();
+        assert_eq!(
+            recovery_codes.recovery_codes.len(),
+            xors_api::utils::RECOVERY_CODES_COUNT
+        );
+        (secret.secret, recovery_codes.recovery_codes)
+    }
+
+    #[test]
+    fn totp_code_rfc_vectors() {
+        // The RFC 6238 SHA1 test vectors, truncated to 6 digits
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        for (timestamp, code) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (2000000000, "279037"),
+        ] {
+            assert_eq!(
+                xors_api::utils::totp_code(secret, timestamp).as_deref(),
+                Some(code)
+            );
+        }
+        assert_eq!(xors_api::utils::totp_code("not base32!", 59), None);
+    }
+
+    #[tokio::test]
+    async fn signin_requires_totp_code() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_signed_in_player(&conn, "TwoFactorSignin").await;
+
+        let res = send_2fa(&service, "user/2fa/enable", &player, None::<&()>).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        // Not confirmed yet, so it's not required
+        let res = signin(&service, &player.user.username, None).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        let mut res = send_2fa(
+            &service,
+            "user/2fa/confirm",
+            &player,
+            Some(&ConfirmTotpSchema::new("ABCDEF".to_owned())),
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "invalid_totp_code");
+
+        let (secret, _) = enable(&service, &player).await;
+
+        let mut res = signin(&service, &player.user.username, None).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "totp_required");
+
+        let mut res = signin(&service, &player.user.username, Some("ABCDEF".to_owned())).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "invalid_totp_code");
+
+        // The code of the current period is used by the confirmation
+        let code = xors_api::utils::totp_code(
+            &secret,
+            chrono::Utc::now().timestamp() + xors_api::utils::TOTP_PERIOD,
+        );
+        let res = signin(&service, &player.user.username, code.clone()).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        let mut res = signin(&service, &player.user.username, code).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        assert_eq!(
+            error_code(&mut res).await,
+            "invalid_totp_code",
+            "The accepted code can't be replayed"
+        );
+
+        let mut res = send_2fa(&service, "user/2fa/enable", &player, None::<&()>).await;
+        assert_eq!(res.status_code, Some(StatusCode::CONFLICT), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "totp_already_enabled");
+    }
+
+    #[tokio::test]
+    async fn recovery_code_used_once() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_signed_in_player(&conn, "TwoFactorRecovery").await;
+        let (_, recovery_codes) = enable(&service, &player).await;
+
+        // The recovery codes are not case sensitive
+        let res = signin(
+            &service,
+            &player.user.username,
+            Some(recovery_codes[0].to_lowercase()),
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        let mut res = signin(
+            &service,
+            &player.user.username,
+            Some(recovery_codes[0].clone()),
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "invalid_totp_code");
+
+        let res = signin(
+            &service,
+            &player.user.username,
+            Some(recovery_codes[1].clone()),
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn disable_totp() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_signed_in_player(&conn, "TwoFactorDisable").await;
+        let (_, recovery_codes) = enable(&service, &player).await;
+
+        let mut res = send_2fa(
+            &service,
+            "user/2fa/disable",
+            &player,
+            Some(&DisableTotpSchema::new("ABCDEF".to_owned())),
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "invalid_totp_code");
+
+        let res = send_2fa(
+            &service,
+            "user/2fa/disable",
+            &player,
+            Some(&DisableTotpSchema::new(recovery_codes[0].clone())),
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        let res = signin(&service, &player.user.username, None).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let mut res = send_2fa(
+            &service,
+            "user/2fa/disable",
+            &player,
+            Some(&DisableTotpSchema::new(recovery_codes[1].clone())),
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::CONFLICT), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "totp_not_enabled");
+    }
+
+    #[tokio::test]
+    async fn confirm_without_enable() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_signed_in_player(&conn, "TwoFactorNotPending").await;
+
+        let mut res = send_2fa(
+            &service,
+            "user/2fa/confirm",
+            &player,
+            Some(&ConfirmTotpSchema::new("123456".to_owned())),
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::CONFLICT), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "totp_not_pending");
+    }
+}
+
+#[cfg(test)]
+mod get_user_stats {
+    use super::*;
+    use sea_orm::{ActiveModelTrait, Set};
+
+    #[tokio::test]
+    async fn stats() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_player(&conn, "stats_player").await;
+        let opponent = create_player(&conn, "stats_opponent").await;
+
+        for (winner, duration) in [
+            (Some(player.uuid), 60),
+            (Some(opponent.uuid), 90),
+            (None, 120),
+        ] {
+            let mut game = db_utils::create_game(&conn, player.uuid, opponent.uuid, 10, false, 0)
+                .await
+                .expect("Failed to create game");
+            game.created_at =
+                Set(chrono::Utc::now().naive_utc() - chrono::Duration::seconds(duration));
+            let game = game.save(&conn).await.unwrap();
+            let reason = if winner.is_some() {
+                GameOverReason::PlayerWon
+            } else {
+                GameOverReason::Draw
+            };
+            db_utils::end_game(&conn, game.uuid.as_ref(), winner, &reason)
+                .await
+                .expect("Failed to end game");
+        }
+
+        let mut res = send(
+            &service,
+            &format!("user/{}/stats", player.uuid),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(
+            res.status_code,
+            Some(StatusCode::OK),
+            "The response should have a `200 OK` status code {res:?}"
+        );
+        let stats: UserStatsSchema = res.take_json().await.unwrap();
+        assert_eq!(
+            (stats.wins, stats.losses, stats.draws),
+            (1, 1, 1),
+            "{stats:?}"
+        );
+        assert_eq!((stats.current_win_streak, stats.longest_win_streak), (0, 1));
+        assert_eq!(stats.average_game_duration, Some(90));
+        assert_eq!(stats.rating_trend.len(), 3);
+        assert_eq!(stats.rating_trend.last(), Some(&stats.rating));
+    }
+
+    #[tokio::test]
+    async fn stats_without_games() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_player(&conn, "stats_new_player").await;
+
+        let mut res = send(
+            &service,
+            &format!("user/{}/stats", player.uuid),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let stats: UserStatsSchema = res.take_json().await.unwrap();
+        assert_eq!(stats.average_game_duration, None);
+        assert!(stats.rating_trend.is_empty());
+    }
+
+    #[tokio::test]
+    async fn stats_of_unknown_user() {
+        let service = get_service().await.expect("Failed to get service");
+
+        let res = send(
+            &service,
+            &format!("user/{}/stats", Uuid::new_v4()),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::NOT_FOUND), "{res:?}");
+    }
+}
+
+#[cfg(test)]
+mod get_user_games {
+    use super::*;
+
+    async fn play_game(
+        conn: &sea_orm::DatabaseConnection,
+        x_player: Uuid,
+        o_player: Uuid,
+        winner: Option<Uuid>,
+    ) -> Uuid {
+        let game = db_utils::create_game(conn, x_player, o_player, 10, false, 0)
+            .await
+            .expect("Failed to create game");
+        let reason = if winner.is_some() {
+            GameOverReason::PlayerWon
+        } else {
+            GameOverReason::Draw
+        };
+        db_utils::end_game(conn, game.uuid.as_ref(), winner, &reason)
+            .await
+            .expect("Failed to end game");
+        *game.uuid.as_ref()
+    }
+
+    async fn get_games(service: &Service, user_uuid: Uuid, query: &str) -> Response {
+        send(
+            service,
+            &format!("user/{user_uuid}/games?{query}"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn cursor_pagination() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_player(&conn, "history_player").await;
+        let opponent = create_player(&conn, "history_opponent").await;
+        let mut games = Vec::new();
+        for _ in 0..25 {
+            games.push(play_game(&conn, player.uuid, opponent.uuid, None).await);
+        }
+        games.reverse();
+
+        let mut res = get_games(&service, player.uuid, "").await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let page: UserGamesSchema = res.take_json().await.unwrap();
+        assert_eq!(
+            page.games.iter().map(|game| game.uuid).collect::<Vec<_>>(),
+            games[..20],
+            "The first page should have the newest 20 games"
+        );
+        let next_cursor = page.next_cursor.expect("There is a next page");
+
+        let mut res = get_games(&service, player.uuid, &format!("before={next_cursor}")).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let page: UserGamesSchema = res.take_json().await.unwrap();
+        assert_eq!(
+            page.games.iter().map(|game| game.uuid).collect::<Vec<_>>(),
+            games[20..]
+        );
+        assert_eq!(page.next_cursor, None, "It's the last page");
+    }
+
+    #[tokio::test]
+    async fn filters() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_player(&conn, "filters_player").await;
+        let opponent = create_player(&conn, "filters_opponent").await;
+        let other_opponent = create_player(&conn, "filters_other").await;
+        let won = play_game(&conn, player.uuid, opponent.uuid, Some(player.uuid)).await;
+        let lost = play_game(&conn, opponent.uuid, player.uuid, Some(opponent.uuid)).await;
+        let draw = play_game(&conn, player.uuid, other_opponent.uuid, None).await;
+
+        for (query, expected) in [
+            ("result=win".to_owned(), vec![won]),
+            ("result=loss".to_owned(), vec![lost]),
+            ("result=draw".to_owned(), vec![draw]),
+            (format!("opponent={}", opponent.uuid), vec![lost, won]),
+            (format!("opponent={}&result=win", opponent.uuid), vec![won]),
+            (
+                format!("from={}", chrono::Utc::now().date_naive()),
+                vec![draw, lost, won],
+            ),
+            (
+                format!(
+                    "to={}",
+                    chrono::Utc::now().date_naive() - chrono::Duration::days(1)
+                ),
+                vec![],
+            ),
+        ] {
+            let mut res = get_games(&service, player.uuid, &query).await;
+            assert_eq!(res.status_code, Some(StatusCode::OK), "{query}: {res:?}");
+            let page: UserGamesSchema = res.take_json().await.unwrap();
+            assert_eq!(
+                page.games.iter().map(|game| game.uuid).collect::<Vec<_>>(),
+                expected,
+                "{query}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_filters() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_player(&conn, "invalid_filters_player").await;
+
+        let mut res = get_games(&service, player.uuid, "result=lost").await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{res:?}");
+        assert_eq!(
+            res.take_json::<serde_json::Value>().await.unwrap()["code"],
+            "invalid_game_result"
+        );
+
+        let res = get_games(&service, player.uuid, "from=2026-02-02&to=2026-01-01").await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{res:?}");
+
+        let res = get_games(&service, player.uuid, &format!("before={}", Uuid::new_v4())).await;
+        assert_eq!(res.status_code, Some(StatusCode::NOT_FOUND), "{res:?}");
+    }
+}
+
+mod change_username {
+    use super::*;
+
+    async fn change_username(service: &Service, jwt: &str, username: &str) -> Response {
+        send(
+            service,
+            "user/username",
+            Method::PUT,
+            Some(&ChangeUsernameSchema::new(username.to_owned())),
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {jwt}")).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    async fn error_code(res: &mut Response) -> String {
+        res.take_json::<serde_json::Value>().await.unwrap()["code"]
+            .as_str()
+            .unwrap()
+            .to_owned()
+    }
+
+    #[tokio::test]
+    async fn change_username_success() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_signed_in_player(&conn, "renamed_before").await;
+
+        let mut res = change_username(&service, &player.jwt, " renamed_after ").await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let user = res.take_json::<UserSchema>().await.unwrap();
+        assert_eq!(user.uuid, player.user.uuid);
+        assert_eq!(user.username, "renamed_after");
+
+        // Only the case is changed
+        let res = change_username(&service, &player.jwt, "Renamed_After").await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+
+        // The old username is free, and the new one is taken
+        let other = create_signed_in_player(&conn, "renamed_before").await;
+        let mut res = change_username(&service, &other.jwt, "RENAMED_AFTER").await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "username_already_exists");
+    }
+
+    #[tokio::test]
+    async fn change_username_invalid() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_signed_in_player(&conn, "renamed_invalid").await;
+
+        for username in ["ab", "1renamed", "renamed user", &"a".repeat(33)] {
+            let mut res = change_username(&service, &player.jwt, username).await;
+            assert_eq!(
+                res.status_code,
+                Some(StatusCode::BAD_REQUEST),
+                "{username}: {res:?}"
+            );
+            assert_eq!(error_code(&mut res).await, "invalid_username");
+        }
+        let mut res = change_username(&service, &player.jwt, "renamed_invalid").await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "username_not_changed");
+    }
+
+    #[tokio::test]
+    async fn change_username_guest() {
+        let service = get_service().await.expect("Failed to get service");
+        let mut res = send::<()>(&service, "auth/guest", Method::POST, None, vec![]).await;
+        let guest = res.take_json::<UserSigninSchema>().await.unwrap();
+
+        let res = change_username(&service, &guest.jwt, "renamed_guest").await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN), "{res:?}");
+    }
+}
+
+mod data_export {
+    use super::*;
+
+    use std::io::Read;
+
+    async fn send_export(service: &Service, path: &str, method: Method, jwt: &str) -> Response {
+        send(
+            service,
+            path,
+            method,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {jwt}")).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    async fn error_code(res: &mut Response) -> String {
+        res.take_json::<serde_json::Value>().await.unwrap()["code"]
+            .as_str()
+            .unwrap()
+            .to_owned()
+    }
+
+    /// Waits for the export to be generated, returns its status.
+    async fn wait_export(service: &Service, jwt: &str) -> DataExportSchema {
+        for _ in 0..100 {
+            let mut res = send_export(service, "user/export", Method::GET, jwt).await;
+            assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+            let export = res.take_json::<DataExportSchema>().await.unwrap();
+            if export.status != DataExportStatus::Pending {
+                return export;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        panic!("The data export is not generated");
+    }
+
+    #[tokio::test]
+    async fn export_user_data() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_signed_in_player(&conn, "exported_player").await;
+        let opponent = create_signed_in_player(&conn, "exported_opponent").await;
+        let game = db_utils::create_game(&conn, player.user.uuid, opponent.user.uuid, 10, false, 0)
+            .await
+            .expect("Failed to create game");
+        db_utils::end_game(
+            &conn,
+            game.uuid.as_ref(),
+            Some(player.user.uuid),
+            &GameOverReason::PlayerWon,
+        )
+        .await
+        .expect("Failed to end game");
+
+        let mut res = send_export(&service, "user/export", Method::GET, &player.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::NOT_FOUND), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "data_export_not_found");
+
+        let mut res = send_export(&service, "user/export", Method::POST, &player.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let export = res.take_json::<DataExportSchema>().await.unwrap();
+        assert_eq!(export.status, DataExportStatus::Pending);
+
+        let ready = wait_export(&service, &player.jwt).await;
+        assert_eq!(ready.uuid, export.uuid);
+        assert_eq!(ready.status, DataExportStatus::Ready);
+        assert!(ready.expires_at.is_some());
+
+        let mut res = send_export(&service, "user/export/download", Method::GET, &player.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        assert_eq!(
+            res.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            "attachment; filename=\"xors-export-exported_player.json\""
+        );
+        let data = res.take_json::<UserDataExportSchema>().await.unwrap();
+        assert_eq!(data.profile.uuid, player.user.uuid);
+        assert_eq!(data.games.len(), 1);
+        assert_eq!(data.games[0].uuid, *game.uuid.as_ref());
+        assert_eq!(data.games[0].winner, Some(player.user.uuid));
+
+        let mut res = send_export(
+            &service,
+            "user/export/download?gzip=true",
+            Method::GET,
+            &player.jwt,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/gzip"
+        );
+        let compressed = res.take_bytes(None).await.unwrap();
+        let mut decompressed = String::new();
+        flate2::read::GzDecoder::new(compressed.as_ref())
+            .read_to_string(&mut decompressed)
+            .expect("The export is gzip compressed");
+        let compressed_data: UserDataExportSchema = serde_json::from_str(&decompressed).unwrap();
+        assert_eq!(compressed_data.profile.uuid, player.user.uuid);
+    }
+
+    #[tokio::test]
+    async fn export_in_progress() {
+        let service = get_service().await.expect("Failed to get service");
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = create_signed_in_player(&conn, "exporting_player").await;
+
+        // A pending export, e.g. a large one
+        db_utils::create_data_export(&conn, player.user.uuid)
+            .await
+            .unwrap();
+        let mut res = send_export(&service, "user/export", Method::POST, &player.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::CONFLICT), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "data_export_in_progress");
+
+        let mut res = send_export(&service, "user/export/download", Method::GET, &player.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::CONFLICT), "{res:?}");
+        assert_eq!(error_code(&mut res).await, "data_export_not_ready");
+    }
+}
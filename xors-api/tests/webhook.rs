@@ -0,0 +1,446 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+include!("mod.rs");
+
+use std::sync::Arc;
+
+use salvo::conn::Acceptor;
+use sea_orm::{ActiveModelTrait, Set};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use xors_api::api::{
+    jwt::TokenLifetimes,
+    webhook::{WebhookPolicy, WebhookSender, MAX_WEBHOOK_ATTEMPTS},
+    ServiceConfig,
+};
+
+/// The webhooks policy of the tests, the receivers are local and the retries are fast.
+const TEST_WEBHOOK_POLICY: WebhookPolicy = WebhookPolicy {
+    retry_delay: std::time::Duration::from_millis(100),
+    allow_local_endpoints: true,
+};
+
+/// Returns the service of the webhooks tests, with the installed webhooks sender.
+async fn get_webhook_service() -> Service {
+    // Another test may have installed it already
+    let _ = WebhookSender::new(TEST_WEBHOOK_POLICY)
+        .expect("Failed to create the webhooks sender")
+        .install();
+    xors_api::api::service(
+        get_connection().await.unwrap(),
+        get_jwt_keys(),
+        ServiceConfig {
+            webhook_policy: TEST_WEBHOOK_POLICY,
+            ..test_service_config()
+        },
+    )
+    .0
+}
+
+/// A request received by the local webhook endpoint.
+struct ReceivedRequest {
+    event: String,
+    delivery: String,
+    signature: String,
+    body: Vec<u8>,
+}
+
+/// Records the received requests, the `/fail` path responds with `500`.
+#[handler]
+async fn receive(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let header = |name: &str| {
+        req.header::<String>(name)
+            .expect("The webhook requests have the XORS headers")
+    };
+    let received = ReceivedRequest {
+        event: header("X-Xors-Event"),
+        delivery: header("X-Xors-Delivery"),
+        signature: header("X-Xors-Signature"),
+        body: req.payload().await.unwrap().to_vec(),
+    };
+    depot
+        .obtain::<Arc<UnboundedSender<ReceivedRequest>>>()
+        .unwrap()
+        .send(received)
+        .ok();
+    if req.uri().path() == "/fail" {
+        res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
+
+/// Serves a local webhook endpoint, returns its url and the received requests.
+async fn serve_receiver() -> (String, UnboundedReceiver<ReceivedRequest>) {
+    let acceptor = salvo::conn::TcpListener::new("127.0.0.1:0").bind().await;
+    let addr = acceptor.holdings()[0]
+        .local_addr
+        .clone()
+        .into_std()
+        .expect("The address is a socket address");
+    let (tx, rx) = mpsc::unbounded_channel();
+    let router = Router::new()
+        .hoop(affix::inject(Arc::new(tx)))
+        .push(Router::with_path("<**>").post(receive));
+    tokio::spawn(Server::new(acceptor).serve(router));
+    (format!("http://{addr}"), rx)
+}
+
+/// Creates a new user with the role and returns its jwt.
+async fn new_user(conn: &sea_orm::DatabaseConnection, username: &str, role: UserRole) -> String {
+    let user = db_utils::create_user(
+        conn,
+        NewUserSchema {
+            username: username.to_owned(),
+            first_name: "Hooked".to_owned(),
+            password: "fdkDFLKJL4859#$&".to_owned(),
+            ..Default::default()
+        },
+    )
+    .await
+    .expect("Failed to create user");
+    let mut active_user = db_utils::get_user(conn, user.uuid).await.unwrap();
+    active_user.role = Set(role.to_string());
+    active_user.save(conn).await.unwrap();
+
+    // The tests send several requests with the same token
+    let lifetimes = TokenLifetimes {
+        jwt: Duration::seconds(30),
+        refresh: Duration::seconds(60),
+        short_refresh: Duration::seconds(50),
+        refresh_active_after: Duration::seconds(40),
+        refresh_grace_period: Duration::zero(),
+    };
+    db_utils::signin_user(
+        conn,
+        UserSchema { role, ..user },
+        &get_jwt_keys(),
+        &lifetimes,
+        true,
+    )
+    .await
+    .expect("Failed to signin user")
+    .jwt
+}
+
+/// Returns the authorization header of the jwt.
+fn auth_header(jwt: &str) -> Vec<(HeaderName, HeaderValue)> {
+    vec![(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {jwt}")).unwrap(),
+    )]
+}
+
+async fn error_code(res: &mut Response) -> String {
+    res.take_json::<serde_json::Value>().await.unwrap()["code"]
+        .as_str()
+        .unwrap()
+        .to_owned()
+}
+
+async fn create_webhook(service: &Service, jwt: &str, url: &str) -> WebhookSchema {
+    let mut res = send(
+        service,
+        "admin/webhooks",
+        Method::POST,
+        Some(&NewWebhookSchema::new(
+            url.to_owned(),
+            vec![WebhookEvent::UserCreated],
+        )),
+        auth_header(jwt),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    res.take_json().await.unwrap()
+}
+
+/// Creates a guest user, returns its uuid. It sends the `user.created` event.
+async fn create_guest(service: &Service) -> Uuid {
+    let mut res = send::<()>(service, "auth/guest", Method::POST, None, vec![]).await;
+    assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    res.take_json::<UserSigninSchema>().await.unwrap().user.uuid
+}
+
+/// Waits for the delivery of the user, the other tests create users too.
+async fn wait_delivery(
+    service: &Service,
+    jwt: &str,
+    webhook_uuid: Uuid,
+    user_uuid: Uuid,
+    done: impl Fn(&WebhookDeliverySchema) -> bool,
+) -> WebhookDeliverySchema {
+    for _ in 0..100 {
+        let mut res = send::<()>(
+            service,
+            &format!("admin/webhooks/{webhook_uuid}/deliveries"),
+            Method::GET,
+            None,
+            auth_header(jwt),
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        let delivery = res
+            .take_json::<Vec<WebhookDeliverySchema>>()
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|delivery| delivery.payload["data"]["uuid"] == user_uuid.to_string());
+        if let Some(delivery) = delivery.filter(&done) {
+            return delivery;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("The delivery of the user {user_uuid} is not done");
+}
+
+#[tokio::test]
+async fn signed_delivery() {
+    let service = get_webhook_service().await;
+    let conn = get_connection().await.unwrap();
+    let jwt = new_user(&conn, "webhook_admin1", UserRole::Admin).await;
+    let (url, mut received) = serve_receiver().await;
+
+    let webhook = create_webhook(&service, &jwt, &format!("{url}/ok")).await;
+    assert_eq!(webhook.events, vec![WebhookEvent::UserCreated]);
+    let secret = webhook.secret.expect("The secret is returned on creation");
+    let guest_uuid = create_guest(&service).await;
+
+    let request = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+        loop {
+            let request = received.recv().await.expect("The receiver is open");
+            let payload: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            if payload["data"]["uuid"] == guest_uuid.to_string() {
+                return request;
+            }
+        }
+    })
+    .await
+    .expect("The webhook is not delivered");
+    assert_eq!(request.event, "user.created");
+    assert_eq!(
+        request.signature,
+        format!(
+            "sha256={}",
+            xors_api::utils::webhook_signature(&secret, &request.body)
+        )
+    );
+    let payload: WebhookPayload = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(payload.id.to_string(), request.delivery);
+    assert_eq!(payload.event, WebhookEvent::UserCreated);
+
+    let delivery = wait_delivery(&service, &jwt, webhook.uuid, guest_uuid, |delivery| {
+        delivery.delivered_at.is_some()
+    })
+    .await;
+    assert_eq!(delivery.uuid, payload.id);
+    assert_eq!(delivery.attempts, 1);
+    assert_eq!(delivery.status_code, Some(200));
+    assert_eq!(delivery.error, None);
+
+    // The secret is not listed
+    let mut res = send::<()>(
+        &service,
+        "admin/webhooks",
+        Method::GET,
+        None,
+        auth_header(&jwt),
+    )
+    .await;
+    let webhooks = res.take_json::<Vec<WebhookSchema>>().await.unwrap();
+    let listed = webhooks
+        .iter()
+        .find(|listed| listed.uuid == webhook.uuid)
+        .expect("The webhook is listed");
+    assert_eq!(listed.secret, None);
+
+    let res = send::<()>(
+        &service,
+        &format!("admin/webhooks/{}", webhook.uuid),
+        Method::DELETE,
+        None,
+        auth_header(&jwt),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+    let mut res = send::<()>(
+        &service,
+        &format!("admin/webhooks/{}/deliveries", webhook.uuid),
+        Method::GET,
+        None,
+        auth_header(&jwt),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::NOT_FOUND), "{res:?}");
+    assert_eq!(error_code(&mut res).await, "webhook_not_found");
+}
+
+#[tokio::test]
+async fn failed_delivery_retries() {
+    let service = get_webhook_service().await;
+    let conn = get_connection().await.unwrap();
+    let jwt = new_user(&conn, "webhook_admin2", UserRole::Admin).await;
+    let (url, _received) = serve_receiver().await;
+
+    let webhook = create_webhook(&service, &jwt, &format!("{url}/fail")).await;
+    let guest_uuid = create_guest(&service).await;
+
+    let delivery = wait_delivery(&service, &jwt, webhook.uuid, guest_uuid, |delivery| {
+        delivery.attempts == MAX_WEBHOOK_ATTEMPTS
+    })
+    .await;
+    assert_eq!(delivery.status_code, Some(500));
+    assert_eq!(delivery.delivered_at, None);
+
+    // No more attempts after the last one
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    let delivery = wait_delivery(&service, &jwt, webhook.uuid, guest_uuid, |_| true).await;
+    assert_eq!(delivery.attempts, MAX_WEBHOOK_ATTEMPTS);
+
+    send::<()>(
+        &service,
+        &format!("admin/webhooks/{}", webhook.uuid),
+        Method::DELETE,
+        None,
+        auth_header(&jwt),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn non_public_deliveries() {
+    let (url, mut received) = serve_receiver().await;
+    let sender = WebhookSender::new(WebhookPolicy::default()).expect("Failed to create the sender");
+
+    let port = url.rsplit(':').next().unwrap();
+    for url in [
+        format!("https://localhost:{port}/ok"),
+        format!("https://127.0.0.1:{port}/ok"),
+        format!("https://[::ffff:127.0.0.1]:{port}/ok"),
+    ] {
+        let res = sender
+            .send(
+                &url,
+                "secret",
+                WebhookEvent::UserCreated,
+                Uuid::new_v4(),
+                "{}",
+            )
+            .await;
+        assert!(
+            res.as_ref()
+                .is_err_and(|err| err.contains("non-public address")),
+            "The delivery to {url} should be rejected: {res:?}"
+        );
+    }
+    let res = sender
+        .send(
+            &format!("{url}/ok"),
+            "secret",
+            WebhookEvent::UserCreated,
+            Uuid::new_v4(),
+            "{}",
+        )
+        .await;
+    assert!(res.is_err(), "The HTTP URLs should be rejected: {res:?}");
+    assert!(
+        received.try_recv().is_err(),
+        "The receiver should not receive any request"
+    );
+}
+
+#[tokio::test]
+async fn invalid_webhooks() {
+    let service = get_service().await.unwrap();
+    let conn = get_connection().await.unwrap();
+    let admin_jwt = new_user(&conn, "webhook_admin3", UserRole::Admin).await;
+    let moderator_jwt = new_user(&conn, "webhook_moderator", UserRole::Moderator).await;
+
+    for url in [
+        "ftp://example.com",
+        "example.com/hook",
+        "https://",
+        "https://exa mple.com",
+        "http://93.184.215.14/hook",
+        "https://localhost/hook",
+        "https://127.0.0.1/hook",
+        "https://10.0.0.1/hook",
+        "https://192.168.1.1/hook",
+        "https://169.254.169.254/latest/meta-data",
+        "https://0.0.0.0/hook",
+        "https://[::1]/hook",
+        "https://[fe80::1]/hook",
+        "https://[::ffff:127.0.0.1]/hook",
+    ] {
+        let mut res = send(
+            &service,
+            "admin/webhooks",
+            Method::POST,
+            Some(&NewWebhookSchema::new(
+                url.to_owned(),
+                vec![WebhookEvent::GameFinished],
+            )),
+            auth_header(&admin_jwt),
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST), "{url}");
+        assert_eq!(error_code(&mut res).await, "invalid_webhook_url");
+    }
+    let mut res = send(
+        &service,
+        "admin/webhooks",
+        Method::POST,
+        Some(&NewWebhookSchema::new(
+            format!("https://example.com/{}", "a".repeat(2048)),
+            vec![WebhookEvent::GameFinished],
+        )),
+        auth_header(&admin_jwt),
+    )
+    .await;
+    assert_eq!(error_code(&mut res).await, "invalid_webhook_url");
+    let mut res = send(
+        &service,
+        "admin/webhooks",
+        Method::POST,
+        Some(&NewWebhookSchema::new(
+            "https://example.com/xors".to_owned(),
+            vec![],
+        )),
+        auth_header(&admin_jwt),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST));
+    assert_eq!(error_code(&mut res).await, "invalid_webhook_events");
+
+    let mut res = send::<()>(
+        &service,
+        &format!("admin/webhooks/{}", Uuid::new_v4()),
+        Method::DELETE,
+        None,
+        auth_header(&admin_jwt),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::NOT_FOUND));
+    assert_eq!(error_code(&mut res).await, "webhook_not_found");
+
+    // Only the admins manage the webhooks
+    let res = send::<()>(
+        &service,
+        "admin/webhooks",
+        Method::GET,
+        None,
+        auth_header(&moderator_jwt),
+    )
+    .await;
+    assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN));
+}
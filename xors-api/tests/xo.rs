@@ -0,0 +1,2063 @@
+// A RESTful tic tac toy API for XORS project
+// Copyright (C) 2024  Awiteb <Awiteb@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+include!("mod.rs");
+
+use futures_util::{SinkExt, StreamExt};
+use salvo::conn::Acceptor;
+use tokio_tungstenite::{
+    tungstenite::{client::IntoClientRequest, Message as WsMessage},
+    MaybeTlsStream, WebSocketStream,
+};
+use xors_api::api::{
+    xo::{
+        DuplicateConnectionPolicy, GameClock, MoveTimeoutPolicy, TokenExpiryPolicy,
+        BANNED_CLOSE_CODE, REPLACED_CLOSE_CODE, TOKEN_EXPIRED_CLOSE_CODE,
+    },
+    ServiceConfig,
+};
+
+type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// The search queue and the resumable games are shared by all the servers of the tests, the tests that search
+/// for a game or resume the games take this lock.
+static SEARCH_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Serve the API on a random port with the given token expiry policy, returns the XO websocket url.
+async fn serve(token_expiry_policy: TokenExpiryPolicy) -> String {
+    serve_with(ServiceConfig {
+        token_expiry_policy,
+        ..test_service_config()
+    })
+    .await
+}
+
+/// Serve the API on a random port with the given configuration, returns the XO websocket url.
+async fn serve_with(config: ServiceConfig) -> String {
+    std::env::set_var("XORS_API_TEST", "true");
+
+    let acceptor = salvo::conn::TcpListener::new("127.0.0.1:0").bind().await;
+    let addr = acceptor.holdings()[0]
+        .local_addr
+        .clone()
+        .into_std()
+        .expect("The address is a socket address");
+    let service = xors_api::api::service(
+        get_connection().await.expect("Failed to get connection"),
+        get_jwt_keys(),
+        config,
+    )
+    .0;
+    tokio::spawn(Server::new(acceptor).serve(service));
+
+    format!("ws://{addr}/xo")
+}
+
+/// Creates a new user and returns its signin data.
+async fn new_player(username: &str) -> UserSigninSchema {
+    let conn = get_connection().await.expect("Failed to get connection");
+    create_signed_in_player(&conn, username).await
+}
+
+/// Creates a new user and returns its signin data, its token outlives the game clocks of the tests.
+async fn new_long_lived_player(username: &str) -> UserSigninSchema {
+    let lifetimes = xors_api::api::jwt::TokenLifetimes {
+        jwt: Duration::seconds(30),
+        refresh: Duration::seconds(60),
+        short_refresh: Duration::seconds(50),
+        refresh_active_after: Duration::seconds(40),
+        refresh_grace_period: Duration::zero(),
+    };
+    db_utils::signin_user(
+        &get_connection().await.expect("Failed to get connection"),
+        new_player(username).await.user,
+        &get_jwt_keys(),
+        &lifetimes,
+        true,
+    )
+    .await
+    .unwrap()
+}
+
+/// Connects to the XO websocket with the given jwt.
+async fn connect(url: &str, jwt: &str) -> WsStream {
+    connect_from(url, jwt, None)
+        .await
+        .expect("Failed to connect to the XO websocket")
+}
+
+/// Connects to the XO websocket with the given jwt, from the given origin if any.
+async fn connect_from(
+    url: &str,
+    jwt: &str,
+    origin: Option<&str>,
+) -> Result<WsStream, tokio_tungstenite::tungstenite::Error> {
+    let mut req = url.into_client_request().expect("Valid websocket url");
+    req.headers_mut().insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {jwt}")).unwrap(),
+    );
+    if let Some(origin) = origin {
+        req.headers_mut()
+            .insert(header::ORIGIN, HeaderValue::from_str(origin).unwrap());
+    }
+    tokio_tungstenite::connect_async(req)
+        .await
+        .map(|(ws, _)| ws)
+}
+
+/// Returns the next message, the test will fail if there is no message in 5 seconds.
+async fn next_message(ws: &mut WsStream) -> WsMessage {
+    tokio::time::timeout(std::time::Duration::from_secs(5), ws.next())
+        .await
+        .expect("No message received in 5 seconds")
+        .expect("The websocket is closed")
+        .expect("Failed to receive a message")
+}
+
+/// Returns the next server event.
+async fn next_event(ws: &mut WsStream) -> serde_json::Value {
+    let msg = next_message(ws).await;
+    serde_json::from_str(msg.to_text().expect("The message should be text"))
+        .expect("The message should be a json event")
+}
+
+/// Sends the client event.
+async fn send_event(ws: &mut WsStream, event: serde_json::Value) {
+    ws.send(WsMessage::text(event.to_string())).await.unwrap();
+}
+
+/// Sends the `search` event, then waits for the server to handle it.
+async fn search(ws: &mut WsStream) {
+    send_event(ws, serde_json::json!({"event": "search"})).await;
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+}
+
+/// Returns the next `game_over` event, skipping the other events.
+async fn next_game_over(ws: &mut WsStream) -> serde_json::Value {
+    loop {
+        let event = next_event(ws).await;
+        if event["event"] == "game_over" {
+            return event;
+        }
+    }
+}
+
+/// Asserts that the next message is the token expired close frame.
+async fn assert_token_expired_close(ws: &mut WsStream) {
+    match next_message(ws).await {
+        WsMessage::Close(Some(frame)) => {
+            assert_eq!(u16::from(frame.code), TOKEN_EXPIRED_CLOSE_CODE)
+        }
+        msg => panic!("The connection should be closed with the token expired code {msg:?}"),
+    }
+}
+
+#[cfg(test)]
+mod token_expiry {
+    use super::*;
+
+    #[tokio::test]
+    async fn close_on_expiry() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let player = new_player("token_expiry_close").await;
+
+        let mut ws = connect(&url, &player.jwt).await;
+        assert_token_expired_close(&mut ws).await;
+    }
+
+    #[tokio::test]
+    async fn unregister_on_expiry() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = new_player("token_expiry_unregister").await;
+        let friend = new_long_lived_player("token_expiry_unregister_friend").await;
+        db_utils::send_friend_request(&conn, player.user.uuid, friend.user.uuid)
+            .await
+            .unwrap();
+        db_utils::accept_friend_request(&conn, friend.user.uuid, player.user.uuid)
+            .await
+            .unwrap();
+
+        let mut friend_ws = connect(&url, &friend.jwt).await;
+        // Not read, so the client never answers the close frame
+        let _player_ws = connect(&url, &player.jwt).await;
+        for presence in ["online", "offline"] {
+            let event = next_event(&mut friend_ws).await;
+            assert_eq!(event["event"], "friend_presence", "{event}");
+            assert_eq!(event["data"]["friend"], player.user.uuid.to_string());
+            assert_eq!(
+                event["data"]["presence"], presence,
+                "The server should unregister the player when its token expires"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn reauthenticate_disabled() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let player = new_player("token_expiry_reauth_disabled").await;
+
+        let mut ws = connect(&url, &player.jwt).await;
+        ws.send(WsMessage::text(
+            serde_json::json!({"event": "reauthenticate", "data": {"token": player.jwt}})
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+
+        let event = next_event(&mut ws).await;
+        assert_eq!(event["event"], "error");
+        assert_eq!(event["data"], "reauthentication_disabled");
+        assert_token_expired_close(&mut ws).await;
+    }
+
+    #[tokio::test]
+    async fn reauthenticate_before_expiry() {
+        let url = serve(TokenExpiryPolicy::Reauthenticate).await;
+        let player = new_player("token_expiry_reauth").await;
+
+        let mut ws = connect(&url, &player.jwt).await;
+        let event = next_event(&mut ws).await;
+        assert_eq!(event["event"], "token_expiring");
+        let expires_at = event["data"]["expires_at"].as_i64().unwrap();
+
+        // The warning is sent at least one second after the token creation, so the new token expires later
+        let conn = get_connection().await.expect("Failed to get connection");
+        let refreshed = db_utils::signin_user(
+            &conn,
+            player.user.clone(),
+            &get_jwt_keys(),
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .expect("Failed to signin user");
+        ws.send(WsMessage::text(
+            serde_json::json!({"event": "reauthenticate", "data": {"token": refreshed.jwt}})
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+
+        let event = next_event(&mut ws).await;
+        assert_eq!(event["event"], "reauthenticated");
+        let new_expires_at = event["data"]["new_expires_at"].as_i64().unwrap();
+        assert!(new_expires_at > expires_at);
+
+        // The connection still alive after the old token expiry, and warned again before the new one
+        let event = next_event(&mut ws).await;
+        assert_eq!(event["event"], "token_expiring");
+        assert_eq!(
+            event["data"]["expires_at"].as_i64().unwrap(),
+            new_expires_at
+        );
+        assert_token_expired_close(&mut ws).await;
+    }
+
+    #[tokio::test]
+    async fn reauthenticate_with_other_player_token() {
+        let url = serve(TokenExpiryPolicy::Reauthenticate).await;
+        let player = new_player("token_expiry_reauth_player").await;
+        let other_player = new_player("token_expiry_reauth_other").await;
+
+        let mut ws = connect(&url, &player.jwt).await;
+        ws.send(WsMessage::text(
+            serde_json::json!({"event": "reauthenticate", "data": {"token": other_player.jwt}})
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+
+        let mut event = next_event(&mut ws).await;
+        // The warning may be sent before the response
+        if event["event"] == "token_expiring" {
+            event = next_event(&mut ws).await;
+        }
+        assert_eq!(event["event"], "error");
+        assert_eq!(event["data"], "invalid_token");
+    }
+}
+
+#[cfg(test)]
+mod play {
+    use super::*;
+
+    async fn assert_move_error(ws: &mut WsStream, code: &str) {
+        let event = next_event(ws).await;
+        assert_eq!(event["event"], "error");
+        assert_eq!(
+            event["data"], code,
+            "The error frame should have the move error code"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_moves() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let x_player = new_player("rejected_moves_x_player").await;
+        let o_player = new_player("rejected_moves_o_player").await;
+
+        let mut x_ws = connect(&url, &x_player.jwt).await;
+        let mut o_ws = connect(&url, &o_player.jwt).await;
+        send_event(&mut x_ws, serde_json::json!({"event": "search"})).await;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        send_event(&mut o_ws, serde_json::json!({"event": "search"})).await;
+
+        for expected in [
+            "queued",
+            "match_found",
+            "game_found",
+            "round_start",
+            "your_turn",
+        ] {
+            assert_eq!(next_event(&mut x_ws).await["event"], expected);
+        }
+        for expected in ["match_found", "game_found", "round_start"] {
+            assert_eq!(next_event(&mut o_ws).await["event"], expected);
+        }
+
+        let play = |place: u8| serde_json::json!({"event": "play", "data": {"place": place}});
+        send_event(&mut o_ws, play(4)).await;
+        assert_move_error(&mut o_ws, "not_your_turn").await;
+        send_event(&mut x_ws, play(9)).await;
+        assert_move_error(&mut x_ws, "out_of_bounds").await;
+        send_event(
+            &mut x_ws,
+            serde_json::json!({"event": "play", "data": {"place": 0, "seen_moves": 1}}),
+        )
+        .await;
+        assert_move_error(&mut x_ws, "stale_state").await;
+
+        send_event(&mut x_ws, play(0)).await;
+        assert_eq!(next_event(&mut o_ws).await["event"], "play");
+        assert_eq!(next_event(&mut o_ws).await["event"], "your_turn");
+        send_event(&mut o_ws, play(0)).await;
+        assert_move_error(&mut o_ws, "cell_occupied").await;
+    }
+}
+
+#[cfg(test)]
+mod unique_pair_games {
+    use super::*;
+
+    #[tokio::test]
+    async fn already_playing_each_other() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let url = serve_with(ServiceConfig {
+            unique_pair_games: true,
+            ..test_service_config()
+        })
+        .await;
+        let conn = get_connection().await.expect("Failed to get connection");
+        let x_player = new_player("unique_pair_x_player").await;
+        let o_player = new_player("unique_pair_o_player").await;
+        let game =
+            db_utils::create_game(&conn, x_player.user.uuid, o_player.user.uuid, 10, false, 0)
+                .await
+                .expect("Failed to create game");
+
+        let mut x_ws = connect(&url, &x_player.jwt).await;
+        let mut o_ws = connect(&url, &o_player.jwt).await;
+        for ws in [&mut x_ws, &mut o_ws] {
+            ws.send(WsMessage::text(
+                serde_json::json!({"event": "search"}).to_string(),
+            ))
+            .await
+            .unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        let event = next_event(&mut o_ws).await;
+        assert_eq!(event["event"], "error");
+        assert_eq!(
+            event["data"],
+            serde_json::json!({"already_playing_each_other": {"game_id": game.uuid.as_ref()}}),
+            "The second game between the players should be rejected"
+        );
+
+        db_utils::end_game(&conn, game.uuid.as_ref(), None, &GameOverReason::Draw)
+            .await
+            .expect("Failed to end game");
+    }
+}
+
+#[cfg(test)]
+mod daily_games_limit {
+    use super::*;
+
+    #[tokio::test]
+    async fn limit_reached() {
+        let url = serve_with(ServiceConfig {
+            daily_games_limit: Some(2),
+            ..test_service_config()
+        })
+        .await;
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = new_player("daily_limit_player").await;
+        let opponent = new_player("daily_limit_opponent").await;
+
+        let mut games = Vec::new();
+        for _ in 0..2 {
+            let game =
+                db_utils::create_game(&conn, player.user.uuid, opponent.user.uuid, 10, false, 0)
+                    .await
+                    .expect("Failed to create game");
+            db_utils::end_game(&conn, game.uuid.as_ref(), None, &GameOverReason::Draw)
+                .await
+                .expect("Failed to end game");
+            games.push(*game.uuid.as_ref());
+        }
+
+        let mut ws = connect(&url, &player.jwt).await;
+        ws.send(WsMessage::text(
+            serde_json::json!({"event": "search"}).to_string(),
+        ))
+        .await
+        .unwrap();
+        let event = next_event(&mut ws).await;
+        assert_eq!(event["event"], "error");
+        assert_eq!(
+            event["data"], "daily_limit_reached",
+            "The third game in the window should be blocked"
+        );
+
+        // Move the games out of the window
+        for game_uuid in games {
+            let mut game = sea_orm::IntoActiveModel::into_active_model(
+                db_utils::get_game::<true>(&conn, &game_uuid)
+                    .await
+                    .expect("Failed to get game"),
+            );
+            game.created_at = sea_orm::Set(chrono::Utc::now().naive_utc() - Duration::hours(25));
+            sea_orm::ActiveModelTrait::save(game, &conn)
+                .await
+                .expect("Failed to update the game");
+        }
+        assert_eq!(
+            db_utils::count_user_games_since(
+                &conn,
+                player.user.uuid,
+                chrono::Utc::now().naive_utc() - Duration::hours(24),
+            )
+            .await
+            .expect("Failed to count games"),
+            0,
+            "The games out of the window should not be counted"
+        );
+    }
+}
+
+#[cfg(test)]
+mod chat_limits {
+    use super::*;
+    use xors_api::api::xo::{ChatLimiter, CHAT_MUTE_AFTER_VIOLATIONS};
+
+    #[test]
+    fn rate_limit() {
+        let limiter = ChatLimiter::new(2, 100);
+        let (game, player) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert!(limiter.check(game, player, 10).is_ok());
+        assert!(limiter.check(game, player, 10).is_ok());
+        assert!(matches!(
+            limiter.check(game, player, 10),
+            Err(ErrorData::ChatRateLimited)
+        ));
+        assert!(
+            limiter.check(Uuid::new_v4(), player, 10).is_ok(),
+            "The other games should not be affected"
+        );
+        assert!(
+            limiter.check(game, Uuid::new_v4(), 10).is_ok(),
+            "The other player should not be affected"
+        );
+    }
+
+    #[test]
+    fn size_limit() {
+        let limiter = ChatLimiter::new(10, 100);
+        let (game, player) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert!(limiter.check(game, player, 100).is_ok());
+        assert!(matches!(
+            limiter.check(game, player, 101),
+            Err(ErrorData::ChatMessageTooLarge)
+        ));
+    }
+
+    #[test]
+    fn mute_after_violations() {
+        let limiter = ChatLimiter::new(10, 100);
+        let (game, player) = (Uuid::new_v4(), Uuid::new_v4());
+
+        for _ in 0..CHAT_MUTE_AFTER_VIOLATIONS {
+            assert!(matches!(
+                limiter.check(game, player, 101),
+                Err(ErrorData::ChatMessageTooLarge)
+            ));
+        }
+        assert!(
+            matches!(limiter.check(game, player, 10), Err(ErrorData::ChatMuted)),
+            "The player should be muted after the violations"
+        );
+        assert!(
+            limiter.check(Uuid::new_v4(), player, 10).is_ok(),
+            "The player should be muted only in the game"
+        );
+    }
+}
+
+#[cfg(test)]
+mod blocks {
+    use super::*;
+
+    #[tokio::test]
+    async fn matchmaking_skips_blocked_pairs() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let conn = get_connection().await.expect("Failed to get connection");
+        let blocker = new_player("matchmaking_blocker").await;
+        let blocked = new_player("matchmaking_blocked").await;
+        let other = new_player("matchmaking_other").await;
+        db_utils::block_user(&conn, blocker.user.uuid, blocked.user.uuid)
+            .await
+            .expect("Failed to block user");
+
+        let mut blocker_ws = connect(&url, &blocker.jwt).await;
+        let mut blocked_ws = connect(&url, &blocked.jwt).await;
+        search(&mut blocked_ws).await;
+        search(&mut blocker_ws).await;
+        for ws in [&mut blocked_ws, &mut blocker_ws] {
+            assert_eq!(next_event(ws).await["event"], "queued");
+        }
+
+        // The blocked user is still in the search queue
+        search(&mut blocked_ws).await;
+        let event = next_event(&mut blocked_ws).await;
+        assert_eq!(event["event"], "error");
+        assert_eq!(
+            event["data"], "already_in_search",
+            "The blocked pair should not be matched"
+        );
+
+        let mut other_ws = connect(&url, &other.jwt).await;
+        search(&mut other_ws).await;
+        let event = next_event(&mut other_ws).await;
+        assert_eq!(event["event"], "match_found");
+        assert_eq!(event["data"]["opponent"], blocked.user.uuid.to_string());
+        let event = next_event(&mut other_ws).await;
+        assert_eq!(event["event"], "game_found");
+        assert!(
+            [&event["data"]["x_player"], &event["data"]["o_player"]]
+                .contains(&&serde_json::json!(blocked.user.uuid)),
+            "The blocked user should be matched with other users {event}"
+        );
+        assert_eq!(next_event(&mut blocked_ws).await["event"], "match_found");
+        assert_eq!(
+            next_event(&mut blocked_ws).await["event"],
+            "game_found",
+            "The blocked user should be matched with other users"
+        );
+
+        other_ws.close(None).await.unwrap();
+        blocked_ws.close(None).await.unwrap();
+        blocker_ws.close(None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+}
+
+#[cfg(test)]
+mod allowed_origins {
+    use super::*;
+    use tokio_tungstenite::tungstenite::Error as WsError;
+
+    #[tokio::test]
+    async fn disallowed_origin() {
+        let url = serve_with(ServiceConfig {
+            ws_allowed_origins: Some(vec!["https://xors.example.com".to_owned()]),
+            ..test_service_config()
+        })
+        .await;
+        let player = new_player("allowed_origins_player").await;
+
+        match connect_from(&url, &player.jwt, Some("https://evil.example.com")).await {
+            Err(WsError::Http(res)) => assert_eq!(res.status(), StatusCode::FORBIDDEN),
+            res => panic!("The disallowed origin should be rejected at the handshake {res:?}"),
+        }
+        connect_from(&url, &player.jwt, Some("https://xors.example.com"))
+            .await
+            .expect("The allowed origin should connect");
+        connect_from(&url, &player.jwt, None)
+            .await
+            .expect("The handshake without origin should connect");
+    }
+}
+
+#[cfg(test)]
+mod duplicate_connections {
+    use super::*;
+
+    #[tokio::test]
+    async fn allow_multiple() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let x_player = new_player("allow_multiple_x_player").await;
+        let o_player = new_player("allow_multiple_o_player").await;
+
+        let mut x_ws = connect(&url, &x_player.jwt).await;
+        let mut x_second_ws = connect(&url, &x_player.jwt).await;
+        let mut o_ws = connect(&url, &o_player.jwt).await;
+        send_event(&mut x_ws, serde_json::json!({"event": "search"})).await;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        send_event(&mut o_ws, serde_json::json!({"event": "search"})).await;
+
+        for ws in [&mut x_ws, &mut x_second_ws] {
+            for expected in [
+                "queued",
+                "match_found",
+                "game_found",
+                "round_start",
+                "your_turn",
+            ] {
+                assert_eq!(
+                    next_event(ws).await["event"],
+                    expected,
+                    "The events should be sent to all the player's connections"
+                );
+            }
+        }
+        for expected in ["match_found", "game_found", "round_start"] {
+            assert_eq!(next_event(&mut o_ws).await["event"], expected);
+        }
+
+        // The first connection is closed, the game continues with the second one
+        x_ws.close(None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        send_event(
+            &mut x_second_ws,
+            serde_json::json!({"event": "play", "data": {"place": 4}}),
+        )
+        .await;
+        let event = next_event(&mut o_ws).await;
+        assert_eq!(event["event"], "play", "The game should continue {event}");
+        assert_eq!(
+            event["data"]["place"], 4,
+            "The move should be attributed to the player {event}"
+        );
+        assert_eq!(next_event(&mut o_ws).await["event"], "your_turn");
+
+        // The last connection is closed, the player is disconnected
+        x_second_ws.close(None).await.unwrap();
+        let event = next_event(&mut o_ws).await;
+        assert_eq!(event["event"], "game_over");
+        assert_eq!(event["data"]["reason"], "player_disconnected");
+    }
+
+    #[tokio::test]
+    async fn single() {
+        let url = serve_with(ServiceConfig {
+            duplicate_connection_policy: DuplicateConnectionPolicy::Single,
+            ..test_service_config()
+        })
+        .await;
+        let player = new_player("single_connection_player").await;
+
+        let mut old_ws = connect(&url, &player.jwt).await;
+        let mut new_ws = connect(&url, &player.jwt).await;
+        match next_message(&mut old_ws).await {
+            WsMessage::Close(Some(frame)) => {
+                assert_eq!(u16::from(frame.code), REPLACED_CLOSE_CODE)
+            }
+            msg => panic!("The older connection should be closed with the replaced code {msg:?}"),
+        }
+
+        send_event(
+            &mut new_ws,
+            serde_json::json!({"event": "play", "data": {"place": 4}}),
+        )
+        .await;
+        let event = next_event(&mut new_ws).await;
+        assert_eq!(event["event"], "error");
+        assert_eq!(
+            event["data"], "not_in_game",
+            "The newer connection should stay open"
+        );
+    }
+}
+
+#[cfg(test)]
+mod restart {
+    use std::str::FromStr;
+
+    use super::*;
+
+    /// Creates an in-progress game in the second round, like a game of the previous server run.
+    async fn create_previous_run_game(
+        conn: &sea_orm::DatabaseConnection,
+        x_player: &UserSigninSchema,
+        o_player: &UserSigninSchema,
+    ) -> Uuid {
+        let mut game =
+            db_utils::create_game(conn, x_player.user.uuid, o_player.user.uuid, 10, false, 0)
+                .await
+                .expect("Failed to create game");
+        game.round = sea_orm::Set(2);
+        game.rounds_result = sea_orm::Set("X XXXOO----:03142".to_owned());
+        game.board = sea_orm::Set("XO-------:01".to_owned());
+        sea_orm::ActiveModelTrait::save(game, conn)
+            .await
+            .expect("Failed to save game")
+            .uuid
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn resume_game() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let conn = get_connection().await.expect("Failed to get connection");
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let x_player = new_long_lived_player("resume_game_x_player").await;
+        let o_player = new_long_lived_player("resume_game_o_player").await;
+        let game_uuid = create_previous_run_game(&conn, &x_player, &o_player).await;
+
+        assert!(
+            xors_api::api::xo::resume_games(
+                conn.clone(),
+                std::time::Duration::from_secs(10),
+                Default::default()
+            )
+            .await
+            .expect("Failed to resume the games")
+                >= 1
+        );
+
+        let mut x_ws = connect(&url, &x_player.jwt).await;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let mut o_ws = connect(&url, &o_player.jwt).await;
+
+        let expected = serde_json::json!({
+            "uuid": game_uuid,
+            "x_player": x_player.user.uuid,
+            "o_player": o_player.user.uuid,
+            "round": 2,
+            "board": Board::from_str("XO-------:01").unwrap(),
+            "rounds_result": RoundsResult::from_str("X XXXOO----:03142").unwrap(),
+        });
+        for ws in [&mut x_ws, &mut o_ws] {
+            let event = next_event(ws).await;
+            assert_eq!(event["event"], "game_resumed");
+            assert_eq!(
+                event["data"], expected,
+                "The resumed game should match the game before the restart"
+            );
+        }
+        assert_eq!(
+            next_event(&mut x_ws).await["event"],
+            "your_turn",
+            "The X player should continue the round"
+        );
+
+        x_ws.send(WsMessage::text(
+            serde_json::json!({"event": "play", "data": {"place": 4}}).to_string(),
+        ))
+        .await
+        .unwrap();
+        let event = next_event(&mut o_ws).await;
+        assert_eq!(event["event"], "play", "The game should continue {event}");
+        assert_eq!(event["data"]["place"], 4);
+        assert_eq!(next_event(&mut o_ws).await["event"], "your_turn");
+
+        // The move is saved after the events are sent
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let game = db_utils::get_game::<false>(&conn, &game_uuid)
+            .await
+            .expect("The game should be in progress");
+        assert_eq!(game.board, "XO--X----:014");
+        assert_eq!(game.round, 2);
+    }
+
+    #[tokio::test]
+    async fn grace_period_expired() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let conn = get_connection().await.expect("Failed to get connection");
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let x_player = new_long_lived_player("grace_period_x_player").await;
+        let o_player = new_long_lived_player("grace_period_o_player").await;
+        let game_uuid = create_previous_run_game(&conn, &x_player, &o_player).await;
+
+        xors_api::api::xo::resume_games(
+            conn.clone(),
+            std::time::Duration::from_secs(1),
+            Default::default(),
+        )
+        .await
+        .expect("Failed to resume the games");
+
+        // Only the O player reconnects. The resumed games include the in-progress games of the other tests,
+        // so their expiry may take longer than the grace period, the tokens outlive it.
+        let mut o_ws = connect(&url, &o_player.jwt).await;
+        let event = next_event(&mut o_ws).await;
+        assert_eq!(event["event"], "game_over");
+        assert_eq!(event["data"]["winner"], o_player.user.uuid.to_string());
+        assert_eq!(event["data"]["reason"], "player_disconnected");
+
+        // The game is ended after the event is sent
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let game = db_utils::get_game::<true>(&conn, &game_uuid)
+            .await
+            .expect("The game should be ended");
+        assert_eq!(game.winner, Some(o_player.user.uuid));
+    }
+
+    #[tokio::test]
+    async fn bot_game_of_deleted_player() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let conn = get_connection().await.expect("Failed to get connection");
+        // The X player is not a user, like a player that is deleted while the game is paused
+        let (deleted_player, bot_uuid) = (Uuid::new_v4(), Uuid::new_v4());
+        let game_uuid = db_utils::create_bot_game(
+            &conn,
+            deleted_player,
+            bot_uuid,
+            10,
+            0,
+            bot_uuid,
+            BotDifficulty::Random,
+        )
+        .await
+        .expect("Failed to create game")
+        .uuid
+        .unwrap();
+
+        xors_api::api::xo::resume_games(
+            conn.clone(),
+            std::time::Duration::from_secs(1),
+            Default::default(),
+        )
+        .await
+        .expect("Failed to resume the games");
+
+        // The resumed games include the in-progress games of the other tests, so their expiry may take longer
+        // than the grace period
+        let mut ended_game = None;
+        for _ in 0..50 {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            if let Ok(game) = db_utils::get_game::<true>(&conn, &game_uuid).await {
+                ended_game = Some(game);
+                break;
+            }
+        }
+        let game = ended_game.expect("The game should be ended");
+        assert_eq!(
+            game.winner,
+            Some(bot_uuid),
+            "The bot should be reconnected on its side"
+        );
+    }
+}
+
+#[cfg(test)]
+mod random_opening {
+    use super::*;
+
+    #[tokio::test]
+    async fn opening_move_played() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let url = serve_with(ServiceConfig {
+            random_opening_moves: 1,
+            ..test_service_config()
+        })
+        .await;
+        let x_player = new_player("opening_move_x_player").await;
+        let o_player = new_player("opening_move_o_player").await;
+
+        let mut x_ws = connect(&url, &x_player.jwt).await;
+        let mut o_ws = connect(&url, &o_player.jwt).await;
+        send_event(&mut x_ws, serde_json::json!({"event": "search"})).await;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        send_event(&mut o_ws, serde_json::json!({"event": "search"})).await;
+
+        assert_eq!(next_event(&mut x_ws).await["event"], "queued");
+        let mut opening_place = None;
+        for ws in [&mut x_ws, &mut o_ws] {
+            assert_eq!(next_event(ws).await["event"], "match_found");
+            assert_eq!(next_event(ws).await["event"], "game_found");
+            let event = next_event(ws).await;
+            assert_eq!(event["event"], "round_start");
+            assert_eq!(
+                event["data"]["opening"].as_array().map(Vec::len),
+                Some(1),
+                "The round should start with the opening move {event}"
+            );
+            opening_place = event["data"]["opening"][0].as_u64();
+        }
+        assert_eq!(
+            next_event(&mut o_ws).await["event"],
+            "your_turn",
+            "The O player should play after the X opening move"
+        );
+
+        // The opening move is played for the X player, it's not its turn
+        send_event(
+            &mut x_ws,
+            serde_json::json!({"event": "play", "data": {"place": (opening_place.unwrap() + 1) % 9}}),
+        )
+        .await;
+        let event = next_event(&mut x_ws).await;
+        assert_eq!(event["event"], "error");
+        assert_eq!(event["data"], "not_your_turn");
+    }
+}
+
+#[cfg(test)]
+mod matchmaking_pause {
+    use super::*;
+
+    /// Sends the pause or resume matchmaking request, returns its status code.
+    async fn matchmake(service: &Service, action: &str, jwt: &str) -> StatusCode {
+        send(
+            service,
+            &format!("game/matchmake/{action}"),
+            Method::POST,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {jwt}")).unwrap(),
+            )],
+        )
+        .await
+        .status_code
+        .unwrap()
+    }
+
+    /// Asserts that the player doesn't receive any message in 200 milliseconds.
+    async fn assert_no_message(ws: &mut WsStream) {
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(200), ws.next())
+                .await
+                .is_err(),
+            "The player should not receive any message"
+        );
+    }
+
+    #[tokio::test]
+    async fn paused_player_is_not_matched() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let service = get_service().await.unwrap();
+        let paused = new_player("matchmaking_paused").await;
+        let searcher = new_player("matchmaking_searcher").await;
+
+        let mut paused_ws = connect(&url, &paused.jwt).await;
+        let mut searcher_ws = connect(&url, &searcher.jwt).await;
+        search(&mut paused_ws).await;
+        assert_eq!(next_event(&mut paused_ws).await["event"], "queued");
+        assert_eq!(
+            matchmake(&service, "pause", &paused.jwt).await,
+            StatusCode::OK
+        );
+        search(&mut searcher_ws).await;
+        assert_eq!(next_event(&mut searcher_ws).await["event"], "queued");
+        assert_no_message(&mut searcher_ws).await;
+
+        assert_eq!(
+            matchmake(&service, "resume", &paused.jwt).await,
+            StatusCode::OK
+        );
+        for ws in [&mut paused_ws, &mut searcher_ws] {
+            assert_eq!(next_event(ws).await["event"], "match_found");
+            let event = next_event(ws).await;
+            assert_eq!(event["event"], "game_found");
+            assert_eq!(event["data"]["x_player"], searcher.user.uuid.to_string());
+            assert_eq!(event["data"]["o_player"], paused.user.uuid.to_string());
+        }
+
+        paused_ws.close(None).await.unwrap();
+        searcher_ws.close(None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    #[tokio::test]
+    async fn resume_keeps_priority() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let service = get_service().await.unwrap();
+        let conn = get_connection().await.expect("Failed to get connection");
+        let first = new_player("matchmaking_priority_first").await;
+        let second = new_player("matchmaking_priority_second").await;
+        let newcomer = new_player("matchmaking_priority_newcomer").await;
+        // The first and the second players are waiting in the queue without being matched with each other
+        db_utils::block_user(&conn, first.user.uuid, second.user.uuid)
+            .await
+            .expect("Failed to block user");
+
+        let mut first_ws = connect(&url, &first.jwt).await;
+        let mut second_ws = connect(&url, &second.jwt).await;
+        let mut newcomer_ws = connect(&url, &newcomer.jwt).await;
+        search(&mut first_ws).await;
+        assert_eq!(next_event(&mut first_ws).await["event"], "queued");
+        assert_eq!(
+            matchmake(&service, "pause", &first.jwt).await,
+            StatusCode::OK
+        );
+        search(&mut second_ws).await;
+        assert_eq!(
+            matchmake(&service, "resume", &first.jwt).await,
+            StatusCode::OK
+        );
+        assert_no_message(&mut first_ws).await;
+
+        search(&mut newcomer_ws).await;
+        assert_eq!(next_event(&mut newcomer_ws).await["event"], "match_found");
+        let event = next_event(&mut newcomer_ws).await;
+        assert_eq!(event["event"], "game_found");
+        assert_eq!(
+            event["data"]["x_player"],
+            first.user.uuid.to_string(),
+            "The resumed player should keep its place before the second player"
+        );
+        assert_eq!(next_event(&mut first_ws).await["event"], "match_found");
+        assert_eq!(next_event(&mut first_ws).await["event"], "game_found");
+
+        newcomer_ws.close(None).await.unwrap();
+        second_ws.close(None).await.unwrap();
+        first_ws.close(None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    #[tokio::test]
+    async fn not_in_search() {
+        let service = get_service().await.unwrap();
+        let player = new_player("matchmaking_not_searching").await;
+
+        assert_eq!(
+            matchmake(&service, "pause", &player.jwt).await,
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            matchmake(&service, "resume", &player.jwt).await,
+            StatusCode::CONFLICT
+        );
+    }
+}
+
+#[cfg(test)]
+mod matchmaking {
+    use super::*;
+    use xors_api::api::matchmaking::MatchmakingPolicy;
+
+    /// Creates a new player with the given rating, its token outlives the matchmaking waits.
+    async fn new_rated_player(username: &str, rating: i64) -> UserSigninSchema {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = new_long_lived_player(username).await;
+        let mut user = db_utils::get_user(&conn, player.user.uuid).await.unwrap();
+        user.rating = sea_orm::Set(rating);
+        sea_orm::ActiveModelTrait::save(user, &conn).await.unwrap();
+        player
+    }
+
+    #[test]
+    fn widening_tolerance() {
+        let policy = MatchmakingPolicy {
+            initial_tolerance: 100,
+            tolerance_growth: 50,
+            max_tolerance: 300,
+            queue_timeout: None,
+        };
+        let secs = std::time::Duration::from_secs;
+
+        assert_eq!(policy.tolerance(secs(0)), 100);
+        assert_eq!(policy.tolerance(secs(2)), 200);
+        assert_eq!(policy.tolerance(secs(60)), 300);
+        assert!(policy.can_match(1200, secs(0), 1300, secs(0)));
+        assert!(!policy.can_match(1200, secs(0), 1350, secs(0)));
+        assert!(
+            policy.can_match(1200, secs(0), 1350, secs(1)),
+            "The tolerance of the longer waiting player should be used"
+        );
+        assert!(!policy.can_match(1200, secs(60), 1550, secs(60)));
+
+        assert!(policy.validate().is_ok());
+        assert!(MatchmakingPolicy {
+            initial_tolerance: 400,
+            ..policy
+        }
+        .validate()
+        .is_err());
+        assert!(MatchmakingPolicy {
+            queue_timeout: Some(0),
+            ..policy
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn match_within_widened_tolerance() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let url = serve_with(ServiceConfig {
+            matchmaking: MatchmakingPolicy {
+                initial_tolerance: 100,
+                tolerance_growth: 100,
+                max_tolerance: 300,
+                queue_timeout: None,
+            },
+            ..test_service_config()
+        })
+        .await;
+        let low = new_rated_player("matchmaking_low_rating", 1000).await;
+        let high = new_rated_player("matchmaking_high_rating", 1250).await;
+
+        let mut low_ws = connect(&url, &low.jwt).await;
+        let mut high_ws = connect(&url, &high.jwt).await;
+        send_event(&mut low_ws, serde_json::json!({"event": "search"})).await;
+        let event = next_event(&mut low_ws).await;
+        assert_eq!(event["event"], "queued");
+        assert_eq!(event["data"]["rating"], 1000);
+        assert_eq!(
+            event["data"].get("timeout_at"),
+            None,
+            "The search should not time out {event}"
+        );
+        send_event(&mut high_ws, serde_json::json!({"event": "search"})).await;
+        assert_eq!(
+            next_event(&mut high_ws).await["event"],
+            "queued",
+            "The players should not be matched before the tolerance widens"
+        );
+
+        for (ws, opponent, opponent_rating) in
+            [(&mut low_ws, &high, 1250), (&mut high_ws, &low, 1000)]
+        {
+            let event = next_event(ws).await;
+            assert_eq!(event["event"], "match_found");
+            assert_eq!(event["data"]["opponent"], opponent.user.uuid.to_string());
+            assert_eq!(event["data"]["opponent_rating"], opponent_rating);
+            assert_eq!(next_event(ws).await["event"], "game_found");
+        }
+
+        low_ws.close(None).await.unwrap();
+        high_ws.close(None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    #[tokio::test]
+    async fn queue_timeout() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let url = serve_with(ServiceConfig {
+            matchmaking: MatchmakingPolicy {
+                queue_timeout: Some(1),
+                ..Default::default()
+            },
+            ..test_service_config()
+        })
+        .await;
+        let player = new_long_lived_player("matchmaking_timeout").await;
+
+        let mut ws = connect(&url, &player.jwt).await;
+        send_event(&mut ws, serde_json::json!({"event": "search"})).await;
+        let event = next_event(&mut ws).await;
+        assert_eq!(event["event"], "queued");
+        assert!(event["data"]["timeout_at"].is_i64(), "{event}");
+
+        let event = next_event(&mut ws).await;
+        assert_eq!(event["event"], "queue_cancelled");
+        assert_eq!(event["data"]["reason"], "timeout");
+
+        // The player left the search queue, it can search again
+        send_event(&mut ws, serde_json::json!({"event": "search"})).await;
+        assert_eq!(next_event(&mut ws).await["event"], "queued");
+    }
+
+    #[tokio::test]
+    async fn cancel_search() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let player = new_long_lived_player("matchmaking_cancel").await;
+
+        let mut ws = connect(&url, &player.jwt).await;
+        send_event(&mut ws, serde_json::json!({"event": "search"})).await;
+        assert_eq!(next_event(&mut ws).await["event"], "queued");
+
+        send_event(&mut ws, serde_json::json!({"event": "cancel_search"})).await;
+        let event = next_event(&mut ws).await;
+        assert_eq!(event["event"], "queue_cancelled");
+        assert_eq!(event["data"]["reason"], "cancelled");
+
+        send_event(&mut ws, serde_json::json!({"event": "cancel_search"})).await;
+        let event = next_event(&mut ws).await;
+        assert_eq!(event["event"], "error");
+        assert_eq!(event["data"], "not_in_search");
+    }
+}
+
+#[cfg(test)]
+mod spectate {
+    use super::*;
+    use tokio_tungstenite::tungstenite::Error as WsError;
+
+    /// Returns the spectate websocket url of the game.
+    fn spectate_url(url: &str, game_uuid: Uuid) -> String {
+        format!("{}/game/{game_uuid}/spectate", url.trim_end_matches("/xo"))
+    }
+
+    #[tokio::test]
+    async fn spectate_game() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let x_player = new_player("spectate_x_player").await;
+        let o_player = new_player("spectate_o_player").await;
+        let spectator = new_player("spectate_spectator").await;
+
+        let mut x_ws = connect(&url, &x_player.jwt).await;
+        let mut o_ws = connect(&url, &o_player.jwt).await;
+        send_event(&mut x_ws, serde_json::json!({"event": "search"})).await;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        send_event(&mut o_ws, serde_json::json!({"event": "search"})).await;
+        for expected in [
+            "queued",
+            "match_found",
+            "game_found",
+            "round_start",
+            "your_turn",
+        ] {
+            assert_eq!(next_event(&mut x_ws).await["event"], expected);
+        }
+
+        let conn = get_connection().await.expect("Failed to get connection");
+        let game_uuid = db_utils::get_online_games(&conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|game| game.x_player == x_player.user.uuid)
+            .expect("The game is in progress")
+            .uuid;
+        let mut spectator_ws = connect(&spectate_url(&url, game_uuid), &spectator.jwt).await;
+        let event = next_event(&mut spectator_ws).await;
+        assert_eq!(event["event"], "game_state");
+        assert_eq!(event["data"]["uuid"], game_uuid.to_string());
+        assert_eq!(event["data"]["x_player"], x_player.user.uuid.to_string());
+        assert_eq!(event["data"]["round"], 1);
+
+        send_event(
+            &mut x_ws,
+            serde_json::json!({"event": "play", "data": {"place": 4}}),
+        )
+        .await;
+        let event = next_event(&mut spectator_ws).await;
+        assert_eq!(event["event"], "play");
+        assert_eq!(event["data"]["place"], 4);
+
+        send_event(
+            &mut spectator_ws,
+            serde_json::json!({"event": "play", "data": {"place": 0}}),
+        )
+        .await;
+        let event = next_event(&mut spectator_ws).await;
+        assert_eq!(event["event"], "error");
+        assert_eq!(event["data"], "spectating");
+
+        x_ws.close(None).await.unwrap();
+        let event = next_event(&mut spectator_ws).await;
+        assert_eq!(event["event"], "game_over");
+        assert_eq!(event["data"]["winner"], o_player.user.uuid.to_string());
+        assert!(
+            next_message(&mut spectator_ws).await.is_close(),
+            "The spectator connection should be closed after the game is over"
+        );
+    }
+
+    #[tokio::test]
+    async fn game_not_in_progress() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let spectator = new_player("spectate_not_in_progress").await;
+
+        match connect_from(&spectate_url(&url, Uuid::new_v4()), &spectator.jwt, None).await {
+            Err(WsError::Http(res)) => assert_eq!(res.status(), StatusCode::NOT_FOUND),
+            res => panic!("Spectating a game that is not in progress should be rejected {res:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod play_bot {
+    use super::*;
+
+    #[tokio::test]
+    async fn perfect_bot_game() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let conn = get_connection().await.expect("Failed to get connection");
+        let user = new_player("play_bot_player").await.user;
+        // The game can outlive the test tokens lifetime, the connection is closed when the token expires
+        let lifetimes = xors_api::api::jwt::TokenLifetimes {
+            jwt: Duration::seconds(30),
+            refresh: Duration::seconds(60),
+            short_refresh: Duration::seconds(50),
+            refresh_active_after: Duration::seconds(40),
+            refresh_grace_period: Duration::zero(),
+        };
+        let player = db_utils::signin_user(&conn, user, &get_jwt_keys(), &lifetimes, true)
+            .await
+            .unwrap();
+        let player_uuid = player.user.uuid.to_string();
+
+        let mut ws = connect(&url, &player.jwt).await;
+        send_event(
+            &mut ws,
+            serde_json::json!({"event": "play_bot", "data": {"difficulty": "perfect"}}),
+        )
+        .await;
+        let event = next_event(&mut ws).await;
+        assert_eq!(event["event"], "game_found");
+        assert!(
+            event["data"]["x_player"] == player_uuid || event["data"]["o_player"] == player_uuid
+        );
+
+        // Play the first empty cell, until the game is over
+        let mut board = Board::default();
+        let game_over = loop {
+            let event = next_event(&mut ws).await;
+            match event["event"].as_str().unwrap() {
+                "round_start" => board = Board::default(),
+                "play" => {
+                    board.set_cell(event["data"]["place"].as_u64().unwrap() as u8, board.turn())
+                }
+                "your_turn" => {
+                    let place = board.empty_cells()[0];
+                    board.set_cell(place, board.turn());
+                    send_event(
+                        &mut ws,
+                        serde_json::json!({"event": "play", "data": {"place": place}}),
+                    )
+                    .await;
+                }
+                "round_end" => {}
+                "game_over" => break event,
+                _ => panic!("Unexpected event {event}"),
+            }
+        };
+        assert_ne!(
+            game_over["data"]["winner"], player_uuid,
+            "The perfect bot should not lose"
+        );
+
+        let game_uuid: Uuid = game_over["data"]["uuid"].as_str().unwrap().parse().unwrap();
+        // The game is ended after the event is sent
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let game = db_utils::get_game::<true>(&conn, &game_uuid)
+            .await
+            .expect("The game is ended");
+        assert_eq!(game.bot_difficulty.as_deref(), Some("perfect"));
+        let bot_uuid = if game.x_player == player.user.uuid {
+            game.o_player
+        } else {
+            game.x_player
+        };
+        assert_eq!(game.bot_uuid, Some(bot_uuid), "The bot side is stored");
+        let user = db_utils::get_user(&conn, player.user.uuid).await.unwrap();
+        assert_eq!(
+            (
+                *user.wins.as_ref(),
+                *user.losts.as_ref(),
+                *user.draw.as_ref(),
+                *user.rating.as_ref()
+            ),
+            (0, 0, 0, 1200),
+            "The bot games should not count in the results and rating"
+        );
+
+        let service = get_service().await.unwrap();
+        let mut res = send(
+            &service,
+            &format!("game/{game_uuid}"),
+            Method::GET,
+            None::<&()>,
+            vec![],
+        )
+        .await;
+        let game: GameSchema = serde_json::from_str(&res.take_string().await.unwrap()).unwrap();
+        assert_eq!(game.bot_difficulty, Some(BotDifficulty::Perfect));
+        assert!(game.x_player.username == "Bot" || game.o_player.username == "Bot");
+    }
+}
+
+#[cfg(test)]
+mod game_clock {
+    use super::*;
+
+    /// Starts a game against the random bot, returns the first `your_turn` event.
+    /// The bot moves before it are played on the board.
+    async fn start_bot_game(ws: &mut WsStream, board: &mut Board) -> serde_json::Value {
+        send_event(
+            ws,
+            serde_json::json!({"event": "play_bot", "data": {"difficulty": "random"}}),
+        )
+        .await;
+        assert_eq!(next_event(ws).await["event"], "game_found");
+        assert_eq!(next_event(ws).await["event"], "round_start");
+        loop {
+            let event = next_event(ws).await;
+            match event["event"].as_str().unwrap() {
+                "play" => {
+                    board.set_cell(event["data"]["place"].as_u64().unwrap() as u8, board.turn())
+                }
+                "your_turn" => break event,
+                _ => panic!("Unexpected event {event}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn game_time_timeout() {
+        let url = serve_with(ServiceConfig {
+            game_clock: GameClock::new(10).with_game_time(2),
+            ..test_service_config()
+        })
+        .await;
+        let player = new_long_lived_player("game_time_player").await;
+        let mut ws = connect(&url, &player.jwt).await;
+
+        let mut board = Board::default();
+        let your_turn = start_bot_game(&mut ws, &mut board).await;
+        assert_eq!(your_turn["data"]["time_left"], 2000);
+
+        let place = board.empty_cells()[0];
+        board.set_cell(place, board.turn());
+        send_event(
+            &mut ws,
+            serde_json::json!({"event": "play", "data": {"place": place}}),
+        )
+        .await;
+        let event = next_event(&mut ws).await;
+        assert_eq!(event["event"], "play", "The bot should play {event}");
+        let bot_time_left = event["data"]["time_left"].as_i64().unwrap();
+        assert!((0..=2000).contains(&bot_time_left));
+        let event = next_event(&mut ws).await;
+        assert_eq!(event["event"], "your_turn");
+        let time_left = event["data"]["time_left"].as_i64().unwrap();
+        assert!(
+            time_left < 2000 && time_left > 0,
+            "The player move time should be counted {time_left}"
+        );
+
+        // The player ran out of its time
+        tokio::time::sleep(std::time::Duration::from_millis(time_left as u64 + 100)).await;
+        send_event(
+            &mut ws,
+            serde_json::json!({"event": "play", "data": {"place": board.empty_cells()[0]}}),
+        )
+        .await;
+        let game_over = next_game_over(&mut ws).await;
+        assert_eq!(game_over["data"]["reason"], "timeout");
+        assert_ne!(game_over["data"]["winner"], player.user.uuid.to_string());
+        assert!(game_over["data"]["winner"].is_string());
+
+        let game_uuid: Uuid = game_over["data"]["uuid"].as_str().unwrap().parse().unwrap();
+        // The game is ended after the event is sent
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let game = db_utils::get_game::<true>(
+            &get_connection().await.expect("Failed to get connection"),
+            &game_uuid,
+        )
+        .await
+        .expect("The game is ended");
+        assert_eq!(game.reason.as_deref(), Some("Timeout"));
+    }
+
+    #[tokio::test]
+    async fn move_timeout_forfeit() {
+        let url = serve_with(ServiceConfig {
+            game_clock: GameClock::new(1).with_move_timeout_policy(MoveTimeoutPolicy::Forfeit),
+            ..test_service_config()
+        })
+        .await;
+        let player = new_long_lived_player("move_timeout_player").await;
+        let mut ws = connect(&url, &player.jwt).await;
+
+        let mut board = Board::default();
+        let your_turn = start_bot_game(&mut ws, &mut board).await;
+        assert!(
+            your_turn["data"].get("time_left").is_none(),
+            "The time left is only sent with the game time"
+        );
+
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        send_event(
+            &mut ws,
+            serde_json::json!({"event": "play", "data": {"place": board.empty_cells()[0]}}),
+        )
+        .await;
+        let game_over = next_game_over(&mut ws).await;
+        assert_eq!(game_over["data"]["reason"], "timeout");
+        assert_ne!(game_over["data"]["winner"], player.user.uuid.to_string());
+    }
+}
+
+#[cfg(test)]
+mod invite {
+    use super::*;
+
+    /// Creates an invite of the player, returns its code.
+    async fn create_invite(service: &Service, jwt: &str) -> String {
+        let mut res = send(
+            service,
+            "game/invite",
+            Method::POST,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {jwt}")).unwrap(),
+            )],
+        )
+        .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+        res.take_json::<InviteSchema>().await.unwrap().code
+    }
+
+    /// Sends the join invite request, returns its status code.
+    async fn join(service: &Service, code: &str, jwt: &str) -> StatusCode {
+        send(
+            service,
+            &format!("game/join/{code}"),
+            Method::POST,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {jwt}")).unwrap(),
+            )],
+        )
+        .await
+        .status_code
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn join_with_event() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let service = get_service().await.unwrap();
+        let host = new_player("invite_event_host").await;
+        let guest = new_player("invite_event_guest").await;
+
+        let mut host_ws = connect(&url, &host.jwt).await;
+        let mut guest_ws = connect(&url, &guest.jwt).await;
+        let code = create_invite(&service, &host.jwt).await;
+        send_event(
+            &mut guest_ws,
+            serde_json::json!({"event": "join_code", "data": {"code": code.to_lowercase()}}),
+        )
+        .await;
+        for ws in [&mut host_ws, &mut guest_ws] {
+            let event = next_event(ws).await;
+            assert_eq!(event["event"], "game_found");
+            assert_eq!(event["data"]["x_player"], host.user.uuid.to_string());
+            assert_eq!(event["data"]["o_player"], guest.user.uuid.to_string());
+        }
+
+        host_ws.close(None).await.unwrap();
+        guest_ws.close(None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    #[tokio::test]
+    async fn join_with_endpoint() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let service = get_service().await.unwrap();
+        let host = new_player("invite_endpoint_host").await;
+        let guest = new_player("invite_endpoint_guest").await;
+
+        let mut host_ws = connect(&url, &host.jwt).await;
+        let mut guest_ws = connect(&url, &guest.jwt).await;
+        let code = create_invite(&service, &host.jwt).await;
+        assert_eq!(join(&service, &code, &guest.jwt).await, StatusCode::OK);
+        for ws in [&mut host_ws, &mut guest_ws] {
+            assert_eq!(next_event(ws).await["event"], "game_found");
+        }
+        assert_eq!(
+            join(&service, &code, &guest.jwt).await,
+            StatusCode::NOT_FOUND,
+            "The invite should be used once"
+        );
+
+        host_ws.close(None).await.unwrap();
+        guest_ws.close(None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    #[tokio::test]
+    async fn new_invite_replaces_previous() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let service = get_service().await.unwrap();
+        let host = new_player("invite_replaced_host").await;
+        let guest = new_player("invite_replaced_guest").await;
+
+        let _host_ws = connect(&url, &host.jwt).await;
+        let mut guest_ws = connect(&url, &guest.jwt).await;
+        let previous_code = create_invite(&service, &host.jwt).await;
+        create_invite(&service, &host.jwt).await;
+        send_event(
+            &mut guest_ws,
+            serde_json::json!({"event": "join_code", "data": {"code": previous_code}}),
+        )
+        .await;
+        let event = next_event(&mut guest_ws).await;
+        assert_eq!(event["event"], "error");
+        assert_eq!(event["data"], "invalid_invite_code");
+    }
+
+    #[tokio::test]
+    async fn join_errors() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let service = get_service().await.unwrap();
+        let host = new_player("invite_errors_host").await;
+        let guest = new_player("invite_errors_guest").await;
+
+        let code = create_invite(&service, &host.jwt).await;
+        assert_eq!(
+            join(&service, &code, &guest.jwt).await,
+            StatusCode::CONFLICT,
+            "The player must be connected to the XO websocket"
+        );
+
+        let _guest_ws = connect(&url, &guest.jwt).await;
+        let _host_ws = connect(&url, &host.jwt).await;
+        assert_eq!(
+            join(&service, "INVALID0", &guest.jwt).await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            join(&service, &code, &host.jwt).await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn variant_invite() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let service = get_service().await.unwrap();
+        let host = new_player("invite_variant_host").await;
+        let guest = new_player("invite_variant_guest").await;
+        let auth_header = vec![(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", host.jwt)).unwrap(),
+        )];
+
+        let res = send(
+            &service,
+            "game/invite?size=16",
+            Method::POST,
+            None::<&()>,
+            auth_header.clone(),
+        )
+        .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::BAD_REQUEST);
+
+        let mut host_ws = connect(&url, &host.jwt).await;
+        let mut guest_ws = connect(&url, &guest.jwt).await;
+        let mut res = send(
+            &service,
+            "game/invite?size=15",
+            Method::POST,
+            None::<&()>,
+            auth_header,
+        )
+        .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+        let invite = res.take_json::<InviteSchema>().await.unwrap();
+        assert_eq!(invite.variant, GameVariant::new(15, 5));
+        assert_eq!(
+            join(&service, &invite.code, &guest.jwt).await,
+            StatusCode::OK
+        );
+
+        for ws in [&mut host_ws, &mut guest_ws] {
+            let event = next_event(ws).await;
+            assert_eq!(event["event"], "game_found");
+            assert_eq!(
+                event["data"]["variant"],
+                serde_json::json!({"size": 15, "win_length": 5})
+            );
+            assert_eq!(next_event(ws).await["event"], "round_start");
+        }
+        assert_eq!(next_event(&mut host_ws).await["event"], "your_turn");
+        send_event(
+            &mut host_ws,
+            serde_json::json!({"event": "play", "data": {"place": 224}}),
+        )
+        .await;
+        let event = next_event(&mut guest_ws).await;
+        assert_eq!(event["event"], "play");
+        assert_eq!(event["data"]["place"], 224);
+
+        host_ws.close(None).await.unwrap();
+        guest_ws.close(None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    #[tokio::test]
+    async fn host_offline() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let service = get_service().await.unwrap();
+        let host = new_player("invite_offline_host").await;
+        let guest = new_player("invite_offline_guest").await;
+
+        let code = create_invite(&service, &host.jwt).await;
+        let _guest_ws = connect(&url, &guest.jwt).await;
+        assert_eq!(
+            join(&service, &code, &guest.jwt).await,
+            StatusCode::CONFLICT
+        );
+    }
+}
+
+#[cfg(test)]
+mod play_tournament {
+    use super::*;
+
+    /// Creates a tournament of the two players and registers them, returns its uuid.
+    async fn create_tournament(service: &Service, players: [&UserSigninSchema; 2]) -> Uuid {
+        let auth_header = |jwt: &str| {
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {jwt}")).unwrap(),
+            )]
+        };
+        let mut res = send(
+            service,
+            "tournaments",
+            Method::POST,
+            Some(&NewTournamentSchema::new("Duel cup".to_owned(), 2)),
+            auth_header(&players[0].jwt),
+        )
+        .await;
+        let tournament_uuid = res.take_json::<TournamentSchema>().await.unwrap().uuid;
+        for player in players {
+            let res = send(
+                service,
+                &format!("tournaments/{tournament_uuid}/join"),
+                Method::POST,
+                None::<&()>,
+                auth_header(&player.jwt),
+            )
+            .await;
+            assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+        }
+        tournament_uuid
+    }
+
+    #[tokio::test]
+    async fn match_game() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let service = get_service().await.unwrap();
+        // The second player doesn't read its connection, so its token must outlive the test
+        let first = new_long_lived_player("play_tournament_first").await;
+        let second = new_long_lived_player("play_tournament_second").await;
+        let tournament_uuid = create_tournament(&service, [&first, &second]).await;
+        let conn = get_connection().await.expect("Failed to get connection");
+        let tournament_match =
+            db_utils::get_player_tournament_match(&conn, tournament_uuid, first.user.uuid)
+                .await
+                .unwrap()
+                .expect("The players have a match");
+
+        let mut first_ws = connect(&url, &first.jwt).await;
+        let mut second_ws = connect(&url, &second.jwt).await;
+        let event = serde_json::json!({"event": "play_tournament", "data": {"tournament": tournament_uuid}});
+        send_event(&mut first_ws, event.clone()).await;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        send_event(&mut second_ws, event).await;
+        for ws in [&mut first_ws, &mut second_ws] {
+            let event = next_event(ws).await;
+            assert_eq!(event["event"], "game_found");
+            assert_eq!(
+                event["data"]["x_player"],
+                tournament_match.x_player.unwrap().to_string()
+            );
+        }
+
+        first_ws.close(None).await.unwrap();
+        let mut tournament = db_utils::get_tournament(&conn, tournament_uuid)
+            .await
+            .unwrap();
+        for _ in 0..50 {
+            if tournament.ended_at.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            tournament = db_utils::get_tournament(&conn, tournament_uuid)
+                .await
+                .unwrap();
+        }
+        assert_eq!(tournament.winner, Some(second.user.uuid));
+        assert!(tournament.ended_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn no_tournament_match() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let player = new_player("play_tournament_no_match").await;
+
+        let mut ws = connect(&url, &player.jwt).await;
+        send_event(
+            &mut ws,
+            serde_json::json!({"event": "play_tournament", "data": {"tournament": Uuid::new_v4()}}),
+        )
+        .await;
+        let event = next_event(&mut ws).await;
+        assert_eq!(event["event"], "error");
+        assert_eq!(event["data"], "no_tournament_match");
+    }
+}
+
+mod moderation {
+    use super::*;
+    use sea_orm::{ActiveModelTrait, Set};
+
+    async fn new_moderator(username: &str) -> UserSigninSchema {
+        let conn = get_connection().await.expect("Failed to get connection");
+        let moderator = new_player(username).await;
+        let mut user = db_utils::get_user(&conn, moderator.user.uuid)
+            .await
+            .unwrap();
+        user.role = Set(UserRole::Moderator.to_string());
+        let user = user.save(&conn).await.unwrap();
+        db_utils::signin_user(
+            &conn,
+            UserSchema::from_active_model(&conn, user).await.unwrap(),
+            &get_jwt_keys(),
+            &test_token_lifetimes(),
+            true,
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn send_as(service: &Service, path: &str, jwt: &str) -> Response {
+        send(
+            service,
+            path,
+            Method::POST,
+            None::<&()>,
+            vec![(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {jwt}")).unwrap(),
+            )],
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn force_end_online_game() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let service = get_service().await.unwrap();
+        let conn = get_connection().await.expect("Failed to get connection");
+        let host = new_player("force_end_host").await;
+        let guest = new_player("force_end_guest").await;
+        let moderator = new_moderator("force_end_moderator").await;
+
+        let mut host_ws = connect(&url, &host.jwt).await;
+        let mut guest_ws = connect(&url, &guest.jwt).await;
+        let mut res = send_as(&service, "game/invite", &host.jwt).await;
+        let code = res.take_json::<InviteSchema>().await.unwrap().code;
+        let res = send_as(&service, &format!("game/join/{code}"), &guest.jwt).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        for ws in [&mut host_ws, &mut guest_ws] {
+            assert_eq!(next_event(ws).await["event"], "game_found");
+        }
+        let game = db_utils::get_active_game_between(&conn, host.user.uuid, guest.user.uuid)
+            .await
+            .unwrap()
+            .expect("The game should be in progress");
+
+        let res = send_as(
+            &service,
+            &format!("admin/games/{}/end", game.uuid),
+            &moderator.jwt,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        for ws in [&mut host_ws, &mut guest_ws] {
+            let event = next_game_over(ws).await;
+            assert_eq!(event["data"]["uuid"], game.uuid.to_string());
+            assert_eq!(event["data"]["winner"], serde_json::Value::Null);
+            assert_eq!(event["data"]["reason"], "force_ended");
+        }
+
+        host_ws.close(None).await.unwrap();
+        guest_ws.close(None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    #[tokio::test]
+    async fn ban_closes_connections() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let service = get_service().await.unwrap();
+        let player = new_player("banned_connected_player").await;
+        let moderator = new_moderator("ban_connected_moderator").await;
+
+        let mut ws = connect(&url, &player.jwt).await;
+        let res = send_as(
+            &service,
+            &format!("admin/users/{}/ban", player.user.uuid),
+            &moderator.jwt,
+        )
+        .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK), "{res:?}");
+        match next_message(&mut ws).await {
+            WsMessage::Close(Some(frame)) => assert_eq!(u16::from(frame.code), BANNED_CLOSE_CODE),
+            msg => panic!("The connection should be closed with the banned code {msg:?}"),
+        }
+        assert!(
+            connect_from(&url, &player.jwt, None).await.is_err(),
+            "The banned player should not connect again"
+        );
+    }
+}
+
+#[cfg(test)]
+mod reconnection {
+    use super::*;
+
+    /// Starts a game between the players, the X player gets its `your_turn` event.
+    async fn start_game(x_ws: &mut WsStream, o_ws: &mut WsStream) {
+        send_event(x_ws, serde_json::json!({"event": "search"})).await;
+        assert_eq!(next_event(x_ws).await["event"], "queued");
+        send_event(o_ws, serde_json::json!({"event": "search"})).await;
+        for ws in [&mut *x_ws, &mut *o_ws] {
+            assert_eq!(next_event(ws).await["event"], "match_found");
+            assert_eq!(next_event(ws).await["event"], "game_found");
+            assert_eq!(next_event(ws).await["event"], "round_start");
+        }
+        assert_eq!(next_event(x_ws).await["event"], "your_turn");
+    }
+
+    #[tokio::test]
+    async fn reconnect_resumes_game() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let url = serve_with(ServiceConfig {
+            game_clock: GameClock::new(10).with_reconnect_window(5),
+            ..test_service_config()
+        })
+        .await;
+        let x_player = new_long_lived_player("reconnect_x_player").await;
+        let o_player = new_long_lived_player("reconnect_o_player").await;
+        let mut x_ws = connect(&url, &x_player.jwt).await;
+        let mut o_ws = connect(&url, &o_player.jwt).await;
+        start_game(&mut x_ws, &mut o_ws).await;
+
+        o_ws.close(None).await.unwrap();
+        let event = next_event(&mut x_ws).await;
+        assert_eq!(event["event"], "opponent_disconnected");
+        assert_eq!(event["data"]["player"], o_player.user.uuid.to_string());
+        assert!(
+            event["data"]["reconnect_before"].as_i64().unwrap() > chrono::Utc::now().timestamp()
+        );
+
+        send_event(
+            &mut x_ws,
+            serde_json::json!({"event": "play", "data": {"place": 4}}),
+        )
+        .await;
+        let event = next_event(&mut x_ws).await;
+        assert_eq!(event["event"], "error");
+        assert_eq!(event["data"], "game_paused");
+
+        let mut o_ws = connect(&url, &o_player.jwt).await;
+        let event = next_event(&mut o_ws).await;
+        assert_eq!(event["event"], "game_resumed");
+        assert_eq!(event["data"]["x_player"], x_player.user.uuid.to_string());
+        let event = next_event(&mut x_ws).await;
+        assert_eq!(event["event"], "opponent_reconnected");
+        assert_eq!(event["data"]["player"], o_player.user.uuid.to_string());
+        assert_eq!(next_event(&mut x_ws).await["event"], "your_turn");
+
+        // The game continues with the reconnected player
+        send_event(
+            &mut x_ws,
+            serde_json::json!({"event": "play", "data": {"place": 4}}),
+        )
+        .await;
+        let event = next_event(&mut o_ws).await;
+        assert_eq!(event["event"], "play");
+        assert_eq!(event["data"]["place"], 4);
+        assert_eq!(next_event(&mut o_ws).await["event"], "your_turn");
+    }
+
+    #[tokio::test]
+    async fn reconnect_window_expired() {
+        let _search_lock = SEARCH_LOCK.lock().await;
+        let url = serve_with(ServiceConfig {
+            game_clock: GameClock::new(10).with_reconnect_window(1),
+            ..test_service_config()
+        })
+        .await;
+        let x_player = new_long_lived_player("reconnect_expired_x_player").await;
+        let o_player = new_long_lived_player("reconnect_expired_o_player").await;
+        let mut x_ws = connect(&url, &x_player.jwt).await;
+        let mut o_ws = connect(&url, &o_player.jwt).await;
+        start_game(&mut x_ws, &mut o_ws).await;
+
+        o_ws.close(None).await.unwrap();
+        assert_eq!(
+            next_event(&mut x_ws).await["event"],
+            "opponent_disconnected"
+        );
+        let event = next_event(&mut x_ws).await;
+        assert_eq!(event["event"], "game_over");
+        assert_eq!(event["data"]["reason"], "player_disconnected");
+        assert_eq!(event["data"]["winner"], x_player.user.uuid.to_string());
+    }
+}
+
+#[cfg(test)]
+mod friend_presence {
+    use super::*;
+
+    async fn assert_presence(ws: &mut WsStream, friend: &UserSchema, presence: &str) {
+        let event = next_event(ws).await;
+        assert_eq!(event["event"], "friend_presence", "{event}");
+        assert_eq!(event["data"]["friend"], friend.uuid.to_string());
+        assert_eq!(event["data"]["presence"], presence);
+    }
+
+    #[tokio::test]
+    async fn presence_broadcast_to_friends() {
+        let url = serve(TokenExpiryPolicy::Close).await;
+        let conn = get_connection().await.expect("Failed to get connection");
+        let player = new_long_lived_player("presence_player").await;
+        let friend = new_long_lived_player("presence_friend").await;
+        db_utils::send_friend_request(&conn, player.user.uuid, friend.user.uuid)
+            .await
+            .unwrap();
+        db_utils::accept_friend_request(&conn, friend.user.uuid, player.user.uuid)
+            .await
+            .unwrap();
+
+        let mut player_ws = connect(&url, &player.jwt).await;
+        let mut friend_ws = connect(&url, &friend.jwt).await;
+        assert_presence(&mut player_ws, &friend.user, "online").await;
+
+        send_event(
+            &mut friend_ws,
+            serde_json::json!({"event": "play_bot", "data": {"difficulty": "random"}}),
+        )
+        .await;
+        assert_presence(&mut player_ws, &friend.user, "in_game").await;
+
+        friend_ws.close(None).await.unwrap();
+        assert_presence(&mut player_ws, &friend.user, "offline").await;
+    }
+}